@@ -1,6 +1,6 @@
 use clap::Args;
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{env, fs, io};
 
 /// CLI arguments for the `init` command
@@ -10,6 +10,33 @@ pub struct NewArgs {
     pub package_name: String,
 }
 
+/// CLI arguments for the `wit` command
+#[derive(Args, Debug)]
+pub struct WitArgs {
+    /// The project's WIT directory; Faasta's packages are written to its `deps`
+    #[arg(long, default_value = "wit")]
+    pub dir: PathBuf,
+}
+
+/// Faasta's WIT packages as (directory below `deps`, file name, contents)
+pub const WIT_PACKAGES: &[(&str, &str, &str)] = &[
+    (
+        "faasta-platform",
+        "world.wit",
+        include_str!("../../faasta/wit/world.wit"),
+    ),
+    (
+        "faasta-log",
+        "log.wit",
+        include_str!("../../faasta/wit/deps/log/log.wit"),
+    ),
+    (
+        "faasta-image",
+        "image.wit",
+        include_str!("../../faasta/wit/deps/image/image.wit"),
+    ),
+];
+
 pub const HTTP_CARGO_TOML: &str = include_str!("../template/notCargo.toml");
 pub const HTTP_LIB_RS: &str = include_str!("../template/lib.rs");
 pub fn handle_new(args: &NewArgs) -> Result<(), Box<dyn Error>> {
@@ -44,6 +71,22 @@ pub fn handle_new(args: &NewArgs) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Vendor Faasta's WIT packages into a project, replacing earlier copies.
+pub fn handle_wit(args: &WitArgs) -> Result<(), Box<dyn Error>> {
+    let deps = args.dir.join("deps");
+    for (package, file, contents) in WIT_PACKAGES {
+        let dir = deps.join(package);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(file), contents)?;
+    }
+    println!(
+        "✅ Wrote Faasta's WIT packages to '{}'. Target wasi:http/service@0.3.0-rc-2026-03-15 \
+         and add `include faasta:platform/extensions@0.1.0;` to your world to use them.",
+        deps.display()
+    );
+    Ok(())
+}
+
 /// Writes the embedded Cargo.toml & main.rs to disk,
 /// updating the `[package] name` in Cargo.toml to `package_name`.
 fn write_files(
@@ -4,3 +4,4 @@ pub mod auth;
 pub mod github_oauth;
 pub mod init;
 pub mod run;
+pub mod size;
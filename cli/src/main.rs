@@ -2,13 +2,17 @@
 mod github_oauth;
 mod init;
 mod run;
+mod size;
 
 use anyhow::{Context, Error};
 use cyper::Client as HttpClient;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::process::exit;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const DEFAULT_INVOKE_URL: &str = "https://faasta.lol/";
 const MAX_PROJECTS_PER_USER: usize = 10;
@@ -19,6 +23,9 @@ const CONFIG_FILE: &str = "config.json";
 struct FaastaConfig {
     github_username: Option<String>,
     github_token: Option<String>,
+    /// Hex request-signing keys, by server address
+    #[serde(default)]
+    signing_keys: BTreeMap<String, String>,
 }
 
 /// Get the configuration directory
@@ -66,8 +73,8 @@ fn save_config(config: &FaastaConfig) -> Result<(), Error> {
     Ok(())
 }
 
-use crate::init::NewArgs;
-use clap::{Args, Parser, Subcommand};
+use crate::init::{NewArgs, WitArgs};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 /// Main entry point
 #[compio::main]
@@ -76,6 +83,15 @@ async fn main() {
 
     match cli.command {
         Commands::Deploy(args) => {
+            if let Some(static_dir) = &args.static_dir {
+                deploy_static_site(&args, static_dir).await;
+                return;
+            }
+            if let Some(reference) = &args.oci {
+                deploy_oci(&args, reference).await;
+                return;
+            }
+
             let spinner = indicatif::ProgressBar::new_spinner();
             spinner.set_message("Linting project...");
             spinner.enable_steady_tick(std::time::Duration::from_millis(100));
@@ -219,47 +235,96 @@ async fn main() {
                 exit(1);
             };
 
-            // Connect to the function service
-            let server_addr = &args.server;
-
-            // Use the connect function to get a client
-            let client = match run::connect_to_function_service(server_addr).await {
-                Ok(client) => client,
+            let targets = match deploy_targets(&args).await {
+                Ok(targets) => targets,
                 Err(e) => {
                     spinner.finish_and_clear();
-                    eprintln!("Failed to connect to server: {e}");
+                    eprintln!("{e}");
                     exit(1);
                 }
             };
 
-            // Publish the function
             let auth_token = format!("{github_username}:{github_token}");
-            match client
-                .publish(artifact_data, function_name.clone(), auth_token)
-                .await
-            {
-                Ok(Ok(message)) => {
-                    spinner.finish_and_clear();
-                    println!("✅ {message}");
-
-                    // Extract server hostname from server address (remove port)
-                    let server_host = extract_server_host(&args.server);
-                    println!(
-                        "Function URL: {}",
-                        format_function_url(&function_name, &server_host)
-                    );
+            for server_addr in &targets {
+                if targets.len() > 1 {
+                    spinner.set_message(format!(
+                        "Uploading function '{function_name}' to {server_addr}..."
+                    ));
                 }
-                Ok(Err(e)) => {
-                    spinner.finish_and_clear();
-                    eprintln!("Server error: {e:?}");
-                    exit(1);
+                let client = match connect(server_addr).await {
+                    Ok(client) => client,
+                    Err(e) => {
+                        spinner.finish_and_clear();
+                        eprintln!("Failed to connect to {server_addr}: {e}");
+                        exit(1);
+                    }
+                };
+
+                match client
+                    .publish(
+                        artifact_data.clone(),
+                        function_name.clone(),
+                        auth_token.clone(),
+                    )
+                    .await
+                {
+                    Ok(Ok(message)) => {
+                        spinner.suspend(|| println!("✅ {message}"));
+                    }
+                    Ok(Err(e)) => {
+                        spinner.finish_and_clear();
+                        eprintln!("Server error from {server_addr}: {e:?}");
+                        exit(1);
+                    }
+                    Err(e) => {
+                        spinner.finish_and_clear();
+                        eprintln!(
+                            "Communication error with {server_addr}: {}",
+                            run::describe_rpc_error(&e)
+                        );
+                        exit(1);
+                    }
+                };
+
+                if !args.region.is_empty() {
+                    let regions = args.region.clone();
+                    if let Err(e) =
+                        update_function_config(server_addr, &function_name, &auth_token, |config| {
+                            config.regions = regions
+                        })
+                        .await
+                    {
+                        spinner.finish_and_clear();
+                        eprintln!("Failed to pin '{function_name}' on {server_addr}: {e}");
+                        exit(1);
+                    }
                 }
-                Err(e) => {
-                    spinner.finish_and_clear();
-                    eprintln!("Communication error: {e}");
-                    exit(1);
+
+                let readme_path = package_root.join("README.md");
+                if readme_path.is_file()
+                    && let Err(e) =
+                        upload_readme(server_addr, &function_name, &auth_token, &readme_path).await
+                {
+                    spinner.suspend(|| {
+                        eprintln!("Warning: README.md was not published to {server_addr}: {e}")
+                    });
                 }
-            };
+            }
+            spinner.finish_and_clear();
+
+            // Extract server hostname from server address (remove port)
+            let server_host = extract_server_host(&targets[0]);
+            let function_url = format_function_url(&function_name, &server_host);
+            println!("Function URL: {function_url}");
+            if package_root.join("README.md").is_file() {
+                println!(
+                    "About page: {}/_faasta/about",
+                    function_url.trim_end_matches('/')
+                );
+            }
+            if !args.region.is_empty() {
+                println!("Regions: {}", args.region.join(", "));
+            }
         }
 
         Commands::Invoke(args) => {
@@ -271,6 +336,53 @@ async fn main() {
                 });
         }
 
+        Commands::Jobs(args) => {
+            let result = match args.action {
+                JobsAction::List { name } => {
+                    let auth_token = require_auth_token();
+                    list_jobs(&args.server, &name, &auth_token)
+                        .await
+                        .map(|jobs| print_jobs(&name, &jobs))
+                }
+                JobsAction::Inspect { job_id } => job_status(&args.server, &job_id)
+                    .await
+                    .map(|status| print_job_status(&status)),
+                JobsAction::Cancel { job_id } => {
+                    let auth_token = require_auth_token();
+                    cancel_job(&args.server, &job_id, &auth_token)
+                        .await
+                        .map(|()| println!("✅ Cancelled job {job_id}"))
+                }
+                JobsAction::Retry {
+                    name,
+                    max_attempts,
+                    backoff_ms,
+                    max_backoff_ms,
+                } => {
+                    let auth_token = require_auth_token();
+                    update_function_config(&args.server, &name, &auth_token, |config| {
+                        let policy = &mut config.job_retry;
+                        policy.max_attempts = max_attempts.unwrap_or(policy.max_attempts);
+                        policy.initial_backoff_ms =
+                            backoff_ms.unwrap_or(policy.initial_backoff_ms);
+                        policy.max_backoff_ms = max_backoff_ms.unwrap_or(policy.max_backoff_ms);
+                    })
+                    .await
+                    .map(|config| {
+                        let policy = config.job_retry;
+                        println!(
+                            "✅ Jobs of '{name}' are attempted up to {} times, backing off from {}ms to at most {}ms",
+                            policy.max_attempts, policy.initial_backoff_ms, policy.max_backoff_ms
+                        )
+                    })
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to manage jobs: {e}");
+                exit(1);
+            }
+        }
+
         Commands::Init => {
             let _package_name = "".to_string();
 
@@ -293,6 +405,20 @@ async fn main() {
             }
         }
 
+        Commands::Wit(args) => {
+            if let Err(err) = init::handle_wit(&args) {
+                eprintln!("Failed to write WIT packages: {err}");
+                exit(1);
+            }
+        }
+
+        Commands::Size(args) => {
+            if let Err(e) = show_size(&args).await {
+                eprintln!("{e:#}");
+                exit(1);
+            }
+        }
+
         Commands::Build(build_args) => {
             let spinner = indicatif::ProgressBar::new_spinner();
             spinner.set_message("Building project...");
@@ -438,7 +564,7 @@ async fn main() {
                 let server_addr = &build_args.server;
 
                 // Use the connect function to get a client
-                let client = match run::connect_to_function_service(server_addr).await {
+                let client = match connect(server_addr).await {
                     Ok(client) => client,
                     Err(e) => {
                         spinner.finish_and_clear();
@@ -471,7 +597,7 @@ async fn main() {
                     }
                     Err(e) => {
                         spinner.finish_and_clear();
-                        eprintln!("Communication error: {e}");
+                        eprintln!("Communication error: {}", run::describe_rpc_error(&e));
                         exit(1);
                     }
                 };
@@ -546,6 +672,10 @@ async fn main() {
                     }
                 }
             }
+
+            if login_args.sign_requests {
+                issue_signing_key(&login_args.server, login_args.otp).await;
+            }
         }
 
         Commands::Metrics(args) => {
@@ -576,7 +706,7 @@ async fn main() {
             let (github_username, github_token) = github_config.unwrap();
 
             // Connect to the server
-            let client = match run::connect_to_function_service(&args.server).await {
+            let client = match connect(&args.server).await {
                 Ok(client) => client,
                 Err(e) => {
                     spinner.finish_and_clear();
@@ -621,7 +751,7 @@ async fn main() {
             let (github_username, github_token) = github_config.unwrap();
 
             // Connect to the function service
-            let client = match run::connect_to_function_service(&args.server).await {
+            let client = match connect(&args.server).await {
                 Ok(client) => client,
                 Err(e) => {
                     spinner.finish_and_clear();
@@ -632,6 +762,7 @@ async fn main() {
 
             // Create auth token (username:token format)
             let auth_token = format!("{github_username}:{github_token}");
+            let client = elevate(client, &auth_token, args.otp.clone()).await;
 
             // Call the unpublish RPC
             match client.unpublish(args.name.clone(), auth_token).await {
@@ -654,7 +785,7 @@ async fn main() {
                 }
                 Err(e) => {
                     spinner.finish_and_clear();
-                    eprintln!("Communication error: {e}");
+                    eprintln!("Communication error: {}", run::describe_rpc_error(&e));
                     exit(1);
                 }
             }
@@ -687,109 +818,820 @@ async fn main() {
             // Get GitHub credentials
             let (github_username, github_token) = github_config.unwrap();
 
-            // Connect to the server
-            let client = match run::connect_to_function_service(&args.server).await {
-                Ok(client) => client,
-                Err(e) => {
-                    spinner.finish_and_clear();
-                    eprintln!("Failed to connect to server: {e}");
-                    exit(1);
-                }
-            };
-
             // Call list_functions
             spinner.finish_and_clear();
-            if let Err(e) = list_functions(&client, &github_username, &github_token).await {
+            let nodes: Vec<String> = std::iter::once(args.server).chain(args.nodes).collect();
+            if let Err(e) = list_functions(&nodes, &github_username, &github_token).await {
                 eprintln!("Error listing functions: {e}");
                 exit(1);
             }
         }
 
-        Commands::Run(run_args) => {
-            // Call the run module handler
-            run::handle_run(run_args.port).await.unwrap_or_else(|e| {
-                eprintln!("Failed to run function: {e}");
-                exit(1);
-            });
+        Commands::Mirror(args) => {
+            let auth_token = require_auth_token();
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.mirror = if args.disable {
+                    None
+                } else {
+                    args.target
+                        .clone()
+                        .map(|target| faasta_interface::MirrorConfig {
+                            target,
+                            percent: args.percent,
+                        })
+                };
+            })
+            .await;
+
+            match result {
+                Ok(config) => match config.mirror {
+                    Some(mirror) => println!(
+                        "✅ Mirroring {}% of '{}' traffic to '{}'",
+                        mirror.percent, args.name, mirror.target
+                    ),
+                    None => println!("✅ Mirroring disabled for '{}'", args.name),
+                },
+                Err(e) => {
+                    eprintln!("Failed to update mirroring: {e}");
+                    exit(1);
+                }
+            }
         }
-    }
-}
 
-#[derive(Args, Debug)]
-pub struct LoginArgs {
-    /// GitHub username (only needed for manual login)
-    #[arg(long)]
-    username: Option<String>,
+        Commands::Experiment(args) => {
+            let auth_token = require_auth_token();
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.experiment = match (&args.by, args.disable) {
+                    (Some(assignment), false) => Some(faasta_interface::ExperimentConfig {
+                        assignment: assignment.clone(),
+                        variants: args.variants.clone(),
+                    }),
+                    _ => None,
+                };
+            })
+            .await;
+
+            match result {
+                Ok(config) => match config.experiment {
+                    Some(experiment) => {
+                        println!("✅ Experiment enabled for '{}':", args.name);
+                        for variant in experiment.variants {
+                            println!(
+                                "  {} -> {} (weight {})",
+                                variant.name, variant.function, variant.weight
+                            );
+                        }
+                    }
+                    None => println!("✅ Experiment disabled for '{}'", args.name),
+                },
+                Err(e) => {
+                    eprintln!("Failed to update experiment: {e}");
+                    exit(1);
+                }
+            }
+        }
 
-    /// GitHub token (only needed for manual login)
-    #[arg(long)]
-    token: Option<String>,
+        Commands::Routes(args) => {
+            use faasta_interface::{RouteAction, RouteRule, TrailingSlash};
 
-    /// Skip browser OAuth flow and manually provide credentials
-    #[arg(long)]
-    manual: bool,
-}
+            let auth_token = require_auth_token();
+            let result = match args.action {
+                RoutesAction::List => {
+                    fetch_function_config(&args.server, &args.name, &auth_token).await
+                }
+                action => {
+                    update_function_config(&args.server, &args.name, &auth_token, |config| {
+                        let routes = &mut config.routes;
+                        match action {
+                            RoutesAction::List => {}
+                            RoutesAction::Redirect {
+                                source,
+                                destination,
+                                status,
+                            } => routes.rules.push(RouteRule {
+                                source,
+                                destination,
+                                action: RouteAction::Redirect(status),
+                            }),
+                            RoutesAction::Rewrite {
+                                source,
+                                destination,
+                            } => routes.rules.push(RouteRule {
+                                source,
+                                destination,
+                                action: RouteAction::Rewrite,
+                            }),
+                            RoutesAction::Remove { source } => {
+                                routes.rules.retain(|rule| rule.source != source)
+                            }
+                            RoutesAction::TrailingSlash { mode } => {
+                                routes.trailing_slash = match mode {
+                                    TrailingSlashMode::Always => Some(TrailingSlash::Always),
+                                    TrailingSlashMode::Never => Some(TrailingSlash::Never),
+                                    TrailingSlashMode::Off => None,
+                                }
+                            }
+                            RoutesAction::StripPrefix { mode } => {
+                                routes.strip_prefix = matches!(mode, StripPrefixMode::On)
+                            }
+                            RoutesAction::Clear => routes.rules.clear(),
+                        }
+                    })
+                    .await
+                }
+            };
 
-#[derive(Parser)] // requires `derive` feature
-#[command(name = "cargo")]
-#[command(bin_name = "cargo")]
-#[command(styles = CLAP_STYLING)]
-enum Faasta {
-    #[command(name = "faasta")]
-    Faasta(Cli),
-}
+            match result {
+                Ok(config) => {
+                    let routes = config.routes;
+                    if let Some(mode) = routes.trailing_slash {
+                        println!("Trailing slash: {mode:?}");
+                    }
+                    if routes.strip_prefix {
+                        println!("Strip prefix: on");
+                    }
+                    if routes.rules.is_empty() {
+                        println!("No route rules for '{}'", args.name);
+                    }
+                    for (index, rule) in routes.rules.iter().enumerate() {
+                        let action = match rule.action {
+                            RouteAction::Redirect(status) => format!("redirect {status}"),
+                            RouteAction::Rewrite => "rewrite".to_string(),
+                        };
+                        println!(
+                            "{:>3}. {} -> {} ({action})",
+                            index + 1,
+                            rule.source,
+                            rule.destination
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to update routes: {e}");
+                    exit(1);
+                }
+            }
+        }
 
-#[derive(Args, Debug)]
-struct Cli {
-    #[command(subcommand)]
-    command: Commands,
-}
+        Commands::Headers(args) => {
+            let auth_token = require_auth_token();
+            let result = match args.action {
+                HeadersAction::List => {
+                    fetch_function_config(&args.server, &args.name, &auth_token).await
+                }
+                action => {
+                    update_function_config(&args.server, &args.name, &auth_token, |config| {
+                        let headers = &mut config.response_headers;
+                        match action {
+                            HeadersAction::List => {}
+                            HeadersAction::Set {
+                                name,
+                                value,
+                                replace,
+                            } => {
+                                headers.retain(|header| !header.name.eq_ignore_ascii_case(&name));
+                                headers.push(faasta_interface::ResponseHeader {
+                                    name,
+                                    value,
+                                    replace,
+                                });
+                            }
+                            HeadersAction::Remove { name } => {
+                                headers.retain(|header| !header.name.eq_ignore_ascii_case(&name))
+                            }
+                            HeadersAction::Clear => headers.clear(),
+                        }
+                    })
+                    .await
+                }
+            };
 
-#[derive(Subcommand, Debug)]
-enum Commands {
-    /// Deploys a project to the server
-    Deploy(DeployArgs),
-    /// Invokes a function with the specified name and argument
-    Invoke(InvokeArgs),
-    /// Initialize a new project in the current directory
-    Init,
-    /// Create a new project in a new directory
-    New(NewArgs),
-    /// Build the function (and optionally deploy it)
-    Build(BuildArgs),
-    /// Set up GitHub authentication
-    Login(LoginArgs),
-    /// Get metrics for deployed functions
-    Metrics(ServerArgs),
-    /// List all functions deployed under the current GitHub account
-    List(ServerArgs),
-    /// Run a function locally for testing
-    Run(RunArgs),
-    /// Unpublish a function from the server
-    Unpublish(UnpublishArgs),
-}
+            match result {
+                Ok(config) => {
+                    if config.response_headers.is_empty() {
+                        println!("No response headers for '{}'", args.name);
+                    }
+                    for header in &config.response_headers {
+                        let mode = if header.replace { " (replace)" } else { "" };
+                        println!("{}: {}{mode}", header.name, header.value);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to update response headers: {e}");
+                    exit(1);
+                }
+            }
+        }
 
-#[derive(Args, Debug)]
-struct DeployArgs {
-    /// Path to the project to deploy
-    path: Option<String>,
+        Commands::Secrets(args) => {
+            let auth_token = require_auth_token();
+            let client = match connect(&args.server).await {
+                Ok(client) => client,
+                Err(e) => {
+                    eprintln!("Failed to connect to server: {e}");
+                    exit(1);
+                }
+            };
+            let name = args.name.clone();
+            let result = match args.action {
+                SecretsAction::List => client
+                    .list_secrets(name, auth_token)
+                    .await
+                    .map(|result| result.map(Some)),
+                SecretsAction::Set { key, value } => {
+                    let value = match value {
+                        Some(value) => value,
+                        None => {
+                            let mut value = String::new();
+                            if let Err(e) = std::io::stdin().read_to_string(&mut value) {
+                                eprintln!("Failed to read the secret from stdin: {e}");
+                                exit(1);
+                            }
+                            value.trim_end_matches(['\r', '\n']).to_string()
+                        }
+                    };
+                    println!("Setting secret '{key}' for '{}'", args.name);
+                    client
+                        .set_secret(name, key, value, auth_token)
+                        .await
+                        .map(|result| result.map(|()| None))
+                }
+                SecretsAction::Remove { key } => {
+                    println!("Removing secret '{key}' from '{}'", args.name);
+                    client
+                        .delete_secret(name, key, auth_token)
+                        .await
+                        .map(|result| result.map(|()| None))
+                }
+            };
 
-    /// Skip GitHub authentication
-    #[arg(long)]
-    skip_auth: bool,
+            match result {
+                Ok(Ok(Some(names))) => {
+                    if names.is_empty() {
+                        println!("No secrets for '{}'", args.name);
+                    }
+                    for name in names {
+                        println!("{name}");
+                    }
+                }
+                Ok(Ok(None)) => println!("✅ Secrets updated for '{}'", args.name),
+                Ok(Err(e)) => {
+                    eprintln!("Server error: {e}");
+                    exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Communication error: {}", run::describe_rpc_error(&e));
+                    exit(1);
+                }
+            }
+        }
 
-    /// Explicit path to compiled WASIp3 component artifact (overrides automatic detection)
-    #[arg(long)]
-    artifact_path: Option<String>,
+        Commands::Sessions(args) => {
+            let auth_token = require_auth_token();
+            let result = match args.action {
+                SessionsAction::List => list_sessions(&args.server, &auth_token)
+                    .await
+                    .map(|sessions| print_sessions(&sessions)),
+                SessionsAction::Revoke { session_id } => {
+                    revoke_session(&args.server, &session_id, &auth_token)
+                        .await
+                        .map(|()| println!("✅ Revoked session {session_id}"))
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to manage sessions: {e}");
+                exit(1);
+            }
+        }
 
-    /// Function name to use (if different from package name)
-    #[arg(long)]
-    function_name: Option<String>,
+        Commands::TwoFactor(args) => {
+            let auth_token = require_auth_token();
+            let username = auth_token.split(':').next().unwrap_or_default().to_string();
+            let client = match connect(&args.server).await {
+                Ok(client) => client,
+                Err(e) => {
+                    eprintln!("Failed to connect to server: {e}");
+                    exit(1);
+                }
+            };
+            let result = match args.action {
+                TwoFactorAction::Enable { otp } => {
+                    let client = elevate(client, &auth_token, otp).await;
+                    enable_two_factor(&client, &username, &auth_token).await
+                }
+                TwoFactorAction::Disable { otp } => {
+                    let client = elevate(client, &auth_token, Some(otp)).await;
+                    disable_two_factor(&client, &username, &auth_token)
+                        .await
+                        .map(|()| println!("✅ Two-factor authentication disabled"))
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to manage two-factor authentication: {e}");
+                exit(1);
+            }
+        }
 
-    /// Server address to deploy to (e.g., "faasta.lol:4433")
-    #[arg(long, default_value = "faasta.lol:4433")]
-    server: String,
-}
+        Commands::ErrorPages(args) => {
+            let auth_token = require_auth_token();
+            let result = match args.action {
+                ErrorPagesAction::List => {
+                    fetch_function_config(&args.server, &args.name, &auth_token).await
+                }
+                ErrorPagesAction::Set { status, file } => match fs::read_to_string(&file) {
+                    Ok(html) => {
+                        update_function_config(&args.server, &args.name, &auth_token, |config| {
+                            let pages = &mut config.error_pages;
+                            pages.retain(|page| !page.status.eq_ignore_ascii_case(&status));
+                            pages.push(faasta_interface::ErrorPage { status, html });
+                        })
+                        .await
+                    }
+                    Err(e) => Err(anyhow::anyhow!("Failed to read {}: {e}", file.display())),
+                },
+                action => {
+                    update_function_config(&args.server, &args.name, &auth_token, |config| {
+                        let pages = &mut config.error_pages;
+                        match action {
+                            ErrorPagesAction::Remove { status } => {
+                                pages.retain(|page| !page.status.eq_ignore_ascii_case(&status))
+                            }
+                            ErrorPagesAction::Clear => pages.clear(),
+                            ErrorPagesAction::List | ErrorPagesAction::Set { .. } => {}
+                        }
+                    })
+                    .await
+                }
+            };
+
+            match result {
+                Ok(config) => {
+                    if config.error_pages.is_empty() {
+                        println!("No custom error pages for '{}'", args.name);
+                    }
+                    for page in &config.error_pages {
+                        println!("{} ({})", page.status, format_size(page.html.len() as u64));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to update error pages: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Maintenance(args) => {
+            let auth_token = require_auth_token();
+            let (name, maintenance) = match args.action {
+                MaintenanceAction::On {
+                    name,
+                    message,
+                    retry_after,
+                } => (
+                    name,
+                    Some(faasta_interface::Maintenance {
+                        message,
+                        retry_after_secs: retry_after,
+                    }),
+                ),
+                MaintenanceAction::Off { name } => (name, None),
+            };
+            let enabled = maintenance.is_some();
+            let result = update_function_config(&args.server, &name, &auth_token, |config| {
+                config.maintenance = maintenance;
+            })
+            .await;
+
+            match result {
+                Ok(_) if enabled => println!("✅ '{name}' is in maintenance mode"),
+                Ok(_) => println!("✅ '{name}' is serving traffic again"),
+                Err(e) => {
+                    eprintln!("Failed to update maintenance mode: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Logs(args) => {
+            let auth_token = require_auth_token();
+            let since_millis = args
+                .since
+                .and_then(|since| SystemTime::now().checked_sub(since))
+                .and_then(|start| start.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |start| start.as_millis() as u64);
+            match get_logs(
+                &args.server,
+                &args.name,
+                since_millis,
+                args.tail.unwrap_or(0),
+                &auth_token,
+            )
+            .await
+            {
+                Ok(entries) => print_logs(&args.name, &entries),
+                Err(e) => {
+                    eprintln!("Failed to fetch logs: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Kv(args) => {
+            let auth_token = require_auth_token();
+            let result = match args.action {
+                KvAction::Usage => get_kv_usage(&args.server, &args.name, &auth_token)
+                    .await
+                    .map(|usage| print_kv_usage(&args.name, &usage)),
+                KvAction::Keys { bucket } => {
+                    list_kv_keys(&args.server, &args.name, &bucket, &auth_token)
+                        .await
+                        .map(|keys| print_kv_keys(&bucket, &keys))
+                }
+                KvAction::Get { key, bucket } => {
+                    match get_kv_value(&args.server, &args.name, &bucket, &key, &auth_token).await {
+                        Ok(Some(value)) => std::io::stdout()
+                            .write_all(&value)
+                            .map_err(anyhow::Error::from),
+                        Ok(None) => Err(anyhow::anyhow!("No key '{key}' in bucket '{bucket}'")),
+                        Err(e) => Err(e),
+                    }
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to inspect key-value data: {e}");
+                exit(1);
+            }
+        }
+
+        Commands::Warm(args) => {
+            let auth_token = require_auth_token();
+            match warm_function(&args.server, &args.name, &auth_token).await {
+                Ok(elapsed_ms) => println!("✅ Warmed '{}' in {elapsed_ms}ms", args.name),
+                Err(e) => {
+                    eprintln!("Failed to warm function: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::KeepWarm(args) => {
+            let auth_token = require_auth_token();
+            let policy =
+                args.from
+                    .zip(args.to)
+                    .map(|(start_hour, end_hour)| faasta_interface::KeepWarm {
+                        start_hour,
+                        end_hour,
+                        scale_to_zero: args.scale_to_zero,
+                    });
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.keep_warm = policy;
+            })
+            .await;
+
+            match result {
+                Ok(config) => match config.keep_warm {
+                    Some(policy) => println!(
+                        "✅ Keeping '{}' warm from {:02}:00 to {:02}:00 UTC{}",
+                        args.name,
+                        policy.start_hour,
+                        policy.end_hour,
+                        if policy.scale_to_zero {
+                            ", unloaded otherwise"
+                        } else {
+                            ""
+                        }
+                    ),
+                    None => println!("✅ Keep-warm disabled for '{}'", args.name),
+                },
+                Err(e) => {
+                    eprintln!("Failed to update keep-warm policy: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Priority(args) => {
+            use faasta_interface::Priority;
+
+            let auth_token = require_auth_token();
+            let priority = match args.priority {
+                PriorityClass::Critical => Priority::Critical,
+                PriorityClass::Standard => Priority::Standard,
+                PriorityClass::Batch => Priority::Batch,
+            };
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.priority = priority;
+            })
+            .await;
+
+            match result {
+                Ok(_) => println!("✅ '{}' now has {priority:?} priority", args.name),
+                Err(e) => {
+                    eprintln!("Failed to update priority: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Deterministic(args) => {
+            let auth_token = require_auth_token();
+            let mode = (!args.disable).then_some(faasta_interface::Deterministic {
+                seed: args.seed,
+                start_unix_secs: args.start,
+            });
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.deterministic = mode;
+            })
+            .await;
+
+            match result {
+                Ok(config) => match config.deterministic {
+                    Some(mode) => println!(
+                        "✅ '{}' now runs with seed {} and clocks starting at {}",
+                        args.name, mode.seed, mode.start_unix_secs
+                    ),
+                    None => println!("✅ Determinism mode disabled for '{}'", args.name),
+                },
+                Err(e) => {
+                    eprintln!("Failed to update determinism mode: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Graphql(args) => {
+            let auth_token = require_auth_token();
+            let graphql = (!args.disable).then_some(faasta_interface::GraphQl { path: args.path });
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.graphql = graphql;
+            })
+            .await;
+
+            match result {
+                Ok(config) => match config.graphql {
+                    Some(graphql) => {
+                        println!("✅ '{}' serves GraphQL at {}", args.name, graphql.path)
+                    }
+                    None => println!("✅ GraphQL handling disabled for '{}'", args.name),
+                },
+                Err(e) => {
+                    eprintln!("Failed to update GraphQL endpoint: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::ServerTiming(args) => {
+            let auth_token = require_auth_token();
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.server_timing = !args.disable;
+            })
+            .await;
+
+            match result {
+                Ok(config) if config.server_timing => {
+                    println!(
+                        "✅ '{}' now reports platform timings in Server-Timing",
+                        args.name
+                    )
+                }
+                Ok(_) => println!("✅ Platform Server-Timing disabled for '{}'", args.name),
+                Err(e) => {
+                    eprintln!("Failed to update Server-Timing: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::PublicStats(args) => {
+            let auth_token = require_auth_token();
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.public_stats = !args.disable;
+            })
+            .await;
+
+            match result {
+                Ok(config) if config.public_stats => println!(
+                    "✅ Stats for '{}' are public at https://{}.faasta.lol/_faasta/stats",
+                    args.name, args.name
+                ),
+                Ok(_) => println!("✅ Stats for '{}' are no longer public", args.name),
+                Err(e) => {
+                    eprintln!("Failed to update public stats: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Preload(args) => {
+            let auth_token = require_auth_token();
+            let targets = args.targets.clone();
+            let result = update_function_config(&args.server, &args.name, &auth_token, |config| {
+                config.preload = targets;
+            })
+            .await;
+
+            match result {
+                Ok(config) if config.preload.is_empty() => {
+                    println!("✅ '{}' no longer declares functions to preload", args.name)
+                }
+                Ok(config) => println!(
+                    "✅ Traffic to '{}' now warms {}",
+                    args.name,
+                    config.preload.join(", ")
+                ),
+                Err(e) => {
+                    eprintln!("Failed to update preloads: {e}");
+                    exit(1);
+                }
+            }
+        }
+
+        Commands::Run(run_args) => {
+            // Call the run module handler
+            run::handle_run(run_args.port).await.unwrap_or_else(|e| {
+                eprintln!("Failed to run function: {e}");
+                exit(1);
+            });
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct LoginArgs {
+    /// GitHub username (only needed for manual login)
+    #[arg(long)]
+    username: Option<String>,
+
+    /// GitHub token (only needed for manual login)
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Skip browser OAuth flow and manually provide credentials
+    #[arg(long)]
+    manual: bool,
+
+    /// Request a signing key (needs two-factor); once issued, management calls must be signed
+    #[arg(long)]
+    sign_requests: bool,
+
+    /// Current code from your authenticator, if two-factor authentication is enabled
+    #[arg(long, requires = "sign_requests")]
+    otp: Option<String>,
+
+    /// Server that issues the signing key
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Parser)] // requires `derive` feature
+#[command(name = "cargo")]
+#[command(bin_name = "cargo")]
+#[command(styles = CLAP_STYLING)]
+enum Faasta {
+    #[command(name = "faasta")]
+    Faasta(Cli),
+}
+
+#[derive(Args, Debug)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Deploys a project to the server
+    Deploy(DeployArgs),
+    /// Invokes a function with the specified name and argument
+    Invoke(InvokeArgs),
+    /// List, inspect and cancel asynchronous invocations, and set how they are retried
+    Jobs(JobsArgs),
+    /// Initialize a new project in the current directory
+    Init,
+    /// Create a new project in a new directory
+    New(NewArgs),
+    /// Vendor the WIT packages Faasta provides into a project, for toolchains other than cargo
+    Wit(WitArgs),
+    /// Build the function (and optionally deploy it)
+    Build(BuildArgs),
+    /// Break down what takes up space in the built component
+    Size(SizeArgs),
+    /// Set up GitHub authentication
+    Login(LoginArgs),
+    /// Get metrics for deployed functions
+    Metrics(ServerArgs),
+    /// Show what a function recently wrote to stdout, stderr or its log
+    Logs(LogsArgs),
+    /// Inspect what a function stores through wasi:keyvalue
+    Kv(KvArgs),
+    /// List all functions deployed under the current GitHub account
+    List(ListArgs),
+    /// Run a function locally for testing
+    Run(RunArgs),
+    /// Unpublish a function from the server
+    Unpublish(UnpublishArgs),
+    /// Copy a share of a function's traffic to a shadow function
+    Mirror(MirrorArgs),
+    /// Split a function's traffic between variants for A/B testing
+    Experiment(ExperimentArgs),
+    /// Manage redirect and rewrite rules evaluated before a function runs
+    Routes(RoutesArgs),
+    /// Manage headers the platform adds to a function's responses
+    Headers(HeadersArgs),
+    /// Manage HTML pages shown for errors returned on a function's behalf
+    ErrorPages(ErrorPagesArgs),
+    /// Take a function offline with a 503 page without unpublishing it
+    Maintenance(MaintenanceArgs),
+    /// Load and instantiate a function ahead of traffic
+    Warm(WarmArgs),
+    /// Keep a function loaded during a daily window
+    KeepWarm(KeepWarmArgs),
+    /// Set the order in which a function's traffic is shed under load
+    Priority(PriorityArgs),
+    /// Give a function fixed clocks and seeded randomness for reproducible tests
+    Deterministic(DeterministicArgs),
+    /// Normalize GraphQL requests and resolve persisted queries at the edge
+    Graphql(GraphqlArgs),
+    /// Report cache state, cold starts and handler time in a Server-Timing header
+    ServerTiming(ServerTimingArgs),
+    /// Warm other functions whenever a function receives traffic
+    Preload(PreloadArgs),
+    /// Publish a function's requests per day and p95 latency on a public stats page
+    PublicStats(PublicStatsArgs),
+    /// Manage secrets passed to a function as environment variables
+    Secrets(SecretsArgs),
+    /// List the tokens that have access to your account and revoke them
+    Sessions(SessionsArgs),
+    /// Require an authenticator code for unpublishing and issuing signing keys
+    TwoFactor(TwoFactorArgs),
+}
+
+#[derive(Args, Debug)]
+struct DeployArgs {
+    /// Path to the project to deploy
+    path: Option<String>,
+
+    /// Skip GitHub authentication
+    #[arg(long)]
+    skip_auth: bool,
+
+    /// Explicit path to compiled WASIp3 component artifact (overrides automatic detection)
+    #[arg(long)]
+    artifact_path: Option<String>,
+
+    /// Function name to use (if different from package name)
+    #[arg(long)]
+    function_name: Option<String>,
+
+    /// Server address to deploy to (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+
+    /// Deploy a directory of static assets instead of a component
+    #[arg(long = "static", value_name = "DIR", conflicts_with = "artifact_path")]
+    static_dir: Option<PathBuf>,
+
+    /// Have the server pull the component from an OCI registry, e.g. `ghcr.io/me/fn:tag`
+    #[arg(
+        long,
+        value_name = "REFERENCE",
+        conflicts_with_all = ["artifact_path", "static_dir"]
+    )]
+    oci: Option<String>,
+
+    /// Another node of a federated deployment to publish to (repeatable)
+    #[arg(long = "node", value_name = "ADDR", conflicts_with = "static_dir")]
+    nodes: Vec<String>,
+
+    /// Publish only to nodes in this region and pin the function to it (repeatable)
+    #[arg(long, conflicts_with = "static_dir")]
+    region: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct SizeArgs {
+    /// Component to analyze (defaults to the project's build output)
+    #[arg(long)]
+    artifact_path: Option<String>,
+
+    /// Deployed function to compare with (if different from package name)
+    #[arg(long)]
+    function_name: Option<String>,
+
+    /// Number of largest functions to list
+    #[arg(long, default_value_t = 20)]
+    top: usize,
+
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+
+    /// Don't compare with the deployed version
+    #[arg(long)]
+    offline: bool,
+}
 
 #[derive(Args, Debug)]
 struct BuildArgs {
@@ -805,107 +1647,1159 @@ struct BuildArgs {
     #[arg(long)]
     function_name: Option<String>,
 
-    /// Server address to deploy to (e.g., "faasta.lol:4433")
-    #[arg(long, default_value = "faasta.lol:4433")]
-    server: String,
+    /// Server address to deploy to (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// Port to run the local server on
+    #[arg(short, long, default_value = "3000")]
+    port: u16,
+}
+
+#[derive(Args, Debug)]
+struct InvokeArgs {
+    /// Name of the function to invoke
+    name: String,
+    /// Optional argument to pass to the function
+    #[arg(default_value = "")]
+    arg: String,
+}
+
+#[derive(Args, Debug)]
+struct JobsArgs {
+    #[command(subcommand)]
+    action: JobsAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum JobsAction {
+    /// List a function's jobs, newest first
+    List {
+        /// Name of the function
+        name: String,
+    },
+    /// Show a job's state and, once it finished, its result
+    Inspect {
+        /// Id returned by `/v1/invoke-async`
+        job_id: String,
+    },
+    /// Cancel a queued or running job
+    Cancel {
+        /// Id returned by `/v1/invoke-async`
+        job_id: String,
+    },
+    /// Set how a function's failed jobs are retried
+    Retry {
+        /// Name of the function
+        name: String,
+        /// Attempts in total, including the first
+        #[arg(long)]
+        max_attempts: Option<u32>,
+        /// Delay before the first retry, doubled for each further one
+        #[arg(long)]
+        backoff_ms: Option<u64>,
+        /// Longest delay between attempts
+        #[arg(long)]
+        max_backoff_ms: Option<u64>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct UnpublishArgs {
+    /// Name of the function to unpublish
+    name: String,
+    /// Current code from your authenticator, if two-factor authentication is enabled
+    #[arg(long)]
+    otp: Option<String>,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct LogsArgs {
+    /// Name of the function
+    name: String,
+    /// Show only the last N lines
+    #[arg(long, value_name = "N")]
+    tail: Option<u32>,
+    /// Show only lines from the last DURATION, e.g. 30s, 10m, 2h or 1d
+    #[arg(long, value_name = "DURATION", value_parser = parse_since)]
+    since: Option<Duration>,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct KvArgs {
+    /// Name of the function whose key-value data is inspected
+    name: String,
+    #[command(subcommand)]
+    action: KvAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum KvAction {
+    /// Show the function's buckets and how much of its quota they use
+    Usage,
+    /// List the keys of a bucket and the size of their values
+    Keys {
+        #[arg(long, default_value = "default")]
+        bucket: String,
+    },
+    /// Write the value of KEY to stdout
+    Get {
+        key: String,
+        #[arg(long, default_value = "default")]
+        bucket: String,
+    },
+}
+
+/// A duration of whole seconds, minutes, hours or days, e.g. `10m`
+fn parse_since(value: &str) -> Result<Duration, String> {
+    let split = value.len() - value.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("expected a number followed by s, m, h or d, got '{value}'"))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit '{unit}'; use s, m, h or d")),
+    };
+    Ok(Duration::from_secs(amount.saturating_mul(unit_secs)))
+}
+
+#[derive(Args, Debug)]
+struct WarmArgs {
+    /// Name of the function to warm
+    name: String,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct KeepWarmArgs {
+    /// Name of the function to keep warm
+    name: String,
+    /// Hour (UTC, 0-23) at which the function is loaded
+    #[arg(long, required_unless_present = "disable", value_parser = clap::value_parser!(u8).range(0..=23))]
+    from: Option<u8>,
+    /// Hour (UTC, 0-23) at which the window ends; equal to --from keeps it warm all day
+    #[arg(long, required_unless_present = "disable", value_parser = clap::value_parser!(u8).range(0..=23))]
+    to: Option<u8>,
+    /// Unload the function outside the window so it starts cold
+    #[arg(long)]
+    scale_to_zero: bool,
+    /// Remove the keep-warm policy
+    #[arg(long, conflicts_with_all = ["from", "to", "scale_to_zero"])]
+    disable: bool,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct PriorityArgs {
+    /// Name of the function
+    name: String,
+    /// Priority class; batch is shed first and critical last
+    #[arg(value_enum)]
+    priority: PriorityClass,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct DeterministicArgs {
+    /// Name of the function
+    name: String,
+    /// Seed of the function's random sources
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Wall-clock time every request starts at, in seconds since the Unix epoch
+    #[arg(long, default_value_t = 1_704_067_200)]
+    start: u64,
+    /// Restore real clocks and randomness
+    #[arg(long, conflicts_with_all = ["seed", "start"])]
+    disable: bool,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct GraphqlArgs {
+    /// Name of the function
+    name: String,
+    /// Path of the GraphQL endpoint below the function's mount
+    #[arg(long, default_value = "/graphql")]
+    path: String,
+    /// Stop treating requests as GraphQL
+    #[arg(long, conflicts_with = "path")]
+    disable: bool,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct PreloadArgs {
+    /// Name of the function
+    name: String,
+    /// Functions to warm when it receives traffic; none clears the list
+    targets: Vec<String>,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct ServerTimingArgs {
+    /// Name of the function
+    name: String,
+    /// Stop adding the platform's timings
+    #[arg(long)]
+    disable: bool,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct PublicStatsArgs {
+    /// Name of the function
+    name: String,
+    /// Take the stats page down again
+    #[arg(long)]
+    disable: bool,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PriorityClass {
+    Critical,
+    Standard,
+    Batch,
+}
+
+#[derive(Args, Debug)]
+struct MirrorArgs {
+    /// Name of the function whose traffic is mirrored
+    name: String,
+    /// Shadow function that receives the copied requests
+    #[arg(long, required_unless_present = "disable")]
+    target: Option<String>,
+    /// Percentage of requests to mirror
+    #[arg(long, default_value = "100", value_parser = clap::value_parser!(u8).range(0..=100))]
+    percent: u8,
+    /// Stop mirroring traffic
+    #[arg(long, conflicts_with = "target")]
+    disable: bool,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct ExperimentArgs {
+    /// Name of the function whose traffic is split
+    name: String,
+    /// How clients are assigned: `header:NAME`, `cookie:NAME` or `ip`
+    #[arg(long, required_unless_present = "disable", value_parser = parse_assignment)]
+    by: Option<faasta_interface::VariantAssignment>,
+    /// Variant as `label=function:weight` (repeatable)
+    #[arg(long = "variant", required_unless_present = "disable", value_parser = parse_variant)]
+    variants: Vec<faasta_interface::Variant>,
+    /// Stop the experiment and route all traffic to the function itself
+    #[arg(long, conflicts_with_all = ["by", "variants"])]
+    disable: bool,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct RoutesArgs {
+    /// Name of the function whose routes are managed
+    name: String,
+    #[command(subcommand)]
+    action: RoutesAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum RoutesAction {
+    /// Show the configured rules
+    List,
+    /// Redirect SOURCE to DESTINATION (a path or absolute URL); `*` matches a suffix
+    Redirect {
+        source: String,
+        destination: String,
+        /// Redirect status code
+        #[arg(long, default_value_t = 301)]
+        status: u16,
+    },
+    /// Invoke the function with DESTINATION as the path when SOURCE matches
+    Rewrite { source: String, destination: String },
+    /// Remove the rules for SOURCE
+    Remove { source: String },
+    /// Redirect paths to a canonical trailing-slash form
+    TrailingSlash {
+        #[arg(value_enum)]
+        mode: TrailingSlashMode,
+    },
+    /// Invoke the function without the `/name` prefix on path-based URLs
+    StripPrefix {
+        #[arg(value_enum)]
+        mode: StripPrefixMode,
+    },
+    /// Remove every rule
+    Clear,
+}
+
+#[derive(Args, Debug)]
+struct HeadersArgs {
+    /// Name of the function whose response headers are managed
+    name: String,
+    #[command(subcommand)]
+    action: HeadersAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum HeadersAction {
+    /// Show the configured headers
+    List,
+    /// Add NAME: VALUE to responses that don't already set NAME
+    Set {
+        name: String,
+        value: String,
+        /// Also overwrite the header when the function sets it
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Stop adding NAME
+    Remove { name: String },
+    /// Remove every header
+    Clear,
+}
+
+#[derive(Args, Debug)]
+struct SecretsArgs {
+    /// Name of the function whose secrets are managed
+    name: String,
+    #[command(subcommand)]
+    action: SecretsAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum SecretsAction {
+    /// Show the names of the function's secrets
+    List,
+    /// Set KEY, reading the value from stdin when VALUE is omitted
+    Set { key: String, value: Option<String> },
+    /// Remove KEY
+    Remove { key: String },
+}
+
+#[derive(Args, Debug)]
+struct SessionsArgs {
+    #[command(subcommand)]
+    action: SessionsAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum SessionsAction {
+    /// Show the tokens that have been used with your account, most recently used first
+    List,
+    /// Refuse a token on the server from now on, e.g. a leaked CI token
+    Revoke {
+        /// Id shown by `cargo faasta sessions list`
+        session_id: String,
+    },
+}
+
+#[derive(Args, Debug)]
+struct TwoFactorArgs {
+    #[command(subcommand)]
+    action: TwoFactorAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum TwoFactorAction {
+    /// Set up an authenticator app, replacing any previous one
+    Enable {
+        /// Current code from the previous authenticator, when replacing one
+        #[arg(long)]
+        otp: Option<String>,
+    },
+    /// Stop requiring authenticator codes
+    Disable {
+        /// Current code from your authenticator
+        #[arg(long)]
+        otp: String,
+    },
+}
+
+#[derive(Args, Debug)]
+struct ErrorPagesArgs {
+    /// Name of the function whose error pages are managed
+    name: String,
+    #[command(subcommand)]
+    action: ErrorPagesAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum ErrorPagesAction {
+    /// Show which statuses have a custom page
+    List,
+    /// Use the HTML template in FILE for STATUS (e.g. `404` or `5xx`)
+    Set { status: String, file: PathBuf },
+    /// Remove the page for STATUS
+    Remove { status: String },
+    /// Remove every page
+    Clear,
+}
+
+#[derive(Args, Debug)]
+struct MaintenanceArgs {
+    #[command(subcommand)]
+    action: MaintenanceAction,
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433", global = true)]
+    server: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum MaintenanceAction {
+    /// Serve a 503 maintenance response instead of invoking the function
+    On {
+        /// Name of the function
+        name: String,
+        /// Message shown to clients
+        #[arg(long)]
+        message: Option<String>,
+        /// Seconds after which clients should retry (sent as Retry-After)
+        #[arg(long)]
+        retry_after: Option<u64>,
+    },
+    /// Resume serving the function
+    Off {
+        /// Name of the function
+        name: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TrailingSlashMode {
+    Always,
+    Never,
+    Off,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum StripPrefixMode {
+    On,
+    Off,
+}
+
+fn parse_assignment(value: &str) -> Result<faasta_interface::VariantAssignment, String> {
+    use faasta_interface::VariantAssignment;
+    match value.split_once(':') {
+        Some(("header", name)) if !name.is_empty() => {
+            Ok(VariantAssignment::Header(name.to_ascii_lowercase()))
+        }
+        Some(("cookie", name)) if !name.is_empty() => {
+            Ok(VariantAssignment::Cookie(name.to_string()))
+        }
+        None if value == "ip" => Ok(VariantAssignment::ClientIp),
+        _ => Err("expected `header:NAME`, `cookie:NAME` or `ip`".to_string()),
+    }
+}
+
+fn parse_variant(value: &str) -> Result<faasta_interface::Variant, String> {
+    let (name, rest) = value
+        .split_once('=')
+        .ok_or("expected `label=function:weight`")?;
+    let (function, weight) = match rest.rsplit_once(':') {
+        Some((function, weight)) => (
+            function,
+            weight
+                .parse()
+                .map_err(|_| format!("invalid weight '{weight}'"))?,
+        ),
+        None => (rest, 1),
+    };
+    if name.is_empty() || function.is_empty() {
+        return Err("expected `label=function:weight`".to_string());
+    }
+    Ok(faasta_interface::Variant {
+        name: name.to_string(),
+        function: function.to_string(),
+        weight,
+    })
+}
+
+#[derive(Args, Debug)]
+struct ServerArgs {
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+}
+
+#[derive(Args, Debug)]
+struct ListArgs {
+    /// Server address (e.g., "faasta.lol:4433")
+    #[arg(long, default_value = "faasta.lol:4433")]
+    server: String,
+    /// Another node of a federated deployment to include (repeatable)
+    #[arg(long = "node", value_name = "ADDR")]
+    nodes: Vec<String>,
+}
+
+/// Custom styling for the CLI
+pub const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling::Styles::styled()
+    .header(clap_cargo::style::HEADER)
+    .usage(clap_cargo::style::USAGE)
+    .literal(clap_cargo::style::LITERAL)
+    .placeholder(clap_cargo::style::PLACEHOLDER)
+    .error(clap_cargo::style::ERROR)
+    .valid(clap_cargo::style::VALID)
+    .invalid(clap_cargo::style::INVALID);
+
+/// Formats the function URL based on the server URL
+/// If server is a domain (not localhost or an IP), it uses function_name as a subdomain
+/// Otherwise, it appends function_name as a path
+fn format_function_url(function_name: &str, server: &str) -> String {
+    // Ensure server has a scheme
+    let server_url = if !server.contains("://") {
+        format!("https://{server}")
+    } else {
+        server.to_string()
+    };
+
+    // Parse the server URL to get the hostname
+    // Format: scheme://host/path
+    let url_parts: Vec<&str> = server_url.split("://").collect();
+    if url_parts.len() != 2 {
+        // If URL doesn't follow the expected format, fall back to a simple approach
+        return format!("https://{server}/{function_name}");
+    }
+
+    let scheme = url_parts[0];
+    let rest = url_parts[1];
+
+    // Split host and path
+    let host_path_parts: Vec<&str> = rest.split('/').collect();
+    let host = host_path_parts[0];
+
+    // Check if host is localhost or an IP address
+    if host == "localhost" || host == "127.0.0.1" || is_ip_address(host) {
+        // For localhost or IP, append function_name as a path
+        let base = if server_url.ends_with('/') {
+            server_url
+        } else {
+            format!("{server_url}/")
+        };
+        format!("{base}{function_name}")
+    } else {
+        // For a domain name, use function_name as a subdomain
+        format!("{scheme}://{function_name}.{host}/")
+    }
+}
+
+/// Extract the server host from a server address (removing any port)
+fn extract_server_host(server_addr: &str) -> String {
+    // If it already has a scheme, use it as is
+    if server_addr.contains("://") {
+        return server_addr.to_string();
+    }
+
+    // Remove port if present
+    if let Some(host) = server_addr.split(':').next() {
+        format!("https://{host}")
+    } else {
+        format!("https://{server_addr}")
+    }
+}
+
+/// Load the stored GitHub credentials as an RPC auth token, exiting if not logged in
+/// Obtain a request-signing key from `server` and store it in the config.
+async fn issue_signing_key(server: &str, otp: Option<String>) {
+    let auth_token = require_auth_token();
+    let username = auth_token.split(':').next().unwrap_or_default().to_string();
+    let client = match connect(server).await {
+        Ok(client) => elevate(client, &auth_token, otp).await,
+        Err(e) => {
+            eprintln!("Failed to connect to server: {e}");
+            exit(1);
+        }
+    };
+    let key = match client.rotate_signing_key(&username, auth_token).await {
+        Ok(Ok(key)) => key,
+        Ok(Err(e)) => {
+            eprintln!("Server error: {e:?}");
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("Communication error: {}", run::describe_rpc_error(&e));
+            exit(1);
+        }
+    };
+
+    let mut config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(1);
+        }
+    };
+    config.signing_keys.insert(server.to_string(), key);
+    if let Err(e) = save_config(&config) {
+        eprintln!("Failed to save signing key: {e}");
+        exit(1);
+    }
+    println!("🔏 Management calls to {server} will now be signed.");
+}
+
+/// Exchange `otp`, if given, for an elevation that `client` then carries on signed calls.
+async fn elevate(
+    client: run::FunctionServiceClient,
+    auth_token: &str,
+    otp: Option<String>,
+) -> run::FunctionServiceClient {
+    let Some(otp) = otp else {
+        return client;
+    };
+    match client.elevate(otp, auth_token.to_string()).await {
+        Ok(Ok(elevation)) => client.with_elevation(Some(elevation)),
+        Ok(Err(e)) => {
+            eprintln!("Two-factor check failed: {e}");
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("Communication error: {}", run::describe_rpc_error(&e));
+            exit(1);
+        }
+    }
+}
+
+/// Connect to a server, signing management calls if `login --sign-requests` issued a key for it.
+async fn connect(server_addr: &str) -> anyhow::Result<run::FunctionServiceClient> {
+    let signing_key = load_config()
+        .ok()
+        .and_then(|config| config.signing_keys.get(server_addr).cloned())
+        .and_then(|key| hex::decode(key).ok());
+    Ok(run::connect_to_function_service(server_addr)
+        .await?
+        .with_signing_key(signing_key))
+}
+
+fn require_auth_token() -> String {
+    match load_config() {
+        Ok(FaastaConfig {
+            github_username: Some(username),
+            github_token: Some(token),
+            ..
+        }) => format!("{username}:{token}"),
+        Ok(_) => {
+            println!(
+                "No GitHub credentials found. Run 'cargo faasta login' to set up authentication."
+            );
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to load config: {e}");
+            exit(1);
+        }
+    }
+}
+
+/// Upload a directory of static assets, named after `--function-name` or the current directory
+async fn deploy_static_site(args: &DeployArgs, static_dir: &std::path::Path) {
+    let auth_token = require_auth_token();
+
+    let function_name = match args.function_name.clone().or_else(|| {
+        std::env::current_dir()
+            .ok()
+            .and_then(|dir| dir.file_name()?.to_str().map(str::to_owned))
+    }) {
+        Some(name) => name,
+        None => {
+            eprintln!("Error: Could not determine a site name; pass --function-name");
+            exit(1);
+        }
+    };
+
+    let spinner = indicatif::ProgressBar::new_spinner();
+    spinner.set_message(format!("Collecting files from {}...", static_dir.display()));
+    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+
+    let mut assets = Vec::new();
+    if let Err(e) = collect_static_assets(static_dir, static_dir, &mut assets) {
+        spinner.finish_and_clear();
+        eprintln!("Failed to read {}: {e}", static_dir.display());
+        exit(1);
+    }
+    let total_size: usize = assets.iter().map(|asset| asset.contents.len()).sum();
+    if total_size > faasta_interface::MAX_STATIC_SITE_SIZE {
+        spinner.finish_and_clear();
+        eprintln!(
+            "Error: Static site too large ({}MB). Maximum allowed size is 100MB.",
+            total_size / 1024 / 1024
+        );
+        exit(1);
+    }
+
+    spinner.set_message(format!(
+        "Uploading {} files for '{function_name}'...",
+        assets.len()
+    ));
+    let client = match connect(&args.server).await {
+        Ok(client) => client,
+        Err(e) => {
+            spinner.finish_and_clear();
+            eprintln!("Failed to connect to server: {e}");
+            exit(1);
+        }
+    };
+
+    match client
+        .publish_static(assets, function_name.clone(), auth_token)
+        .await
+    {
+        Ok(Ok(message)) => {
+            spinner.finish_and_clear();
+            println!("✅ {message}");
+            let server_host = extract_server_host(&args.server);
+            println!(
+                "Site URL: {}",
+                format_function_url(&function_name, &server_host)
+            );
+        }
+        Ok(Err(e)) => {
+            spinner.finish_and_clear();
+            eprintln!("Server error: {e:?}");
+            exit(1);
+        }
+        Err(e) => {
+            spinner.finish_and_clear();
+            eprintln!("Communication error: {}", run::describe_rpc_error(&e));
+            exit(1);
+        }
+    }
+}
+
+/// Have every target node pull and publish the component at `reference`, named after
+/// `--function-name` or the reference's repository
+async fn deploy_oci(args: &DeployArgs, reference: &str) {
+    let auth_token = require_auth_token();
+
+    let Some(function_name) = args
+        .function_name
+        .clone()
+        .or_else(|| oci_function_name(reference).map(str::to_owned))
+    else {
+        eprintln!("Error: Could not determine a function name; pass --function-name");
+        exit(1);
+    };
+
+    let spinner = indicatif::ProgressBar::new_spinner();
+    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+    let targets = match deploy_targets(args).await {
+        Ok(targets) => targets,
+        Err(e) => {
+            spinner.finish_and_clear();
+            eprintln!("{e}");
+            exit(1);
+        }
+    };
+
+    for server_addr in &targets {
+        spinner.set_message(format!(
+            "Publishing '{function_name}' from {reference} on {server_addr}..."
+        ));
+        let client = match connect(server_addr).await {
+            Ok(client) => client,
+            Err(e) => {
+                spinner.finish_and_clear();
+                eprintln!("Failed to connect to {server_addr}: {e}");
+                exit(1);
+            }
+        };
+        match client
+            .publish_oci(
+                reference.to_string(),
+                function_name.clone(),
+                auth_token.clone(),
+            )
+            .await
+        {
+            Ok(Ok(message)) => spinner.suspend(|| println!("✅ {message}")),
+            Ok(Err(e)) => {
+                spinner.finish_and_clear();
+                eprintln!("Server error from {server_addr}: {e:?}");
+                exit(1);
+            }
+            Err(e) => {
+                spinner.finish_and_clear();
+                eprintln!(
+                    "Communication error with {server_addr}: {}",
+                    run::describe_rpc_error(&e)
+                );
+                exit(1);
+            }
+        }
+
+        if !args.region.is_empty() {
+            let regions = args.region.clone();
+            if let Err(e) =
+                update_function_config(server_addr, &function_name, &auth_token, |config| {
+                    config.regions = regions
+                })
+                .await
+            {
+                spinner.finish_and_clear();
+                eprintln!("Failed to pin '{function_name}' on {server_addr}: {e}");
+                exit(1);
+            }
+        }
+    }
+    spinner.finish_and_clear();
+
+    let server_host = extract_server_host(&targets[0]);
+    println!(
+        "Function URL: {}",
+        format_function_url(&function_name, &server_host)
+    );
+    if !args.region.is_empty() {
+        println!("Regions: {}", args.region.join(", "));
+    }
+}
+
+/// The repository's last component, e.g. `fn` for `ghcr.io/me/fn:tag`
+fn oci_function_name(reference: &str) -> Option<&str> {
+    let name = reference.split('@').next()?.rsplit('/').next()?;
+    name.split(':').next().filter(|name| !name.is_empty())
 }
 
-#[derive(Args, Debug)]
-struct RunArgs {
-    /// Port to run the local server on
-    #[arg(short, long, default_value = "3000")]
-    port: u16,
+/// Recursively read every file under `dir` with paths relative to `root`. Symlinks are
+/// skipped rather than followed, so nothing outside `root` is uploaded.
+fn collect_static_assets(
+    root: &std::path::Path,
+    dir: &std::path::Path,
+    assets: &mut Vec<faasta_interface::StaticAsset>,
+) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        let file_type = fs::symlink_metadata(&path)
+            .with_context(|| format!("reading {}", path.display()))?
+            .file_type();
+        if file_type.is_symlink() {
+            eprintln!("Warning: skipping symlink {}", path.display());
+        } else if file_type.is_dir() {
+            collect_static_assets(root, &path, assets)?;
+        } else if file_type.is_file() {
+            let relative = path
+                .strip_prefix(root)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let size = fs::metadata(&path)
+                .with_context(|| format!("reading {}", path.display()))?
+                .len();
+            if size > faasta_interface::MAX_STATIC_ASSET_SIZE as u64 {
+                anyhow::bail!(
+                    "{relative} is too large ({size} bytes, maximum is {} MB per file)",
+                    faasta_interface::MAX_STATIC_ASSET_SIZE / 1024 / 1024
+                );
+            }
+            let contents =
+                fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+            assets.push(faasta_interface::StaticAsset {
+                path: relative,
+                contents,
+            });
+        }
+    }
+    if assets.len() > faasta_interface::MAX_STATIC_SITE_FILES {
+        anyhow::bail!(
+            "too many files (maximum is {})",
+            faasta_interface::MAX_STATIC_SITE_FILES
+        );
+    }
+    Ok(())
 }
 
-#[derive(Args, Debug)]
-struct InvokeArgs {
-    /// Name of the function to invoke
-    name: String,
-    /// Optional argument to pass to the function
-    #[arg(default_value = "")]
-    arg: String,
+/// Compile and instantiate a function on the server, returning the time taken in ms
+async fn warm_function(server: &str, name: &str, auth_token: &str) -> anyhow::Result<u64> {
+    let client = connect(server).await?;
+    client
+        .warm(name.to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
 }
 
-#[derive(Args, Debug)]
-struct UnpublishArgs {
-    /// Name of the function to unpublish
-    name: String,
-    /// Server address (e.g., "faasta.lol:4433")
-    #[arg(long, default_value = "faasta.lol:4433")]
-    server: String,
+/// Fetch a function's platform settings
+/// Nodes a deploy goes to: `--server` and every `--node`, restricted with `--region` to
+/// the nodes labelled with one of the regions.
+async fn deploy_targets(args: &DeployArgs) -> anyhow::Result<Vec<String>> {
+    let nodes = std::iter::once(&args.server).chain(&args.nodes);
+    if args.region.is_empty() {
+        return Ok(nodes.cloned().collect());
+    }
+    let mut targets = Vec::new();
+    for node in nodes {
+        let info = connect(node)
+            .await?
+            .server_info()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to query {node}: {e}"))?;
+        if info
+            .region
+            .is_some_and(|region| args.region.contains(&region))
+        {
+            targets.push(node.clone());
+        }
+    }
+    if targets.is_empty() {
+        anyhow::bail!("No node is in region {}", args.region.join(", "));
+    }
+    Ok(targets)
 }
 
-#[derive(Args, Debug)]
-struct ServerArgs {
-    /// Server address (e.g., "faasta.lol:4433")
-    #[arg(long, default_value = "faasta.lol:4433")]
-    server: String,
+async fn fetch_function_config(
+    server: &str,
+    name: &str,
+    auth_token: &str,
+) -> anyhow::Result<faasta_interface::FunctionConfig> {
+    let client = connect(server).await?;
+    client
+        .get_function_config(name.to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
 }
 
-/// Custom styling for the CLI
-pub const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling::Styles::styled()
-    .header(clap_cargo::style::HEADER)
-    .usage(clap_cargo::style::USAGE)
-    .literal(clap_cargo::style::LITERAL)
-    .placeholder(clap_cargo::style::PLACEHOLDER)
-    .error(clap_cargo::style::ERROR)
-    .valid(clap_cargo::style::VALID)
-    .invalid(clap_cargo::style::INVALID);
+/// Fetch a function's platform settings, apply `update`, and store the result
+async fn update_function_config(
+    server: &str,
+    name: &str,
+    auth_token: &str,
+    update: impl FnOnce(&mut faasta_interface::FunctionConfig),
+) -> anyhow::Result<faasta_interface::FunctionConfig> {
+    let mut config = fetch_function_config(server, name, auth_token).await?;
 
-/// Formats the function URL based on the server URL
-/// If server is a domain (not localhost or an IP), it uses function_name as a subdomain
-/// Otherwise, it appends function_name as a path
-fn format_function_url(function_name: &str, server: &str) -> String {
-    // Ensure server has a scheme
-    let server_url = if !server.contains("://") {
-        format!("https://{server}")
-    } else {
-        server.to_string()
-    };
+    update(&mut config);
 
-    // Parse the server URL to get the hostname
-    // Format: scheme://host/path
-    let url_parts: Vec<&str> = server_url.split("://").collect();
-    if url_parts.len() != 2 {
-        // If URL doesn't follow the expected format, fall back to a simple approach
-        return format!("https://{server}/{function_name}");
+    let client = connect(server).await?;
+    client
+        .set_function_config(name.to_string(), config.clone(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+    Ok(config)
+}
+
+/// Break down a built component by section, core module and function, and compare it
+/// with the size limit and the version deployed on `--server`
+async fn show_size(args: &SizeArgs) -> anyhow::Result<()> {
+    let (artifact_path, function_name) = match &args.artifact_path {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            let name = args.function_name.clone().or_else(|| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .map(str::to_owned)
+            });
+            (path, name)
+        }
+        None => {
+            let (target_directory, package_name, _) =
+                run::get_project_info().context("Failed to get project information")?;
+            (
+                run::default_artifact_path(&target_directory, &package_name),
+                Some(args.function_name.clone().unwrap_or(package_name)),
+            )
+        }
+    };
+    let component = fs::read(&artifact_path).with_context(|| {
+        format!(
+            "Failed to read {}; run 'cargo faasta build' first or pass --artifact-path",
+            artifact_path.display()
+        )
+    })?;
+    let report = size::analyze(&component)?;
+
+    let limit = faasta_interface::MAX_WASM_SIZE as u64;
+    println!(
+        "{}: {} ({:.1}% of the {} limit)",
+        artifact_path.display(),
+        format_size(report.total),
+        share(report.total, limit),
+        format_size(limit)
+    );
+    if report.total > limit {
+        println!(
+            "Over the limit by {}; the server will reject it",
+            format_size(report.total - limit)
+        );
+    }
+    if let Some(name) = function_name.filter(|_| !args.offline) {
+        match deployed_size(&args.server, &name).await {
+            Ok(Some(deployed)) => println!(
+                "Deployed '{name}': {} ({})",
+                format_size(deployed),
+                if report.total >= deployed {
+                    format!(
+                        "this build is {} larger",
+                        format_size(report.total - deployed)
+                    )
+                } else {
+                    format!(
+                        "this build is {} smaller",
+                        format_size(deployed - report.total)
+                    )
+                }
+            ),
+            Ok(None) => println!("'{name}' is not deployed on {}", args.server),
+            Err(e) => println!("Could not compare with the deployed version: {e:#}"),
+        }
     }
 
-    let scheme = url_parts[0];
-    let rest = url_parts[1];
+    print_size_entries("Sections", &report.sections, report.total, usize::MAX);
+    if report.modules.len() > 1 {
+        print_size_entries("Core modules", &report.modules, report.total, usize::MAX);
+    }
+    print_size_entries(
+        "Largest functions",
+        &report.functions,
+        report.total,
+        args.top,
+    );
+    if report
+        .sections
+        .iter()
+        .any(|section| section.name.starts_with("custom \".debug"))
+    {
+        println!(
+            "\nDebug info is included; building with `strip = true` in the release profile removes it."
+        );
+    }
+    Ok(())
+}
 
-    // Split host and path
-    let host_path_parts: Vec<&str> = rest.split('/').collect();
-    let host = host_path_parts[0];
+/// Size of `name` as uploaded on `server`, or `None` if the caller has no such function
+async fn deployed_size(server: &str, name: &str) -> anyhow::Result<Option<u64>> {
+    let auth_token = match load_config()? {
+        FaastaConfig {
+            github_username: Some(username),
+            github_token: Some(token),
+            ..
+        } => format!("{username}:{token}"),
+        _ => anyhow::bail!("not logged in; run 'cargo faasta login'"),
+    };
+    let metrics = connect(server)
+        .await?
+        .get_metrics(auth_token)
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+    Ok(metrics
+        .function_metrics
+        .iter()
+        .find(|function| function.function_name == name)
+        .map(|function| {
+            function
+                .provenance
+                .as_ref()
+                .map_or(function.artifact_bytes, |provenance| {
+                    provenance.upload_bytes
+                })
+        }))
+}
 
-    // Check if host is localhost or an IP address
-    if host == "localhost" || host == "127.0.0.1" || is_ip_address(host) {
-        // For localhost or IP, append function_name as a path
-        let base = if server_url.ends_with('/') {
-            server_url
-        } else {
-            format!("{server_url}/")
-        };
-        format!("{base}{function_name}")
-    } else {
-        // For a domain name, use function_name as a subdomain
-        format!("{scheme}://{function_name}.{host}/")
+fn print_size_entries(title: &str, entries: &[size::Entry], total: u64, limit: usize) {
+    if entries.is_empty() {
+        return;
+    }
+    println!("\n{title}:");
+    for entry in entries.iter().take(limit) {
+        println!(
+            "  {:>10}  {:>5.1}%  {}",
+            format_size(entry.bytes),
+            share(entry.bytes, total),
+            entry.name
+        );
+    }
+    if entries.len() > limit {
+        println!("  ... and {} more", entries.len() - limit);
     }
 }
 
-/// Extract the server host from a server address (removing any port)
-fn extract_server_host(server_addr: &str) -> String {
-    // If it already has a scheme, use it as is
-    if server_addr.contains("://") {
-        return server_addr.to_string();
+fn share(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
     }
+}
 
-    // Remove port if present
-    if let Some(host) = server_addr.split(':').next() {
-        format!("https://{host}")
-    } else {
-        format!("https://{server_addr}")
+/// Publish the project's README.md as the function's `/_faasta/about` page.
+async fn upload_readme(
+    server: &str,
+    name: &str,
+    auth_token: &str,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    let readme = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+    if readme.len() > faasta_interface::MAX_README_BYTES {
+        anyhow::bail!(
+            "README.md exceeds {} KiB",
+            faasta_interface::MAX_README_BYTES / 1024
+        );
     }
+    update_function_config(server, name, auth_token, |config| {
+        config.readme = Some(readme)
+    })
+    .await
+    .map(|_| ())
 }
 
 /// Check if a host string is an IP address
@@ -940,11 +2834,288 @@ async fn invoke_function(name: &str, arg: &str) -> anyhow::Result<()> {
         .send()
         .await
         .with_context(|| format!("failed to invoke function at {https_url}"))?;
-    println!("Response status: {}", resp.status());
-    println!("{}", resp.text().await?);
+    let status = resp.status();
+    println!("Response status: {status}");
+    let body = resp.text().await?;
+    if !status.is_success()
+        && let Ok(error) = serde_json::from_str::<faasta_interface::ErrorBody>(&body)
+    {
+        println!("Error: {error}");
+        if let Some(docs_url) = error.docs_url {
+            println!("See {docs_url}");
+        }
+        return Ok(());
+    }
+    println!("{body}");
+    Ok(())
+}
+
+async fn job_status(server: &str, job_id: &str) -> anyhow::Result<faasta_interface::JobStatus> {
+    let client = connect(server).await?;
+    client
+        .job_status(job_id.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn list_jobs(
+    server: &str,
+    name: &str,
+    auth_token: &str,
+) -> anyhow::Result<Vec<faasta_interface::JobSummary>> {
+    let client = connect(server).await?;
+    client
+        .list_jobs(name.to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn get_logs(
+    server: &str,
+    name: &str,
+    since_millis: u64,
+    tail: u32,
+    auth_token: &str,
+) -> anyhow::Result<Vec<faasta_interface::LogEntry>> {
+    let client = connect(server).await?;
+    client
+        .get_logs(name.to_string(), since_millis, tail, auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn get_kv_usage(
+    server: &str,
+    name: &str,
+    auth_token: &str,
+) -> anyhow::Result<faasta_interface::KvUsage> {
+    let client = connect(server).await?;
+    client
+        .get_kv_usage(name.to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn list_kv_keys(
+    server: &str,
+    name: &str,
+    bucket: &str,
+    auth_token: &str,
+) -> anyhow::Result<Vec<faasta_interface::KvKey>> {
+    let client = connect(server).await?;
+    client
+        .list_kv_keys(name.to_string(), bucket.to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn get_kv_value(
+    server: &str,
+    name: &str,
+    bucket: &str,
+    key: &str,
+    auth_token: &str,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let client = connect(server).await?;
+    client
+        .get_kv_value(
+            name.to_string(),
+            bucket.to_string(),
+            key.to_string(),
+            auth_token.to_string(),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn cancel_job(server: &str, job_id: &str, auth_token: &str) -> anyhow::Result<()> {
+    let client = connect(server).await?;
+    client
+        .cancel_job(job_id.to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn list_sessions(
+    server: &str,
+    auth_token: &str,
+) -> anyhow::Result<Vec<faasta_interface::SessionInfo>> {
+    let client = connect(server).await?;
+    client
+        .list_sessions(auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+async fn revoke_session(server: &str, session_id: &str, auth_token: &str) -> anyhow::Result<()> {
+    let client = connect(server).await?;
+    client
+        .revoke_session(session_id.to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+/// Issue a TOTP secret, show it, and enable it once the user enters a code from it.
+async fn enable_two_factor(
+    client: &run::FunctionServiceClient,
+    username: &str,
+    auth_token: &str,
+) -> anyhow::Result<()> {
+    let uri = client
+        .setup_totp(username, auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+    println!("Add this account to your authenticator app:\n\n  {uri}\n");
+    print!("Code from the app: ");
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut code = String::new();
+    std::io::stdin().read_line(&mut code)?;
+    client
+        .confirm_totp(code.trim().to_string(), auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))?;
+    println!(
+        "✅ Two-factor authentication enabled; pass --otp CODE to unpublish or issue signing keys"
+    );
     Ok(())
 }
 
+async fn disable_two_factor(
+    client: &run::FunctionServiceClient,
+    username: &str,
+    auth_token: &str,
+) -> anyhow::Result<()> {
+    client
+        .disable_totp(username, auth_token.to_string())
+        .await
+        .map_err(|e| anyhow::anyhow!("Communication error: {}", run::describe_rpc_error(&e)))?
+        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+}
+
+fn print_sessions(sessions: &[faasta_interface::SessionInfo]) {
+    if sessions.is_empty() {
+        println!("No sessions");
+    }
+    for session in sessions {
+        println!(
+            "{}  token {}  created: {}  last used: {}{}",
+            session.session_id,
+            session.token_hint,
+            session.created_at,
+            session.last_used_at,
+            if session.current {
+                "  (this machine)"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+fn print_logs(name: &str, entries: &[faasta_interface::LogEntry]) {
+    if entries.is_empty() {
+        println!("No logs for '{name}'");
+    }
+    for entry in entries {
+        let stream = match entry.stream {
+            faasta_interface::LogStream::Stdout => "stdout",
+            faasta_interface::LogStream::Stderr => "stderr",
+            faasta_interface::LogStream::Debug => "debug",
+            faasta_interface::LogStream::Info => "info",
+            faasta_interface::LogStream::Warn => "warn",
+            faasta_interface::LogStream::Error => "error",
+        };
+        println!("{}  {stream:<6}  {}", entry.timestamp, entry.line);
+    }
+}
+
+fn print_kv_usage(name: &str, usage: &faasta_interface::KvUsage) {
+    println!(
+        "Key-value data of '{name}': {} of {}",
+        format_size(usage.used_bytes),
+        format_size(usage.quota_bytes)
+    );
+    for bucket in &usage.buckets {
+        println!(
+            "  {}  {} keys  {}",
+            bucket.name,
+            bucket.keys,
+            format_size(bucket.bytes)
+        );
+    }
+}
+
+fn print_kv_keys(bucket: &str, keys: &[faasta_interface::KvKey]) {
+    if keys.is_empty() {
+        println!("No keys in bucket '{bucket}'");
+    }
+    for key in keys {
+        println!("{}  {}", key.key, format_size(key.value_bytes));
+    }
+}
+
+fn print_jobs(name: &str, jobs: &[faasta_interface::JobSummary]) {
+    if jobs.is_empty() {
+        println!("No jobs for '{name}'");
+    }
+    for job in jobs {
+        println!(
+            "{}  {:<9}  attempts: {}  submitted: {}{}",
+            job.job_id,
+            format!("{:?}", job.state),
+            job.attempts,
+            job.submitted_at,
+            job.finished_at
+                .as_ref()
+                .map(|at| format!("  finished: {at}"))
+                .unwrap_or_default()
+        );
+    }
+}
+
+fn print_job_status(status: &faasta_interface::JobStatus) {
+    println!("Job: {} ({})", status.job_id, status.function_name);
+    println!("State: {:?}", status.state);
+    println!("Attempts: {}", status.attempts);
+    println!("Submitted: {}", status.submitted_at);
+    if let Some(finished_at) = &status.finished_at {
+        println!("Finished: {finished_at}");
+    }
+    if let Some(delivered) = status.callback_delivered {
+        println!(
+            "Callback: {}",
+            if delivered { "delivered" } else { "failed" }
+        );
+    }
+    if let Some(error) = &status.error {
+        if status.state.is_finished() {
+            println!("Error: {error}");
+        } else {
+            println!("Last error: {error}");
+        }
+        return;
+    }
+    if let Some(code) = status.status {
+        println!("Response status: {code}");
+        for (name, value) in &status.headers {
+            println!("{name}: {value}");
+        }
+        println!();
+        println!("{}", String::from_utf8_lossy(&status.body));
+    }
+}
+
 // Function to fetch and display metrics
 async fn get_metrics(
     client: &run::FunctionServiceClient,
@@ -976,6 +3147,33 @@ async fn get_metrics(
 
             println!("║ Total Execution Time: {total_time}");
             println!("║ Functions Deployed: {}", metrics.function_metrics.len());
+            let disk = &metrics.disk;
+            println!(
+                "║ Functions Disk Usage: {} (sandboxes: {})",
+                format_size(disk.functions_dir_bytes),
+                format_size(disk.sandbox_bytes)
+            );
+            if disk.orphaned_artifacts + disk.stale_files + disk.quarantined_files > 0 {
+                println!(
+                    "║ Orphaned Artifacts: {}, Stale Files: {}, Quarantined: {}",
+                    disk.orphaned_artifacts, disk.stale_files, disk.quarantined_files
+                );
+            }
+            let admission = &metrics.admission;
+            println!(
+                "║ In-flight Invocations: {}/{}",
+                admission.in_flight, admission.limit
+            );
+            println!("║ Loaded Components: {}", metrics.runtime.cached_components);
+            if admission.shed_requests > 0 {
+                println!(
+                    "║ Shed Since Start: {} (critical: {}, standard: {}, batch: {})",
+                    admission.shed_requests,
+                    admission.shed_critical,
+                    admission.shed_standard,
+                    admission.shed_batch
+                );
+            }
             println!("╠══════════════════════════════════════════════════════");
 
             // If we have no functions, show a message
@@ -1015,6 +3213,86 @@ async fn get_metrics(
                 };
 
                 println!("║ ├─ Average Time per Call: {avg_time}");
+                let cpu_millis = function.cpu_time_micros as f64 / 1000.0;
+                let avg_cpu = if function.call_count > 0 {
+                    format!("{:.3} ms", cpu_millis / function.call_count as f64)
+                } else {
+                    "N/A".to_string()
+                };
+                println!("║ ├─ CPU Time: {cpu_millis:.3} ms ({avg_cpu} per call)");
+                println!("║ ├─ Latency: {}", format_latency(&function.latency));
+                println!(
+                    "║ ├─ Warm Invocations: {}",
+                    format_latency(&function.starts.warm)
+                );
+                println!(
+                    "║ ├─ Cold Starts: {}",
+                    format_latency(&function.starts.cold)
+                );
+                println!(
+                    "║ ├─ Artifact Size: {}",
+                    format_size(function.artifact_bytes)
+                );
+                if let Some(health) = &function.artifact_health {
+                    let state = if health.quarantined {
+                        "quarantined"
+                    } else {
+                        "failing"
+                    };
+                    println!(
+                        "║ ├─ Artifact: {state} after {} failed load(s): {}",
+                        health.load_failures, health.last_error
+                    );
+                }
+                if let Some(provenance) = &function.provenance {
+                    let preinit = if provenance.preinitialized {
+                        ", pre-initialized"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "║ ├─ Built: {} by {}{preinit} from {} upload sha256:{}",
+                        provenance.built_at,
+                        provenance.built_by,
+                        format_size(provenance.upload_bytes),
+                        provenance
+                            .upload_sha256
+                            .get(..12)
+                            .unwrap_or(&provenance.upload_sha256)
+                    );
+                }
+                if function.dedicated {
+                    println!("║ ├─ Isolation: dedicated pool");
+                }
+                if let Some(failure) = &function.last_failure {
+                    println!(
+                        "║ ├─ Last Failure: {} ({}) at {}, {} so far: {}",
+                        failure.message,
+                        failure.code,
+                        failure.failed_at,
+                        failure.failures,
+                        failure.detail
+                    );
+                    for frame in failure.backtrace.lines() {
+                        println!("║ │    {frame}");
+                    }
+                }
+                for variant in &function.variants {
+                    println!(
+                        "║ ├─ Variant {}: {}, {} server error(s)",
+                        variant.name,
+                        format_latency(&variant.latency),
+                        variant.server_errors
+                    );
+                }
+                for timing in &function.server_timings {
+                    println!(
+                        "║ ├─ Server-Timing {}: {:.2} ms average over {} response(s)",
+                        timing.name,
+                        timing.total_micros as f64 / 1000.0 / timing.count.max(1) as f64,
+                        timing.count
+                    );
+                }
                 println!("║ └─ Last Called: {}", function.last_called);
                 println!("╟──────────────────────────────────────────────────────");
             }
@@ -1025,59 +3303,113 @@ async fn get_metrics(
             eprintln!("Server error: {e:?}");
             Err(anyhow::anyhow!("Server error: {:?}", e))
         }
-        Err(e) => Err(anyhow::anyhow!("Communication error: {}", e)),
+        Err(e) => Err(anyhow::anyhow!(
+            "Communication error: {}",
+            run::describe_rpc_error(&e)
+        )),
+    }
+}
+
+/// Count, mean and percentiles of a latency distribution
+fn format_latency(latency: &faasta_interface::LatencyDistribution) -> String {
+    if latency.count == 0 {
+        return "0".to_string();
+    }
+    let bound = |ms: Option<u64>| ms.map_or("N/A".to_string(), |ms| format!("≤ {ms} ms"));
+    format!(
+        "{} (avg {:.2} ms, p50 {}, p95 {}, p99 {})",
+        latency.count,
+        latency.total_micros as f64 / latency.count as f64 / 1000.0,
+        bound(latency.p50_ms),
+        bound(latency.p95_ms),
+        bound(latency.p99_ms)
+    )
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 * 1024 {
+        format!("{:.2} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    } else if bytes >= 1024 * 1024 {
+        format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.2} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{bytes} bytes")
     }
 }
 
 // Function to fetch and display list of functions
-async fn list_functions(
-    client: &run::FunctionServiceClient,
-    username: &str,
-    token: &str,
-) -> anyhow::Result<()> {
+async fn list_functions(nodes: &[String], username: &str, token: &str) -> anyhow::Result<()> {
     // Create auth token (username:token format)
     let auth_token = format!("{username}:{token}");
 
     println!("Fetching functions for GitHub user: {username}...");
 
-    // Call the list_functions RPC
-    match client.list_functions(auth_token).await {
-        Ok(Ok(functions)) => {
-            if functions.is_empty() {
-                println!("\nNo functions deployed under this GitHub account.");
-                println!("Use 'cargo faasta deploy' to deploy a function.");
-                return Ok(());
-            }
-
-            // Print header
-            println!("\n╔══════════════════════════════════════════════════════");
-            println!("║ FUNCTIONS DEPLOYED BY {}", username.to_uppercase());
-            println!("╠══════════════════════════════════════════════════════");
-            println!("║ Total Functions: {}", functions.len());
-            println!("╠══════════════════════════════════════════════════════");
+    // Function name -> the function and the regions it is deployed to. Nodes without a
+    // region label are identified by their address.
+    let mut deployed: BTreeMap<String, (faasta_interface::FunctionInfo, Vec<String>)> =
+        BTreeMap::new();
+    let mut labelled = false;
+    for node in nodes {
+        let client = connect(node).await?;
+        let region = match client.server_info().await {
+            Ok(info) => info.region,
+            Err(_) => None,
+        };
+        labelled |= region.is_some();
+        let location = region.unwrap_or_else(|| node.clone());
+        let functions = client
+            .list_functions(auth_token.clone())
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Communication error with {node}: {}",
+                    run::describe_rpc_error(&e)
+                )
+            })?
+            .map_err(|e| anyhow::anyhow!("Server error from {node}: {:?}", e))?;
+        for function in functions {
+            deployed
+                .entry(function.name.clone())
+                .or_insert_with(|| (function, Vec::new()))
+                .1
+                .push(location.clone());
+        }
+    }
+    let show_regions = labelled || nodes.len() > 1;
 
-            // Print functions in alphabetical order
-            let mut sorted_functions = functions.clone();
-            sorted_functions.sort_by(|a, b| a.name.cmp(&b.name));
+    if deployed.is_empty() {
+        println!("\nNo functions deployed under this GitHub account.");
+        println!("Use 'cargo faasta deploy' to deploy a function.");
+        return Ok(());
+    }
 
-            for function in sorted_functions {
-                println!("║ Function: {}", function.name);
+    // Print header
+    println!("\n╔══════════════════════════════════════════════════════");
+    println!("║ FUNCTIONS DEPLOYED BY {}", username.to_uppercase());
+    println!("╠══════════════════════════════════════════════════════");
+    println!("║ Total Functions: {}", deployed.len());
+    println!("╠══════════════════════════════════════════════════════");
 
-                // Parse the published_at date for pretty formatting
-                println!("║ ├─ Published: {}", function.published_at);
+    // Functions are kept in alphabetical order
+    for (function, regions) in deployed.values() {
+        println!("║ Function: {}", function.name);
 
-                // URL
-                println!("║ ├─ URL: {}", function.usage);
+        // Parse the published_at date for pretty formatting
+        println!("║ ├─ Published: {}", function.published_at);
 
-                // Add a command to invoke it
-                println!("║ └─ Invoke: cargo faasta invoke {}", function.name);
-                println!("╟──────────────────────────────────────────────────────");
-            }
-            println!("╚══════════════════════════════════════════════════════");
+        // URL
+        println!("║ ├─ URL: {}", function.usage);
 
-            Ok(())
+        if show_regions {
+            println!("║ ├─ Regions: {}", regions.join(", "));
         }
-        Ok(Err(e)) => Err(anyhow::anyhow!("Server error: {:?}", e)),
-        Err(e) => Err(anyhow::anyhow!("Communication error: {}", e)),
+
+        // Add a command to invoke it
+        println!("║ └─ Invoke: cargo faasta invoke {}", function.name);
+        println!("╟──────────────────────────────────────────────────────");
     }
+    println!("╚══════════════════════════════════════════════════════");
+
+    Ok(())
 }
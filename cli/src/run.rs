@@ -1,9 +1,16 @@
 use anyhow::{Result, anyhow};
 use bitrpc::{RpcError, tokio::TokioHttpTransport};
-use faasta_interface::{FunctionResult, FunctionServiceRpcClient};
+use faasta_interface::{
+    ErrorBody, FunctionConfig, FunctionResult, FunctionServiceRpcClient, StaticAsset,
+    UPLOAD_CHUNK_SIZE,
+};
+use faasta_interface::{chunking, elevation, protocol, signing};
+use sha2::{Digest, Sha256};
 use std::io;
 use std::path::{Path as StdPath, PathBuf};
 use std::process::exit;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::debug;
 use url::Url;
 
@@ -16,38 +23,256 @@ fn same_file_path(a: &str, b: &str) -> bool {
     path_a == path_b
 }
 
+const DEFAULT_RPC_RETRIES: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(8);
+
 #[derive(Clone)]
 pub struct FunctionServiceClient {
     endpoint: String,
+    retries: u32,
+    signing_key: Option<Vec<u8>>,
+    elevation: Option<String>,
 }
 
 impl FunctionServiceClient {
     fn new(endpoint: String) -> Self {
-        Self { endpoint }
+        // FAASTA_RPC_RETRIES=0 disables retries
+        let retries = std::env::var("FAASTA_RPC_RETRIES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_RPC_RETRIES);
+        Self {
+            endpoint,
+            retries,
+            signing_key: None,
+            elevation: None,
+        }
+    }
+
+    /// Sign management calls with the key issued by `rotate_signing_key`.
+    pub fn with_signing_key(mut self, signing_key: Option<Vec<u8>>) -> Self {
+        self.signing_key = signing_key;
+        self
+    }
+
+    /// Prove a recent second factor on signed calls with a token from `elevate`.
+    pub fn with_elevation(mut self, elevation: Option<String>) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    /// Attach the elevation, if any, and a fresh signature to `token` if a signing key
+    /// is configured. Each attempt needs its own signature, since the server rejects a
+    /// nonce it has already seen.
+    fn sign(&self, token: &str, operation: &str, name: &str, payload: &[u8]) -> String {
+        let token = match &self.elevation {
+            Some(elevation) => elevation::attach(token, elevation),
+            None => token.to_string(),
+        };
+        let Some(key) = &self.signing_key else {
+            return token;
+        };
+        static NONCE_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let nonce = format!(
+            "{:x}{:x}{:x}",
+            now.as_nanos(),
+            std::process::id(),
+            NONCE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        signing::sign_token(&token, key, operation, name, payload, now.as_secs(), &nonce)
     }
 
     fn new_transport(&self) -> TokioHttpTransport {
         TokioHttpTransport::new(self.endpoint.clone())
     }
 
+    /// Run `call`, retrying with exponential backoff while it fails with a transient
+    /// transport error. Only use this for calls that are safe to repeat.
+    async fn with_retries<T>(
+        &self,
+        operation: &str,
+        mut call: impl AsyncFnMut(
+            &mut FunctionServiceRpcClient<TokioHttpTransport>,
+        ) -> Result<T, RpcError>,
+    ) -> Result<T, RpcError> {
+        let mut attempt = 0;
+        loop {
+            let mut client = FunctionServiceRpcClient::new(self.new_transport());
+            match call(&mut client).await {
+                Err(err) if attempt < self.retries && is_transient(&err) => {
+                    let delay = RETRY_BASE_DELAY
+                        .saturating_mul(1 << attempt.min(16))
+                        .min(RETRY_MAX_DELAY);
+                    attempt += 1;
+                    eprintln!(
+                        "{operation} failed ({err}); retrying in {:.1}s (attempt {attempt} of {})",
+                        delay.as_secs_f64(),
+                        self.retries
+                    );
+                    compio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Publish the artifact, sending only what changed since the deployed version when
+    /// that is small, and otherwise uploading it in resumable chunks.
     pub async fn publish(
         &self,
         wasm_file: Vec<u8>,
         name: String,
         github_auth_token: String,
     ) -> Result<FunctionResult<String>, RpcError> {
-        let mut client = FunctionServiceRpcClient::new(self.new_transport());
-        let response = client.publish(wasm_file, name, github_auth_token).await?;
-        Ok(response)
+        if let Some(Ok(message)) = self
+            .publish_delta(&wasm_file, &name, &github_auth_token)
+            .await?
+        {
+            return Ok(Ok(message));
+        }
+
+        let mut offset = 0;
+        loop {
+            let end = (offset + UPLOAD_CHUNK_SIZE).min(wasm_file.len());
+            let chunk = &wasm_file[offset..end];
+            let received = self
+                .with_retries("Upload", async |client| {
+                    let token = self.sign(
+                        &github_auth_token,
+                        signing::OP_UPLOAD_CHUNK,
+                        &name,
+                        &signing::chunk_payload(offset as u64, chunk),
+                    );
+                    client
+                        .upload_chunk(name.clone(), offset as u64, chunk.to_vec(), token)
+                        .await
+                })
+                .await?;
+            match received {
+                Ok(received) => offset = received as usize,
+                Err(err) => return Ok(Err(err)),
+            }
+            if offset >= wasm_file.len() {
+                break;
+            }
+        }
+
+        self.with_retries("Publish", async |client| {
+            let token = self.sign(&github_auth_token, signing::OP_PUBLISH_UPLOAD, &name, &[]);
+            client.publish_upload(name.clone(), token).await
+        })
+        .await
+    }
+
+    /// Publish the artifact as a delta against the deployed upload. `None` when there is
+    /// nothing to diff against or too much changed to be worth it. Any other failure is
+    /// left for the full upload to report.
+    async fn publish_delta(
+        &self,
+        wasm_file: &[u8],
+        name: &str,
+        github_auth_token: &str,
+    ) -> Result<Option<FunctionResult<String>>, RpcError> {
+        let base = match self
+            .with_retries("Fetching the deployed version", async |client| {
+                client
+                    .artifact_chunks(name.to_string(), github_auth_token.to_string())
+                    .await
+            })
+            .await?
+        {
+            Ok(base) => base,
+            Err(_) => return Ok(None),
+        };
+
+        let ops = chunking::diff(&base.chunks, wasm_file);
+        let sent = chunking::data_len(&ops);
+        if sent > UPLOAD_CHUNK_SIZE || sent > wasm_file.len() / 2 {
+            return Ok(None);
+        }
+        debug!("sending {sent} of {} bytes as a delta", wasm_file.len());
+        let sha256 = hex::encode(Sha256::digest(wasm_file));
+        let payload = signing::delta_payload(&base.sha256, &ops, &sha256);
+        // The server checks the base and result digests, so repeating it is harmless
+        let result = self
+            .with_retries("Publish", async |client| {
+                let token = self.sign(github_auth_token, signing::OP_PUBLISH_DELTA, name, &payload);
+                client
+                    .publish_delta(
+                        name.to_string(),
+                        base.sha256.clone(),
+                        ops.clone(),
+                        sha256.clone(),
+                        token,
+                    )
+                    .await
+            })
+            .await?;
+        Ok(Some(result))
+    }
+
+    pub async fn publish_static(
+        &self,
+        assets: Vec<StaticAsset>,
+        name: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<String>, RpcError> {
+        // A static publish replaces the whole site, so repeating it is harmless
+        let payload = signing::encoded_payload(&assets);
+        self.with_retries("Publish", async |client| {
+            let token = self.sign(
+                &github_auth_token,
+                signing::OP_PUBLISH_STATIC,
+                &name,
+                &payload,
+            );
+            client
+                .publish_static(assets.clone(), name.clone(), token)
+                .await
+        })
+        .await
+    }
+
+    pub async fn publish_oci(
+        &self,
+        reference: String,
+        name: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<String>, RpcError> {
+        // Publishing the same reference again installs the same component
+        self.with_retries("Publish", async |client| {
+            let token = self.sign(
+                &github_auth_token,
+                signing::OP_PUBLISH_OCI,
+                &name,
+                reference.as_bytes(),
+            );
+            client
+                .publish_oci(reference.clone(), name.clone(), token)
+                .await
+        })
+        .await
     }
 
     pub async fn list_functions(
         &self,
         github_auth_token: String,
     ) -> Result<FunctionResult<Vec<faasta_interface::FunctionInfo>>, RpcError> {
-        let mut client = FunctionServiceRpcClient::new(self.new_transport());
-        let response = client.list_functions(github_auth_token).await?;
-        Ok(response)
+        self.with_retries("List", async |client| {
+            client.list_functions(github_auth_token.clone()).await
+        })
+        .await
+    }
+
+    pub async fn server_info(&self) -> Result<faasta_interface::ServerInfo, RpcError> {
+        self.with_retries("Fetching server info", async |client| {
+            client.server_info().await
+        })
+        .await
     }
 
     pub async fn unpublish(
@@ -55,8 +280,9 @@ impl FunctionServiceClient {
         name: String,
         github_auth_token: String,
     ) -> Result<FunctionResult<()>, RpcError> {
+        let token = self.sign(&github_auth_token, signing::OP_UNPUBLISH, &name, &[]);
         let mut client = FunctionServiceRpcClient::new(self.new_transport());
-        let response = client.unpublish(name, github_auth_token).await?;
+        let response = client.unpublish(name, token).await?;
         Ok(response)
     }
 
@@ -64,12 +290,326 @@ impl FunctionServiceClient {
         &self,
         github_auth_token: String,
     ) -> Result<FunctionResult<faasta_interface::Metrics>, RpcError> {
+        self.with_retries("Metrics", async |client| {
+            client.get_metrics(github_auth_token.clone()).await
+        })
+        .await
+    }
+
+    pub async fn warm(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<u64>, RpcError> {
+        self.with_retries("Warming", async |client| {
+            client.warm(name.clone(), github_auth_token.clone()).await
+        })
+        .await
+    }
+
+    pub async fn job_status(
+        &self,
+        job_id: String,
+    ) -> Result<FunctionResult<faasta_interface::JobStatus>, RpcError> {
+        self.with_retries("Job status", async |client| {
+            client.job_status(job_id.clone()).await
+        })
+        .await
+    }
+
+    pub async fn list_jobs(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<Vec<faasta_interface::JobSummary>>, RpcError> {
+        self.with_retries("Listing jobs", async |client| {
+            client
+                .list_jobs(name.clone(), github_auth_token.clone())
+                .await
+        })
+        .await
+    }
+
+    pub async fn get_logs(
+        &self,
+        name: String,
+        since_millis: u64,
+        tail: u32,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<Vec<faasta_interface::LogEntry>>, RpcError> {
+        self.with_retries("Fetching logs", async |client| {
+            client
+                .get_logs(name.clone(), since_millis, tail, github_auth_token.clone())
+                .await
+        })
+        .await
+    }
+
+    pub async fn cancel_job(
+        &self,
+        job_id: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<()>, RpcError> {
+        let token = self.sign(&github_auth_token, signing::OP_CANCEL_JOB, &job_id, &[]);
+        let mut client = FunctionServiceRpcClient::new(self.new_transport());
+        let response = client.cancel_job(job_id, token).await?;
+        Ok(response)
+    }
+
+    pub async fn get_function_config(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<FunctionConfig>, RpcError> {
+        self.with_retries("Fetching settings", async |client| {
+            client
+                .get_function_config(name.clone(), github_auth_token.clone())
+                .await
+        })
+        .await
+    }
+
+    pub async fn set_function_config(
+        &self,
+        name: String,
+        config: FunctionConfig,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<()>, RpcError> {
+        // Settings are replaced wholesale, so repeating the call is harmless
+        let payload = signing::encoded_payload(&config);
+        self.with_retries("Saving settings", async |client| {
+            let token = self.sign(
+                &github_auth_token,
+                signing::OP_SET_FUNCTION_CONFIG,
+                &name,
+                &payload,
+            );
+            client
+                .set_function_config(name.clone(), config.clone(), token)
+                .await
+        })
+        .await
+    }
+
+    pub async fn get_kv_usage(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<faasta_interface::KvUsage>, RpcError> {
+        self.with_retries("Reading key-value usage", async |client| {
+            client
+                .get_kv_usage(name.clone(), github_auth_token.clone())
+                .await
+        })
+        .await
+    }
+
+    pub async fn list_kv_keys(
+        &self,
+        name: String,
+        bucket: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<Vec<faasta_interface::KvKey>>, RpcError> {
+        self.with_retries("Listing keys", async |client| {
+            client
+                .list_kv_keys(name.clone(), bucket.clone(), github_auth_token.clone())
+                .await
+        })
+        .await
+    }
+
+    pub async fn get_kv_value(
+        &self,
+        name: String,
+        bucket: String,
+        key: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<Option<Vec<u8>>>, RpcError> {
+        self.with_retries("Reading the value", async |client| {
+            client
+                .get_kv_value(
+                    name.clone(),
+                    bucket.clone(),
+                    key.clone(),
+                    github_auth_token.clone(),
+                )
+                .await
+        })
+        .await
+    }
+
+    pub async fn list_secrets(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<Vec<String>>, RpcError> {
+        self.with_retries("Listing secrets", async |client| {
+            client
+                .list_secrets(name.clone(), github_auth_token.clone())
+                .await
+        })
+        .await
+    }
+
+    pub async fn set_secret(
+        &self,
+        name: String,
+        key: String,
+        value: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<()>, RpcError> {
+        // Setting the same value again is harmless
+        let payload = signing::secret_payload(&key, &value);
+        self.with_retries("Saving secret", async |client| {
+            let token = self.sign(&github_auth_token, signing::OP_SET_SECRET, &name, &payload);
+            client
+                .set_secret(name.clone(), key.clone(), value.clone(), token)
+                .await
+        })
+        .await
+    }
+
+    pub async fn delete_secret(
+        &self,
+        name: String,
+        key: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<()>, RpcError> {
+        let token = self.sign(
+            &github_auth_token,
+            signing::OP_DELETE_SECRET,
+            &name,
+            key.as_bytes(),
+        );
         let mut client = FunctionServiceRpcClient::new(self.new_transport());
-        let response = client.get_metrics(github_auth_token).await?;
+        let response = client.delete_secret(name, key, token).await?;
+        Ok(response)
+    }
+
+    /// Ask the server for a new request-signing key for `username`.
+    pub async fn rotate_signing_key(
+        &self,
+        username: &str,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<String>, RpcError> {
+        let token = self.sign(
+            &github_auth_token,
+            signing::OP_ROTATE_SIGNING_KEY,
+            username,
+            &[],
+        );
+        let mut client = FunctionServiceRpcClient::new(self.new_transport());
+        let response = client.rotate_signing_key(token).await?;
+        Ok(response)
+    }
+
+    pub async fn list_sessions(
+        &self,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<Vec<faasta_interface::SessionInfo>>, RpcError> {
+        self.with_retries("Listing sessions", async |client| {
+            client.list_sessions(github_auth_token.clone()).await
+        })
+        .await
+    }
+
+    pub async fn revoke_session(
+        &self,
+        session_id: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<()>, RpcError> {
+        let token = self.sign(
+            &github_auth_token,
+            signing::OP_REVOKE_SESSION,
+            &session_id,
+            &[],
+        );
+        let mut client = FunctionServiceRpcClient::new(self.new_transport());
+        let response = client.revoke_session(session_id, token).await?;
+        Ok(response)
+    }
+
+    pub async fn setup_totp(
+        &self,
+        username: &str,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<String>, RpcError> {
+        let token = self.sign(&github_auth_token, signing::OP_SETUP_TOTP, username, &[]);
+        let mut client = FunctionServiceRpcClient::new(self.new_transport());
+        let response = client.setup_totp(token).await?;
+        Ok(response)
+    }
+
+    pub async fn confirm_totp(
+        &self,
+        code: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<()>, RpcError> {
+        let mut client = FunctionServiceRpcClient::new(self.new_transport());
+        let response = client.confirm_totp(code, github_auth_token).await?;
+        Ok(response)
+    }
+
+    /// Exchange a TOTP code for an elevation token. Codes are accepted once, so this is
+    /// never retried.
+    pub async fn elevate(
+        &self,
+        code: String,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<String>, RpcError> {
+        let mut client = FunctionServiceRpcClient::new(self.new_transport());
+        let response = client.elevate(code, github_auth_token).await?;
+        Ok(response)
+    }
+
+    pub async fn disable_totp(
+        &self,
+        username: &str,
+        github_auth_token: String,
+    ) -> Result<FunctionResult<()>, RpcError> {
+        let token = self.sign(&github_auth_token, signing::OP_DISABLE_TOTP, username, &[]);
+        let mut client = FunctionServiceRpcClient::new(self.new_transport());
+        let response = client.disable_totp(token).await?;
         Ok(response)
     }
 }
 
+/// Whether an RPC failure looks like a dropped connection or timeout rather than a
+/// rejected request.
+/// Describe a failed RPC call. Requests the server rejects before they reach the RPC
+/// service, e.g. from a source outside `MANAGEMENT_ALLOW`, carry an [`ErrorBody`] that
+/// the transport passes on in its error message.
+pub fn describe_rpc_error(err: &RpcError) -> String {
+    let message = err.to_string();
+    match parse_error_body(&message) {
+        Some(body) => body.to_string(),
+        None => message,
+    }
+}
+
+/// The server's error body, on its own or embedded in a longer message.
+pub fn parse_error_body(text: &str) -> Option<ErrorBody> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    serde_json::from_str(text.get(start..=end)?).ok()
+}
+
+fn is_transient(err: &RpcError) -> bool {
+    let message = err.to_string().to_ascii_lowercase();
+    [
+        "connection reset",
+        "connection refused",
+        "connection aborted",
+        "connection closed",
+        "broken pipe",
+        "unexpected eof",
+        "timed out",
+        "timeout",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
 fn normalize_endpoint(server_addr: &str) -> Result<String> {
     let trimmed = server_addr.trim();
     if trimmed.is_empty() {
@@ -84,7 +624,8 @@ fn normalize_endpoint(server_addr: &str) -> Result<String> {
             .map_err(|e| anyhow!("Invalid server address '{trimmed}': {e}"))?
     };
 
-    if url.scheme() != "https" {
+    // Plain HTTP only reaches a management listener on this machine
+    if url.scheme() != "https" && !(url.scheme() == "http" && is_loopback(&url)) {
         url.set_scheme("https")
             .map_err(|_| anyhow!("Server address must use HTTPS"))?;
     }
@@ -92,10 +633,23 @@ fn normalize_endpoint(server_addr: &str) -> Result<String> {
     if url.path() == "/" {
         url.set_path("/rpc");
     }
+    url.query_pairs_mut().append_pair(
+        protocol::PROTOCOL_PARAM,
+        &protocol::PROTOCOL_VERSION.to_string(),
+    );
 
     Ok(url.to_string())
 }
 
+fn is_loopback(url: &Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
+
 // Create a connection to the function service
 pub async fn connect_to_function_service(server_addr: &str) -> Result<FunctionServiceClient> {
     let endpoint = normalize_endpoint(server_addr)?;
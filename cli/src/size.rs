@@ -0,0 +1,201 @@
+//! Size breakdown of a built component for `cargo faasta size`: bytes per section kind
+//! across the component and its core modules, per core module, and per function body,
+//! named from the modules' `name` sections when they have one.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use wasmparser::{Encoding, KnownCustom, Name, Parser, Payload, TypeRef};
+
+/// One line of a breakdown
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub total: u64,
+    /// Largest first
+    pub sections: Vec<Entry>,
+    /// Core modules in the order they appear
+    pub modules: Vec<Entry>,
+    /// Function bodies, largest first
+    pub functions: Vec<Entry>,
+}
+
+/// Function bodies of the core module being parsed, until its names are known
+#[derive(Default)]
+struct Module {
+    index: usize,
+    imported_functions: u32,
+    bodies: Vec<u64>,
+    names: BTreeMap<u32, String>,
+}
+
+pub fn analyze(component: &[u8]) -> Result<Report> {
+    let mut sections: BTreeMap<String, u64> = BTreeMap::new();
+    let mut report = Report {
+        total: component.len() as u64,
+        ..Report::default()
+    };
+    // Modules and components nest, so keep what each level has seen so far
+    let mut stack: Vec<Option<Module>> = vec![None];
+
+    for payload in Parser::new(0).parse_all(component) {
+        let payload = payload.context("malformed component")?;
+        if let Some((_, range)) = payload.as_section()
+            && let Some(kind) = section_kind(&payload)
+        {
+            *sections.entry(kind).or_default() += range.len() as u64;
+        }
+        match payload {
+            // A plain core module rather than a component
+            Payload::Version {
+                encoding: Encoding::Module,
+                ..
+            } if stack.len() == 1 => {
+                stack[0] = Some(Module::default());
+                report.modules.push(Entry {
+                    name: "module 0".to_string(),
+                    bytes: report.total,
+                });
+            }
+            Payload::ModuleSection {
+                unchecked_range, ..
+            } => {
+                stack.push(Some(Module {
+                    index: report.modules.len(),
+                    ..Module::default()
+                }));
+                report.modules.push(Entry {
+                    name: format!("module {}", report.modules.len()),
+                    bytes: unchecked_range.len() as u64,
+                });
+            }
+            Payload::ComponentSection { .. } => stack.push(None),
+            Payload::ImportSection(reader) => {
+                if let Some(Some(module)) = stack.last_mut() {
+                    for import in reader.into_imports() {
+                        if matches!(import?.ty, TypeRef::Func(_)) {
+                            module.imported_functions += 1;
+                        }
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                if let Some(Some(module)) = stack.last_mut() {
+                    module.bodies.push(body.range().len() as u64);
+                }
+            }
+            Payload::CustomSection(reader) => {
+                if let (Some(Some(module)), KnownCustom::Name(names)) =
+                    (stack.last_mut(), reader.as_known())
+                {
+                    // Names are a debugging aid, so a malformed section just goes unused
+                    for name in names.into_iter().flatten() {
+                        if let Name::Function(map) = name {
+                            for naming in map.into_iter().flatten() {
+                                module.names.insert(naming.index, naming.name.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            Payload::End(_) => {
+                if let Some(Some(module)) = stack.pop() {
+                    for (position, bytes) in module.bodies.into_iter().enumerate() {
+                        let index = module.imported_functions + position as u32;
+                        let name = module
+                            .names
+                            .get(&index)
+                            .cloned()
+                            .unwrap_or_else(|| format!("function[{index}]"));
+                        report.functions.push(Entry {
+                            name: format!("{name} (module {})", module.index),
+                            bytes,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    report.sections = sections
+        .into_iter()
+        .map(|(name, bytes)| Entry { name, bytes })
+        .collect();
+    report
+        .sections
+        .sort_by_key(|entry| std::cmp::Reverse(entry.bytes));
+    report
+        .functions
+        .sort_by_key(|entry| std::cmp::Reverse(entry.bytes));
+    Ok(report)
+}
+
+/// What a section is reported as; `None` for payloads that aren't sections or that
+/// only contain other sections, which are counted on their own.
+fn section_kind(payload: &Payload) -> Option<String> {
+    let kind = match payload {
+        Payload::TypeSection(_) => "type",
+        Payload::ImportSection(_) => "import",
+        Payload::FunctionSection(_) => "function",
+        Payload::TableSection(_) => "table",
+        Payload::MemorySection(_) => "memory",
+        Payload::TagSection(_) => "tag",
+        Payload::GlobalSection(_) => "global",
+        Payload::ExportSection(_) => "export",
+        Payload::StartSection { .. } => "start",
+        Payload::ElementSection(_) => "element",
+        Payload::DataCountSection { .. } => "data count",
+        Payload::DataSection(_) => "data",
+        Payload::CodeSectionStart { .. } => "code",
+        Payload::InstanceSection(_) => "core instance",
+        Payload::CoreTypeSection(_) => "core type",
+        Payload::ComponentInstanceSection(_) => "component instance",
+        Payload::ComponentAliasSection(_) => "component alias",
+        Payload::ComponentTypeSection(_) => "component type",
+        Payload::ComponentCanonicalSection(_) => "canonical function",
+        Payload::ComponentStartSection { .. } => "component start",
+        Payload::ComponentImportSection(_) => "component import",
+        Payload::ComponentExportSection(_) => "component export",
+        Payload::CustomSection(reader) => return Some(format!("custom \"{}\"", reader.name())),
+        Payload::UnknownSection { .. } => "unknown",
+        _ => return None,
+    };
+    Some(kind.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaks_down_sections_and_functions() {
+        let module = wat::parse_str(
+            r#"(module
+                (import "env" "log" (func $log (param i32)))
+                (func $small)
+                (func $large (result i32)
+                    i32.const 1 i32.const 2 i32.add
+                    i32.const 3 i32.add i32.const 4 i32.add)
+                (data (i32.const 0) "0123456789")
+                (memory 1))"#,
+        )
+        .unwrap();
+        let report = analyze(&module).unwrap();
+
+        assert_eq!(report.total, module.len() as u64);
+        let names: Vec<_> = report.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["large (module 0)", "small (module 0)"]);
+        assert!(report.functions[0].bytes > report.functions[1].bytes);
+        let kinds: Vec<_> = report.sections.iter().map(|s| s.name.as_str()).collect();
+        for kind in ["code", "data", "import", "custom \"name\""] {
+            assert!(kinds.contains(&kind), "{kinds:?}");
+        }
+        assert_eq!(report.modules.len(), 1);
+        assert!(analyze(b"\0asm garbage").is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use faasta::fs::Files;
+use faasta::http::{Raw, Request, ResponseWithStatus};
+use serde_json::json;
+
+const UPLOADS: &str = "uploads";
+
+/// `PUT /files/NAME` stores the body, `GET /files` lists the stored files,
+/// `GET /files/NAME` downloads one and `DELETE /files/NAME` removes it.
+#[faasta::handler]
+async fn handle(request: Request, files: Files) -> faasta::Result<ResponseWithStatus<Raw>> {
+    // The function may be mounted below a prefix, so look at the last two segments
+    let path = request.path.split('?').next().unwrap_or("/");
+    let mut segments = path.trim_end_matches('/').rsplit('/');
+    let name = match (segments.next(), segments.next()) {
+        (Some("files"), _) => "",
+        (Some(name), Some("files")) => name,
+        _ => return Ok(error(404, "use /files or /files/NAME")),
+    };
+
+    if name.is_empty() {
+        return match request.method.as_str() {
+            "GET" => {
+                let entries = files.list(UPLOADS).await?;
+                let listed: Vec<_> = entries
+                    .iter()
+                    .filter(|entry| !entry.is_dir)
+                    .map(|entry| json!({ "name": entry.name, "size": entry.size }))
+                    .collect();
+                Ok(json_reply(200, json!({ "files": listed })))
+            }
+            _ => Ok(error(405, "use GET /files or /files/NAME")),
+        };
+    }
+    if name.contains('/') || name.starts_with('.') {
+        return Ok(error(
+            400,
+            "file names cannot contain '/' or start with '.'",
+        ));
+    }
+    let stored = format!("{UPLOADS}/{name}");
+
+    match request.method.as_str() {
+        "PUT" | "POST" => {
+            files.write(&stored, &request.body).await?;
+            Ok(json_reply(
+                201,
+                json!({ "name": name, "size": request.body.len() }),
+            ))
+        }
+        "GET" => match files.read(&stored).await? {
+            Some(data) => {
+                let content_type = request
+                    .header("accept")
+                    .filter(|accept| accept.starts_with("text/"))
+                    .unwrap_or("application/octet-stream")
+                    .to_string();
+                Ok(Raw::new(content_type, data).with_status(200))
+            }
+            None => Ok(error(404, "no such file")),
+        },
+        "DELETE" if files.delete(&stored).await? => Ok(json_reply(200, json!({ "deleted": name }))),
+        "DELETE" => Ok(error(404, "no such file")),
+        _ => Ok(error(405, "use GET, PUT or DELETE")),
+    }
+}
+
+fn json_reply(status: u16, value: serde_json::Value) -> ResponseWithStatus<Raw> {
+    Raw::new("application/json", value.to_string()).with_status(status)
+}
+
+fn error(status: u16, message: &str) -> ResponseWithStatus<Raw> {
+    json_reply(status, json!({ "error": message }))
+}
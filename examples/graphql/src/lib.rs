@@ -0,0 +1,68 @@
+use async_graphql::http::parse_query_string;
+use async_graphql::{
+    EmptyMutation, EmptySubscription, ID, Object, Schema, ServerError, SimpleObject,
+};
+use faasta::http::{Json, Request};
+
+#[derive(Clone, SimpleObject)]
+struct Book {
+    id: ID,
+    title: String,
+    author: String,
+}
+
+struct Query;
+
+#[Object]
+impl Query {
+    /// A greeting, to check the endpoint is up
+    async fn hello(&self, name: Option<String>) -> String {
+        format!("Hello, {}!", name.as_deref().unwrap_or("world"))
+    }
+
+    async fn books(&self) -> Vec<Book> {
+        library()
+    }
+
+    async fn book(&self, id: ID) -> Option<Book> {
+        library().into_iter().find(|book| book.id == id)
+    }
+}
+
+fn library() -> Vec<Book> {
+    [
+        (
+            "1",
+            "The Rust Programming Language",
+            "Steve Klabnik and Carol Nichols",
+        ),
+        ("2", "Zero To Production In Rust", "Luca Palmieri"),
+        ("3", "Rust for Rustaceans", "Jon Gjengset"),
+    ]
+    .into_iter()
+    .map(|(id, title, author)| Book {
+        id: ID::from(id),
+        title: title.to_string(),
+        author: author.to_string(),
+    })
+    .collect()
+}
+
+#[faasta::handler]
+async fn handle(request: Request) -> faasta::Result<Json<async_graphql::Response>> {
+    let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+
+    // The host has already turned `application/graphql` bodies into JSON and filled in
+    // persisted queries, so only the two standard encodings are left to parse.
+    let parsed = match request.method.as_str() {
+        "GET" => {
+            parse_query_string(request.query().unwrap_or_default()).map_err(|err| err.to_string())
+        }
+        _ => request.json().map_err(|err| err.to_string()),
+    };
+    let response = match parsed {
+        Ok(graphql_request) => schema.execute(graphql_request).await,
+        Err(message) => async_graphql::Response::from_errors(vec![ServerError::new(message, None)]),
+    };
+    Ok(Json(response))
+}
@@ -0,0 +1,48 @@
+//! Deployment identity of the running function. The platform sets these variables on
+//! every invocation; outside Faasta (e.g. `cargo faasta run`) they may be missing.
+
+use crate::env::var;
+
+pub const FUNCTION_VAR: &str = "FAASTA_FUNCTION";
+pub const VERSION_VAR: &str = "FAASTA_VERSION";
+pub const DEPLOYMENT_ID_VAR: &str = "FAASTA_DEPLOYMENT_ID";
+pub const REGION_VAR: &str = "FAASTA_REGION";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Context {
+    /// Name the function was published under
+    pub function: Option<String>,
+    /// Identifies the uploaded component; the same upload keeps the same version
+    pub version: Option<String>,
+    /// Changes on every publish, even of the same component
+    pub deployment_id: Option<String>,
+    /// Region of the node serving the request, if the operator set one
+    pub region: Option<String>,
+}
+
+impl Context {
+    pub fn current() -> Self {
+        Self {
+            function: var(FUNCTION_VAR),
+            version: var(VERSION_VAR),
+            deployment_id: var(DEPLOYMENT_ID_VAR),
+            region: var(REGION_VAR),
+        }
+    }
+}
+
+pub fn function_name() -> Option<String> {
+    var(FUNCTION_VAR)
+}
+
+pub fn version() -> Option<String> {
+    var(VERSION_VAR)
+}
+
+pub fn deployment_id() -> Option<String> {
+    var(DEPLOYMENT_ID_VAR)
+}
+
+pub fn region() -> Option<String> {
+    var(REGION_VAR)
+}
@@ -0,0 +1,33 @@
+//! Environment of the running function: secrets set with `cargo faasta secrets`,
+//! plus the platform's own `FAASTA_*` variables (see [`crate::context`]).
+
+use anyhow::{Context, anyhow};
+
+/// The variable's value, or `None` if it is unset, empty or not UTF-8.
+pub fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// The variable's value, failing with an error naming it when it is missing.
+pub fn require(name: &str) -> crate::Result<String> {
+    var(name).ok_or_else(|| {
+        anyhow!(
+            "{name} is not set; add it with `cargo faasta secrets <function> set {name} <value>`"
+        )
+    })
+}
+
+/// The variable parsed as `T`, or `None` if it is unset.
+pub fn parse<T>(name: &str) -> crate::Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    var(name)
+        .map(|value| {
+            value
+                .parse()
+                .with_context(|| format!("{name} is not valid"))
+        })
+        .transpose()
+}
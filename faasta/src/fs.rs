@@ -0,0 +1,154 @@
+//! Files in the function's sandbox directory, mounted in the guest at [`ROOT`]. The
+//! directory belongs to the function alone and outlives requests, but it lives on the
+//! node serving them; keep files every node must see in `faasta::blob`.
+
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+/// Where the sandbox is mounted
+pub const ROOT: &str = "/data";
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Files;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+impl Files {
+    /// Absolute path of `name`, which must be relative and stay inside the sandbox.
+    pub fn path(&self, name: &str) -> crate::Result<PathBuf> {
+        resolve(Path::new(ROOT), name)
+    }
+
+    /// Contents of the file, or `None` if it doesn't exist.
+    pub async fn read(&self, name: &str) -> crate::Result<Option<Vec<u8>>> {
+        read(Path::new(ROOT), name)
+    }
+
+    /// Create or replace the file, creating missing parent directories.
+    pub async fn write(&self, name: &str, data: impl AsRef<[u8]>) -> crate::Result<()> {
+        write(Path::new(ROOT), name, data.as_ref())
+    }
+
+    /// Entries of a directory sorted by name; `""` lists the sandbox itself. A missing
+    /// directory has no entries.
+    pub async fn list(&self, dir: &str) -> crate::Result<Vec<Entry>> {
+        list(Path::new(ROOT), dir)
+    }
+
+    /// Remove the file. Returns whether it existed.
+    pub async fn delete(&self, name: &str) -> crate::Result<bool> {
+        delete(Path::new(ROOT), name)
+    }
+}
+
+fn resolve(root: &Path, name: &str) -> crate::Result<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => bail!("'{name}' must be a relative path inside the sandbox"),
+        }
+    }
+    Ok(path)
+}
+
+fn read(root: &Path, name: &str) -> crate::Result<Option<Vec<u8>>> {
+    match std::fs::read(resolve(root, name)?) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to read '{name}'")),
+    }
+}
+
+fn write(root: &Path, name: &str, data: &[u8]) -> crate::Result<()> {
+    let path = resolve(root, name)?;
+    if path == root {
+        bail!("a file name is required");
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create the directory of '{name}'"))?;
+    }
+    std::fs::write(&path, data).with_context(|| format!("failed to write '{name}'"))
+}
+
+fn list(root: &Path, dir: &str) -> crate::Result<Vec<Entry>> {
+    let entries = match std::fs::read_dir(resolve(root, dir)?) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to list '{dir}'")),
+    };
+    let mut listed = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to list '{dir}'"))?;
+        let metadata = entry.metadata()?;
+        listed.push(Entry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            size: metadata.len(),
+            is_dir: metadata.is_dir(),
+        });
+    }
+    listed.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(listed)
+}
+
+fn delete(root: &Path, name: &str) -> crate::Result<bool> {
+    match std::fs::remove_file(resolve(root, name)?) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err).with_context(|| format!("failed to delete '{name}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("faasta-fs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn round_trips_files() {
+        let root = temp_root("round-trip");
+        write(&root, "uploads/a.txt", b"hello").unwrap();
+        write(&root, "uploads/b.txt", b"hi").unwrap();
+        assert_eq!(read(&root, "uploads/a.txt").unwrap().unwrap(), b"hello");
+        assert_eq!(read(&root, "missing").unwrap(), None);
+
+        let entries = list(&root, "uploads").unwrap();
+        let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+        assert_eq!(entries[0].size, 5);
+        assert_eq!(list(&root, "").unwrap()[0].name, "uploads");
+        assert!(list(&root, "nothing-here").unwrap().is_empty());
+
+        assert!(delete(&root, "uploads/a.txt").unwrap());
+        assert!(!delete(&root, "uploads/a.txt").unwrap());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn stays_inside_the_sandbox() {
+        let root = temp_root("escape");
+        for name in ["../escape.txt", "uploads/../../escape.txt", "/etc/passwd"] {
+            assert!(write(&root, name, b"x").is_err(), "{name} was accepted");
+            assert!(read(&root, name).is_err(), "{name} was accepted");
+        }
+        assert!(!root.parent().unwrap().join("escape.txt").exists());
+        assert!(write(&root, "", b"x").is_err());
+        assert_eq!(resolve(&root, "./a/./b").unwrap(), root.join("a").join("b"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,14 +1,213 @@
+use std::time::{Duration, Instant};
+
 use serde::Serialize;
-use wasip3::http::types::{ErrorCode, Fields, Response};
+use serde::de::DeserializeOwned;
+use wasip3::http::types::{ErrorCode, Fields, Method, Response};
 use wasip3::{wit_bindgen, wit_future, wit_stream};
 
+/// The incoming request, with its body read into memory. Take it as a handler
+/// argument to see what the caller sent.
+#[derive(Clone, Debug, Default)]
+pub struct Request {
+    pub method: String,
+    /// Path and query, e.g. `/graphql?query=...`
+    pub path: String,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    #[doc(hidden)]
+    pub async fn from_wasi(request: wasip3::http::types::Request) -> Self {
+        let method = match request.get_method() {
+            Method::Get => "GET".to_string(),
+            Method::Head => "HEAD".to_string(),
+            Method::Post => "POST".to_string(),
+            Method::Put => "PUT".to_string(),
+            Method::Delete => "DELETE".to_string(),
+            Method::Connect => "CONNECT".to_string(),
+            Method::Options => "OPTIONS".to_string(),
+            Method::Trace => "TRACE".to_string(),
+            Method::Patch => "PATCH".to_string(),
+            Method::Other(method) => method,
+        };
+        let path = request
+            .get_path_with_query()
+            .unwrap_or_else(|| "/".to_string());
+        let headers = request.get_headers().copy_all();
+        let (_, body_result_rx) = wit_future::new(|| Ok(()));
+        let (body, _trailers) = wasip3::http::types::Request::consume_body(request, body_result_rx);
+        let body = body.collect().await;
+        Self {
+            method,
+            path,
+            headers,
+            body,
+        }
+    }
+
+    /// First value of the header, if it is valid UTF-8. Names are case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, value)| std::str::from_utf8(value).ok())
+    }
+
+    /// The query string, without the leading `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.path.split_once('?').map(|(_, query)| query)
+    }
+
+    pub fn text(&self) -> crate::Result<&str> {
+        Ok(std::str::from_utf8(&self.body)?)
+    }
+
+    pub fn json<T>(&self) -> crate::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
 pub struct Html<T>(pub T);
 pub struct Json<T>(pub T);
 
+/// A body of any content type, e.g. a file download.
+pub struct Raw {
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
 pub trait IntoResponse {
     fn into_response(self) -> Result<Response, ErrorCode>;
 }
 
+/// A response before it is handed to the host. Every body type converts into one, so
+/// [`ResponseExt`] can add headers and trailers to any of them.
+#[derive(Clone, Debug)]
+pub struct Parts {
+    pub status: u16,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+    /// Sent after the body
+    pub trailers: Vec<(String, Vec<u8>)>,
+}
+
+pub trait IntoParts {
+    fn into_parts(self) -> Result<Parts, ErrorCode>;
+}
+
+/// Headers, trailers and timings added to a response. `use faasta::prelude::*` brings
+/// these methods into scope.
+pub trait ResponseExt: IntoParts + Sized {
+    fn with_header(self, name: impl Into<String>, value: impl Into<Vec<u8>>) -> WithMetadata<Self> {
+        WithMetadata::new(self).with_header(name, value)
+    }
+
+    fn with_trailer(
+        self,
+        name: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+    ) -> WithMetadata<Self> {
+        WithMetadata::new(self).with_trailer(name, value)
+    }
+
+    /// Report `timing` in a `Server-Timing` header. The platform also adds the
+    /// durations to the function's metrics.
+    fn with_server_timing(self, timing: &ServerTiming) -> WithMetadata<Self> {
+        WithMetadata::new(self).with_server_timing(timing)
+    }
+}
+
+impl<T: IntoParts> ResponseExt for T {}
+
+/// A response with extra headers or trailers, from [`ResponseExt`].
+pub struct WithMetadata<T> {
+    response: T,
+    headers: Vec<(String, Vec<u8>)>,
+    trailers: Vec<(String, Vec<u8>)>,
+}
+
+impl<T> WithMetadata<T> {
+    fn new(response: T) -> Self {
+        Self {
+            response,
+            headers: Vec::new(),
+            trailers: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_trailer(mut self, name: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        self.trailers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_server_timing(self, timing: &ServerTiming) -> Self {
+        if timing.is_empty() {
+            return self;
+        }
+        self.with_header(SERVER_TIMING, timing.to_string())
+    }
+}
+
+pub const SERVER_TIMING: &str = "server-timing";
+
+/// Durations of the steps a handler took, sent as `Server-Timing`.
+///
+/// ```ignore
+/// let mut timing = ServerTiming::new();
+/// let rows = timing.measure("db", sql.query("SELECT * FROM posts", ())).await?;
+/// Ok(Json(rows).with_server_timing(&timing))
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ServerTiming {
+    entries: Vec<(String, f64)>,
+}
+
+impl ServerTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the step `name` took `duration`. `name` should be a short token
+    /// such as `db` or `render`.
+    pub fn record(&mut self, name: impl Into<String>, duration: Duration) {
+        self.entries
+            .push((name.into(), duration.as_secs_f64() * 1000.0));
+    }
+
+    /// Await `future`, recording how long it took as `name`.
+    pub async fn measure<F: Future>(&mut self, name: impl Into<String>, future: F) -> F::Output {
+        let start = Instant::now();
+        let output = future.await;
+        self.record(name, start.elapsed());
+        output
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl std::fmt::Display for ServerTiming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, (name, duration_ms)) in self.entries.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{name};dur={duration_ms:.3}")?;
+        }
+        Ok(())
+    }
+}
+
 impl<T> Html<T> {
     pub fn with_status(self, status: u16) -> ResponseWithStatus<Self> {
         ResponseWithStatus {
@@ -27,48 +226,77 @@ impl<T> Json<T> {
     }
 }
 
+impl Raw {
+    pub fn new(content_type: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            content_type: content_type.into(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_status(self, status: u16) -> ResponseWithStatus<Self> {
+        ResponseWithStatus {
+            status,
+            response: self,
+        }
+    }
+}
+
 pub struct ResponseWithStatus<T> {
     status: u16,
     response: T,
 }
 
-impl<T> IntoResponse for Html<T>
+impl<T> IntoParts for Html<T>
 where
     T: Into<String>,
 {
-    fn into_response(self) -> Result<Response, ErrorCode> {
-        body_response(200, "text/html; charset=utf-8", self.0.into().into_bytes())
+    fn into_parts(self) -> Result<Parts, ErrorCode> {
+        Ok(body_parts(
+            "text/html; charset=utf-8",
+            self.0.into().into_bytes(),
+        ))
     }
 }
 
-impl<T> IntoResponse for Json<T>
+impl<T> IntoParts for Json<T>
 where
     T: Serialize,
 {
-    fn into_response(self) -> Result<Response, ErrorCode> {
-        json_response(200, &self.0)
+    fn into_parts(self) -> Result<Parts, ErrorCode> {
+        let body = serde_json::to_vec(&self.0).map_err(|err| {
+            ErrorCode::InternalError(Some(format!("serializing response: {err}")))
+        })?;
+        Ok(body_parts("application/json", body))
     }
 }
 
-impl<T> IntoResponse for ResponseWithStatus<Html<T>>
-where
-    T: Into<String>,
-{
-    fn into_response(self) -> Result<Response, ErrorCode> {
-        body_response(
-            self.status,
-            "text/html; charset=utf-8",
-            self.response.0.into().into_bytes(),
-        )
+impl IntoParts for Raw {
+    fn into_parts(self) -> Result<Parts, ErrorCode> {
+        Ok(body_parts(&self.content_type, self.body))
     }
 }
 
-impl<T> IntoResponse for ResponseWithStatus<Json<T>>
-where
-    T: Serialize,
-{
+impl<T: IntoParts> IntoParts for ResponseWithStatus<T> {
+    fn into_parts(self) -> Result<Parts, ErrorCode> {
+        let mut parts = self.response.into_parts()?;
+        parts.status = self.status;
+        Ok(parts)
+    }
+}
+
+impl<T: IntoParts> IntoParts for WithMetadata<T> {
+    fn into_parts(self) -> Result<Parts, ErrorCode> {
+        let mut parts = self.response.into_parts()?;
+        parts.headers.extend(self.headers);
+        parts.trailers.extend(self.trailers);
+        Ok(parts)
+    }
+}
+
+impl<T: IntoParts> IntoResponse for T {
     fn into_response(self) -> Result<Response, ErrorCode> {
-        json_response(self.status, &self.response.0)
+        send(self.into_parts()?)
     }
 }
 
@@ -83,31 +311,58 @@ pub fn json_response<T>(status: u16, value: &T) -> Result<Response, ErrorCode>
 where
     T: Serialize,
 {
-    let body = serde_json::to_vec(value)
-        .map_err(|err| ErrorCode::InternalError(Some(format!("serializing response: {err}"))))?;
-    body_response(status, "application/json", body)
+    Json(value).with_status(status).into_response()
 }
 
-fn body_response(status: u16, content_type: &str, body: Vec<u8>) -> Result<Response, ErrorCode> {
-    let headers = Fields::new();
-    headers
-        .set("content-type", &[content_type.as_bytes().to_vec()])
-        .map_err(|err| ErrorCode::InternalError(Some(format!("setting header: {err:?}"))))?;
+fn body_parts(content_type: &str, body: Vec<u8>) -> Parts {
+    Parts {
+        status: 200,
+        headers: vec![("content-type".to_string(), content_type.as_bytes().to_vec())],
+        body,
+        trailers: Vec::new(),
+    }
+}
+
+fn fields(entries: &[(String, Vec<u8>)]) -> Result<Fields, ErrorCode> {
+    let fields = Fields::new();
+    for (name, value) in entries {
+        fields
+            .append(name, value)
+            .map_err(|err| ErrorCode::InternalError(Some(format!("setting header: {err:?}"))))?;
+    }
+    Ok(fields)
+}
+
+fn send(parts: Parts) -> Result<Response, ErrorCode> {
+    let headers = fields(&parts.headers)?;
     headers
-        .set("content-length", &[body.len().to_string().into_bytes()])
+        .set(
+            "content-length",
+            &[parts.body.len().to_string().into_bytes()],
+        )
         .map_err(|err| ErrorCode::InternalError(Some(format!("setting header: {err:?}"))))?;
+    let trailers = if parts.trailers.is_empty() {
+        None
+    } else {
+        Some(fields(&parts.trailers)?)
+    };
 
     let (mut body_tx, body_rx) = wit_stream::new();
     let (body_result_tx, body_result_rx) = wit_future::new(|| Ok(None));
     let (response, _response_result) = Response::new(headers, Some(body_rx), body_result_rx);
     response
-        .set_status_code(status)
+        .set_status_code(parts.status)
         .map_err(|()| ErrorCode::InternalError(Some("setting status code".to_string())))?;
-    drop(body_result_tx);
 
+    let body = parts.body;
     wit_bindgen::spawn(async move {
         let remaining = body_tx.write_all(body).await;
         assert!(remaining.is_empty());
+        drop(body_tx);
+        // Trailers follow the body; without any the default `Ok(None)` is sent
+        if let Some(trailers) = trailers {
+            let _ = body_result_tx.write(Ok(Some(trailers))).await;
+        }
     });
 
     Ok(response)
@@ -0,0 +1,257 @@
+//! Image transforms run by the Faasta host. Decoding and encoding happen natively
+//! outside the guest, so functions don't need to compile in image codecs.
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Images;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Jpeg,
+    Png,
+    Webp,
+    Gif,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Jpeg => "image/jpeg",
+            Format::Png => "image/png",
+            Format::Webp => "image/webp",
+            Format::Gif => "image/gif",
+        }
+    }
+}
+
+/// How a resize to both a width and a height treats the aspect ratio.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fit {
+    /// Fit inside the box, keeping the aspect ratio
+    #[default]
+    Contain,
+    /// Fill the box, keeping the aspect ratio and cropping the overflow
+    Cover,
+    /// Stretch to exactly the box
+    Fill,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: Format,
+}
+
+/// Operations applied in order, then the output encoding.
+#[derive(Clone, Debug, Default)]
+pub struct Transform {
+    operations: Vec<Operation>,
+    format: Option<Format>,
+    quality: Option<u8>,
+}
+
+/// Only the guest reads the fields, when it hands the operations to the host
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+enum Operation {
+    Resize {
+        width: Option<u32>,
+        height: Option<u32>,
+        fit: Fit,
+    },
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Grayscale,
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resize to the box; a missing side follows the aspect ratio.
+    pub fn resize(self, width: Option<u32>, height: Option<u32>, fit: Fit) -> Self {
+        self.then(Operation::Resize { width, height, fit })
+    }
+
+    /// Scale to `width`, keeping the aspect ratio.
+    pub fn width(self, width: u32) -> Self {
+        self.resize(Some(width), None, Fit::Contain)
+    }
+
+    /// Scale to `height`, keeping the aspect ratio.
+    pub fn height(self, height: u32) -> Self {
+        self.resize(None, Some(height), Fit::Contain)
+    }
+
+    pub fn crop(self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        self.then(Operation::Crop {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Rotate clockwise by 90 degrees.
+    pub fn rotate90(self) -> Self {
+        self.then(Operation::Rotate90)
+    }
+
+    pub fn rotate180(self) -> Self {
+        self.then(Operation::Rotate180)
+    }
+
+    pub fn rotate270(self) -> Self {
+        self.then(Operation::Rotate270)
+    }
+
+    pub fn flip_horizontal(self) -> Self {
+        self.then(Operation::FlipHorizontal)
+    }
+
+    pub fn flip_vertical(self) -> Self {
+        self.then(Operation::FlipVertical)
+    }
+
+    pub fn grayscale(self) -> Self {
+        self.then(Operation::Grayscale)
+    }
+
+    /// Encode the result as `format` instead of the input's format.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// JPEG quality from 1 to 100; the host defaults to 80.
+    pub fn quality(mut self, quality: u8) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    fn then(mut self, operation: Operation) -> Self {
+        self.operations.push(operation);
+        self
+    }
+}
+
+impl Images {
+    /// Apply `transform` to an encoded image and return the encoded result.
+    pub async fn transform(&self, image: &[u8], transform: &Transform) -> crate::Result<Vec<u8>> {
+        apply(image, transform).await
+    }
+
+    /// Dimensions and format of an encoded image.
+    pub async fn info(&self, image: &[u8]) -> crate::Result<ImageInfo> {
+        info(image).await
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[allow(unsafe_code)]
+mod bindings {
+    wasip3::wit_bindgen::generate!({
+        path: "wit",
+        world: "faasta:image/imports",
+        runtime_path: "wasip3::wit_bindgen::rt",
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+use bindings::faasta::image::transform as host;
+
+#[cfg(target_arch = "wasm32")]
+async fn apply(image: &[u8], transform: &Transform) -> crate::Result<Vec<u8>> {
+    let options = host::Options {
+        operations: transform
+            .operations
+            .iter()
+            .map(|operation| match *operation {
+                Operation::Resize { width, height, fit } => host::Operation::Resize(host::Resize {
+                    width,
+                    height,
+                    fit: match fit {
+                        Fit::Contain => host::Fit::Contain,
+                        Fit::Cover => host::Fit::Cover,
+                        Fit::Fill => host::Fit::Fill,
+                    },
+                }),
+                Operation::Crop {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => host::Operation::Crop(host::Crop {
+                    x,
+                    y,
+                    width,
+                    height,
+                }),
+                Operation::Rotate90 => host::Operation::Rotate(host::Rotation::Quarter),
+                Operation::Rotate180 => host::Operation::Rotate(host::Rotation::Half),
+                Operation::Rotate270 => host::Operation::Rotate(host::Rotation::ThreeQuarters),
+                Operation::FlipHorizontal => host::Operation::FlipHorizontal,
+                Operation::FlipVertical => host::Operation::FlipVertical,
+                Operation::Grayscale => host::Operation::Grayscale,
+            })
+            .collect(),
+        format: transform.format.map(|format| match format {
+            Format::Jpeg => host::Format::Jpeg,
+            Format::Png => host::Format::Png,
+            Format::Webp => host::Format::Webp,
+            Format::Gif => host::Format::Gif,
+        }),
+        quality: transform.quality,
+    };
+    host::transform(image, &options).map_err(host_error)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn apply(_image: &[u8], _transform: &Transform) -> crate::Result<Vec<u8>> {
+    anyhow::bail!("faasta::image is only available in a WASI guest")
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn info(image: &[u8]) -> crate::Result<ImageInfo> {
+    let info = host::info(image).map_err(host_error)?;
+    Ok(ImageInfo {
+        width: info.width,
+        height: info.height,
+        format: match info.format {
+            host::Format::Jpeg => Format::Jpeg,
+            host::Format::Png => Format::Png,
+            host::Format::Webp => Format::Webp,
+            host::Format::Gif => Format::Gif,
+        },
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn info(_image: &[u8]) -> crate::Result<ImageInfo> {
+    anyhow::bail!("faasta::image is only available in a WASI guest")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn host_error(err: host::Error) -> crate::Error {
+    match err {
+        host::Error::UnsupportedFormat => {
+            anyhow::anyhow!("unsupported image format; use JPEG, PNG, WebP or GIF")
+        }
+        host::Error::InvalidImage(message) => anyhow::anyhow!("invalid image: {message}"),
+        host::Error::TooLarge(message) => anyhow::anyhow!("image too large: {message}"),
+        host::Error::InvalidOperation(message) => {
+            anyhow::anyhow!("invalid image operation: {message}")
+        }
+        host::Error::Internal(message) => anyhow::anyhow!("image transform failed: {message}"),
+    }
+}
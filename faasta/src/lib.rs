@@ -1,13 +1,42 @@
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 
+#[cfg(feature = "blob")]
 pub mod blob;
+pub mod context;
+pub mod env;
+#[cfg(feature = "fs")]
+pub mod fs;
 pub mod http;
+#[cfg(feature = "image")]
+pub mod image;
+#[cfg(feature = "kv")]
 pub mod kv;
+#[cfg(feature = "log")]
+pub mod log;
+#[cfg(feature = "sql")]
 pub mod sql;
 
 pub use anyhow::{Error, Result};
 pub use faasta_macros::handler;
 
+/// Everything a handler usually needs: `use faasta::prelude::*;`
+pub mod prelude {
+    pub use crate::context::Context;
+    pub use crate::http::{Html, Json, Raw, Request, ResponseExt, ServerTiming};
+    pub use crate::{Error, Result, handler};
+
+    #[cfg(feature = "blob")]
+    pub use crate::blob::Blobs;
+    #[cfg(feature = "fs")]
+    pub use crate::fs::Files;
+    #[cfg(feature = "image")]
+    pub use crate::image::{Images, Transform};
+    #[cfg(feature = "kv")]
+    pub use crate::kv::Kv;
+    #[cfg(feature = "sql")]
+    pub use crate::sql::Sql;
+}
+
 #[doc(hidden)]
 pub mod __private {
     use crate::http::IntoResponse;
@@ -0,0 +1,62 @@
+//! Leveled log lines recorded by the host against the function's name. Outside a WASI
+//! guest they go to stderr, so `cargo test` output still shows them.
+//!
+//! ```ignore
+//! faasta::log::info(format!("processed {count} rows"));
+//! ```
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+pub fn log(level: Level, message: impl AsRef<str>) {
+    write(level, message.as_ref());
+}
+
+pub fn debug(message: impl AsRef<str>) {
+    log(Level::Debug, message);
+}
+
+pub fn info(message: impl AsRef<str>) {
+    log(Level::Info, message);
+}
+
+pub fn warn(message: impl AsRef<str>) {
+    log(Level::Warn, message);
+}
+
+pub fn error(message: impl AsRef<str>) {
+    log(Level::Error, message);
+}
+
+#[cfg(target_arch = "wasm32")]
+#[allow(unsafe_code)]
+mod bindings {
+    wasip3::wit_bindgen::generate!({
+        path: "wit",
+        world: "faasta:log/imports",
+        runtime_path: "wasip3::wit_bindgen::rt",
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(level: Level, message: &str) {
+    use bindings::faasta::log::logging as host;
+
+    let level = match level {
+        Level::Debug => host::Level::Debug,
+        Level::Info => host::Level::Info,
+        Level::Warn => host::Level::Warn,
+        Level::Error => host::Level::Error,
+    };
+    host::log(level, message);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(level: Level, message: &str) {
+    eprintln!("[{level:?}] {message}");
+}
@@ -0,0 +1,186 @@
+//! Content-defined chunking for differential uploads.
+//!
+//! Chunk boundaries fall where a rolling gear hash of the preceding bytes matches a
+//! mask, so they depend only on nearby content: an edit moves the boundaries around
+//! it and leaves the rest of the artifact's chunks, and their digests, unchanged. The
+//! client diffs its build against the chunks of the deployed upload and sends only
+//! the bytes the server doesn't already have.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use sha2::{Digest, Sha256};
+
+use crate::{ChunkRef, DeltaOp};
+
+const MIN_CHUNK: usize = 2 * 1024;
+const MAX_CHUNK: usize = 64 * 1024;
+/// 13 bits for chunks of about 8 KiB on average
+const BOUNDARY_MASK: u64 = (1 << 13) - 1;
+
+/// Pseudo-random value per byte, fixed so that client and server agree on boundaries
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64
+    let mut table = [0; 256];
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Split `data` into chunks of between 2 and 64 KiB, except for a shorter last one.
+pub fn split(data: &[u8]) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let end = (start + MAX_CHUNK).min(data.len());
+        let mut hash: u64 = 0;
+        let mut boundary = end;
+        for (i, byte) in data[start..end].iter().enumerate() {
+            hash = (hash << 1).wrapping_add(GEAR[usize::from(*byte)]);
+            if i + 1 >= MIN_CHUNK && hash & BOUNDARY_MASK == 0 {
+                boundary = start + i + 1;
+                break;
+            }
+        }
+        chunks.push(start..boundary);
+        start = boundary;
+    }
+    chunks
+}
+
+pub fn chunk_refs(data: &[u8]) -> Vec<ChunkRef> {
+    split(data)
+        .into_iter()
+        .map(|range| ChunkRef {
+            len: range.len() as u32,
+            sha256: Sha256::digest(&data[range]).into(),
+        })
+        .collect()
+}
+
+/// Rebuild `data` as copies of the chunks of `base` where it has them, and new bytes
+/// for the rest.
+pub fn diff(base: &[ChunkRef], data: &[u8]) -> Vec<DeltaOp> {
+    let mut offsets = HashMap::new();
+    let mut offset = 0;
+    for chunk in base {
+        offsets.entry(chunk.sha256).or_insert((offset, chunk.len));
+        offset += u64::from(chunk.len);
+    }
+
+    let mut ops: Vec<DeltaOp> = Vec::new();
+    for range in split(data) {
+        let sha256: [u8; 32] = Sha256::digest(&data[range.clone()]).into();
+        let found = offsets.get(&sha256).copied();
+        // Extend the previous op when it continues it
+        match (found, ops.last_mut()) {
+            (
+                Some((offset, len)),
+                Some(DeltaOp::Copy {
+                    offset: start,
+                    len: total,
+                }),
+            ) if *start + u64::from(*total) == offset => {
+                *total += len;
+                continue;
+            }
+            (None, Some(DeltaOp::Data(bytes))) => {
+                bytes.extend_from_slice(&data[range]);
+                continue;
+            }
+            _ => {}
+        }
+        ops.push(match found {
+            Some((offset, len)) => DeltaOp::Copy { offset, len },
+            None => DeltaOp::Data(data[range].to_vec()),
+        });
+    }
+    ops
+}
+
+/// New bytes carried by `ops`.
+pub fn data_len(ops: &[DeltaOp]) -> usize {
+    ops.iter()
+        .map(|op| match op {
+            DeltaOp::Data(bytes) => bytes.len(),
+            DeltaOp::Copy { .. } => 0,
+        })
+        .sum()
+}
+
+/// Reassemble what [`diff`] encoded against `base`, refusing to grow past `limit`.
+pub fn apply(base: &[u8], ops: &[DeltaOp], limit: usize) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    for op in ops {
+        let bytes = match op {
+            DeltaOp::Copy { offset, len } => usize::try_from(*offset)
+                .ok()
+                .and_then(|start| base.get(start..start.checked_add(*len as usize)?))
+                .ok_or_else(|| {
+                    format!("copy of {len} bytes at {offset} is outside the deployed artifact")
+                })?,
+            DeltaOp::Data(bytes) => bytes.as_slice(),
+        };
+        if data.len() + bytes.len() > limit {
+            return Err(format!("reassembled artifact is larger than {limit} bytes"));
+        }
+        data.extend_from_slice(bytes);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sends_only_changed_chunks() {
+        let base = pseudo_random(1024 * 1024, 1);
+        let chunks = split(&base);
+        assert_eq!(
+            chunks.iter().map(|range| range.len()).sum::<usize>(),
+            base.len()
+        );
+        assert!(
+            chunks[..chunks.len() - 1]
+                .iter()
+                .all(|range| (MIN_CHUNK..=MAX_CHUNK).contains(&range.len()))
+        );
+
+        let mut data = base.clone();
+        data.splice(300_000..300_000, b"a small edit".iter().copied());
+        data.truncate(900_000);
+        let ops = diff(&chunk_refs(&base), &data);
+        assert!(data_len(&ops) < 3 * MAX_CHUNK, "{}", data_len(&ops));
+        assert_eq!(apply(&base, &ops, data.len()).unwrap(), data);
+        assert!(apply(&base, &ops, data.len() - 1).is_err());
+
+        let outside = [DeltaOp::Copy {
+            offset: base.len() as u64,
+            len: 1,
+        }];
+        assert!(apply(&base, &outside, usize::MAX).is_err());
+    }
+}
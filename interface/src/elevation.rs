@@ -0,0 +1,41 @@
+//! Proof of a recent second factor, required for destructive management calls by users
+//! who enabled two-factor authentication.
+//!
+//! `elevate` exchanges a TOTP code for a short-lived elevation token, which travels in
+//! the auth token ahead of any request signature (`user:token#elevated=<token>`), so
+//! a stolen long-lived GitHub token alone can't unpublish functions or issue keys.
+
+const ELEVATION_MARKER: &str = "#elevated=";
+
+/// How long an elevation token is accepted, in seconds.
+pub const ELEVATION_TTL_SECS: u64 = 300;
+
+/// Append an elevation token to an auth token. Sign the result, not the other way round.
+pub fn attach(token: &str, elevation: &str) -> String {
+    format!("{token}{ELEVATION_MARKER}{elevation}")
+}
+
+/// Separate an auth token, with any signature already split off, from the elevation
+/// token appended by [`attach`].
+pub fn split_token(token: &str) -> (&str, Option<&str>) {
+    match token.split_once(ELEVATION_MARKER) {
+        Some((token, elevation)) => (token, Some(elevation)),
+        None => (token, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing;
+
+    #[test]
+    fn survives_signing() {
+        let token = attach("alice:ghp_abc", "e1e1");
+        let signed = signing::sign_token(&token, b"key", "unpublish", "api", &[], 1, "n");
+        let (token, signature) = signing::split_token(&signed);
+        assert!(signature.is_some());
+        assert_eq!(split_token(token), ("alice:ghp_abc", Some("e1e1")));
+        assert_eq!(split_token("alice:ghp_abc"), ("alice:ghp_abc", None));
+    }
+}
@@ -2,7 +2,26 @@ use bitrpc::bitcode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod chunking;
+pub mod elevation;
+pub mod protocol;
+pub mod signing;
+
 pub const MAX_WASM_SIZE: usize = 30 * 1024 * 1024;
+/// Size of the pieces a component artifact is sent in by `upload_chunk`
+pub const UPLOAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+pub const MAX_STATIC_SITE_SIZE: usize = 100 * 1024 * 1024;
+pub const MAX_STATIC_SITE_FILES: usize = 10_000;
+/// Maximum size of one file in a static site
+pub const MAX_STATIC_ASSET_SIZE: usize = 25 * 1024 * 1024;
+/// Maximum number of path components of a file in a static site, the file included
+pub const MAX_STATIC_ASSET_DEPTH: usize = 16;
+/// Maximum length in bytes of a file's path in a static site
+pub const MAX_STATIC_ASSET_PATH_LEN: usize = 1024;
+/// Maximum size of one secret value
+pub const MAX_SECRET_BYTES: usize = 32 * 1024;
+/// Maximum number of secrets per function
+pub const MAX_SECRETS: usize = 100;
 
 // Define a custom error type that can be serialized
 #[derive(Debug, Error, Serialize, Deserialize, Clone, Encode, Decode)]
@@ -19,13 +38,103 @@ pub enum FunctionError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
 
+impl FunctionError {
+    /// Error code used when the error is sent in an [`ErrorBody`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            FunctionError::AuthError(_) => error_code::UNAUTHENTICATED,
+            FunctionError::NotFound(_) => error_code::NOT_FOUND,
+            FunctionError::PermissionDenied(_) => error_code::PERMISSION_DENIED,
+            FunctionError::InvalidInput(_) => error_code::INVALID_INPUT,
+            FunctionError::Conflict(_) => error_code::DEPLOY_IN_PROGRESS,
+            FunctionError::InternalError(_) => error_code::INTERNAL,
+        }
+    }
+}
+
 // Type alias for Result with our custom error
 pub type FunctionResult<T> = std::result::Result<T, FunctionError>;
 
+/// Where the error codes are documented
+pub const ERROR_DOCS_URL: &str =
+    "https://github.com/fourlexboehm/faasta/blob/main/server/README.md#error-responses";
+
+/// Body of every error response the server sends over HTTP, on the function traffic
+/// and management paths alike
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Encode,
+    Decode,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct ErrorBody {
+    /// Stable, machine-readable cause; one of the [`error_code`] constants
+    pub code: String,
+    /// Human-readable description
+    pub message: String,
+    /// Also sent as the `X-Request-Id` header; quote it when reporting a problem
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub docs_url: Option<String>,
+}
+
+impl std::fmt::Display for ErrorBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, " [request id {request_id}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Codes sent in [`ErrorBody::code`]
+pub mod error_code {
+    pub const BAD_REQUEST: &str = "bad_request";
+    pub const INVALID_FUNCTION_NAME: &str = "invalid_function_name";
+    pub const UNAUTHENTICATED: &str = "unauthenticated";
+    pub const PERMISSION_DENIED: &str = "permission_denied";
+    pub const SOURCE_NOT_ALLOWED: &str = "source_not_allowed";
+    pub const NOT_FOUND: &str = "not_found";
+    pub const FUNCTION_NOT_FOUND: &str = "function_not_found";
+    pub const METHOD_NOT_ALLOWED: &str = "method_not_allowed";
+    pub const INVALID_INPUT: &str = "invalid_input";
+    pub const INCOMPATIBLE_CLIENT: &str = "incompatible_client";
+    pub const IDEMPOTENCY_IN_PROGRESS: &str = "idempotency_in_progress";
+    pub const DEPLOY_IN_PROGRESS: &str = "deploy_in_progress";
+    pub const IDEMPOTENCY_MISMATCH: &str = "idempotency_mismatch";
+    pub const RANGE_NOT_SATISFIABLE: &str = "range_not_satisfiable";
+    pub const MAINTENANCE: &str = "maintenance";
+    pub const AT_CAPACITY: &str = "at_capacity";
+    pub const ARTIFACT_UNAVAILABLE: &str = "artifact_unavailable";
+    pub const NOT_READY: &str = "not_ready";
+    pub const INVOCATION_FAILED: &str = "invocation_failed";
+    pub const CPU_BUDGET_EXCEEDED: &str = "cpu_budget_exceeded";
+    pub const OUT_OF_MEMORY: &str = "out_of_memory";
+    pub const STACK_OVERFLOW: &str = "stack_overflow";
+    pub const FUNCTION_PANICKED: &str = "function_panicked";
+    pub const FUNCTION_TRAPPED: &str = "function_trapped";
+    pub const JOB_TIMED_OUT: &str = "job_timed_out";
+    pub const RESULT_TOO_LARGE: &str = "result_too_large";
+    pub const TOO_MANY_JOBS: &str = "too_many_jobs";
+    pub const PAYLOAD_TOO_LARGE: &str = "payload_too_large";
+    pub const BAD_GATEWAY: &str = "bad_gateway";
+    pub const INTERNAL: &str = "internal";
+}
+
 // Define the data structures for our service
 
 /// Represents a published function
@@ -43,17 +152,373 @@ pub struct FunctionInfo {
     pub usage: String,
 }
 
+/// A content-defined chunk of an artifact, see [`chunking`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct ChunkRef {
+    pub sha256: [u8; 32],
+    pub len: u32,
+}
+
+/// The chunks of a function's deployed upload, to diff a new build against
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct ArtifactChunks {
+    /// SHA-256 of the whole upload, hex-encoded
+    pub sha256: String,
+    pub chunks: Vec<ChunkRef>,
+}
+
+/// One step of reassembling an artifact from the deployed upload
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum DeltaOp {
+    /// Bytes `offset..offset + len` of the deployed upload
+    Copy { offset: u64, len: u32 },
+    /// New bytes
+    Data(Vec<u8>),
+}
+
+/// A file in a static-site deployment
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct StaticAsset {
+    /// Path relative to the site root, using `/` separators
+    pub path: String,
+    /// File contents
+    pub contents: Vec<u8>,
+}
+
 /// Function metrics information
 #[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct FunctionMetricsResponse {
     /// Name of the function
     pub function_name: String,
-    /// Total execution time in milliseconds
+    /// Total execution time in milliseconds, wall-clock from dispatch to response.
+    /// Summed from `latency`, so accurate to about 1%.
     pub total_time_millis: u64,
+    /// CPU time the function's guest ran for, in microseconds. Unlike
+    /// `total_time_millis` this leaves out queueing and waiting on I/O.
+    #[serde(default)]
+    pub cpu_time_micros: u64,
     /// Number of times the function was called
     pub call_count: u64,
     /// Last time the function was called (ISO 8601 format)
     pub last_called: String,
+    /// Size of the deployed artifact in bytes
+    #[serde(default)]
+    pub artifact_bytes: u64,
+    /// Set when the artifact recently failed to load
+    #[serde(default)]
+    pub artifact_health: Option<ArtifactHealth>,
+    /// How the live artifact was built. Only sent to the function's owner.
+    #[serde(default)]
+    pub provenance: Option<ArtifactProvenance>,
+    /// Runs in the dedicated isolation tier
+    #[serde(default)]
+    pub dedicated: bool,
+    /// Most recent invocation that trapped or hit a limit. Only sent to the function's
+    /// owner.
+    #[serde(default)]
+    pub last_failure: Option<InvocationFailure>,
+    /// `Server-Timing` entries reported by the function since the node started
+    #[serde(default)]
+    pub server_timings: Vec<ServerTimingMetric>,
+    /// Cold and warm invocations since the node started
+    #[serde(default)]
+    pub starts: StartLatency,
+    /// Calls the function's experiment sent to each variant since the node started
+    #[serde(default)]
+    pub variants: Vec<VariantMetric>,
+    /// Wall-time distribution of every call, accurate to about 1%
+    #[serde(default)]
+    pub latency: LatencyDistribution,
+    /// The same as a base64, compressed V2 HdrHistogram of microseconds, for other
+    /// percentiles or merging across nodes
+    #[serde(default)]
+    pub latency_hdr: String,
+}
+
+/// Latency of a function's invocations, split by whether its component had to be
+/// loaded first
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct StartLatency {
+    /// Invocations that found the component resident
+    pub warm: LatencyDistribution,
+    /// Invocations that had to load the component (cold starts)
+    pub cold: LatencyDistribution,
+}
+
+/// Wall-time distribution of a set of invocations. Percentiles are upper bounds, in
+/// milliseconds, of the histogram bucket they fall in.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct LatencyDistribution {
+    pub count: u64,
+    pub total_micros: u64,
+    pub p50_ms: Option<u64>,
+    pub p95_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+}
+
+/// Calls an experiment sent to one of its variants
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct VariantMetric {
+    pub name: String,
+    /// Responses with a 5xx status
+    pub server_errors: u64,
+    pub latency: LatencyDistribution,
+}
+
+/// Totals of one `Server-Timing` entry name reported by a function
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct ServerTimingMetric {
+    pub name: String,
+    /// Responses that reported a duration for the entry
+    pub count: u64,
+    pub total_micros: u64,
+}
+
+/// Where a function's live component came from
+#[derive(
+    Clone, Debug, Default, Serialize, Deserialize, Encode, Decode, bincode::Encode, bincode::Decode,
+)]
+pub struct ArtifactProvenance {
+    /// SHA-256 of the component as uploaded, hex-encoded
+    pub upload_sha256: String,
+    /// Size of the component as uploaded
+    pub upload_bytes: u64,
+    /// The live component is a pre-initialized snapshot of the upload
+    pub preinitialized: bool,
+    /// When the live component was built from the upload (RFC 3339)
+    pub built_at: String,
+    /// Version of the server that built it
+    pub built_by: String,
+}
+
+/// Load failures of a function's artifact since it last loaded successfully
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct ArtifactHealth {
+    /// Consecutive failed loads
+    pub load_failures: u32,
+    /// Requests are refused until a retry is due
+    pub quarantined: bool,
+    /// Error of the most recent failed load
+    pub last_error: String,
+}
+
+/// A failed invocation of a function, kept for its owner
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct InvocationFailure {
+    /// Error code sent to the client, e.g. `out_of_memory`
+    pub code: String,
+    /// Message sent to the client
+    pub message: String,
+    /// The underlying error, which clients don't see
+    pub detail: String,
+    /// Innermost guest frames, one per line
+    pub backtrace: String,
+    /// When it failed (RFC 3339)
+    pub failed_at: String,
+    /// Failed invocations since the function was published or the server started
+    pub failures: u64,
+}
+
+/// Disk usage of the server's functions directory
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct DiskUsage {
+    /// Total bytes under the functions directory, including sandboxes
+    pub functions_dir_bytes: u64,
+    /// Bytes used by function sandboxes
+    pub sandbox_bytes: u64,
+    /// Artifacts on disk with no function record
+    pub orphaned_artifacts: u64,
+    /// Stale precompiled artifacts and leftover publish staging
+    pub stale_files: u64,
+    /// Files moved aside by `--fsck --repair`
+    pub quarantined_files: u64,
+}
+
+/// Load shedding by the server's admission controller
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct AdmissionStats {
+    /// Function invocations currently running
+    pub in_flight: u64,
+    /// In-flight invocations at which new ones are shed
+    pub limit: u64,
+    /// Invocations turned away with a 503 since the server started
+    pub shed_requests: u64,
+    /// Of which critical-priority
+    #[serde(default)]
+    pub shed_critical: u64,
+    /// Of which standard-priority
+    #[serde(default)]
+    pub shed_standard: u64,
+    /// Of which batch-priority, including mirrored traffic
+    #[serde(default)]
+    pub shed_batch: u64,
+}
+
+/// Compiled components held by the server, across its shared and dedicated runtimes
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Encode, Decode)]
+pub struct RuntimeStats {
+    /// Components compiled and resident in memory
+    pub cached_components: u64,
+    /// Components being loaded
+    pub loads_in_progress: u64,
+}
+
+/// Identity of the node answering an RPC
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct ServerInfo {
+    /// Region label set by the operator
+    pub region: Option<String>,
+    /// Server version
+    pub version: String,
+    /// Management protocol the server speaks; see [`protocol`]
+    pub protocol_version: u32,
+}
+
+/// Progress of an asynchronous invocation
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Encode,
+    Decode,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub enum JobState {
+    /// Waiting for a free slot or for its next attempt
+    Queued,
+    Running,
+    /// The function returned a response with a status below 500
+    Succeeded,
+    /// The last attempt trapped, timed out, could not be started or returned a 5xx
+    Failed,
+    /// Cancelled by the function's owner
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobState::Succeeded | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+/// An asynchronous invocation, as reported by `job_status`
+#[derive(
+    Clone, Debug, Serialize, Deserialize, Encode, Decode, bincode::Encode, bincode::Decode,
+)]
+pub struct JobStatus {
+    pub job_id: String,
+    pub function_name: String,
+    pub state: JobState,
+    /// Attempts started so far
+    pub attempts: u32,
+    /// When the job was accepted (RFC 3339)
+    pub submitted_at: String,
+    /// When it succeeded, failed or was cancelled (RFC 3339)
+    pub finished_at: Option<String>,
+    /// Status of the function's response
+    pub status: Option<u16>,
+    /// Headers of the function's response
+    pub headers: Vec<(String, String)>,
+    /// Body of the function's response
+    pub body: Vec<u8>,
+    /// Why the job failed
+    pub error: Option<ErrorBody>,
+    /// Whether the result was delivered to the callback URL, if one was given
+    pub callback_delivered: Option<bool>,
+}
+
+/// An asynchronous invocation without its result, as listed by `list_jobs`
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct JobSummary {
+    pub job_id: String,
+    pub function_name: String,
+    pub state: JobState,
+    pub attempts: u32,
+    /// When the job was accepted (RFC 3339)
+    pub submitted_at: String,
+    /// When it succeeded, failed or was cancelled (RFC 3339)
+    pub finished_at: Option<String>,
+}
+
+/// Where a captured log line was written
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Encode,
+    Decode,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// `faasta:log` at debug level
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// A line a function wrote, as returned by `get_logs`
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct LogEntry {
+    /// When the line was written (RFC 3339)
+    pub timestamp: String,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+/// A bucket of a function's key-value store
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct KvBucket {
+    pub name: String,
+    pub keys: u64,
+    /// Bytes of keys and values, as counted against the quota
+    pub bytes: u64,
+}
+
+/// What a function stores through `wasi:keyvalue`, as returned by `get_kv_usage`
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct KvUsage {
+    pub used_bytes: u64,
+    pub quota_bytes: u64,
+    pub buckets: Vec<KvBucket>,
+}
+
+/// A key in a bucket, as listed by `list_kv_keys`
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct KvKey {
+    pub key: String,
+    pub value_bytes: u64,
+}
+
+/// An auth token that has been used with the server, as listed by `list_sessions`
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
+pub struct SessionInfo {
+    /// Identifies the session to `revoke_session`; derived from the token, which the
+    /// server does not keep
+    pub session_id: String,
+    /// Last characters of the token
+    pub token_hint: String,
+    /// When the token was first used (RFC 3339)
+    pub created_at: String,
+    /// When the token was last used, to within a minute (RFC 3339)
+    pub last_used_at: String,
+    /// Whether this is the token making the call
+    pub current: bool,
 }
 
 /// Overall metrics information
@@ -65,6 +530,271 @@ pub struct Metrics {
     pub total_calls: u64,
     /// Metrics for individual functions
     pub function_metrics: Vec<FunctionMetricsResponse>,
+    /// Disk usage of the functions directory
+    #[serde(default)]
+    pub disk: DiskUsage,
+    /// Admission control since the server started
+    #[serde(default)]
+    pub admission: AdmissionStats,
+    #[serde(default)]
+    pub runtime: RuntimeStats,
+}
+
+/// Platform-managed settings for a published function
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct FunctionConfig {
+    /// Shadow traffic copied to another function
+    #[serde(default)]
+    pub mirror: Option<MirrorConfig>,
+    /// A/B experiment splitting traffic across variant functions
+    #[serde(default)]
+    pub experiment: Option<ExperimentConfig>,
+    /// Redirect and rewrite rules evaluated before the function is invoked
+    #[serde(default)]
+    pub routes: RouteConfig,
+    /// Headers the host adds to every response from the function
+    #[serde(default)]
+    pub response_headers: Vec<ResponseHeader>,
+    /// HTML pages shown to browsers for errors the host returns on the function's behalf
+    #[serde(default)]
+    pub error_pages: Vec<ErrorPage>,
+    /// When set, requests get a 503 instead of reaching the function
+    #[serde(default)]
+    pub maintenance: Option<Maintenance>,
+    /// Daily window during which the function is kept loaded
+    #[serde(default)]
+    pub keep_warm: Option<KeepWarm>,
+    /// Order in which the function's traffic is shed under load
+    #[serde(default)]
+    pub priority: Priority,
+    /// Markdown description rendered by the host at `/_faasta/about`
+    #[serde(default)]
+    pub readme: Option<String>,
+    /// Fixed clocks and seeded randomness, for reproducible tests and replays
+    #[serde(default)]
+    pub deterministic: Option<Deterministic>,
+    /// Regions the function is pinned to; nodes labelled with another region don't serve
+    /// it. Empty means every region.
+    #[serde(default)]
+    pub regions: Vec<String>,
+    /// How failed asynchronous invocations are retried
+    #[serde(default)]
+    pub job_retry: RetryPolicy,
+    /// GraphQL endpoint whose requests the host normalizes and resolves persisted
+    /// queries for
+    #[serde(default)]
+    pub graphql: Option<GraphQl>,
+    /// Add a `Server-Timing` header breaking responses down into cache state, cold
+    /// start, instantiation and handler time
+    #[serde(default)]
+    pub server_timing: bool,
+    /// Functions of the same owner warmed when this one receives traffic, e.g. the
+    /// APIs its pages call
+    #[serde(default)]
+    pub preload: Vec<String>,
+    /// Publish requests per day and p95 latency at `/_faasta/stats` and `/v1/stats/NAME`
+    #[serde(default)]
+    pub public_stats: bool,
+}
+
+/// Where a function serves GraphQL
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct GraphQl {
+    /// Path of the endpoint below the function's mount, e.g. `/graphql`
+    pub path: String,
+}
+
+/// Retries of a failed asynchronous invocation, with exponential backoff
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for each further one
+    pub initial_backoff_ms: u64,
+    /// Longest delay between attempts
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 60_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the attempt following attempt number `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> std::time::Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(32);
+        std::time::Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+}
+
+/// Makes every request to a function see the same clocks and random numbers
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Deterministic {
+    /// Seed of the function's random sources
+    pub seed: u64,
+    /// Wall-clock time, in seconds since the Unix epoch, at which every request starts
+    pub start_unix_secs: u64,
+}
+
+/// Load-shedding class; lower classes are turned away first when the server is busy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum Priority {
+    /// Shed only when the instance pool is completely full
+    Critical,
+    #[default]
+    Standard,
+    /// Shed first, well before standard traffic
+    Batch,
+}
+
+/// Keeps a function compiled and pre-instantiated during a daily window, trading memory
+/// for cold-start latency
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct KeepWarm {
+    /// Hour (UTC, 0-23) at which the function is loaded
+    pub start_hour: u8,
+    /// Hour (UTC, 0-23) at which the window ends; equal to `start_hour` means all day
+    pub end_hour: u8,
+    /// Unload the function when the window ends so idle hours cost no memory
+    #[serde(default)]
+    pub scale_to_zero: bool,
+}
+
+impl KeepWarm {
+    /// Whether `hour` (UTC) falls inside the window, which may wrap past midnight.
+    pub fn contains_hour(&self, hour: u8) -> bool {
+        match self.start_hour.cmp(&self.end_hour) {
+            std::cmp::Ordering::Equal => true,
+            std::cmp::Ordering::Less => (self.start_hour..self.end_hour).contains(&hour),
+            std::cmp::Ordering::Greater => hour >= self.start_hour || hour < self.end_hour,
+        }
+    }
+}
+
+/// Takes a function offline without unpublishing it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Maintenance {
+    /// Message shown to clients
+    pub message: Option<String>,
+    /// Sent as `Retry-After` so clients know when to come back
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Maximum size of a function's README.
+pub const MAX_README_BYTES: usize = 64 * 1024;
+
+/// Maximum size of one custom error page template.
+pub const MAX_ERROR_PAGE_BYTES: usize = 64 * 1024;
+
+/// An HTML template for host-generated error responses
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct ErrorPage {
+    /// Exact status (`404`) or status class (`5xx`)
+    pub status: String,
+    /// Template; `{{status}}`, `{{reason}}` and `{{message}}` are substituted
+    pub html: String,
+}
+
+/// A header injected into a function's responses by the host
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct ResponseHeader {
+    /// Header name (case-insensitive)
+    pub name: String,
+    pub value: String,
+    /// Replace a value set by the function instead of only filling in a missing header
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// Asynchronously copies a share of requests to a shadow function
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct MirrorConfig {
+    /// Name of the shadow function, which must have the same owner
+    pub target: String,
+    /// Percentage of requests to mirror (0-100)
+    pub percent: u8,
+}
+
+/// Routes requests for a function across variant functions
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct ExperimentConfig {
+    /// How a client is assigned to a variant
+    pub assignment: VariantAssignment,
+    /// Variants and their relative weights
+    pub variants: Vec<Variant>,
+}
+
+/// Source of the key used to assign a client to a variant
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub enum VariantAssignment {
+    /// Value of the named request header
+    Header(String),
+    /// Value of the named cookie, which is set on first assignment for stickiness
+    Cookie(String),
+    /// Stable hash of the client IP address
+    ClientIp,
+}
+
+/// One arm of an experiment
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Variant {
+    /// Variant label, also accepted as an explicit header or cookie value
+    pub name: String,
+    /// Function that serves this variant
+    pub function: String,
+    /// Relative share of traffic
+    pub weight: u32,
+}
+
+/// Lightweight routing handled by the host without invoking the function
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct RouteConfig {
+    /// Rules evaluated in order; the first matching rule wins
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+    /// Redirect paths to a canonical trailing-slash form before rules are applied
+    #[serde(default)]
+    pub trailing_slash: Option<TrailingSlash>,
+    /// Deliver path-based requests without the `/name` prefix, so the function sees the
+    /// same paths as on its own subdomain
+    #[serde(default)]
+    pub strip_prefix: bool,
+}
+
+/// Maps a request path to a new location
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct RouteRule {
+    /// Path to match; a trailing `*` matches any suffix
+    pub source: String,
+    /// Target path or absolute URL; a trailing `*` is replaced with the matched suffix
+    pub destination: String,
+    pub action: RouteAction,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub enum RouteAction {
+    /// Respond with a redirect using the given status code
+    Redirect(u16),
+    /// Invoke the function with the rewritten path
+    Rewrite,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub enum TrailingSlash {
+    /// Add a trailing slash to paths that don't look like files
+    Always,
+    /// Strip trailing slashes from every path except the root
+    Never,
 }
 
 /// Service interface for managing functions via bitrpc.
@@ -81,6 +811,52 @@ pub trait FunctionService {
         name: String,
         github_auth_token: String,
     ) -> bitrpc::Result<FunctionResult<String>>;
+    /// Write part of a component artifact to the function's resumable upload, starting at
+    /// `offset`. Resending a chunk is harmless. Returns the number of bytes received so far.
+    async fn upload_chunk(
+        &self,
+        name: String,
+        offset: u64,
+        chunk: Vec<u8>,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<u64>>;
+    /// Publish the artifact assembled by `upload_chunk`
+    async fn publish_upload(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>>;
+    /// Chunks of the function's deployed upload, for `publish_delta`
+    async fn artifact_chunks(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<ArtifactChunks>>;
+    /// Publish an artifact rebuilt from the deployed upload, whose SHA-256 must still be
+    /// `base_sha256`, and new bytes. `sha256` is checked against the result.
+    async fn publish_delta(
+        &self,
+        name: String,
+        base_sha256: String,
+        ops: Vec<DeltaOp>,
+        sha256: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>>;
+    /// Publish a directory of static assets served directly by the host
+    async fn publish_static(
+        &self,
+        assets: Vec<StaticAsset>,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>>;
+    /// Publish a component the server pulls from an OCI registry, given as
+    /// `registry/repository[:tag][@sha256:...]`
+    async fn publish_oci(
+        &self,
+        reference: String,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>>;
     /// List all functions for the authenticated user
     async fn list_functions(
         &self,
@@ -97,4 +873,131 @@ pub trait FunctionService {
         &self,
         github_auth_token: String,
     ) -> bitrpc::Result<FunctionResult<Metrics>>;
+    /// Load, compile and instantiate a function ahead of traffic. Returns the time taken
+    /// in milliseconds.
+    async fn warm(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<u64>>;
+    /// Get the platform settings for a function
+    async fn get_function_config(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<FunctionConfig>>;
+    /// Replace the platform settings for a function
+    async fn set_function_config(
+        &self,
+        name: String,
+        config: FunctionConfig,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>>;
+    /// List the names of a function's secrets. Values are never returned.
+    async fn list_secrets(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<String>>>;
+    /// Set a secret, exposed to the function as the environment variable `key`
+    async fn set_secret(
+        &self,
+        name: String,
+        key: String,
+        value: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>>;
+    /// Remove a secret
+    async fn delete_secret(
+        &self,
+        name: String,
+        key: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>>;
+    /// Describe the node, e.g. its region. Needs no authentication.
+    async fn server_info(&self) -> bitrpc::Result<ServerInfo>;
+    /// State and, once finished, result of an asynchronous invocation. The job id is
+    /// unguessable and authorizes the call on its own.
+    async fn job_status(&self, job_id: String) -> bitrpc::Result<FunctionResult<JobStatus>>;
+    /// Asynchronous invocations of a function kept on this node, newest first.
+    async fn list_jobs(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<JobSummary>>>;
+    /// Lines the function recently wrote to stdout, stderr or `faasta:log`, oldest first:
+    /// those written after `since_millis` (Unix milliseconds, 0 for all), and of those
+    /// only the last `tail` (0 for all).
+    async fn get_logs(
+        &self,
+        name: String,
+        since_millis: u64,
+        tail: u32,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<LogEntry>>>;
+    /// The function's `wasi:keyvalue` buckets and how much of its quota they use. Only
+    /// available on servers using the local key-value backend.
+    async fn get_kv_usage(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<KvUsage>>;
+    /// Keys of one of the function's buckets, in key order
+    async fn list_kv_keys(
+        &self,
+        name: String,
+        bucket: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<KvKey>>>;
+    /// Value stored under `key` in one of the function's buckets
+    async fn get_kv_value(
+        &self,
+        name: String,
+        bucket: String,
+        key: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Option<Vec<u8>>>>;
+    /// Cancel a queued or running asynchronous invocation of one of the caller's functions.
+    async fn cancel_job(
+        &self,
+        job_id: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>>;
+    /// Issue a new request-signing key (hex) for the caller. The first key needs two-factor
+    /// authentication and an elevation. Once a key exists, management calls must be signed
+    /// with it, including the call that replaces it.
+    async fn rotate_signing_key(
+        &self,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>>;
+    /// Tokens that have been used with the caller's account, most recently used first
+    async fn list_sessions(
+        &self,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<SessionInfo>>>;
+    /// Refuse a token from now on, even while GitHub still accepts it
+    async fn revoke_session(
+        &self,
+        session_id: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>>;
+    /// Start enabling two-factor authentication. Returns an `otpauth://` URI with a new
+    /// TOTP secret, which takes effect once `confirm_totp` is called with a code from it.
+    /// Replacing an enabled secret needs elevation.
+    async fn setup_totp(&self, github_auth_token: String)
+    -> bitrpc::Result<FunctionResult<String>>;
+    /// Enable the secret from `setup_totp`, given a current code from it
+    async fn confirm_totp(
+        &self,
+        code: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>>;
+    /// Exchange a current TOTP code for an elevation token; see [`elevation`]
+    async fn elevate(
+        &self,
+        code: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>>;
+    /// Turn two-factor authentication off. Needs elevation.
+    async fn disable_totp(&self, github_auth_token: String) -> bitrpc::Result<FunctionResult<()>>;
 }
@@ -0,0 +1,158 @@
+//! Versioning of the management RPC wire format.
+//!
+//! bitcode encodes structs positionally, so adding a field to a config or a method to
+//! [`crate::FunctionService`] breaks peers built against the old types in ways that
+//! only show up as decode failures. Clients therefore tag every call with the protocol
+//! version they were built with (`?protocol=N` on the RPC endpoint), and the server
+//! turns away versions it cannot decode with an error that says which side to upgrade.
+//!
+//! Bump [`PROTOCOL_VERSION`] whenever an RPC type or method changes shape, and raise
+//! [`MIN_CLIENT_PROTOCOL_VERSION`] to it unless the server still decodes older calls.
+
+/// Wire format spoken by this build of the interface
+pub const PROTOCOL_VERSION: u32 = 16;
+/// Oldest client wire format the server accepts
+pub const MIN_CLIENT_PROTOCOL_VERSION: u32 = 16;
+const _: () = assert!(MIN_CLIENT_PROTOCOL_VERSION <= PROTOCOL_VERSION);
+
+/// Query parameter on the RPC endpoint carrying the client's protocol version
+pub const PROTOCOL_PARAM: &str = "protocol";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Incompatible {
+    /// The client is older than the server still decodes; `None` for clients from
+    /// before versioning, which don't send one
+    ClientTooOld { client: Option<u32>, minimum: u32 },
+    /// The client speaks a newer protocol than the server
+    ClientTooNew { client: u32, server: u32 },
+}
+
+impl std::fmt::Display for Incompatible {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Incompatible::ClientTooOld {
+                client: Some(client),
+                minimum,
+            } => write!(
+                f,
+                "cargo-faasta speaks protocol {client} but this server requires at least \
+                 {minimum}; upgrade with `cargo install cargo-faasta`"
+            ),
+            Incompatible::ClientTooOld {
+                client: None,
+                minimum,
+            } => write!(
+                f,
+                "this version of cargo-faasta predates protocol versioning and this server \
+                 requires protocol {minimum}; upgrade with `cargo install cargo-faasta`"
+            ),
+            Incompatible::ClientTooNew { client, server } => write!(
+                f,
+                "cargo-faasta speaks protocol {client} but this server only understands up \
+                 to {server}; upgrade the server or use an older cargo-faasta"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Incompatible {}
+
+/// The protocol version a client sent in the RPC endpoint's query string.
+pub fn client_version(query: Option<&str>) -> Option<u32> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == PROTOCOL_PARAM)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// Whether this server accepts calls from a client speaking `client`.
+pub fn check_client(client: Option<u32>) -> Result<(), Incompatible> {
+    check(client, MIN_CLIENT_PROTOCOL_VERSION, PROTOCOL_VERSION)
+}
+
+fn check(client: Option<u32>, minimum: u32, server: u32) -> Result<(), Incompatible> {
+    match client {
+        Some(client) if client > server => Err(Incompatible::ClientTooNew { client, server }),
+        Some(client) if client >= minimum => Ok(()),
+        client => Err(Incompatible::ClientTooOld { client, minimum }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A server release: the protocol it speaks and the oldest client it accepts
+    struct Server {
+        version: u32,
+        minimum: u32,
+    }
+
+    impl Server {
+        fn accepts(&self, client: Option<u32>) -> Result<(), Incompatible> {
+            check(client, self.minimum, self.version)
+        }
+    }
+
+    const CURRENT: Server = Server {
+        version: PROTOCOL_VERSION,
+        minimum: MIN_CLIENT_PROTOCOL_VERSION,
+    };
+
+    #[test]
+    fn current_releases_agree() {
+        assert_eq!(CURRENT.accepts(Some(PROTOCOL_VERSION)), Ok(()));
+        assert_eq!(check_client(Some(PROTOCOL_VERSION)), Ok(()));
+    }
+
+    #[test]
+    fn unversioned_clients_are_told_to_upgrade() {
+        let err = CURRENT.accepts(None).unwrap_err();
+        assert!(matches!(
+            err,
+            Incompatible::ClientTooOld { client: None, .. }
+        ));
+        assert!(err.to_string().contains("cargo install cargo-faasta"));
+    }
+
+    #[test]
+    fn current_client_against_previous_server() {
+        let previous = Server {
+            version: PROTOCOL_VERSION - 1,
+            minimum: PROTOCOL_VERSION - 1,
+        };
+        let err = previous.accepts(Some(PROTOCOL_VERSION)).unwrap_err();
+        assert_eq!(
+            err,
+            Incompatible::ClientTooNew {
+                client: PROTOCOL_VERSION,
+                server: PROTOCOL_VERSION - 1,
+            }
+        );
+        assert!(err.to_string().contains("upgrade the server"));
+    }
+
+    #[test]
+    fn previous_client_against_current_server() {
+        let previous = PROTOCOL_VERSION - 1;
+        // A server that still decodes the previous format accepts it
+        let lenient = Server {
+            version: PROTOCOL_VERSION,
+            minimum: previous,
+        };
+        assert_eq!(lenient.accepts(Some(previous)), Ok(()));
+        assert_eq!(
+            CURRENT.accepts(Some(previous)).is_ok(),
+            previous >= MIN_CLIENT_PROTOCOL_VERSION
+        );
+    }
+
+    #[test]
+    fn reads_the_version_from_the_query() {
+        assert_eq!(client_version(Some("protocol=3")), Some(3));
+        assert_eq!(client_version(Some("a=b&protocol=2")), Some(2));
+        assert_eq!(client_version(Some("protocol=x")), None);
+        assert_eq!(client_version(None), None);
+    }
+}
@@ -29,6 +29,9 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
         Kv,
         Sql,
         Blobs,
+        Images,
+        Files,
+        Request,
     }
 
     let mut arg_kinds = Vec::new();
@@ -57,11 +60,27 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     Some("Kv") => arg_kinds.push(ArgKind::Kv),
                     Some("Sql") => arg_kinds.push(ArgKind::Sql),
                     Some("Blobs") => arg_kinds.push(ArgKind::Blobs),
+                    Some("Images") => arg_kinds.push(ArgKind::Images),
+                    Some("Files") => arg_kinds.push(ArgKind::Files),
+                    Some("Request") => {
+                        if arg_kinds
+                            .iter()
+                            .any(|kind| matches!(kind, ArgKind::Request))
+                        {
+                            return syn::Error::new_spanned(
+                                &pat_type.ty,
+                                "#[faasta::handler] functions take at most one Request",
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+                        arg_kinds.push(ArgKind::Request);
+                    }
                     other => {
                         return syn::Error::new_spanned(
                             &pat_type.ty,
                             format!(
-                                "unsupported argument type: {:?}. Supported injected types are Kv, Sql, and Blobs",
+                                "unsupported argument type: {:?}. Supported injected types are Request, Kv, Sql, Blobs, Images, and Files",
                                 other.unwrap_or("<unknown>")
                             ),
                         )
@@ -81,8 +100,21 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
             ArgKind::Kv => quote! { ::faasta::kv::Kv::default() },
             ArgKind::Sql => quote! { ::faasta::sql::Sql::default() },
             ArgKind::Blobs => quote! { ::faasta::blob::Blobs::default() },
+            ArgKind::Images => quote! { ::faasta::image::Images::default() },
+            ArgKind::Files => quote! { ::faasta::fs::Files::default() },
+            ArgKind::Request => quote! { __faasta_request },
         })
         .collect();
+    let read_request = if arg_kinds
+        .iter()
+        .any(|kind| matches!(kind, ArgKind::Request))
+    {
+        quote! {
+            let __faasta_request = ::faasta::http::Request::from_wasi(_request).await;
+        }
+    } else {
+        quote! {}
+    };
 
     let output = quote! {
         #input
@@ -96,6 +128,7 @@ pub fn handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 ::faasta::__private::wasip3::http::types::Response,
                 ::faasta::__private::wasip3::http::types::ErrorCode,
             > {
+                #read_request
                 ::faasta::__private::response_from_result(
                     #original_fn_name(#(#call_args),*).await
                 )
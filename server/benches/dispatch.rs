@@ -0,0 +1,176 @@
+//! Benchmarks for the request dispatch path.
+//!
+//! The component benches run the component in `tests/fixtures`, which answers every
+//! request with an empty 200, unless `FAASTA_BENCH_COMPONENT` points at another WASI
+//! HTTP component, e.g.
+//!
+//! ```sh
+//! FAASTA_BENCH_COMPONENT=path/to/function.wasm cargo bench -p server --bench dispatch
+//! ```
+//!
+//! Baselines to compare against are kept in `benches/baselines`.
+
+use std::path::PathBuf;
+
+use bytes::Bytes;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use faasta_server::scheduler::CpuScheduler;
+use faasta_server::wasi_server::{build_faasta_request, faasta_response_to_http};
+use faasta_server::wasm_function::{
+    InstanceSettings, PoolConfig, WasmFunctionRuntime, WasmRequest, WasmResponse,
+};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
+use tokio::runtime::Runtime;
+
+const BENCH_FUNCTION: &str = "bench-function";
+const HEADER_COUNTS: [usize; 3] = [0, 16, 64];
+const BODY_SIZES: [usize; 3] = [0, 64 * 1024, 1024 * 1024];
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/hello.wat");
+
+fn request_headers(count: usize) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for index in 0..count {
+        headers.insert(
+            HeaderName::from_bytes(format!("x-bench-header-{index}").as_bytes()).unwrap(),
+            HeaderValue::from_static("a-reasonably-sized-header-value"),
+        );
+    }
+    headers
+}
+
+fn request_conversion(c: &mut Criterion) {
+    let uri: Uri = "/bench-function/items?page=2".parse().unwrap();
+
+    let mut group = c.benchmark_group("build_faasta_request/headers");
+    for count in HEADER_COUNTS {
+        let headers = request_headers(count);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &headers,
+            |b, headers| {
+                b.iter(|| {
+                    build_faasta_request(Method::GET, uri.clone(), headers.clone(), Bytes::new())
+                })
+            },
+        );
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("build_faasta_request/body");
+    for size in BODY_SIZES {
+        let body = Bytes::from(vec![0u8; size]);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &body, |b, body| {
+            b.iter(|| {
+                build_faasta_request(Method::POST, uri.clone(), HeaderMap::new(), body.clone())
+            })
+        });
+    }
+    group.finish();
+}
+
+fn response_conversion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("faasta_response_to_http/headers");
+    for count in HEADER_COUNTS {
+        let headers = request_headers(count);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &headers,
+            |b, headers| {
+                b.iter_batched(
+                    || WasmResponse {
+                        status: StatusCode::OK,
+                        headers: headers.clone(),
+                        body: Bytes::new(),
+                        stream: None,
+                        trailers: HeaderMap::new(),
+                        timing: Default::default(),
+                        cpu_meter: None,
+                    },
+                    faasta_response_to_http,
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("faasta_response_to_http/body");
+    let headers = request_headers(4);
+    for size in BODY_SIZES {
+        let body = Bytes::from(vec![0u8; size]);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &body, |b, body| {
+            b.iter_batched(
+                || WasmResponse {
+                    status: StatusCode::OK,
+                    headers: headers.clone(),
+                    body: body.clone(),
+                    stream: None,
+                    trailers: HeaderMap::new(),
+                    timing: Default::default(),
+                    cpu_meter: None,
+                },
+                faasta_response_to_http,
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn component(c: &mut Criterion) {
+    // Holds the fixture's artifact, when it is the one benched
+    let dir = tempfile::tempdir().unwrap();
+    let artifact_path = match std::env::var_os("FAASTA_BENCH_COMPONENT") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let path = dir.path().join(format!("{BENCH_FUNCTION}.wasm"));
+            std::fs::write(&path, wat::parse_file(FIXTURE).unwrap()).unwrap();
+            path
+        }
+    };
+
+    let runtime = Runtime::new().unwrap();
+    let wasm = runtime
+        .block_on(WasmFunctionRuntime::new(
+            PoolConfig::default(),
+            CpuScheduler::default(),
+        ))
+        .unwrap();
+    // The first load compiles the component; everything measured below is a cache hit.
+    runtime
+        .block_on(wasm.warm(BENCH_FUNCTION, BENCH_FUNCTION, &artifact_path))
+        .unwrap();
+
+    c.bench_function("component/instantiate_cached", |b| {
+        b.to_async(&runtime).iter(|| async {
+            wasm.warm(BENCH_FUNCTION, BENCH_FUNCTION, &artifact_path)
+                .await
+                .unwrap()
+        })
+    });
+
+    let request = WasmRequest {
+        method: Method::GET,
+        uri: Uri::from_static("/"),
+        headers: request_headers(8),
+        body: Bytes::new(),
+    };
+    c.bench_function("component/invoke", |b| {
+        b.to_async(&runtime).iter(|| async {
+            wasm.invoke(
+                BENCH_FUNCTION,
+                BENCH_FUNCTION,
+                &artifact_path,
+                request.clone(),
+                &InstanceSettings::default(),
+            )
+            .await
+            .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, request_conversion, response_conversion, component);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use bytes::Bytes;
+use faasta_server::wasi_server::{build_faasta_request, faasta_response_to_http};
+use faasta_server::wasm_function::WasmResponse;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    method: &'a [u8],
+    uri: &'a [u8],
+    headers: Vec<(&'a [u8], &'a [u8])>,
+    body: &'a [u8],
+    status: u16,
+}
+
+fn headers(pairs: &[(&[u8], &[u8])]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        if let (Ok(name), Ok(value)) =
+            (HeaderName::from_bytes(name), HeaderValue::from_bytes(value))
+        {
+            headers.append(name, value);
+        }
+    }
+    headers
+}
+
+fuzz_target!(|input: Input<'_>| {
+    let (Ok(method), Ok(uri)) = (Method::from_bytes(input.method), Uri::try_from(input.uri)) else {
+        return;
+    };
+    let headers = headers(&input.headers);
+    let body = Bytes::copy_from_slice(input.body);
+
+    let request = build_faasta_request(method.clone(), uri.clone(), headers.clone(), body.clone());
+    assert_eq!(request.method, method);
+    assert_eq!(request.uri, uri);
+    assert_eq!(request.headers, headers);
+    assert_eq!(request.body, body);
+
+    let Ok(status) = StatusCode::from_u16(input.status) else {
+        return;
+    };
+    let response = faasta_response_to_http(WasmResponse {
+        status,
+        headers: headers.clone(),
+        body,
+        stream: None,
+        trailers: HeaderMap::new(),
+        timing: Default::default(),
+    });
+    assert_eq!(response.status(), status);
+    assert_eq!(response.headers(), &headers);
+});
@@ -0,0 +1,31 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use faasta_server::wasi_server::{
+    allow_nested_subdomains, function_name_from_host, resolve_function_name, sanitize_function_name,
+};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    host: Option<&'a str>,
+    path: &'a str,
+    base_domain: &'a str,
+    nested: bool,
+}
+
+fuzz_target!(|input: Input<'_>| {
+    allow_nested_subdomains(input.nested);
+    if let Some(name) = function_name_from_host(input.host, input.base_domain) {
+        // Names taken from the Host header are always valid and canonical
+        assert_eq!(sanitize_function_name(&name).as_ref(), Some(&name));
+        assert_eq!(name, name.to_ascii_lowercase());
+        assert_eq!(
+            resolve_function_name(input.host, input.path, input.base_domain),
+            Some(name)
+        );
+    } else if let Some(name) = resolve_function_name(input.host, input.path, input.base_domain) {
+        assert!(!name.is_empty());
+        assert!(!name.contains('/'));
+    }
+});
@@ -0,0 +1,49 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use faasta_interface::{RouteAction, RouteConfig, RouteRule, TrailingSlash};
+use faasta_server::{inspect, routes};
+use http::Uri;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    uri: &'a str,
+    mount: Option<&'a str>,
+    rules: Vec<(&'a str, &'a str, Option<u16>)>,
+    trailing_slash: Option<bool>,
+    strip_prefix: bool,
+}
+
+fuzz_target!(|input: Input<'_>| {
+    let Ok(uri) = input.uri.parse::<Uri>() else {
+        return;
+    };
+    let mount = input
+        .mount
+        .map(|name| format!("/{name}"))
+        .unwrap_or_default();
+    let config = RouteConfig {
+        rules: input
+            .rules
+            .into_iter()
+            .map(|(source, destination, redirect)| RouteRule {
+                source: source.to_string(),
+                destination: destination.to_string(),
+                action: redirect.map_or(RouteAction::Rewrite, RouteAction::Redirect),
+            })
+            .collect(),
+        trailing_slash: input.trailing_slash.map(|always| {
+            if always {
+                TrailingSlash::Always
+            } else {
+                TrailingSlash::Never
+            }
+        }),
+        strip_prefix: input.strip_prefix,
+    };
+
+    let _ = routes::path_below_mount(uri.path(), &mount);
+    let _ = inspect::probe(&uri, &mount);
+    let _ = routes::apply_routes(&config, &mount, &uri);
+});
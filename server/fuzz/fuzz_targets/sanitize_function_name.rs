@@ -0,0 +1,16 @@
+#![no_main]
+
+use faasta_server::wasi_server::sanitize_function_name;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|name: &str| {
+    if let Some(sanitized) = sanitize_function_name(name) {
+        assert_eq!(sanitized, name);
+        assert!(!sanitized.is_empty());
+        assert!(
+            sanitized
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+        );
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use faasta_server::wasm_function::{rewrite_qmark_params, validate_single_statement};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|query: &str| {
+    let _ = validate_single_statement(query);
+    let rewritten = rewrite_qmark_params(query);
+    if !query.contains('?') {
+        assert_eq!(rewritten, query);
+    }
+    // Rewriting only touches placeholders, so it never changes the statement count
+    assert_eq!(
+        validate_single_statement(&rewritten).is_ok(),
+        validate_single_statement(query).is_ok()
+    );
+});
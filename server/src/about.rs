@@ -0,0 +1,57 @@
+//! The `/_faasta/about` page: a function's README rendered by the host, so every
+//! function can describe itself without guest code.
+
+use axum::body::Body;
+use http::{HeaderValue, Method, Response, StatusCode, header};
+use pulldown_cmark::{Event, Options, Parser, html};
+
+/// Path, relative to the function, at which its README is served.
+pub const PATH: &str = "/_faasta/about";
+
+/// Raw HTML in the README is shown as text, and the page may not run scripts.
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; style-src 'unsafe-inline'; img-src https: data:; frame-ancestors 'none'";
+
+const STYLE: &str = "body{max-width:46rem;margin:2rem auto;padding:0 1rem;\
+font-family:system-ui,sans-serif;line-height:1.6;color-scheme:light dark}\
+pre{overflow-x:auto;padding:.75rem;border:1px solid #8884}\
+code{font-size:.9em}table{border-collapse:collapse}td,th{border:1px solid #8884;padding:.25rem .5rem}\
+img{max-width:100%}footer{margin-top:3rem;font-size:.85em;opacity:.7}";
+
+/// Render `markdown` as the about page of `function_name`.
+pub fn render(function_name: &str, markdown: &str, method: &Method) -> Response<Body> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        event => event,
+    });
+    let mut content = String::new();
+    html::push_html(&mut content, events);
+
+    // Function names are restricted to [A-Za-z0-9_-], so they need no escaping
+    let name = function_name;
+    let page = format!(
+        "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+<title>{name}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<main>\n{content}</main>\n\
+<footer>{name} runs on Faasta</footer>\n</body>\n</html>\n"
+    );
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::from(page)
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(CONTENT_SECURITY_POLICY),
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .body(body)
+        .unwrap()
+}
@@ -0,0 +1,145 @@
+//! Admission control for function invocations.
+//!
+//! Every invocation needs a slot from the instance pool, and a request that finds the
+//! pool exhausted fails late, after its body has been read and the component loaded.
+//! The controller turns requests away early, with a 503 and `Retry-After`, once
+//! in-flight invocations reach a watermark below the pool size or the runtime's task
+//! queue backs up. Each priority class has its own threshold, so batch traffic is shed
+//! first and critical traffic last.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use faasta_interface::{AdmissionStats, Priority};
+use tokio::runtime::Handle;
+
+/// Share of the standard limit available to batch traffic, in percent.
+const BATCH_SHARE_PERCENT: usize = 75;
+/// How often a waiting invocation checks for a free slot.
+const WAIT_POLL: Duration = Duration::from_millis(100);
+
+pub struct AdmissionController {
+    in_flight: Arc<AtomicUsize>,
+    /// Pool capacity; critical traffic may use all of it
+    capacity: usize,
+    /// Watermark at which standard traffic is shed
+    limit: usize,
+    max_queue_depth: usize,
+    shed_critical: AtomicU64,
+    shed_standard: AtomicU64,
+    shed_batch: AtomicU64,
+    retry_after_secs: u64,
+}
+
+/// Held for the duration of an admitted invocation, including the time its response
+/// body takes to stream.
+pub struct AdmissionPermit {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl AdmissionController {
+    /// `capacity` is the number of invocations the instance pool can hold at once;
+    /// standard traffic stops being admitted at `watermark_percent` of it.
+    pub fn new(
+        capacity: usize,
+        watermark_percent: u8,
+        max_queue_depth: usize,
+        retry_after_secs: u64,
+    ) -> Self {
+        let capacity = capacity.max(1);
+        let limit = (capacity * usize::from(watermark_percent) / 100).max(1);
+        Self {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            capacity,
+            limit,
+            max_queue_depth,
+            shed_critical: AtomicU64::new(0),
+            shed_standard: AtomicU64::new(0),
+            shed_batch: AtomicU64::new(0),
+            retry_after_secs,
+        }
+    }
+
+    /// Admit an invocation of the given priority, or record it as shed and return `None`.
+    pub fn try_admit(&self, priority: Priority) -> Option<AdmissionPermit> {
+        if let Some(permit) = self.admit(priority) {
+            return Some(permit);
+        }
+        let shed = match priority {
+            Priority::Critical => &self.shed_critical,
+            Priority::Standard => &self.shed_standard,
+            Priority::Batch => &self.shed_batch,
+        };
+        shed.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Wait up to `timeout` for the invocation to be admitted, for work that is queued
+    /// rather than shed, such as asynchronous invocations.
+    pub async fn admit_within(
+        &self,
+        priority: Priority,
+        timeout: Duration,
+    ) -> Option<AdmissionPermit> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(permit) = self.admit(priority) {
+                return Some(permit);
+            }
+            if tokio::time::Instant::now() + WAIT_POLL > deadline {
+                return None;
+            }
+            tokio::time::sleep(WAIT_POLL).await;
+        }
+    }
+
+    fn admit(&self, priority: Priority) -> Option<AdmissionPermit> {
+        let (limit, max_queue_depth) = match priority {
+            // Critical traffic ignores the queue and may fill the whole pool
+            Priority::Critical => (self.capacity, 0),
+            Priority::Standard => (self.limit, self.max_queue_depth),
+            Priority::Batch => (
+                (self.limit * BATCH_SHARE_PERCENT / 100).max(1),
+                self.max_queue_depth / 2,
+            ),
+        };
+        let queue_backed_up = max_queue_depth > 0
+            && Handle::try_current()
+                .is_ok_and(|handle| handle.metrics().global_queue_depth() > max_queue_depth);
+        let admitted = !queue_backed_up
+            && self
+                .in_flight
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |in_flight| {
+                    (in_flight < limit).then_some(in_flight + 1)
+                })
+                .is_ok();
+        admitted.then(|| AdmissionPermit {
+            in_flight: self.in_flight.clone(),
+        })
+    }
+
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after_secs
+    }
+
+    pub fn stats(&self) -> AdmissionStats {
+        let shed_critical = self.shed_critical.load(Ordering::Relaxed);
+        let shed_standard = self.shed_standard.load(Ordering::Relaxed);
+        let shed_batch = self.shed_batch.load(Ordering::Relaxed);
+        AdmissionStats {
+            in_flight: self.in_flight.load(Ordering::Relaxed) as u64,
+            limit: self.limit as u64,
+            shed_requests: shed_critical + shed_standard + shed_batch,
+            shed_critical,
+            shed_standard,
+            shed_batch,
+        }
+    }
+}
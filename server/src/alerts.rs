@@ -0,0 +1,50 @@
+//! Operator alerts. Every alert is logged; with `ALERT_WEBHOOK_URL` set it is also
+//! posted as JSON, with a `text` field so Slack-compatible webhooks display it as is.
+
+use anyhow::{Context, Result};
+use reqwest::Client as HttpClient;
+use serde_json::json;
+use tracing::warn;
+
+pub struct Alerts {
+    webhook_url: Option<String>,
+    client: HttpClient,
+}
+
+impl Alerts {
+    pub fn from_env() -> Self {
+        Self {
+            webhook_url: std::env::var("ALERT_WEBHOOK_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Raise an alert. Delivery failures are logged, never returned.
+    pub async fn send(&self, summary: &str, details: &str) {
+        warn!("ALERT: {summary}: {details}");
+        if let Err(err) = self.post(summary, details).await {
+            warn!("failed to deliver alert to the webhook: {err:#}");
+        }
+    }
+
+    async fn post(&self, summary: &str, details: &str) -> Result<()> {
+        let Some(url) = &self.webhook_url else {
+            return Ok(());
+        };
+        self.client
+            .post(url)
+            .json(&json!({
+                "text": format!("{summary}\n{details}"),
+                "summary": summary,
+                "details": details,
+            }))
+            .send()
+            .await
+            .context("request failed")?
+            .error_for_status()
+            .context("webhook rejected the alert")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use axum::body::Body;
+use bytes::Bytes;
+use http::{Method, Response, StatusCode, header};
+
+const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nAllow: /\n";
+/// The landing page and docs from the `faasta-website` example.
+const WEBSITE: &str = include_str!("../../examples/faasta-website/src/index.html");
+const CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// `/robots.txt`, `/favicon.ico` and optionally the landing page at `/` for the base
+/// domain, which would otherwise be resolved as function names.
+pub struct ApexFiles {
+    robots_txt: Bytes,
+    favicon: Option<Bytes>,
+    website: bool,
+}
+
+impl ApexFiles {
+    /// Load operator-supplied files, falling back to an allow-all robots.txt and an
+    /// empty favicon response. With `website`, `/` serves the built-in landing page.
+    pub fn load(
+        robots_txt_path: Option<&Path>,
+        favicon_path: Option<&Path>,
+        website: bool,
+    ) -> Result<Self> {
+        let robots_txt = match robots_txt_path {
+            Some(path) => fs::read(path)
+                .with_context(|| format!("failed to read robots.txt from {}", path.display()))?
+                .into(),
+            None => Bytes::from_static(DEFAULT_ROBOTS_TXT.as_bytes()),
+        };
+        let favicon = favicon_path
+            .map(|path| {
+                fs::read(path)
+                    .with_context(|| format!("failed to read favicon from {}", path.display()))
+            })
+            .transpose()?
+            .map(Bytes::from);
+        Ok(Self {
+            robots_txt,
+            favicon,
+            website,
+        })
+    }
+
+    /// The built-in response for `path`, if it is one of the apex files.
+    pub fn serve(&self, method: &Method, path: &str) -> Option<Response<Body>> {
+        if method != Method::GET && method != Method::HEAD {
+            return None;
+        }
+        let (content_type, body) = match path {
+            "/robots.txt" => ("text/plain; charset=utf-8", Some(self.robots_txt.clone())),
+            "/favicon.ico" => ("image/x-icon", self.favicon.clone()),
+            "/" if self.website => (
+                "text/html; charset=utf-8",
+                Some(Bytes::from_static(WEBSITE.as_bytes())),
+            ),
+            _ => return None,
+        };
+
+        let builder = Response::builder().header(header::CACHE_CONTROL, CACHE_CONTROL);
+        let response = match body {
+            Some(body) => {
+                let body = if method == Method::HEAD {
+                    Body::empty()
+                } else {
+                    Body::from(body)
+                };
+                builder
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_TYPE, content_type)
+                    .body(body)
+            }
+            None => builder.status(StatusCode::NO_CONTENT).body(Body::empty()),
+        };
+        response.ok()
+    }
+}
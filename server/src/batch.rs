@@ -0,0 +1,156 @@
+//! `POST /v1/batch`: several invocations of the caller's functions in one round trip.
+//!
+//! The body lists sub-requests, each naming a function, method, path, headers and a
+//! text body. They run concurrently, each admitted like any other request, and the
+//! response lists their results in the same order. Route rules, experiments and the
+//! response cache don't apply; a sub-request goes straight to the named function.
+
+use std::collections::BTreeMap;
+
+use axum::body::{Body, to_bytes};
+use bytes::Bytes;
+use faasta_interface::{ErrorBody, error_code};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Response, StatusCode, Uri, header};
+use serde::{Deserialize, Serialize};
+
+use crate::error_body;
+
+/// Sub-requests accepted in one batch
+pub const MAX_REQUESTS: usize = 50;
+/// Largest batch body
+pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Largest sub-response body returned; longer ones are replaced by an error
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub requests: Vec<SubRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubRequest {
+    pub function: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Path and query the function sees
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    /// Response body, when it is UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Response body in hex, when it is not UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_hex: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    pub responses: Vec<SubResponse>,
+}
+
+/// A sub-request ready to invoke.
+pub struct Invocation {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl SubRequest {
+    pub fn invocation(&self) -> Result<Invocation, String> {
+        let method = Method::from_bytes(self.method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| format!("invalid method '{}'", self.method))?;
+        if !self.path.starts_with('/') {
+            return Err(format!("path '{}' must start with '/'", self.path));
+        }
+        let uri = self
+            .path
+            .parse::<Uri>()
+            .map_err(|_| format!("invalid path '{}'", self.path))?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid header name '{name}'"))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid value for header '{name}'"))?;
+            headers.append(name, value);
+        }
+        Ok(Invocation {
+            method,
+            uri,
+            headers,
+            body: Bytes::from(self.body.clone()),
+        })
+    }
+}
+
+impl SubResponse {
+    /// Buffer a function's (or the host's error) response.
+    pub async fn collect(response: Response<Body>) -> Self {
+        let (parts, body) = response.into_parts();
+        let mut headers = BTreeMap::new();
+        for (name, value) in &parts.headers {
+            if let Ok(value) = value.to_str() {
+                headers
+                    .entry(name.to_string())
+                    .and_modify(|existing: &mut String| {
+                        existing.push_str(", ");
+                        existing.push_str(value);
+                    })
+                    .or_insert_with(|| value.to_string());
+            }
+        }
+        let body = match to_bytes(body, MAX_RESPONSE_BYTES).await {
+            Ok(body) => body,
+            Err(_) => {
+                return Self::error(
+                    StatusCode::BAD_GATEWAY,
+                    &error_body::body(
+                        error_code::RESULT_TOO_LARGE,
+                        format!("Response is larger than {} KiB", MAX_RESPONSE_BYTES / 1024),
+                    ),
+                );
+            }
+        };
+        let (body, body_hex) = match String::from_utf8(body.to_vec()) {
+            Ok(text) => (Some(text), None),
+            Err(err) => (None, Some(hex::encode(err.into_bytes()))),
+        };
+        Self {
+            status: parts.status.as_u16(),
+            headers,
+            body,
+            body_hex,
+        }
+    }
+
+    pub fn error(status: StatusCode, error: &ErrorBody) -> Self {
+        Self {
+            status: status.as_u16(),
+            headers: BTreeMap::from([(
+                header::CONTENT_TYPE.to_string(),
+                "application/json".to_string(),
+            )]),
+            body: serde_json::to_string(error).ok(),
+            body_hex: None,
+        }
+    }
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
@@ -1,13 +1,28 @@
 use anyhow::{Context, Result};
-use reqwest::Client as HttpClient;
+use rcgen::{CertificateParams, KeyPair};
+use reqwest::{Client as HttpClient, RequestBuilder};
+use ring::digest::{SHA1_FOR_LEGACY_USE_ONLY, digest};
+use rustls::pki_types::CertificateDer;
+use serde::de::{DeserializeOwned, IgnoredAny};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
 use std::env;
+use std::fmt;
 use std::fs;
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
+use tokio::sync::OnceCell;
 use tokio::time;
 use tracing::{info, warn};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::{GeneralName, ParsedExtension};
+use x509_parser::oid_registry::OID_PKIX_ACCESS_DESCRIPTOR_OCSP;
+
+use crate::alerts::Alerts;
+use crate::tls::ServerCertificate;
 
 // Porkbun API response structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,15 +47,40 @@ struct PorkbunRequest {
     pub secretapikey: String,
 }
 
+// Read the Porkbun API keys from the environment
+fn porkbun_credentials() -> Result<PorkbunRequest> {
+    let apikey = env::var("PORKBUN_API_KEY").map_err(|_| {
+        anyhow::anyhow!(
+            "PORKBUN_API_KEY environment variable not set. Please set it to your Porkbun API key."
+        )
+    })?;
+    let secretapikey = env::var("PORKBUN_SECRET_API_KEY").map_err(|_| {
+        anyhow::anyhow!(
+            "PORKBUN_SECRET_API_KEY environment variable not set. Please set it to your Porkbun Secret API key."
+        )
+    })?;
+    Ok(PorkbunRequest {
+        apikey,
+        secretapikey,
+    })
+}
+
 pub struct CertManager {
     domain: String,
+    provider: DnsProviderKind,
     cert_path: PathBuf,
     key_path: PathBuf,
     client: HttpClient,
 }
 
 impl CertManager {
-    pub fn new(domain: String, certs_dir: PathBuf, cert_path: PathBuf, key_path: PathBuf) -> Self {
+    pub fn new(
+        domain: String,
+        provider: DnsProviderKind,
+        certs_dir: PathBuf,
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    ) -> Self {
         // Make sure the certs directory exists
         if !certs_dir.exists() {
             fs::create_dir_all(&certs_dir).expect("Failed to create certificates directory");
@@ -48,6 +88,7 @@ impl CertManager {
 
         Self {
             domain,
+            provider,
             cert_path,
             key_path,
             client: HttpClient::new(),
@@ -119,24 +160,10 @@ impl CertManager {
 
     // Retrieve SSL certificate from Porkbun API
     async fn get_ssl(&self) -> Result<PorkbunResponse> {
-        // Get API keys from environment variables
-        let apikey = match env::var("PORKBUN_API_KEY") {
-            Ok(key) => key,
-            Err(_) => {
-                return Err(anyhow::anyhow!(
-                    "PORKBUN_API_KEY environment variable not set. Please set it to your Porkbun API key."
-                ));
-            }
-        };
-
-        let secretapikey = match env::var("PORKBUN_SECRET_API_KEY") {
-            Ok(key) => key,
-            Err(_) => {
-                return Err(anyhow::anyhow!(
-                    "PORKBUN_SECRET_API_KEY environment variable not set. Please set it to your Porkbun Secret API key."
-                ));
-            }
-        };
+        let PorkbunRequest {
+            apikey,
+            secretapikey,
+        } = porkbun_credentials()?;
 
         let url = format!(
             "https://api.porkbun.com/api/json/v3/ssl/retrieve/{}",
@@ -162,8 +189,8 @@ impl CertManager {
         );
 
         let request_body = PorkbunRequest {
-            apikey: apikey.clone(),
-            secretapikey: secretapikey.clone(),
+            apikey,
+            secretapikey,
         };
 
         info!("Sending request to Porkbun API for domain: {}", self.domain);
@@ -216,41 +243,45 @@ impl CertManager {
             return Ok(());
         }
 
-        // Get certificates from Porkbun API
         info!("Downloading certificates for domain: {}", self.domain);
-        let cert_json = self.get_ssl().await?;
+        let (cert_chain, private_key) = match self.provider {
+            DnsProviderKind::Porkbun => {
+                let cert_json = self.get_ssl().await?;
+                (
+                    cert_json
+                        .certificate_chain
+                        .context("Certificate chain missing in Porkbun API response")?,
+                    cert_json
+                        .private_key
+                        .context("Private key missing in Porkbun API response")?,
+                )
+            }
+            DnsProviderKind::Cloudflare => {
+                CloudflareDns::from_env(self.domain.clone())?
+                    .origin_certificate()
+                    .await?
+            }
+        };
 
         // Save domain certificate
-        if let Some(cert_chain) = cert_json.certificate_chain {
-            info!("Installing domain certificate to {:?}", self.cert_path);
-            tokio::fs::write(&self.cert_path, cert_chain.into_bytes())
-                .await
-                .context("Failed to write certificate file")?;
-        } else {
-            return Err(anyhow::anyhow!(
-                "Certificate chain missing in Porkbun API response"
-            ));
-        }
+        info!("Installing domain certificate to {:?}", self.cert_path);
+        tokio::fs::write(&self.cert_path, cert_chain.into_bytes())
+            .await
+            .context("Failed to write certificate file")?;
 
         // Save private key
-        if let Some(private_key) = cert_json.private_key {
-            info!("Installing private key to {:?}", self.key_path);
-            let key_path = &self.key_path;
-            tokio::fs::write(key_path, private_key.into_bytes())
-                .await
-                .context("Failed to write private key file")?;
-            // Ensure restrictive permissions on the private key file.
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let mut perms = fs::metadata(key_path)?.permissions();
-                perms.set_mode(0o600);
-                fs::set_permissions(key_path, perms)?;
-            }
-        } else {
-            return Err(anyhow::anyhow!(
-                "Private key missing in Porkbun API response"
-            ));
+        info!("Installing private key to {:?}", self.key_path);
+        let key_path = &self.key_path;
+        tokio::fs::write(key_path, private_key.into_bytes())
+            .await
+            .context("Failed to write private key file")?;
+        // Ensure restrictive permissions on the private key file.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(key_path)?.permissions();
+            perms.set_mode(0o600);
+            fs::set_permissions(key_path, perms)?;
         }
 
         info!(
@@ -281,3 +312,837 @@ impl CertManager {
         });
     }
 }
+
+/// How often the stapled OCSP response is replaced. Responders issue responses valid
+/// for several days, so this leaves plenty of room for failed fetches.
+const OCSP_REFRESH: Duration = Duration::from_secs(12 * 60 * 60);
+const OCSP_RETRY: Duration = Duration::from_secs(60 * 60);
+
+/// Keep a fresh OCSP response stapled to the handshakes of `certificate`.
+pub fn spawn_ocsp_stapling(certificate: Arc<ServerCertificate>) {
+    tokio::spawn(async move {
+        let client = HttpClient::new();
+        loop {
+            let delay = match fetch_ocsp(&client, &certificate.chain()).await {
+                Ok(Some(response)) => {
+                    info!("Stapling a fresh OCSP response ({} bytes)", response.len());
+                    certificate.set_ocsp(Some(response));
+                    OCSP_REFRESH
+                }
+                Ok(None) => {
+                    info!("Certificate names no OCSP responder, not stapling");
+                    return;
+                }
+                Err(e) => {
+                    // Keep stapling the previous response until it can be replaced
+                    warn!("Failed to fetch OCSP response: {e:#}");
+                    OCSP_RETRY
+                }
+            };
+            time::sleep(delay).await;
+        }
+    });
+}
+
+/// Fetch the OCSP response for the leaf of `chain`, or `None` if it has no responder.
+async fn fetch_ocsp(
+    client: &HttpClient,
+    chain: &[CertificateDer<'static>],
+) -> Result<Option<Vec<u8>>> {
+    let [leaf, issuer, ..] = chain else {
+        anyhow::bail!("Certificate chain has no issuer certificate");
+    };
+    let (_, leaf) = x509_parser::parse_x509_certificate(leaf)
+        .map_err(|e| anyhow::anyhow!("Failed to parse certificate: {e}"))?;
+    let (_, issuer) = x509_parser::parse_x509_certificate(issuer)
+        .map_err(|e| anyhow::anyhow!("Failed to parse issuer certificate: {e}"))?;
+    let Some(responder) = ocsp_responder(&leaf) else {
+        return Ok(None);
+    };
+
+    let response = client
+        .post(&responder)
+        .header(reqwest::header::CONTENT_TYPE, "application/ocsp-request")
+        .body(ocsp_request(&leaf, &issuer))
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach OCSP responder {responder}"))?
+        .error_for_status()
+        .with_context(|| format!("OCSP responder {responder} returned an error"))?
+        .bytes()
+        .await
+        .context("Failed to read OCSP response")?;
+    match ocsp_response_status(&response) {
+        Some(0) => Ok(Some(response.to_vec())),
+        status => Err(anyhow::anyhow!(
+            "OCSP responder {responder} answered with status {status:?}"
+        )),
+    }
+}
+
+fn ocsp_responder(certificate: &X509Certificate<'_>) -> Option<String> {
+    certificate
+        .extensions()
+        .iter()
+        .find_map(|extension| match extension.parsed_extension() {
+            ParsedExtension::AuthorityInfoAccess(access) => {
+                access.accessdescs.iter().find_map(|description| {
+                    match (&description.access_method, &description.access_location) {
+                        (method, GeneralName::URI(uri))
+                            if *method == OID_PKIX_ACCESS_DESCRIPTOR_OCSP =>
+                        {
+                            Some(uri.to_string())
+                        }
+                        _ => None,
+                    }
+                })
+            }
+            _ => None,
+        })
+}
+
+/// DER-encoded OCSP request for a single certificate (RFC 6960).
+fn ocsp_request(certificate: &X509Certificate<'_>, issuer: &X509Certificate<'_>) -> Vec<u8> {
+    // Responders universally accept SHA-1 CertIDs
+    let sha1 = |data: &[u8]| digest(&SHA1_FOR_LEGACY_USE_ONLY, data).as_ref().to_vec();
+    let algorithm = der(
+        0x30,
+        &[der(0x06, &[0x2b, 0x0e, 0x03, 0x02, 0x1a]), vec![0x05, 0x00]].concat(),
+    );
+    let cert_id = der(
+        0x30,
+        &[
+            algorithm,
+            der(0x04, &sha1(issuer.subject().as_raw())),
+            der(
+                0x04,
+                &sha1(issuer.public_key().subject_public_key.data.as_ref()),
+            ),
+            der(0x02, certificate.raw_serial()),
+        ]
+        .concat(),
+    );
+    // OCSPRequest { TBSRequest { requestList { Request { CertID } } } }
+    der(0x30, &der(0x30, &der(0x30, &der(0x30, &cert_id))))
+}
+
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if contents.len() < 0x80 {
+        encoded.push(contents.len() as u8);
+    } else {
+        let length = contents.len().to_be_bytes();
+        let skip = length.iter().take_while(|byte| **byte == 0).count();
+        encoded.push(0x80 | (length.len() - skip) as u8);
+        encoded.extend_from_slice(&length[skip..]);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+/// `responseStatus` of a DER-encoded OCSPResponse; 0 is `successful`.
+fn ocsp_response_status(response: &[u8]) -> Option<u8> {
+    let (&0x30, rest) = response.split_first()? else {
+        return None;
+    };
+    let (&length, rest) = rest.split_first()?;
+    let rest = if length & 0x80 != 0 {
+        rest.get(usize::from(length & 0x7f)..)?
+    } else {
+        rest
+    };
+    match rest {
+        [0x0a, 0x01, status, ..] => Some(*status),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CtLogEntry {
+    id: u64,
+    issuer_name: String,
+    name_value: String,
+    serial_number: String,
+    not_before: String,
+}
+
+/// Watches Certificate Transparency logs (through crt.sh) for certificates issued for
+/// the base domain that the server did not deploy.
+pub struct CtMonitor {
+    pub domain: String,
+    /// Certificates from issuers whose name contains one of these are expected
+    pub expected_issuers: Vec<String>,
+    /// The deployed certificate, which is never reported
+    pub cert_path: PathBuf,
+    /// Log entries already examined; the first check only records a baseline
+    pub state_path: PathBuf,
+    pub alerts: Arc<Alerts>,
+}
+
+impl CtMonitor {
+    pub fn spawn(self, interval: Duration) {
+        tokio::spawn(async move {
+            let client = HttpClient::new();
+            loop {
+                if let Err(e) = self.check(&client).await {
+                    warn!("Certificate transparency check failed: {e:#}");
+                }
+                time::sleep(interval).await;
+            }
+        });
+    }
+
+    async fn check(&self, client: &HttpClient) -> Result<()> {
+        let mut entries = Vec::new();
+        for query in [self.domain.clone(), format!("%.{}", self.domain)] {
+            let logged: Vec<CtLogEntry> = client
+                .get("https://crt.sh/")
+                .query(&[("q", query.as_str()), ("output", "json")])
+                .send()
+                .await
+                .context("Failed to query crt.sh")?
+                .error_for_status()
+                .context("crt.sh returned an error")?
+                .json()
+                .await
+                .context("Failed to parse crt.sh response")?;
+            entries.extend(logged);
+        }
+
+        let baseline = !self.state_path.exists();
+        let mut seen: HashSet<u64> = if baseline {
+            HashSet::new()
+        } else {
+            serde_json::from_slice(&fs::read(&self.state_path)?)
+                .with_context(|| format!("Failed to parse {:?}", self.state_path))?
+        };
+        let deployed = self.deployed_serial();
+        for entry in entries {
+            if !seen.insert(entry.id) || baseline || self.is_expected(&entry, deployed.as_deref()) {
+                continue;
+            }
+            self.alerts
+                .send(
+                    &format!("Unexpected certificate issued for {}", self.domain),
+                    &format!(
+                        "{} issued by {} on {} (https://crt.sh/?id={})",
+                        entry.name_value.replace('\n', ", "),
+                        entry.issuer_name,
+                        entry.not_before,
+                        entry.id
+                    ),
+                )
+                .await;
+        }
+        if baseline {
+            info!(
+                "Recorded {} logged certificates as the transparency baseline",
+                seen.len()
+            );
+        }
+        fs::write(&self.state_path, serde_json::to_vec(&seen)?)
+            .with_context(|| format!("Failed to write {:?}", self.state_path))
+    }
+
+    fn is_expected(&self, entry: &CtLogEntry, deployed: Option<&str>) -> bool {
+        deployed == Some(normalize_serial(&entry.serial_number).as_str())
+            || self
+                .expected_issuers
+                .iter()
+                .any(|issuer| entry.issuer_name.contains(issuer.as_str()))
+    }
+
+    fn deployed_serial(&self) -> Option<String> {
+        let pem = fs::read(&self.cert_path).ok()?;
+        let der = rustls_pemfile::certs(&mut std::io::Cursor::new(pem))
+            .next()?
+            .ok()?;
+        let (_, certificate) = x509_parser::parse_x509_certificate(&der).ok()?;
+        Some(normalize_serial(&hex::encode(certificate.raw_serial())))
+    }
+}
+
+fn normalize_serial(serial: &str) -> String {
+    serial.trim_start_matches('0').to_ascii_lowercase()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DnsProviderKind {
+    Porkbun,
+    Cloudflare,
+}
+
+enum DnsBackend {
+    Porkbun(PorkbunDns),
+    Cloudflare(CloudflareDns),
+}
+
+/// Record management for the base domain through the configured provider.
+pub struct DnsManager {
+    domain: String,
+    backend: DnsBackend,
+}
+
+impl DnsManager {
+    /// Connect to `provider` with the API credentials in its environment variables.
+    pub fn from_env(provider: DnsProviderKind, domain: String) -> Result<Self> {
+        let backend = match provider {
+            DnsProviderKind::Porkbun => DnsBackend::Porkbun(PorkbunDns::from_env(domain.clone())?),
+            DnsProviderKind::Cloudflare => {
+                DnsBackend::Cloudflare(CloudflareDns::from_env(domain.clone())?)
+            }
+        };
+        Ok(Self { domain, backend })
+    }
+
+    /// Lowest TTL the provider accepts, in seconds.
+    pub fn min_ttl(&self) -> u32 {
+        match self.backend {
+            DnsBackend::Porkbun(_) => 600,
+            DnsBackend::Cloudflare(_) => 60,
+        }
+    }
+
+    /// Records of `record_type` at `subdomain` (empty for the apex).
+    pub async fn records(&self, record_type: &str, subdomain: &str) -> Result<Vec<DnsRecord>> {
+        match &self.backend {
+            DnsBackend::Porkbun(dns) => dns.records(record_type, subdomain).await,
+            DnsBackend::Cloudflare(dns) => dns.records(record_type, &self.fqdn(subdomain)).await,
+        }
+    }
+
+    pub async fn create_record(
+        &self,
+        record_type: &str,
+        subdomain: &str,
+        content: &str,
+        ttl: u32,
+    ) -> Result<()> {
+        match &self.backend {
+            DnsBackend::Porkbun(dns) => {
+                dns.create_record(record_type, subdomain, content, ttl)
+                    .await
+            }
+            DnsBackend::Cloudflare(dns) => {
+                dns.create_record(record_type, &self.fqdn(subdomain), content, ttl)
+                    .await
+            }
+        }
+    }
+
+    pub async fn delete_record(&self, id: &str) -> Result<()> {
+        match &self.backend {
+            DnsBackend::Porkbun(dns) => dns.delete_record(id).await,
+            DnsBackend::Cloudflare(dns) => dns.delete_record(id).await,
+        }
+    }
+
+    /// Create a record unless one with the same content exists. Returns whether it was created.
+    pub async fn ensure_record(
+        &self,
+        record_type: &str,
+        subdomain: &str,
+        content: &str,
+        ttl: u32,
+    ) -> Result<bool> {
+        let records = self.records(record_type, subdomain).await?;
+        if records.iter().any(|record| record.content == content) {
+            return Ok(false);
+        }
+        self.create_record(record_type, subdomain, content, ttl)
+            .await?;
+        Ok(true)
+    }
+
+    /// Delete the records of `record_type` at `subdomain` that hold `content`, leaving
+    /// any others (e.g. other nodes' addresses) in place.
+    pub async fn remove_record(
+        &self,
+        record_type: &str,
+        subdomain: &str,
+        content: &str,
+    ) -> Result<()> {
+        for record in self.records(record_type, subdomain).await? {
+            if record.content == content {
+                self.delete_record(&record.id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Publish a dns-01 challenge token for `subdomain` (empty for the apex and wildcard).
+    pub async fn set_acme_challenge(&self, subdomain: &str, token: &str) -> Result<()> {
+        self.create_record(
+            "TXT",
+            &acme_challenge_name(subdomain),
+            token,
+            self.min_ttl(),
+        )
+        .await
+    }
+
+    /// Remove every dns-01 challenge token for `subdomain`.
+    pub async fn clear_acme_challenge(&self, subdomain: &str) -> Result<()> {
+        for record in self.records("TXT", &acme_challenge_name(subdomain)).await? {
+            self.delete_record(&record.id).await?;
+        }
+        Ok(())
+    }
+
+    /// Check the apex and wildcard records that function subdomains rely on, custom
+    /// domains that should resolve to them, and leftover ACME challenges. With `fix`,
+    /// missing records are created for `address` and leftover challenges removed.
+    pub async fn verify(
+        &self,
+        address: Option<IpAddr>,
+        custom_domains: &[String],
+        fix: bool,
+    ) -> Result<Vec<DnsCheck>> {
+        let mut checks = Vec::new();
+        let mut served = HashSet::new();
+
+        for subdomain in ["", "*"] {
+            let name = self.fqdn(subdomain);
+            let mut found = Vec::new();
+            for record_type in ["A", "AAAA"] {
+                found.extend(
+                    self.records(record_type, subdomain)
+                        .await?
+                        .into_iter()
+                        .map(|record| record.content),
+                );
+            }
+            served.extend(
+                found
+                    .iter()
+                    .filter_map(|content| content.parse::<IpAddr>().ok()),
+            );
+
+            let missing = match address {
+                Some(address) => !found.contains(&address.to_string()),
+                None => found.is_empty(),
+            };
+            let status = match (missing, address) {
+                (false, _) => DnsStatus::Ok(found),
+                (true, Some(address)) if fix => {
+                    self.ensure_record(
+                        record_type_for(address),
+                        subdomain,
+                        &address.to_string(),
+                        self.min_ttl(),
+                    )
+                    .await?;
+                    served.insert(address);
+                    DnsStatus::Created(address.to_string())
+                }
+                (true, Some(address)) => DnsStatus::Missing(address.to_string()),
+                (true, None) => DnsStatus::Missing("an A or AAAA record".to_string()),
+            };
+            checks.push(DnsCheck { name, status });
+        }
+
+        for domain in custom_domains {
+            let status = match tokio::net::lookup_host((domain.as_str(), 443)).await {
+                Ok(resolved) => {
+                    let resolved: Vec<IpAddr> = resolved.map(|addr| addr.ip()).collect();
+                    if resolved.iter().any(|ip| served.contains(ip)) {
+                        DnsStatus::Ok(resolved.iter().map(IpAddr::to_string).collect())
+                    } else {
+                        DnsStatus::WrongTarget(resolved.iter().map(IpAddr::to_string).collect())
+                    }
+                }
+                Err(err) => DnsStatus::Unresolved(err.to_string()),
+            };
+            checks.push(DnsCheck {
+                name: domain.clone(),
+                status,
+            });
+        }
+
+        // The apex and the wildcard certificate share one challenge name.
+        let challenge_name = acme_challenge_name("");
+        let challenges = self.records("TXT", &challenge_name).await?;
+        if !challenges.is_empty() {
+            if fix {
+                self.clear_acme_challenge("").await?;
+            }
+            checks.push(DnsCheck {
+                name: self.fqdn(&challenge_name),
+                status: DnsStatus::StaleChallenge {
+                    count: challenges.len(),
+                    removed: fix,
+                },
+            });
+        }
+
+        Ok(checks)
+    }
+
+    fn fqdn(&self, subdomain: &str) -> String {
+        if subdomain.is_empty() {
+            self.domain.clone()
+        } else {
+            format!("{subdomain}.{}", self.domain)
+        }
+    }
+}
+
+/// The result of checking one name with [`DnsManager::verify`].
+#[derive(Debug)]
+pub struct DnsCheck {
+    pub name: String,
+    pub status: DnsStatus,
+}
+
+#[derive(Debug)]
+pub enum DnsStatus {
+    /// Present, with these values.
+    Ok(Vec<String>),
+    /// Created by `--fix` with this value.
+    Created(String),
+    /// Expected to hold this value but does not.
+    Missing(String),
+    /// A custom domain that resolves, but not to any address of the base domain.
+    WrongTarget(Vec<String>),
+    /// A custom domain that does not resolve.
+    Unresolved(String),
+    /// Challenge tokens left behind by an earlier dns-01 validation.
+    StaleChallenge { count: usize, removed: bool },
+}
+
+impl DnsCheck {
+    /// Whether the check leaves something for the operator to fix.
+    pub fn is_problem(&self) -> bool {
+        matches!(
+            self.status,
+            DnsStatus::Missing(_)
+                | DnsStatus::WrongTarget(_)
+                | DnsStatus::Unresolved(_)
+                | DnsStatus::StaleChallenge { removed: false, .. }
+        )
+    }
+}
+
+impl fmt::Display for DnsCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = &self.name;
+        match &self.status {
+            DnsStatus::Ok(values) => write!(f, "ok        {name} -> {}", values.join(", ")),
+            DnsStatus::Created(value) => write!(f, "created   {name} -> {value}"),
+            DnsStatus::Missing(value) => write!(f, "missing   {name} should point at {value}"),
+            DnsStatus::WrongTarget(values) => write!(
+                f,
+                "mismatch  {name} resolves to {}, not the base domain",
+                values.join(", ")
+            ),
+            DnsStatus::Unresolved(err) => write!(f, "unresolved {name}: {err}"),
+            DnsStatus::StaleChallenge { count, removed } => write!(
+                f,
+                "{}  {name} has {count} leftover challenge token(s)",
+                if *removed { "removed " } else { "stale   " }
+            ),
+        }
+    }
+}
+
+fn acme_challenge_name(subdomain: &str) -> String {
+    match subdomain {
+        "" | "*" => "_acme-challenge".to_string(),
+        _ => format!("_acme-challenge.{subdomain}"),
+    }
+}
+
+/// `A` for IPv4 addresses, `AAAA` for IPv6.
+pub fn record_type_for(address: IpAddr) -> &'static str {
+    match address {
+        IpAddr::V4(_) => "A",
+        IpAddr::V6(_) => "AAAA",
+    }
+}
+
+/// A DNS record as returned by the provider's API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DnsRecord {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PorkbunDnsResponse {
+    status: String,
+    message: Option<String>,
+    #[serde(default)]
+    records: Vec<DnsRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct PorkbunCreateRecord<'a> {
+    #[serde(flatten)]
+    credentials: &'a PorkbunRequest,
+    name: &'a str,
+    #[serde(rename = "type")]
+    record_type: &'a str,
+    content: &'a str,
+    ttl: String,
+}
+
+/// Record management for a domain hosted on Porkbun.
+pub struct PorkbunDns {
+    domain: String,
+    credentials: PorkbunRequest,
+    client: HttpClient,
+}
+
+impl PorkbunDns {
+    pub fn from_env(domain: String) -> Result<Self> {
+        Ok(Self {
+            domain,
+            credentials: porkbun_credentials()?,
+            client: HttpClient::new(),
+        })
+    }
+
+    /// Records of `record_type` at `subdomain` (empty for the apex).
+    pub async fn records(&self, record_type: &str, subdomain: &str) -> Result<Vec<DnsRecord>> {
+        let mut url = format!(
+            "https://api.porkbun.com/api/json/v3/dns/retrieveByNameType/{}/{record_type}",
+            self.domain
+        );
+        if !subdomain.is_empty() {
+            url.push('/');
+            url.push_str(subdomain);
+        }
+        Ok(self.call(&url, &self.credentials).await?.records)
+    }
+
+    pub async fn create_record(
+        &self,
+        record_type: &str,
+        subdomain: &str,
+        content: &str,
+        ttl: u32,
+    ) -> Result<()> {
+        let url = format!(
+            "https://api.porkbun.com/api/json/v3/dns/create/{}",
+            self.domain
+        );
+        let body = PorkbunCreateRecord {
+            credentials: &self.credentials,
+            name: subdomain,
+            record_type,
+            content,
+            ttl: ttl.to_string(),
+        };
+        self.call(&url, &body).await.map(|_| ())
+    }
+
+    pub async fn delete_record(&self, id: &str) -> Result<()> {
+        let url = format!(
+            "https://api.porkbun.com/api/json/v3/dns/delete/{}/{id}",
+            self.domain
+        );
+        self.call(&url, &self.credentials).await.map(|_| ())
+    }
+
+    async fn call(&self, url: &str, body: &impl Serialize) -> Result<PorkbunDnsResponse> {
+        let response: PorkbunDnsResponse = self
+            .client
+            .post(url)
+            .json(body)
+            .send()
+            .await
+            .context("Failed to send request to Porkbun API")?
+            .json()
+            .await
+            .context("Failed to parse Porkbun API response")?;
+        if response.status == "ERROR" {
+            return Err(anyhow::anyhow!(
+                "Porkbun DNS request failed: {}",
+                response
+                    .message
+                    .unwrap_or_else(|| "Unknown error".to_string())
+            ));
+        }
+        Ok(response)
+    }
+}
+
+const CLOUDFLARE_API: &str = "https://api.cloudflare.com/client/v4";
+/// Longest validity Cloudflare issues origin certificates for (15 years), in days.
+const ORIGIN_CERT_VALIDITY_DAYS: u32 = 5475;
+
+#[derive(Debug, Deserialize)]
+struct CloudflareResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<CloudflareError>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudflareError {
+    code: u32,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudflareZone {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OriginCertificate {
+    certificate: String,
+}
+
+/// Record management and origin certificates for a zone hosted on Cloudflare.
+pub struct CloudflareDns {
+    domain: String,
+    token: String,
+    zone_id: OnceCell<String>,
+    proxied: bool,
+    client: HttpClient,
+}
+
+impl CloudflareDns {
+    pub fn from_env(domain: String) -> Result<Self> {
+        let token = env::var("CLOUDFLARE_API_TOKEN").map_err(|_| {
+            anyhow::anyhow!(
+                "CLOUDFLARE_API_TOKEN environment variable not set. Please set it to a Cloudflare API token with DNS and SSL edit access to the zone."
+            )
+        })?;
+        Ok(Self {
+            domain,
+            token,
+            // Looked up from the domain on first use when not given
+            zone_id: OnceCell::new_with(env::var("CLOUDFLARE_ZONE_ID").ok()),
+            proxied: env::var("CLOUDFLARE_PROXIED").is_ok_and(|value| value == "true"),
+            client: HttpClient::new(),
+        })
+    }
+
+    /// Records of `record_type` named `name` (fully qualified).
+    pub async fn records(&self, record_type: &str, name: &str) -> Result<Vec<DnsRecord>> {
+        let url = format!(
+            "{CLOUDFLARE_API}/zones/{}/dns_records",
+            self.zone_id().await?
+        );
+        self.call(
+            self.client
+                .get(url)
+                .query(&[("type", record_type), ("name", name)]),
+        )
+        .await
+    }
+
+    pub async fn create_record(
+        &self,
+        record_type: &str,
+        name: &str,
+        content: &str,
+        ttl: u32,
+    ) -> Result<()> {
+        let url = format!(
+            "{CLOUDFLARE_API}/zones/{}/dns_records",
+            self.zone_id().await?
+        );
+        let body = json!({
+            "type": record_type,
+            "name": name,
+            "content": content,
+            "ttl": ttl,
+            "proxied": self.proxied && matches!(record_type, "A" | "AAAA" | "CNAME"),
+        });
+        self.call::<IgnoredAny>(self.client.post(url).json(&body))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn delete_record(&self, id: &str) -> Result<()> {
+        let url = format!(
+            "{CLOUDFLARE_API}/zones/{}/dns_records/{id}",
+            self.zone_id().await?
+        );
+        self.call::<IgnoredAny>(self.client.delete(url))
+            .await
+            .map(|_| ())
+    }
+
+    /// Issue an Origin CA certificate for the domain and its wildcard. Returns the
+    /// certificate and private key, both PEM; the key is generated locally.
+    pub async fn origin_certificate(&self) -> Result<(String, String)> {
+        let hostnames = vec![self.domain.clone(), format!("*.{}", self.domain)];
+        let key = KeyPair::generate().context("Failed to generate private key")?;
+        let csr = CertificateParams::new(hostnames.clone())
+            .and_then(|params| params.serialize_request(&key))
+            .and_then(|request| request.pem())
+            .context("Failed to build certificate signing request")?;
+
+        info!(
+            "Requesting Cloudflare origin certificate for domain: {}",
+            self.domain
+        );
+        let body = json!({
+            "hostnames": hostnames,
+            "requested_validity": ORIGIN_CERT_VALIDITY_DAYS,
+            "request_type": "origin-ecc",
+            "csr": csr,
+        });
+        let certificate: OriginCertificate = self
+            .call(
+                self.client
+                    .post(format!("{CLOUDFLARE_API}/certificates"))
+                    .json(&body),
+            )
+            .await?;
+        Ok((certificate.certificate, key.serialize_pem()))
+    }
+
+    async fn zone_id(&self) -> Result<&str> {
+        self.zone_id
+            .get_or_try_init(|| async {
+                let zones: Vec<CloudflareZone> = self
+                    .call(
+                        self.client
+                            .get(format!("{CLOUDFLARE_API}/zones"))
+                            .query(&[("name", self.domain.as_str())]),
+                    )
+                    .await?;
+                zones
+                    .into_iter()
+                    .next()
+                    .map(|zone| zone.id)
+                    .with_context(|| format!("No Cloudflare zone found for {}", self.domain))
+            })
+            .await
+            .map(String::as_str)
+    }
+
+    async fn call<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let response: CloudflareResponse<T> = request
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .context("Failed to send request to Cloudflare API")?
+            .json()
+            .await
+            .context("Failed to parse Cloudflare API response")?;
+        if !response.success {
+            let errors: Vec<String> = response
+                .errors
+                .iter()
+                .map(|error| format!("{} ({})", error.message, error.code))
+                .collect();
+            return Err(anyhow::anyhow!(
+                "Cloudflare API request failed: {}",
+                errors.join(", ")
+            ));
+        }
+        response
+            .result
+            .context("Cloudflare API response has no result")
+    }
+}
@@ -0,0 +1,160 @@
+//! Fault injection for exercising retries, the load-failure quarantine and recovery
+//! paths against a running server.
+//!
+//! Faults are configured with `FAASTA_CHAOS_*` variables and only honoured by a server
+//! built with the `chaos` feature; a release build refuses to start with them set. Each
+//! kind of fault draws from its own generator seeded from `FAASTA_CHAOS_SEED`, so a run
+//! with the same seed and the same sequence of operations fails the same ones.
+//!
+//! - `FAASTA_CHAOS_SLOW_WRITES_MS`: delay every metadata write by this long
+//! - `FAASTA_CHAOS_ARTIFACT_READ_ERRORS`: probability that reading an artifact fails
+//! - `FAASTA_CHAOS_FUNCTION_ERRORS`: probability that an invocation answers 500
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use once_cell::sync::OnceCell;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tracing::warn;
+
+const VARS: [&str; 4] = [
+    "FAASTA_CHAOS_SEED",
+    "FAASTA_CHAOS_SLOW_WRITES_MS",
+    "FAASTA_CHAOS_ARTIFACT_READ_ERRORS",
+    "FAASTA_CHAOS_FUNCTION_ERRORS",
+];
+
+static CHAOS: OnceCell<Chaos> = OnceCell::new();
+
+#[derive(Debug)]
+struct Chaos {
+    slow_writes: Option<Duration>,
+    artifact_read_errors: Fault,
+    function_errors: Fault,
+}
+
+/// A fault that strikes with a fixed probability, drawn from its own seeded generator
+#[derive(Debug)]
+struct Fault {
+    probability: f64,
+    rng: Mutex<StdRng>,
+}
+
+impl Fault {
+    fn new(probability: f64, seed: u64) -> Self {
+        Self {
+            probability,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    fn strikes(&self) -> bool {
+        self.probability > 0.0
+            && self
+                .rng
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .random_bool(self.probability)
+    }
+}
+
+fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn probability(name: &str) -> Result<f64> {
+    let Some(value) = var(name) else {
+        return Ok(0.0);
+    };
+    let probability: f64 = value
+        .parse()
+        .with_context(|| format!("{name} must be a number between 0 and 1"))?;
+    ensure!(
+        (0.0..=1.0).contains(&probability),
+        "{name} must be between 0 and 1"
+    );
+    Ok(probability)
+}
+
+/// Read the `FAASTA_CHAOS_*` settings; does nothing when none are set.
+pub fn configure_from_env() -> Result<()> {
+    if !VARS.iter().any(|name| var(name).is_some()) {
+        return Ok(());
+    }
+    if !cfg!(feature = "chaos") {
+        bail!("FAASTA_CHAOS_* settings need a server built with the `chaos` feature");
+    }
+    let seed = match var("FAASTA_CHAOS_SEED") {
+        Some(seed) => seed
+            .parse()
+            .context("FAASTA_CHAOS_SEED must be an unsigned integer")?,
+        None => 0,
+    };
+    let slow_writes = var("FAASTA_CHAOS_SLOW_WRITES_MS")
+        .map(|millis| {
+            millis
+                .parse()
+                .map(Duration::from_millis)
+                .context("FAASTA_CHAOS_SLOW_WRITES_MS must be a number of milliseconds")
+        })
+        .transpose()?;
+    let chaos = Chaos {
+        slow_writes,
+        artifact_read_errors: Fault::new(probability("FAASTA_CHAOS_ARTIFACT_READ_ERRORS")?, seed),
+        function_errors: Fault::new(
+            probability("FAASTA_CHAOS_FUNCTION_ERRORS")?,
+            seed.wrapping_add(1),
+        ),
+    };
+    warn!("chaos mode enabled: {chaos:?}");
+    CHAOS
+        .set(chaos)
+        .map_err(|_| anyhow!("chaos mode already configured"))
+}
+
+pub fn enabled() -> bool {
+    CHAOS.get().is_some()
+}
+
+/// How long to stall each metadata write, if at all.
+pub fn metadata_write_delay() -> Option<Duration> {
+    CHAOS.get()?.slow_writes
+}
+
+/// Fail an artifact read when the fault strikes.
+pub fn artifact_read(function_name: &str) -> Result<()> {
+    match CHAOS.get() {
+        Some(chaos) if chaos.artifact_read_errors.strikes() => Err(anyhow!(
+            "chaos: injected read error for the artifact of '{function_name}'"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether to replace an invocation's response with a 500.
+pub fn function_error() -> bool {
+    CHAOS
+        .get()
+        .is_some_and(|chaos| chaos.function_errors.strikes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faults_repeat_for_a_seed() {
+        let draws = |seed| {
+            let fault = Fault::new(0.3, seed);
+            (0..256).map(|_| fault.strikes()).collect::<Vec<_>>()
+        };
+        assert_eq!(draws(7), draws(7));
+        assert_ne!(draws(7), draws(8));
+        let struck = draws(7).into_iter().filter(|struck| *struck).count();
+        assert!((40..120).contains(&struck), "{struck}");
+        assert!(!Fault::new(0.0, 7).strikes());
+        assert!(Fault::new(1.0, 7).strikes());
+    }
+}
@@ -0,0 +1,70 @@
+//! Latency of cold and warm invocations, kept apart so that loads don't skew a
+//! function's usual latency and their own cost stays visible.
+//!
+//! An invocation is cold when the function's component wasn't resident and had to be
+//! loaded before it could be instantiated; warm invocations found it in the cache.
+//! Distributions are kept in memory since the node started and shown by
+//! `cargo faasta metrics`.
+
+use std::time::Duration;
+
+use dashmap::DashMap;
+use faasta_interface::StartLatency;
+use once_cell::sync::Lazy;
+
+use crate::function_key::MetricKey;
+use crate::latency::Histogram;
+
+static STARTS: Lazy<DashMap<MetricKey, Starts>> = Lazy::new(DashMap::new);
+
+#[derive(Clone, Debug, Default)]
+struct Starts {
+    warm: Histogram,
+    cold: Histogram,
+}
+
+/// Count one invocation recorded under `metric_key` that took `duration`.
+pub fn record(metric_key: &MetricKey, cold: bool, duration: Duration) {
+    let mut starts = STARTS.entry(metric_key.clone()).or_default();
+    if cold {
+        starts.cold.record(duration);
+    } else {
+        starts.warm.record(duration);
+    }
+}
+
+pub fn snapshot(metric_key: &MetricKey) -> StartLatency {
+    STARTS
+        .get(metric_key)
+        .map(|starts| StartLatency {
+            warm: starts.warm.distribution(),
+            cold: starts.cold.distribution(),
+        })
+        .unwrap_or_default()
+}
+
+pub fn forget(metric_key: &MetricKey) {
+    STARTS.remove(metric_key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function_key::FunctionKey;
+
+    #[test]
+    fn keeps_cold_and_warm_apart() {
+        let key = MetricKey::Function(FunctionKey::new("starts-owner", "starts-test"));
+        record(&key, true, Duration::from_millis(400));
+        record(&key, false, Duration::from_millis(3));
+        record(&key, false, Duration::from_millis(4));
+
+        let starts = snapshot(&key);
+        assert_eq!((starts.cold.count, starts.cold.p95_ms), (1, Some(500)));
+        assert_eq!((starts.warm.count, starts.warm.p95_ms), (2, Some(5)));
+        assert_eq!(starts.warm.total_micros, 7000);
+
+        forget(&key);
+        assert_eq!(snapshot(&key).cold.count, 0);
+    }
+}
@@ -0,0 +1,101 @@
+//! CPU time spent running a future, as opposed to the wall time it took.
+//!
+//! Guests run only while their future is polled, so adding up the thread CPU clock
+//! across polls charges an invocation for the time its guest actually ran: queueing,
+//! parked turns and I/O waits are left out, and so is work the host hands to other
+//! tasks, such as outbound HTTP.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// CPU time spent so far on a future run by [`measure_into`], readable while it runs.
+#[derive(Clone, Debug, Default)]
+pub struct Meter(Arc<AtomicU64>);
+
+impl Meter {
+    pub fn spent(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+
+    fn add(&self, spent: Duration) {
+        self.0.fetch_add(spent.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Run `future` to completion, returning its output and the CPU time spent polling it.
+pub async fn measure<F: Future>(future: F) -> (F::Output, Duration) {
+    let meter = Meter::default();
+    let output = measure_into(future, meter.clone()).await;
+    (output, meter.spent())
+}
+
+/// Run `future` to completion, adding the CPU time spent polling it to `meter` as it
+/// goes.
+pub async fn measure_into<F: Future>(future: F, meter: Meter) -> F::Output {
+    Measured {
+        future: Box::pin(future),
+        meter,
+    }
+    .await
+}
+
+struct Measured<F> {
+    future: Pin<Box<F>>,
+    meter: Meter,
+}
+
+impl<F: Future> Future for Measured<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let before = thread_cpu_time();
+        let poll = self.future.as_mut().poll(cx);
+        self.meter.add(thread_cpu_time().saturating_sub(before));
+        poll
+    }
+}
+
+/// CPU time the current thread has used. Zero where the platform has no per-thread
+/// clock.
+pub fn thread_cpu_time() -> Duration {
+    #[cfg(unix)]
+    {
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `now` is a valid timespec for the call to write to
+        if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut now) } == 0 {
+            return Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+        }
+    }
+    Duration::ZERO
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_cpu_but_not_sleep() {
+        let ((), spent) = measure(async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        })
+        .await;
+        assert!(spent < Duration::from_millis(25), "{spent:?}");
+
+        let (burned, spent) = measure(async {
+            let started = thread_cpu_time();
+            while thread_cpu_time() - started < Duration::from_millis(20) {
+                std::hint::black_box(started);
+            }
+            thread_cpu_time() - started
+        })
+        .await;
+        assert!(spent >= burned, "{spent:?} < {burned:?}");
+    }
+}
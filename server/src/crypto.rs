@@ -0,0 +1,86 @@
+//! Selection of the rustls crypto provider.
+//!
+//! The provider is installed process-wide before any TLS configuration is built, so the
+//! HTTPS and management listeners, the RPC endpoint and outbound API clients all use
+//! it. `ring` is always available; `aws-lc-rs` and its FIPS module need the server to
+//! be built with the `aws-lc-rs` or `fips` feature.
+
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow, bail};
+use once_cell::sync::OnceCell;
+use rustls::server::ProducesTickets;
+use tracing::info;
+
+static INSTALLED: OnceCell<CryptoProvider> = OnceCell::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CryptoProvider {
+    Ring,
+    AwsLcRs,
+    /// aws-lc-rs restricted to its FIPS 140-3 validated module
+    Fips,
+}
+
+impl CryptoProvider {
+    /// Install the provider as the process default. Must run before any TLS config is built.
+    pub fn install(self) -> Result<()> {
+        let provider = match self {
+            Self::Ring => rustls::crypto::ring::default_provider(),
+            Self::AwsLcRs => aws_lc_rs_provider()?,
+            Self::Fips => {
+                let provider = fips_provider()?;
+                if !provider.fips() {
+                    bail!("the aws-lc-rs provider is not running in FIPS mode");
+                }
+                provider
+            }
+        };
+        provider
+            .install_default()
+            .map_err(|_| anyhow!("a rustls crypto provider is already installed"))?;
+        let _ = INSTALLED.set(self);
+        info!("using the {self:?} crypto provider");
+        Ok(())
+    }
+}
+
+/// A session ticket encrypter with a fresh key, from the installed provider.
+pub fn ticketer() -> Result<Arc<dyn ProducesTickets>> {
+    match INSTALLED.get().copied().unwrap_or(CryptoProvider::Ring) {
+        CryptoProvider::Ring => rustls::crypto::ring::Ticketer::new()
+            .map_err(|err| anyhow!("failed to create session ticket key: {err}")),
+        CryptoProvider::AwsLcRs | CryptoProvider::Fips => aws_lc_rs_ticketer(),
+    }
+}
+
+#[cfg(feature = "aws-lc-rs")]
+fn aws_lc_rs_ticketer() -> Result<Arc<dyn ProducesTickets>> {
+    rustls::crypto::aws_lc_rs::Ticketer::new()
+        .map_err(|err| anyhow!("failed to create session ticket key: {err}"))
+}
+
+#[cfg(not(feature = "aws-lc-rs"))]
+fn aws_lc_rs_ticketer() -> Result<Arc<dyn ProducesTickets>> {
+    bail!("this build does not include aws-lc-rs")
+}
+
+#[cfg(feature = "aws-lc-rs")]
+fn aws_lc_rs_provider() -> Result<rustls::crypto::CryptoProvider> {
+    Ok(rustls::crypto::aws_lc_rs::default_provider())
+}
+
+#[cfg(not(feature = "aws-lc-rs"))]
+fn aws_lc_rs_provider() -> Result<rustls::crypto::CryptoProvider> {
+    bail!("this build does not include aws-lc-rs; rebuild with `--features aws-lc-rs`")
+}
+
+#[cfg(feature = "fips")]
+fn fips_provider() -> Result<rustls::crypto::CryptoProvider> {
+    Ok(rustls::crypto::default_fips_provider())
+}
+
+#[cfg(not(feature = "fips"))]
+fn fips_provider() -> Result<rustls::crypto::CryptoProvider> {
+    bail!("this build does not include the FIPS module; rebuild with `--features fips`")
+}
@@ -0,0 +1,58 @@
+//! Composite keys for the metadata store. Each part is prefixed with its length in
+//! bytes (`5:alice3:api`), so parts may hold any character without being mistaken for a
+//! separator, and a key never parses as a different one.
+
+/// Encode `parts` as one key.
+pub fn encode<'a>(parts: impl IntoIterator<Item = &'a str>) -> String {
+    let mut key = String::new();
+    for part in parts {
+        key.push_str(&part.len().to_string());
+        key.push(':');
+        key.push_str(part);
+    }
+    key
+}
+
+/// The parts of a key written by [`encode`]; `None` for anything else.
+pub fn decode(key: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut rest = key;
+    while !rest.is_empty() {
+        let (len, tail) = rest.split_once(':')?;
+        if len.is_empty() || !len.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        let len: usize = len.parse().ok()?;
+        if tail.len() < len || !tail.is_char_boundary(len) {
+            return None;
+        }
+        let (part, tail) = tail.split_at(len);
+        parts.push(part);
+        rest = tail;
+    }
+    (!parts.is_empty()).then_some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_any_parts() {
+        for parts in [
+            vec!["fn", "alice", "api"],
+            vec!["shadow", "a:b", "3:x/y"],
+            vec!["", "héllo"],
+        ] {
+            assert_eq!(decode(&encode(parts.iter().copied())).unwrap(), parts);
+        }
+        assert_eq!(encode(["fn", "alice", "api"]), "2:fn5:alice3:api");
+    }
+
+    #[test]
+    fn rejects_other_keys() {
+        for key in ["", "api", "shadow:api", "alice/api", "3:ab", "x:abc", "1:é"] {
+            assert_eq!(decode(key), None, "{key}");
+        }
+    }
+}
@@ -0,0 +1,499 @@
+//! Metadata persistence. Everything outside this module talks to storage through
+//! [`MetadataStore`], so the backing engine can be swapped or migrated without
+//! touching callers.
+
+pub mod key;
+pub mod record;
+mod redb_store;
+mod sqlite;
+
+use anyhow::{Context, Result, bail, ensure};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::latency::{self, Hdr};
+use crate::{chaos, encryption};
+use redb_store::RedbStore;
+use sqlite::SqliteStore;
+
+const MAX_KEY_LEN: usize = 512;
+const MAX_VALUE_LEN: usize = 1024 * 1024;
+const SLOW_OPERATION: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MetadataBackend {
+    Sqlite,
+    Redb,
+}
+
+impl MetadataBackend {
+    /// Backend selected by the `METADATA_BACKEND` environment variable, defaulting to SQLite.
+    pub fn from_env() -> Self {
+        std::env::var("METADATA_BACKEND")
+            .ok()
+            .and_then(|value| <Self as clap::ValueEnum>::from_str(&value, true).ok())
+            .unwrap_or(Self::Sqlite)
+    }
+}
+
+/// Named collections of metadata records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Tree {
+    Functions,
+    FunctionConfig,
+    /// User records with their project lists, as written by older releases; moved to
+    /// `Accounts`, `Projects` and `ProjectOwners` at startup
+    Users,
+    Metrics,
+    PublishIntents,
+    SigningKeys,
+    Secrets,
+    Artifacts,
+    Jobs,
+    /// Username -> account
+    Accounts,
+    /// `key::encode([username, project])` -> nothing; a user's projects are a prefix scan
+    Projects,
+    /// Project -> owning username, the reverse of `Projects`
+    ProjectOwners,
+    /// Token hash -> session, see `sessions`
+    Sessions,
+    /// Username -> TOTP secret, see `two_factor`
+    TwoFactor,
+    /// Function name -> its recent output, see `function_logs`
+    Logs,
+}
+
+impl Tree {
+    pub const ALL: [Tree; 15] = [
+        Tree::Functions,
+        Tree::FunctionConfig,
+        Tree::Users,
+        Tree::Metrics,
+        Tree::PublishIntents,
+        Tree::SigningKeys,
+        Tree::Secrets,
+        Tree::Artifacts,
+        Tree::Jobs,
+        Tree::Accounts,
+        Tree::Projects,
+        Tree::ProjectOwners,
+        Tree::Sessions,
+        Tree::TwoFactor,
+        Tree::Logs,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tree::Functions => "functions",
+            Tree::FunctionConfig => "function_config",
+            Tree::Users => "user_data",
+            Tree::Metrics => "metrics",
+            Tree::PublishIntents => "publish_intents",
+            Tree::SigningKeys => "signing_keys",
+            Tree::Secrets => "secrets",
+            Tree::Artifacts => "artifacts",
+            Tree::Jobs => "jobs",
+            Tree::Accounts => "accounts",
+            Tree::Projects => "projects",
+            Tree::ProjectOwners => "project_owners",
+            Tree::Sessions => "sessions",
+            Tree::TwoFactor => "two_factor",
+            Tree::Logs => "logs",
+        }
+    }
+}
+
+/// A single write applied as part of [`MetadataStore::transaction`].
+#[derive(Clone, Debug)]
+pub enum MetadataOp {
+    Put {
+        tree: Tree,
+        key: String,
+        value: Vec<u8>,
+    },
+    Delete {
+        tree: Tree,
+        key: String,
+    },
+}
+
+pub trait MetadataStore: Send + Sync {
+    fn get(&self, tree: Tree, key: &str) -> Result<Option<Vec<u8>>>;
+    fn put(&self, tree: Tree, key: &str, value: &[u8]) -> Result<()>;
+    fn delete(&self, tree: Tree, key: &str) -> Result<()>;
+    fn scan(&self, tree: Tree) -> Result<Vec<(String, Vec<u8>)>>;
+    /// Apply every operation or none of them.
+    fn transaction(&self, ops: Vec<MetadataOp>) -> Result<()>;
+    fn flush(&self) -> Result<()>;
+
+    fn get_function(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.get(Tree::Functions, name)
+    }
+
+    fn put_function(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.put(Tree::Functions, name, data)
+    }
+
+    fn delete_function(&self, name: &str) -> Result<()> {
+        self.delete(Tree::Functions, name)
+    }
+
+    fn put_function_config(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.put(Tree::FunctionConfig, name, data)
+    }
+
+    fn delete_function_config(&self, name: &str) -> Result<()> {
+        self.delete(Tree::FunctionConfig, name)
+    }
+
+    fn iter_function_configs(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        self.scan(Tree::FunctionConfig)
+    }
+
+    fn get_metric(&self, function_name: &str) -> Result<Option<MetricRecord>> {
+        self.get(Tree::Metrics, function_name)?
+            .map(|value| decode_metric(&value))
+            .transpose()
+    }
+
+    fn upsert_metric(&self, function_name: &str, metric: MetricRecord) -> Result<()> {
+        self.put(Tree::Metrics, function_name, &encode_metric(&metric)?)
+    }
+
+    fn metric_exists(&self, function_name: &str) -> Result<bool> {
+        Ok(self.get(Tree::Metrics, function_name)?.is_some())
+    }
+
+    /// Store `metric` under `to` and drop the row at `from`, atomically.
+    fn move_metric(&self, from: &str, to: &str, metric: MetricRecord) -> Result<()> {
+        self.transaction(vec![
+            MetadataOp::Put {
+                tree: Tree::Metrics,
+                key: to.to_string(),
+                value: encode_metric(&metric)?,
+            },
+            MetadataOp::Delete {
+                tree: Tree::Metrics,
+                key: from.to_string(),
+            },
+        ])
+    }
+
+    fn iter_metrics(&self) -> Result<Vec<(String, MetricRecord)>> {
+        self.scan(Tree::Metrics)?
+            .into_iter()
+            .map(|(name, value)| Ok((name, decode_metric(&value)?)))
+            .collect()
+    }
+}
+
+/// Open a metadata store under `base_path` (a directory, or a file path with an extension).
+pub fn open(backend: MetadataBackend, base_path: &Path) -> Result<Arc<dyn MetadataStore>> {
+    let mut store: Box<dyn MetadataStore> = match backend {
+        MetadataBackend::Sqlite => Box::new(SqliteStore::open(base_path)?),
+        MetadataBackend::Redb => Box::new(RedbStore::open(base_path)?),
+    };
+    if encryption::enabled() {
+        store = Box::new(EncryptedStore { inner: store });
+    }
+    if let Some(delay) = chaos::metadata_write_delay() {
+        store = Box::new(SlowWritesStore {
+            inner: store,
+            delay,
+        });
+    }
+    Ok(Arc::new(InstrumentedStore {
+        inner: store,
+        backend,
+    }))
+}
+
+/// Copy every record from one store into another in a single transaction.
+/// Returns the number of records copied.
+pub fn migrate(from: &dyn MetadataStore, to: &dyn MetadataStore) -> Result<usize> {
+    let mut ops = Vec::new();
+    for tree in Tree::ALL {
+        let rows = from
+            .scan(tree)
+            .with_context(|| format!("failed to read {}", tree.name()))?;
+        info!("migrating {} records from {}", rows.len(), tree.name());
+        ops.extend(
+            rows.into_iter()
+                .map(|(key, value)| MetadataOp::Put { tree, key, value }),
+        );
+    }
+    let copied = ops.len();
+    to.transaction(ops)
+        .context("failed to write migrated records")?;
+    to.flush()?;
+    Ok(copied)
+}
+
+/// Enforces key/value size limits and logs slow or failing operations.
+struct InstrumentedStore {
+    inner: Box<dyn MetadataStore>,
+    backend: MetadataBackend,
+}
+
+impl InstrumentedStore {
+    fn observe<T>(
+        &self,
+        operation: &str,
+        tree: Option<Tree>,
+        run: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        let started = Instant::now();
+        let result = run();
+        let elapsed = started.elapsed();
+        let tree = tree.map(Tree::name).unwrap_or("*");
+        if let Err(err) = &result {
+            warn!(
+                "metadata {operation} on {tree} failed after {elapsed:?} ({:?}): {err:#}",
+                self.backend
+            );
+        } else if elapsed > SLOW_OPERATION {
+            warn!(
+                "slow metadata {operation} on {tree}: {elapsed:?} ({:?})",
+                self.backend
+            );
+        }
+        result
+    }
+}
+
+fn check_limits(key: &str, value: &[u8]) -> Result<()> {
+    ensure!(
+        key.len() <= MAX_KEY_LEN,
+        "metadata key exceeds {MAX_KEY_LEN} bytes"
+    );
+    ensure!(
+        value.len() <= MAX_VALUE_LEN,
+        "metadata value for '{key}' exceeds {MAX_VALUE_LEN} bytes"
+    );
+    Ok(())
+}
+
+impl MetadataStore for InstrumentedStore {
+    fn get(&self, tree: Tree, key: &str) -> Result<Option<Vec<u8>>> {
+        self.observe("get", Some(tree), || self.inner.get(tree, key))
+    }
+
+    fn put(&self, tree: Tree, key: &str, value: &[u8]) -> Result<()> {
+        check_limits(key, value)?;
+        self.observe("put", Some(tree), || self.inner.put(tree, key, value))
+    }
+
+    fn delete(&self, tree: Tree, key: &str) -> Result<()> {
+        self.observe("delete", Some(tree), || self.inner.delete(tree, key))
+    }
+
+    fn scan(&self, tree: Tree) -> Result<Vec<(String, Vec<u8>)>> {
+        self.observe("scan", Some(tree), || self.inner.scan(tree))
+    }
+
+    fn transaction(&self, ops: Vec<MetadataOp>) -> Result<()> {
+        for op in &ops {
+            if let MetadataOp::Put { key, value, .. } = op {
+                check_limits(key, value)?;
+            }
+        }
+        self.observe("transaction", None, || self.inner.transaction(ops))
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.observe("flush", None, || self.inner.flush())
+    }
+}
+
+/// Stalls every write, for chaos testing.
+struct SlowWritesStore {
+    inner: Box<dyn MetadataStore>,
+    delay: Duration,
+}
+
+impl MetadataStore for SlowWritesStore {
+    fn get(&self, tree: Tree, key: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.get(tree, key)
+    }
+
+    fn put(&self, tree: Tree, key: &str, value: &[u8]) -> Result<()> {
+        std::thread::sleep(self.delay);
+        self.inner.put(tree, key, value)
+    }
+
+    fn delete(&self, tree: Tree, key: &str) -> Result<()> {
+        std::thread::sleep(self.delay);
+        self.inner.delete(tree, key)
+    }
+
+    fn scan(&self, tree: Tree) -> Result<Vec<(String, Vec<u8>)>> {
+        self.inner.scan(tree)
+    }
+
+    fn transaction(&self, ops: Vec<MetadataOp>) -> Result<()> {
+        std::thread::sleep(self.delay);
+        self.inner.transaction(ops)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Seals records in [`encryption::SENSITIVE_TREES`] on write and opens them on read.
+struct EncryptedStore {
+    inner: Box<dyn MetadataStore>,
+}
+
+impl EncryptedStore {
+    fn seal(tree: Tree, key: &str, value: &[u8]) -> Result<Vec<u8>> {
+        if encryption::SENSITIVE_TREES.contains(&tree) {
+            encryption::seal(value, &encryption::record_context(tree, key))
+        } else {
+            Ok(value.to_vec())
+        }
+    }
+
+    fn open(tree: Tree, key: &str, value: Vec<u8>) -> Result<Vec<u8>> {
+        if encryption::SENSITIVE_TREES.contains(&tree) {
+            encryption::open(value, &encryption::record_context(tree, key))
+                .with_context(|| format!("failed to decrypt {}/{key}", tree.name()))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl MetadataStore for EncryptedStore {
+    fn get(&self, tree: Tree, key: &str) -> Result<Option<Vec<u8>>> {
+        self.inner
+            .get(tree, key)?
+            .map(|value| Self::open(tree, key, value))
+            .transpose()
+    }
+
+    fn put(&self, tree: Tree, key: &str, value: &[u8]) -> Result<()> {
+        self.inner.put(tree, key, &Self::seal(tree, key, value)?)
+    }
+
+    fn delete(&self, tree: Tree, key: &str) -> Result<()> {
+        self.inner.delete(tree, key)
+    }
+
+    fn scan(&self, tree: Tree) -> Result<Vec<(String, Vec<u8>)>> {
+        self.inner
+            .scan(tree)?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = Self::open(tree, &key, value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    fn transaction(&self, ops: Vec<MetadataOp>) -> Result<()> {
+        let ops = ops
+            .into_iter()
+            .map(|op| match op {
+                MetadataOp::Put { tree, key, value } => {
+                    let value = Self::seal(tree, &key, &value)?;
+                    Ok(MetadataOp::Put { tree, key, value })
+                }
+                op => Ok(op),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.inner.transaction(ops)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn store_path(base_path: &Path, default_name: &str) -> PathBuf {
+    if base_path.extension().is_some() {
+        base_path.to_path_buf()
+    } else {
+        base_path.join(default_name)
+    }
+}
+
+/// Persisted invocation totals of one [`crate::function_key::MetricKey`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricRecord {
+    /// Wall times from dispatch to response
+    pub latency: Hdr,
+    /// Unix milliseconds
+    pub last_called: u64,
+    /// CPU time the guest ran for, in microseconds
+    pub cpu_micros: u64,
+}
+
+impl Default for MetricRecord {
+    fn default() -> Self {
+        Self {
+            latency: latency::new_hdr(),
+            last_called: 0,
+            cpu_micros: 0,
+        }
+    }
+}
+
+impl MetricRecord {
+    pub fn call_count(&self) -> u64 {
+        self.latency.len()
+    }
+
+    /// Total wall time in milliseconds, to the histogram's precision
+    pub fn total_time(&self) -> u64 {
+        latency::hdr_total_micros(&self.latency) / 1000
+    }
+
+    /// Add `other`'s calls to these.
+    pub fn merge(&mut self, other: &MetricRecord) -> Result<()> {
+        self.latency
+            .add(&other.latency)
+            .map_err(|err| anyhow::anyhow!("failed to merge latency histograms: {err:?}"))?;
+        self.last_called = self.last_called.max(other.last_called);
+        self.cpu_micros = self.cpu_micros.saturating_add(other.cpu_micros);
+        Ok(())
+    }
+}
+
+/// Metric records are `last_called` and `cpu_micros` as little-endian u64s, then the
+/// latency histogram in HdrHistogram's V2 encoding.
+fn encode_metric(metric: &MetricRecord) -> Result<Vec<u8>> {
+    let mut encoded = [metric.last_called, metric.cpu_micros]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect::<Vec<_>>();
+    encoded.extend(latency::encode_hdr(&metric.latency)?);
+    Ok(encoded)
+}
+
+/// Also reads records of older releases, which held `total_time` (milliseconds),
+/// `call_count`, `last_called` and, later, `cpu_micros` as 24 or 32 bytes of
+/// little-endian u64s. A V2 histogram never fits in that few.
+fn decode_metric(value: &[u8]) -> Result<MetricRecord> {
+    let field = |index: usize| {
+        value
+            .get(index * 8..index * 8 + 8)
+            .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    match value.len() {
+        24 | 32 => Ok(MetricRecord {
+            latency: latency::hdr_from_totals(field(0), field(1)),
+            last_called: field(2),
+            cpu_micros: field(3),
+        }),
+        len if len > 16 => Ok(MetricRecord {
+            latency: latency::decode_hdr(&value[16..])?,
+            last_called: field(0),
+            cpu_micros: field(1),
+        }),
+        len => bail!("metric record has {len} bytes"),
+    }
+}
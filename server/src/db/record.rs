@@ -0,0 +1,84 @@
+//! Versioned encoding of metadata records: a marker byte, the format version, then the
+//! record in bincode's standard configuration.
+//!
+//! Records written before versioning are bare bincode. Their first byte is the length
+//! or value of their first field as a bincode varint, which is never [`MARKER`], so
+//! both kinds can be told apart and legacy records still read; [`migrate`] rewrites
+//! them at startup.
+
+use anyhow::{Context, Result, bail};
+use bincode::{Decode, Encode};
+use faasta_interface::FunctionInfo;
+
+use super::{MetadataOp, MetadataStore, Tree};
+
+/// Not a valid first byte of a bincode varint, so no legacy record starts with it
+const MARKER: u8 = 0xff;
+/// Version of the encoding written by [`encode`]
+pub const FORMAT_VERSION: u8 = 1;
+
+pub fn encode<T: Encode>(record: &T) -> Result<Vec<u8>> {
+    let mut encoded = vec![MARKER, FORMAT_VERSION];
+    encoded.extend(bincode::encode_to_vec(record, bincode::config::standard())?);
+    Ok(encoded)
+}
+
+pub fn decode<T: Decode<()>>(encoded: &[u8]) -> Result<T> {
+    let payload = match encoded {
+        [MARKER, FORMAT_VERSION, payload @ ..] => payload,
+        [MARKER, version, ..] => bail!("unsupported record format version {version}"),
+        legacy => legacy,
+    };
+    Ok(bincode::decode_from_slice(payload, bincode::config::standard())?.0)
+}
+
+fn is_current(encoded: &[u8]) -> bool {
+    encoded.starts_with(&[MARKER, FORMAT_VERSION])
+}
+
+/// Rewrite function records written before versioning in the current format, in one
+/// transaction. Returns how many were rewritten.
+pub fn migrate(db: &dyn MetadataStore) -> Result<usize> {
+    let mut ops = Vec::new();
+    for (name, encoded) in db.scan(Tree::Functions)? {
+        if is_current(&encoded) {
+            continue;
+        }
+        let info: FunctionInfo =
+            decode(&encoded).with_context(|| format!("unreadable function record '{name}'"))?;
+        ops.push(MetadataOp::Put {
+            tree: Tree::Functions,
+            key: name,
+            value: encode(&info)?,
+        });
+    }
+    let migrated = ops.len();
+    if migrated > 0 {
+        db.transaction(ops)?;
+    }
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_versioned_and_legacy_records() {
+        let info = FunctionInfo {
+            name: "api".to_string(),
+            owner: "alice".to_string(),
+            published_at: "2026-10-15T00:00:00Z".to_string(),
+            usage: String::new(),
+        };
+        let encoded = encode(&info).unwrap();
+        assert!(is_current(&encoded));
+        assert_eq!(decode::<FunctionInfo>(&encoded).unwrap().owner, "alice");
+
+        let legacy = bincode::encode_to_vec(&info, bincode::config::standard()).unwrap();
+        assert!(!is_current(&legacy));
+        assert_eq!(decode::<FunctionInfo>(&legacy).unwrap().name, "api");
+
+        assert!(decode::<FunctionInfo>(&[MARKER, FORMAT_VERSION + 1]).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use redb::{Database, ReadableTable, TableDefinition};
+use std::path::Path;
+
+use super::{MetadataOp, MetadataStore, Tree, store_path};
+
+/// Embedded pure-Rust backend; every tree is a `&str -> &[u8]` table.
+pub struct RedbStore {
+    db: Database,
+}
+
+fn table_definition(tree: Tree) -> TableDefinition<'static, &'static str, &'static [u8]> {
+    TableDefinition::new(tree.name())
+}
+
+impl RedbStore {
+    pub fn open(base_path: &Path) -> Result<Self> {
+        let db_path = store_path(base_path, "faasta.redb");
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create redb parent dir {:?}", parent))?;
+        }
+
+        let db =
+            Database::create(&db_path).with_context(|| format!("failed to open {:?}", db_path))?;
+
+        // Create every table up front so read transactions never see a missing table.
+        let txn = db.begin_write()?;
+        for tree in Tree::ALL {
+            txn.open_table(table_definition(tree))?;
+        }
+        txn.commit()?;
+
+        Ok(Self { db })
+    }
+}
+
+impl MetadataStore for RedbStore {
+    fn get(&self, tree: Tree, key: &str) -> Result<Option<Vec<u8>>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(table_definition(tree))?;
+        Ok(table.get(key)?.map(|value| value.value().to_vec()))
+    }
+
+    fn put(&self, tree: Tree, key: &str, value: &[u8]) -> Result<()> {
+        self.transaction(vec![MetadataOp::Put {
+            tree,
+            key: key.to_string(),
+            value: value.to_vec(),
+        }])
+    }
+
+    fn delete(&self, tree: Tree, key: &str) -> Result<()> {
+        self.transaction(vec![MetadataOp::Delete {
+            tree,
+            key: key.to_string(),
+        }])
+    }
+
+    fn scan(&self, tree: Tree) -> Result<Vec<(String, Vec<u8>)>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(table_definition(tree))?;
+        let mut rows = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            rows.push((key.value().to_string(), value.value().to_vec()));
+        }
+        Ok(rows)
+    }
+
+    fn transaction(&self, ops: Vec<MetadataOp>) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        for op in &ops {
+            match op {
+                MetadataOp::Put { tree, key, value } => {
+                    let mut table = txn.open_table(table_definition(*tree))?;
+                    table.insert(key.as_str(), value.as_slice())?;
+                }
+                MetadataOp::Delete { tree, key } => {
+                    let mut table = txn.open_table(table_definition(*tree))?;
+                    table.remove(key.as_str())?;
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        // Committed redb transactions are already durable.
+        Ok(())
+    }
+}
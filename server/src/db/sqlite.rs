@@ -0,0 +1,299 @@
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{MetadataOp, MetadataStore, Tree, decode_metric, encode_metric, store_path};
+
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(base_path: &Path) -> Result<Self> {
+        let db_path = store_path(base_path, "faasta.sqlite3");
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create sqlite parent dir {:?}", parent))?;
+        }
+
+        let conn =
+            Connection::open(&db_path).with_context(|| format!("failed to open {:?}", db_path))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+
+        let db = Self {
+            conn: Mutex::new(conn),
+        };
+        db.init_schema()?;
+        Ok(db)
+    }
+
+    fn init_schema(&self) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite mutex poisoned");
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS functions (
+                name TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS function_config (
+                name TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS user_data (
+                username TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS publish_intents (
+                name TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS signing_keys (
+                username TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS secrets (
+                name TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS artifacts (
+                name TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS accounts (
+                username TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS projects (
+                key TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS project_owners (
+                name TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS two_factor (
+                username TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS logs (
+                name TEXT PRIMARY KEY,
+                data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS metrics (
+                function_name TEXT PRIMARY KEY,
+                total_time INTEGER NOT NULL,
+                call_count INTEGER NOT NULL,
+                last_called INTEGER NOT NULL,
+                cpu_micros INTEGER NOT NULL DEFAULT 0,
+                latency BLOB
+            );",
+        )?;
+        // Databases created before CPU time and latency histograms were tracked lack
+        // the columns
+        Self::add_missing_column(&conn, "metrics", "cpu_micros", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::add_missing_column(&conn, "metrics", "latency", "BLOB")?;
+        Ok(())
+    }
+
+    fn add_missing_column(
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<()> {
+        let exists = conn
+            .prepare(&format!(
+                "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+            ))?
+            .exists(params![column])?;
+        if !exists {
+            conn.execute(
+                &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                [],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Metrics are kept in columns, with the totals derived from the latency histogram
+    /// so the table stays readable.
+    fn upsert_metric_row(conn: &Connection, function_name: &str, value: &[u8]) -> Result<()> {
+        let metric = decode_metric(value)?;
+        // Stored as encoded, less the fields that have columns of their own
+        let latency = &encode_metric(&metric)?[16..];
+        conn.execute(
+            "INSERT INTO metrics(function_name, total_time, call_count, last_called, cpu_micros, latency)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(function_name) DO UPDATE SET
+                total_time = excluded.total_time,
+                call_count = excluded.call_count,
+                last_called = excluded.last_called,
+                cpu_micros = excluded.cpu_micros,
+                latency = excluded.latency",
+            params![
+                function_name,
+                metric.total_time() as i64,
+                metric.call_count() as i64,
+                metric.last_called as i64,
+                metric.cpu_micros as i64,
+                latency
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The encoded record of a metrics row read from `first` on. Rows written before
+    /// latency histograms were kept come back in the older, totals-only encoding.
+    fn metric_row(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Vec<u8>> {
+        let field = |index: usize| {
+            row.get::<_, i64>(first + index)
+                .map(|value| value.max(0) as u64)
+        };
+        let latency: Option<Vec<u8>> = row.get(first + 4)?;
+        let fields = match latency {
+            Some(_) => vec![field(2)?, field(3)?],
+            None => vec![field(0)?, field(1)?, field(2)?, field(3)?],
+        };
+        let mut encoded: Vec<u8> = fields
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        encoded.extend(latency.unwrap_or_default());
+        Ok(encoded)
+    }
+
+    fn put_row(conn: &Connection, tree: Tree, key: &str, value: &[u8]) -> Result<()> {
+        match tree {
+            Tree::Metrics => Self::upsert_metric_row(conn, key, value),
+            _ => {
+                let (table, key_column) = table_for(tree);
+                conn.execute(
+                    &format!(
+                        "INSERT INTO {table}({key_column}, data) VALUES (?1, ?2)
+                         ON CONFLICT({key_column}) DO UPDATE SET data = excluded.data"
+                    ),
+                    params![key, value],
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    fn delete_row(conn: &Connection, tree: Tree, key: &str) -> Result<()> {
+        let (table, key_column) = table_for(tree);
+        conn.execute(
+            &format!("DELETE FROM {table} WHERE {key_column} = ?1"),
+            params![key],
+        )?;
+        Ok(())
+    }
+}
+
+impl MetadataStore for SqliteStore {
+    fn get(&self, tree: Tree, key: &str) -> Result<Option<Vec<u8>>> {
+        let conn = self.conn.lock().expect("sqlite mutex poisoned");
+        match tree {
+            Tree::Metrics => conn
+                .query_row(
+                    "SELECT total_time, call_count, last_called, cpu_micros, latency FROM metrics
+                     WHERE function_name = ?1",
+                    params![key],
+                    |row| Self::metric_row(row, 0),
+                )
+                .optional()
+                .map_err(Into::into),
+            _ => {
+                let (table, key_column) = table_for(tree);
+                conn.query_row(
+                    &format!("SELECT data FROM {table} WHERE {key_column} = ?1"),
+                    params![key],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(Into::into)
+            }
+        }
+    }
+
+    fn put(&self, tree: Tree, key: &str, value: &[u8]) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite mutex poisoned");
+        Self::put_row(&conn, tree, key, value)
+    }
+
+    fn delete(&self, tree: Tree, key: &str) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite mutex poisoned");
+        Self::delete_row(&conn, tree, key)
+    }
+
+    fn scan(&self, tree: Tree) -> Result<Vec<(String, Vec<u8>)>> {
+        let conn = self.conn.lock().expect("sqlite mutex poisoned");
+        match tree {
+            Tree::Metrics => {
+                let mut stmt = conn.prepare(
+                    "SELECT function_name, total_time, call_count, last_called, cpu_micros, latency
+                     FROM metrics",
+                )?;
+                let rows =
+                    stmt.query_map([], |row| Ok((row.get(0)?, Self::metric_row(row, 1)?)))?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+                    .map_err(Into::into)
+            }
+            _ => {
+                let (table, key_column) = table_for(tree);
+                let mut stmt = conn.prepare(&format!("SELECT {key_column}, data FROM {table}"))?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+                    .map_err(Into::into)
+            }
+        }
+    }
+
+    fn transaction(&self, ops: Vec<MetadataOp>) -> Result<()> {
+        let mut conn = self.conn.lock().expect("sqlite mutex poisoned");
+        let tx = conn.transaction()?;
+        for op in &ops {
+            match op {
+                MetadataOp::Put { tree, key, value } => Self::put_row(&tx, *tree, key, value)?,
+                MetadataOp::Delete { tree, key } => Self::delete_row(&tx, *tree, key)?,
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite mutex poisoned");
+        conn.execute_batch("PRAGMA wal_checkpoint(PASSIVE);")?;
+        Ok(())
+    }
+}
+
+fn table_for(tree: Tree) -> (&'static str, &'static str) {
+    match tree {
+        Tree::Functions => ("functions", "name"),
+        Tree::FunctionConfig => ("function_config", "name"),
+        Tree::Users => ("user_data", "username"),
+        Tree::Metrics => ("metrics", "function_name"),
+        Tree::PublishIntents => ("publish_intents", "name"),
+        Tree::SigningKeys => ("signing_keys", "username"),
+        Tree::Secrets => ("secrets", "name"),
+        Tree::Artifacts => ("artifacts", "name"),
+        Tree::Jobs => ("jobs", "id"),
+        Tree::Accounts => ("accounts", "username"),
+        Tree::Projects => ("projects", "key"),
+        Tree::ProjectOwners => ("project_owners", "name"),
+        Tree::Sessions => ("sessions", "id"),
+        Tree::TwoFactor => ("two_factor", "username"),
+        Tree::Logs => ("logs", "name"),
+    }
+}
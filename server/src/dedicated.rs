@@ -0,0 +1,119 @@
+//! Dedicated isolation tier. Functions the operator assigns to it run in their own
+//! engine, with pooling-allocator slots, CPU accounting, admission limits and worker
+//! threads that shared functions never touch, so a noisy neighbor cannot exhaust the
+//! pool or the runtime they depend on.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::admission::AdmissionController;
+use crate::scheduler::CpuScheduler;
+use crate::wasm_function::{
+    InstanceSettings, PoolConfig, WasmFunctionRuntime, WasmRequest, WasmResponse,
+};
+
+pub struct DedicatedTier {
+    functions: HashSet<String>,
+    runtime: Arc<WasmFunctionRuntime>,
+    /// Worker threads running the tier's guests
+    threads: Handle,
+    /// Owns the worker threads; shut down without blocking when the tier is dropped
+    workers: Option<Runtime>,
+    pub admission: AdmissionController,
+}
+
+impl Drop for DedicatedTier {
+    fn drop(&mut self) {
+        if let Some(workers) = self.workers.take() {
+            workers.shutdown_background();
+        }
+    }
+}
+
+impl DedicatedTier {
+    pub async fn new(
+        functions: HashSet<String>,
+        pool: PoolConfig,
+        worker_threads: usize,
+        cpu: CpuScheduler,
+        admission: AdmissionController,
+    ) -> Result<Self> {
+        let workers = Builder::new_multi_thread()
+            .worker_threads(worker_threads.max(1))
+            .thread_name("faasta-dedicated")
+            .enable_all()
+            .build()
+            .context("failed to start the dedicated worker threads")?;
+        // Providers open their connections on the tier's own threads
+        let threads = workers.handle().clone();
+        let runtime = match threads.spawn(WasmFunctionRuntime::new(pool, cpu)).await {
+            Ok(runtime) => runtime.context("failed to create the dedicated runtime"),
+            Err(err) => Err(anyhow!("dedicated runtime setup panicked: {err}")),
+        };
+        let runtime = match runtime {
+            Ok(runtime) => runtime,
+            Err(err) => {
+                // A runtime can't be dropped from async code
+                workers.shutdown_background();
+                return Err(err);
+            }
+        };
+        Ok(Self {
+            functions,
+            runtime: Arc::new(runtime),
+            threads,
+            workers: Some(workers),
+            admission,
+        })
+    }
+
+    pub fn serves(&self, function_name: &str) -> bool {
+        self.functions.contains(function_name)
+    }
+
+    pub fn runtime(&self) -> &WasmFunctionRuntime {
+        &self.runtime
+    }
+
+    pub async fn invoke(
+        &self,
+        function_name: &str,
+        owner: &str,
+        artifact_path: &Path,
+        request: WasmRequest,
+        settings: &InstanceSettings,
+    ) -> Result<WasmResponse> {
+        let runtime = self.runtime.clone();
+        let (function_name, owner, artifact_path) = owned(function_name, owner, artifact_path);
+        let settings = settings.clone();
+        self.threads
+            .spawn(async move {
+                runtime
+                    .invoke(&function_name, &owner, &artifact_path, request, &settings)
+                    .await
+            })
+            .await
+            .map_err(|err| anyhow!("dedicated invocation panicked: {err}"))?
+    }
+
+    pub async fn warm(&self, function_name: &str, owner: &str, artifact_path: &Path) -> Result<()> {
+        let runtime = self.runtime.clone();
+        let (function_name, owner, artifact_path) = owned(function_name, owner, artifact_path);
+        self.threads
+            .spawn(async move { runtime.warm(&function_name, &owner, &artifact_path).await })
+            .await
+            .map_err(|err| anyhow!("dedicated warm-up panicked: {err}"))?
+    }
+}
+
+fn owned(function_name: &str, owner: &str, artifact_path: &Path) -> (String, String, PathBuf) {
+    (
+        function_name.to_string(),
+        owner.to_string(),
+        artifact_path.to_path_buf(),
+    )
+}
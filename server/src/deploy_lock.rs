@@ -0,0 +1,89 @@
+//! Per-function locks serializing publishes and unpublishes.
+//!
+//! A publish checks who may use the name, stages the artifact at a path derived from
+//! the name, swaps it in and commits metadata. Two of them running at once for the
+//! same name could interleave those steps, or both claim a free name for different
+//! users, so each holds the function's lock from the access check to the commit. A
+//! call that finds the lock taken fails at once instead of queueing behind it.
+
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use faasta_interface::FunctionError;
+use once_cell::sync::Lazy;
+
+static IN_PROGRESS: Lazy<DashMap<String, ()>> = Lazy::new(DashMap::new);
+
+/// Held while a function is being deployed; releases the lock when dropped.
+#[must_use]
+pub struct DeployGuard {
+    name: String,
+}
+
+impl Drop for DeployGuard {
+    fn drop(&mut self) {
+        IN_PROGRESS.remove(&self.name);
+    }
+}
+
+/// Take the lock of function `name`, failing with a conflict if another publish or
+/// unpublish of it holds it.
+pub fn acquire(name: &str) -> Result<DeployGuard, FunctionError> {
+    match IN_PROGRESS.entry(name.to_string()) {
+        Entry::Occupied(_) => Err(FunctionError::Conflict(format!(
+            "Another publish or unpublish of '{name}' is in progress; try again once it finishes"
+        ))),
+        Entry::Vacant(entry) => {
+            entry.insert(());
+            Ok(DeployGuard {
+                name: name.to_string(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn releases_on_drop() {
+        let guard = acquire("lock-test").unwrap();
+        assert!(matches!(
+            acquire("lock-test"),
+            Err(FunctionError::Conflict(_))
+        ));
+        let other = acquire("lock-test-other").unwrap();
+        drop(guard);
+        drop(acquire("lock-test").unwrap());
+        drop(other);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn racing_deploys_run_one_at_a_time() {
+        let deployed = Arc::new(AtomicUsize::new(0));
+        let running = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let deployed = deployed.clone();
+                let running = running.clone();
+                tokio::spawn(async move {
+                    let Ok(_guard) = acquire("lock-race") else {
+                        return;
+                    };
+                    assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    deployed.fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(deployed.load(Ordering::SeqCst) >= 1);
+        drop(acquire("lock-race").unwrap());
+    }
+}
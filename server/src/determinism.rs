@@ -0,0 +1,103 @@
+//! Determinism mode: clocks and randomness that are the same on every request, so a
+//! recorded request replays identically and snapshot tests don't flake.
+//!
+//! Each instance starts its clocks at the configured time and advances them by `STEP`
+//! per read, so guests that measure elapsed time still see it pass. Both the secure
+//! and insecure random sources are seeded from the configured seed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use cap_rand::RngCore;
+use faasta_interface::Deterministic;
+use wasmtime_wasi::{HostMonotonicClock, HostWallClock, WasiCtxBuilder};
+
+/// How far the clocks advance on every read.
+const STEP: Duration = Duration::from_millis(1);
+
+pub fn apply(builder: &mut WasiCtxBuilder, config: &Deterministic) {
+    builder
+        .wall_clock(SteppedClock::new(Duration::from_secs(
+            config.start_unix_secs,
+        )))
+        .monotonic_clock(SteppedClock::new(Duration::ZERO))
+        .secure_random(SeededRng::new(config.seed))
+        .insecure_random(SeededRng::new(config.seed.rotate_left(32)))
+        .insecure_random_seed(u128::from(config.seed) << 64 | u128::from(!config.seed));
+}
+
+struct SteppedClock {
+    start: Duration,
+    reads: AtomicU64,
+}
+
+impl SteppedClock {
+    fn new(start: Duration) -> Self {
+        Self {
+            start,
+            reads: AtomicU64::new(0),
+        }
+    }
+
+    fn next(&self) -> Duration {
+        let reads = self.reads.fetch_add(1, Ordering::Relaxed);
+        self.start + STEP * u32::try_from(reads).unwrap_or(u32::MAX)
+    }
+}
+
+impl HostWallClock for SteppedClock {
+    fn resolution(&self) -> Duration {
+        STEP
+    }
+
+    fn now(&self) -> Duration {
+        self.next()
+    }
+}
+
+impl HostMonotonicClock for SteppedClock {
+    fn resolution(&self) -> u64 {
+        STEP.as_nanos() as u64
+    }
+
+    fn now(&self) -> u64 {
+        self.next().as_nanos() as u64
+    }
+}
+
+/// SplitMix64; small, fast and fully determined by its seed.
+struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), cap_rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+//! Optional at-rest encryption of function artifacts and sensitive metadata.
+//!
+//! With a key configured, published components are sealed with AES-256-GCM before
+//! they go live and opened again when they are loaded, and records in
+//! [`SENSITIVE_TREES`] are sealed as they are written. Data without the envelope header
+//! is read as plaintext, so encryption can be enabled on an existing deployment;
+//! [`rotate`] rewrites everything under the current key.
+//!
+//! The key comes from `FAASTA_ENCRYPTION_KEY` (64 hex characters) or from the output
+//! of `FAASTA_ENCRYPTION_KEY_COMMAND`, which lets a KMS or secret manager unwrap it at
+//! startup. Retired keys listed in `FAASTA_ENCRYPTION_PREVIOUS_KEYS` can still open
+//! data until it has been rotated.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use once_cell::sync::OnceCell;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::digest::{SHA256, digest};
+use ring::rand::{SecureRandom, SystemRandom};
+use tracing::info;
+
+use crate::db::{MetadataOp, MetadataStore, Tree};
+use crate::{chaos, publish};
+
+/// Metadata trees holding credentials, secret values or stored requests.
+pub const SENSITIVE_TREES: [Tree; 6] = [
+    Tree::Users,
+    Tree::Accounts,
+    Tree::SigningKeys,
+    Tree::TwoFactor,
+    Tree::Secrets,
+    Tree::Jobs,
+];
+
+const MAGIC: &[u8; 4] = b"FENC";
+const KEY_ID_LEN: usize = 8;
+const HEADER_LEN: usize = MAGIC.len() + KEY_ID_LEN + NONCE_LEN;
+
+static KEYRING: OnceCell<Keyring> = OnceCell::new();
+
+struct Key {
+    id: [u8; KEY_ID_LEN],
+    key: LessSafeKey,
+}
+
+impl Key {
+    fn from_hex(hex_key: &str, source: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_key.trim())
+            .with_context(|| format!("{source} must be 64 hex characters"))?;
+        let key = UnboundKey::new(&AES_256_GCM, &bytes)
+            .map_err(|_| anyhow!("{source} must be 64 hex characters"))?;
+        // Identify keys by a digest so the envelope never reveals key material
+        let mut id = [0; KEY_ID_LEN];
+        id.copy_from_slice(&digest(&SHA256, &bytes).as_ref()[..KEY_ID_LEN]);
+        Ok(Self {
+            id,
+            key: LessSafeKey::new(key),
+        })
+    }
+}
+
+struct Keyring {
+    current: Key,
+    previous: Vec<Key>,
+    rng: SystemRandom,
+}
+
+impl Keyring {
+    fn find(&self, id: &[u8]) -> Option<&Key> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|key| key.id == id)
+    }
+}
+
+/// Load the keyring from the environment. Leaves encryption disabled when no key is set.
+pub fn configure_from_env() -> Result<()> {
+    let current = match (
+        std::env::var("FAASTA_ENCRYPTION_KEY"),
+        std::env::var("FAASTA_ENCRYPTION_KEY_COMMAND"),
+    ) {
+        (Ok(_), Ok(_)) => {
+            bail!("set only one of FAASTA_ENCRYPTION_KEY and FAASTA_ENCRYPTION_KEY_COMMAND")
+        }
+        (Ok(hex_key), Err(_)) => Key::from_hex(&hex_key, "FAASTA_ENCRYPTION_KEY")?,
+        (Err(_), Ok(command)) => Key::from_hex(
+            &run_key_command(&command)?,
+            "FAASTA_ENCRYPTION_KEY_COMMAND output",
+        )?,
+        (Err(_), Err(_)) => return Ok(()),
+    };
+    let previous = std::env::var("FAASTA_ENCRYPTION_PREVIOUS_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|key| !key.trim().is_empty())
+        .map(|key| Key::from_hex(key, "FAASTA_ENCRYPTION_PREVIOUS_KEYS entries"))
+        .collect::<Result<Vec<_>>>()?;
+
+    KEYRING
+        .set(Keyring {
+            current,
+            previous,
+            rng: SystemRandom::new(),
+        })
+        .map_err(|_| anyhow!("encryption keyring already configured"))?;
+    info!("at-rest encryption enabled");
+    Ok(())
+}
+
+fn run_key_command(command: &str) -> Result<String> {
+    let argv: Vec<&str> = command.split_whitespace().collect();
+    let Some((program, args)) = argv.split_first() else {
+        bail!("FAASTA_ENCRYPTION_KEY_COMMAND is empty");
+    };
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("failed to run encryption key command '{program}'"))?;
+    if !output.status.success() {
+        bail!(
+            "encryption key command exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("encryption key command printed non-UTF-8 output")
+}
+
+pub fn enabled() -> bool {
+    KEYRING.get().is_some()
+}
+
+fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypt `data` under the current key, binding it to `context`. Returns the data
+/// unchanged when encryption is disabled.
+pub fn seal(data: &[u8], context: &[u8]) -> Result<Vec<u8>> {
+    let Some(keyring) = KEYRING.get() else {
+        return Ok(data.to_vec());
+    };
+    let mut nonce = [0; NONCE_LEN];
+    keyring
+        .rng
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("failed to generate nonce"))?;
+
+    let mut sealed = Vec::with_capacity(HEADER_LEN + data.len() + AES_256_GCM.tag_len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&keyring.current.id);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(data);
+    let tag = keyring
+        .current
+        .key
+        .seal_in_place_separate_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(context),
+            &mut sealed[HEADER_LEN..],
+        )
+        .map_err(|_| anyhow!("encryption failed"))?;
+    sealed.extend_from_slice(tag.as_ref());
+    Ok(sealed)
+}
+
+/// Decrypt data produced by [`seal`] with the same `context`. Plaintext written before
+/// encryption was enabled is returned as-is.
+pub fn open(data: Vec<u8>, context: &[u8]) -> Result<Vec<u8>> {
+    if !is_sealed(&data) {
+        return Ok(data);
+    }
+    let keyring = KEYRING
+        .get()
+        .context("data is encrypted but no encryption key is configured")?;
+    ensure!(data.len() > HEADER_LEN, "encrypted data is truncated");
+    let key = keyring
+        .find(&data[MAGIC.len()..MAGIC.len() + KEY_ID_LEN])
+        .context("data is encrypted with an unknown key")?;
+    let nonce = Nonce::try_assume_unique_for_key(&data[MAGIC.len() + KEY_ID_LEN..HEADER_LEN])
+        .map_err(|_| anyhow!("encrypted data is corrupt"))?;
+
+    let mut data = data;
+    let plaintext_len = key
+        .key
+        .open_in_place(nonce, Aad::from(context), &mut data[HEADER_LEN..])
+        .map_err(|_| anyhow!("failed to decrypt data"))?
+        .len();
+    data.drain(..HEADER_LEN);
+    data.truncate(plaintext_len);
+    Ok(data)
+}
+
+/// Whether `data` is already sealed under the current key.
+fn is_current(data: &[u8]) -> bool {
+    KEYRING.get().is_some_and(|keyring| {
+        is_sealed(data) && data[MAGIC.len()..].starts_with(&keyring.current.id)
+    })
+}
+
+/// Encrypt a function artifact in place. Does nothing when encryption is disabled.
+pub fn seal_artifact(function_name: &str, path: &Path) -> Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let sealed = seal(&data, function_name.as_bytes())?;
+    fs::write(path, sealed).with_context(|| format!("failed to encrypt {}", path.display()))
+}
+
+/// Read a function artifact, decrypting it if needed.
+pub fn read_artifact(function_name: &str, path: &Path) -> Result<Vec<u8>> {
+    chaos::artifact_read(function_name)?;
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    open(data, function_name.as_bytes())
+        .with_context(|| format!("failed to open artifact {}", path.display()))
+}
+
+/// Associated data for a metadata record, so a sealed value cannot be moved to
+/// another key or tree.
+pub fn record_context(tree: Tree, key: &str) -> Vec<u8> {
+    format!("{}/{key}", tree.name()).into_bytes()
+}
+
+#[derive(Debug, Default)]
+pub struct RotationReport {
+    pub artifacts: usize,
+    pub records: usize,
+}
+
+/// Re-encrypt every artifact and sensitive record under the current key, including
+/// data that is still plaintext or sealed with a previous key.
+pub fn rotate(db: &dyn MetadataStore, functions_dir: &Path) -> Result<RotationReport> {
+    ensure!(enabled(), "no encryption key is configured");
+    let mut report = RotationReport::default();
+
+    let originals_dir = functions_dir.join(publish::ORIGINALS_DIR);
+    let mut entries = fs::read_dir(functions_dir)
+        .with_context(|| format!("failed to read {}", functions_dir.display()))?
+        .collect::<Vec<_>>();
+    if originals_dir.is_dir() {
+        entries.extend(
+            fs::read_dir(&originals_dir)
+                .with_context(|| format!("failed to read {}", originals_dir.display()))?,
+        );
+    }
+    for entry in entries {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(name) = file_name
+            .strip_suffix(".wasm")
+            .or_else(|| file_name.strip_suffix(".cwasm"))
+        else {
+            continue;
+        };
+        let data = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        if is_current(&data) {
+            continue;
+        }
+        let plaintext = open(data, name.as_bytes())
+            .with_context(|| format!("failed to open artifact {}", path.display()))?;
+        let rotated = path.with_file_name(format!("{file_name}.rotate"));
+        fs::write(&rotated, seal(&plaintext, name.as_bytes())?)
+            .with_context(|| format!("failed to write {}", rotated.display()))?;
+        fs::rename(&rotated, &path)
+            .with_context(|| format!("failed to replace {}", path.display()))?;
+        report.artifacts += 1;
+    }
+
+    // The store decrypts on read and seals under the current key on write
+    let mut ops = Vec::new();
+    for tree in SENSITIVE_TREES {
+        let rows = db
+            .scan(tree)
+            .with_context(|| format!("failed to read {}", tree.name()))?;
+        ops.extend(
+            rows.into_iter()
+                .map(|(key, value)| MetadataOp::Put { tree, key, value }),
+        );
+    }
+    report.records = ops.len();
+    db.transaction(ops)
+        .context("failed to write re-encrypted records")?;
+    db.flush()?;
+    Ok(report)
+}
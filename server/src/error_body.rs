@@ -0,0 +1,78 @@
+//! The JSON error body every HTTP error response carries, and the request id it quotes.
+//!
+//! Each request gets an id, taken from a well-formed `X-Request-Id` header or generated,
+//! which is echoed in the response header, recorded on the request's trace span and
+//! quoted in error bodies, so a user's report can be matched with the server logs.
+
+use axum::body::Body;
+use axum::middleware::Next;
+use faasta_interface::{ERROR_DOCS_URL, ErrorBody};
+use http::{HeaderName, HeaderValue, Request, Response, StatusCode, header};
+use tracing::error;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-supplied request id that is kept
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Middleware assigning the request id for everything the request runs.
+pub async fn assign_request_id(request: Request<Body>, next: Next) -> Response<Body> {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(request))
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .entry(&REQUEST_ID_HEADER)
+            .or_insert(value);
+    }
+    response
+}
+
+/// Id of the request being handled, if called within one.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+pub fn body(code: &str, message: impl Into<String>) -> ErrorBody {
+    ErrorBody {
+        code: code.to_string(),
+        message: message.into(),
+        request_id: current_request_id(),
+        docs_url: Some(ERROR_DOCS_URL.to_string()),
+    }
+}
+
+pub fn response(status: StatusCode, code: &str, message: impl Into<String>) -> Response<Body> {
+    let body = match serde_json::to_vec(&body(code, message)) {
+        Ok(body) => body,
+        Err(err) => {
+            error!("failed to encode error body: {err}");
+            Vec::new()
+        }
+    };
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn is_valid(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= MAX_REQUEST_ID_LEN
+        && request_id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.'))
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use axum::body::Body;
+use faasta_interface::ErrorPage;
+use http::{HeaderValue, Response, StatusCode, header};
+use tracing::info;
+
+use crate::error_body;
+
+const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"><title>{{status}} {{reason}}</title></head>
+<body>
+<h1>{{status}} {{reason}}</h1>
+<p>{{message}}</p>
+<p><small>Request {{request_id}}</small></p>
+</body>
+</html>
+";
+
+/// Error responses generated by the host on the function traffic path.
+///
+/// Clients that accept HTML get a page rendered from the function's own template, the
+/// instance template, or a built-in one, in that order; everyone else gets the usual
+/// JSON error body. Templates are keyed by exact status (`404`) or class (`5xx`) and
+/// may use the `{{status}}`, `{{reason}}`, `{{message}}` and `{{request_id}}`
+/// placeholders.
+pub struct ErrorPages {
+    templates: HashMap<String, String>,
+}
+
+impl ErrorPages {
+    /// Load `<status>.html` and `<class>xx.html` templates from the instance directory.
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let mut templates = HashMap::new();
+        let Some(dir) = dir else {
+            return Ok(Self { templates });
+        };
+
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("failed to read error pages from {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("html") {
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if !is_valid_key(key) {
+                continue;
+            }
+            let template = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read error page {}", path.display()))?;
+            templates.insert(key.to_ascii_lowercase(), template);
+        }
+        info!("loaded {} instance error page(s)", templates.len());
+        Ok(Self { templates })
+    }
+
+    pub fn render(
+        &self,
+        status: StatusCode,
+        code: &str,
+        message: &str,
+        accept: Option<&HeaderValue>,
+        function_pages: &[ErrorPage],
+    ) -> Response<Body> {
+        if !prefers_html(accept) {
+            return error_body::response(status, code, message);
+        }
+
+        let exact = status.as_str().to_string();
+        let class = format!("{}xx", status.as_u16() / 100);
+        let function_template = [&exact, &class].into_iter().find_map(|key| {
+            function_pages
+                .iter()
+                .find(|page| page.status.eq_ignore_ascii_case(key))
+                .map(|page| page.html.as_str())
+        });
+        let template = function_template
+            .or_else(|| self.templates.get(&exact).map(String::as_str))
+            .or_else(|| self.templates.get(&class).map(String::as_str))
+            .unwrap_or(DEFAULT_TEMPLATE);
+
+        let body = template
+            .replace("{{status}}", status.as_str())
+            .replace(
+                "{{reason}}",
+                &escape_html(status.canonical_reason().unwrap_or("Error")),
+            )
+            .replace("{{message}}", &escape_html(message))
+            .replace(
+                "{{request_id}}",
+                &escape_html(&error_body::current_request_id().unwrap_or_default()),
+            );
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(body))
+            .unwrap()
+    }
+}
+
+/// Whether an error page key names a 4xx/5xx status (`404`) or class (`5xx`).
+pub fn is_valid_key(key: &str) -> bool {
+    match key.as_bytes() {
+        [b'4' | b'5', b'x' | b'X', b'x' | b'X'] => true,
+        [b'4' | b'5', tens, units] => tens.is_ascii_digit() && units.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+/// Browsers send `text/html` first; API clients and tools generally don't ask for it.
+fn prefers_html(accept: Option<&HeaderValue>) -> bool {
+    let Some(accept) = accept.and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let position = |media: &str| {
+        accept
+            .split(',')
+            .position(|part| part.trim().starts_with(media))
+    };
+    match (position("text/html"), position("application/json")) {
+        (Some(html), Some(json)) => html < json,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,153 @@
+//! Node health and DNS-based failover for multi-node deployments.
+//!
+//! Each node publishes its own address in the records that point at the cluster
+//! (typically the apex and the `*` wildcard). While the node is ready the hooks keep
+//! its address registered; when it starts draining or fails readiness a few times in a
+//! row they remove it, so resolvers send new clients to the remaining nodes.
+//!
+//! A node labelled with a region also registers under the names of the functions pinned
+//! to that region. An explicit record takes precedence over the wildcard, so the
+//! subdomain of a pinned function resolves only to the healthy nodes of its regions,
+//! rather than to every node in the cluster.
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use tokio::sync::Notify;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::cert_manager::{DnsManager, record_type_for};
+use crate::db::Tree;
+use crate::platform;
+use crate::wasi_server::FaastaServer;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Consecutive failed readiness checks before the node is deregistered.
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// Drain state shared by the readiness endpoint, the failover hooks and shutdown.
+#[derive(Default)]
+pub struct NodeHealth {
+    draining: AtomicBool,
+    changed: Notify,
+}
+
+impl NodeHealth {
+    /// Stop advertising the node; requests are still served until shutdown.
+    pub fn drain(&self) {
+        if !self.draining.swap(true, Ordering::AcqRel) {
+            info!("node is draining");
+            self.changed.notify_one();
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// Whether the node should receive new traffic.
+    pub fn check(&self, server: &FaastaServer) -> Result<()> {
+        if self.is_draining() {
+            bail!("node is draining");
+        }
+        server.metadata_db.get(Tree::Functions, "")?;
+        Ok(())
+    }
+}
+
+pub struct FailoverHooks {
+    pub dns: DnsManager,
+    /// Record names relative to the domain (`"@"` for the apex, `"*"` for the wildcard)
+    pub records: Vec<String>,
+    /// This node's public address
+    pub address: IpAddr,
+    pub ttl: u32,
+}
+
+impl FailoverHooks {
+    /// Spawn the task that keeps the node's records in line with its health.
+    pub fn spawn(self, server: Arc<FaastaServer>, health: Arc<NodeHealth>) {
+        tokio::spawn(async move {
+            // Names holding this node's address, unknown until the first sync succeeds
+            let mut registered: Option<HashSet<String>> = None;
+            let mut failures = 0;
+            loop {
+                match health.check(&server) {
+                    Ok(()) => failures = 0,
+                    Err(err) => {
+                        failures += 1;
+                        if !health.is_draining() {
+                            warn!(
+                                "readiness check failed ({failures}/{FAILURE_THRESHOLD}): {err:#}"
+                            );
+                        }
+                    }
+                }
+                let healthy = !health.is_draining() && failures < FAILURE_THRESHOLD;
+
+                let names = self.names(&server);
+                let wanted = if healthy {
+                    names.clone()
+                } else {
+                    HashSet::new()
+                };
+                if registered.as_ref() != Some(&wanted) {
+                    let (add, remove) = match &registered {
+                        Some(current) => (
+                            wanted.difference(current).cloned().collect(),
+                            current.difference(&wanted).cloned().collect(),
+                        ),
+                        // Records may be left over from a previous run either way
+                        None => (wanted.clone(), names.difference(&wanted).cloned().collect()),
+                    };
+                    match self.apply(&add, &remove).await {
+                        Ok(()) => registered = Some(wanted),
+                        Err(err) => warn!("failed to update DNS failover records: {err:#}"),
+                    }
+                }
+
+                tokio::select! {
+                    _ = time::sleep(CHECK_INTERVAL) => {}
+                    _ = health.changed.notified() => {}
+                }
+            }
+        });
+    }
+
+    /// Every name this node holds while healthy: the configured records, plus the
+    /// functions pinned to its region.
+    fn names(&self, server: &FaastaServer) -> HashSet<String> {
+        let mut names: HashSet<String> = self.records.iter().cloned().collect();
+        if let Some(region) = platform::region() {
+            names.extend(server.function_configs.pinned_to(region));
+        }
+        names
+    }
+
+    async fn apply(&self, add: &HashSet<String>, remove: &HashSet<String>) -> Result<()> {
+        let record_type = record_type_for(self.address);
+        let content = self.address.to_string();
+        for name in add {
+            self.dns
+                .ensure_record(record_type, record_name(name), &content, self.ttl)
+                .await?;
+            info!("registered {} as '{name}' in DNS", self.address);
+        }
+        for name in remove {
+            self.dns
+                .remove_record(record_type, record_name(name), &content)
+                .await?;
+            info!("deregistered {} from '{name}' in DNS", self.address);
+        }
+        Ok(())
+    }
+}
+
+fn record_name(name: &str) -> &str {
+    if name == "@" { "" } else { name }
+}
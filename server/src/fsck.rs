@@ -0,0 +1,253 @@
+//! Consistency check between the functions directory and the metadata store.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use faasta_interface::FunctionInfo;
+use tracing::{error, info, warn};
+
+use crate::db::{Tree, record};
+use crate::function_key::MetricKey;
+use crate::metrics::{self, METRICS_DB};
+use crate::wasi_server::FaastaServer;
+
+/// Directory under the functions directory that repaired orphans are moved into.
+pub const ORPHANED_DIR: &str = "orphaned";
+
+#[derive(Debug)]
+pub enum Issue {
+    /// An artifact on disk with no function record; nobody can own or unpublish it.
+    ArtifactWithoutMetadata { name: String, path: PathBuf },
+    /// A function record whose artifact is gone.
+    MetadataWithoutArtifact { name: String },
+    /// A precompiled artifact shadowed by a `.wasm` of the same name.
+    StaleCompiledArtifact { path: PathBuf },
+    /// Staging output of a publish that never committed.
+    LeftoverStaging { path: PathBuf },
+    /// A user's project list names a function that does not exist.
+    ProjectWithoutFunction { username: String, project: String },
+    /// A function missing from its owner's project list, or registered to someone else
+    /// in the project owner index.
+    UnregisteredProject { name: String, owner: String },
+    /// Persisted metrics for a function that no longer exists, or that its current
+    /// owner doesn't own.
+    MetricsWithoutFunction { metric_key: String },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::ArtifactWithoutMetadata { name, path } => {
+                write!(
+                    f,
+                    "artifact {} for '{name}' has no metadata",
+                    path.display()
+                )
+            }
+            Issue::MetadataWithoutArtifact { name } => {
+                write!(f, "function '{name}' has metadata but no artifact")
+            }
+            Issue::StaleCompiledArtifact { path } => {
+                write!(f, "stale compiled artifact {}", path.display())
+            }
+            Issue::LeftoverStaging { path } => {
+                write!(f, "leftover publish staging at {}", path.display())
+            }
+            Issue::ProjectWithoutFunction { username, project } => {
+                write!(f, "user '{username}' lists missing function '{project}'")
+            }
+            Issue::UnregisteredProject { name, owner } => {
+                write!(
+                    f,
+                    "function '{name}' is missing from owner '{owner}' project list"
+                )
+            }
+            Issue::MetricsWithoutFunction { metric_key } => {
+                write!(f, "metrics recorded for missing function '{metric_key}'")
+            }
+        }
+    }
+}
+
+/// Cross-check artifacts, function records, project lists and metrics.
+/// With `repair`, each issue is fixed; returns the issues that were found.
+pub async fn run(server: &FaastaServer, repair: bool) -> Result<Vec<Issue>> {
+    let issues = scan(server)?;
+    for issue in &issues {
+        warn!("fsck: {issue}");
+        if repair && let Err(err) = fix(server, issue).await {
+            error!("fsck: failed to repair ({issue}): {err:#}");
+        }
+    }
+    info!(
+        "fsck: {} issue(s) found{}",
+        issues.len(),
+        if repair { ", repairs attempted" } else { "" }
+    );
+    Ok(issues)
+}
+
+/// Find inconsistencies without logging or repairing them.
+pub fn scan(server: &FaastaServer) -> Result<Vec<Issue>> {
+    let mut issues = Vec::new();
+
+    let mut owners = HashMap::new();
+    for (name, encoded) in server.metadata_db.scan(Tree::Functions)? {
+        match record::decode::<FunctionInfo>(&encoded) {
+            Ok(info) => {
+                owners.insert(name, info.owner);
+            }
+            Err(err) => warn!("unreadable function record for '{name}': {err}"),
+        }
+    }
+
+    // Artifacts on disk
+    let mut on_disk = HashSet::new();
+    for entry in fs::read_dir(&server.functions_dir)
+        .with_context(|| format!("failed to read {}", server.functions_dir.display()))?
+    {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if file_name.ends_with(".wasm.tmp") || file_name.ends_with(".cwasm.tmp") {
+            issues.push(Issue::LeftoverStaging { path });
+        } else if let Some(name) = file_name
+            .strip_suffix(".wasm")
+            .or_else(|| file_name.strip_suffix(".cwasm"))
+        {
+            // A `.cwasm` beside the `.wasm` is its compiled copy, stale once the component
+            // has been replaced.
+            if file_name.ends_with(".cwasm")
+                && is_older(&path, &server.functions_dir.join(format!("{name}.wasm")))
+            {
+                issues.push(Issue::StaleCompiledArtifact { path });
+                continue;
+            }
+            on_disk.insert(name.to_string());
+            if !owners.contains_key(name) {
+                issues.push(Issue::ArtifactWithoutMetadata {
+                    name: name.to_string(),
+                    path,
+                });
+            }
+        }
+    }
+
+    let sites_dir = server.functions_dir.join("sites");
+    if sites_dir.is_dir() {
+        for entry in fs::read_dir(&sites_dir)? {
+            let path = entry?.path();
+            let Some(dir_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if dir_name.ends_with(".staging") || dir_name.ends_with(".previous") {
+                issues.push(Issue::LeftoverStaging { path });
+            } else if path.is_dir() {
+                on_disk.insert(dir_name.to_string());
+                if !owners.contains_key(dir_name) {
+                    issues.push(Issue::ArtifactWithoutMetadata {
+                        name: dir_name.to_string(),
+                        path,
+                    });
+                }
+            }
+        }
+    }
+
+    // Function records
+    for (name, owner) in &owners {
+        if !on_disk.contains(name) {
+            issues.push(Issue::MetadataWithoutArtifact { name: name.clone() });
+        } else if !server
+            .github_auth
+            .get_user_projects(owner)
+            .is_some_and(|projects| projects.contains(name))
+            || server.github_auth.project_owner(name).as_ref() != Some(owner)
+        {
+            issues.push(Issue::UnregisteredProject {
+                name: name.clone(),
+                owner: owner.clone(),
+            });
+        }
+    }
+
+    // Project lists
+    for (username, projects) in server.github_auth.all_user_projects() {
+        for project in projects {
+            if !owners.contains_key(&project) {
+                issues.push(Issue::ProjectWithoutFunction {
+                    username: username.clone(),
+                    project,
+                });
+            }
+        }
+    }
+
+    // Metrics
+    for (metric_key, ..) in METRICS_DB.iter_metrics()? {
+        let current = MetricKey::decode(&metric_key).is_some_and(|key| {
+            let function = key.function();
+            owners
+                .get(&function.name)
+                .is_some_and(|owner| *owner == function.owner)
+        });
+        if !current {
+            issues.push(Issue::MetricsWithoutFunction { metric_key });
+        }
+    }
+    Ok(issues)
+}
+
+async fn fix(server: &FaastaServer, issue: &Issue) -> Result<()> {
+    match issue {
+        Issue::ArtifactWithoutMetadata { path, .. } => {
+            // Keep the bytes around for an operator to inspect rather than deleting them.
+            let orphaned = server.functions_dir.join(ORPHANED_DIR);
+            fs::create_dir_all(&orphaned)?;
+            let file_name = path.file_name().context("artifact path has no file name")?;
+            fs::rename(path, orphaned.join(file_name))?;
+        }
+        Issue::MetadataWithoutArtifact { name } => {
+            server.metadata_db.delete_function(name)?;
+            server.function_configs.remove(name)?;
+            for (username, projects) in server.github_auth.all_user_projects() {
+                if projects.contains(name) {
+                    server.github_auth.remove_project(&username, name).await?;
+                }
+            }
+        }
+        Issue::StaleCompiledArtifact { path } => fs::remove_file(path)?,
+        Issue::LeftoverStaging { path } => remove_path(path)?,
+        Issue::ProjectWithoutFunction { username, project } => {
+            server.github_auth.remove_project(username, project).await?;
+        }
+        Issue::UnregisteredProject { name, owner } => {
+            let registration = server.github_auth.project_registration(owner, name)?;
+            server.metadata_db.transaction(registration)?;
+            server.github_auth.project_registered(owner, name);
+        }
+        Issue::MetricsWithoutFunction { metric_key } => metrics::forget_metric(metric_key)?,
+    }
+    Ok(())
+}
+
+/// Whether `path` was last modified before `other`; false if either is missing.
+fn is_older(path: &Path, other: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(path), modified(other)) {
+        (Ok(modified), Ok(other)) => modified < other,
+        _ => false,
+    }
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use dashmap::DashMap;
+use faasta_interface::{FunctionConfig, KeepWarm};
+use tracing::warn;
+
+use crate::db::MetadataStore;
+
+/// In-memory view of per-function platform settings, persisted as JSON so new
+/// policy fields can be added with serde defaults without migrating rows.
+pub struct FunctionConfigStore {
+    configs: DashMap<String, Arc<FunctionConfig>>,
+    db: Arc<dyn MetadataStore>,
+}
+
+impl FunctionConfigStore {
+    pub fn new(db: Arc<dyn MetadataStore>) -> Result<Self> {
+        let configs = DashMap::new();
+        for (name, encoded) in db.iter_function_configs()? {
+            match serde_json::from_slice::<FunctionConfig>(&encoded) {
+                Ok(config) => {
+                    configs.insert(name, Arc::new(config));
+                }
+                Err(err) => warn!("ignoring unreadable config for function '{name}': {err}"),
+            }
+        }
+
+        Ok(Self { configs, db })
+    }
+
+    /// Settings for a function, or the defaults if none were stored.
+    pub fn get(&self, function_name: &str) -> Arc<FunctionConfig> {
+        self.configs
+            .get(function_name)
+            .map(|entry| entry.clone())
+            .unwrap_or_default()
+    }
+
+    /// Functions with a keep-warm policy.
+    pub fn keep_warm_policies(&self) -> Vec<(String, KeepWarm)> {
+        self.configs
+            .iter()
+            .filter_map(|entry| {
+                let policy = entry.value().keep_warm.clone()?;
+                Some((entry.key().clone(), policy))
+            })
+            .collect()
+    }
+
+    /// Functions pinned to `region`.
+    pub fn pinned_to(&self, region: &str) -> Vec<String> {
+        self.configs
+            .iter()
+            .filter(|entry| entry.value().regions.iter().any(|pinned| pinned == region))
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    pub fn put(&self, function_name: &str, config: FunctionConfig) -> Result<()> {
+        let encoded = serde_json::to_vec(&config)?;
+        self.db.put_function_config(function_name, &encoded)?;
+        self.configs
+            .insert(function_name.to_string(), Arc::new(config));
+        Ok(())
+    }
+
+    pub fn remove(&self, function_name: &str) -> Result<()> {
+        self.configs.remove(function_name);
+        self.db.delete_function_config(function_name)
+    }
+}
@@ -0,0 +1,136 @@
+//! Keys for state the server keeps per function. Function names are unique today, but
+//! compiled components and metrics are keyed by owner and name so that functions of
+//! different owners can share a name once names are namespaced.
+
+use std::fmt;
+
+use crate::db::key;
+
+/// How shadow traffic is labelled in metrics, and how older releases keyed it
+const SHADOW_PREFIX: &str = "shadow:";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FunctionKey {
+    pub owner: String,
+    pub name: String,
+}
+
+impl FunctionKey {
+    pub fn new(owner: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            owner: owner.into(),
+            name: name.into(),
+        }
+    }
+}
+
+impl fmt::Display for FunctionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+/// What invocation metrics are recorded under.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MetricKey {
+    Function(FunctionKey),
+    /// Mirrored traffic, kept apart from the function's real traffic
+    Shadow(FunctionKey),
+}
+
+impl MetricKey {
+    pub fn function(&self) -> &FunctionKey {
+        match self {
+            MetricKey::Function(function) | MetricKey::Shadow(function) => function,
+        }
+    }
+
+    /// Name the metrics are reported under: the function's, or `shadow:NAME`.
+    pub fn display_name(&self) -> String {
+        match self {
+            MetricKey::Function(function) => function.name.clone(),
+            MetricKey::Shadow(function) => format!("{SHADOW_PREFIX}{}", function.name),
+        }
+    }
+
+    /// Persisted form; see [`crate::db::key`].
+    pub fn encode(&self) -> String {
+        let (kind, function) = match self {
+            MetricKey::Function(function) => ("fn", function),
+            MetricKey::Shadow(function) => ("shadow", function),
+        };
+        key::encode([kind, &function.owner, &function.name])
+    }
+
+    pub fn decode(encoded: &str) -> Option<Self> {
+        match key::decode(encoded)?.as_slice() {
+            ["fn", owner, name] => Some(MetricKey::Function(FunctionKey::new(*owner, *name))),
+            ["shadow", owner, name] => Some(MetricKey::Shadow(FunctionKey::new(*owner, *name))),
+            _ => None,
+        }
+    }
+
+    /// A key written by an older release: a bare function name or `owner/name`, with
+    /// shadow traffic under a `shadow:` prefix on the name. `owner_of` supplies the
+    /// owner of bare names.
+    pub fn from_legacy(legacy: &str, owner_of: impl FnOnce(&str) -> String) -> Self {
+        let (owner, name) = match legacy.split_once('/') {
+            Some((owner, name)) => (Some(owner), name),
+            None => (None, legacy),
+        };
+        let (shadow, name) = match name.strip_prefix(SHADOW_PREFIX) {
+            Some(name) => (true, name),
+            None => (false, name),
+        };
+        let owner = owner.map_or_else(|| owner_of(name), str::to_string);
+        let function = FunctionKey::new(owner, name);
+        if shadow {
+            MetricKey::Shadow(function)
+        } else {
+            MetricKey::Function(function)
+        }
+    }
+}
+
+impl fmt::Display for MetricKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricKey::Function(function) => function.fmt(f),
+            MetricKey::Shadow(function) => write!(f, "{function} (shadow)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_persisted_keys() {
+        let key = MetricKey::Function(FunctionKey::new("alice", "api"));
+        assert_eq!(key.encode(), "2:fn5:alice3:api");
+        assert_eq!(MetricKey::decode(&key.encode()), Some(key));
+        let shadow = MetricKey::Shadow(FunctionKey::new("alice", "api"));
+        assert_eq!(shadow.display_name(), "shadow:api");
+        assert_eq!(MetricKey::decode(&shadow.encode()), Some(shadow));
+        assert_eq!(MetricKey::decode("2:db5:alice3:api"), None);
+    }
+
+    #[test]
+    fn reads_legacy_keys() {
+        let owner_of = |name: &str| format!("owner-of-{name}");
+        assert_eq!(MetricKey::decode("api"), None);
+        assert_eq!(
+            MetricKey::from_legacy("api", owner_of),
+            MetricKey::Function(FunctionKey::new("owner-of-api", "api"))
+        );
+        assert_eq!(
+            MetricKey::from_legacy("shadow:api", owner_of),
+            MetricKey::Shadow(FunctionKey::new("owner-of-api", "api"))
+        );
+        assert_eq!(
+            MetricKey::from_legacy("alice/shadow:api", owner_of),
+            MetricKey::Shadow(FunctionKey::new("alice", "api"))
+        );
+    }
+}
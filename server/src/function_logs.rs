@@ -0,0 +1,322 @@
+//! Recent output of each function, for `cargo faasta logs`: lines its guests wrote to
+//! stdout, stderr or `faasta:log`.
+//!
+//! Each function keeps its last [`MAX_LINES`] lines, and at most [`MAX_BYTES`] of them,
+//! in a ring buffer. Once [`persist_to`] names a store, buffers are loaded from it on
+//! first use and written back by a periodic flush, so a restart loses at most one
+//! interval's lines. Each of an invocation's output streams gets the same line length
+//! and byte budget as `faasta:log`.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use bincode::{Decode, Encode};
+use dashmap::DashMap;
+use faasta_interface::{LogEntry, LogStream};
+use once_cell::sync::{Lazy, OnceCell};
+use tokio::io::AsyncWrite;
+use tracing::{error, warn};
+
+use crate::db::{MetadataStore, Tree, record};
+use crate::guest_log::{MAX_INVOCATION_BYTES, MAX_LINE_BYTES};
+
+/// Lines kept per function
+pub const MAX_LINES: usize = 1000;
+/// Bytes of line text kept per function
+pub const MAX_BYTES: usize = 256 * 1024;
+
+static BUFFERS: Lazy<DashMap<String, Buffer>> = Lazy::new(DashMap::new);
+static STORE: OnceCell<Arc<dyn MetadataStore>> = OnceCell::new();
+
+#[derive(Default)]
+struct Buffer {
+    lines: VecDeque<Line>,
+    bytes: usize,
+    /// Changed since it was last written to the store
+    dirty: bool,
+}
+
+/// Record in `Tree::Logs`, one list per function
+#[derive(Clone, Debug, Encode, Decode)]
+struct Line {
+    /// Unix milliseconds
+    at: u64,
+    stream: LogStream,
+    text: String,
+}
+
+/// Keep logs in `store` across restarts.
+pub fn persist_to(store: Arc<dyn MetadataStore>) {
+    let _ = STORE.set(store);
+}
+
+fn load(function: &str) -> Buffer {
+    let Some(store) = STORE.get() else {
+        return Buffer::default();
+    };
+    let lines: Vec<Line> = match store.get(Tree::Logs, function) {
+        Ok(Some(encoded)) => record::decode(&encoded).unwrap_or_else(|err| {
+            warn!("dropping unreadable logs of '{function}': {err:#}");
+            Vec::new()
+        }),
+        Ok(None) => Vec::new(),
+        Err(err) => {
+            error!("failed to load logs of '{function}': {err:#}");
+            Vec::new()
+        }
+    };
+    Buffer {
+        bytes: lines.iter().map(|line| line.text.len()).sum(),
+        lines: lines.into(),
+        dirty: false,
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Append a line to the log of `function`, dropping its oldest lines to make room.
+pub fn append(function: &str, stream: LogStream, text: &str) {
+    let mut buffer = BUFFERS
+        .entry(function.to_string())
+        .or_insert_with(|| load(function));
+    buffer.bytes += text.len();
+    buffer.lines.push_back(Line {
+        at: now_millis(),
+        stream,
+        text: text.to_string(),
+    });
+    while buffer.lines.len() > MAX_LINES || buffer.bytes > MAX_BYTES {
+        let Some(oldest) = buffer.lines.pop_front() else {
+            break;
+        };
+        buffer.bytes -= oldest.text.len();
+    }
+    buffer.dirty = true;
+}
+
+/// Lines of `function` written after `since` (Unix milliseconds), oldest first, and of
+/// those only the last `tail`; 0 for either means no limit.
+pub fn read(function: &str, since: u64, tail: usize) -> Vec<LogEntry> {
+    let buffer = BUFFERS
+        .entry(function.to_string())
+        .or_insert_with(|| load(function))
+        .downgrade();
+    let lines: Vec<&Line> = buffer.lines.iter().filter(|line| line.at > since).collect();
+    let skip = if tail == 0 {
+        0
+    } else {
+        lines.len().saturating_sub(tail)
+    };
+    lines[skip..]
+        .iter()
+        .map(|line| LogEntry {
+            timestamp: chrono::DateTime::<chrono::Utc>::from(
+                UNIX_EPOCH + Duration::from_millis(line.at),
+            )
+            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            stream: line.stream,
+            line: line.text.clone(),
+        })
+        .collect()
+}
+
+/// Forget the logs of `function`, e.g. when it is unpublished.
+pub fn remove(function: &str) -> Result<()> {
+    BUFFERS.remove(function);
+    match STORE.get() {
+        Some(store) => store.delete(Tree::Logs, function),
+        None => Ok(()),
+    }
+}
+
+/// Write buffers changed since the last flush to the store.
+pub fn flush() {
+    let Some(store) = STORE.get() else {
+        return;
+    };
+    let changed: Vec<(String, Vec<Line>)> = BUFFERS
+        .iter_mut()
+        .filter(|buffer| buffer.dirty)
+        .map(|mut buffer| {
+            buffer.dirty = false;
+            (buffer.key().clone(), buffer.lines.iter().cloned().collect())
+        })
+        .collect();
+    for (function, lines) in changed {
+        let written = record::encode(&lines).and_then(|encoded| {
+            store.put(Tree::Logs, &function, &encoded)?;
+            Ok(())
+        });
+        if let Err(err) = written {
+            error!("failed to persist logs of '{function}': {err:#}");
+            if let Some(mut buffer) = BUFFERS.get_mut(&function) {
+                buffer.dirty = true;
+            }
+        }
+    }
+}
+
+pub fn spawn_periodic_flush(interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            tokio::task::spawn_blocking(flush).await.ok();
+        }
+    });
+}
+
+/// Collects what a guest writes to stdout or stderr into its function's log, a line at
+/// a time. Lines are cut off at `MAX_LINE_BYTES`, and once the invocation's budget is
+/// spent the rest of its output is dropped with a single note.
+pub struct Capture {
+    function: String,
+    stream: LogStream,
+    partial: Vec<u8>,
+    remaining: usize,
+    dropped: bool,
+}
+
+impl Capture {
+    pub fn new(function: &str, stream: LogStream) -> Self {
+        Self {
+            function: function.to_string(),
+            stream,
+            partial: Vec::new(),
+            remaining: MAX_INVOCATION_BYTES,
+            dropped: false,
+        }
+    }
+
+    fn write(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let (line, rest, complete) = match bytes.iter().position(|byte| *byte == b'\n') {
+                Some(end) => (&bytes[..end], &bytes[end + 1..], true),
+                None => (bytes, &[][..], false),
+            };
+            let room = MAX_LINE_BYTES.saturating_sub(self.partial.len());
+            self.partial
+                .extend_from_slice(&line[..line.len().min(room)]);
+            if complete {
+                self.end_line();
+            }
+            bytes = rest;
+        }
+    }
+
+    fn end_line(&mut self) {
+        let partial = std::mem::take(&mut self.partial);
+        let text = String::from_utf8_lossy(&partial);
+        let text = text.trim_end();
+        if self.dropped {
+            return;
+        }
+        if text.len() > self.remaining {
+            self.dropped = true;
+            append(
+                &self.function,
+                LogStream::Warn,
+                &format!(
+                    "log limit of {} KiB per invocation reached; dropping further {} output",
+                    MAX_INVOCATION_BYTES / 1024,
+                    if self.stream == LogStream::Stderr {
+                        "stderr"
+                    } else {
+                        "stdout"
+                    }
+                ),
+            );
+            return;
+        }
+        self.remaining -= text.len();
+        append(&self.function, self.stream, text);
+    }
+}
+
+impl AsyncWrite for Capture {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut().write(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let capture = self.get_mut();
+        if !capture.partial.is_empty() {
+            capture.end_line();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        // A last line without a newline
+        if !self.partial.is_empty() {
+            self.end_line();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_lines() {
+        for index in 0..MAX_LINES + 10 {
+            append("logs-ring", LogStream::Info, &index.to_string());
+        }
+        let lines = read("logs-ring", 0, 0);
+        assert_eq!(lines.len(), MAX_LINES);
+        assert_eq!(lines[0].line, "10");
+        let tail: Vec<_> = read("logs-ring", 0, 2)
+            .into_iter()
+            .map(|entry| entry.line)
+            .collect();
+        assert_eq!(
+            tail,
+            [(MAX_LINES + 8).to_string(), (MAX_LINES + 9).to_string()]
+        );
+        assert!(read("logs-ring", now_millis() + 1000, 0).is_empty());
+        remove("logs-ring").unwrap();
+        assert!(read("logs-ring", 0, 0).is_empty());
+    }
+
+    #[test]
+    fn splits_captured_output_into_lines() {
+        {
+            let mut capture = Capture::new("logs-capture", LogStream::Stdout);
+            capture.write(b"first\nsec");
+            capture.write(b"ond\n\n");
+            capture.write(&vec![b'x'; MAX_LINE_BYTES * 2]);
+            capture.write(b"\nunterminated");
+        }
+        let lines: Vec<_> = read("logs-capture", 0, 0)
+            .into_iter()
+            .map(|entry| (entry.stream, entry.line))
+            .collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], (LogStream::Stdout, "first".to_string()));
+        assert_eq!(lines[1].1, "second");
+        assert_eq!(lines[2].1, "");
+        assert_eq!(lines[3].1.len(), MAX_LINE_BYTES);
+        assert_eq!(lines[4].1, "unterminated");
+    }
+}
@@ -0,0 +1,139 @@
+//! Background removal of on-disk data that no published function refers to.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::db::Tree;
+use crate::fsck::ORPHANED_DIR;
+use crate::metrics::disk_size;
+use crate::wasi_server::FaastaServer;
+
+#[derive(Debug, Default)]
+pub struct GcStats {
+    pub compiled_artifacts: usize,
+    pub sandboxes: usize,
+    pub quarantined: usize,
+    pub uploads: usize,
+    pub bytes: u64,
+}
+
+/// Remove data untouched for longer than `retention`: precompiled artifacts and
+/// sandboxes of functions that are no longer published, resumable uploads, and
+/// artifacts quarantined by `--fsck --repair`.
+pub fn collect(server: &FaastaServer, retention: Duration) -> Result<GcStats> {
+    let mut stats = GcStats::default();
+    // Functions with a publish in flight are left alone even before their record exists.
+    let publishing = server.metadata_db.scan(Tree::PublishIntents)?;
+    let unpublished = |name: &str| -> Result<bool> {
+        Ok(server.metadata_db.get_function(name)?.is_none()
+            && !publishing.iter().any(|(key, _)| key == name))
+    };
+
+    for entry in fs::read_dir(&server.functions_dir)? {
+        let path = entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".cwasm"))
+        else {
+            continue;
+        };
+        if unpublished(name)? && expired(&path, retention) {
+            stats.bytes += remove(&path);
+            stats.compiled_artifacts += 1;
+        }
+    }
+
+    for entry in fs::read_dir(&server.sandbox_root)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if unpublished(name)? && expired(&path, retention) {
+            stats.bytes += remove(&path);
+            stats.sandboxes += 1;
+        }
+    }
+
+    for (dir, count) in [
+        (ORPHANED_DIR, &mut stats.quarantined),
+        ("uploads", &mut stats.uploads),
+    ] {
+        let dir = server.functions_dir.join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if expired(&path, retention) {
+                stats.bytes += remove(&path);
+                *count += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Spawn a background task that runs [`collect`] every `interval`.
+pub fn spawn_periodic(server: Arc<FaastaServer>, interval: Duration, retention: Duration) {
+    if interval.is_zero() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match collect(&server, retention) {
+                Ok(stats)
+                    if stats.compiled_artifacts
+                        + stats.sandboxes
+                        + stats.quarantined
+                        + stats.uploads
+                        > 0 =>
+                {
+                    info!(
+                        "gc removed {} compiled artifacts, {} sandboxes, {} uploads and {} quarantined files ({} bytes)",
+                        stats.compiled_artifacts,
+                        stats.sandboxes,
+                        stats.uploads,
+                        stats.quarantined,
+                        stats.bytes
+                    )
+                }
+                Ok(_) => {}
+                Err(err) => error!("gc failed: {err:#}"),
+            }
+        }
+    });
+}
+
+fn expired(path: &Path, retention: Duration) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age >= retention)
+}
+
+/// Delete a file or directory tree, returning the bytes freed.
+fn remove(path: &Path) -> u64 {
+    let size = disk_size(path);
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Ok(()) => size,
+        Err(err) => {
+            warn!("gc failed to remove {}: {err}", path.display());
+            0
+        }
+    }
+}
@@ -1,59 +1,94 @@
+//! GitHub users and the projects (function names) they own.
+//!
+//! Accounts, projects and the project -> owner index are separate trees, written
+//! together in one transaction whenever a project is added or removed. Tokens are not
+//! stored: every call is checked against the GitHub API, and recorded as a session
+//! the user can revoke.
+
 use anyhow::Result;
 use bincode::{Decode, Encode};
 use dashmap::DashMap;
+use faasta_interface::{elevation, signing};
 use reqwest::Client as HttpClient;
-use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::db::Database;
+use crate::db::{MetadataOp, MetadataStore, Tree, key};
+use crate::sessions::{self, Sessions};
 
 const MAX_PROJECTS_PER_USER: usize = 10;
 const USER_AGENT: &str = "faasta-server";
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Base URL of the GitHub API, overridable with `FAASTA_GITHUB_API_URL` for GitHub
+/// Enterprise or a stand-in in tests
+fn github_api_url() -> String {
+    std::env::var("FAASTA_GITHUB_API_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| GITHUB_API_URL.to_string())
+}
 
 pub struct GitHubAuth {
-    user_projects: DashMap<String, UserData>,
-    db: std::sync::Arc<Database>,
+    user_projects: DashMap<String, Vec<String>>,
+    project_owners: DashMap<String, String>,
+    pub sessions: Sessions,
+    db: std::sync::Arc<dyn MetadataStore>,
 }
-#[derive(Serialize, Deserialize, Clone, Debug, Encode, Decode)]
-pub struct UserData {
-    pub github_username: String,
-    pub projects: Vec<String>,
+
+/// Record in `Tree::Accounts`
+#[derive(Clone, Debug, Encode, Decode)]
+struct Account {
+    github_username: String,
+}
+
+/// Record in the legacy `Tree::Users`, which held each user's project list
+#[derive(Clone, Debug, Encode, Decode)]
+struct LegacyUserData {
+    github_username: String,
+    projects: Vec<String>,
 }
 
 impl GitHubAuth {
-    pub async fn new(db: std::sync::Arc<Database>) -> Result<Self> {
-        // Load existing user data
-        let user_projects = DashMap::new();
-        for (username, encoded) in db.iter_users()? {
-            if let Ok((user_data, _)) =
-                bincode::decode_from_slice::<UserData, _>(&encoded, bincode::config::standard())
-            {
-                user_projects.insert(username, user_data);
+    pub async fn new(db: std::sync::Arc<dyn MetadataStore>) -> Result<Self> {
+        migrate_legacy_users(db.as_ref())?;
+
+        let user_projects: DashMap<String, Vec<String>> = DashMap::new();
+        for (username, _) in db.scan(Tree::Accounts)? {
+            user_projects.entry(username).or_default();
+        }
+        for (encoded, _) in db.scan(Tree::Projects)? {
+            match key::decode(&encoded).as_deref() {
+                Some([username, project]) => user_projects
+                    .entry(username.to_string())
+                    .or_default()
+                    .push(project.to_string()),
+                _ => tracing::warn!("Ignoring malformed project key '{encoded}'"),
             }
         }
+        let project_owners = DashMap::new();
+        for (project, owner) in db.scan(Tree::ProjectOwners)? {
+            project_owners.insert(project, String::from_utf8(owner)?);
+        }
 
-        Ok(Self { user_projects, db })
+        Ok(Self {
+            user_projects,
+            project_owners,
+            sessions: Sessions::new(db.clone())?,
+            db,
+        })
     }
 
     /// Authenticate and extract username from GitHub token in a single API call
     /// Returns (username, is_valid) tuple
     pub async fn authenticate_github(&self, token: &str) -> Result<(String, bool)> {
-        // Check if the token is in the format "username:token"
-        let (provided_username, token_value) =
-            if let Some((username, token_part)) = token.split_once(':') {
-                (
-                    Some(username.to_string()),
-                    token_part
-                        .strip_prefix("Bearer ")
-                        .unwrap_or(token_part)
-                        .trim(),
-                )
-            } else {
-                (None, token.strip_prefix("Bearer ").unwrap_or(token).trim())
-            };
+        let (provided_username, token_value) = parse_token(token);
+        let session = sessions::session_id(token_value);
+        if self.sessions.is_revoked(&session) {
+            tracing::warn!("Refusing revoked session {session}");
+            return Ok(("".to_string(), false));
+        }
 
         let response = match HttpClient::new()
-            .get("https://api.github.com/user")
+            .get(format!("{}/user", github_api_url()))
             .header("User-Agent", USER_AGENT)
             .header("Authorization", format!("Bearer {token_value}"))
             .send()
@@ -94,15 +129,23 @@ impl GitHubAuth {
             return Ok((api_username.to_string(), false));
         }
 
+        if let Err(e) = self.sessions.touch(&session, api_username, token_value) {
+            tracing::warn!("Failed to record session for '{api_username}': {e:#}");
+        }
         Ok((api_username.to_string(), true))
     }
 
+    /// Session of the GitHub token in an auth token
+    pub fn session_id(&self, token: &str) -> String {
+        sessions::session_id(parse_token(token).1)
+    }
+
     /// Check if a user can upload more projects (limit is MAX_PROJECTS_PER_USER)
     pub fn can_upload_project(&self, username: &str, project_name: &str) -> bool {
-        if let Some(user_data) = self.user_projects.get(username) {
+        if let Some(projects) = self.user_projects.get(username) {
             // Check if they're already at the limit
-            if user_data.projects.len() >= MAX_PROJECTS_PER_USER
-                && !user_data.projects.contains(&project_name.to_string())
+            if projects.len() >= MAX_PROJECTS_PER_USER
+                && !projects.iter().any(|project| project == project_name)
             {
                 return false;
             }
@@ -110,47 +153,85 @@ impl GitHubAuth {
         true
     }
 
-    /// Add a project to a user's list
-    pub async fn add_project(&self, username: &str, project_name: &str) -> Result<()> {
-        // Get or create user data
-        let mut user_data = if let Some(data) = self.user_projects.get(username) {
-            data.clone()
-        } else {
-            UserData {
-                github_username: username.to_string(),
-                projects: Vec::new(),
-            }
+    /// Database writes that register a project for a user, so they can be committed in
+    /// the same transaction as the function's metadata. A listing of the project under a
+    /// previous owner is dropped. Call `project_registered` once the transaction has
+    /// been applied.
+    pub fn project_registration(
+        &self,
+        username: &str,
+        project_name: &str,
+    ) -> Result<Vec<MetadataOp>> {
+        let account = Account {
+            github_username: username.to_string(),
         };
-
-        // Add or update the project
-        if !user_data.projects.contains(&project_name.to_string()) {
-            user_data.projects.push(project_name.to_string());
+        let mut ops = vec![
+            MetadataOp::Put {
+                tree: Tree::Accounts,
+                key: username.to_string(),
+                value: bincode::encode_to_vec(&account, bincode::config::standard())?,
+            },
+            MetadataOp::Put {
+                tree: Tree::Projects,
+                key: key::encode([username, project_name]),
+                value: Vec::new(),
+            },
+            MetadataOp::Put {
+                tree: Tree::ProjectOwners,
+                key: project_name.to_string(),
+                value: username.as_bytes().to_vec(),
+            },
+        ];
+        if let Some(previous) = self.project_owner(project_name)
+            && previous != username
+        {
+            ops.push(MetadataOp::Delete {
+                tree: Tree::Projects,
+                key: key::encode([previous.as_str(), project_name]),
+            });
         }
+        Ok(ops)
+    }
 
-        // Update the map
-        self.user_projects
-            .insert(username.to_string(), user_data.clone());
-
-        // Save to database
-        let encoded = bincode::encode_to_vec(&user_data, bincode::config::standard())?;
-        self.db.put_user(username, &encoded)?;
-
-        Ok(())
+    /// Reflect a committed `project_registration` in memory
+    pub fn project_registered(&self, username: &str, project_name: &str) {
+        if let Some(previous) = self
+            .project_owners
+            .insert(project_name.to_string(), username.to_string())
+            && previous != username
+            && let Some(mut projects) = self.user_projects.get_mut(&previous)
+        {
+            projects.retain(|project| project != project_name);
+        }
+        let mut projects = self.user_projects.entry(username.to_string()).or_default();
+        if !projects.iter().any(|project| project == project_name) {
+            projects.push(project_name.to_string());
+        }
     }
 
-    /// Remove a project from a user's list
+    /// Remove a project from a user's list, and from the owner index if it points at them
     pub async fn remove_project(&self, username: &str, project_name: &str) -> Result<()> {
-        // Get user data
-        if let Some(mut user_data) = self.user_projects.get_mut(username) {
-            // Remove the project
-            user_data.projects.retain(|p| p != project_name);
-
-            // Save to database
-            let user_data_clone = user_data.clone();
-            let encoded = bincode::encode_to_vec(&user_data_clone, bincode::config::standard())?;
-            self.db.put_user(username, &encoded)?;
+        let owned = self
+            .project_owner(project_name)
+            .is_some_and(|owner| owner == username);
+        let mut ops = vec![MetadataOp::Delete {
+            tree: Tree::Projects,
+            key: key::encode([username, project_name]),
+        }];
+        if owned {
+            ops.push(MetadataOp::Delete {
+                tree: Tree::ProjectOwners,
+                key: project_name.to_string(),
+            });
         }
+        self.db.transaction(ops)?;
 
+        if let Some(mut projects) = self.user_projects.get_mut(username) {
+            projects.retain(|project| project != project_name);
+        }
+        if owned {
+            self.project_owners.remove(project_name);
+        }
         Ok(())
     }
 
@@ -158,6 +239,88 @@ impl GitHubAuth {
     pub fn get_user_projects(&self, username: &str) -> Option<Vec<String>> {
         self.user_projects
             .get(username)
-            .map(|user_data| user_data.projects.clone())
+            .map(|projects| projects.clone())
+    }
+
+    /// The user a project is registered to
+    pub fn project_owner(&self, project_name: &str) -> Option<String> {
+        self.project_owners
+            .get(project_name)
+            .map(|owner| owner.clone())
+    }
+
+    /// Every user with their registered projects
+    pub fn all_user_projects(&self) -> Vec<(String, Vec<String>)> {
+        self.user_projects
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+}
+
+/// Username, if given, and GitHub token of an auth token of the form
+/// `[username:][Bearer ]token[#elevated=...][#sig=...]`. Request signatures and
+/// elevation are checked separately.
+fn parse_token(token: &str) -> (Option<&str>, &str) {
+    let token = elevation::split_token(signing::split_token(token).0).0;
+    let (username, token) = match token.split_once(':') {
+        Some((username, token)) => (Some(username), token),
+        None => (None, token),
+    };
+    (
+        username,
+        token.strip_prefix("Bearer ").unwrap_or(token).trim(),
+    )
+}
+
+/// Move user records of the legacy layout into the account, project and owner trees,
+/// one transaction per user. Records that don't decode are left for an operator.
+fn migrate_legacy_users(db: &dyn MetadataStore) -> Result<()> {
+    let mut claimed: std::collections::HashSet<String> = db
+        .scan(Tree::ProjectOwners)?
+        .into_iter()
+        .map(|(project, _)| project)
+        .collect();
+    for (username, encoded) in db.scan(Tree::Users)? {
+        let Ok((legacy, _)) =
+            bincode::decode_from_slice::<LegacyUserData, _>(&encoded, bincode::config::standard())
+        else {
+            tracing::warn!("Leaving undecodable legacy user record '{username}' in place");
+            continue;
+        };
+        let account = Account {
+            github_username: legacy.github_username,
+        };
+        let mut ops = vec![MetadataOp::Put {
+            tree: Tree::Accounts,
+            key: username.clone(),
+            value: bincode::encode_to_vec(&account, bincode::config::standard())?,
+        }];
+        for project in &legacy.projects {
+            ops.push(MetadataOp::Put {
+                tree: Tree::Projects,
+                key: key::encode([username.as_str(), project.as_str()]),
+                value: Vec::new(),
+            });
+            // A project listed by two users is indexed to the first; fsck re-registers it
+            // if that isn't the function's owner
+            if claimed.insert(project.clone()) {
+                ops.push(MetadataOp::Put {
+                    tree: Tree::ProjectOwners,
+                    key: project.clone(),
+                    value: username.as_bytes().to_vec(),
+                });
+            }
+        }
+        ops.push(MetadataOp::Delete {
+            tree: Tree::Users,
+            key: username.clone(),
+        });
+        db.transaction(ops)?;
+        tracing::info!(
+            "Migrated user '{username}' with {} projects to the account and project trees",
+            legacy.projects.len()
+        );
     }
+    Ok(())
 }
@@ -0,0 +1,350 @@
+//! GraphQL requests to a function's configured endpoint.
+//!
+//! Bodies sent as `application/graphql` are rewritten into the JSON form every GraphQL
+//! server accepts, and automatic persisted queries (APQ) are resolved at the edge: once
+//! a client has sent a query with its SHA-256 in `extensions.persistedQuery`, later
+//! requests may carry only the hash, for `POST` bodies and `GET` query strings alike,
+//! and the host fills the query back in before the function sees the request. Queries
+//! are kept in memory per function, oldest dropped first; a client whose hash is
+//! unknown gets `PersistedQueryNotFound` and resends the full query, as the protocol
+//! expects. Batched (array) bodies are passed through untouched.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use axum::body::Body;
+use bytes::Bytes;
+use dashmap::DashMap;
+use http::{HeaderMap, HeaderValue, Method, Response, StatusCode, Uri, header};
+use ring::digest::{SHA256, digest};
+use serde_json::{Map, Value, json};
+
+pub const CONTENT_TYPE: &str = "application/graphql";
+/// Longest query the host remembers
+const MAX_QUERY_BYTES: usize = 64 * 1024;
+
+/// The response to send instead of invoking the function
+pub type Rejection = Box<Response<Body>>;
+
+/// Queries seen with a persisted-query hash, per function.
+pub struct PersistedQueries {
+    capacity: usize,
+    functions: DashMap<String, Queries>,
+}
+
+#[derive(Default)]
+struct Queries {
+    by_hash: HashMap<String, Arc<str>>,
+    order: VecDeque<String>,
+}
+
+/// What a request's `query` and `extensions` resolve to.
+#[derive(Debug)]
+enum Resolved {
+    /// Forward the request as it is
+    Unchanged,
+    /// Forward the request with this query filled in
+    Query(Arc<str>),
+}
+
+impl PersistedQueries {
+    /// Keeps up to `capacity` queries per function; 0 turns persisted queries off.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            functions: DashMap::new(),
+        }
+    }
+
+    /// Turn a request to a GraphQL endpoint into what the function receives, rewriting
+    /// its headers. Fails with the response to send instead.
+    pub fn prepare(
+        &self,
+        function_name: &str,
+        method: &Method,
+        headers: &mut HeaderMap,
+        uri: Uri,
+        body: Bytes,
+    ) -> Result<(Uri, Bytes), Rejection> {
+        if method == Method::GET {
+            return Ok((self.prepare_query_string(function_name, uri)?, body));
+        }
+        if method != Method::POST {
+            return Ok((uri, body));
+        }
+
+        if is_graphql_body(headers) {
+            let query = std::str::from_utf8(&body)
+                .map_err(|_| graphql_error(StatusCode::BAD_REQUEST, "Query is not UTF-8", None))?;
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            headers.remove(header::CONTENT_LENGTH);
+            return Ok((uri, Bytes::from(json!({ "query": query }).to_string())));
+        }
+
+        let Ok(Value::Object(mut operation)) = serde_json::from_slice::<Value>(&body) else {
+            return Ok((uri, body));
+        };
+        let query = operation.get("query").and_then(Value::as_str);
+        match self.resolve(function_name, query, operation.get("extensions"))? {
+            Resolved::Unchanged => Ok((uri, body)),
+            Resolved::Query(query) => {
+                operation.insert("query".to_string(), Value::String(query.to_string()));
+                headers.remove(header::CONTENT_LENGTH);
+                Ok((uri, Bytes::from(Value::Object(operation).to_string())))
+            }
+        }
+    }
+
+    fn prepare_query_string(&self, function_name: &str, uri: Uri) -> Result<Uri, Rejection> {
+        let Some(query_string) = uri.query() else {
+            return Ok(uri);
+        };
+        let params: Vec<(String, String)> = form_urlencoded::parse(query_string.as_bytes())
+            .into_owned()
+            .collect();
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let Some(extensions) = param("extensions") else {
+            return Ok(uri);
+        };
+        let extensions: Value = serde_json::from_str(extensions).map_err(|_| {
+            graphql_error(
+                StatusCode::BAD_REQUEST,
+                "extensions is not valid JSON",
+                None,
+            )
+        })?;
+        let Resolved::Query(query) =
+            self.resolve(function_name, param("query"), Some(&extensions))?
+        else {
+            return Ok(uri);
+        };
+
+        let mut rewritten = form_urlencoded::Serializer::new(String::new());
+        rewritten.append_pair("query", &query);
+        for (key, value) in params.iter().filter(|(key, _)| key != "query") {
+            rewritten.append_pair(key, value);
+        }
+        format!("{}?{}", uri.path(), rewritten.finish())
+            .parse()
+            .map_err(|_| graphql_error(StatusCode::BAD_REQUEST, "Invalid query string", None))
+    }
+
+    fn resolve(
+        &self,
+        function_name: &str,
+        query: Option<&str>,
+        extensions: Option<&Value>,
+    ) -> Result<Resolved, Rejection> {
+        let Some(persisted) = extensions.and_then(|extensions| extensions.get("persistedQuery"))
+        else {
+            return Ok(Resolved::Unchanged);
+        };
+        if self.capacity == 0 {
+            return Err(graphql_error(
+                StatusCode::OK,
+                "PersistedQueryNotSupported",
+                Some("PERSISTED_QUERY_NOT_SUPPORTED"),
+            ));
+        }
+        if persisted.get("version").and_then(Value::as_u64) != Some(1) {
+            return Err(graphql_error(
+                StatusCode::BAD_REQUEST,
+                "Unsupported persisted query version",
+                None,
+            ));
+        }
+        let Some(hash) = persisted
+            .get("sha256Hash")
+            .and_then(Value::as_str)
+            .map(str::to_ascii_lowercase)
+        else {
+            return Err(graphql_error(
+                StatusCode::BAD_REQUEST,
+                "persistedQuery is missing sha256Hash",
+                None,
+            ));
+        };
+
+        match query {
+            Some(query) => {
+                if hex::encode(digest(&SHA256, query.as_bytes())) != hash {
+                    return Err(graphql_error(
+                        StatusCode::BAD_REQUEST,
+                        "provided sha does not match query",
+                        None,
+                    ));
+                }
+                self.store(function_name, hash, query);
+                Ok(Resolved::Unchanged)
+            }
+            None => self
+                .lookup(function_name, &hash)
+                .map(Resolved::Query)
+                .ok_or_else(|| {
+                    graphql_error(
+                        StatusCode::OK,
+                        "PersistedQueryNotFound",
+                        Some("PERSISTED_QUERY_NOT_FOUND"),
+                    )
+                }),
+        }
+    }
+
+    fn lookup(&self, function_name: &str, hash: &str) -> Option<Arc<str>> {
+        self.functions
+            .get(function_name)?
+            .by_hash
+            .get(hash)
+            .cloned()
+    }
+
+    fn store(&self, function_name: &str, hash: String, query: &str) {
+        if query.len() > MAX_QUERY_BYTES {
+            return;
+        }
+        let mut queries = self.functions.entry(function_name.to_string()).or_default();
+        if queries.by_hash.contains_key(&hash) {
+            return;
+        }
+        while queries.order.len() >= self.capacity {
+            let Some(oldest) = queries.order.pop_front() else {
+                break;
+            };
+            queries.by_hash.remove(&oldest);
+        }
+        queries.order.push_back(hash.clone());
+        queries.by_hash.insert(hash, Arc::from(query));
+    }
+}
+
+fn is_graphql_body(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|essence| essence.trim().eq_ignore_ascii_case(CONTENT_TYPE))
+}
+
+/// A GraphQL response carrying a single error, the way clients expect APQ failures.
+fn graphql_error(status: StatusCode, message: &str, code: Option<&str>) -> Rejection {
+    let mut error = Map::new();
+    error.insert("message".to_string(), Value::from(message));
+    if let Some(code) = code {
+        error.insert("extensions".to_string(), json!({ "code": code }));
+    }
+    Box::new(
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "errors": [error] }).to_string()))
+            .unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUERY: &str = "{ hello }";
+
+    fn hash(query: &str) -> String {
+        hex::encode(digest(&SHA256, query.as_bytes()))
+    }
+
+    fn json_headers(content_type: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers
+    }
+
+    fn persisted_body(query: Option<&str>) -> Bytes {
+        let mut body = json!({
+            "extensions": { "persistedQuery": { "version": 1, "sha256Hash": hash(QUERY) } },
+        });
+        if let Some(query) = query {
+            body["query"] = Value::from(query);
+        }
+        Bytes::from(body.to_string())
+    }
+
+    fn post(
+        queries: &PersistedQueries,
+        headers: &mut HeaderMap,
+        body: Bytes,
+    ) -> Result<Value, StatusCode> {
+        queries
+            .prepare(
+                "fn",
+                &Method::POST,
+                headers,
+                Uri::from_static("/graphql"),
+                body,
+            )
+            .map(|(_, body)| serde_json::from_slice(&body).unwrap())
+            .map_err(|response| response.status())
+    }
+
+    #[test]
+    fn wraps_graphql_bodies() {
+        let queries = PersistedQueries::new(10);
+        let mut headers = json_headers("application/graphql; charset=utf-8");
+        let body = post(&queries, &mut headers, Bytes::from_static(QUERY.as_bytes())).unwrap();
+        assert_eq!(body, json!({ "query": QUERY }));
+        assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    }
+
+    #[test]
+    fn resolves_persisted_queries() {
+        let queries = PersistedQueries::new(10);
+        let mut headers = json_headers("application/json");
+
+        // Unknown hash: the client is told to resend the query
+        assert_eq!(
+            post(&queries, &mut headers, persisted_body(None)),
+            Err(StatusCode::OK)
+        );
+        assert_eq!(
+            post(&queries, &mut headers, persisted_body(Some("{ other }"))),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        post(&queries, &mut headers, persisted_body(Some(QUERY))).unwrap();
+
+        let body = post(&queries, &mut headers, persisted_body(None)).unwrap();
+        assert_eq!(body["query"], QUERY);
+
+        let extensions = json!({ "persistedQuery": { "version": 1, "sha256Hash": hash(QUERY) } });
+        let uri: Uri = format!(
+            "/graphql?operationName=Hello&extensions={}",
+            form_urlencoded::byte_serialize(extensions.to_string().as_bytes()).collect::<String>()
+        )
+        .parse()
+        .unwrap();
+        let (uri, _) = queries
+            .prepare("fn", &Method::GET, &mut HeaderMap::new(), uri, Bytes::new())
+            .unwrap();
+        let params: HashMap<String, String> =
+            form_urlencoded::parse(uri.query().unwrap().as_bytes())
+                .into_owned()
+                .collect();
+        assert_eq!(params["query"], QUERY);
+        assert_eq!(params["operationName"], "Hello");
+    }
+
+    #[test]
+    fn evicts_oldest_queries() {
+        let queries = PersistedQueries::new(1);
+        queries.store("fn", hash("{ a }"), "{ a }");
+        queries.store("fn", hash("{ b }"), "{ b }");
+        assert!(queries.lookup("fn", &hash("{ a }")).is_none());
+        assert!(queries.lookup("fn", &hash("{ b }")).is_some());
+        assert!(queries.lookup("other", &hash("{ b }")).is_none());
+    }
+}
@@ -0,0 +1,426 @@
+//! gRPC-web and Connect unary calls.
+//!
+//! Functions see a unary call as a plain `POST` to `/package.Service/Method` whose body
+//! is the request message, with `content-type` `application/proto` or
+//! `application/json`. The host strips the gRPC-web length prefix (and the base64 of
+//! `grpc-web-text`) on the way in, and on the way out frames the function's response
+//! body as the reply message followed by a trailer frame carrying `grpc-status`. A
+//! function can set `grpc-status` and `grpc-message` response headers itself; otherwise
+//! they are derived from the HTTP status and the host's error body. Connect unary calls
+//! need no framing, so only their errors are rewritten into Connect's JSON form.
+//! Native gRPC over HTTP/2 needs response trailers and isn't supported.
+
+use axum::body::{Body, to_bytes};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bytes::{BufMut, Bytes, BytesMut};
+use faasta_interface::{ErrorBody, error_code};
+use http::{HeaderMap, HeaderValue, Method, Response, StatusCode, header};
+
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_MESSAGE: &str = "grpc-message";
+const CONNECT_PROTOCOL_VERSION: &str = "connect-protocol-version";
+/// Flag bit marking a frame as compressed
+const COMPRESSED_FLAG: u8 = 0x01;
+/// Flag bit marking the trailer frame
+const TRAILER_FLAG: u8 = 0x80;
+const FRAME_HEADER_LEN: usize = 5;
+
+/// The protocol of an incoming unary call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Call {
+    GrpcWeb {
+        /// `application/grpc-web-text`: the body is base64
+        text: bool,
+        /// Message encoding passed to the function, `application/proto` or `application/json`
+        message_type: &'static str,
+        /// Content type of the request, echoed on the response
+        content_type: HeaderValue,
+    },
+    Connect,
+}
+
+/// gRPC status codes, as numbered by gRPC and named by Connect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Code {
+    Ok = 0,
+    Unknown = 2,
+    InvalidArgument = 3,
+    DeadlineExceeded = 4,
+    NotFound = 5,
+    PermissionDenied = 7,
+    ResourceExhausted = 8,
+    Unimplemented = 12,
+    Internal = 13,
+    Unavailable = 14,
+    Unauthenticated = 16,
+}
+
+impl Call {
+    /// The call protocol of a request, if it is one.
+    pub fn detect(method: &Method, headers: &HeaderMap) -> Option<Self> {
+        if method != Method::POST {
+            return None;
+        }
+        let content_type = headers.get(header::CONTENT_TYPE)?;
+        let essence = content_type
+            .to_str()
+            .ok()?
+            .split(';')
+            .next()?
+            .trim()
+            .to_ascii_lowercase();
+        if let Some(rest) = essence.strip_prefix("application/grpc-web") {
+            let (text, subtype) = match rest.strip_prefix("-text") {
+                Some(subtype) => (true, subtype),
+                None => (false, rest),
+            };
+            let message_type = match subtype {
+                "" | "+proto" => "application/proto",
+                "+json" => "application/json",
+                _ => return None,
+            };
+            return Some(Call::GrpcWeb {
+                text,
+                message_type,
+                content_type: content_type.clone(),
+            });
+        }
+        let unary = matches!(essence.as_str(), "application/proto" | "application/json");
+        (unary && headers.contains_key(CONNECT_PROTOCOL_VERSION)).then_some(Call::Connect)
+    }
+
+    /// Turn the request into the message the function receives, rewriting its content
+    /// type. Fails with the response to send instead.
+    pub fn unwrap_request(
+        &self,
+        headers: &mut HeaderMap,
+        body: Bytes,
+    ) -> Result<Bytes, Box<Response<Body>>> {
+        let Call::GrpcWeb {
+            text, message_type, ..
+        } = self
+        else {
+            return Ok(body);
+        };
+        let body = if *text {
+            BASE64.decode(body.as_ref()).map(Bytes::from).map_err(|_| {
+                Box::new(self.error(Code::InvalidArgument, "request is not valid base64"))
+            })?
+        } else {
+            body
+        };
+        let message = match read_frame(&body) {
+            Ok(message) => message,
+            Err(code) => {
+                return Err(Box::new(
+                    self.error(code, "request is not a single gRPC-web frame"),
+                ));
+            }
+        };
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(message_type));
+        headers.remove(header::CONTENT_LENGTH);
+        Ok(message)
+    }
+
+    /// Turn the function's (or the host's error) response into the protocol's reply.
+    pub async fn wrap_response(&self, response: Response<Body>) -> Response<Body> {
+        let (mut parts, body) = response.into_parts();
+        let body = match to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(_) => return self.error(Code::Internal, "failed to read the function's response"),
+        };
+        match self {
+            Call::Connect => {
+                if parts.status.is_success() {
+                    return Response::from_parts(parts, Body::from(body));
+                }
+                let (code, message) = describe(parts.status, &parts.headers, &body);
+                let error = serde_json::json!({
+                    "code": code.connect_name(),
+                    "message": message,
+                });
+                parts.headers.remove(header::CONTENT_LENGTH);
+                parts.headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+                Response::from_parts(parts, Body::from(error.to_string()))
+            }
+            Call::GrpcWeb { .. } => {
+                let (code, status_message) = if parts.status.is_success() {
+                    let code = grpc_status_header(&parts.headers).unwrap_or(Code::Ok as u32);
+                    (code, message_text(&parts.headers, code))
+                } else {
+                    let (code, message) = describe(parts.status, &parts.headers, &body);
+                    let code = grpc_status_header(&parts.headers).unwrap_or(code as u32);
+                    (code, message)
+                };
+                let message = (code == Code::Ok as u32).then_some(body);
+                let reply = self.frame(message, code, &status_message);
+                let mut response = Response::from_parts(parts, Body::from(reply));
+                self.finish(&mut response);
+                response
+            }
+        }
+    }
+
+    /// Body of a gRPC-web reply: the message, if any, then the trailers.
+    fn frame(&self, message: Option<Bytes>, code: u32, status_message: &str) -> Bytes {
+        let mut trailers = format!("{GRPC_STATUS}:{code}\r\n");
+        if !status_message.is_empty() {
+            trailers.push_str(&format!(
+                "{GRPC_MESSAGE}:{}\r\n",
+                percent_encode(status_message)
+            ));
+        }
+        let mut framed = BytesMut::new();
+        if let Some(message) = message {
+            write_frame(&mut framed, 0, &message);
+        }
+        write_frame(&mut framed, TRAILER_FLAG, trailers.as_bytes());
+        let framed = framed.freeze();
+        match self {
+            Call::GrpcWeb { text: true, .. } => Bytes::from(BASE64.encode(&framed)),
+            _ => framed,
+        }
+    }
+
+    /// Headers every gRPC-web reply carries: status 200, the request's content type,
+    /// and the status in trailers rather than headers.
+    fn finish(&self, response: &mut Response<Body>) {
+        let Call::GrpcWeb { content_type, .. } = self else {
+            return;
+        };
+        *response.status_mut() = StatusCode::OK;
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, content_type.clone());
+        for name in [header::CONTENT_LENGTH.as_str(), GRPC_STATUS, GRPC_MESSAGE] {
+            headers.remove(name);
+        }
+    }
+
+    /// A reply the host makes up, e.g. for a malformed request.
+    fn error(&self, code: Code, message: &str) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        match self {
+            Call::Connect => {
+                *response.status_mut() = match code {
+                    Code::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+                    _ => StatusCode::BAD_REQUEST,
+                };
+                response.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+                *response.body_mut() = Body::from(
+                    serde_json::json!({
+                        "code": code.connect_name(),
+                        "message": message,
+                    })
+                    .to_string(),
+                );
+            }
+            Call::GrpcWeb { .. } => {
+                *response.body_mut() = Body::from(self.frame(None, code as u32, message));
+                self.finish(&mut response);
+            }
+        }
+        response
+    }
+}
+
+impl Code {
+    fn connect_name(self) -> &'static str {
+        match self {
+            Code::Ok => "ok",
+            Code::Unknown => "unknown",
+            Code::InvalidArgument => "invalid_argument",
+            Code::DeadlineExceeded => "deadline_exceeded",
+            Code::NotFound => "not_found",
+            Code::PermissionDenied => "permission_denied",
+            Code::ResourceExhausted => "resource_exhausted",
+            Code::Unimplemented => "unimplemented",
+            Code::Internal => "internal",
+            Code::Unavailable => "unavailable",
+            Code::Unauthenticated => "unauthenticated",
+        }
+    }
+
+    /// Code for an error the host reported with one of its own error codes.
+    fn from_error_code(code: &str) -> Option<Self> {
+        Some(match code {
+            error_code::BAD_REQUEST
+            | error_code::INVALID_FUNCTION_NAME
+            | error_code::INVALID_INPUT => Code::InvalidArgument,
+            error_code::FUNCTION_NOT_FOUND | error_code::NOT_FOUND => Code::NotFound,
+            error_code::UNAUTHENTICATED => Code::Unauthenticated,
+            error_code::PERMISSION_DENIED | error_code::SOURCE_NOT_ALLOWED => {
+                Code::PermissionDenied
+            }
+            error_code::METHOD_NOT_ALLOWED => Code::Unimplemented,
+            error_code::MAINTENANCE
+            | error_code::AT_CAPACITY
+            | error_code::ARTIFACT_UNAVAILABLE
+            | error_code::NOT_READY => Code::Unavailable,
+            error_code::CPU_BUDGET_EXCEEDED => Code::DeadlineExceeded,
+            error_code::OUT_OF_MEMORY => Code::ResourceExhausted,
+            error_code::INTERNAL
+            | error_code::INVOCATION_FAILED
+            | error_code::STACK_OVERFLOW
+            | error_code::FUNCTION_PANICKED
+            | error_code::FUNCTION_TRAPPED => Code::Internal,
+            _ => return None,
+        })
+    }
+
+    /// gRPC's mapping of HTTP statuses without a more specific cause.
+    fn from_status(status: StatusCode) -> Self {
+        match status.as_u16() {
+            400 => Code::Internal,
+            401 => Code::Unauthenticated,
+            403 => Code::PermissionDenied,
+            404 => Code::Unimplemented,
+            429 | 502 | 503 | 504 => Code::Unavailable,
+            _ => Code::Unknown,
+        }
+    }
+}
+
+/// Status code and message for an unsuccessful response.
+fn describe(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> (Code, String) {
+    let error = serde_json::from_slice::<ErrorBody>(body).ok();
+    let code = error
+        .as_ref()
+        .and_then(|error| Code::from_error_code(&error.code))
+        .unwrap_or_else(|| Code::from_status(status));
+    let message = match (&error, headers.get(GRPC_MESSAGE)) {
+        (_, Some(message)) => message.to_str().unwrap_or_default().to_string(),
+        (Some(error), None) => error.message.clone(),
+        (None, None) => String::from_utf8_lossy(body).chars().take(256).collect(),
+    };
+    (code, message)
+}
+
+/// `grpc-status` set by the function, if it is a valid code.
+fn grpc_status_header(headers: &HeaderMap) -> Option<u32> {
+    headers
+        .get(GRPC_STATUS)?
+        .to_str()
+        .ok()?
+        .parse::<u32>()
+        .ok()
+        .filter(|code| *code <= 16)
+}
+
+fn message_text(headers: &HeaderMap, code: u32) -> String {
+    if code == Code::Ok as u32 {
+        return String::new();
+    }
+    headers
+        .get(GRPC_MESSAGE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The message of a body holding exactly one uncompressed data frame.
+fn read_frame(body: &[u8]) -> Result<Bytes, Code> {
+    if body.len() < FRAME_HEADER_LEN {
+        return Err(Code::InvalidArgument);
+    }
+    let flags = body[0];
+    if flags & COMPRESSED_FLAG != 0 {
+        return Err(Code::Unimplemented);
+    }
+    let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+    if flags & TRAILER_FLAG != 0 || body.len() != FRAME_HEADER_LEN + len {
+        return Err(Code::InvalidArgument);
+    }
+    Ok(Bytes::copy_from_slice(&body[FRAME_HEADER_LEN..]))
+}
+
+fn write_frame(buffer: &mut BytesMut, flags: u8, payload: &[u8]) {
+    buffer.put_u8(flags);
+    buffer.put_u32(payload.len() as u32);
+    buffer.put_slice(payload);
+}
+
+/// Percent-encode a `grpc-message` as the gRPC spec asks.
+fn percent_encode(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        if (0x20..=0x7e).contains(&byte) && byte != b'%' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grpc_web_headers(content_type: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers
+    }
+
+    #[test]
+    fn detects_grpc_web_and_connect() {
+        let call = Call::detect(
+            &Method::POST,
+            &grpc_web_headers("application/grpc-web+json"),
+        );
+        assert!(matches!(
+            call,
+            Some(Call::GrpcWeb {
+                text: false,
+                message_type: "application/json",
+                ..
+            })
+        ));
+        let call = Call::detect(
+            &Method::POST,
+            &grpc_web_headers("application/grpc-web-text"),
+        );
+        assert!(matches!(call, Some(Call::GrpcWeb { text: true, .. })));
+
+        let mut headers = grpc_web_headers("application/proto");
+        assert_eq!(Call::detect(&Method::POST, &headers), None);
+        headers.insert(CONNECT_PROTOCOL_VERSION, HeaderValue::from_static("1"));
+        assert_eq!(Call::detect(&Method::POST, &headers), Some(Call::Connect));
+        assert_eq!(Call::detect(&Method::GET, &headers), None);
+    }
+
+    #[test]
+    fn unwraps_a_single_frame() {
+        let call = Call::detect(&Method::POST, &grpc_web_headers("application/grpc-web")).unwrap();
+        let mut headers = grpc_web_headers("application/grpc-web");
+        let message = call
+            .unwrap_request(&mut headers, Bytes::from_static(b"\x00\x00\x00\x00\x02hi"))
+            .unwrap();
+        assert_eq!(message, Bytes::from_static(b"hi"));
+        assert_eq!(headers[header::CONTENT_TYPE], "application/proto");
+
+        assert!(read_frame(b"\x00\x00\x00\x00\x05hi").is_err());
+        assert_eq!(
+            read_frame(b"\x01\x00\x00\x00\x02hi"),
+            Err(Code::Unimplemented)
+        );
+    }
+
+    #[test]
+    fn frames_message_and_trailers() {
+        let call = Call::detect(&Method::POST, &grpc_web_headers("application/grpc-web")).unwrap();
+        let body = call.frame(Some(Bytes::from_static(b"ok")), 0, "");
+        assert_eq!(
+            body.as_ref(),
+            b"\x00\x00\x00\x00\x02ok\x80\x00\x00\x00\x0fgrpc-status:0\r\n"
+        );
+        assert_eq!(percent_encode("50% off\n"), "50%25 off%0A");
+    }
+}
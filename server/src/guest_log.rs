@@ -0,0 +1,144 @@
+//! Log lines written by guests through `faasta:log/logging`.
+//!
+//! Each line becomes a tracing event under the `guest` target carrying the function's
+//! name, so operators can filter a function's output like any other server log. Lines
+//! are truncated and each invocation gets a byte budget; once it is spent the rest of
+//! the invocation's lines are dropped with a single warning, so a chatty function
+//! cannot flood the server's log. Recorded lines also go to the function's own log,
+//! see `function_logs`.
+
+use anyhow::{Result, anyhow};
+use faasta_interface::LogStream;
+use tracing::{debug, error, info, warn};
+use wasmtime::component::{HasData, Linker};
+
+use crate::function_logs;
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "../faasta/wit",
+        world: "faasta:log/imports",
+    });
+}
+
+pub use bindings::faasta::log::logging::Level;
+
+/// Longest line recorded; the rest is cut off
+pub const MAX_LINE_BYTES: usize = 4 * 1024;
+/// Bytes one invocation may log
+pub const MAX_INVOCATION_BYTES: usize = 64 * 1024;
+
+/// Per-store logger for one invocation of `function`.
+pub struct GuestLog {
+    function: String,
+    remaining: usize,
+    dropped: bool,
+}
+
+impl GuestLog {
+    pub fn new(function: &str) -> Self {
+        Self {
+            function: function.to_string(),
+            remaining: MAX_INVOCATION_BYTES,
+            dropped: false,
+        }
+    }
+
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    /// The line as recorded, or `None` once the invocation's budget is spent.
+    fn admit<'a>(&mut self, message: &'a str) -> Option<&'a str> {
+        let line = truncate(message.trim_end(), MAX_LINE_BYTES);
+        if line.len() > self.remaining {
+            self.remaining = 0;
+            return None;
+        }
+        self.remaining -= line.len();
+        Some(line)
+    }
+}
+
+impl HasData for GuestLog {
+    type Data<'a> = &'a mut GuestLog;
+}
+
+impl bindings::faasta::log::logging::Host for GuestLog {
+    fn log(&mut self, level: Level, message: String) {
+        let Some(line) = self.admit(&message) else {
+            if !self.dropped {
+                self.dropped = true;
+                warn!(
+                    target: "guest",
+                    function = %self.function,
+                    "log limit of {} KiB per invocation reached; dropping further lines",
+                    MAX_INVOCATION_BYTES / 1024
+                );
+            }
+            return;
+        };
+        let function = &self.function;
+        function_logs::append(
+            function,
+            match level {
+                Level::Debug => LogStream::Debug,
+                Level::Info => LogStream::Info,
+                Level::Warn => LogStream::Warn,
+                Level::Error => LogStream::Error,
+            },
+            line,
+        );
+        match level {
+            Level::Debug => debug!(target: "guest", %function, "{line}"),
+            Level::Info => info!(target: "guest", %function, "{line}"),
+            Level::Warn => warn!(target: "guest", %function, "{line}"),
+            Level::Error => error!(target: "guest", %function, "{line}"),
+        }
+    }
+}
+
+pub fn add_to_linker<T: Send + 'static>(
+    linker: &mut Linker<T>,
+    get: fn(&mut T) -> &mut GuestLog,
+) -> Result<()> {
+    bindings::faasta::log::logging::add_to_linker::<T, GuestLog>(linker, get)
+        .map_err(|err| anyhow!("failed to add guest log imports to linker: {err}"))
+}
+
+/// The longest prefix of `line` within `max` bytes that ends on a char boundary.
+fn truncate(line: &str, max: usize) -> &str {
+    if line.len() <= max {
+        return line;
+    }
+    let mut end = max;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_on_char_boundaries() {
+        assert_eq!(truncate("héllo", 2), "h");
+        assert_eq!(truncate("héllo", 3), "hé");
+        assert_eq!(truncate("hi", 10), "hi");
+    }
+
+    #[test]
+    fn stops_at_the_invocation_budget() {
+        let mut log = GuestLog::new("fn");
+        let line = "x".repeat(MAX_LINE_BYTES * 2);
+        let mut admitted = 0;
+        while let Some(recorded) = log.admit(&line) {
+            assert_eq!(recorded.len(), MAX_LINE_BYTES);
+            admitted += recorded.len();
+        }
+        assert_eq!(admitted, MAX_INVOCATION_BYTES);
+        assert_eq!(log.admit("short"), None);
+    }
+}
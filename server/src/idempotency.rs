@@ -0,0 +1,348 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use bytes::Bytes;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use http::{HeaderMap, Method, Response, StatusCode};
+use tokio::time;
+use tracing::debug;
+
+use crate::fnv::fnv1a;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_KEY_LEN: usize = 255;
+/// Keys remembered at once, across all functions
+pub const MAX_ENTRIES: usize = 100_000;
+/// Largest response body kept for replay; larger responses are passed on uncached
+pub const MAX_REPLAY_BODY_BYTES: usize = 1024 * 1024;
+
+/// Caches function responses by `Idempotency-Key` so retried requests replay the
+/// original response instead of re-running side effects in the guest.
+pub struct IdempotencyCache {
+    window: Duration,
+    max_entries: usize,
+    entries: DashMap<(String, String), IdempotencyEntry>,
+}
+
+enum IdempotencyEntry {
+    InFlight {
+        fingerprint: String,
+        started: Instant,
+    },
+    Complete {
+        fingerprint: String,
+        stored: Instant,
+        response: CachedResponse,
+    },
+}
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Outcome of looking up an idempotency key before invoking a function.
+pub enum IdempotencyLookup<'a> {
+    /// No usable entry; the caller holds the key until it completes or drops the claim.
+    Proceed(IdempotencyClaim<'a>),
+    /// A completed response is cached for this key.
+    Replay(Response<Body>),
+    /// Another request with the same key is still being processed.
+    InProgress,
+    /// The key was already used for a different request.
+    Mismatch,
+    /// Too many keys are remembered to take another.
+    Full,
+}
+
+/// A key claimed by [`IdempotencyCache::begin`]. Dropping it without completing it
+/// releases the key, e.g. when the client disconnects mid-invocation, so a retry runs
+/// the request again.
+pub struct IdempotencyClaim<'a> {
+    cache: &'a IdempotencyCache,
+    entry: (String, String),
+    fingerprint: String,
+    started: Instant,
+}
+
+impl IdempotencyCache {
+    pub fn new(window: Duration) -> Self {
+        Self::with_max_entries(window, MAX_ENTRIES)
+    }
+
+    pub fn with_max_entries(window: Duration, max_entries: usize) -> Self {
+        Self {
+            window,
+            max_entries,
+            entries: DashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.window.is_zero()
+    }
+
+    /// Extract the idempotency key from a request if the method is not inherently idempotent.
+    pub fn request_key(&self, method: &Method, headers: &HeaderMap) -> Option<String> {
+        if !self.enabled() || method.is_safe() {
+            return None;
+        }
+        let key = headers.get(IDEMPOTENCY_KEY_HEADER)?.to_str().ok()?.trim();
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return None;
+        }
+        Some(key.to_string())
+    }
+
+    pub fn begin(
+        &self,
+        function_name: &str,
+        key: &str,
+        fingerprint: &str,
+    ) -> IdempotencyLookup<'_> {
+        let now = Instant::now();
+        let entry = (function_name.to_string(), key.to_string());
+        let claim = IdempotencyClaim {
+            cache: self,
+            entry: entry.clone(),
+            fingerprint: fingerprint.to_string(),
+            started: now,
+        };
+        if self.entries.len() >= self.max_entries && !self.entries.contains_key(&entry) {
+            self.sweep();
+            if self.entries.len() >= self.max_entries {
+                return IdempotencyLookup::Full;
+            }
+        }
+        match self.entries.entry(entry) {
+            Entry::Occupied(mut occupied) => {
+                let expired = match occupied.get() {
+                    IdempotencyEntry::InFlight { started, .. } => {
+                        now.duration_since(*started) > self.window
+                    }
+                    IdempotencyEntry::Complete { stored, .. } => {
+                        now.duration_since(*stored) > self.window
+                    }
+                };
+                if expired {
+                    occupied.insert(IdempotencyEntry::InFlight {
+                        fingerprint: fingerprint.to_string(),
+                        started: now,
+                    });
+                    return IdempotencyLookup::Proceed(claim);
+                }
+
+                match occupied.get() {
+                    IdempotencyEntry::InFlight {
+                        fingerprint: existing,
+                        ..
+                    } => {
+                        if existing == fingerprint {
+                            IdempotencyLookup::InProgress
+                        } else {
+                            IdempotencyLookup::Mismatch
+                        }
+                    }
+                    IdempotencyEntry::Complete {
+                        fingerprint: existing,
+                        response,
+                        ..
+                    } => {
+                        if existing == fingerprint {
+                            IdempotencyLookup::Replay(response.to_response())
+                        } else {
+                            IdempotencyLookup::Mismatch
+                        }
+                    }
+                }
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(IdempotencyEntry::InFlight {
+                    fingerprint: fingerprint.to_string(),
+                    started: now,
+                });
+                IdempotencyLookup::Proceed(claim)
+            }
+        }
+    }
+
+    fn sweep(&self) {
+        let now = Instant::now();
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| {
+            let since = match entry {
+                IdempotencyEntry::InFlight { started, .. } => *started,
+                IdempotencyEntry::Complete { stored, .. } => *stored,
+            };
+            now.duration_since(since) <= self.window
+        });
+        let removed = before.saturating_sub(self.entries.len());
+        if removed > 0 {
+            debug!("evicted {removed} expired idempotency entries");
+        }
+    }
+
+    /// Spawn a background task that evicts expired entries once per minute.
+    pub fn spawn_periodic_sweep(self: Arc<Self>) {
+        if !self.enabled() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut ticker = time::interval(Duration::from_secs(60));
+            loop {
+                ticker.tick().await;
+                self.sweep();
+            }
+        });
+    }
+}
+
+impl IdempotencyClaim<'_> {
+    /// Store the final response for the key, to replay to retries.
+    pub fn complete(self, response: CachedResponse) {
+        self.cache.entries.insert(
+            self.entry.clone(),
+            IdempotencyEntry::Complete {
+                fingerprint: self.fingerprint.clone(),
+                stored: Instant::now(),
+                response,
+            },
+        );
+    }
+}
+
+impl Drop for IdempotencyClaim<'_> {
+    fn drop(&mut self) {
+        // Only while still in flight under this claim: once completed the entry stays,
+        // and once it expired another request may hold the key
+        self.cache.entries.remove_if(&self.entry, |_, entry| {
+            matches!(entry, IdempotencyEntry::InFlight { started, .. } if *started == self.started)
+        });
+    }
+}
+
+impl CachedResponse {
+    pub fn new(status: StatusCode, headers: HeaderMap, body: Bytes) -> Self {
+        Self {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    fn to_response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response.headers_mut().insert(
+            IDEMPOTENT_REPLAYED_HEADER,
+            http::HeaderValue::from_static("true"),
+        );
+        response
+    }
+}
+
+/// Fingerprint of the parts of a request that must match for a replay to be valid.
+pub fn request_fingerprint(method: &Method, path_and_query: &str, body: &[u8]) -> String {
+    let hash = fnv1a(
+        method
+            .as_str()
+            .as_bytes()
+            .iter()
+            .chain(b" ")
+            .chain(path_and_query.as_bytes())
+            .chain(b"\n")
+            .chain(body),
+    );
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn cached(body: &'static str) -> CachedResponse {
+        CachedResponse::new(StatusCode::CREATED, HeaderMap::new(), Bytes::from(body))
+    }
+
+    #[test]
+    fn replays_completed_responses() {
+        let cache = IdempotencyCache::new(WINDOW);
+        let IdempotencyLookup::Proceed(claim) = cache.begin("fn", "key", "a") else {
+            panic!("a new key should proceed");
+        };
+        claim.complete(cached("done"));
+
+        let IdempotencyLookup::Replay(response) = cache.begin("fn", "key", "a") else {
+            panic!("a completed key should replay");
+        };
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[IDEMPOTENT_REPLAYED_HEADER], "true");
+        // Keys are per function
+        assert!(matches!(
+            cache.begin("other", "key", "a"),
+            IdempotencyLookup::Proceed(_)
+        ));
+    }
+
+    #[test]
+    fn refuses_a_key_reused_for_another_request() {
+        let cache = IdempotencyCache::new(WINDOW);
+        let IdempotencyLookup::Proceed(claim) = cache.begin("fn", "key", "a") else {
+            panic!("a new key should proceed");
+        };
+        assert!(matches!(
+            cache.begin("fn", "key", "b"),
+            IdempotencyLookup::Mismatch
+        ));
+        claim.complete(cached("done"));
+        assert!(matches!(
+            cache.begin("fn", "key", "b"),
+            IdempotencyLookup::Mismatch
+        ));
+    }
+
+    #[test]
+    fn dropped_claims_release_their_key() {
+        let cache = IdempotencyCache::new(WINDOW);
+        let IdempotencyLookup::Proceed(claim) = cache.begin("fn", "key", "a") else {
+            panic!("a new key should proceed");
+        };
+        assert!(matches!(
+            cache.begin("fn", "key", "a"),
+            IdempotencyLookup::InProgress
+        ));
+        // As when the client disconnects mid-invocation
+        drop(claim);
+        assert!(matches!(
+            cache.begin("fn", "key", "a"),
+            IdempotencyLookup::Proceed(_)
+        ));
+    }
+
+    #[test]
+    fn caps_the_keys_remembered() {
+        let cache = IdempotencyCache::with_max_entries(WINDOW, 2);
+        for key in ["a", "b"] {
+            let IdempotencyLookup::Proceed(claim) = cache.begin("fn", key, key) else {
+                panic!("{key} should proceed");
+            };
+            claim.complete(cached("done"));
+        }
+        assert!(matches!(
+            cache.begin("fn", "c", "c"),
+            IdempotencyLookup::Full
+        ));
+        assert!(matches!(
+            cache.begin("fn", "a", "a"),
+            IdempotencyLookup::Replay(_)
+        ));
+    }
+}
@@ -0,0 +1,335 @@
+//! Image transforms performed by the host on behalf of guests.
+//!
+//! Guests import `faasta:image/transform` (wrapped by `faasta::image` in the SDK) to
+//! resize, crop, rotate and re-encode images. Decoding and encoding run natively on the
+//! blocking thread pool, so image functions don't need to compile codecs into their
+//! component, which would quickly run into the artifact size limit and run far slower
+//! in Wasm. Inputs are capped in bytes and pixels, and the number of transforms running
+//! at once is bounded so one busy function cannot take over the blocking pool.
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader, Limits};
+use tokio::sync::Semaphore;
+use wasmtime::component::{HasData, Linker};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "../faasta/wit",
+        world: "faasta:image/imports",
+        imports: { default: async },
+    });
+}
+
+pub use bindings::faasta::image::transform::{
+    Crop, Error, Fit, Format, ImageInfo, Operation, Options, Resize, Rotation,
+};
+
+/// Largest encoded image accepted
+pub const MAX_INPUT_BYTES: usize = 20 * 1024 * 1024;
+/// Largest width or height decoded or produced
+const MAX_DIMENSION: u32 = 16_384;
+/// Largest image decoded or produced, in pixels
+const MAX_PIXELS: u64 = 40_000_000;
+const DEFAULT_QUALITY: u8 = 80;
+
+/// Per-store handle to the host's image transforms.
+#[derive(Clone)]
+pub struct ImageHost {
+    permits: Arc<Semaphore>,
+}
+
+impl ImageHost {
+    /// Allow `concurrency` transforms to run at once across all guests.
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+        }
+    }
+
+    async fn run<T, F>(&self, image: Vec<u8>, work: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&[u8]) -> Result<T, Error> + Send + 'static,
+    {
+        if image.len() > MAX_INPUT_BYTES {
+            return Err(Error::TooLarge(format!(
+                "images are limited to {} MiB",
+                MAX_INPUT_BYTES / (1024 * 1024)
+            )));
+        }
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+        tokio::task::spawn_blocking(move || work(&image))
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+    }
+}
+
+impl HasData for ImageHost {
+    type Data<'a> = &'a mut ImageHost;
+}
+
+impl bindings::faasta::image::transform::Host for ImageHost {
+    async fn transform(&mut self, image: Vec<u8>, options: Options) -> Result<Vec<u8>, Error> {
+        self.run(image, move |image| apply(image, &options)).await
+    }
+
+    async fn info(&mut self, image: Vec<u8>) -> Result<ImageInfo, Error> {
+        self.run(image, info).await
+    }
+}
+
+pub fn add_to_linker<T: Send + 'static>(
+    linker: &mut Linker<T>,
+    get: fn(&mut T) -> &mut ImageHost,
+) -> Result<()> {
+    bindings::faasta::image::transform::add_to_linker::<T, ImageHost>(linker, get)
+        .map_err(|err| anyhow!("failed to add image transform imports to linker: {err}"))
+}
+
+/// Decode `image`, run the operations in order and encode the result.
+pub fn apply(image: &[u8], options: &Options) -> Result<Vec<u8>, Error> {
+    let (mut decoded, format) = decode(image)?;
+    for operation in &options.operations {
+        decoded = match operation {
+            Operation::Resize(resize) => resize_image(&decoded, resize)?,
+            Operation::Crop(crop) => crop_image(&decoded, crop)?,
+            Operation::Rotate(Rotation::Quarter) => decoded.rotate90(),
+            Operation::Rotate(Rotation::Half) => decoded.rotate180(),
+            Operation::Rotate(Rotation::ThreeQuarters) => decoded.rotate270(),
+            Operation::FlipHorizontal => decoded.fliph(),
+            Operation::FlipVertical => decoded.flipv(),
+            Operation::Grayscale => decoded.grayscale(),
+        };
+    }
+    encode(&decoded, options.format.unwrap_or(format), options.quality)
+}
+
+pub fn info(image: &[u8]) -> Result<ImageInfo, Error> {
+    let reader = reader(image)?;
+    let format = reader
+        .format()
+        .and_then(to_format)
+        .ok_or(Error::UnsupportedFormat)?;
+    let (width, height) = reader
+        .into_dimensions()
+        .map_err(|err| Error::InvalidImage(err.to_string()))?;
+    Ok(ImageInfo {
+        width,
+        height,
+        format,
+    })
+}
+
+fn reader(image: &[u8]) -> Result<ImageReader<Cursor<&[u8]>>, Error> {
+    let mut reader = ImageReader::new(Cursor::new(image))
+        .with_guessed_format()
+        .map_err(|err| Error::InvalidImage(err.to_string()))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+    Ok(reader)
+}
+
+fn decode(image: &[u8]) -> Result<(DynamicImage, Format), Error> {
+    let reader = reader(image)?;
+    let format = reader
+        .format()
+        .and_then(to_format)
+        .ok_or(Error::UnsupportedFormat)?;
+    let decoded = reader.decode().map_err(|err| match err {
+        image::ImageError::Limits(err) => Error::TooLarge(err.to_string()),
+        err => Error::InvalidImage(err.to_string()),
+    })?;
+    check_size(decoded.width(), decoded.height())?;
+    Ok((decoded, format))
+}
+
+fn resize_image(image: &DynamicImage, resize: &Resize) -> Result<DynamicImage, Error> {
+    let (width, height) = image.dimensions();
+    let scaled = |length: u32, to: u32, from: u32| {
+        let length = (u64::from(length) * u64::from(to) + u64::from(from) / 2) / u64::from(from);
+        u32::try_from(length.max(1)).unwrap_or(u32::MAX)
+    };
+    let (target_width, target_height) = match (resize.width, resize.height) {
+        (Some(target_width), Some(target_height)) => (target_width, target_height),
+        (Some(target_width), None) => (target_width, scaled(height, target_width, width)),
+        (None, Some(target_height)) => (scaled(width, target_height, height), target_height),
+        (None, None) => {
+            return Err(Error::InvalidOperation(
+                "resize needs a width or a height".to_string(),
+            ));
+        }
+    };
+    check_size(target_width, target_height)?;
+    Ok(match resize.fit {
+        Fit::Contain => image.resize(target_width, target_height, FilterType::Lanczos3),
+        Fit::Cover => image.resize_to_fill(target_width, target_height, FilterType::Lanczos3),
+        Fit::Fill => image.resize_exact(target_width, target_height, FilterType::Lanczos3),
+    })
+}
+
+fn crop_image(image: &DynamicImage, crop: &Crop) -> Result<DynamicImage, Error> {
+    let (width, height) = image.dimensions();
+    let fits = |offset: u32, length: u32, limit: u32| {
+        length > 0 && offset.checked_add(length).is_some_and(|end| end <= limit)
+    };
+    if !fits(crop.x, crop.width, width) || !fits(crop.y, crop.height, height) {
+        return Err(Error::InvalidOperation(format!(
+            "crop {}x{}+{}+{} is outside the {width}x{height} image",
+            crop.width, crop.height, crop.x, crop.y
+        )));
+    }
+    Ok(image.crop_imm(crop.x, crop.y, crop.width, crop.height))
+}
+
+fn encode(image: &DynamicImage, format: Format, quality: Option<u8>) -> Result<Vec<u8>, Error> {
+    let mut encoded = Cursor::new(Vec::new());
+    let result = match format {
+        Format::Jpeg => {
+            let quality = quality.unwrap_or(DEFAULT_QUALITY).clamp(1, 100);
+            // JPEG has no alpha channel
+            image
+                .to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, quality))
+        }
+        Format::Png => image.write_to(&mut encoded, ImageFormat::Png),
+        Format::Webp => image.write_to(&mut encoded, ImageFormat::WebP),
+        Format::Gif => image.write_to(&mut encoded, ImageFormat::Gif),
+    };
+    result.map_err(|err| Error::Internal(format!("encoding failed: {err}")))?;
+    Ok(encoded.into_inner())
+}
+
+fn check_size(width: u32, height: u32) -> Result<(), Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidOperation(
+            "width and height must be at least 1".to_string(),
+        ));
+    }
+    if width > MAX_DIMENSION
+        || height > MAX_DIMENSION
+        || u64::from(width) * u64::from(height) > MAX_PIXELS
+    {
+        return Err(Error::TooLarge(format!(
+            "{width}x{height} exceeds the limit of {MAX_DIMENSION} pixels per side and {MAX_PIXELS} pixels in total"
+        )));
+    }
+    Ok(())
+}
+
+fn to_format(format: ImageFormat) -> Option<Format> {
+    match format {
+        ImageFormat::Jpeg => Some(Format::Jpeg),
+        ImageFormat::Png => Some(Format::Png),
+        ImageFormat::WebP => Some(Format::Webp),
+        ImageFormat::Gif => Some(Format::Gif),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            width,
+            height,
+            Rgba([200, 10, 10, 255]),
+        ));
+        encode(&image, Format::Png, None).unwrap()
+    }
+
+    fn options(operations: Vec<Operation>, format: Option<Format>) -> Options {
+        Options {
+            operations,
+            format,
+            quality: None,
+        }
+    }
+
+    #[test]
+    fn resizes_keeping_aspect_ratio_and_converts() {
+        let resized = apply(
+            &png(400, 200),
+            &options(
+                vec![Operation::Resize(Resize {
+                    width: Some(100),
+                    height: None,
+                    fit: Fit::Contain,
+                })],
+                Some(Format::Jpeg),
+            ),
+        )
+        .unwrap();
+        let info = info(&resized).unwrap();
+        assert_eq!((info.width, info.height), (100, 50));
+        assert!(matches!(info.format, Format::Jpeg));
+    }
+
+    #[test]
+    fn covers_crops_and_rotates() {
+        let result = apply(
+            &png(400, 200),
+            &options(
+                vec![
+                    Operation::Resize(Resize {
+                        width: Some(100),
+                        height: Some(100),
+                        fit: Fit::Cover,
+                    }),
+                    Operation::Crop(Crop {
+                        x: 0,
+                        y: 0,
+                        width: 100,
+                        height: 40,
+                    }),
+                    Operation::Rotate(Rotation::Quarter),
+                ],
+                None,
+            ),
+        )
+        .unwrap();
+        let info = info(&result).unwrap();
+        assert_eq!((info.width, info.height), (40, 100));
+        assert!(matches!(info.format, Format::Png));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(matches!(
+            apply(b"not an image", &options(Vec::new(), None)),
+            Err(Error::UnsupportedFormat)
+        ));
+        let crop = Operation::Crop(Crop {
+            x: 50,
+            y: 0,
+            width: 60,
+            height: 10,
+        });
+        assert!(matches!(
+            apply(&png(100, 100), &options(vec![crop], None)),
+            Err(Error::InvalidOperation(_))
+        ));
+        let resize = Operation::Resize(Resize {
+            width: Some(MAX_DIMENSION + 1),
+            height: None,
+            fit: Fit::Fill,
+        });
+        assert!(matches!(
+            apply(&png(10, 10), &options(vec![resize], None)),
+            Err(Error::TooLarge(_))
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+//! Owner-only request inspector. A request to `/_faasta/inspect/<path>` on a function
+//! goes through the same routing as a request to `/<path>`, but instead of invoking the
+//! function the host answers with the request the function would have received.
+
+use axum::body::Body;
+use faasta_interface::ResponseHeader;
+use http::{HeaderMap, Method, Response, StatusCode, Uri, header};
+use serde_json::{Map, Value, json};
+
+use crate::routes;
+
+/// Path, relative to the function, under which requests are inspected.
+pub const PREFIX: &str = "/_faasta/inspect";
+
+/// The request being inspected, if `uri` addresses the inspector of the function
+/// mounted at `mount` (`""` on its own subdomain).
+pub fn probe(uri: &Uri, mount: &str) -> Option<Uri> {
+    let rest = routes::path_below_mount(uri.path(), mount).strip_prefix(PREFIX)?;
+    let rest = match rest {
+        "" => "/",
+        rest if rest.starts_with('/') => rest,
+        _ => return None,
+    };
+    let query = uri
+        .query()
+        .map(|query| format!("?{query}"))
+        .unwrap_or_default();
+    format!("{mount}{rest}{query}").parse().ok()
+}
+
+/// What the platform would deliver for an inspected request.
+pub struct Delivery<'a> {
+    pub function: &'a str,
+    /// Experiment variant the request was assigned to
+    pub variant: Option<&'a str>,
+    pub static_site: bool,
+    /// Whether a route rule rewrote the path
+    pub rewritten: bool,
+    pub method: &'a Method,
+    pub uri: &'a Uri,
+    pub headers: &'a HeaderMap,
+    pub body_len: usize,
+    /// Headers the host adds to the function's response
+    pub response_headers: &'a [ResponseHeader],
+}
+
+pub fn report(delivery: &Delivery<'_>) -> Response<Body> {
+    json_report(json!({
+        "outcome": if delivery.static_site { "static_site" } else { "invoke" },
+        "function": delivery.function,
+        "variant": delivery.variant,
+        "rewritten": delivery.rewritten,
+        "request": {
+            "method": delivery.method.as_str(),
+            "uri": delivery.uri.to_string(),
+            "headers": headers_json(delivery.headers),
+            "body_bytes": delivery.body_len,
+        },
+        "response_headers": delivery
+            .response_headers
+            .iter()
+            .map(|rule| json!({
+                "name": rule.name,
+                "value": rule.value,
+                "replace": rule.replace,
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// Report a request that a route rule answers with a redirect.
+pub fn report_redirect(function: &str, redirect: &Response<Body>) -> Response<Body> {
+    json_report(json!({
+        "outcome": "redirect",
+        "function": function,
+        "status": redirect.status().as_u16(),
+        "location": redirect
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok()),
+    }))
+}
+
+/// Header values as strings; `Authorization` carried the owner's token for the
+/// inspector itself, so it is redacted.
+fn headers_json(headers: &HeaderMap) -> Value {
+    let mut map = Map::new();
+    for name in headers.keys() {
+        let values: Vec<Value> = if name == header::AUTHORIZATION {
+            vec![Value::from("[redacted]")]
+        } else {
+            headers
+                .get_all(name)
+                .iter()
+                .map(|value| Value::from(String::from_utf8_lossy(value.as_bytes()).into_owned()))
+                .collect()
+        };
+        map.insert(name.to_string(), Value::from(values));
+    }
+    Value::Object(map)
+}
+
+fn json_report(body: Value) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
@@ -0,0 +1,732 @@
+//! Asynchronous invocations.
+//!
+//! `POST /v1/invoke-async/NAME/path` is answered at once with a `202` and a job id.
+//! Jobs are kept in the metadata store, so queued and retrying jobs survive a restart.
+//! A job waits for a free slot instead of being shed, and each attempt may wait and run
+//! for up to the job timeout. Failed attempts are retried with exponential backoff under
+//! the function's `RetryPolicy`. The result is posted to the callback URL, if the
+//! request named one, and kept for `RETENTION` so it can be fetched with `job_status`.
+//! Callbacks only go to public addresses outside the server's base domain, and
+//! redirects are not followed, so a job can't be used to reach the server's network.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use axum::body::to_bytes;
+use bincode::{Decode, Encode};
+use bytes::Bytes;
+use dashmap::DashMap;
+use faasta_interface::{ErrorBody, JobState, JobStatus, JobSummary, Priority, error_code};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Uri, header};
+use reqwest::Url;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect;
+use tokio::task::AbortHandle;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::db::{MetadataStore, Tree};
+use crate::error_body;
+use crate::quarantine::Quarantined;
+use crate::traps::Fault;
+use crate::wasi_server::FaastaServer;
+
+/// Request header naming the URL the result is posted to
+pub const CALLBACK_HEADER: &str = "faasta-callback-url";
+/// Sent with the `202` and with the callback
+pub const JOB_ID_HEADER: &str = "faasta-job-id";
+/// `succeeded` or `failed`, sent with the callback
+pub const JOB_STATE_HEADER: &str = "faasta-job-state";
+/// Status of the function's response, sent with the callback when there is one
+pub const RESULT_STATUS_HEADER: &str = "faasta-result-status";
+/// Largest request body accepted for a job; it is stored with the job
+pub const MAX_REQUEST_BYTES: usize = 256 * 1024;
+
+/// How long finished jobs are kept for `job_status`
+const RETENTION: Duration = Duration::from_secs(60 * 60);
+/// Jobs kept at once, finished or not
+const MAX_JOBS: usize = 10_000;
+/// Jobs of one function kept at once, so one function can't take every slot
+const MAX_JOBS_PER_FUNCTION: usize = 1_000;
+/// Largest response body kept as a job's result. Request and result share one metadata
+/// record, which is capped at 1 MiB.
+const MAX_RESULT_BYTES: usize = 512 * 1024;
+const CALLBACK_ATTEMPTS: u32 = 3;
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returned when the server holds `MAX_JOBS` jobs, or the function
+/// `MAX_JOBS_PER_FUNCTION`.
+#[derive(Debug)]
+pub enum TooManyJobs {
+    Server,
+    Function,
+}
+
+impl fmt::Display for TooManyJobs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Server => write!(f, "the server holds {MAX_JOBS} jobs"),
+            Self::Function => write!(f, "the function has {MAX_JOBS_PER_FUNCTION} jobs"),
+        }
+    }
+}
+
+impl std::error::Error for TooManyJobs {}
+
+pub struct Jobs {
+    timeout: Duration,
+    db: Arc<dyn MetadataStore>,
+    /// Every stored job without its request and result. Stored records are written while
+    /// holding the job's entry, so a cancel can't be overwritten by a running attempt.
+    index: DashMap<String, JobSummary>,
+    /// Tasks of unfinished jobs, aborted on cancel
+    tasks: DashMap<String, AbortHandle>,
+    client: reqwest::Client,
+}
+
+/// An invocation to run in the background.
+pub struct JobRequest {
+    pub function_name: String,
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub callback: Option<Url>,
+}
+
+#[derive(Encode, Decode)]
+struct StoredJob {
+    status: JobStatus,
+    request: StoredRequest,
+    /// Unix time in milliseconds before which the next attempt doesn't start
+    not_before_ms: u64,
+}
+
+#[derive(Encode, Decode)]
+struct StoredRequest {
+    method: String,
+    uri: String,
+    headers: Vec<(String, Vec<u8>)>,
+    body: Vec<u8>,
+    callback: Option<String>,
+}
+
+/// The function's response to one attempt.
+struct Outcome {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Jobs {
+    /// Load the jobs kept in `db`. Unfinished ones resume with [`Jobs::resume`].
+    pub fn new(timeout: Duration, db: Arc<dyn MetadataStore>) -> Result<Self> {
+        let index = DashMap::new();
+        for (job_id, encoded) in db.scan(Tree::Jobs).context("failed to load jobs")? {
+            match decode(&encoded) {
+                Ok(job) => {
+                    index.insert(job_id, summary(&job.status));
+                }
+                Err(err) => {
+                    warn!("dropping unreadable job {job_id}: {err:#}");
+                    db.delete(Tree::Jobs, &job_id)?;
+                }
+            }
+        }
+        Ok(Self {
+            timeout,
+            db,
+            index,
+            tasks: DashMap::new(),
+            client: reqwest::Client::builder()
+                .timeout(CALLBACK_TIMEOUT)
+                .redirect(redirect::Policy::none())
+                .dns_resolver(Arc::new(PublicResolver))
+                .build()
+                .context("failed to build the callback client")?,
+        })
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Restart the jobs that were queued, waiting for a retry or running when the server
+    /// stopped. An interrupted attempt is started again and counts as a further attempt.
+    pub fn resume(self: &Arc<Self>, server: Arc<FaastaServer>) {
+        let pending: Vec<String> = self
+            .index
+            .iter()
+            .filter(|entry| !entry.state.is_finished())
+            .map(|entry| entry.key().clone())
+            .collect();
+        if !pending.is_empty() {
+            info!("resuming {} asynchronous job(s)", pending.len());
+        }
+        for job_id in pending {
+            self.spawn(server.clone(), job_id);
+        }
+    }
+
+    /// Store the invocation and return its job id. Fails with [`TooManyJobs`] when the
+    /// server holds `MAX_JOBS` jobs or the function `MAX_JOBS_PER_FUNCTION`.
+    pub fn submit(
+        self: &Arc<Self>,
+        server: Arc<FaastaServer>,
+        request: JobRequest,
+    ) -> Result<String> {
+        if let Err(full) = self.check_room(&request.function_name) {
+            self.sweep();
+            if self.check_room(&request.function_name).is_err() {
+                return Err(full.into());
+            }
+        }
+        let job_id = hex::encode(rand::random::<[u8; 16]>());
+        let job = StoredJob {
+            status: JobStatus {
+                job_id: job_id.clone(),
+                function_name: request.function_name,
+                state: JobState::Queued,
+                attempts: 0,
+                submitted_at: chrono::Utc::now().to_rfc3339(),
+                finished_at: None,
+                status: None,
+                headers: Vec::new(),
+                body: Vec::new(),
+                error: None,
+                callback_delivered: None,
+            },
+            request: StoredRequest {
+                method: request.method.to_string(),
+                uri: request.uri.to_string(),
+                headers: request
+                    .headers
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+                    .collect(),
+                body: request.body.to_vec(),
+                callback: request.callback.map(String::from),
+            },
+            not_before_ms: 0,
+        };
+        self.index.insert(job_id.clone(), summary(&job.status));
+        if let Err(err) = self.save(&job) {
+            self.index.remove(&job_id);
+            return Err(err);
+        }
+        self.spawn(server, job_id.clone());
+        Ok(job_id)
+    }
+
+    fn check_room(&self, function_name: &str) -> Result<(), TooManyJobs> {
+        if self.index.len() >= MAX_JOBS {
+            return Err(TooManyJobs::Server);
+        }
+        let held = self
+            .index
+            .iter()
+            .filter(|entry| entry.function_name == function_name)
+            .count();
+        if held >= MAX_JOBS_PER_FUNCTION {
+            return Err(TooManyJobs::Function);
+        }
+        Ok(())
+    }
+
+    pub fn status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        if !self.index.contains_key(job_id) {
+            return Ok(None);
+        }
+        Ok(self.load(job_id)?.map(|job| job.status))
+    }
+
+    pub fn summary(&self, job_id: &str) -> Option<JobSummary> {
+        self.index.get(job_id).map(|entry| entry.clone())
+    }
+
+    /// Jobs of `function_name`, newest first.
+    pub fn list(&self, function_name: &str) -> Vec<JobSummary> {
+        let mut jobs: Vec<JobSummary> = self
+            .index
+            .iter()
+            .filter(|entry| entry.function_name == function_name)
+            .map(|entry| entry.clone())
+            .collect();
+        jobs.sort_by(|a, b| b.submitted_at.cmp(&a.submitted_at));
+        jobs
+    }
+
+    /// Cancel an unfinished job. Returns `false` if it had already finished.
+    pub fn cancel(&self, job_id: &str) -> Result<bool> {
+        let Some(mut entry) = self.index.get_mut(job_id) else {
+            return Ok(false);
+        };
+        if entry.state.is_finished() {
+            return Ok(false);
+        }
+        if let Some((_, task)) = self.tasks.remove(job_id) {
+            task.abort();
+        }
+        let mut job = self
+            .load(job_id)?
+            .with_context(|| format!("job {job_id} is missing from the store"))?;
+        job.status.state = JobState::Cancelled;
+        job.status.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        self.db.put(Tree::Jobs, job_id, &encode(&job)?)?;
+        *entry = summary(&job.status);
+        info!("job {job_id} of '{}' cancelled", job.status.function_name);
+        Ok(true)
+    }
+
+    pub fn spawn_periodic_sweep(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = time::interval(Duration::from_secs(60));
+            loop {
+                ticker.tick().await;
+                self.sweep();
+            }
+        });
+    }
+
+    /// Drop jobs that finished more than `RETENTION` ago.
+    fn sweep(&self) {
+        let cutoff = chrono::Utc::now() - RETENTION;
+        let expired: Vec<String> = self
+            .index
+            .iter()
+            .filter(|entry| {
+                entry
+                    .finished_at
+                    .as_deref()
+                    .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+                    .is_some_and(|at| at < cutoff)
+            })
+            .map(|entry| entry.key().clone())
+            .collect();
+        for job_id in expired {
+            if let Err(err) = self.db.delete(Tree::Jobs, &job_id) {
+                warn!("failed to drop job {job_id}: {err:#}");
+                continue;
+            }
+            self.index.remove(&job_id);
+            self.tasks.remove(&job_id);
+        }
+    }
+
+    fn spawn(self: &Arc<Self>, server: Arc<FaastaServer>, job_id: String) {
+        let jobs = self.clone();
+        let task = tokio::spawn({
+            let job_id = job_id.clone();
+            async move {
+                if let Err(err) = jobs.run(&server, &job_id).await {
+                    error!("job {job_id} stopped: {err:#}");
+                }
+                jobs.tasks.remove(&job_id);
+            }
+        });
+        self.tasks.insert(job_id, task.abort_handle());
+    }
+
+    fn load(&self, job_id: &str) -> Result<Option<StoredJob>> {
+        self.db
+            .get(Tree::Jobs, job_id)?
+            .map(|encoded| decode(&encoded))
+            .transpose()
+    }
+
+    /// Write the job back, unless it was cancelled or dropped in the meantime.
+    fn save(&self, job: &StoredJob) -> Result<()> {
+        let Some(mut entry) = self.index.get_mut(&job.status.job_id) else {
+            return Ok(());
+        };
+        if entry.state == JobState::Cancelled {
+            return Ok(());
+        }
+        self.db.put(Tree::Jobs, &job.status.job_id, &encode(job)?)?;
+        *entry = summary(&job.status);
+        Ok(())
+    }
+
+    async fn run(&self, server: &FaastaServer, job_id: &str) -> Result<()> {
+        let Some(mut job) = self.load(job_id)? else {
+            return Ok(());
+        };
+        let function_name = job.status.function_name.clone();
+        let policy = server
+            .function_configs
+            .get(&function_name)
+            .job_retry
+            .clone();
+        let started = Instant::now();
+
+        let result = loop {
+            let wait = job.not_before_ms.saturating_sub(unix_millis());
+            if wait > 0 {
+                time::sleep(Duration::from_millis(wait)).await;
+            }
+            job.status.attempts += 1;
+            let result = self.attempt(server, &mut job).await;
+            let retry = match &result {
+                Ok(outcome) => outcome.status >= 500,
+                Err(error) => error.code != error_code::RESULT_TOO_LARGE,
+            };
+            if !retry || job.status.attempts >= policy.max_attempts {
+                break result;
+            }
+            let backoff = policy.backoff(job.status.attempts);
+            warn!(
+                "attempt {} of job {job_id} ('{function_name}') failed, retrying in {}ms",
+                job.status.attempts,
+                backoff.as_millis()
+            );
+            job.status.state = JobState::Queued;
+            job.status.error = result.err();
+            job.not_before_ms = unix_millis() + backoff.as_millis() as u64;
+            self.save(&job)?;
+        };
+
+        job.status.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        match result {
+            Ok(outcome) => {
+                job.status.state = if outcome.status < 500 {
+                    JobState::Succeeded
+                } else {
+                    JobState::Failed
+                };
+                job.status.status = Some(outcome.status);
+                job.status.headers = outcome.headers;
+                job.status.body = outcome.body;
+                job.status.error = None;
+            }
+            Err(error) => {
+                job.status.state = JobState::Failed;
+                job.status.error = Some(error);
+            }
+        }
+        self.save(&job)?;
+        info!(
+            "job {job_id} of '{function_name}' {:?} after {} attempt(s) in {}ms",
+            job.status.state,
+            job.status.attempts,
+            started.elapsed().as_millis()
+        );
+
+        if let Some(callback) = &job.request.callback {
+            let delivered = match parse_callback(callback, &server.base_domain) {
+                Ok(callback) => self.deliver(&callback, &job.status).await,
+                Err(_) => false,
+            };
+            job.status.callback_delivered = Some(delivered);
+            self.save(&job)?;
+        }
+        Ok(())
+    }
+
+    /// Run one attempt: wait for a slot, then invoke the function, all within the job
+    /// timeout.
+    async fn attempt(
+        &self,
+        server: &FaastaServer,
+        job: &mut StoredJob,
+    ) -> Result<Outcome, ErrorBody> {
+        let started = Instant::now();
+        let function_name = job.status.function_name.clone();
+        let (method, uri, headers) = job.request.parts().map_err(|err| {
+            error_body::body(
+                error_code::BAD_REQUEST,
+                format!("Stored request is invalid: {err:#}"),
+            )
+        })?;
+        let admission = server.admission_for(&function_name);
+        let Some(_permit) = admission.admit_within(Priority::Batch, self.timeout).await else {
+            return Err(error_body::body(
+                error_code::AT_CAPACITY,
+                "No slot became free before the job timed out",
+            ));
+        };
+        job.status.state = JobState::Running;
+        self.save(job).map_err(|err| {
+            error!("failed to store job {}: {err:#}", job.status.job_id);
+            error_body::body(error_code::INTERNAL, "Failed to store the job")
+        })?;
+
+        let remaining = self.timeout.saturating_sub(started.elapsed());
+        let invocation = server.invoke(
+            &function_name,
+            method,
+            uri,
+            headers,
+            Bytes::from(job.request.body.clone()),
+        );
+        let response = match time::timeout(remaining, invocation).await {
+            Err(_) => {
+                return Err(error_body::body(
+                    error_code::JOB_TIMED_OUT,
+                    format!("Function did not finish within {}s", self.timeout.as_secs()),
+                ));
+            }
+            Ok(Err(err)) => return Err(describe_failure(&function_name, &err)),
+            Ok(Ok(response)) => response,
+        };
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, MAX_RESULT_BYTES).await.map_err(|_| {
+            error_body::body(
+                error_code::RESULT_TOO_LARGE,
+                format!(
+                    "Function response is larger than {} KiB",
+                    MAX_RESULT_BYTES / 1024
+                ),
+            )
+        })?;
+        Ok(Outcome {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: body.to_vec(),
+        })
+    }
+
+    /// Post the job's result to its callback URL: the function's response as is when
+    /// there is one, the error body otherwise.
+    async fn deliver(&self, callback: &Url, status: &JobStatus) -> bool {
+        let state = match status.state {
+            JobState::Succeeded => "succeeded",
+            _ => "failed",
+        };
+        for attempt in 0..CALLBACK_ATTEMPTS {
+            if attempt > 0 {
+                time::sleep(Duration::from_secs(1 << attempt)).await;
+            }
+            let request = self
+                .client
+                .post(callback.clone())
+                .header(JOB_ID_HEADER, &status.job_id)
+                .header(JOB_STATE_HEADER, state);
+            let request = match (&status.error, status.status) {
+                (Some(error), _) => request.json(error),
+                (None, code) => {
+                    let mut request =
+                        request.header(RESULT_STATUS_HEADER, code.unwrap_or_default());
+                    for (name, value) in &status.headers {
+                        if !is_framing_header(name) {
+                            request = request.header(name, value);
+                        }
+                    }
+                    request.body(status.body.clone())
+                }
+            };
+            match request.send().await {
+                Ok(response) if response.status().is_success() => return true,
+                Ok(response) => warn!(
+                    "callback for job {} returned {}",
+                    status.job_id,
+                    response.status()
+                ),
+                Err(err) => warn!("callback for job {} failed: {err}", status.job_id),
+            }
+        }
+        false
+    }
+}
+
+impl StoredRequest {
+    fn parts(&self) -> Result<(Method, Uri, HeaderMap)> {
+        let method = Method::from_bytes(self.method.as_bytes())?;
+        let uri = self.uri.parse::<Uri>()?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_bytes(value)?,
+            );
+        }
+        Ok((method, uri, headers))
+    }
+}
+
+/// Parse a callback URL. Only HTTPS callbacks to hosts outside `base_domain` are
+/// accepted, and an IP address must be a public one. Names are checked again when
+/// they are resolved, by [`PublicResolver`].
+pub fn parse_callback(value: &str, base_domain: &str) -> Result<Url, String> {
+    let url = Url::parse(value).map_err(|err| format!("invalid callback URL: {err}"))?;
+    if url.scheme() != "https" {
+        return Err("callback URL must use https".to_string());
+    }
+    let host = url
+        .host_str()
+        .ok_or("callback URL must name a host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        if !is_public(ip) {
+            return Err("callback URL must not point at a private address".to_string());
+        }
+    } else if host == "localhost" || host.ends_with(".localhost") {
+        return Err("callback URL must not point at a private address".to_string());
+    } else if host == base_domain || host.ends_with(&format!(".{base_domain}")) {
+        return Err("callback URL must not point at this server".to_string());
+    }
+    Ok(url)
+}
+
+/// Whether `ip` is reachable on the public internet, rather than loopback, private,
+/// link-local or otherwise reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            // 100.64.0.0/10 is carrier-grade NAT, 192.0.0.0/24 protocol assignments
+            let shared = ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64;
+            let protocol = ip.octets()[..3] == [192, 0, 0];
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || shared
+                || protocol
+                || ip.octets()[0] == 0
+                || ip.octets()[0] >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    // 64:ff9b::/96 translates to IPv4 addresses
+                    || ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                    && !is_public(IpAddr::V4(Ipv4Addr::from_bits(
+                        ip.to_bits() as u32,
+                    ))))
+            }
+        },
+    }
+}
+
+/// Resolves callback hosts to their public addresses only, so a name pointing at the
+/// server's own network is refused when it is looked up rather than only when the
+/// job is submitted.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn summary(status: &JobStatus) -> JobSummary {
+    JobSummary {
+        job_id: status.job_id.clone(),
+        function_name: status.function_name.clone(),
+        state: status.state,
+        attempts: status.attempts,
+        submitted_at: status.submitted_at.clone(),
+        finished_at: status.finished_at.clone(),
+    }
+}
+
+fn encode(job: &StoredJob) -> Result<Vec<u8>> {
+    Ok(bincode::encode_to_vec(job, bincode::config::standard())?)
+}
+
+fn decode(encoded: &[u8]) -> Result<StoredJob> {
+    let (job, _) = bincode::decode_from_slice(encoded, bincode::config::standard())?;
+    Ok(job)
+}
+
+fn unix_millis() -> u64 {
+    chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
+fn describe_failure(function_name: &str, err: &anyhow::Error) -> ErrorBody {
+    if let Some(&fault) = err.downcast_ref::<Fault>() {
+        warn!("job of '{function_name}' failed: {err:#}");
+        return error_body::body(fault.code(), fault.message());
+    }
+    if err.downcast_ref::<Quarantined>().is_some() {
+        return error_body::body(
+            error_code::ARTIFACT_UNAVAILABLE,
+            "Function artifact is unavailable, try again later",
+        );
+    }
+    warn!("job of '{function_name}' failed: {err:?}");
+    error_body::body(error_code::INVOCATION_FAILED, "Function invocation failed")
+}
+
+fn is_framing_header(name: &str) -> bool {
+    [
+        header::CONTENT_LENGTH.as_str(),
+        header::TRANSFER_ENCODING.as_str(),
+        header::CONNECTION.as_str(),
+    ]
+    .contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callbacks_must_be_public_https() {
+        let base = "faasta.xyz";
+        assert!(parse_callback("https://hooks.example.com/done", base).is_ok());
+        assert!(parse_callback("https://93.184.216.34/done", base).is_ok());
+        for refused in [
+            "http://hooks.example.com/done",
+            "https://127.0.0.1/",
+            "https://10.1.2.3/",
+            "https://169.254.169.254/latest/meta-data/",
+            "https://100.64.0.1/",
+            "https://[::1]/",
+            "https://[fd00::1]/",
+            "https://[::ffff:192.168.0.1]/",
+            "https://localhost/",
+            "https://LOCALHOST./",
+            "https://faasta.xyz/",
+            "https://my-function.faasta.xyz/",
+        ] {
+            assert!(parse_callback(refused, base).is_err(), "{refused}");
+        }
+    }
+
+    #[test]
+    fn only_public_addresses_are_public() {
+        for public in ["8.8.8.8", "2606:4700::1111", "64:ff9b::808:808"] {
+            assert!(is_public(public.parse().unwrap()), "{public}");
+        }
+        for private in [
+            "0.0.0.0",
+            "192.168.1.1",
+            "172.16.0.1",
+            "192.0.0.8",
+            "255.255.255.255",
+            "fe80::1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public(private.parse().unwrap()), "{private}");
+        }
+    }
+}
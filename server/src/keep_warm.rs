@@ -0,0 +1,47 @@
+//! Applies per-function keep-warm windows: functions are loaded when their window
+//! opens and, with `scale_to_zero`, unloaded when it closes.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Timelike, Utc};
+use tokio::time;
+use tracing::{debug, warn};
+
+use crate::wasi_server::FaastaServer;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Spawn the background task that enforces keep-warm policies.
+pub fn spawn(server: Arc<FaastaServer>) {
+    tokio::spawn(async move {
+        // Whether each function was inside its window at the previous check, so
+        // functions are only unloaded when a window closes rather than after every
+        // request that arrives outside it.
+        let mut in_window: HashMap<String, bool> = HashMap::new();
+        let mut ticker = time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let hour = Utc::now().hour() as u8;
+            let policies = server.function_configs.keep_warm_policies();
+            in_window.retain(|name, _| policies.iter().any(|(policy_name, _)| policy_name == name));
+
+            for (name, policy) in policies {
+                let inside = policy.contains_hour(hour);
+                let was_inside = in_window.insert(name.clone(), inside);
+                if inside {
+                    if !server.is_loaded(&name)
+                        && server.function_exists(&name)
+                        && let Err(err) = server.warm(&name).await
+                    {
+                        warn!("failed to keep '{name}' warm: {err:#}");
+                    }
+                } else if policy.scale_to_zero && was_inside != Some(false) {
+                    debug!("keep-warm window closed for '{name}', unloading");
+                    server.unload(&name);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,238 @@
+//! Embedded store behind `wasi:keyvalue` when `FAASTA_KV_BACKEND=local`, so functions
+//! keep state across requests and restarts without an external service.
+//!
+//! Every function's buckets live in one redb file, keyed by function, bucket and key.
+//! A function may store up to `FAASTA_KV_QUOTA_BYTES` of keys and values across its
+//! buckets; a `set` that would go over fails and leaves the store unchanged.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, ensure};
+use faasta_interface::{KvBucket, KvKey, KvUsage};
+use once_cell::sync::OnceCell;
+use redb::{Database, ReadableTable, TableDefinition};
+
+/// (function, bucket, key) -> value
+const ENTRIES: TableDefinition<(&str, &str, &str), &[u8]> = TableDefinition::new("entries");
+/// function -> bytes of keys and values stored
+const USAGE: TableDefinition<&str, u64> = TableDefinition::new("usage");
+
+pub const DEFAULT_QUOTA_BYTES: u64 = 16 * 1024 * 1024;
+
+static SHARED: OnceCell<Arc<LocalKeyValue>> = OnceCell::new();
+
+pub struct LocalKeyValue {
+    db: Database,
+    quota_bytes: u64,
+}
+
+/// Bytes an entry counts against its function's quota
+fn entry_bytes(bucket: &str, key: &str, value_len: usize) -> u64 {
+    (bucket.len() + key.len() + value_len) as u64
+}
+
+impl LocalKeyValue {
+    pub fn open(path: &Path, quota_bytes: u64) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create key-value parent dir {parent:?}"))?;
+        }
+        let db = Database::create(path).with_context(|| format!("failed to open {path:?}"))?;
+        let txn = db.begin_write()?;
+        txn.open_table(ENTRIES)?;
+        txn.open_table(USAGE)?;
+        txn.commit()?;
+        Ok(Self { db, quota_bytes })
+    }
+
+    pub fn get(&self, function: &str, bucket: &str, key: &str) -> Result<Option<Vec<u8>>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(ENTRIES)?;
+        Ok(table
+            .get((function, bucket, key))?
+            .map(|value| value.value().to_vec()))
+    }
+
+    pub fn set(&self, function: &str, bucket: &str, key: &str, value: &[u8]) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut usage = txn.open_table(USAGE)?;
+            let previous = entries
+                .get((function, bucket, key))?
+                .map_or(0, |value| entry_bytes(bucket, key, value.value().len()));
+            let used = usage.get(function)?.map_or(0, |used| used.value());
+            let used = used.saturating_sub(previous) + entry_bytes(bucket, key, value.len());
+            ensure!(
+                used <= self.quota_bytes,
+                "key-value quota of {} bytes for '{function}' exceeded",
+                self.quota_bytes
+            );
+            entries.insert((function, bucket, key), value)?;
+            usage.insert(function, used)?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    pub fn delete(&self, function: &str, bucket: &str, key: &str) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut usage = txn.open_table(USAGE)?;
+            let removed = entries
+                .remove((function, bucket, key))?
+                .map(|value| entry_bytes(bucket, key, value.value().len()));
+            if let Some(removed) = removed {
+                let used = usage.get(function)?.map_or(0, |used| used.value());
+                usage.insert(function, used.saturating_sub(removed))?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Keys of `bucket` and the size of their values, in key order.
+    pub fn keys(&self, function: &str, bucket: &str) -> Result<Vec<KvKey>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(ENTRIES)?;
+        let mut keys = Vec::new();
+        for entry in table.range((function, bucket, "")..)? {
+            let (key, value) = entry?;
+            let (entry_function, entry_bucket, key) = key.value();
+            if entry_function != function || entry_bucket != bucket {
+                break;
+            }
+            keys.push(KvKey {
+                key: key.to_string(),
+                value_bytes: value.value().len() as u64,
+            });
+        }
+        Ok(keys)
+    }
+
+    pub fn usage(&self, function: &str) -> Result<KvUsage> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(ENTRIES)?;
+        let mut buckets: Vec<KvBucket> = Vec::new();
+        for entry in table.range((function, "", "")..)? {
+            let (key, value) = entry?;
+            let (entry_function, bucket, key) = key.value();
+            if entry_function != function {
+                break;
+            }
+            if buckets.last().is_none_or(|last| last.name != bucket) {
+                buckets.push(KvBucket {
+                    name: bucket.to_string(),
+                    keys: 0,
+                    bytes: 0,
+                });
+            }
+            if let Some(last) = buckets.last_mut() {
+                last.keys += 1;
+                last.bytes += entry_bytes(bucket, key, value.value().len());
+            }
+        }
+        let used_bytes = txn
+            .open_table(USAGE)?
+            .get(function)?
+            .map_or(0, |used| used.value());
+        Ok(KvUsage {
+            used_bytes,
+            quota_bytes: self.quota_bytes,
+            buckets,
+        })
+    }
+
+    /// Drop every bucket of `function`, e.g. when it is unpublished.
+    pub fn remove_function(&self, function: &str) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut entries = txn.open_table(ENTRIES)?;
+            let mut doomed = Vec::new();
+            for entry in entries.range((function, "", "")..)? {
+                let (key, _) = entry?;
+                let (entry_function, bucket, key) = key.value();
+                if entry_function != function {
+                    break;
+                }
+                doomed.push((bucket.to_string(), key.to_string()));
+            }
+            for (bucket, key) in &doomed {
+                entries.remove((function, bucket.as_str(), key.as_str()))?;
+            }
+            txn.open_table(USAGE)?.remove(function)?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+/// The store configured by `FAASTA_WASI_KV_PATH` and `FAASTA_KV_QUOTA_BYTES`, opened
+/// on first use and shared by every runtime.
+pub fn shared() -> Result<Arc<LocalKeyValue>> {
+    SHARED
+        .get_or_try_init(|| {
+            let path = PathBuf::from(
+                std::env::var("FAASTA_WASI_KV_PATH")
+                    .unwrap_or_else(|_| "./data/wasi-kv.redb".to_string()),
+            );
+            let quota_bytes = match std::env::var("FAASTA_KV_QUOTA_BYTES") {
+                Ok(value) => value
+                    .parse()
+                    .with_context(|| format!("invalid FAASTA_KV_QUOTA_BYTES '{value}'"))?,
+                Err(_) => DEFAULT_QUOTA_BYTES,
+            };
+            Ok(Arc::new(LocalKeyValue::open(&path, quota_bytes)?))
+        })
+        .cloned()
+}
+
+/// The shared store if the local backend is in use.
+pub fn opened() -> Option<Arc<LocalKeyValue>> {
+    SHARED.get().cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforces_the_quota_per_function() {
+        let dir = tempfile::tempdir().unwrap();
+        let kv = LocalKeyValue::open(&dir.path().join("kv.redb"), 120).unwrap();
+
+        kv.set("app", "default", "a", &[0; 40]).unwrap();
+        kv.set("app", "cache", "b", &[0; 40]).unwrap();
+        assert!(kv.set("app", "default", "c", &[0; 40]).is_err());
+        // Overwriting only counts the difference, and other functions have their own quota
+        kv.set("app", "default", "a", &[0; 50]).unwrap();
+        kv.set("other", "default", "c", &[0; 90]).unwrap();
+        assert_eq!(kv.get("app", "default", "c").unwrap(), None);
+
+        let usage = kv.usage("app").unwrap();
+        assert_eq!(usage.used_bytes, (7 + 1 + 50) + (5 + 1 + 40));
+        let buckets: Vec<_> = usage
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.name.as_str(), bucket.keys))
+            .collect();
+        assert_eq!(buckets, [("cache", 1), ("default", 1)]);
+
+        kv.delete("app", "cache", "b").unwrap();
+        kv.set("app", "default", "c", &[0; 40]).unwrap();
+        let keys: Vec<_> = kv
+            .keys("app", "default")
+            .unwrap()
+            .into_iter()
+            .map(|key| key.key)
+            .collect();
+        assert_eq!(keys, ["a", "c"]);
+
+        kv.remove_function("app").unwrap();
+        assert_eq!(kv.usage("app").unwrap().used_bytes, 0);
+        assert!(kv.keys("app", "default").unwrap().is_empty());
+        assert_eq!(kv.keys("other", "default").unwrap().len(), 1);
+    }
+}
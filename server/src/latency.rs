@@ -0,0 +1,211 @@
+//! Latency histograms. [`Histogram`] has a few fixed buckets, cheap enough to keep per
+//! function and day; percentiles are reported as the upper bound of the bucket they
+//! fall in. [`Hdr`] is an HdrHistogram accurate to 1%, kept per function for its
+//! all-time latency and persisted with its metrics.
+
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use faasta_interface::LatencyDistribution;
+use hdrhistogram::serialization::{Deserializer, Serializer, V2DeflateSerializer, V2Serializer};
+
+/// HdrHistogram of wall times in microseconds
+pub type Hdr = hdrhistogram::Histogram<u64>;
+
+/// Slowest latency an [`Hdr`] resolves; slower calls are recorded as this
+const HDR_HIGHEST_MICROS: u64 = 60 * 60 * 1_000_000;
+/// Significant figures an [`Hdr`] keeps, i.e. values are accurate to within 1%
+const HDR_SIGFIGS: u8 = 2;
+
+/// Upper bounds of the buckets, in milliseconds; slower calls count as the last
+pub const BUCKET_BOUNDS_MS: [u64; 13] =
+    [1, 2, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u64; BUCKET_BOUNDS_MS.len()],
+    total_micros: u64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, duration: Duration) {
+        let elapsed_ms = duration.as_millis();
+        let bucket = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| elapsed_ms <= u128::from(*bound))
+            .unwrap_or(BUCKET_BOUNDS_MS.len() - 1);
+        self.counts[bucket] += 1;
+        self.total_micros = self
+            .total_micros
+            .saturating_add(duration.as_micros() as u64);
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (total, count) in self.counts.iter_mut().zip(other.counts) {
+            *total += count;
+        }
+        self.total_micros = self.total_micros.saturating_add(other.total_micros);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Upper bound, in milliseconds, of the bucket holding the `percent`th percentile.
+    pub fn percentile(&self, percent: u64) -> Option<u64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = count.saturating_mul(percent).div_ceil(100).max(1);
+        let mut seen = 0;
+        self.counts
+            .iter()
+            .zip(BUCKET_BOUNDS_MS)
+            .find(|(count, _)| {
+                seen += **count;
+                seen >= rank
+            })
+            .map(|(_, bound)| bound)
+    }
+
+    pub fn distribution(&self) -> LatencyDistribution {
+        LatencyDistribution {
+            count: self.count(),
+            total_micros: self.total_micros,
+            p50_ms: self.percentile(50),
+            p95_ms: self.percentile(95),
+            p99_ms: self.percentile(99),
+        }
+    }
+}
+
+pub fn new_hdr() -> Hdr {
+    Hdr::new_with_bounds(1, HDR_HIGHEST_MICROS, HDR_SIGFIGS).expect("valid histogram bounds")
+}
+
+pub fn record_hdr(hdr: &mut Hdr, duration: Duration) {
+    let micros = duration
+        .as_micros()
+        .clamp(1, u128::from(HDR_HIGHEST_MICROS)) as u64;
+    hdr.saturating_record(micros);
+}
+
+/// Stand-in for totals persisted before latencies were: `call_count` calls at their mean.
+pub fn hdr_from_totals(total_time_millis: u64, call_count: u64) -> Hdr {
+    let mut hdr = new_hdr();
+    if let Some(mean) = total_time_millis.checked_div(call_count) {
+        let mean = Duration::from_millis(mean);
+        let micros = mean.as_micros().clamp(1, u128::from(HDR_HIGHEST_MICROS)) as u64;
+        hdr.saturating_record_n(micros, call_count);
+    }
+    hdr
+}
+
+/// Sum of the recorded latencies, to the histogram's precision.
+pub fn hdr_total_micros(hdr: &Hdr) -> u64 {
+    (hdr.mean() * hdr.len() as f64).round() as u64
+}
+
+pub fn hdr_distribution(hdr: &Hdr) -> LatencyDistribution {
+    let percentile =
+        |quantile: f64| (!hdr.is_empty()).then(|| hdr.value_at_quantile(quantile).div_ceil(1000));
+    LatencyDistribution {
+        count: hdr.len(),
+        total_micros: hdr_total_micros(hdr),
+        p50_ms: percentile(0.5),
+        p95_ms: percentile(0.95),
+        p99_ms: percentile(0.99),
+    }
+}
+
+/// Uncompressed V2 encoding, as persisted.
+pub fn encode_hdr(hdr: &Hdr) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    V2Serializer::new()
+        .serialize(hdr, &mut encoded)
+        .map_err(|err| anyhow!("failed to encode latency histogram: {err:?}"))?;
+    Ok(encoded)
+}
+
+pub fn decode_hdr(mut encoded: &[u8]) -> Result<Hdr> {
+    let decoded: Hdr = Deserializer::new()
+        .deserialize(&mut encoded)
+        .map_err(|err| anyhow!("failed to decode latency histogram: {err:?}"))?;
+    let mut hdr = new_hdr();
+    hdr.add(&decoded)
+        .map_err(|err| anyhow!("failed to decode latency histogram: {err:?}"))?;
+    Ok(hdr)
+}
+
+/// Base64 of the compressed V2 encoding, the form HdrHistogram's log tooling reads.
+pub fn export_hdr(hdr: &Hdr) -> String {
+    let mut encoded = Vec::new();
+    match V2DeflateSerializer::new().serialize(hdr, &mut encoded) {
+        Ok(_) => BASE64.encode(encoded),
+        Err(_) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_bucket_bounds_for_percentiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(50), None);
+        for _ in 0..95 {
+            histogram.record(Duration::from_millis(8));
+        }
+        for _ in 0..4 {
+            histogram.record(Duration::from_millis(900));
+        }
+        histogram.record(Duration::from_secs(60));
+
+        let distribution = histogram.distribution();
+        assert_eq!(distribution.count, 100);
+        assert_eq!(distribution.total_micros, 64_360_000);
+        assert_eq!(
+            (
+                distribution.p50_ms,
+                distribution.p95_ms,
+                distribution.p99_ms
+            ),
+            (Some(10), Some(10), Some(1000))
+        );
+
+        let mut merged = Histogram::default();
+        merged.merge(&histogram);
+        merged.merge(&histogram);
+        assert_eq!(merged.count(), 200);
+        assert_eq!(merged.percentile(100), Some(10000));
+    }
+
+    #[test]
+    fn round_trips_hdr_histograms() {
+        let mut hdr = new_hdr();
+        for millis in 1..=100 {
+            record_hdr(&mut hdr, Duration::from_millis(millis));
+        }
+        let distribution = hdr_distribution(&hdr);
+        assert_eq!(distribution.count, 100);
+        assert_eq!(
+            (
+                distribution.p50_ms,
+                distribution.p95_ms,
+                distribution.p99_ms
+            ),
+            (Some(51), Some(96), Some(100))
+        );
+        assert_eq!(decode_hdr(&encode_hdr(&hdr).unwrap()).unwrap(), hdr);
+        assert!(!export_hdr(&hdr).is_empty());
+
+        let legacy = hdr_from_totals(500, 10);
+        assert_eq!(legacy.len(), 10);
+        assert_eq!(hdr_distribution(&legacy).p99_ms, Some(51));
+        assert!(hdr_distribution(&new_hdr()).p50_ms.is_none());
+    }
+}
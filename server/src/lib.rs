@@ -0,0 +1,66 @@
+//! Faasta server internals, shared by the `faasta-server` binary and the benches.
+
+#![warn(unused_extern_crates)]
+
+pub mod about;
+pub mod admission;
+pub mod alerts;
+pub mod allowlist;
+pub mod apex_files;
+pub mod batch;
+pub mod cert_manager;
+pub mod chaos;
+pub mod cold_starts;
+pub mod cpu_time;
+pub mod crypto;
+pub mod db;
+pub mod dedicated;
+pub mod deploy_lock;
+pub mod determinism;
+pub mod encryption;
+pub mod error_body;
+pub mod error_pages;
+pub mod failover;
+pub mod fnv;
+pub mod fsck;
+pub mod function_config;
+pub mod function_key;
+pub mod function_logs;
+pub mod gc;
+pub mod github_auth;
+pub mod graphql;
+pub mod grpc_web;
+pub mod guest_log;
+pub mod idempotency;
+pub mod image_transform;
+pub mod inspect;
+pub mod jobs;
+pub mod keep_warm;
+pub mod kv_store;
+pub mod latency;
+pub mod metrics;
+pub mod mtls;
+pub mod oci;
+pub mod platform;
+pub mod preinit;
+pub mod preload;
+pub mod public_stats;
+pub mod publish;
+pub mod quarantine;
+pub mod reoptimize;
+pub mod request_signing;
+pub mod response_cache;
+pub mod routes;
+pub mod rpc_service;
+pub mod scheduler;
+pub mod secrets;
+pub mod security_headers;
+pub mod server_timing;
+pub mod sessions;
+pub mod static_site;
+pub mod tls;
+pub mod traffic;
+pub mod traps;
+pub mod two_factor;
+pub mod wasi_server;
+pub mod wasm_function;
@@ -3,44 +3,74 @@
 use anyhow::{Context, Result};
 use axum::Router;
 use axum::body::{Body, to_bytes};
-use axum::extract::{OriginalUri, Path, State};
-use axum::http::{HeaderMap, Request, StatusCode, header};
+use axum::extract::{ConnectInfo, OriginalUri, Path, State};
+use axum::http::{HeaderMap, HeaderValue, Method, Request, StatusCode, Uri, header};
+use axum::middleware::{self, Next};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use axum_server::tls_rustls::RustlsConfig;
 use bitrpc::tokio as bitrpc_tokio;
-use clap::Parser;
-use faasta_interface::FunctionError;
+use bytes::Bytes;
+use clap::builder::TypedValueParser;
+use clap::{Parser, Subcommand};
 use faasta_interface::RpcRequestServiceWrapper;
+use faasta_interface::{FunctionError, error_code, protocol};
 use serde::Serialize;
 use serde_json::json;
-use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path as FsPath, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
 use tower::ServiceBuilder;
 use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::trace::TraceLayer;
-use tracing::{Level, error, info};
-
-mod cert_manager;
-mod db;
-mod github_auth;
-mod metrics;
-mod rpc_service;
-mod wasi_server;
-mod wasm_function;
-
-use cert_manager::CertManager;
-use db::Database;
-use metrics::{get_metrics, spawn_periodic_flush};
+use tracing::{Level, error, info, warn};
+
+use faasta_server::{
+    about, admission, alerts, allowlist, apex_files, batch, cert_manager, chaos, crypto, db,
+    dedicated, encryption, error_body, error_pages, failover, fsck, function_key, function_logs,
+    gc, graphql, grpc_web, idempotency, inspect, jobs, keep_warm, metrics, mtls, platform, preinit,
+    public_stats, quarantine, reoptimize, response_cache, routes, rpc_service, scheduler,
+    security_headers, server_timing, static_site, tls, traffic, traps, wasi_server, wasm_function,
+};
+
+use admission::AdmissionController;
+use alerts::Alerts;
+use allowlist::Cidr;
+use apex_files::ApexFiles;
+use batch::{BatchRequest, BatchResponse, SubRequest, SubResponse};
+use cert_manager::{CertManager, CtMonitor, DnsManager, DnsProviderKind};
+use crypto::CryptoProvider;
+use db::{MetadataBackend, Tree};
+use dedicated::DedicatedTier;
+use error_pages::ErrorPages;
+use failover::{FailoverHooks, NodeHealth};
+use function_key::MetricKey;
+use graphql::PersistedQueries;
+use idempotency::{CachedResponse, IdempotencyCache, IdempotencyLookup, request_fingerprint};
+use jobs::{JobRequest, TooManyJobs};
+use metrics::{Timer, get_metrics, spawn_periodic_flush};
+use quarantine::Quarantined;
+use response_cache::{CacheKey, CacheLookup, ResponseCache};
+use routes::RouteOutcome;
 use rpc_service::create_service;
-use wasi_server::{FaastaServer, FunctionInvoker, SERVER, sanitize_function_name};
+use scheduler::CpuScheduler;
+use security_headers::HeaderPolicy;
+use tls::{ServerCertificate, TlsPolicy};
+use traps::Fault;
+use wasi_server::{
+    FaastaServer, FunctionInvoker, SERVER, hold_until_body_ends, sanitize_function_name,
+};
+use wasm_function::PoolConfig;
 
 #[derive(Parser, Debug, Clone)]
 #[command(name = "server")]
 #[command(about = "Faasta WASI HTTP Function Server", long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Address to listen on (e.g., 0.0.0.0:443)
     #[arg(short, long, env = "LISTEN_ADDR", default_value = "0.0.0.0:443")]
     listen_addr: SocketAddr,
@@ -53,6 +83,47 @@ struct Args {
     #[arg(long, env = "BASE_DOMAIN", default_value = "faasta.lol")]
     base_domain: String,
 
+    /// rustls crypto provider (aws-lc-rs and fips need the matching build feature)
+    #[arg(long, env = "CRYPTO_PROVIDER", value_enum, default_value = "ring")]
+    crypto_provider: CryptoProvider,
+
+    /// Staple OCSP responses from the certificate's responder to TLS handshakes
+    #[arg(long, env = "OCSP_STAPLING")]
+    ocsp_stapling: bool,
+
+    /// Alert when Certificate Transparency logs show an unexpected certificate for BASE_DOMAIN
+    #[arg(long, env = "CT_MONITOR")]
+    ct_monitor: bool,
+
+    /// Issuer name fragments (comma-separated) whose certificates are expected by CT_MONITOR
+    #[arg(long, env = "CT_EXPECTED_ISSUERS", value_delimiter = ',')]
+    ct_expected_issuers: Vec<String>,
+
+    /// Seconds between Certificate Transparency checks
+    #[arg(long, env = "CT_CHECK_INTERVAL_SECS", default_value_t = 21600)]
+    ct_check_interval_secs: u64,
+
+    /// Accept TLS 1.3 handshakes only
+    #[arg(long, env = "TLS13_ONLY")]
+    tls13_only: bool,
+
+    /// Allowed TLS cipher suites by IANA name (comma-separated); defaults to the provider's set
+    #[arg(long, env = "TLS_CIPHER_SUITES", value_delimiter = ',')]
+    tls_cipher_suites: Vec<String>,
+
+    /// ALPN protocols offered by the HTTPS listeners, in order of preference
+    #[arg(
+        long,
+        env = "TLS_ALPN",
+        value_delimiter = ',',
+        default_value = "h2,http/1.1"
+    )]
+    tls_alpn: Vec<String>,
+
+    /// Seconds each TLS session ticket key is used before rotation (0 disables tickets)
+    #[arg(long, env = "TLS_TICKET_ROTATION_SECS", default_value_t = 0)]
+    tls_ticket_rotation_secs: u64,
+
     /// Path to the TLS certificate file (PEM format)
     #[arg(long, env = "TLS_CERT", default_value = "./certs/cert.pem")]
     tls_cert_path: PathBuf,
@@ -65,38 +136,311 @@ struct Args {
     #[arg(long, env = "CERTS_DIR", default_value = "./certs")]
     certs_dir: PathBuf,
 
-    /// Path to the SQLite metadata database directory or file
+    /// Path to the metadata database directory or file
     #[arg(long, env = "DB_PATH", default_value = "./data/db")]
     db_path: PathBuf,
 
+    /// Storage engine for function, user, and metrics metadata
+    #[arg(long, env = "METADATA_BACKEND", value_enum, default_value = "sqlite")]
+    metadata_backend: MetadataBackend,
+
+    /// Copy all metadata from the configured backend into this backend, then exit
+    #[arg(long, value_enum)]
+    migrate_metadata_to: Option<MetadataBackend>,
+
     /// Path to the functions directory containing uploaded WASI components
     #[arg(long, env = "FUNCTIONS_PATH", default_value = "./functions")]
     functions_path: PathBuf,
 
-    /// Address for the RPC server (QUIC)
+    /// CA bundle (PEM) for management client certificates. When set, the RPC and publish
+    /// endpoints move to the management listener and require a certificate signed by it
+    #[arg(long, env = "CLIENT_CA")]
+    client_ca_path: Option<PathBuf>,
+
+    /// Serve the RPC and publish endpoints on this address instead of the public listener
+    /// (defaults to 0.0.0.0:8443 when --client-ca-path is set)
+    #[arg(long, env = "MANAGEMENT_LISTEN_ADDR")]
+    management_listen_addr: Option<SocketAddr>,
+
+    /// Serve the management listener over plain HTTP, for a TLS-terminating proxy on the
+    /// same host or local testing. Only allowed on a loopback MANAGEMENT_LISTEN_ADDR
+    #[arg(long, env = "MANAGEMENT_PLAINTEXT")]
+    management_plaintext: bool,
+
+    /// Source networks (CIDR, comma-separated) allowed to reach the RPC and publish endpoints
+    #[arg(long, env = "MANAGEMENT_ALLOW", value_delimiter = ',')]
+    management_allow: Vec<Cidr>,
+
+    /// Do not serve the bitRPC endpoint; only the REST publish endpoint remains
+    #[arg(long, env = "DISABLE_RPC")]
+    disable_rpc: bool,
+
+    /// Origins (comma-separated, or `*`) allowed to call the /v1 and RPC endpoints from a browser
+    #[arg(long, env = "CORS_ALLOW_ORIGINS", value_delimiter = ',')]
+    cors_allow_origins: Vec<String>,
+
+    /// Strict-Transport-Security max-age for platform endpoints, in seconds (0 disables)
+    #[arg(long, env = "HSTS_MAX_AGE", default_value_t = 31_536_000)]
+    hsts_max_age: u64,
+
+    /// Add `preload` to the HSTS header (requires HSTS_MAX_AGE of at least one year)
+    #[arg(long, env = "HSTS_PRELOAD")]
+    hsts_preload: bool,
+
+    /// Serve the built-in Faasta landing page and docs at the root of BASE_DOMAIN
+    #[arg(long, env = "SERVE_WEBSITE")]
+    serve_website: bool,
+
+    /// Resolve `label.name.BASE_DOMAIN` to function `name` instead of rejecting the host
+    #[arg(long, env = "ALLOW_NESTED_SUBDOMAINS")]
+    allow_nested_subdomains: bool,
+
+    /// URL browsers are redirected to when no function matches the request (404 if unset)
+    #[arg(long, env = "FALLBACK_URL")]
+    fallback_url: Option<String>,
+
+    /// HTTPS port clients are redirected to from HTTP_LISTEN_ADDR
+    #[arg(long, env = "REDIRECT_HTTPS_PORT", default_value_t = 443)]
+    redirect_https_port: u16,
+
+    /// File served as /robots.txt on the base domain (defaults to allowing all crawlers)
+    #[arg(long, env = "ROBOTS_TXT")]
+    robots_txt_path: Option<PathBuf>,
+
+    /// Icon served as /favicon.ico on the base domain (defaults to an empty response)
+    #[arg(long, env = "FAVICON")]
+    favicon_path: Option<PathBuf>,
+
+    /// Directory of instance-wide HTML error pages (`404.html`, `5xx.html`, ...)
+    #[arg(long, env = "ERROR_PAGES_DIR")]
+    error_pages_dir: Option<PathBuf>,
+
+    /// Command that snapshots a component after running its initialization, applied to
+    /// every published component; `{input}` and `{output}` are substituted
+    #[arg(long, env = "PREINIT_COMMAND")]
+    preinit_command: Option<String>,
+
+    /// Threads serving requests (defaults to the number of CPU cores)
+    #[arg(long, env = "WORKER_THREADS", value_parser = clap::value_parser!(u16).range(1..).map(usize::from))]
+    worker_threads: Option<usize>,
+
+    /// Concurrent function instances the pooling allocator reserves slots for
+    #[arg(long, env = "POOL_INSTANCES", default_value_t = PoolConfig::default().component_instances)]
+    pool_instances: u32,
+
+    /// Core module instances (and tables and stacks) across the pool
+    #[arg(long, env = "POOL_CORE_INSTANCES", default_value_t = PoolConfig::default().core_instances)]
+    pool_core_instances: u32,
+
+    /// Linear memories across the pool
+    #[arg(long, env = "POOL_MEMORIES", default_value_t = PoolConfig::default().memories)]
+    pool_memories: u32,
+
+    /// Largest linear memory a function instance may grow to, in MiB
+    #[arg(long, env = "POOL_MAX_MEMORY_MB", default_value_t = 256)]
+    pool_max_memory_mb: usize,
+
+    /// Share of the instance pool (percent) in use at which new invocations are shed
+    #[arg(long, env = "ADMISSION_WATERMARK_PERCENT", default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    admission_watermark_percent: u8,
+
+    /// Tasks waiting in the runtime's queue at which new invocations are shed (0 disables)
+    #[arg(long, env = "ADMISSION_MAX_QUEUE_DEPTH", default_value_t = 1024)]
+    admission_max_queue_depth: usize,
+
+    /// Retry-After sent with shed requests, in seconds
+    #[arg(long, env = "ADMISSION_RETRY_AFTER_SECS", default_value_t = 1)]
+    admission_retry_after_secs: u64,
+
+    /// Functions served from their own instance pool and worker threads
+    #[arg(long, env = "DEDICATED_FUNCTIONS", value_delimiter = ',')]
+    dedicated_functions: Vec<String>,
+
+    /// Concurrent instances (and linear memories) reserved for dedicated functions
+    #[arg(long, env = "DEDICATED_POOL_INSTANCES", default_value_t = 10)]
+    dedicated_pool_instances: u32,
+
+    /// Threads running dedicated functions
+    #[arg(long, env = "DEDICATED_WORKER_THREADS", default_value_t = 2, value_parser = clap::value_parser!(u16).range(1..).map(usize::from))]
+    dedicated_worker_threads: usize,
+
+    /// Shares of guest CPU time as `OWNER=WEIGHT`; owners not listed have weight 1
+    #[arg(long, env = "CPU_WEIGHTS", value_delimiter = ',', value_parser = parse_cpu_weight)]
+    cpu_weights: Vec<(String, u32)>,
+
+    /// CPU time a single invocation may use before it is aborted, in milliseconds (0 disables)
+    #[arg(long, env = "CPU_BUDGET_MS", default_value_t = 0)]
+    cpu_budget_ms: u64,
+
+    /// DNS and certificate provider of the base domain (API keys are read from its env vars)
+    #[arg(long, env = "DNS_PROVIDER", value_enum, default_value = "porkbun")]
+    dns_provider: DnsProviderKind,
+
+    /// Publish this node's address in DNS while it is healthy and remove it when it
+    /// drains or fails readiness
+    #[arg(long, env = "DNS_FAILOVER", requires = "node_address")]
+    dns_failover: bool,
+
+    /// Records under the base domain that point at this node (`@` is the apex)
+    #[arg(
+        long,
+        env = "DNS_FAILOVER_RECORDS",
+        value_delimiter = ',',
+        default_value = "@,*"
+    )]
+    dns_failover_records: Vec<String>,
+
+    /// TTL of the records created for this node, in seconds
+    #[arg(long, env = "DNS_FAILOVER_TTL", default_value_t = 600)]
+    dns_failover_ttl: u32,
+
+    /// Region reported to functions as `FAASTA_REGION`
+    #[arg(long, env = "REGION")]
+    region: Option<String>,
+
+    /// Public address of this node, as published in DNS
+    #[arg(long, env = "NODE_ADDRESS")]
+    node_address: Option<IpAddr>,
+
+    /// On SIGTERM, how long the node keeps serving after it starts draining, in seconds
+    #[arg(long, env = "DRAIN_GRACE_SECS", default_value_t = 30)]
+    drain_grace_secs: u64,
+
+    /// Path of the RPC endpoint on the HTTPS listener
     #[arg(long, env = "RPC_PATH", default_value = "/rpc")]
     rpc_path: String,
 
-    /// Auto-generate TLS certificate using Porkbun
+    /// Auto-generate TLS certificate through the DNS provider
     #[arg(long, env = "AUTO_CERT", default_value = "false")]
     auto_cert: bool,
+
+    /// How long responses are replayed for a repeated Idempotency-Key (0 disables)
+    #[arg(long, env = "IDEMPOTENCY_WINDOW_SECS", default_value = "86400")]
+    idempotency_window_secs: u64,
+
+    /// Maximum number of function responses held in the shared response cache (0 disables)
+    #[arg(long, env = "RESPONSE_CACHE_ENTRIES", default_value = "10000")]
+    response_cache_entries: usize,
+
+    /// Maximum size of the responses held in the shared response cache, in MiB (0 disables)
+    #[arg(long, env = "RESPONSE_CACHE_MB", default_value = "256")]
+    response_cache_mb: usize,
+
+    /// GraphQL queries remembered per function for automatic persisted queries (0 disables)
+    #[arg(long, env = "GRAPHQL_PERSISTED_QUERIES", default_value = "1000")]
+    graphql_persisted_queries: usize,
+
+    /// How long an asynchronous invocation may wait for a slot and run, in seconds
+    #[arg(long, env = "ASYNC_INVOKE_TIMEOUT_SECS", default_value_t = 900)]
+    async_invoke_timeout_secs: u64,
+
+    /// How often unreferenced compiled artifacts, sandboxes and quarantined files are removed (0 disables)
+    #[arg(long, env = "GC_INTERVAL_SECS", default_value = "3600")]
+    gc_interval_secs: u64,
+
+    /// How long unreferenced data is kept on disk before garbage collection removes it
+    #[arg(long, env = "GC_RETENTION_SECS", default_value = "604800")]
+    gc_retention_secs: u64,
+
+    /// Delete a user's request-signing key so they can issue a new one, then exit
+    #[arg(long, value_name = "USERNAME")]
+    reset_signing_key: Option<String>,
+
+    /// Turn off a user's two-factor authentication, e.g. after they lost their
+    /// authenticator, then exit
+    #[arg(long, value_name = "USERNAME")]
+    reset_two_factor: Option<String>,
+
+    /// Re-encrypt function artifacts and sensitive metadata under the current
+    /// encryption key, then exit
+    #[arg(long)]
+    rotate_encryption_key: bool,
+
+    /// Check functions, metadata, project lists and metrics for inconsistencies, then exit
+    #[arg(long)]
+    fsck: bool,
+
+    /// Repair the inconsistencies found by --fsck
+    #[arg(long, requires = "fsck")]
+    repair: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Manage the DNS records the server relies on
+    #[command(subcommand)]
+    Dns(DnsCommand),
+    /// Rebuild every published component from its upload with the current
+    /// pre-initializer and engine settings, then exit
+    Reoptimize,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum DnsCommand {
+    /// Check the apex, wildcard and custom-domain records, then exit
+    Verify {
+        /// Create missing records for NODE_ADDRESS and remove leftover ACME challenges
+        #[arg(long)]
+        fix: bool,
+
+        /// Custom domain expected to resolve to this deployment (repeatable)
+        #[arg(long = "custom-domain")]
+        custom_domains: Vec<String>,
+    },
 }
 
 #[derive(Clone)]
 struct AppState {
     server: Arc<FaastaServer>,
+    idempotency: Arc<IdempotencyCache>,
+    persisted_queries: Arc<PersistedQueries>,
+    apex_files: Arc<ApexFiles>,
+    error_pages: Arc<ErrorPages>,
+    health: Arc<NodeHealth>,
+    fallback_url: Option<HeaderValue>,
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
-    rustls::crypto::ring::default_provider()
-        .install_default()
-        .expect("Failed to install rustls crypto provider");
+fn parse_cpu_weight(value: &str) -> Result<(String, u32), String> {
+    let (owner, weight) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected OWNER=WEIGHT, got '{value}'"))?;
+    let weight = weight
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|weight| *weight > 0)
+        .ok_or_else(|| format!("weight of '{owner}' must be a positive integer"))?;
+    Ok((owner.trim().to_string(), weight))
+}
 
+fn main() -> Result<()> {
     let _ = dotenvy::dotenv();
     tracing_subscriber::fmt().with_max_level(Level::INFO).init();
 
     let args = Args::parse();
+    args.crypto_provider
+        .install()
+        .context("failed to install the rustls crypto provider")?;
+
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    runtime.enable_all();
+    if let Some(worker_threads) = args.worker_threads {
+        runtime.worker_threads(worker_threads);
+    }
+    runtime
+        .build()
+        .context("failed to start tokio runtime")?
+        .block_on(run(args))
+}
+
+async fn run(args: Args) -> Result<()> {
+    if let Some(Command::Dns(DnsCommand::Verify {
+        fix,
+        custom_domains,
+    })) = &args.command
+    {
+        return verify_dns(&args, *fix, custom_domains).await;
+    }
 
     std::fs::create_dir_all(&args.db_path)
         .with_context(|| format!("failed to create db directory at {:?}", args.db_path))?;
@@ -108,10 +452,20 @@ async fn main() -> Result<()> {
     })?;
     std::fs::create_dir_all(&args.certs_dir)
         .with_context(|| format!("failed to create cert directory at {:?}", args.certs_dir))?;
+    if let Some(command) = &args.preinit_command {
+        preinit::configure(command).context("invalid PREINIT_COMMAND")?;
+    }
+    encryption::configure_from_env().context("invalid at-rest encryption settings")?;
+    chaos::configure_from_env().context("invalid chaos settings")?;
+    wasi_server::allow_nested_subdomains(args.allow_nested_subdomains);
+    if let Some(region) = &args.region {
+        platform::set_region(region.clone());
+    }
 
     if args.auto_cert {
         let cert_manager = Arc::new(CertManager::new(
             args.base_domain.clone(),
+            args.dns_provider,
             args.certs_dir.clone(),
             args.tls_cert_path.clone(),
             args.tls_key_path.clone(),
@@ -123,8 +477,92 @@ async fn main() -> Result<()> {
         cert_manager.spawn_periodic_renewal();
     }
 
-    let metadata_db = Arc::new(Database::open(&args.db_path).context("failed to open sqlite db")?);
-    let invoker = FunctionInvoker::wasm().await?;
+    if let Some(target) = args.migrate_metadata_to {
+        return migrate_metadata(args.metadata_backend, target, &args.db_path);
+    }
+
+    let metadata_db =
+        db::open(args.metadata_backend, &args.db_path).context("failed to open metadata db")?;
+    if let Some(username) = &args.reset_signing_key {
+        metadata_db.delete(Tree::SigningKeys, username)?;
+        println!("Removed the request-signing key for '{username}'");
+        return Ok(());
+    }
+    if let Some(username) = &args.reset_two_factor {
+        metadata_db.delete(Tree::TwoFactor, username)?;
+        println!("Turned off two-factor authentication for '{username}'");
+        return Ok(());
+    }
+    if args.rotate_encryption_key {
+        let report = encryption::rotate(metadata_db.as_ref(), &args.functions_path)?;
+        println!(
+            "Re-encrypted {} artifact(s) and {} metadata record(s)",
+            report.artifacts, report.records
+        );
+        return Ok(());
+    }
+    let migrated =
+        db::record::migrate(metadata_db.as_ref()).context("failed to migrate function records")?;
+    if migrated > 0 {
+        info!(
+            "rewrote {migrated} function record(s) in format version {}",
+            db::record::FORMAT_VERSION
+        );
+    }
+    let pool = PoolConfig {
+        component_instances: args.pool_instances,
+        core_instances: args.pool_core_instances,
+        memories: args.pool_memories,
+        max_memory_bytes: args.pool_max_memory_mb * 1024 * 1024,
+    };
+    pool.validate().context("invalid instance pool settings")?;
+    info!(
+        "instance pool: {} instances, {} memories of up to {} MiB ({} MiB if all are full)",
+        pool.component_instances,
+        pool.memories,
+        args.pool_max_memory_mb,
+        pool.worst_case_memory_bytes() / (1024 * 1024)
+    );
+    let cpu_budget = (args.cpu_budget_ms > 0).then(|| Duration::from_millis(args.cpu_budget_ms));
+    let cpu = CpuScheduler::new(args.cpu_weights.iter().cloned().collect(), cpu_budget);
+    let mut invoker = FunctionInvoker::wasm(pool, cpu).await?;
+    if !args.dedicated_functions.is_empty() {
+        let dedicated_pool = PoolConfig {
+            component_instances: args.dedicated_pool_instances,
+            core_instances: args.dedicated_pool_instances.saturating_mul(10),
+            memories: args.dedicated_pool_instances,
+            max_memory_bytes: pool.max_memory_bytes,
+        };
+        dedicated_pool
+            .validate()
+            .context("invalid dedicated pool settings")?;
+        let tier = DedicatedTier::new(
+            args.dedicated_functions.iter().cloned().collect(),
+            dedicated_pool,
+            args.dedicated_worker_threads,
+            CpuScheduler::new(args.cpu_weights.iter().cloned().collect(), cpu_budget),
+            AdmissionController::new(
+                dedicated_pool.component_instances as usize,
+                args.admission_watermark_percent,
+                0,
+                args.admission_retry_after_secs,
+            ),
+        )
+        .await?;
+        info!(
+            "dedicated tier: {} function(s), {} instances, {} threads",
+            args.dedicated_functions.len(),
+            dedicated_pool.component_instances,
+            args.dedicated_worker_threads
+        );
+        invoker = invoker.with_dedicated(tier);
+    }
+    let admission = AdmissionController::new(
+        pool.component_instances.min(pool.memories) as usize,
+        args.admission_watermark_percent,
+        args.admission_max_queue_depth,
+        args.admission_retry_after_secs,
+    );
 
     let server = Arc::new(
         FaastaServer::new(
@@ -132,48 +570,308 @@ async fn main() -> Result<()> {
             args.base_domain.clone(),
             args.functions_path.clone(),
             invoker,
+            admission,
+            ResponseCache::new(args.response_cache_entries, args.response_cache_mb << 20),
+            Duration::from_secs(args.async_invoke_timeout_secs),
         )
         .await?,
     );
     SERVER
         .set(server.clone())
         .map_err(|_| anyhow::anyhow!("server already initialised"))?;
+    let migrated = metrics::migrate_legacy_keys(&server)
+        .context("failed to move metrics to owner-scoped keys")?;
+    if migrated > 0 {
+        info!("moved {migrated} metrics record(s) to owner-scoped keys");
+    }
+
+    if let Some(Command::Reoptimize) = &args.command {
+        let report = reoptimize::run(&server).await?;
+        for (name, err) in &report.failed {
+            println!("{name}: {err}");
+        }
+        println!(
+            "Rebuilt {} function(s), {} failed",
+            report.rebuilt,
+            report.failed.len()
+        );
+        return Ok(());
+    }
+
+    if args.fsck {
+        let issues = fsck::run(&server, args.repair).await?;
+        for issue in &issues {
+            println!("{issue}");
+        }
+        println!("{} issue(s) found", issues.len());
+        return Ok(());
+    }
+    // Report-only at startup; repairs are left to an explicit `--fsck --repair`.
+    if let Err(err) = fsck::run(&server, false).await {
+        error!("startup consistency check failed: {err:#}");
+    }
 
     spawn_periodic_flush(60);
+    function_logs::persist_to(metrics::METRICS_DB.clone());
+    function_logs::spawn_periodic_flush(Duration::from_secs(5));
+    gc::spawn_periodic(
+        server.clone(),
+        Duration::from_secs(args.gc_interval_secs),
+        Duration::from_secs(args.gc_retention_secs),
+    );
+    keep_warm::spawn(server.clone());
+    server.jobs.resume(server.clone());
+
+    let alerts = Arc::new(Alerts::from_env());
+    if args.ct_monitor {
+        CtMonitor {
+            domain: args.base_domain.clone(),
+            expected_issuers: args.ct_expected_issuers.clone(),
+            cert_path: args.tls_cert_path.clone(),
+            state_path: args.certs_dir.join("ct_seen.json"),
+            alerts: alerts.clone(),
+        }
+        .spawn(Duration::from_secs(args.ct_check_interval_secs));
+    }
+
+    let health = Arc::new(NodeHealth::default());
+    if args.dns_failover {
+        let hooks = FailoverHooks {
+            dns: DnsManager::from_env(args.dns_provider, args.base_domain.clone())
+                .context("failed to set up DNS failover")?,
+            records: args.dns_failover_records.clone(),
+            address: args
+                .node_address
+                .context("NODE_ADDRESS is required for DNS failover")?,
+            ttl: args.dns_failover_ttl,
+        };
+        hooks.spawn(server.clone(), health.clone());
+    }
+    let shutdown = axum_server::Handle::new();
+    tokio::spawn(drain_on_signal(
+        health.clone(),
+        shutdown.clone(),
+        Duration::from_secs(args.drain_grace_secs),
+    ));
+
+    let idempotency = Arc::new(IdempotencyCache::new(Duration::from_secs(
+        args.idempotency_window_secs,
+    )));
+    idempotency.clone().spawn_periodic_sweep();
 
+    let apex_files = Arc::new(ApexFiles::load(
+        args.robots_txt_path.as_deref(),
+        args.favicon_path.as_deref(),
+        args.serve_website,
+    )?);
+
+    let error_pages = Arc::new(ErrorPages::load(args.error_pages_dir.as_deref())?);
+
+    let fallback_url = args
+        .fallback_url
+        .as_deref()
+        .map(HeaderValue::from_str)
+        .transpose()
+        .context("invalid FALLBACK_URL")?;
     let app_state = AppState {
         server: server.clone(),
+        idempotency,
+        persisted_queries: Arc::new(PersistedQueries::new(args.graphql_persisted_queries)),
+        apex_files,
+        error_pages,
+        health,
+        fallback_url,
     };
 
-    let router = Router::new()
+    let mut management_routes = Router::new()
+        .route("/v1/publish/{function_name}", post(publish_handler))
+        .route("/v1/warm/{function_name}", post(warm_handler));
+    if !args.disable_rpc {
+        management_routes = management_routes.route(&args.rpc_path, post(rpc_handler));
+    }
+    if args.hsts_preload && args.hsts_max_age < 31_536_000 {
+        warn!("HSTS_PRELOAD is set but preload lists require HSTS_MAX_AGE of at least one year");
+    }
+    let hsts = security_headers::hsts_header(args.hsts_max_age, args.hsts_preload);
+    let header_policy = Arc::new(HeaderPolicy::new(
+        hsts.clone(),
+        args.cors_allow_origins.clone(),
+    ));
+    let management_routes = management_routes
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(args.management_allow.clone()),
+            management_allowlist,
+        ))
+        .layer(middleware::from_fn_with_state(
+            header_policy.clone(),
+            security_headers::apply,
+        ));
+    let layers = ServiceBuilder::new()
+        .layer(middleware::from_fn(error_body::assign_request_id))
+        .layer(CatchPanicLayer::custom(|_| {
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INTERNAL,
+                "Internal server error",
+            )
+        }))
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &Request<Body>| {
+                tracing::debug_span!(
+                    "request",
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    request_id = %error_body::current_request_id().unwrap_or_default(),
+                )
+            }),
+        );
+
+    let mut public_routes = Router::new()
         .route("/healthz", get(health_handler))
+        .route("/readyz", get(ready_handler))
         .route("/v1/metrics", get(metrics_handler))
-        .route(&args.rpc_path, post(rpc_handler))
-        .route("/v1/publish/{function_name}", post(publish_handler))
+        .route("/v1/stats/{function_name}", get(public_stats_handler))
+        .route("/v1/invoke-async/{*target}", post(invoke_async_handler))
+        .route("/v1/batch", post(batch_handler))
+        .layer(middleware::from_fn_with_state(
+            header_policy,
+            security_headers::apply,
+        ));
+    let management_listen_addr = args.management_listen_addr.or_else(|| {
+        args.client_ca_path
+            .is_some()
+            .then(|| SocketAddr::from(([0, 0, 0, 0], 8443)))
+    });
+    let tls_policy = TlsPolicy {
+        tls13_only: args.tls13_only,
+        cipher_suites: args.tls_cipher_suites.clone(),
+        alpn_protocols: args.tls_alpn.clone(),
+        ticket_rotation: (args.tls_ticket_rotation_secs > 0)
+            .then(|| Duration::from_secs(args.tls_ticket_rotation_secs)),
+    };
+    let certificate = ServerCertificate::load(&args.tls_cert_path, &args.tls_key_path)
+        .context("failed to load tls assets")?;
+    if args.ocsp_stapling {
+        cert_manager::spawn_ocsp_stapling(certificate.clone());
+    }
+    if args.management_plaintext {
+        match management_listen_addr {
+            None => anyhow::bail!("MANAGEMENT_PLAINTEXT needs MANAGEMENT_LISTEN_ADDR"),
+            Some(addr) if !addr.ip().is_loopback() => {
+                anyhow::bail!("MANAGEMENT_PLAINTEXT is only allowed on a loopback address")
+            }
+            Some(_) if args.client_ca_path.is_some() => {
+                anyhow::bail!("MANAGEMENT_PLAINTEXT cannot be combined with CLIENT_CA")
+            }
+            Some(_) => {}
+        }
+    }
+    let management = match management_listen_addr {
+        Some(listen_addr) => {
+            let tls_config = match &args.client_ca_path {
+                _ if args.management_plaintext => None,
+                Some(client_ca_path) => Some(mtls::server_config(
+                    &tls_policy,
+                    certificate.clone(),
+                    client_ca_path,
+                )?),
+                None => Some(tls_policy.server_config(certificate.clone(), None)?),
+            };
+            let tls_config = tls_config.map(RustlsConfig::from_config);
+            let router = management_routes
+                .with_state(app_state.clone())
+                .layer(layers.clone());
+            Some((listen_addr, tls_config, router))
+        }
+        None => {
+            public_routes = public_routes.merge(management_routes);
+            None
+        }
+    };
+    let router = public_routes
         .fallback(function_dispatch)
         .with_state(app_state)
-        .layer(
-            ServiceBuilder::new()
-                .layer(CatchPanicLayer::new())
-                .layer(TraceLayer::new_for_http()),
-        );
+        .layer(layers);
 
-    let rustls_config =
-        RustlsConfig::from_pem_file(args.tls_cert_path.clone(), args.tls_key_path.clone())
-            .await
-            .context("failed to load tls assets")?;
+    let rustls_config = RustlsConfig::from_config(tls_policy.server_config(certificate, None)?);
+
+    let redirect = RedirectTarget {
+        base_domain: args.base_domain.to_ascii_lowercase(),
+        https_port: args.redirect_https_port,
+        hsts,
+    };
+    tokio::spawn(run_http_redirect(args.http_listen_addr, redirect));
 
-    let redirect_domain = args.base_domain.clone();
-    tokio::spawn(run_http_redirect(args.http_listen_addr, redirect_domain));
+    let public = async {
+        info!("HTTPS server listening on {}", args.listen_addr);
+        axum_server::bind_rustls(args.listen_addr, rustls_config)
+            .handle(shutdown.clone())
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .context("https server error")
+    };
+    let Some((management_addr, management_tls, management_router)) = management else {
+        return public.await;
+    };
+    let management = async {
+        info!(
+            "management server listening on {management_addr}{}",
+            if args.client_ca_path.is_some() {
+                " (client certificates required)"
+            } else if management_tls.is_none() {
+                " (plain HTTP)"
+            } else {
+                ""
+            }
+        );
+        let service = management_router.into_make_service_with_connect_info::<SocketAddr>();
+        match management_tls {
+            Some(management_tls) => {
+                axum_server::bind_rustls(management_addr, management_tls)
+                    .handle(shutdown.clone())
+                    .serve(service)
+                    .await
+            }
+            None => {
+                axum_server::bind(management_addr)
+                    .handle(shutdown.clone())
+                    .serve(service)
+                    .await
+            }
+        }
+        .context("management server error")
+    };
+    tokio::try_join!(public, management).map(|_| ())
+}
 
-    info!("HTTPS server listening on {}", args.listen_addr);
-    axum_server::bind_rustls(args.listen_addr, rustls_config)
-        .serve(router.into_make_service())
+async fn verify_dns(args: &Args, fix: bool, custom_domains: &[String]) -> Result<()> {
+    if fix && args.node_address.is_none() {
+        anyhow::bail!("--fix needs NODE_ADDRESS to know what the records should point at");
+    }
+    let dns = DnsManager::from_env(args.dns_provider, args.base_domain.clone())?;
+    let checks = dns
+        .verify(args.node_address, custom_domains, fix)
         .await
-        .context("https server error")
+        .context("failed to verify DNS records")?;
+    for check in &checks {
+        println!("{check}");
+    }
+    let problems = checks.iter().filter(|check| check.is_problem()).count();
+    if problems > 0 {
+        anyhow::bail!("{problems} DNS problem(s) found");
+    }
+    println!("DNS records for {} look good", args.base_domain);
+    Ok(())
+}
+
+#[derive(Clone)]
+struct RedirectTarget {
+    base_domain: String,
+    https_port: u16,
+    hsts: Option<HeaderValue>,
 }
 
-async fn run_http_redirect(addr: SocketAddr, target_domain: String) {
+async fn run_http_redirect(addr: SocketAddr, target: RedirectTarget) {
     let listener = match TcpListener::bind(addr).await {
         Ok(listener) => listener,
         Err(err) => {
@@ -182,25 +880,44 @@ async fn run_http_redirect(addr: SocketAddr, target_domain: String) {
         }
     };
 
-    let app = Router::new()
-        .fallback(redirect_handler)
-        .with_state(target_domain.clone());
+    let app = Router::new().fallback(redirect_handler).with_state(target);
 
     if let Err(err) = axum::serve(listener, app.into_make_service()).await {
         error!("http redirect server exited with error: {err}");
     }
 }
 
+/// Redirect to the same host and URI over HTTPS. Hosts outside the base domain are
+/// sent to the base domain, so the redirect cannot be aimed at another site.
 async fn redirect_handler(
-    State(target_domain): State<String>,
+    State(target): State<RedirectTarget>,
+    headers: HeaderMap,
     OriginalUri(uri): OriginalUri,
 ) -> impl IntoResponse {
-    let location = format!("https://{}{}", target_domain, uri.path());
-    Response::builder()
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(|host| host.split(':').next().unwrap_or(host).to_ascii_lowercase())
+        .filter(|host| {
+            host == &target.base_domain
+                || host
+                    .strip_suffix(&target.base_domain)
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+        .unwrap_or_else(|| target.base_domain.clone());
+    let port = match target.https_port {
+        443 => String::new(),
+        port => format!(":{port}"),
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+
+    let mut response = Response::builder()
         .status(StatusCode::MOVED_PERMANENTLY)
-        .header(header::LOCATION, location)
-        .body(Body::empty())
-        .unwrap()
+        .header(header::LOCATION, format!("https://{host}{port}{path}"));
+    if let Some(hsts) = target.hsts {
+        response = response.header(header::STRICT_TRANSPORT_SECURITY, hsts);
+    }
+    response.body(Body::empty()).unwrap()
 }
 
 async fn health_handler() -> impl IntoResponse {
@@ -210,16 +927,139 @@ async fn health_handler() -> impl IntoResponse {
         .unwrap()
 }
 
-async fn metrics_handler() -> impl IntoResponse {
-    json_response(StatusCode::OK, get_metrics())
+/// 200 while the node should receive traffic, 503 while it drains or is unhealthy.
+async fn ready_handler(State(state): State<AppState>) -> Response<Body> {
+    match state.health.check(&state.server) {
+        Ok(()) => Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::from("ready"))
+            .unwrap(),
+        Err(err) => error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            error_code::NOT_READY,
+            err.to_string(),
+        ),
+    }
+}
+
+/// On SIGTERM or Ctrl-C, drain the node, keep serving for `grace` so load balancers and
+/// resolvers move clients elsewhere, then shut down gracefully.
+async fn drain_on_signal(
+    health: Arc<NodeHealth>,
+    shutdown: axum_server::Handle<SocketAddr>,
+    grace: Duration,
+) {
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!("failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = terminate => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    health.drain();
+    info!("shutting down in {}s", grace.as_secs());
+    tokio::time::sleep(grace).await;
+    shutdown.graceful_shutdown(Some(grace));
+}
+
+/// Node-wide totals, with per-function metrics only for functions whose owner made
+/// their stats public. Owners see all of theirs through `get_metrics`.
+async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let mut metrics = get_metrics(None);
+    metrics.function_metrics.retain(|function| {
+        state
+            .server
+            .function_configs
+            .get(&function.function_name)
+            .public_stats
+    });
+    json_response(StatusCode::OK, metrics)
+}
+
+/// Usage statistics of a function whose owner made them public.
+async fn public_stats_handler(
+    State(state): State<AppState>,
+    Path(function_name): Path<String>,
+) -> Response<Body> {
+    let Some(function_name) = sanitize_function_name(&function_name) else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::INVALID_FUNCTION_NAME,
+            "Invalid function name",
+        );
+    };
+    if !state.server.function_exists(&function_name)
+        || !state
+            .server
+            .function_configs
+            .get(&function_name)
+            .public_stats
+    {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            error_code::FUNCTION_NOT_FOUND,
+            "No public stats for this function",
+        );
+    }
+    let mut response = json_response(
+        StatusCode::OK,
+        public_stats::snapshot(&state.server.function_key(&function_name)),
+    );
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=60"),
+    );
+    response
+}
+
+/// Reject management requests from sources outside `--management-allow` (if configured).
+async fn management_allowlist(
+    State(allow): State<Arc<Vec<Cidr>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    if allow.is_empty() || allow.iter().any(|network| network.contains(peer.ip())) {
+        next.run(request).await
+    } else {
+        error_response(
+            StatusCode::FORBIDDEN,
+            error_code::SOURCE_NOT_ALLOWED,
+            "Management access is not allowed from this address",
+        )
+    }
 }
 
 async fn rpc_handler(request: Request<Body>) -> impl IntoResponse {
+    // Reject clients whose calls this build can't decode before trying to, so they get
+    // an error that says what to upgrade instead of a decode failure
+    let client = protocol::client_version(request.uri().query());
+    if let Err(err) = protocol::check_client(client) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::INCOMPATIBLE_CLIENT,
+            err.to_string(),
+        );
+    }
+
     let body_bytes = match to_bytes(request.into_body(), usize::MAX).await {
         Ok(bytes) => bytes,
         Err(err) => {
             error!("failed to read RPC body: {err}");
-            return error_response(StatusCode::BAD_REQUEST, "Failed to read request body");
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                error_code::BAD_REQUEST,
+                "Failed to read request body",
+            );
         }
     };
 
@@ -227,7 +1067,11 @@ async fn rpc_handler(request: Request<Body>) -> impl IntoResponse {
         Ok(service) => RpcRequestServiceWrapper(service),
         Err(err) => {
             error!("failed to create RPC service: {err}");
-            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INTERNAL,
+                "Internal server error",
+            );
         }
     };
 
@@ -239,7 +1083,11 @@ async fn rpc_handler(request: Request<Body>) -> impl IntoResponse {
         }
         Err(err) => {
             error!("RPC dispatch failed: {err}");
-            error_response(StatusCode::BAD_REQUEST, "Invalid RPC request")
+            error_response(
+                StatusCode::BAD_REQUEST,
+                error_code::BAD_REQUEST,
+                "Invalid RPC request",
+            )
         }
     }
 }
@@ -249,36 +1097,47 @@ async fn publish_handler(
     request: Request<Body>,
 ) -> impl IntoResponse {
     let Some(sanitized_name) = sanitize_function_name(&function_name) else {
-        return error_response(StatusCode::BAD_REQUEST, "Invalid function name");
-    };
-
-    let token_header = match request.headers().get(header::AUTHORIZATION) {
-        Some(value) => value,
-        None => return error_response(StatusCode::UNAUTHORIZED, "Missing Authorization header"),
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::INVALID_FUNCTION_NAME,
+            "Invalid function name",
+        );
     };
 
-    let token = match token_header.to_str() {
-        Ok(token) => token.trim().trim_start_matches("Bearer ").to_string(),
-        Err(_) => return error_response(StatusCode::UNAUTHORIZED, "Invalid Authorization header"),
+    let token = match bearer_token(request.headers()) {
+        Ok(token) => token,
+        Err(response) => return *response,
     };
 
     let body_bytes = match to_bytes(request.into_body(), usize::MAX).await {
         Ok(bytes) => bytes,
         Err(err) => {
             error!("failed to read publish body: {err}");
-            return error_response(StatusCode::BAD_REQUEST, "Failed to read request body");
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                error_code::BAD_REQUEST,
+                "Failed to read request body",
+            );
         }
     };
 
     if body_bytes.is_empty() {
-        return error_response(StatusCode::BAD_REQUEST, "Empty artifact body");
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::BAD_REQUEST,
+            "Empty artifact body",
+        );
     }
 
     let service = match create_service() {
         Ok(service) => service,
         Err(err) => {
             error!("failed to create publish service: {err}");
-            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INTERNAL,
+                "Internal server error",
+            );
         }
     };
 
@@ -293,21 +1152,363 @@ async fn publish_handler(
                 "message": message,
             }),
         ),
+        Err(err) => function_error_response(&err),
+    }
+}
+
+async fn warm_handler(
+    Path(function_name): Path<String>,
+    request_headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(sanitized_name) = sanitize_function_name(&function_name) else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::INVALID_FUNCTION_NAME,
+            "Invalid function name",
+        );
+    };
+    let token = match bearer_token(&request_headers) {
+        Ok(token) => token,
+        Err(response) => return *response,
+    };
+
+    let service = match create_service() {
+        Ok(service) => service,
         Err(err) => {
-            let status = map_function_error(&err);
-            json_response(
-                status,
+            error!("failed to create warm service: {err}");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INTERNAL,
+                "Internal server error",
+            );
+        }
+    };
+
+    match service.warm_impl(sanitized_name, token).await {
+        Ok(elapsed_ms) => json_response(
+            StatusCode::OK,
+            json!({
+                "success": true,
+                "elapsed_ms": elapsed_ms,
+            }),
+        ),
+        Err(err) => function_error_response(&err),
+    }
+}
+
+/// Accept an asynchronous invocation of `NAME/path` and answer with its job id.
+async fn invoke_async_handler(
+    State(state): State<AppState>,
+    Path(target): Path<String>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response<Body> {
+    let (function_name, path) = target.split_once('/').unwrap_or((&target, ""));
+    let Some(function_name) = sanitize_function_name(function_name) else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::INVALID_FUNCTION_NAME,
+            "Invalid function name",
+        );
+    };
+    if !state.server.function_exists(&function_name) {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            error_code::FUNCTION_NOT_FOUND,
+            "Function not found",
+        );
+    }
+    if state.server.is_static_site(&function_name) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::BAD_REQUEST,
+            "Static sites cannot be invoked asynchronously",
+        );
+    }
+    if state
+        .server
+        .function_configs
+        .get(&function_name)
+        .maintenance
+        .is_some()
+    {
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            error_code::MAINTENANCE,
+            "This function is temporarily down for maintenance",
+        );
+    }
+    let callback = match headers.get(jobs::CALLBACK_HEADER).map(HeaderValue::to_str) {
+        None => None,
+        Some(Ok(value)) => match jobs::parse_callback(value, &state.server.base_domain) {
+            Ok(callback) => Some(callback),
+            Err(message) => {
+                return error_response(StatusCode::BAD_REQUEST, error_code::INVALID_INPUT, message);
+            }
+        },
+        Some(Err(_)) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                error_code::INVALID_INPUT,
+                "invalid callback URL",
+            );
+        }
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("/{path}?{query}"),
+        None => format!("/{path}"),
+    };
+    let Ok(function_uri) = path_and_query.parse::<Uri>() else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::BAD_REQUEST,
+            "Invalid path",
+        );
+    };
+
+    if body.len() > jobs::MAX_REQUEST_BYTES {
+        return error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            error_code::PAYLOAD_TOO_LARGE,
+            format!(
+                "Asynchronous invocations take at most {} KiB of body",
+                jobs::MAX_REQUEST_BYTES / 1024
+            ),
+        );
+    }
+
+    let request = JobRequest {
+        function_name: function_name.clone(),
+        method: Method::POST,
+        uri: function_uri,
+        headers,
+        body,
+        callback,
+    };
+    match state.server.jobs.submit(state.server.clone(), request) {
+        Ok(job_id) => {
+            let mut response = json_response(
+                StatusCode::ACCEPTED,
                 json!({
-                    "success": false,
-                    "error": err.to_string(),
+                    "job_id": job_id,
+                    "function": function_name,
                 }),
+            );
+            if let Ok(value) = HeaderValue::from_str(&job_id) {
+                response.headers_mut().insert(jobs::JOB_ID_HEADER, value);
+            }
+            response
+        }
+        Err(err) if err.is::<TooManyJobs>() => error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            error_code::TOO_MANY_JOBS,
+            "Too many asynchronous jobs, try again later",
+        ),
+        Err(err) => {
+            error!("failed to store job for '{function_name}': {err:#}");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INTERNAL,
+                "Failed to queue the invocation",
             )
         }
     }
 }
 
+/// Run a batch of invocations of the caller's functions concurrently.
+async fn batch_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response<Body> {
+    let token = match bearer_token(&headers) {
+        Ok(token) => token,
+        Err(response) => return *response,
+    };
+    if body.len() > batch::MAX_BODY_BYTES {
+        return error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            error_code::PAYLOAD_TOO_LARGE,
+            format!(
+                "Batch bodies are limited to {} KiB",
+                batch::MAX_BODY_BYTES / 1024
+            ),
+        );
+    }
+    let batch: BatchRequest = match serde_json::from_slice(&body) {
+        Ok(batch) => batch,
+        Err(err) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                error_code::BAD_REQUEST,
+                format!("Invalid batch: {err}"),
+            );
+        }
+    };
+    if batch.requests.len() > batch::MAX_REQUESTS {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            error_code::INVALID_INPUT,
+            format!("A batch holds at most {} requests", batch::MAX_REQUESTS),
+        );
+    }
+
+    let service = match create_service() {
+        Ok(service) => service,
+        Err(err) => {
+            error!("failed to create batch service: {err}");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INTERNAL,
+                "Internal server error",
+            );
+        }
+    };
+    // Every named function must be the caller's before any of them runs
+    let mut functions = Vec::with_capacity(batch.requests.len());
+    for request in &batch.requests {
+        let Some(function_name) = sanitize_function_name(&request.function) else {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                error_code::INVALID_FUNCTION_NAME,
+                format!("Invalid function name '{}'", request.function),
+            );
+        };
+        if !functions.contains(&function_name)
+            && let Err(err) = service.authorize_owner(&function_name, &token).await
+        {
+            return function_error_response(&err);
+        }
+        functions.push(function_name);
+    }
+
+    let responses = futures_util::future::join_all(
+        batch
+            .requests
+            .iter()
+            .zip(&functions)
+            .map(|(request, function_name)| invoke_batched(&state, function_name, request)),
+    )
+    .await;
+    json_response(StatusCode::OK, BatchResponse { responses })
+}
+
+async fn invoke_batched(
+    state: &AppState,
+    function_name: &str,
+    request: &SubRequest,
+) -> SubResponse {
+    let fail = |status: StatusCode, code: &str, message: String| {
+        SubResponse::error(status, &error_body::body(code, message))
+    };
+    if !state.server.function_exists(function_name) {
+        return fail(
+            StatusCode::NOT_FOUND,
+            error_code::FUNCTION_NOT_FOUND,
+            format!("Function '{function_name}' not found"),
+        );
+    }
+    if state.server.is_static_site(function_name) {
+        return fail(
+            StatusCode::BAD_REQUEST,
+            error_code::BAD_REQUEST,
+            format!("'{function_name}' is a static site"),
+        );
+    }
+    if state
+        .server
+        .function_configs
+        .get(function_name)
+        .maintenance
+        .is_some()
+    {
+        return fail(
+            StatusCode::SERVICE_UNAVAILABLE,
+            error_code::MAINTENANCE,
+            format!("'{function_name}' is down for maintenance"),
+        );
+    }
+    let invocation = match request.invocation() {
+        Ok(invocation) => invocation,
+        Err(message) => return fail(StatusCode::BAD_REQUEST, error_code::BAD_REQUEST, message),
+    };
+    let response = invoke_function(
+        state,
+        function_name,
+        invocation.method,
+        invocation.uri,
+        invocation.headers,
+        invocation.body,
+    )
+    .await;
+    SubResponse::collect(response).await
+}
+
+/// Allow a request to the inspector only from the function's owner.
+async fn authorize_inspector(
+    function_name: &str,
+    headers: &HeaderMap,
+) -> Result<(), Response<Body>> {
+    let token = bearer_token(headers).map_err(|response| *response)?;
+    let service = create_service().map_err(|err| {
+        error!("failed to create inspect service: {err}");
+        error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            error_code::INTERNAL,
+            "Internal server error",
+        )
+    })?;
+    match service.authorize_owner(function_name, &token).await {
+        Ok(_) => Ok(()),
+        Err(err) => Err(function_error_response(&err)),
+    }
+}
+
+/// GitHub token from an `Authorization: Bearer` header.
+fn bearer_token(headers: &HeaderMap) -> Result<String, Box<Response<Body>>> {
+    let Some(token_header) = headers.get(header::AUTHORIZATION) else {
+        return Err(Box::new(error_response(
+            StatusCode::UNAUTHORIZED,
+            error_code::UNAUTHENTICATED,
+            "Missing Authorization header",
+        )));
+    };
+    match token_header.to_str() {
+        Ok(token) => Ok(token.trim().trim_start_matches("Bearer ").to_string()),
+        Err(_) => Err(Box::new(error_response(
+            StatusCode::UNAUTHORIZED,
+            error_code::UNAUTHENTICATED,
+            "Invalid Authorization header",
+        ))),
+    }
+}
+
+/// Copy the metadata and metrics stores from one backend to another.
+fn migrate_metadata(from: MetadataBackend, to: MetadataBackend, db_path: &FsPath) -> Result<()> {
+    if from == to {
+        anyhow::bail!("metadata is already stored in {from:?}");
+    }
+    for path in [
+        db_path.to_path_buf(),
+        PathBuf::from(metrics::metrics_db_path()),
+    ] {
+        let source = db::open(from, &path)
+            .with_context(|| format!("failed to open {from:?} store at {}", path.display()))?;
+        let destination = db::open(to, &path)
+            .with_context(|| format!("failed to open {to:?} store at {}", path.display()))?;
+        let copied = db::migrate(source.as_ref(), destination.as_ref())?;
+        info!(
+            "migrated {copied} records at {} from {from:?} to {to:?}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
 async fn function_dispatch(
     State(state): State<AppState>,
+    ConnectInfo(client_addr): ConnectInfo<SocketAddr>,
     request: Request<Body>,
 ) -> impl IntoResponse {
     let host_string = request
@@ -320,50 +1521,510 @@ async fn function_dispatch(
     let uri = request.uri().clone();
     let headers: HeaderMap = request.headers().clone();
 
+    if wasi_server::function_name_from_host(host_ref, &state.server.base_domain).is_none()
+        && let Some(response) = state.apex_files.serve(&method, uri.path())
+    {
+        return response;
+    }
+
     let body_bytes = match to_bytes(request.into_body(), usize::MAX).await {
         Ok(bytes) => bytes,
         Err(err) => {
             error!("failed to read request body: {err}");
-            return error_response(StatusCode::BAD_REQUEST, "Failed to read request body");
+            return dispatch_error(
+                &state,
+                headers.get(header::ACCEPT),
+                None,
+                StatusCode::BAD_REQUEST,
+                error_code::BAD_REQUEST,
+                "Failed to read request body",
+            );
         }
     };
 
     let Some(function_name) =
         wasi_server::resolve_function_name(host_ref, uri.path(), &state.server.base_domain)
     else {
-        return error_response(StatusCode::NOT_FOUND, "Function name missing");
+        return no_function(
+            &state,
+            &method,
+            headers.get(header::ACCEPT),
+            "Function name missing",
+        );
     };
 
     let Some(sanitized_function) = sanitize_function_name(&function_name) else {
-        return error_response(StatusCode::BAD_REQUEST, "Invalid function name");
+        return dispatch_error(
+            &state,
+            headers.get(header::ACCEPT),
+            None,
+            StatusCode::BAD_REQUEST,
+            error_code::INVALID_FUNCTION_NAME,
+            "Invalid function name",
+        );
     };
 
     if !state.server.function_exists(&sanitized_function) {
-        return error_response(StatusCode::NOT_FOUND, "Function not found");
+        return no_function(
+            &state,
+            &method,
+            headers.get(header::ACCEPT),
+            "Function not found",
+        );
+    }
+
+    let config = state.server.function_configs.get(&sanitized_function);
+
+    let mount =
+        if wasi_server::function_name_from_host(host_ref, &state.server.base_domain).is_some() {
+            String::new()
+        } else {
+            format!("/{function_name}")
+        };
+
+    if let Some(readme) = &config.readme
+        && matches!(method, Method::GET | Method::HEAD)
+        && routes::path_below_mount(uri.path(), &mount) == about::PATH
+    {
+        return about::render(&sanitized_function, readme, &method);
+    }
+    if config.public_stats
+        && matches!(method, Method::GET | Method::HEAD)
+        && routes::path_below_mount(uri.path(), &mount) == public_stats::PATH
+    {
+        let stats = public_stats::snapshot(&state.server.function_key(&sanitized_function));
+        return public_stats::render(&stats, &method);
     }
 
+    let inspected = inspect::probe(&uri, &mount);
+    if inspected.is_some()
+        && let Err(response) = authorize_inspector(&sanitized_function, &headers).await
+    {
+        return response;
+    }
+    let inspecting = inspected.is_some();
+    let uri = inspected.unwrap_or(uri);
+    let grpc_call = grpc_web::Call::detect(&method, &headers).filter(|_| !inspecting);
+
+    if let Some(maintenance) = config.maintenance.as_ref().filter(|_| !inspecting) {
+        let message = maintenance
+            .message
+            .as_deref()
+            .unwrap_or("This function is temporarily down for maintenance");
+        let mut response = dispatch_error(
+            &state,
+            headers.get(header::ACCEPT),
+            Some(&sanitized_function),
+            StatusCode::SERVICE_UNAVAILABLE,
+            error_code::MAINTENANCE,
+            message,
+        );
+        if let Some(retry_after) = maintenance.retry_after_secs {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        return match &grpc_call {
+            Some(call) => call.wrap_response(response).await,
+            None => response,
+        };
+    }
+
+    let (uri, uri_rewritten) = match routes::apply_routes(&config.routes, &mount, &uri) {
+        RouteOutcome::Continue => (uri, false),
+        RouteOutcome::Rewrite(rewritten) => (rewritten, true),
+        RouteOutcome::Redirect(response) if inspecting => {
+            return inspect::report_redirect(&sanitized_function, &response);
+        }
+        RouteOutcome::Redirect(response) => return response,
+    };
+
+    let selection = config.experiment.as_ref().and_then(|experiment| {
+        traffic::select_variant(experiment, &headers, Some(client_addr.ip()))
+    });
+    let target_function = match &selection {
+        Some(selection) if state.server.function_exists(&selection.variant.function) => {
+            selection.variant.function.as_str()
+        }
+        _ => sanitized_function.as_str(),
+    };
+
+    if inspecting {
+        let target_config = state.server.function_configs.get(target_function);
+        return inspect::report(&inspect::Delivery {
+            function: target_function,
+            variant: selection
+                .as_ref()
+                .map(|selection| selection.variant.name.as_str()),
+            static_site: state.server.is_static_site(target_function),
+            rewritten: uri_rewritten,
+            method: &method,
+            uri: &uri,
+            headers: &headers,
+            body_len: body_bytes.len(),
+            response_headers: &target_config.response_headers,
+        });
+    }
+
+    let mut headers = headers;
+    let (uri, body_bytes) = match &config.graphql {
+        Some(graphql)
+            if grpc_call.is_none()
+                && routes::path_below_mount(
+                    uri.path(),
+                    routes::delivered_mount(&config.routes, &mount),
+                ) == graphql.path =>
+        {
+            match state.persisted_queries.prepare(
+                &sanitized_function,
+                &method,
+                &mut headers,
+                uri,
+                body_bytes,
+            ) {
+                Ok(prepared) => prepared,
+                Err(response) => return *response,
+            }
+        }
+        _ => (uri, body_bytes),
+    };
+
+    let started = Instant::now();
+    let mut response = if state.server.is_static_site(target_function) {
+        let _timer = Timer::new(MetricKey::Function(
+            state.server.function_key(target_function),
+        ));
+        static_site::serve(
+            &state.server.static_site_dir(target_function),
+            &method,
+            routes::path_below_mount(uri.path(), routes::delivered_mount(&config.routes, &mount)),
+            &headers,
+        )
+        .await
+    } else if let Some(call) = grpc_call {
+        let body = match call.unwrap_request(&mut headers, body_bytes) {
+            Ok(body) => body,
+            Err(response) => return *response,
+        };
+        let response = invoke_idempotent(&state, target_function, method, uri, headers, body).await;
+        call.wrap_response(response).await
+    } else if let Some(cache_key) =
+        state
+            .server
+            .response_cache
+            .request_key(target_function, &method, &uri, &headers)
+    {
+        invoke_cached(&state, cache_key, target_function, method, uri, headers).await
+    } else {
+        invoke_idempotent(&state, target_function, method, uri, headers, body_bytes).await
+    };
+
+    if config.server_timing {
+        server_timing::annotate(&mut response);
+    }
+    if let Some(selection) = selection {
+        traffic::record(
+            &MetricKey::Function(state.server.function_key(&sanitized_function)),
+            &selection.variant.name,
+            response.status(),
+            started.elapsed(),
+        );
+        if let Ok(value) = HeaderValue::from_str(&selection.variant.name) {
+            response
+                .headers_mut()
+                .insert(traffic::VARIANT_HEADER, value);
+        }
+        if let Some(cookie) = selection.set_cookie
+            && let Ok(value) = HeaderValue::from_str(&cookie)
+        {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    response
+}
+
+/// Serve a cacheable request from the shared response cache, refreshing stale entries
+/// in the background so the client never waits on revalidation.
+async fn invoke_cached(
+    state: &AppState,
+    cache_key: CacheKey,
+    function_name: &str,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let cache = &state.server.response_cache;
+    match cache.lookup(&cache_key, &headers) {
+        CacheLookup::Fresh(response) => response,
+        CacheLookup::Stale(response) => {
+            let state = state.clone();
+            let function_name = function_name.to_string();
+            tokio::spawn(async move {
+                let refreshed = invoke_function(
+                    &state,
+                    &function_name,
+                    method,
+                    uri,
+                    headers.clone(),
+                    Bytes::new(),
+                )
+                .await;
+                state
+                    .server
+                    .response_cache
+                    .finish_revalidation(cache_key, &headers, refreshed)
+                    .await;
+            });
+            response
+        }
+        CacheLookup::Miss => {
+            let response = invoke_function(
+                state,
+                function_name,
+                method,
+                uri,
+                headers.clone(),
+                Bytes::new(),
+            )
+            .await;
+            cache.store(cache_key, &headers, response).await
+        }
+    }
+}
+
+/// Invoke a function, replaying the cached response for a repeated Idempotency-Key.
+async fn invoke_idempotent(
+    state: &AppState,
+    sanitized_function: &str,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body_bytes: Bytes,
+) -> Response<Body> {
+    let Some(idempotency_key) = state.idempotency.request_key(&method, &headers) else {
+        return invoke_function(state, sanitized_function, method, uri, headers, body_bytes).await;
+    };
+
+    let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    let fingerprint = request_fingerprint(&method, path_and_query, &body_bytes);
+    let claim = match state
+        .idempotency
+        .begin(sanitized_function, &idempotency_key, &fingerprint)
+    {
+        IdempotencyLookup::Proceed(claim) => claim,
+        IdempotencyLookup::Replay(response) => return response,
+        IdempotencyLookup::InProgress => {
+            return dispatch_error(
+                state,
+                headers.get(header::ACCEPT),
+                Some(sanitized_function),
+                StatusCode::CONFLICT,
+                error_code::IDEMPOTENCY_IN_PROGRESS,
+                "A request with this Idempotency-Key is already in progress",
+            );
+        }
+        IdempotencyLookup::Mismatch => {
+            return dispatch_error(
+                state,
+                headers.get(header::ACCEPT),
+                Some(sanitized_function),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                error_code::IDEMPOTENCY_MISMATCH,
+                "Idempotency-Key was already used for a different request",
+            );
+        }
+        IdempotencyLookup::Full => {
+            let mut response = dispatch_error(
+                state,
+                headers.get(header::ACCEPT),
+                Some(sanitized_function),
+                StatusCode::SERVICE_UNAVAILABLE,
+                error_code::AT_CAPACITY,
+                "Too many Idempotency-Keys are in use, try again shortly",
+            );
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+            return response;
+        }
+    };
+
+    // Dropping the claim, on an error or if the client goes away mid-invocation,
+    // releases the key for a retry
+    let accept = headers.get(header::ACCEPT).cloned();
+    let response =
+        invoke_function(state, sanitized_function, method, uri, headers, body_bytes).await;
+    if response.status().is_server_error() {
+        return response;
+    }
+    // Bodies of unknown or excessive size, e.g. streamed ones, are passed on without
+    // being kept for replay
+    let size = http_body::Body::size_hint(response.body()).exact();
+    if size.is_none_or(|size| size > idempotency::MAX_REPLAY_BODY_BYTES as u64) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, idempotency::MAX_REPLAY_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            error!("failed to buffer function response: {err}");
+            return dispatch_error(
+                state,
+                accept.as_ref(),
+                Some(sanitized_function),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INVOCATION_FAILED,
+                "Failed to read function response",
+            );
+        }
+    };
+    claim.complete(CachedResponse::new(
+        parts.status,
+        parts.headers.clone(),
+        body.clone(),
+    ));
+    Response::from_parts(parts, Body::from(body))
+}
+
+async fn invoke_function(
+    state: &AppState,
+    function_name: &str,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response<Body> {
+    let accept = headers.get(header::ACCEPT).cloned();
+    let priority = state.server.function_configs.get(function_name).priority;
+    let admission = state.server.admission_for(function_name);
+    let Some(permit) = admission.try_admit(priority) else {
+        let mut response = dispatch_error(
+            state,
+            accept.as_ref(),
+            Some(function_name),
+            StatusCode::SERVICE_UNAVAILABLE,
+            error_code::AT_CAPACITY,
+            "Server is at capacity, try again shortly",
+        );
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(admission.retry_after_secs()),
+        );
+        return response;
+    };
+
+    state
+        .server
+        .mirror(function_name, &method, &uri, &headers, &body);
+    state.server.preload(function_name);
     match state
         .server
-        .invoke(&sanitized_function, method, uri, headers, body_bytes)
+        .invoke(function_name, method, uri, headers, body)
         .await
     {
-        Ok(response) => response,
+        // A streamed body keeps its instance busy until it ends
+        Ok(response) => hold_until_body_ends(response, permit),
         Err(err) => {
+            if let Some(quarantined) = err.downcast_ref::<Quarantined>() {
+                let mut response = dispatch_error(
+                    state,
+                    accept.as_ref(),
+                    Some(function_name),
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    error_code::ARTIFACT_UNAVAILABLE,
+                    "Function artifact is unavailable, try again later",
+                );
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    HeaderValue::from(quarantined.retry_after.as_secs().max(1)),
+                );
+                return response;
+            }
+            if let Some(&fault) = err.downcast_ref::<Fault>() {
+                warn!("invocation of '{function_name}' failed: {err:#}");
+                let mut response = dispatch_error(
+                    state,
+                    accept.as_ref(),
+                    Some(function_name),
+                    fault.status(),
+                    fault.code(),
+                    fault.message(),
+                );
+                if fault == Fault::PoolExhausted {
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, HeaderValue::from(1));
+                }
+                return response;
+            }
             error!("function invocation failed: {err:?}");
-            error_response(
+            dispatch_error(
+                state,
+                accept.as_ref(),
+                Some(function_name),
                 StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INVOCATION_FAILED,
                 "Function invocation failed",
             )
         }
     }
 }
 
+/// Error response for function traffic, rendered as the function's or the instance's
+/// error page for browsers.
+fn dispatch_error(
+    state: &AppState,
+    accept: Option<&HeaderValue>,
+    function_name: Option<&str>,
+    status: StatusCode,
+    code: &str,
+    message: &str,
+) -> Response<Body> {
+    let config = function_name.map(|name| state.server.function_configs.get(name));
+    let function_pages = config
+        .as_ref()
+        .map(|config| config.error_pages.as_slice())
+        .unwrap_or_default();
+    state
+        .error_pages
+        .render(status, code, message, accept, function_pages)
+}
+
+/// Answer a request that matches no function: browsers are sent to `FALLBACK_URL` if
+/// one is configured, everything else gets a 404.
+fn no_function(
+    state: &AppState,
+    method: &Method,
+    accept: Option<&HeaderValue>,
+    message: &str,
+) -> Response<Body> {
+    if let Some(location) = &state.fallback_url
+        && matches!(*method, Method::GET | Method::HEAD)
+    {
+        return Response::builder()
+            .status(StatusCode::FOUND)
+            .header(header::LOCATION, location.clone())
+            .body(Body::empty())
+            .unwrap();
+    }
+    dispatch_error(
+        state,
+        accept,
+        None,
+        StatusCode::NOT_FOUND,
+        error_code::FUNCTION_NOT_FOUND,
+        message,
+    )
+}
+
 fn map_function_error(error: &FunctionError) -> StatusCode {
     match error {
         FunctionError::AuthError(_) => StatusCode::UNAUTHORIZED,
         FunctionError::NotFound(_) => StatusCode::NOT_FOUND,
         FunctionError::PermissionDenied(_) => StatusCode::FORBIDDEN,
         FunctionError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        FunctionError::Conflict(_) => StatusCode::CONFLICT,
         FunctionError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
@@ -379,16 +2040,17 @@ fn json_response<T: Serialize>(status: StatusCode, value: T) -> Response<Body> {
             error!("failed to encode json response: {err}");
             error_response(
                 StatusCode::INTERNAL_SERVER_ERROR,
+                error_code::INTERNAL,
                 "Failed to encode response",
             )
         }
     }
 }
 
-fn error_response(status: StatusCode, message: impl Into<String>) -> Response<Body> {
-    let payload = json!({
-        "success": false,
-        "error": message.into(),
-    });
-    json_response(status, payload)
+fn error_response(status: StatusCode, code: &str, message: impl Into<String>) -> Response<Body> {
+    error_body::response(status, code, message)
+}
+
+fn function_error_response(error: &FunctionError) -> Response<Body> {
+    error_response(map_function_error(error), error.code(), error.to_string())
 }
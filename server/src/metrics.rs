@@ -1,88 +1,104 @@
 use dashmap::DashMap;
-use faasta_interface::{FunctionMetricsResponse, Metrics};
+use faasta_interface::{DiskUsage, FunctionMetricsResponse, Metrics};
 use once_cell::sync::Lazy;
 use std::path::Path;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::time;
 use tracing::{debug, error, info};
 
-use crate::db::Database;
+use crate::cold_starts;
+use crate::db::{self, MetadataBackend, MetadataStore, MetricRecord, Tree};
+use crate::fsck::{self, Issue};
+use crate::function_key::MetricKey;
+use crate::latency::{self, Hdr};
+use crate::public_stats;
+use crate::publish;
+use crate::server_timing;
+use crate::traffic;
+use crate::wasi_server::{FaastaServer, SERVER};
 
 // Global metrics storage using DashMap for lock-free concurrent access
-pub static FUNCTION_METRICS: Lazy<DashMap<String, FunctionMetric>> = Lazy::new(DashMap::new);
-
-// SQLite database for persistent storage
-pub static METRICS_DB: Lazy<Arc<Database>> = Lazy::new(|| {
-    let db_path = std::env::var("METRICS_DB_PATH").unwrap_or_else(|_| "./data/metrics".to_string());
-    Arc::new(
-        Database::open(std::path::Path::new(&db_path)).expect("Failed to open metrics database"),
-    )
+pub static FUNCTION_METRICS: Lazy<DashMap<MetricKey, FunctionMetric>> = Lazy::new(DashMap::new);
+
+// Persistent metrics storage, using the same backend as the metadata store
+pub static METRICS_DB: Lazy<Arc<dyn MetadataStore>> = Lazy::new(|| {
+    db::open(MetadataBackend::from_env(), Path::new(&metrics_db_path()))
+        .expect("Failed to open metrics database")
 });
 
+pub fn metrics_db_path() -> String {
+    std::env::var("METRICS_DB_PATH").unwrap_or_else(|_| "./data/metrics".to_string())
+}
+
 #[derive(Debug)]
 pub struct FunctionMetric {
-    pub function_name: String,
-    pub total_time: AtomicU64,
-    pub call_count: AtomicU64,
+    pub key: MetricKey,
+    /// Wall times of the calls since the last flush
+    pub latency: Mutex<Hdr>,
     pub last_called: AtomicU64,
+    /// Guest CPU time in microseconds
+    pub cpu_micros: AtomicU64,
 }
 
 // Manual implementation of Clone for FunctionMetric
 impl Clone for FunctionMetric {
     fn clone(&self) -> Self {
         Self {
-            function_name: self.function_name.clone(),
-            total_time: AtomicU64::new(self.total_time.load(Ordering::Relaxed)),
-            call_count: AtomicU64::new(self.call_count.load(Ordering::Relaxed)),
+            key: self.key.clone(),
+            latency: Mutex::new(self.latency()),
             last_called: AtomicU64::new(self.last_called.load(Ordering::Relaxed)),
+            cpu_micros: AtomicU64::new(self.cpu_micros.load(Ordering::Relaxed)),
         }
     }
 }
 
 impl FunctionMetric {
-    pub fn new(function_name: String) -> Self {
+    /// In-memory counters hold calls since the last flush, so they start at zero; the
+    /// persisted totals are added when reading and flushing.
+    pub fn new(key: MetricKey) -> Self {
         // Initialize the last_called timestamp to current time
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or(Duration::from_secs(0))
             .as_millis() as u64;
 
-        // Try to load persisted metrics if they exist
-        let metric = if let Ok(Some((total_time, call_count, last_called))) =
-            METRICS_DB.get_metric(&function_name)
-        {
-            Self {
-                function_name,
-                total_time: AtomicU64::new(total_time),
-                call_count: AtomicU64::new(call_count),
-                last_called: AtomicU64::new(last_called),
-            }
-        } else {
-            Self::default(function_name, now)
-        };
-
-        debug!(
-            "Created or loaded metric for function: {}",
-            metric.function_name
-        );
-        metric
+        debug!("Created metric for function: {}", key);
+        Self::default(key, now)
     }
 
-    fn default(function_name: String, now: u64) -> Self {
+    fn default(key: MetricKey, now: u64) -> Self {
         Self {
-            function_name,
-            total_time: AtomicU64::new(0),
-            call_count: AtomicU64::new(0),
+            key,
+            latency: Mutex::new(latency::new_hdr()),
             last_called: AtomicU64::new(now),
+            cpu_micros: AtomicU64::new(0),
         }
     }
 
-    pub fn record_call(&self, duration_ms: u64) {
+    pub fn latency(&self) -> Hdr {
+        self.latency.lock().expect("latency mutex poisoned").clone()
+    }
+
+    /// Calls since the last flush, as they would be persisted
+    pub fn in_memory(&self) -> MetricRecord {
+        MetricRecord {
+            latency: self.latency(),
+            last_called: self.last_called.load(Ordering::Relaxed),
+            cpu_micros: self.cpu_micros.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn record_call(&self, duration: Duration, cpu: Duration) {
         // Update in-memory metrics
-        let prev_total = self.total_time.fetch_add(duration_ms, Ordering::Relaxed);
-        let prev_count = self.call_count.fetch_add(1, Ordering::Relaxed);
+        let calls = {
+            let mut latency = self.latency.lock().expect("latency mutex poisoned");
+            latency::record_hdr(&mut latency, duration);
+            latency.len()
+        };
+        self.cpu_micros
+            .fetch_add(cpu.as_micros() as u64, Ordering::Relaxed);
 
         // Update last called timestamp (milliseconds since epoch)
         let now = SystemTime::now()
@@ -93,90 +109,107 @@ impl FunctionMetric {
 
         // Log the metrics update with more detailed information
         debug!(
-            "Recorded metrics for function '{}': duration={}ms, prev_total={}ms, new_total={}ms, prev_calls={}, new_calls={}",
-            self.function_name,
-            duration_ms,
-            prev_total,
-            prev_total + duration_ms,
-            prev_count,
-            prev_count + 1
+            "Recorded metrics for function '{}': duration={}us, cpu={}us, calls since flush={}",
+            self.key,
+            duration.as_micros(),
+            cpu.as_micros(),
+            calls
         );
 
         // No immediate persistence; metrics will be flushed periodically
     }
 
-    // Method to flush this individual function's metrics to the database
-    pub fn flush_to_db(&self) {
+    /// Add CPU time to the counters without counting a call.
+    fn record_cpu(&self, cpu: Duration) {
+        self.cpu_micros
+            .fetch_add(cpu.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Calls since the last flush, resetting the counters for the next one
+    fn take(&self) -> MetricRecord {
+        let latency = std::mem::replace(
+            &mut *self.latency.lock().expect("latency mutex poisoned"),
+            latency::new_hdr(),
+        );
+        MetricRecord {
+            latency,
+            // Don't reset last_called timestamp
+            // This preserves when the function was last used even after resetting counters
+            last_called: self.last_called.load(Ordering::Relaxed),
+            cpu_micros: self.cpu_micros.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    // Method to add calls taken from this function's metrics to the database
+    pub fn flush_to_db(&self, in_memory: &MetricRecord) {
         // Load existing DB values
-        let (db_total, db_calls, db_last) =
-            if let Ok(Some((t, c, l))) = METRICS_DB.get_metric(&self.function_name) {
-                info!(
-                    "Found existing DB metrics for '{}': total={}ms, calls={}, last={}",
-                    self.function_name, t, c, l
-                );
-                (t, c, l)
-            } else {
-                info!(
-                    "No existing DB metrics for '{}', using zeros",
-                    self.function_name
-                );
-                (0, 0, 0)
-            };
+        let metric_key = self.key.encode();
+        let mut combined = if let Ok(Some(stored)) = METRICS_DB.get_metric(&metric_key) {
+            info!(
+                "Found existing DB metrics for '{}': total={}ms, calls={}, last={}, cpu={}us",
+                self.key,
+                stored.total_time(),
+                stored.call_count(),
+                stored.last_called,
+                stored.cpu_micros
+            );
+            stored
+        } else {
+            info!("No existing DB metrics for '{}', using zeros", self.key);
+            MetricRecord::default()
+        };
 
         // Add current in-memory values
-        let mem_total = self.total_time.load(Ordering::Relaxed);
-        let mem_calls = self.call_count.load(Ordering::Relaxed);
-        let mem_last = self.last_called.load(Ordering::Relaxed);
-
         info!(
-            "In-memory metrics for '{}': total={}ms, calls={}, last={}",
-            self.function_name, mem_total, mem_calls, mem_last
+            "In-memory metrics for '{}': total={}ms, calls={}, last={}, cpu={}us",
+            self.key,
+            in_memory.total_time(),
+            in_memory.call_count(),
+            in_memory.last_called,
+            in_memory.cpu_micros
         );
-
-        // Calculate combined values
-        let combined_total = db_total + mem_total;
-        let combined_calls = db_calls + mem_calls;
-        let combined_last = std::cmp::max(db_last, mem_last);
+        if let Err(e) = combined.merge(in_memory) {
+            error!("Failed to combine metrics for '{}': {:#}", self.key, e);
+            return;
+        }
 
         info!(
-            "Combined metrics for '{}': total={}ms, calls={}, last={}",
-            self.function_name, combined_total, combined_calls, combined_last
+            "Combined metrics for '{}': total={}ms, calls={}, last={}, cpu={}us",
+            self.key,
+            combined.total_time(),
+            combined.call_count(),
+            combined.last_called,
+            combined.cpu_micros
         );
 
         // Combine and persist
-        match METRICS_DB.upsert_metric(
-            &self.function_name,
-            combined_total,
-            combined_calls,
-            combined_last,
-        ) {
-            Ok(_) => info!(
-                "Successfully persisted metrics for '{}'",
-                self.function_name
-            ),
-            Err(e) => error!(
-                "Failed to persist metrics for '{}': {}",
-                self.function_name, e
-            ),
+        match METRICS_DB.upsert_metric(&metric_key, combined) {
+            Ok(_) => info!("Successfully persisted metrics for '{}'", self.key),
+            Err(e) => error!("Failed to persist metrics for '{}': {}", self.key, e),
         }
     }
 }
 
 // Function to check if a function's WASI component artifact exists.
-fn function_artifact_exists(function_name: &str) -> bool {
+fn function_artifact_exists(key: &MetricKey) -> bool {
+    let function_name = &key.function().name;
     // Get the functions directory from environment or use default
     let functions_dir =
         std::env::var("FUNCTIONS_PATH").unwrap_or_else(|_| "./functions".to_string());
 
+    let functions_dir = Path::new(&functions_dir);
     ["wasm", "cwasm"].iter().any(|extension| {
-        Path::new(&functions_dir)
+        functions_dir
             .join(format!("{function_name}.{extension}"))
             .exists()
-    })
+    }) || functions_dir.join("sites").join(function_name).is_dir()
 }
 
-pub fn get_metrics() -> Metrics {
+/// Metrics of every function. Last failures and build provenance are only filled in
+/// for functions `viewer` owns; anonymous callers pass `None`.
+pub fn get_metrics(viewer: Option<&str>) -> Metrics {
     info!("Retrieving metrics from database...");
+    let server = SERVER.get();
     let mut function_metrics = Vec::new();
     let mut total_time = 0;
     let mut total_calls = 0;
@@ -186,55 +219,95 @@ pub fn get_metrics() -> Metrics {
     let db_entries_count = metric_rows.len();
     info!("Found {} entries in metrics database", db_entries_count);
 
-    for (function_name, db_total_time, db_call_count, db_last_called) in metric_rows {
+    for (metric_key, stored) in metric_rows {
+        let Some(key) = MetricKey::decode(&metric_key) else {
+            debug!("skipping metrics under legacy key '{metric_key}'");
+            continue;
+        };
         info!(
-            "DB metrics for '{}': total={}ms, calls={}, last={}",
-            function_name, db_total_time, db_call_count, db_last_called
+            "DB metrics for '{}': total={}ms, calls={}, last={}, cpu={}us",
+            key,
+            stored.total_time(),
+            stored.call_count(),
+            stored.last_called,
+            stored.cpu_micros
         );
 
         // Load in-memory metrics
-        let (mem_total_time, mem_call_count, mem_last_called) = FUNCTION_METRICS
-            .get(&function_name)
+        let in_memory = FUNCTION_METRICS
+            .get(&key)
             .map(|m| {
-                let total = m.total_time.load(Ordering::Relaxed);
-                let calls = m.call_count.load(Ordering::Relaxed);
-                let last = m.last_called.load(Ordering::Relaxed);
+                let in_memory = m.in_memory();
 
                 info!(
-                    "In-memory metrics for '{}': total={}ms, calls={}, last={}",
-                    function_name, total, calls, last
+                    "In-memory metrics for '{}': total={}ms, calls={}, last={}, cpu={}us",
+                    key,
+                    in_memory.total_time(),
+                    in_memory.call_count(),
+                    in_memory.last_called,
+                    in_memory.cpu_micros
                 );
 
-                (total, calls, last)
+                in_memory
             })
             .unwrap_or_else(|| {
-                info!("No in-memory metrics for '{}', using zeros", function_name);
-                (0, 0, 0)
+                info!("No in-memory metrics for '{}', using zeros", key);
+                MetricRecord::default()
             });
 
         // Combine DB and in-memory metrics
-        let combined_total_time = db_total_time.saturating_add(mem_total_time);
-        let combined_call_count = db_call_count.saturating_add(mem_call_count);
-        let combined_last_called = std::cmp::max(db_last_called, mem_last_called);
+        let mut combined = stored;
+        if let Err(e) = combined.merge(&in_memory) {
+            error!("Failed to combine metrics for '{}': {:#}", key, e);
+        }
 
         info!(
-            "Combined metrics for '{}': total={}ms, calls={}, last={}",
-            function_name, combined_total_time, combined_call_count, combined_last_called
+            "Combined metrics for '{}': total={}ms, calls={}, last={}, cpu={}us",
+            key,
+            combined.total_time(),
+            combined.call_count(),
+            combined.last_called,
+            combined.cpu_micros
         );
 
         // Convert timestamp to ISO string
-        let last_called_time = UNIX_EPOCH + Duration::from_millis(combined_last_called);
+        let last_called_time = UNIX_EPOCH + Duration::from_millis(combined.last_called);
         let last_called_str = chrono::DateTime::<chrono::Utc>::from(last_called_time).to_rfc3339();
 
+        let artifact_name = key.function().name.as_str();
+        let artifact_bytes = server
+            .map(|server| artifact_size(server, artifact_name))
+            .unwrap_or_default();
+        let artifact_health = server.and_then(|server| server.artifact_health(artifact_name));
+        let owned = viewer == Some(key.function().owner.as_str());
+        let server_if_owned = server.filter(|_| owned);
+        let provenance = server_if_owned.and_then(|server| {
+            publish::provenance(server, artifact_name)
+                .inspect_err(|err| error!("{err:#}"))
+                .ok()
+                .flatten()
+        });
+
         function_metrics.push(FunctionMetricsResponse {
-            function_name: function_name.clone(),
-            total_time_millis: combined_total_time,
-            call_count: combined_call_count,
+            function_name: key.display_name(),
+            total_time_millis: combined.total_time(),
+            cpu_time_micros: combined.cpu_micros,
+            call_count: combined.call_count(),
+            latency: latency::hdr_distribution(&combined.latency),
+            latency_hdr: latency::export_hdr(&combined.latency),
             last_called: last_called_str,
+            artifact_bytes,
+            artifact_health,
+            provenance,
+            dedicated: server.is_some_and(|server| server.is_dedicated(artifact_name)),
+            last_failure: server_if_owned.and_then(|server| server.last_failure(artifact_name)),
+            server_timings: server_timing::snapshot(&key),
+            starts: cold_starts::snapshot(&key),
+            variants: traffic::snapshot(&key),
         });
 
-        total_time += combined_total_time;
-        total_calls += combined_call_count;
+        total_time += combined.total_time();
+        total_calls += combined.call_count();
     }
 
     info!(
@@ -248,43 +321,107 @@ pub fn get_metrics() -> Metrics {
         total_time,
         total_calls,
         function_metrics,
+        disk: server.map(|server| disk_usage(server)).unwrap_or_default(),
+        admission: server
+            .map(|server| server.admission.stats())
+            .unwrap_or_default(),
+        runtime: server
+            .map(|server| server.runtime_stats())
+            .unwrap_or_default(),
+    }
+}
+
+fn artifact_size(server: &FaastaServer, function_name: &str) -> u64 {
+    if server.is_static_site(function_name) {
+        disk_size(&server.static_site_dir(function_name))
+    } else {
+        std::fs::metadata(server.artifact_path(function_name))
+            .map(|metadata| metadata.len())
+            .unwrap_or_default()
     }
 }
 
+/// Disk usage of the functions directory, with orphan counts from the consistency check.
+fn disk_usage(server: &FaastaServer) -> DiskUsage {
+    let mut usage = DiskUsage {
+        functions_dir_bytes: disk_size(&server.functions_dir),
+        sandbox_bytes: disk_size(&server.sandbox_root),
+        quarantined_files: std::fs::read_dir(server.functions_dir.join(fsck::ORPHANED_DIR))
+            .map(|entries| entries.count() as u64)
+            .unwrap_or_default(),
+        ..DiskUsage::default()
+    };
+    match fsck::scan(server) {
+        Ok(issues) => {
+            for issue in issues {
+                match issue {
+                    Issue::ArtifactWithoutMetadata { .. } => usage.orphaned_artifacts += 1,
+                    Issue::StaleCompiledArtifact { .. } | Issue::LeftoverStaging { .. } => {
+                        usage.stale_files += 1
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Err(err) => error!("failed to scan for orphaned artifacts: {err:#}"),
+    }
+    usage
+}
+
+/// Bytes used by a file, or by everything below a directory. Symlinks are not followed.
+pub fn disk_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
 // Helper function to get or create a function metric
-pub fn get_or_create_metric(function_name: &str) -> Option<FunctionMetric> {
+pub fn get_or_create_metric(
+    key: &MetricKey,
+) -> Option<dashmap::mapref::one::RefMut<'static, MetricKey, FunctionMetric>> {
     // Use entry API to reduce lock contention
-    let entry = FUNCTION_METRICS.entry(function_name.to_string());
+    let entry = FUNCTION_METRICS.entry(key.clone());
 
     match entry {
-        dashmap::mapref::entry::Entry::Occupied(occupied) => {
-            // Return a clone of the existing metric
-            Some(FunctionMetric::new(occupied.key().clone()))
-        }
+        dashmap::mapref::entry::Entry::Occupied(occupied) => Some(occupied.into_ref()),
         dashmap::mapref::entry::Entry::Vacant(vacant) => {
             // First check if the function's WASM file exists
-            if !function_artifact_exists(function_name) {
+            if !function_artifact_exists(key) {
                 return None;
             }
 
-            debug!("Creating new metric for function: {}", function_name);
-
-            // Create the new metric
-            let metric = FunctionMetric::new(function_name.to_string());
+            debug!("Creating new metric for function: {}", key);
 
-            // Insert it into the map
-            vacant.insert(metric.clone());
+            // Create the new metric and insert it into the map
+            let metric = vacant.insert(FunctionMetric::new(key.clone()));
 
             // New function added - ensure it's recorded in Sled DB even if no calls happen
-            if !METRICS_DB.metric_exists(function_name).unwrap_or(false) {
+            if !METRICS_DB.metric_exists(&key.encode()).unwrap_or(false) {
                 // Get current time in milliseconds for initialization
                 let now = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .unwrap_or(Duration::from_secs(0))
                     .as_millis() as u64;
 
-                let _ = METRICS_DB.upsert_metric(function_name, 0, 0, now);
-                debug!("Added new function '{}' to metrics database", function_name);
+                let _ = METRICS_DB.upsert_metric(
+                    &key.encode(),
+                    MetricRecord {
+                        last_called: now,
+                        ..MetricRecord::default()
+                    },
+                );
+                debug!("Added new function '{}' to metrics database", key);
             }
 
             Some(metric)
@@ -295,17 +432,31 @@ pub fn get_or_create_metric(function_name: &str) -> Option<FunctionMetric> {
 // Timer utility to measure function execution time
 pub struct Timer {
     start: SystemTime,
-    function_name: String,
+    key: MetricKey,
+    cpu: Duration,
+    cold_start: Option<bool>,
 }
 
 impl Timer {
     #[tracing::instrument(level = "debug")]
-    pub fn new(function_name: String) -> Self {
+    pub fn new(key: MetricKey) -> Self {
         Self {
             start: SystemTime::now(),
-            function_name,
+            key,
+            cpu: Duration::ZERO,
+            cold_start: None,
         }
     }
+
+    /// Charge the call for CPU time its guest ran for, recorded apart from wall time.
+    pub fn charge_cpu(&mut self, cpu: Duration) {
+        self.cpu += cpu;
+    }
+
+    /// Record the call's latency as a cold start or a warm invocation as well.
+    pub fn set_cold_start(&mut self, cold: bool) {
+        self.cold_start = Some(cold);
+    }
 }
 
 impl Drop for Timer {
@@ -314,15 +465,55 @@ impl Drop for Timer {
             .duration_since(self.start)
             .unwrap_or(Duration::from_secs(0));
 
-        if let Some(metric) = get_or_create_metric(&self.function_name) {
-            // Round up any duration to at least 1 millisecond
-            let duration_ms = duration.as_millis() as u64;
-            // Ensure the minimum duration is 1ms, even if the actual duration was 0ms
-            let rounded_duration = std::cmp::max(duration_ms, 1);
+        if let Some(metric) = get_or_create_metric(&self.key) {
+            metric.record_call(duration, self.cpu);
+        }
+        if let Some(cold) = self.cold_start {
+            cold_starts::record(&self.key, cold, duration);
+        }
+        if let MetricKey::Function(function) = &self.key {
+            public_stats::record(function, duration);
+        }
+    }
+}
 
-            metric.record_call(rounded_duration);
+/// Charge CPU time a call used after it was recorded, e.g. while writing a streamed
+/// body.
+pub fn charge_cpu(key: &MetricKey, cpu: Duration) {
+    if let Some(metric) = get_or_create_metric(key) {
+        metric.record_cpu(cpu);
+    }
+}
+
+/// Drop all recorded metrics for a persisted key, in memory and on disk.
+pub fn forget_metric(metric_key: &str) -> anyhow::Result<()> {
+    if let Some(key) = MetricKey::decode(metric_key) {
+        FUNCTION_METRICS.remove(&key);
+        server_timing::forget(&key);
+        cold_starts::forget(&key);
+        traffic::forget(&key);
+        if let MetricKey::Function(function) = &key {
+            public_stats::forget(function);
+        }
+    }
+    METRICS_DB.delete(Tree::Metrics, metric_key)
+}
+
+/// Move metrics persisted under keys of older releases to their [`MetricKey`], adding
+/// them to any metrics already there. Returns how many rows moved.
+pub fn migrate_legacy_keys(server: &FaastaServer) -> anyhow::Result<usize> {
+    let mut moved = 0;
+    for (legacy, metric) in METRICS_DB.iter_metrics()? {
+        if MetricKey::decode(&legacy).is_some() {
+            continue;
         }
+        let key = MetricKey::from_legacy(&legacy, |name| server.owner(name)).encode();
+        let mut combined = METRICS_DB.get_metric(&key)?.unwrap_or_default();
+        combined.merge(&metric)?;
+        METRICS_DB.move_metric(&legacy, &key, combined)?;
+        moved += 1;
     }
+    Ok(moved)
 }
 
 /// Flush in-memory metrics to persistent DB and reset counters.
@@ -332,34 +523,28 @@ pub fn flush_metrics_to_db() {
 
     for entry in FUNCTION_METRICS.iter() {
         let metric = entry.value(); // We only need the metric, not the key
-        let function_name = &metric.function_name;
-        let call_count = metric.call_count.load(Ordering::Relaxed);
-        let total_time = metric.total_time.load(Ordering::Relaxed);
+        let key = &metric.key;
+        let call_count = metric.latency.lock().expect("latency mutex poisoned").len();
 
-        // Skip if no calls were made since last flush
-        if call_count == 0 {
+        // Skip if no calls were made since last flush, unless a streamed body from an
+        // earlier one has since been charged CPU
+        if call_count == 0 && metric.cpu_micros.load(Ordering::Relaxed) == 0 {
             debug!(
                 "Skipping flush for function '{}' - no calls since last flush",
-                function_name
+                key
             );
             continue; // Skip if no calls were made
         }
 
         info!(
-            "Flushing metrics for function '{}': calls={}, total_time={}ms",
-            function_name, call_count, total_time
+            "Flushing metrics for function '{}': calls={}",
+            key, call_count
         );
 
-        // First flush this function's current metrics to the database
+        // Reset the in-memory counters, then add what they held to the database
         // using our helper method
-        metric.flush_to_db();
-
-        // Then reset the in-memory counters
-        metric.total_time.store(0, Ordering::Relaxed);
-        metric.call_count.store(0, Ordering::Relaxed);
-
-        // Don't reset last_called timestamp
-        // This preserves when the function was last used even after resetting counters
+        let in_memory = metric.take();
+        metric.flush_to_db(&in_memory);
 
         flushed_count += 1;
     }
@@ -0,0 +1,29 @@
+use anyhow::{Context, Result};
+use rustls::RootCertStore;
+use rustls::server::WebPkiClientVerifier;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::tls::{ServerCertificate, TlsPolicy, load_certificates};
+
+/// TLS configuration for the management listener: the server's certificate plus a
+/// mandatory client certificate signed by one of the CAs in `client_ca_path`.
+pub fn server_config(
+    policy: &TlsPolicy,
+    certificate: Arc<ServerCertificate>,
+    client_ca_path: &Path,
+) -> Result<Arc<rustls::ServerConfig>> {
+    let mut roots = RootCertStore::empty();
+    for ca in load_certificates(client_ca_path)? {
+        roots
+            .add(ca)
+            .with_context(|| format!("invalid CA certificate in {client_ca_path:?}"))?;
+    }
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), policy.provider()?)
+        .build()
+        .context("failed to build client certificate verifier")?;
+
+    policy
+        .server_config(certificate, Some(verifier))
+        .context("failed to build management TLS config")
+}
@@ -0,0 +1,462 @@
+//! Pulling wasm components published as OCI artifacts, as laid out by the CNCF wasm
+//! OCI spec: a manifest whose single `application/wasm` layer is the component.
+//!
+//! Only registries listed in `FAASTA_OCI_REGISTRIES` (comma-separated, by default
+//! `ghcr.io,docker.io,quay.io`) are contacted, over HTTPS and anonymously, so only
+//! public artifacts can be pulled. Every manifest and blob is checked against its
+//! digest before it is used.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use faasta_interface::MAX_WASM_SIZE;
+use reqwest::{Client as HttpClient, Response, StatusCode, header};
+use ring::digest::{SHA256, digest};
+use serde::Deserialize;
+
+const DEFAULT_REGISTRIES: &str = "ghcr.io,docker.io,quay.io";
+const DOCKER_HUB: &str = "docker.io";
+const DOCKER_HUB_HOST: &str = "registry-1.docker.io";
+const USER_AGENT: &str = "faasta-server";
+const PULL_TIMEOUT: Duration = Duration::from_secs(120);
+/// Largest manifest the distribution spec requires registries to accept
+const MAX_MANIFEST_SIZE: usize = 4 * 1024 * 1024;
+
+const WASM_LAYER: &str = "application/wasm";
+const WASM_ARCHITECTURE: &str = "wasm";
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, \
+application/vnd.oci.image.index.v1+json, \
+application/vnd.docker.distribution.manifest.v2+json, \
+application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// A parsed `[registry/]repository[:tag][@digest]` reference
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+    /// Registry as written, or `docker.io` when omitted
+    pub registry: String,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl Reference {
+    pub fn parse(reference: &str) -> Result<Self> {
+        let (rest, digest) = match reference.split_once('@') {
+            Some((rest, digest)) => (rest, Some(digest.to_string())),
+            None => (reference, None),
+        };
+        if let Some(digest) = &digest {
+            sha256_hex(digest)?;
+        }
+        let (name, tag) = match rest.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, Some(tag.to_string())),
+            _ => (rest, None),
+        };
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, path))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_string(), path.to_string())
+            }
+            _ => (DOCKER_HUB.to_string(), name.to_string()),
+        };
+        let repository = if registry == DOCKER_HUB && !repository.contains('/') {
+            format!("library/{repository}")
+        } else {
+            repository
+        };
+        let valid_component = |component: &str| {
+            !component.is_empty()
+                && component.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-')
+                })
+        };
+        if !repository.split('/').all(valid_component) {
+            bail!("invalid repository '{repository}'");
+        }
+        if let Some(tag) = &tag
+            && (tag.is_empty()
+                || tag.len() > 128
+                || !tag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+        {
+            bail!("invalid tag '{tag}'");
+        }
+        Ok(Self {
+            registry,
+            repository,
+            tag,
+            digest,
+        })
+    }
+
+    /// The last component of the repository, e.g. `fn` for `ghcr.io/me/fn:tag`.
+    pub fn name(&self) -> &str {
+        self.repository
+            .rsplit('/')
+            .next()
+            .unwrap_or(&self.repository)
+    }
+
+    fn host(&self) -> &str {
+        if self.registry == DOCKER_HUB {
+            DOCKER_HUB_HOST
+        } else {
+            &self.registry
+        }
+    }
+
+    /// What to ask the registry for: the digest when pinned, else the tag.
+    fn manifest_reference(&self) -> &str {
+        self.digest
+            .as_deref()
+            .or(self.tag.as_deref())
+            .unwrap_or("latest")
+    }
+}
+
+/// A component pulled from a registry
+pub struct Pulled {
+    pub component: Vec<u8>,
+    /// Digest of the manifest the component was pulled from
+    pub manifest_digest: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    /// Set on image indexes instead of `layers`
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+    platform: Option<Platform>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    architecture: String,
+}
+
+/// Registries that may be pulled from.
+pub fn allowed_registries() -> Vec<String> {
+    std::env::var("FAASTA_OCI_REGISTRIES")
+        .unwrap_or_else(|_| DEFAULT_REGISTRIES.to_string())
+        .split(',')
+        .map(|registry| registry.trim().to_ascii_lowercase())
+        .filter(|registry| !registry.is_empty())
+        .collect()
+}
+
+/// Pull the component `reference` points to, verifying every digest on the way.
+pub async fn pull(reference: &Reference) -> Result<Pulled> {
+    if !allowed_registries().contains(&reference.registry.to_ascii_lowercase()) {
+        bail!(
+            "registry '{}' is not allowed on this server",
+            reference.registry
+        );
+    }
+    let client = HttpClient::builder()
+        .timeout(PULL_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()
+        .context("failed to create HTTP client")?;
+    let mut registry = Registry {
+        client,
+        reference,
+        token: None,
+    };
+
+    let (mut manifest, mut manifest_digest) = registry
+        .manifest(reference.manifest_reference(), reference.digest.as_deref())
+        .await?;
+    if manifest.layers.is_empty() && !manifest.manifests.is_empty() {
+        let entry = manifest
+            .manifests
+            .iter()
+            .find(|entry| {
+                entry
+                    .platform
+                    .as_ref()
+                    .is_some_and(|platform| platform.architecture == WASM_ARCHITECTURE)
+            })
+            .ok_or_else(|| anyhow!("the image index has no manifest for the wasm platform"))?;
+        let digest = entry.digest.clone();
+        (manifest, manifest_digest) = registry.manifest(&digest, Some(&digest)).await?;
+    }
+
+    let layer = wasm_layer(&manifest)?;
+    if layer.size > MAX_WASM_SIZE as u64 {
+        bail!(
+            "component is {} bytes; the maximum is {MAX_WASM_SIZE}",
+            layer.size
+        );
+    }
+    let component = registry.blob(&layer.digest).await?;
+    Ok(Pulled {
+        component,
+        manifest_digest,
+    })
+}
+
+/// The one `application/wasm` layer of a component's manifest.
+fn wasm_layer(manifest: &Manifest) -> Result<&Descriptor> {
+    let mut layers = manifest
+        .layers
+        .iter()
+        .filter(|layer| layer.media_type == WASM_LAYER);
+    match (layers.next(), layers.next()) {
+        (Some(layer), None) => Ok(layer),
+        (None, _) => bail!("the artifact has no {WASM_LAYER} layer; is it a wasm component?"),
+        (Some(_), Some(_)) => bail!("the artifact has more than one {WASM_LAYER} layer"),
+    }
+}
+
+struct Registry<'a> {
+    client: HttpClient,
+    reference: &'a Reference,
+    token: Option<String>,
+}
+
+impl Registry<'_> {
+    /// Fetch a manifest, checking it against `expected` when the digest is known.
+    /// Returns it with its digest.
+    async fn manifest(
+        &mut self,
+        reference: &str,
+        expected: Option<&str>,
+    ) -> Result<(Manifest, String)> {
+        let url = format!(
+            "https://{}/v2/{}/manifests/{reference}",
+            self.reference.host(),
+            self.reference.repository
+        );
+        let response = self.get(&url, Some(MANIFEST_TYPES)).await?;
+        let body = read_limited(response, MAX_MANIFEST_SIZE).await?;
+        let digest = format!("sha256:{}", hex::encode(digest(&SHA256, &body)));
+        if let Some(expected) = expected {
+            verify(expected, &body).context("manifest")?;
+        }
+        let manifest = serde_json::from_slice(&body).context("malformed manifest")?;
+        Ok((manifest, digest))
+    }
+
+    async fn blob(&mut self, digest: &str) -> Result<Vec<u8>> {
+        let url = format!(
+            "https://{}/v2/{}/blobs/{digest}",
+            self.reference.host(),
+            self.reference.repository
+        );
+        let response = self.get(&url, None).await?;
+        let blob = read_limited(response, MAX_WASM_SIZE).await?;
+        verify(digest, &blob).context("component layer")?;
+        Ok(blob)
+    }
+
+    /// GET `url`, fetching an anonymous pull token the first time the registry asks.
+    async fn get(&mut self, url: &str, accept: Option<&str>) -> Result<Response> {
+        let mut response = self.send(url, accept).await?;
+        if response.status() == StatusCode::UNAUTHORIZED && self.token.is_none() {
+            let challenge = response
+                .headers()
+                .get(header::WWW_AUTHENTICATE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_challenge)
+                .ok_or_else(|| anyhow!("the registry requires credentials"))?;
+            self.token = Some(self.fetch_token(&challenge).await?);
+            response = self.send(url, accept).await?;
+        }
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => bail!("not found in the registry"),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                bail!("the registry refused access; only public artifacts can be pulled")
+            }
+            status => bail!("the registry answered {status}"),
+        }
+    }
+
+    async fn send(&self, url: &str, accept: Option<&str>) -> Result<Response> {
+        let mut request = self.client.get(url);
+        if let Some(accept) = accept {
+            request = request.header(header::ACCEPT, accept);
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request
+            .send()
+            .await
+            .with_context(|| format!("failed to reach {}", self.reference.registry))
+    }
+
+    async fn fetch_token(&self, challenge: &Challenge) -> Result<String> {
+        if !challenge.realm.starts_with("https://") {
+            bail!("the registry's token service is not served over HTTPS");
+        }
+        let scope = challenge
+            .scope
+            .clone()
+            .unwrap_or_else(|| format!("repository:{}:pull", self.reference.repository));
+        let mut query = vec![("scope", scope.as_str())];
+        if let Some(service) = &challenge.service {
+            query.push(("service", service.as_str()));
+        }
+        let body: serde_json::Value = self
+            .client
+            .get(&challenge.realm)
+            .query(&query)
+            .send()
+            .await
+            .context("failed to reach the registry's token service")?
+            .error_for_status()
+            .context("the registry's token service refused an anonymous token")?
+            .json()
+            .await
+            .context("malformed token response")?;
+        body.get("token")
+            .or_else(|| body.get("access_token"))
+            .and_then(|token| token.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("the token response has no token"))
+    }
+}
+
+/// A `Bearer` challenge from a `WWW-Authenticate` header
+#[derive(Debug, PartialEq, Eq)]
+struct Challenge {
+    realm: String,
+    service: Option<String>,
+    scope: Option<String>,
+}
+
+fn parse_challenge(header: &str) -> Option<Challenge> {
+    let (scheme, params) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let mut realm = None;
+    let mut service = None;
+    let mut scope = None;
+    let mut rest = params.trim();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, after) = quoted.split_once('"')?;
+                (value, after)
+            }
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        match key.trim() {
+            "realm" => realm = Some(value.to_string()),
+            "service" => service = Some(value.to_string()),
+            "scope" => scope = Some(value.to_string()),
+            _ => {}
+        }
+        rest = after.trim_start_matches(',').trim();
+    }
+    Some(Challenge {
+        realm: realm?,
+        service,
+        scope,
+    })
+}
+
+async fn read_limited(mut response: Response, limit: usize) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.context("download interrupted")? {
+        if body.len() + chunk.len() > limit {
+            bail!("response is larger than {limit} bytes");
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+fn sha256_hex(digest: &str) -> Result<&str> {
+    match digest.strip_prefix("sha256:") {
+        Some(hex) if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => Ok(hex),
+        _ => bail!("unsupported digest '{digest}'; only sha256 is supported"),
+    }
+}
+
+fn verify(expected: &str, bytes: &[u8]) -> Result<()> {
+    let expected_hex = sha256_hex(expected)?;
+    let actual = hex::encode(digest(&SHA256, bytes));
+    if !actual.eq_ignore_ascii_case(expected_hex) {
+        bail!("digest mismatch: expected {expected}, got sha256:{actual}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_references() {
+        let reference = Reference::parse("ghcr.io/me/fn:v1").unwrap();
+        assert_eq!(
+            (reference.registry.as_str(), reference.repository.as_str()),
+            ("ghcr.io", "me/fn")
+        );
+        assert_eq!(reference.tag.as_deref(), Some("v1"));
+        assert_eq!(reference.name(), "fn");
+
+        let hub = Reference::parse("hello").unwrap();
+        assert_eq!(
+            (hub.host(), hub.repository.as_str()),
+            (DOCKER_HUB_HOST, "library/hello")
+        );
+        assert_eq!(hub.manifest_reference(), "latest");
+
+        let digest = format!("sha256:{}", "ab".repeat(32));
+        let pinned = Reference::parse(&format!("localhost:5000/fn:v2@{digest}")).unwrap();
+        assert_eq!(pinned.registry, "localhost:5000");
+        assert_eq!(pinned.manifest_reference(), digest);
+
+        assert!(Reference::parse("ghcr.io/Me/fn").is_err());
+        assert!(Reference::parse("ghcr.io/me/fn@md5:abc").is_err());
+        assert!(Reference::parse("ghcr.io/me/fn:").is_err());
+    }
+
+    #[test]
+    fn parses_bearer_challenges() {
+        let challenge = parse_challenge(
+            r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:me/fn:pull""#,
+        )
+        .unwrap();
+        assert_eq!(challenge.realm, "https://ghcr.io/token");
+        assert_eq!(challenge.service.as_deref(), Some("ghcr.io"));
+        assert_eq!(challenge.scope.as_deref(), Some("repository:me/fn:pull"));
+        assert!(parse_challenge(r#"Basic realm="registry""#).is_none());
+    }
+
+    #[test]
+    fn verifies_digests_and_layers() {
+        let digest = format!("sha256:{}", hex::encode(digest(&SHA256, b"wasm")));
+        assert!(verify(&digest, b"wasm").is_ok());
+        assert!(verify(&digest, b"evil").is_err());
+
+        let manifest: Manifest = serde_json::from_str(&format!(
+            r#"{{"layers":[{{"mediaType":"application/wasm","digest":"{digest}","size":4}}]}}"#
+        ))
+        .unwrap();
+        assert_eq!(wasm_layer(&manifest).unwrap().digest, digest);
+        let image: Manifest = serde_json::from_str(&format!(
+            r#"{{"layers":[{{"mediaType":"application/vnd.oci.image.layer.v1.tar+gzip","digest":"{digest}","size":4}}]}}"#
+        ))
+        .unwrap();
+        assert!(wasm_layer(&image).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Platform context passed to every invocation as `FAASTA_*` environment variables, so
+//! functions can log and branch on their deployment identity. The guest SDK reads them
+//! through `faasta::context`.
+
+use once_cell::sync::OnceCell;
+use ring::digest::{SHA256, digest};
+use tracing::debug;
+
+use crate::publish;
+use crate::wasi_server::FaastaServer;
+
+pub const FUNCTION_VAR: &str = "FAASTA_FUNCTION";
+pub const VERSION_VAR: &str = "FAASTA_VERSION";
+pub const DEPLOYMENT_ID_VAR: &str = "FAASTA_DEPLOYMENT_ID";
+pub const REGION_VAR: &str = "FAASTA_REGION";
+
+const RESERVED: [&str; 4] = [FUNCTION_VAR, VERSION_VAR, DEPLOYMENT_ID_VAR, REGION_VAR];
+
+static REGION: OnceCell<String> = OnceCell::new();
+
+/// Region reported to functions running on this node.
+pub fn set_region(region: String) {
+    let _ = REGION.set(region);
+}
+
+/// This node's region label, if the operator set one.
+pub fn region() -> Option<&'static str> {
+    REGION.get().map(String::as_str)
+}
+
+/// Whether this node serves a function pinned to `regions`. Nodes without a region
+/// label serve every function.
+pub fn serves(regions: &[String]) -> bool {
+    match region() {
+        Some(region) => regions.is_empty() || regions.iter().any(|pinned| pinned == region),
+        None => true,
+    }
+}
+
+/// Add the platform context of `function_name` to `env`. Variables the platform
+/// reserves are never taken from `env`, e.g. from a secret of the same name.
+pub fn apply(server: &FaastaServer, function_name: &str, env: &mut Vec<(String, String)>) {
+    let mut context = vec![(FUNCTION_VAR, function_name.to_string())];
+    match publish::provenance(server, function_name) {
+        Ok(Some(provenance)) => {
+            // The version identifies the uploaded component; the deployment changes on
+            // every publish or rebuild, even of the same component
+            let deployment = digest(
+                &SHA256,
+                format!("{}@{}", provenance.upload_sha256, provenance.built_at).as_bytes(),
+            );
+            context.push((VERSION_VAR, short(&provenance.upload_sha256)));
+            context.push((DEPLOYMENT_ID_VAR, short(&hex::encode(deployment))));
+        }
+        Ok(None) => {}
+        Err(err) => debug!("no platform version for '{function_name}': {err:#}"),
+    }
+    if let Some(region) = REGION.get() {
+        context.push((REGION_VAR, region.clone()));
+    }
+
+    env.retain(|(name, _)| !RESERVED.contains(&name.as_str()));
+    env.extend(
+        context
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value)),
+    );
+}
+
+fn short(hex: &str) -> String {
+    hex.chars().take(12).collect()
+}
@@ -0,0 +1,81 @@
+//! Publish-time pre-initialization of components (Wizer-style snapshots).
+//!
+//! When an operator configures a pre-initializer, every published component is run
+//! through it once: the tool executes the guest's initialization and writes a new
+//! component whose memory already holds the result, so requests skip that startup
+//! work. Components the tool cannot initialize are published unchanged.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use once_cell::sync::OnceCell;
+use tracing::{info, warn};
+
+static COMMAND: OnceCell<Vec<String>> = OnceCell::new();
+
+/// Set the pre-initializer command line. `{input}` and `{output}` are replaced with the
+/// component to initialize and the path to write the snapshot to.
+pub fn configure(command: &str) -> Result<()> {
+    let argv: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+    if argv.is_empty() {
+        bail!("pre-initializer command is empty");
+    }
+    if !argv.iter().any(|arg| arg.contains("{input}"))
+        || !argv.iter().any(|arg| arg.contains("{output}"))
+    {
+        bail!("pre-initializer command must reference both {{input}} and {{output}}");
+    }
+    COMMAND
+        .set(argv)
+        .map_err(|_| anyhow::anyhow!("pre-initializer already configured"))
+}
+
+/// Replace the staged component at `path` with its pre-initialized snapshot, if a
+/// pre-initializer is configured and succeeds. Returns whether the artifact changed.
+pub fn snapshot(name: &str, path: &Path) -> bool {
+    let Some(argv) = COMMAND.get() else {
+        return false;
+    };
+    match run(argv, path) {
+        Ok(()) => {
+            info!("published pre-initialized snapshot of '{name}'");
+            true
+        }
+        Err(err) => {
+            warn!("pre-initialization of '{name}' failed, publishing it as-is: {err:#}");
+            false
+        }
+    }
+}
+
+fn run(argv: &[String], path: &Path) -> Result<()> {
+    let output_path = snapshot_path(path);
+    let substitute = |arg: &String| {
+        arg.replace("{input}", &path.to_string_lossy())
+            .replace("{output}", &output_path.to_string_lossy())
+    };
+
+    let output = Command::new(substitute(&argv[0]))
+        .args(argv[1..].iter().map(substitute))
+        .output()
+        .with_context(|| format!("failed to run pre-initializer '{}'", argv[0]))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&output_path);
+        bail!(
+            "pre-initializer exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    fs::rename(&output_path, path)
+        .with_context(|| format!("failed to replace {} with its snapshot", path.display()))
+}
+
+fn snapshot_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".preinit");
+    path.with_file_name(file_name)
+}
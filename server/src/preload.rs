@@ -0,0 +1,237 @@
+//! Speculative warming of the functions a function depends on, so a page served by one
+//! function doesn't leave the browser waiting on cold starts of the APIs it calls.
+//!
+//! Dependencies come from two places: the function's declared `preload` list, and
+//! subdomains of other functions of the same owner that its responses point at
+//! (`Location`, `Link` and textual bodies, sampled at most once a minute). When a
+//! function receives traffic its dependencies that aren't resident are warmed in the
+//! background, at batch priority so real requests come first.
+
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use http::HeaderMap;
+use http::header::{CONTENT_TYPE, LINK, LOCATION};
+
+use crate::wasi_server::function_name_from_host;
+
+/// Dependencies remembered per function, declared and learned alike
+pub const MAX_TARGETS: usize = 8;
+/// How often a function's responses are scanned for references
+const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+/// How much of a body is scanned
+const MAX_SCAN_BYTES: usize = 256 * 1024;
+/// How soon a target is warmed again after an attempt, whether or not it stuck
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+pub struct Preloader {
+    learned: DashMap<String, Learned>,
+    attempted: DashMap<String, Instant>,
+}
+
+struct Learned {
+    targets: Vec<String>,
+    scanned_at: Option<Instant>,
+}
+
+impl Preloader {
+    /// Whether a response of `function` should be scanned now; claims the scan.
+    pub fn should_scan(&self, function: &str) -> bool {
+        let now = Instant::now();
+        let mut learned = self
+            .learned
+            .entry(function.to_string())
+            .or_insert_with(|| Learned {
+                targets: Vec::new(),
+                scanned_at: None,
+            });
+        if learned
+            .scanned_at
+            .is_some_and(|scanned_at| now.duration_since(scanned_at) < SCAN_INTERVAL)
+        {
+            return false;
+        }
+        learned.scanned_at = Some(now);
+        true
+    }
+
+    /// Remember `targets` as dependencies of `function`, keeping the most recent.
+    pub fn learn(&self, function: &str, targets: Vec<String>) {
+        let Some(mut learned) = self.learned.get_mut(function) else {
+            return;
+        };
+        for target in targets {
+            learned.targets.retain(|known| *known != target);
+            learned.targets.insert(0, target);
+        }
+        learned.targets.truncate(MAX_TARGETS);
+    }
+
+    /// `declared` followed by the learned dependencies of `function`, without repeats.
+    pub fn targets(&self, function: &str, declared: &[String]) -> Vec<String> {
+        let mut targets = declared.to_vec();
+        if let Some(learned) = self.learned.get(function) {
+            for target in &learned.targets {
+                if !targets.contains(target) {
+                    targets.push(target.clone());
+                }
+            }
+        }
+        targets.retain(|target| target != function);
+        targets
+    }
+
+    /// Whether `target` may be warmed now; claims the attempt.
+    pub fn begin(&self, target: &str) -> bool {
+        let now = Instant::now();
+        match self.attempted.get_mut(target) {
+            Some(last) if now.duration_since(*last) < RETRY_INTERVAL => false,
+            Some(mut last) => {
+                *last = now;
+                true
+            }
+            None => {
+                self.attempted.insert(target.to_string(), now);
+                true
+            }
+        }
+    }
+
+    /// Drop what was learned about `function`, e.g. after a new publish.
+    pub fn forget(&self, function: &str) {
+        self.learned.remove(function);
+        self.attempted.remove(function);
+    }
+}
+
+/// Functions whose subdomains a response refers to in its `Location` or `Link` headers
+/// or, for textual content, its body.
+pub fn referenced_functions(headers: &HeaderMap, body: &[u8], base_domain: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for value in headers
+        .get_all(LOCATION)
+        .iter()
+        .chain(headers.get_all(LINK))
+    {
+        scan(
+            &value.to_str().unwrap_or_default().to_ascii_lowercase(),
+            base_domain,
+            &mut found,
+        );
+    }
+    let textual = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(is_textual);
+    if textual {
+        let body = &body[..body.len().min(MAX_SCAN_BYTES)];
+        scan(
+            &String::from_utf8_lossy(body).to_ascii_lowercase(),
+            base_domain,
+            &mut found,
+        );
+    }
+    found
+}
+
+fn is_textual(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json" | "application/javascript" | "application/xml"
+        )
+}
+
+/// Add the function names of hosts under `base_domain` in `text` to `found`.
+fn scan(text: &str, base_domain: &str, found: &mut Vec<String>) {
+    let suffix = format!(
+        ".{}",
+        base_domain.trim_end_matches('.').to_ascii_lowercase()
+    );
+    for (index, _) in text.match_indices(&suffix) {
+        let end = index + suffix.len();
+        // `fn.faasta.lol.example.com` is not one of ours
+        let mut rest = text[end..].chars();
+        let continues = match rest.next() {
+            Some('.') => rest.next().is_some_and(is_host_char),
+            Some(next) => is_host_char(next),
+            None => false,
+        };
+        if continues {
+            continue;
+        }
+        let start = text[..index]
+            .rfind(|ch: char| !(is_host_char(ch) || ch == '.'))
+            .map_or(0, |position| position + 1);
+        if let Some(name) = function_name_from_host(Some(&text[start..end]), base_domain)
+            && !found.contains(&name)
+            && found.len() < MAX_TARGETS
+        {
+            found.push(name);
+        }
+    }
+}
+
+fn is_host_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    #[test]
+    fn finds_functions_in_headers_and_bodies() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        headers.insert(
+            LINK,
+            HeaderValue::from_static("<https://fonts.faasta.lol/a.woff2>; rel=preload"),
+        );
+        let body = br#"<script>fetch("https://API.faasta.lol/items")</script>
+            <a href="//api.faasta.lol">again</a>
+            <img src="https://cdn.faasta.lol.example.com/x.png">
+            <a href="https://faasta.lol/">home</a>"#;
+        assert_eq!(
+            referenced_functions(&headers, body, "faasta.lol"),
+            ["fonts", "api"]
+        );
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+        assert_eq!(
+            referenced_functions(&headers, body, "faasta.lol"),
+            ["fonts"]
+        );
+    }
+
+    #[test]
+    fn merges_declared_and_learned_targets() {
+        let preloader = Preloader::default();
+        assert!(preloader.should_scan("site"));
+        assert!(!preloader.should_scan("site"));
+        preloader.learn("site", vec!["api".to_string(), "site".to_string()]);
+        preloader.learn("site", vec!["auth".to_string()]);
+        assert_eq!(
+            preloader.targets("site", &["api".to_string(), "search".to_string()]),
+            ["api", "search", "auth"]
+        );
+
+        assert!(preloader.begin("api"));
+        assert!(!preloader.begin("api"));
+        preloader.forget("site");
+        assert!(preloader.targets("site", &[]).is_empty());
+    }
+}
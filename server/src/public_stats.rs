@@ -0,0 +1,188 @@
+//! Public usage statistics for functions whose owners opt in with `public_stats`:
+//! requests per day and p95 latency over the last week, served as a page at
+//! `/_faasta/stats` on the function and as JSON at `/v1/stats/{name}`.
+//!
+//! Counts are kept in memory since the node started, for every function so that
+//! opting in shows the recent past too. Latencies go into a [`Histogram`], so p95 is
+//! reported as the upper bound of the bucket it falls in.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use dashmap::DashMap;
+use http::{HeaderValue, Method, Response, StatusCode, header};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::function_key::FunctionKey;
+use crate::latency::Histogram;
+
+/// Path, relative to the function, at which its stats page is served.
+pub const PATH: &str = "/_faasta/stats";
+/// Days of history kept and reported, including today
+const DAYS: u64 = 7;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; style-src 'unsafe-inline'; frame-ancestors 'none'";
+const STYLE: &str = "body{max-width:46rem;margin:2rem auto;padding:0 1rem;\
+font-family:system-ui,sans-serif;line-height:1.6;color-scheme:light dark}\
+table{border-collapse:collapse}td,th{border:1px solid #8884;padding:.25rem .75rem;text-align:right}\
+footer{margin-top:3rem;font-size:.85em;opacity:.7}";
+
+/// Latencies per function, by day number since the Unix epoch
+static STATS: Lazy<DashMap<FunctionKey, BTreeMap<u64, Histogram>>> = Lazy::new(DashMap::new);
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PublicStats {
+    pub function: String,
+    /// The last week, oldest first
+    pub days: Vec<DayStats>,
+    /// p95 latency over the whole week, in milliseconds
+    pub p95_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DayStats {
+    /// UTC date, e.g. `2026-10-15`
+    pub date: String,
+    pub requests: u64,
+    pub p95_ms: Option<u64>,
+}
+
+/// Count one call of `function` that took `duration`.
+pub fn record(function: &FunctionKey, duration: Duration) {
+    record_on(function, duration, today());
+}
+
+fn record_on(function: &FunctionKey, duration: Duration, today: u64) {
+    let mut days = STATS.entry(function.clone()).or_default();
+    days.entry(today).or_default().record(duration);
+    days.retain(|day, _| day + DAYS > today);
+}
+
+pub fn snapshot(function: &FunctionKey) -> PublicStats {
+    snapshot_on(function, today())
+}
+
+fn snapshot_on(function: &FunctionKey, today: u64) -> PublicStats {
+    let recorded = STATS
+        .get(function)
+        .map(|days| days.clone())
+        .unwrap_or_default();
+    let mut week = Histogram::default();
+    let days = (today + 1 - DAYS..=today)
+        .map(|day_number| {
+            let day = recorded.get(&day_number).cloned().unwrap_or_default();
+            week.merge(&day);
+            DayStats {
+                date: date(day_number),
+                requests: day.count(),
+                p95_ms: day.percentile(95),
+            }
+        })
+        .collect();
+    PublicStats {
+        function: function.name.clone(),
+        days,
+        p95_ms: week.percentile(95),
+    }
+}
+
+pub fn forget(function: &FunctionKey) {
+    STATS.remove(function);
+}
+
+/// The stats page of a function.
+pub fn render(stats: &PublicStats, method: &Method) -> Response<Body> {
+    let mut rows = String::new();
+    for day in stats.days.iter().rev() {
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            day.date,
+            day.requests,
+            latency(day.p95_ms)
+        ));
+    }
+    let requests: u64 = stats.days.iter().map(|day| day.requests).sum();
+    // Function names are restricted to [A-Za-z0-9_-], so they need no escaping
+    let name = &stats.function;
+    let page = format!(
+        "<!doctype html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+<title>{name} stats</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<main>\n\
+<h1>{name}</h1>\n<p>{requests} requests in the last {DAYS} days, p95 latency {}.</p>\n\
+<table>\n<tr><th>Date (UTC)</th><th>Requests</th><th>p95</th></tr>\n{rows}</table>\n</main>\n\
+<footer>{name} runs on Faasta</footer>\n</body>\n</html>\n",
+        latency(stats.p95_ms)
+    );
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::from(page)
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(header::CACHE_CONTROL, "public, max-age=60")
+        .header(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(CONTENT_SECURITY_POLICY),
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .body(body)
+        .unwrap()
+}
+
+fn latency(p95_ms: Option<u64>) -> String {
+    match p95_ms {
+        Some(ms) => format!("≤ {ms} ms"),
+        None => "–".to_string(),
+    }
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / SECS_PER_DAY)
+        .unwrap_or_default()
+}
+
+fn date(day_number: u64) -> String {
+    chrono::DateTime::from_timestamp((day_number * SECS_PER_DAY) as i64, 0)
+        .map(|date| date.date_naive().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_requests_per_day_and_p95() {
+        let function = FunctionKey::new("stats-owner", "stats-test");
+        let today = 20_000;
+        for _ in 0..95 {
+            record_on(&function, Duration::from_millis(8), today - 1);
+        }
+        for _ in 0..5 {
+            record_on(&function, Duration::from_millis(900), today - 1);
+        }
+        record_on(&function, Duration::from_secs(60), today);
+        record_on(&function, Duration::from_millis(3), today - DAYS);
+
+        let stats = snapshot_on(&function, today);
+        assert_eq!(stats.days.len(), DAYS as usize);
+        let yesterday = &stats.days[DAYS as usize - 2];
+        assert_eq!(yesterday.date, "2024-10-03");
+        assert_eq!((yesterday.requests, yesterday.p95_ms), (100, Some(10)));
+        let latest = &stats.days[DAYS as usize - 1];
+        assert_eq!((latest.requests, latest.p95_ms), (1, Some(10000)));
+        assert_eq!(stats.days[0].requests, 0);
+        assert_eq!(stats.p95_ms, Some(1000));
+
+        forget(&function);
+        assert_eq!(snapshot_on(&function, today).p95_ms, None);
+    }
+}
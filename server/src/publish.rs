@@ -0,0 +1,326 @@
+//! Crash-safe publishing.
+//!
+//! A publish touches the filesystem (the artifact) and the metadata store (function
+//! record and the owner's project list). Before the staged artifact is moved into
+//! place, an intent record is written; the metadata writes and the removal of the
+//! intent then happen in one transaction. On startup any leftover intent is resolved:
+//! if the staged artifact is still present the publish never took effect and is rolled
+//! back, otherwise it is rolled forward by committing the metadata.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use bincode::{Decode, Encode};
+use faasta_interface::{ArtifactProvenance, FunctionInfo};
+use ring::digest::{SHA256, digest};
+use tracing::{error, info, warn};
+
+use crate::db::{MetadataOp, Tree, record};
+use crate::encryption;
+use crate::wasi_server::FaastaServer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum ArtifactKind {
+    Component,
+    StaticSite,
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct PublishIntent {
+    pub name: String,
+    pub owner: String,
+    pub kind: ArtifactKind,
+    pub started_at: String,
+}
+
+/// Where the new artifact must be written before calling [`begin`].
+pub fn staging_path(server: &FaastaServer, name: &str, kind: ArtifactKind) -> PathBuf {
+    match kind {
+        ArtifactKind::Component => server.functions_dir.join(format!("{name}.wasm.tmp")),
+        ArtifactKind::StaticSite => server.static_site_dir(name).with_extension("staging"),
+    }
+}
+
+/// Where a resumable upload of a component is assembled before it is published.
+pub fn upload_path(server: &FaastaServer, name: &str) -> PathBuf {
+    server
+        .functions_dir
+        .join("uploads")
+        .join(format!("{name}.part"))
+}
+
+/// Directory under the functions directory holding uploaded components that the live
+/// artifact was derived from.
+pub const ORIGINALS_DIR: &str = "originals";
+
+/// Where the uploaded component is kept when the live one was derived from it, e.g.
+/// pre-initialized. Otherwise the live `.wasm` is the upload itself.
+pub fn original_path(server: &FaastaServer, name: &str) -> PathBuf {
+    server
+        .functions_dir
+        .join(ORIGINALS_DIR)
+        .join(format!("{name}.wasm"))
+}
+
+/// The component as it was uploaded.
+pub fn original_component(server: &FaastaServer, name: &str) -> PathBuf {
+    let original = original_path(server, name);
+    if original.exists() {
+        original
+    } else {
+        live_path(server, name, ArtifactKind::Component)
+    }
+}
+
+/// Keep `upload` as the function's original component, or with `None` drop a kept
+/// original because the live component is the upload.
+pub fn retain_original(server: &FaastaServer, name: &str, upload: Option<&[u8]>) -> Result<()> {
+    let path = original_path(server, name);
+    let Some(upload) = upload else {
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        return Ok(());
+    };
+    let dir = server.functions_dir.join(ORIGINALS_DIR);
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let staging = path.with_extension("wasm.tmp");
+    fs::write(&staging, encryption::seal(upload, name.as_bytes())?)
+        .with_context(|| format!("failed to write {}", staging.display()))?;
+    fs::rename(&staging, &path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+/// Hex-encoded SHA-256 of an uploaded component, as recorded in its provenance.
+pub fn upload_sha256(upload: &[u8]) -> String {
+    hex::encode(digest(&SHA256, upload))
+}
+
+/// Provenance of a component built from `upload` now.
+pub fn new_provenance(upload: &[u8], preinitialized: bool) -> ArtifactProvenance {
+    ArtifactProvenance {
+        upload_sha256: upload_sha256(upload),
+        upload_bytes: upload.len() as u64,
+        preinitialized,
+        built_at: chrono::Utc::now().to_rfc3339(),
+        built_by: env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
+pub fn provenance(server: &FaastaServer, name: &str) -> Result<Option<ArtifactProvenance>> {
+    let Some(encoded) = server.metadata_db.get(Tree::Artifacts, name)? else {
+        return Ok(None);
+    };
+    let provenance = record::decode(&encoded)
+        .with_context(|| format!("invalid artifact record for '{name}'"))?;
+    Ok(Some(provenance))
+}
+
+pub fn record_provenance(
+    server: &FaastaServer,
+    name: &str,
+    provenance: &ArtifactProvenance,
+) -> Result<()> {
+    let encoded = record::encode(provenance)?;
+    server
+        .metadata_db
+        .put(Tree::Artifacts, name, &encoded)
+        .with_context(|| format!("failed to record the artifact of '{name}'"))
+}
+
+fn live_path(server: &FaastaServer, name: &str, kind: ArtifactKind) -> PathBuf {
+    match kind {
+        ArtifactKind::Component => server.functions_dir.join(format!("{name}.wasm")),
+        ArtifactKind::StaticSite => server.static_site_dir(name),
+    }
+}
+
+fn previous_site_path(server: &FaastaServer, name: &str) -> PathBuf {
+    server.static_site_dir(name).with_extension("previous")
+}
+
+/// Record that a staged artifact is about to replace the live one.
+pub fn begin(
+    server: &FaastaServer,
+    name: &str,
+    owner: &str,
+    kind: ArtifactKind,
+) -> Result<PublishIntent> {
+    let intent = PublishIntent {
+        name: name.to_string(),
+        owner: owner.to_string(),
+        kind,
+        started_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let encoded = bincode::encode_to_vec(&intent, bincode::config::standard())?;
+    server
+        .metadata_db
+        .put(Tree::PublishIntents, name, &encoded)
+        .context("failed to record publish intent")?;
+    Ok(intent)
+}
+
+/// Move the staged artifact into place and commit the metadata for it.
+pub fn commit(server: &FaastaServer, intent: &PublishIntent) -> Result<()> {
+    if let Err(err) = swap_in(server, intent) {
+        roll_back(server, intent);
+        return Err(err);
+    }
+    // Past this point the new artifact is live; if the metadata commit fails the
+    // intent stays behind and is rolled forward on the next startup.
+    finish(server, intent)
+}
+
+fn swap_in(server: &FaastaServer, intent: &PublishIntent) -> Result<()> {
+    let staged = staging_path(server, &intent.name, intent.kind);
+    let live = live_path(server, &intent.name, intent.kind);
+    if intent.kind == ArtifactKind::StaticSite && live.exists() {
+        let previous = previous_site_path(server, &intent.name);
+        if previous.exists() {
+            fs::remove_dir_all(&previous)
+                .with_context(|| format!("failed to clear {}", previous.display()))?;
+        }
+        fs::rename(&live, &previous).context("failed to move the live site aside")?;
+    }
+    fs::rename(&staged, &live).context("failed to move the staged artifact into place")
+}
+
+fn finish(server: &FaastaServer, intent: &PublishIntent) -> Result<()> {
+    let name = &intent.name;
+    let function_info = FunctionInfo {
+        name: name.clone(),
+        owner: intent.owner.clone(),
+        published_at: intent.started_at.clone(),
+        usage: format!("https://{name}.faasta.lol or https://faasta.lol/{name}"),
+    };
+
+    let mut ops = vec![
+        MetadataOp::Put {
+            tree: Tree::Functions,
+            key: name.clone(),
+            value: record::encode(&function_info)?,
+        },
+        MetadataOp::Delete {
+            tree: Tree::PublishIntents,
+            key: name.clone(),
+        },
+    ];
+    ops.extend(
+        server
+            .github_auth
+            .project_registration(&intent.owner, name)?,
+    );
+    if intent.kind == ArtifactKind::StaticSite {
+        ops.push(MetadataOp::Delete {
+            tree: Tree::Artifacts,
+            key: name.clone(),
+        });
+    }
+    server
+        .metadata_db
+        .transaction(ops)
+        .context("failed to commit publish metadata")?;
+    server.github_auth.project_registered(&intent.owner, name);
+
+    // Whatever the new artifact replaced is no longer reachable.
+    let replaced = match intent.kind {
+        ArtifactKind::Component => vec![
+            previous_site_path(server, name),
+            server.static_site_dir(name),
+            server.functions_dir.join(format!("{name}.cwasm")),
+        ],
+        ArtifactKind::StaticSite => vec![
+            previous_site_path(server, name),
+            server.functions_dir.join(format!("{name}.wasm")),
+            server.functions_dir.join(format!("{name}.cwasm")),
+            original_path(server, name),
+        ],
+    };
+    for path in replaced {
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else if path.exists() {
+            fs::remove_file(&path)
+        } else {
+            continue;
+        };
+        if let Err(err) = result {
+            error!(
+                "failed to remove replaced artifact {}: {err}",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn roll_back(server: &FaastaServer, intent: &PublishIntent) {
+    let staged = staging_path(server, &intent.name, intent.kind);
+    let result = if staged.is_dir() {
+        fs::remove_dir_all(&staged)
+    } else if staged.exists() {
+        fs::remove_file(&staged)
+    } else {
+        Ok(())
+    };
+    if let Err(err) = result {
+        error!(
+            "failed to remove staged artifact {}: {err}",
+            staged.display()
+        );
+    }
+
+    if intent.kind == ArtifactKind::StaticSite {
+        let live = live_path(server, &intent.name, intent.kind);
+        let previous = previous_site_path(server, &intent.name);
+        if !live.exists()
+            && previous.exists()
+            && let Err(err) = fs::rename(&previous, &live)
+        {
+            error!(
+                "failed to restore previous site for '{}': {err}",
+                intent.name
+            );
+        }
+    }
+
+    if let Err(err) = server
+        .metadata_db
+        .delete(Tree::PublishIntents, &intent.name)
+    {
+        error!(
+            "failed to clear publish intent for '{}': {err}",
+            intent.name
+        );
+    }
+}
+
+/// Resolve publishes that were interrupted by a crash or restart.
+pub fn recover(server: &FaastaServer) -> Result<()> {
+    for (name, encoded) in server.metadata_db.scan(Tree::PublishIntents)? {
+        let intent = match bincode::decode_from_slice::<PublishIntent, _>(
+            &encoded,
+            bincode::config::standard(),
+        ) {
+            Ok((intent, _)) => intent,
+            Err(err) => {
+                warn!("dropping unreadable publish intent for '{name}': {err}");
+                server.metadata_db.delete(Tree::PublishIntents, &name)?;
+                continue;
+            }
+        };
+
+        if staging_path(server, &intent.name, intent.kind).exists() {
+            info!("rolling back interrupted publish of '{name}'");
+            roll_back(server, &intent);
+        } else if live_path(server, &intent.name, intent.kind).exists() {
+            info!("completing interrupted publish of '{name}'");
+            finish(server, &intent)?;
+        } else {
+            warn!("publish intent for '{name}' has no artifact; discarding it");
+            roll_back(server, &intent);
+        }
+    }
+    Ok(())
+}
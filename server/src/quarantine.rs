@@ -0,0 +1,115 @@
+//! Quarantine for artifacts that fail to load.
+//!
+//! A corrupt or incompatible artifact would otherwise be recompiled, and fail, on every
+//! request. After `FAILURE_THRESHOLD` consecutive load failures the function is
+//! quarantined: requests fail fast until a retry is due, and each failed retry doubles
+//! the wait up to `MAX_BACKOFF`. A successful load or a new publish clears the state.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use faasta_interface::ArtifactHealth;
+use tracing::warn;
+
+pub const FAILURE_THRESHOLD: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+/// Returned instead of loading a quarantined artifact.
+#[derive(Debug)]
+pub struct Quarantined {
+    pub function_name: String,
+    pub retry_after: Duration,
+    pub reason: String,
+}
+
+impl fmt::Display for Quarantined {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "artifact of '{}' is quarantined after repeated load failures ({}); retrying in {}s",
+            self.function_name,
+            self.reason,
+            self.retry_after.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for Quarantined {}
+
+#[derive(Default)]
+pub struct Quarantine {
+    entries: DashMap<String, Entry>,
+}
+
+struct Entry {
+    failures: u32,
+    last_error: String,
+    /// Set once quarantined: loads are refused until then
+    retry_at: Option<Instant>,
+}
+
+impl Quarantine {
+    /// Refuse to load the function while it is quarantined. Once a retry is due, one
+    /// caller is let through and the next retry is pushed back.
+    pub fn check(&self, function_name: &str) -> Result<(), Quarantined> {
+        let Some(mut entry) = self.entries.get_mut(function_name) else {
+            return Ok(());
+        };
+        let Some(retry_at) = entry.retry_at else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if now >= retry_at {
+            entry.retry_at = Some(now + backoff(entry.failures));
+            return Ok(());
+        }
+        Err(Quarantined {
+            function_name: function_name.to_string(),
+            retry_after: retry_at - now,
+            reason: entry.last_error.clone(),
+        })
+    }
+
+    pub fn record_failure(&self, function_name: &str, error: &anyhow::Error) {
+        let mut entry = self
+            .entries
+            .entry(function_name.to_string())
+            .or_insert_with(|| Entry {
+                failures: 0,
+                last_error: String::new(),
+                retry_at: None,
+            });
+        entry.failures += 1;
+        entry.last_error = format!("{error:#}");
+        if entry.failures >= FAILURE_THRESHOLD {
+            let wait = backoff(entry.failures);
+            if entry.retry_at.is_none() {
+                warn!(
+                    "quarantining '{function_name}' after {} failed loads: {error:#}",
+                    entry.failures
+                );
+            }
+            entry.retry_at = Some(Instant::now() + wait);
+        }
+    }
+
+    /// Forget the function's failures, after it loaded or was re-published.
+    pub fn clear(&self, function_name: &str) {
+        self.entries.remove(function_name);
+    }
+
+    pub fn health(&self, function_name: &str) -> Option<ArtifactHealth> {
+        self.entries.get(function_name).map(|entry| ArtifactHealth {
+            load_failures: entry.failures,
+            quarantined: entry.retry_at.is_some(),
+            last_error: entry.last_error.clone(),
+        })
+    }
+}
+
+fn backoff(failures: u32) -> Duration {
+    let doublings = failures.saturating_sub(FAILURE_THRESHOLD).min(16);
+    (INITIAL_BACKOFF * 2u32.pow(doublings)).min(MAX_BACKOFF)
+}
@@ -10,6 +10,8 @@ use tracing::info;
 
 use crate::rpc_service;
 
+/// Serve bitRPC over QUIC. The TLS config is built with the process-wide rustls
+/// provider, so it follows `CRYPTO_PROVIDER` like the HTTPS listeners.
 pub async fn run_rpc_server(
     tls_cert_path: PathBuf,
     tls_key_path: PathBuf,
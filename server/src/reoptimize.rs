@@ -0,0 +1,69 @@
+//! `faasta-server reoptimize`: rebuild every published component from the component it
+//! was uploaded as, with the current pre-initializer and engine settings.
+
+use std::fs;
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+use crate::db::Tree;
+use crate::publish::{self, ArtifactKind};
+use crate::wasi_server::FaastaServer;
+use crate::{encryption, preinit};
+
+#[derive(Debug, Default)]
+pub struct ReoptimizeReport {
+    pub rebuilt: usize,
+    /// Functions that could not be rebuilt, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+pub async fn run(server: &FaastaServer) -> Result<ReoptimizeReport> {
+    let mut report = ReoptimizeReport::default();
+    for (name, _) in server.metadata_db.scan(Tree::Functions)? {
+        if server.is_static_site(&name) {
+            continue;
+        }
+        match rebuild(server, &name).await {
+            Ok(()) => {
+                info!("rebuilt '{name}'");
+                report.rebuilt += 1;
+            }
+            Err(err) => {
+                warn!("failed to rebuild '{name}': {err:#}");
+                report.failed.push((name, format!("{err:#}")));
+            }
+        }
+    }
+    Ok(report)
+}
+
+async fn rebuild(server: &FaastaServer, name: &str) -> Result<()> {
+    let original = publish::original_component(server, name);
+    let upload = encryption::read_artifact(name, &original)
+        .context("no uploaded component to rebuild from")?;
+
+    let staging = publish::staging_path(server, name, ArtifactKind::Component);
+    fs::write(&staging, &upload)
+        .with_context(|| format!("failed to write {}", staging.display()))?;
+    let pre_initialized = preinit::snapshot(name, &staging);
+    encryption::seal_artifact(name, &staging)?;
+    publish::retain_original(server, name, pre_initialized.then_some(upload.as_slice()))?;
+    let live = server.functions_dir.join(format!("{name}.wasm"));
+    fs::rename(&staging, &live).with_context(|| format!("failed to replace {}", live.display()))?;
+
+    // Compile with the current engine settings, replacing the cached `.cwasm`
+    server.remove_from_cache(name).await;
+    let precompiled = server.functions_dir.join(format!("{name}.cwasm"));
+    if precompiled.exists() {
+        fs::remove_file(&precompiled)
+            .with_context(|| format!("failed to remove {}", precompiled.display()))?;
+    }
+    server.warm(name).await?;
+
+    publish::record_provenance(
+        server,
+        name,
+        &publish::new_provenance(&upload, pre_initialized),
+    )
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, bail};
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use faasta_interface::signing::{self, MAX_CLOCK_SKEW_SECS};
+
+use crate::db::{MetadataStore, Tree};
+
+/// Per-user request-signing keys and the nonces already accepted with them.
+///
+/// Signing is opt-in: users without a key are not checked. Once a user has a key,
+/// every management call from them must carry a fresh signature.
+pub struct RequestSigning {
+    db: Arc<dyn MetadataStore>,
+    /// `username:nonce` -> unix time after which the nonce can be forgotten
+    seen_nonces: DashMap<String, u64>,
+}
+
+impl RequestSigning {
+    pub fn new(db: Arc<dyn MetadataStore>) -> Self {
+        Self {
+            db,
+            seen_nonces: DashMap::new(),
+        }
+    }
+
+    /// Check the signature carried by `github_auth_token` for `operation` on `name`.
+    pub fn verify(
+        &self,
+        username: &str,
+        github_auth_token: &str,
+        operation: &str,
+        name: &str,
+        payload: &[u8],
+    ) -> Result<()> {
+        let Some(key) = self.db.get(Tree::SigningKeys, username)? else {
+            return Ok(());
+        };
+        let Some(signature) = signing::split_token(github_auth_token).1 else {
+            bail!("request signature required");
+        };
+
+        let now = unix_now();
+        if signature.timestamp.abs_diff(now) > MAX_CLOCK_SKEW_SECS {
+            bail!("request signature has expired");
+        }
+        if !signature.verify(&key, operation, name, payload) {
+            bail!("invalid request signature");
+        }
+
+        self.seen_nonces.retain(|_, expires| *expires > now);
+        match self
+            .seen_nonces
+            .entry(format!("{username}:{}", signature.nonce))
+        {
+            Entry::Occupied(_) => bail!("request signature has already been used"),
+            Entry::Vacant(entry) => {
+                entry.insert(signature.timestamp + MAX_CLOCK_SKEW_SECS);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn is_enrolled(&self, username: &str) -> Result<bool> {
+        Ok(self.db.get(Tree::SigningKeys, username)?.is_some())
+    }
+
+    /// Generate and store a new key for `username`, replacing any previous one.
+    pub fn rotate(&self, username: &str) -> Result<Vec<u8>> {
+        let key: [u8; 32] = rand::random();
+        self.db.put(Tree::SigningKeys, username, &key)?;
+        Ok(key.to_vec())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,572 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, to_bytes};
+use bytes::Bytes;
+use dashmap::DashMap;
+use faasta_interface::error_code;
+use http::header::{AGE, AUTHORIZATION, CACHE_CONTROL, SET_COOKIE, VARY};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Response, StatusCode, Uri};
+use http_body::Body as _;
+use tokio::time;
+use tracing::{debug, error};
+
+use crate::error_body;
+
+pub const CACHE_STATUS_HEADER: &str = "x-faasta-cache";
+const MAX_CACHED_BODY: usize = 1024 * 1024;
+
+/// Shared cache for function responses, driven by the `s-maxage`, `max-age` and
+/// `stale-while-revalidate` directives the function sets on its own responses.
+/// Bounded by entry count and by the bytes of the responses held.
+pub struct ResponseCache {
+    capacity: usize,
+    max_bytes: usize,
+    /// Bytes of every entry's body and headers
+    bytes: AtomicUsize,
+    entries: DashMap<CacheKey, CacheEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    function_name: String,
+    method: Method,
+    path_and_query: String,
+}
+
+struct CacheEntry {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored: Instant,
+    fresh_for: Duration,
+    stale_for: Duration,
+    /// Request header values the response varies on, captured when it was stored.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    revalidating: AtomicBool,
+}
+
+pub enum CacheLookup {
+    /// Serve the cached response as-is.
+    Fresh(Response<Body>),
+    /// Serve the cached response and refresh it in the background.
+    Stale(Response<Body>),
+    /// Nothing usable is cached; invoke the function and `store` the result.
+    Miss,
+}
+
+#[derive(Default)]
+struct CacheDirectives {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize, max_bytes: usize) -> Self {
+        Self {
+            capacity,
+            max_bytes,
+            bytes: AtomicUsize::new(0),
+            entries: DashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0 && self.max_bytes > 0
+    }
+
+    /// Cache key for a request, if the request may be answered from the shared cache.
+    pub fn request_key(
+        &self,
+        function_name: &str,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+    ) -> Option<CacheKey> {
+        if !self.enabled()
+            || (method != Method::GET && method != Method::HEAD)
+            || headers.contains_key(AUTHORIZATION)
+        {
+            return None;
+        }
+        Some(CacheKey {
+            function_name: function_name.to_string(),
+            method: method.clone(),
+            path_and_query: uri
+                .path_and_query()
+                .map(|pq| pq.as_str())
+                .unwrap_or("/")
+                .to_string(),
+        })
+    }
+
+    pub fn lookup(&self, key: &CacheKey, request_headers: &HeaderMap) -> CacheLookup {
+        let request_directives = parse_cache_control(request_headers);
+        if request_directives.no_cache || request_directives.no_store {
+            return CacheLookup::Miss;
+        }
+
+        let Some(entry) = self.entries.get(key) else {
+            return CacheLookup::Miss;
+        };
+        let matches_vary = entry
+            .vary
+            .iter()
+            .all(|(name, value)| request_headers.get(name) == value.as_ref());
+        if !matches_vary {
+            return CacheLookup::Miss;
+        }
+
+        let age = entry.stored.elapsed();
+        if age < entry.fresh_for {
+            CacheLookup::Fresh(entry.to_response(age, "HIT"))
+        } else if age < entry.fresh_for + entry.stale_for {
+            // Only the first request to see the stale entry triggers a refresh.
+            if entry.revalidating.swap(true, Ordering::AcqRel) {
+                CacheLookup::Fresh(entry.to_response(age, "STALE"))
+            } else {
+                CacheLookup::Stale(entry.to_response(age, "STALE"))
+            }
+        } else {
+            CacheLookup::Miss
+        }
+    }
+
+    /// Buffer a function response, store it if its headers allow shared caching,
+    /// and hand back an equivalent response for the client. Streamed responses and
+    /// bodies over `MAX_CACHED_BODY` are passed through without being read. Either way
+    /// the entry they would have replaced is dropped.
+    pub async fn store(
+        &self,
+        key: CacheKey,
+        request_headers: &HeaderMap,
+        response: Response<Body>,
+    ) -> Response<Body> {
+        let Some((fresh_for, stale_for)) = cache_lifetime(&response) else {
+            self.remove(&key);
+            return annotate(response, "MISS");
+        };
+        if response
+            .body()
+            .size_hint()
+            .exact()
+            .is_none_or(|len| len > MAX_CACHED_BODY as u64)
+        {
+            // Also ends a background refresh, which would otherwise leave the stale
+            // entry marked as revalidating until it expired
+            self.remove(&key);
+            return annotate(response, "MISS");
+        }
+
+        let (parts, body) = response.into_parts();
+        let body = match to_bytes(body, MAX_CACHED_BODY).await {
+            Ok(body) => body,
+            Err(err) => {
+                error!("failed to buffer cacheable function response: {err}");
+                return error_body::response(
+                    StatusCode::BAD_GATEWAY,
+                    error_code::BAD_GATEWAY,
+                    "Failed to read function response",
+                );
+            }
+        };
+        let vary = parts
+            .headers
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+            .map(|name| {
+                let value = request_headers.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+
+        let entry = CacheEntry {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            stored: Instant::now(),
+            fresh_for,
+            stale_for,
+            vary,
+            revalidating: AtomicBool::new(false),
+        };
+        // The entry being replaced is removed first so its bytes make room
+        self.remove(&key);
+        if !self.has_room(entry.size()) {
+            self.sweep();
+        }
+        if self.has_room(entry.size()) {
+            self.bytes.fetch_add(entry.size(), Ordering::AcqRel);
+            if let Some(replaced) = self.entries.insert(key, entry) {
+                self.bytes.fetch_sub(replaced.size(), Ordering::AcqRel);
+            }
+        }
+
+        annotate(Response::from_parts(parts, Body::from(body)), "MISS")
+    }
+
+    /// Record the result of a background refresh. Server errors keep serving the
+    /// stale entry until it expires, and the next request retries the refresh.
+    pub async fn finish_revalidation(
+        &self,
+        key: CacheKey,
+        request_headers: &HeaderMap,
+        response: Response<Body>,
+    ) {
+        if response.status().is_server_error() {
+            if let Some(entry) = self.entries.get(&key) {
+                entry.revalidating.store(false, Ordering::Release);
+            }
+            return;
+        }
+        self.store(key, request_headers, response).await;
+    }
+
+    /// Drop every cached response for a function, e.g. after it is republished.
+    pub fn purge_function(&self, function_name: &str) {
+        self.retain(|key, _| key.function_name != function_name);
+    }
+
+    /// Bytes held by cached responses.
+    pub fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Acquire)
+    }
+
+    fn has_room(&self, size: usize) -> bool {
+        self.entries.len() < self.capacity && self.bytes() + size <= self.max_bytes
+    }
+
+    fn remove(&self, key: &CacheKey) {
+        if let Some((_, entry)) = self.entries.remove(key) {
+            self.bytes.fetch_sub(entry.size(), Ordering::AcqRel);
+        }
+    }
+
+    fn retain(&self, mut keep: impl FnMut(&CacheKey, &CacheEntry) -> bool) -> usize {
+        let mut removed = 0;
+        self.entries.retain(|key, entry| {
+            let kept = keep(key, entry);
+            if !kept {
+                self.bytes.fetch_sub(entry.size(), Ordering::AcqRel);
+                removed += 1;
+            }
+            kept
+        });
+        removed
+    }
+
+    fn sweep(&self) {
+        let removed =
+            self.retain(|_, entry| entry.stored.elapsed() < entry.fresh_for + entry.stale_for);
+        if removed > 0 {
+            debug!("evicted {removed} expired cached responses");
+        }
+    }
+
+    /// Spawn a background task that evicts expired entries once per minute.
+    pub fn spawn_periodic_sweep(self: Arc<Self>) {
+        if !self.enabled() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut ticker = time::interval(Duration::from_secs(60));
+            loop {
+                ticker.tick().await;
+                self.sweep();
+            }
+        });
+    }
+}
+
+impl CacheEntry {
+    /// Bytes counted against the cache's bound
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum::<usize>()
+    }
+
+    fn to_response(&self, age: Duration, cache_status: &'static str) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+            .headers_mut()
+            .insert(AGE, HeaderValue::from(age.as_secs()));
+        annotate(response, cache_status)
+    }
+}
+
+/// How long a response stays fresh and then servable while stale, or `None` if it
+/// must not be stored in a shared cache.
+fn cache_lifetime(response: &Response<Body>) -> Option<(Duration, Duration)> {
+    if !matches!(
+        response.status().as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 410
+    ) || response.headers().contains_key(SET_COOKIE)
+        || response
+            .headers()
+            .get_all(VARY)
+            .iter()
+            .any(|value| value.as_bytes().trim_ascii() == b"*")
+    {
+        return None;
+    }
+
+    let directives = parse_cache_control(response.headers());
+    if directives.no_store || directives.no_cache || directives.private {
+        return None;
+    }
+    let fresh_for = directives.s_maxage.or(directives.max_age).unwrap_or(0);
+    let stale_for = directives.stale_while_revalidate.unwrap_or(0);
+    if fresh_for == 0 && stale_for == 0 {
+        return None;
+    }
+    Some((
+        Duration::from_secs(fresh_for),
+        Duration::from_secs(stale_for),
+    ))
+}
+
+fn parse_cache_control(headers: &HeaderMap) -> CacheDirectives {
+    let mut directives = CacheDirectives::default();
+    for directive in headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+        let seconds = value.and_then(|value| value.parse().ok());
+        match name.to_ascii_lowercase().as_str() {
+            "no-store" => directives.no_store = true,
+            "no-cache" => directives.no_cache = true,
+            "private" => directives.private = true,
+            "max-age" => directives.max_age = seconds,
+            "s-maxage" => directives.s_maxage = seconds,
+            "stale-while-revalidate" => directives.stale_while_revalidate = seconds,
+            _ => {}
+        }
+    }
+    directives
+}
+
+fn annotate(mut response: Response<Body>, cache_status: &'static str) -> Response<Body> {
+    response
+        .headers_mut()
+        .insert(CACHE_STATUS_HEADER, HeaderValue::from_static(cache_status));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+    use http_body_util::{BodyExt, StreamBody};
+
+    use super::*;
+
+    fn cacheable(body: Body) -> Response<Body> {
+        Response::builder()
+            .header(CACHE_CONTROL, "s-maxage=60")
+            .body(body)
+            .unwrap()
+    }
+
+    fn with_cache_control(cache_control: &str) -> Response<Body> {
+        Response::builder()
+            .header(CACHE_CONTROL, cache_control)
+            .body(Body::from("cached"))
+            .unwrap()
+    }
+
+    fn headers(name: HeaderName, value: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(name, HeaderValue::from_static(value))])
+    }
+
+    fn streamed() -> Body {
+        let chunks = stream::iter([Ok::<_, axum::Error>(http_body::Frame::data(
+            Bytes::from_static(b"event"),
+        ))]);
+        Body::new(StreamBody::new(chunks))
+    }
+
+    fn served(lookup: CacheLookup) -> Option<(bool, String)> {
+        let (stale, response) = match lookup {
+            CacheLookup::Fresh(response) => (false, response),
+            CacheLookup::Stale(response) => (true, response),
+            CacheLookup::Miss => return None,
+        };
+        let status = response.headers()[CACHE_STATUS_HEADER].to_str().unwrap();
+        Some((stale, status.to_string()))
+    }
+
+    fn key(cache: &ResponseCache, path: &str) -> CacheKey {
+        let uri = path.parse().unwrap();
+        cache
+            .request_key("app", &Method::GET, &uri, &HeaderMap::new())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn holds_at_most_max_bytes() {
+        let cache = ResponseCache::new(100, 1000);
+        for path in ["/a", "/b", "/c"] {
+            let body = Body::from(vec![b'x'; 400]);
+            cache
+                .store(key(&cache, path), &HeaderMap::new(), cacheable(body))
+                .await;
+        }
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.bytes() <= 1000);
+        assert!(matches!(
+            cache.lookup(&key(&cache, "/c"), &HeaderMap::new()),
+            CacheLookup::Miss
+        ));
+
+        cache.purge_function("app");
+        assert_eq!(cache.bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn passes_large_and_streamed_bodies_through() {
+        let cache = ResponseCache::new(100, usize::MAX);
+        let large = Body::from(vec![b'x'; MAX_CACHED_BODY + 1]);
+        let response = cache
+            .store(key(&cache, "/large"), &HeaderMap::new(), cacheable(large))
+            .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.len(), MAX_CACHED_BODY + 1);
+
+        cache
+            .store(
+                key(&cache, "/events"),
+                &HeaderMap::new(),
+                cacheable(streamed()),
+            )
+            .await;
+        assert_eq!(cache.entries.len(), 0);
+        assert_eq!(cache.bytes(), 0);
+    }
+
+    #[test]
+    fn s_maxage_takes_precedence_over_max_age() {
+        let lifetime = |cache_control| cache_lifetime(&with_cache_control(cache_control));
+        assert_eq!(
+            lifetime("max-age=5, s-maxage=60, stale-while-revalidate=30"),
+            Some((Duration::from_secs(60), Duration::from_secs(30)))
+        );
+        assert_eq!(
+            lifetime("max-age=5"),
+            Some((Duration::from_secs(5), Duration::ZERO))
+        );
+        // Shared caches must not keep what `s-maxage=0` says is already stale
+        assert_eq!(lifetime("s-maxage=0, max-age=60"), None);
+    }
+
+    #[tokio::test]
+    async fn stale_entries_are_refreshed_by_one_request() {
+        let cache = ResponseCache::new(100, usize::MAX);
+        let key = key(&cache, "/swr");
+        let none = HeaderMap::new();
+        let response = with_cache_control("s-maxage=0, stale-while-revalidate=60");
+        cache.store(key.clone(), &none, response).await;
+
+        let stale = Some((true, "STALE".to_string()));
+        let served_stale = Some((false, "STALE".to_string()));
+        assert_eq!(served(cache.lookup(&key, &none)), stale);
+        assert_eq!(served(cache.lookup(&key, &none)), served_stale);
+        assert_eq!(served(cache.lookup(&key, &none)), served_stale);
+
+        // A failed refresh lets the next request try again
+        let failed = Response::builder()
+            .status(StatusCode::BAD_GATEWAY)
+            .body(Body::empty())
+            .unwrap();
+        cache.finish_revalidation(key.clone(), &none, failed).await;
+        assert_eq!(served(cache.lookup(&key, &none)), stale);
+
+        // One that can't be cached drops the entry rather than leaving it stuck
+        cache
+            .finish_revalidation(key.clone(), &none, cacheable(streamed()))
+            .await;
+        assert_eq!(served(cache.lookup(&key, &none)), None);
+
+        let refreshed = with_cache_control("s-maxage=60");
+        cache
+            .finish_revalidation(key.clone(), &none, refreshed)
+            .await;
+        assert_eq!(
+            served(cache.lookup(&key, &none)),
+            Some((false, "HIT".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn vary_mismatches_miss() {
+        let cache = ResponseCache::new(100, usize::MAX);
+        let key = key(&cache, "/vary");
+        let english = headers(http::header::ACCEPT_LANGUAGE, "en");
+        let mut response = with_cache_control("s-maxage=60");
+        response
+            .headers_mut()
+            .insert(VARY, HeaderValue::from_static("accept-language"));
+        cache.store(key.clone(), &english, response).await;
+
+        assert!(served(cache.lookup(&key, &english)).is_some());
+        let french = headers(http::header::ACCEPT_LANGUAGE, "fr");
+        assert_eq!(served(cache.lookup(&key, &french)), None);
+        assert_eq!(served(cache.lookup(&key, &HeaderMap::new())), None);
+    }
+
+    #[tokio::test]
+    async fn requests_can_bypass_the_cache() {
+        let cache = ResponseCache::new(100, usize::MAX);
+        let key = key(&cache, "/bypass");
+        let none = HeaderMap::new();
+        cache
+            .store(key.clone(), &none, with_cache_control("s-maxage=60"))
+            .await;
+
+        assert!(served(cache.lookup(&key, &none)).is_some());
+        for directive in ["no-cache", "no-store"] {
+            let request = headers(CACHE_CONTROL, directive);
+            assert_eq!(served(cache.lookup(&key, &request)), None, "{directive}");
+        }
+    }
+
+    #[tokio::test]
+    async fn private_responses_are_not_stored() {
+        let cache = ResponseCache::new(100, usize::MAX);
+        let none = HeaderMap::new();
+        let mut with_cookie = with_cache_control("s-maxage=60");
+        with_cookie
+            .headers_mut()
+            .insert(SET_COOKIE, HeaderValue::from_static("session=1"));
+        let responses = [
+            ("/no-store", with_cache_control("s-maxage=60, no-store")),
+            ("/private", with_cache_control("private, max-age=60")),
+            ("/cookie", with_cookie),
+        ];
+        for (path, response) in responses {
+            let key = key(&cache, path);
+            let response = cache.store(key.clone(), &none, response).await;
+            assert_eq!(response.headers()[CACHE_STATUS_HEADER], "MISS");
+            assert_eq!(served(cache.lookup(&key, &none)), None, "{path}");
+        }
+        assert_eq!(cache.bytes(), 0);
+    }
+}
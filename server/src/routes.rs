@@ -0,0 +1,308 @@
+use axum::body::Body;
+use faasta_interface::{RouteAction, RouteConfig, RouteRule, TrailingSlash};
+use http::{Response, StatusCode, Uri, header};
+
+/// What to do with a request after evaluating a function's route rules.
+#[derive(Debug)]
+pub enum RouteOutcome {
+    /// Invoke the function with the original request.
+    Continue,
+    /// Invoke the function with a rewritten URI.
+    Rewrite(Uri),
+    /// Answer directly without invoking the function.
+    Redirect(Response<Body>),
+}
+
+/// Evaluate a function's redirect and rewrite rules against a request.
+///
+/// `mount` is the prefix the function is served under: empty on its own subdomain,
+/// `/name` for path-based URLs. Rules match the path below the mount. Redirects to
+/// relative destinations are resolved back under it; so are rewrites, unless
+/// `strip_prefix` is set, in which case the function is invoked with the path below
+/// the mount whether or not a rule matched.
+pub fn apply_routes(routes: &RouteConfig, mount: &str, uri: &Uri) -> RouteOutcome {
+    let strip = routes.strip_prefix && !mount.is_empty();
+    if routes.rules.is_empty() && routes.trailing_slash.is_none() && !strip {
+        return RouteOutcome::Continue;
+    }
+
+    let path = path_below_mount(uri.path(), mount);
+    let query = uri.query();
+
+    if let Some(mode) = routes.trailing_slash
+        && let Some(normalized) = normalize_trailing_slash(path, mode)
+    {
+        let location = with_query(&same_origin(&format!("{mount}{normalized}")), query);
+        return RouteOutcome::Redirect(redirect(StatusCode::PERMANENT_REDIRECT, &location));
+    }
+
+    for rule in &routes.rules {
+        let Some(destination) = match_rule(rule, path) else {
+            continue;
+        };
+        let absolute = is_absolute_url(&destination);
+
+        match rule.action {
+            RouteAction::Redirect(status) => {
+                let location = if absolute {
+                    destination
+                } else {
+                    same_origin(&format!("{mount}{destination}"))
+                };
+                let status = StatusCode::from_u16(status).unwrap_or(StatusCode::FOUND);
+                return RouteOutcome::Redirect(redirect(status, &with_query(&location, query)));
+            }
+            RouteAction::Rewrite if !absolute => {
+                let mount = delivered_mount(routes, mount);
+                let location = with_query(&format!("{mount}{destination}"), query);
+                if let Ok(uri) = location.parse() {
+                    return RouteOutcome::Rewrite(uri);
+                }
+            }
+            RouteAction::Rewrite => {}
+        }
+    }
+
+    if strip && let Ok(uri) = with_query(path, query).parse() {
+        return RouteOutcome::Rewrite(uri);
+    }
+    RouteOutcome::Continue
+}
+
+/// Prefix of the paths a function is invoked with: `mount`, or nothing when the
+/// function's routes strip it.
+pub fn delivered_mount<'a>(routes: &RouteConfig, mount: &'a str) -> &'a str {
+    if routes.strip_prefix { "" } else { mount }
+}
+
+/// Request path relative to the prefix a function is served under.
+pub fn path_below_mount<'a>(path: &'a str, mount: &str) -> &'a str {
+    match path.strip_prefix(mount) {
+        Some("") => "/",
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => path,
+    }
+}
+
+pub fn is_absolute_url(destination: &str) -> bool {
+    destination.starts_with("https://") || destination.starts_with("http://")
+}
+
+fn match_rule(rule: &RouteRule, path: &str) -> Option<String> {
+    match rule.source.strip_suffix('*') {
+        Some(prefix) => {
+            let suffix = path.strip_prefix(prefix)?;
+            Some(match rule.destination.strip_suffix('*') {
+                Some(destination) => format!("{destination}{suffix}"),
+                None => rule.destination.clone(),
+            })
+        }
+        None => (path == rule.source).then(|| rule.destination.trim_end_matches('*').to_string()),
+    }
+}
+
+fn normalize_trailing_slash(path: &str, mode: TrailingSlash) -> Option<String> {
+    if path == "/" {
+        return None;
+    }
+    match mode {
+        TrailingSlash::Always => {
+            let last_segment = path.rsplit('/').next().unwrap_or_default();
+            (!path.ends_with('/') && !last_segment.contains('.')).then(|| format!("{path}/"))
+        }
+        TrailingSlash::Never => path.ends_with('/').then(|| {
+            let trimmed = path.trim_end_matches('/');
+            if trimmed.is_empty() {
+                "/".to_string()
+            } else {
+                trimmed.to_string()
+            }
+        }),
+    }
+}
+
+/// A relative redirect target with its leading slashes collapsed: browsers treat
+/// `//host` and `/\host` as another origin.
+fn same_origin(location: &str) -> String {
+    match location.strip_prefix(['/', '\\']) {
+        Some(rest) => format!("/{}", rest.trim_start_matches(['/', '\\'])),
+        None => location.to_string(),
+    }
+}
+
+fn with_query(location: &str, query: Option<&str>) -> String {
+    match query {
+        Some(query) if !query.is_empty() => {
+            let separator = if location.contains('?') { '&' } else { '?' };
+            format!("{location}{separator}{query}")
+        }
+        _ => location.to_string(),
+    }
+}
+
+fn redirect(status: StatusCode, location: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::LOCATION, location)
+        .body(Body::empty())
+        .unwrap_or_else(|_| Response::builder().status(500).body(Body::empty()).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(source: &str, destination: &str, action: RouteAction) -> RouteRule {
+        RouteRule {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            action,
+        }
+    }
+
+    fn routes(rules: Vec<RouteRule>, strip_prefix: bool) -> RouteConfig {
+        RouteConfig {
+            rules,
+            trailing_slash: None,
+            strip_prefix,
+        }
+    }
+
+    /// URI the function is invoked with, or `None` for a redirect.
+    fn delivered(routes: &RouteConfig, mount: &str, uri: &str) -> Option<String> {
+        let uri: Uri = uri.parse().unwrap();
+        match apply_routes(routes, mount, &uri) {
+            RouteOutcome::Continue => Some(uri.to_string()),
+            RouteOutcome::Rewrite(rewritten) => Some(rewritten.to_string()),
+            RouteOutcome::Redirect(_) => None,
+        }
+    }
+
+    fn location(routes: &RouteConfig, mount: &str, uri: &str) -> String {
+        match apply_routes(routes, mount, &uri.parse().unwrap()) {
+            RouteOutcome::Redirect(response) => response.headers()[header::LOCATION]
+                .to_str()
+                .unwrap()
+                .to_string(),
+            outcome => panic!("expected a redirect, got {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn path_below_mount_strips_only_whole_segments() {
+        assert_eq!(path_below_mount("/hello/world", "/hello"), "/world");
+        assert_eq!(path_below_mount("/hello", "/hello"), "/");
+        assert_eq!(path_below_mount("/hello/", "/hello"), "/");
+        assert_eq!(path_below_mount("/helloworld", "/hello"), "/helloworld");
+        assert_eq!(path_below_mount("/world", ""), "/world");
+    }
+
+    #[test]
+    fn prefix_is_kept_by_default() {
+        let routes = routes(Vec::new(), false);
+        assert_eq!(
+            delivered(&routes, "/hello", "/hello/a?b=c").unwrap(),
+            "/hello/a?b=c"
+        );
+        assert_eq!(delivered(&routes, "", "/a?b=c").unwrap(), "/a?b=c");
+    }
+
+    #[test]
+    fn strip_prefix_delivers_the_subdomain_path() {
+        let routes = routes(Vec::new(), true);
+        for (mount, uri) in [("/hello", "/hello/a?b=c"), ("", "/a?b=c")] {
+            assert_eq!(delivered(&routes, mount, uri).unwrap(), "/a?b=c");
+        }
+        assert_eq!(delivered(&routes, "/hello", "/hello").unwrap(), "/");
+    }
+
+    #[test]
+    fn rewrites_follow_strip_prefix() {
+        let rules = vec![rule("/old/*", "/new/*", RouteAction::Rewrite)];
+        let kept = routes(rules.clone(), false);
+        let stripped = routes(rules, true);
+        assert_eq!(
+            delivered(&kept, "/hello", "/hello/old/x?q=1").unwrap(),
+            "/hello/new/x?q=1"
+        );
+        assert_eq!(
+            delivered(&stripped, "/hello", "/hello/old/x?q=1").unwrap(),
+            "/new/x?q=1"
+        );
+        assert_eq!(delivered(&kept, "", "/old/x").unwrap(), "/new/x");
+        assert_eq!(delivered(&stripped, "", "/old/x").unwrap(), "/new/x");
+    }
+
+    #[test]
+    fn redirects_stay_under_the_mount() {
+        for strip_prefix in [false, true] {
+            let routes = routes(
+                vec![rule("/docs", "/guide", RouteAction::Redirect(301))],
+                strip_prefix,
+            );
+            assert_eq!(
+                location(&routes, "/hello", "/hello/docs?x=1"),
+                "/hello/guide?x=1"
+            );
+            assert_eq!(location(&routes, "", "/docs"), "/guide");
+        }
+    }
+
+    #[test]
+    fn absolute_destinations_only_redirect() {
+        let routes = routes(
+            vec![
+                rule("/ext", "https://example.com/", RouteAction::Rewrite),
+                rule("/away", "https://example.com/*", RouteAction::Redirect(302)),
+            ],
+            false,
+        );
+        assert_eq!(delivered(&routes, "", "/ext").unwrap(), "/ext");
+        assert_eq!(location(&routes, "", "/away"), "https://example.com/");
+    }
+
+    #[test]
+    fn relative_redirects_stay_on_the_origin() {
+        let mut never = routes(Vec::new(), false);
+        never.trailing_slash = Some(TrailingSlash::Never);
+        assert_eq!(location(&never, "", "//evil.com/"), "/evil.com");
+        assert_eq!(location(&never, "", "///evil.com/x/"), "/evil.com/x");
+        assert_eq!(
+            location(&never, "/hello", "/hello//evil.com/"),
+            "/hello//evil.com"
+        );
+
+        let mut always = routes(Vec::new(), false);
+        always.trailing_slash = Some(TrailingSlash::Always);
+        assert_eq!(location(&always, "", "//evil"), "/evil/");
+
+        let splat = routes(vec![rule("/go/*", "/*", RouteAction::Redirect(302))], false);
+        assert_eq!(location(&splat, "", "/go//evil.com"), "/evil.com");
+        assert_eq!(same_origin("/\\evil.com"), "/evil.com");
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let routes = routes(
+            vec![
+                rule("/a/*", "/first/*", RouteAction::Rewrite),
+                rule("/a/b", "/second", RouteAction::Rewrite),
+            ],
+            false,
+        );
+        assert_eq!(delivered(&routes, "", "/a/b").unwrap(), "/first/b");
+    }
+
+    #[test]
+    fn trailing_slash_redirects_before_rules() {
+        let mut routes = routes(vec![rule("/docs/", "/guide", RouteAction::Rewrite)], true);
+        routes.trailing_slash = Some(TrailingSlash::Never);
+        assert_eq!(
+            location(&routes, "/hello", "/hello/docs/?v=2"),
+            "/hello/docs?v=2"
+        );
+        routes.trailing_slash = Some(TrailingSlash::Always);
+        assert_eq!(location(&routes, "", "/docs"), "/docs/");
+        assert_eq!(delivered(&routes, "", "/style.css").unwrap(), "/style.css");
+    }
+}
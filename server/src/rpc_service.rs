@@ -1,8 +1,32 @@
+use crate::db::{Tree, record};
+use crate::deploy_lock;
+use crate::encryption;
+use crate::error_pages;
+use crate::function_logs;
+use crate::kv_store::{self, LocalKeyValue};
 use crate::metrics::get_metrics;
+use crate::oci;
+use crate::platform;
+use crate::preinit;
+use crate::preload;
+use crate::publish::{self, ArtifactKind};
+use crate::routes::is_absolute_url;
+use crate::secrets;
+use crate::static_site;
+use crate::traffic;
 use crate::wasi_server::SERVER;
-use faasta_interface::{FunctionError, FunctionInfo, FunctionResult, FunctionService, Metrics};
+use faasta_interface::{
+    ArtifactChunks, DeltaOp, ErrorPage, FunctionConfig, FunctionError, FunctionInfo,
+    FunctionResult, FunctionService, JobStatus, JobSummary, KvKey, KvUsage, LogEntry,
+    MAX_ERROR_PAGE_BYTES, MAX_README_BYTES, MAX_SECRET_BYTES, MAX_SECRETS, Metrics, ResponseHeader,
+    RouteAction, RouteConfig, ServerInfo, SessionInfo, StaticAsset,
+};
+use faasta_interface::{chunking, signing};
+use http::{HeaderName, HeaderValue};
 use std::fs;
-use std::io::Write;
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::Arc;
+use std::time::Instant;
 use tracing::{debug, error, info};
 
 /// Implementation of the FunctionService
@@ -27,17 +51,342 @@ impl FunctionServiceImpl {
 
 // Helper implementation that uses references to avoid cloning
 impl FunctionServiceImpl {
-    pub(crate) async fn publish_impl(
+    pub async fn publish_impl(
         &self,
         artifact_bytes: Vec<u8>,
         name: String,
         github_auth_token: String,
+    ) -> FunctionResult<String> {
+        // Check WASM file size
+        if artifact_bytes.len() > faasta_interface::MAX_WASM_SIZE {
+            return Err(FunctionError::InvalidInput(format!(
+                "Artifact too large. Maximum allowed size is 30MB, but received {} bytes",
+                artifact_bytes.len()
+            )));
+        }
+
+        let _deploy = deploy_lock::acquire(&name)?;
+        let username = self.check_publish_access(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_PUBLISH,
+            &name,
+            &artifact_bytes,
+        )?;
+        self.install_component(&name, &username, &artifact_bytes)
+            .await
+    }
+
+    async fn install_component(
+        &self,
+        name: &str,
+        username: &str,
+        artifact_bytes: &[u8],
+    ) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+
+        // When publishing a new version, clear any existing cache entry
+        if let Some(server) = SERVER.get() {
+            server.remove_from_cache(name).await;
+        }
+
+        // Write to the staging path first
+        let temp_path = publish::staging_path(server, name, ArtifactKind::Component);
+        let mut file = fs::File::create(&temp_path).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to create temp file: {e}"))
+        })?;
+        file.write_all(artifact_bytes)
+            .map_err(|e| FunctionError::InternalError(format!("Failed to write temp file: {e}")))?;
+
+        // Ensure file is flushed to disk
+        file.sync_all()
+            .map_err(|e| FunctionError::InternalError(format!("Failed to sync temp file: {e}")))?;
+        drop(file);
+
+        let pre_initialized = preinit::snapshot(name, &temp_path);
+        encryption::seal_artifact(name, &temp_path).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to encrypt artifact: {e:#}"))
+        })?;
+
+        // Swap the artifact in and commit its metadata as one recoverable step
+        let intent = publish::begin(server, name, username, ArtifactKind::Component)
+            .map_err(|e| FunctionError::InternalError(format!("Failed to start publish: {e}")))?;
+        publish::commit(server, &intent).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to commit publish: {e:#}"))
+        })?;
+
+        // The upload is kept so the function can be rebuilt with other settings later
+        if let Err(e) =
+            publish::retain_original(server, name, pre_initialized.then_some(artifact_bytes))
+        {
+            error!("Failed to keep the original component of '{name}': {e:#}");
+        }
+        let provenance = publish::new_provenance(artifact_bytes, pre_initialized);
+        if let Err(e) = publish::record_provenance(server, name, &provenance) {
+            error!("{e:#}");
+        }
+
+        if pre_initialized {
+            Ok(format!(
+                "Function '{name}' published successfully (pre-initialized)"
+            ))
+        } else {
+            Ok(format!("Function '{name}' published successfully"))
+        }
+    }
+
+    pub async fn upload_chunk_impl(
+        &self,
+        name: String,
+        offset: u64,
+        chunk: Vec<u8>,
+        github_auth_token: String,
+    ) -> FunctionResult<u64> {
+        let server = SERVER.get().unwrap();
+        let _deploy = deploy_lock::acquire(&name)?;
+        let username = self.check_publish_access(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_UPLOAD_CHUNK,
+            &name,
+            &signing::chunk_payload(offset, &chunk),
+        )?;
+
+        let end = offset + chunk.len() as u64;
+        if end > faasta_interface::MAX_WASM_SIZE as u64 {
+            return Err(FunctionError::InvalidInput(format!(
+                "Artifact too large. Maximum allowed size is 30MB, but upload reached {end} bytes"
+            )));
+        }
+
+        let upload_path = publish::upload_path(server, &name);
+        if let Some(parent) = upload_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                FunctionError::InternalError(format!("Failed to create upload directory: {e}"))
+            })?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(offset == 0)
+            .open(&upload_path)
+            .map_err(|e| FunctionError::InternalError(format!("Failed to open upload: {e}")))?;
+        let received = file
+            .metadata()
+            .map_err(|e| FunctionError::InternalError(format!("Failed to read upload: {e}")))?
+            .len();
+        if offset > received {
+            return Err(FunctionError::InvalidInput(format!(
+                "Upload of '{name}' has only received {received} bytes, cannot write at offset {offset}"
+            )));
+        }
+
+        // A retried chunk overwrites whatever part of it arrived the first time
+        file.set_len(offset)
+            .and_then(|_| file.seek(SeekFrom::Start(offset)))
+            .and_then(|_| file.write_all(&chunk))
+            .and_then(|_| file.sync_all())
+            .map_err(|e| FunctionError::InternalError(format!("Failed to write upload: {e}")))?;
+        Ok(end)
+    }
+
+    pub async fn publish_upload_impl(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+        let _deploy = deploy_lock::acquire(&name)?;
+        let username = self.check_publish_access(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_PUBLISH_UPLOAD,
+            &name,
+            &[],
+        )?;
+
+        // The upload is kept until the next upload or garbage collection, so a publish
+        // whose response was lost can be retried.
+        let artifact_bytes = fs::read(publish::upload_path(server, &name)).map_err(|e| {
+            FunctionError::NotFound(format!("No upload in progress for '{name}': {e}"))
+        })?;
+        self.install_component(&name, &username, &artifact_bytes)
+            .await
+    }
+
+    pub async fn artifact_chunks_impl(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<ArtifactChunks> {
+        self.authorize_owner(&name, &github_auth_token).await?;
+        let base = self.deployed_upload(&name)?;
+        Ok(ArtifactChunks {
+            sha256: publish::upload_sha256(&base),
+            chunks: chunking::chunk_refs(&base),
+        })
+    }
+
+    pub async fn publish_delta_impl(
+        &self,
+        name: String,
+        base_sha256: String,
+        ops: Vec<DeltaOp>,
+        sha256: String,
+        github_auth_token: String,
+    ) -> FunctionResult<String> {
+        let _deploy = deploy_lock::acquire(&name)?;
+        let username = self.authorize_owner(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_PUBLISH_DELTA,
+            &name,
+            &signing::delta_payload(&base_sha256, &ops, &sha256),
+        )?;
+
+        let sent = chunking::data_len(&ops);
+        if sent > faasta_interface::UPLOAD_CHUNK_SIZE {
+            return Err(FunctionError::InvalidInput(format!(
+                "Delta too large. At most {} new bytes may be sent at once; upload the artifact instead",
+                faasta_interface::UPLOAD_CHUNK_SIZE
+            )));
+        }
+        let base = self.deployed_upload(&name)?;
+        if publish::upload_sha256(&base) != base_sha256 {
+            return Err(FunctionError::InvalidInput(format!(
+                "The deployed version of '{name}' changed since its chunks were fetched"
+            )));
+        }
+        let artifact_bytes = chunking::apply(&base, &ops, faasta_interface::MAX_WASM_SIZE)
+            .map_err(FunctionError::InvalidInput)?;
+        let actual = publish::upload_sha256(&artifact_bytes);
+        if actual != sha256 {
+            return Err(FunctionError::InvalidInput(format!(
+                "Reassembled artifact has SHA-256 {actual}, expected {sha256}"
+            )));
+        }
+
+        let message = self
+            .install_component(&name, &username, &artifact_bytes)
+            .await?;
+        Ok(format!(
+            "{message} ({sent} of {} bytes uploaded)",
+            artifact_bytes.len()
+        ))
+    }
+
+    /// The component `name` was last published from.
+    fn deployed_upload(&self, name: &str) -> FunctionResult<Vec<u8>> {
+        let server = SERVER.get().unwrap();
+        encryption::read_artifact(name, &publish::original_component(server, name)).map_err(|e| {
+            FunctionError::NotFound(format!("No deployed component for '{name}': {e:#}"))
+        })
+    }
+
+    pub async fn publish_oci_impl(
+        &self,
+        reference: String,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<String> {
+        let _deploy = deploy_lock::acquire(&name)?;
+        let username = self.check_publish_access(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_PUBLISH_OCI,
+            &name,
+            reference.as_bytes(),
+        )?;
+
+        let parsed = oci::Reference::parse(&reference).map_err(|e| {
+            FunctionError::InvalidInput(format!("Invalid OCI reference '{reference}': {e:#}"))
+        })?;
+        let pulled = oci::pull(&parsed).await.map_err(|e| {
+            FunctionError::InvalidInput(format!("Failed to pull '{reference}': {e:#}"))
+        })?;
+        info!(
+            "Pulled '{name}' from {reference} ({})",
+            pulled.manifest_digest
+        );
+        let message = self
+            .install_component(&name, &username, &pulled.component)
+            .await?;
+        Ok(format!("{message} from {}", pulled.manifest_digest))
+    }
+
+    pub async fn publish_static_impl(
+        &self,
+        assets: Vec<StaticAsset>,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+
+        static_site::check_assets(&assets).map_err(FunctionError::InvalidInput)?;
+
+        let _deploy = deploy_lock::acquire(&name)?;
+        let username = self.check_publish_access(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_PUBLISH_STATIC,
+            &name,
+            &signing::encoded_payload(&assets),
+        )?;
+
+        // Stage the new site next to the live one
+        let staging_dir = publish::staging_path(server, &name, ArtifactKind::StaticSite);
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir).map_err(|e| {
+                FunctionError::InternalError(format!(
+                    "Failed to clear {}: {e}",
+                    staging_dir.display()
+                ))
+            })?;
+        }
+        for asset in &assets {
+            let path = staging_dir.join(&asset.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| {
+                    FunctionError::InternalError(format!("Failed to create directory: {e}"))
+                })?;
+            }
+            fs::write(&path, &asset.contents).map_err(|e| {
+                FunctionError::InternalError(format!("Failed to write '{}': {e}", asset.path))
+            })?;
+        }
+
+        server.remove_from_cache(&name).await;
+        let intent = publish::begin(server, &name, &username, ArtifactKind::StaticSite)
+            .map_err(|e| FunctionError::InternalError(format!("Failed to start publish: {e}")))?;
+        publish::commit(server, &intent).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to commit publish: {e:#}"))
+        })?;
+
+        Ok(format!(
+            "Static site '{name}' published successfully ({} files)",
+            assets.len()
+        ))
+    }
+
+    /// Authenticate the caller and make sure they may publish under `name`: either they
+    /// already own it, or it is free and within their project limit. The name is
+    /// registered to them when the publish commits. Returns the authenticated username.
+    async fn check_publish_access(
+        &self,
+        name: &str,
+        github_auth_token: &str,
     ) -> FunctionResult<String> {
         // Use the new combined authentication function
         let server = SERVER.get().unwrap();
         let (username, is_valid) = server
             .github_auth
-            .authenticate_github(&github_auth_token)
+            .authenticate_github(github_auth_token)
             .await
             .map_err(|e| FunctionError::AuthError(format!("Authentication error: {e}")))?;
 
@@ -58,119 +407,47 @@ impl FunctionServiceImpl {
             ));
         }
 
-        // Check WASM file size
-        if artifact_bytes.len() > faasta_interface::MAX_WASM_SIZE {
-            return Err(FunctionError::InvalidInput(format!(
-                "Artifact too large. Maximum allowed size is 30MB, but received {} bytes",
-                artifact_bytes.len()
-            )));
-        }
-
-        // Expect a pre-built WASI HTTP component for the function.
-        let artifact_filename = format!("{name}.wasm");
-        let artifact_path = server.functions_dir.join(&artifact_filename);
-
-        // Check if function already exists
-        if artifact_path.exists() {
-            let entry_result = server.metadata_db.get_function(&name).map_err(|e| {
-                FunctionError::InternalError(format!("Failed to get function metadata: {e}"))
-            })?;
-
-            if let Some(entry_bytes) = entry_result {
-                // Deserialize the function info
-                let function_info = match bincode::decode_from_slice::<FunctionInfo, _>(
-                    &entry_bytes,
-                    bincode::config::standard(),
-                ) {
-                    Ok((info, _)) => info,
-                    Err(e) => {
+        // Ownership comes from the metadata store, not from whether this node serves the
+        // function: a function pinned to other regions, or whose artifact is missing
+        // here, still belongs to its owner
+        let entry = server.metadata_db.get_function(name).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to get function metadata: {e}"))
+        })?;
+        let owner = match entry {
+            Some(entry_bytes) => Some(
+                record::decode::<FunctionInfo>(&entry_bytes)
+                    .map_err(|e| {
                         error!("Failed to deserialize function info: {}", e);
-                        return Err(FunctionError::InternalError(format!(
+                        FunctionError::InternalError(format!(
                             "Failed to deserialize function info: {e}"
-                        )));
-                    }
-                };
-
-                // Check if user owns the function
-                if function_info.owner != username {
-                    return Err(FunctionError::PermissionDenied(
-                        "A function with this name already exists and belongs to another user"
-                            .to_string(),
-                    ));
-                }
-                // Function exists and user owns it - proceed with update
-            } else {
-                // Function exists on disk but not in memory db - this is inconsistent state
-                // Still enforce ownership check through GitHub auth
+                        ))
+                    })?
+                    .owner,
+            ),
+            None => server.github_auth.project_owner(name),
+        };
+        match owner {
+            Some(owner) if owner != username => {
                 return Err(FunctionError::PermissionDenied(
-                    "A function with this name already exists. Please choose a different name."
+                    "A function with this name already exists and belongs to another user"
                         .to_string(),
                 ));
             }
-        } else {
-            // New function - enforce project limit
-            if !server.github_auth.can_upload_project(&username, &name) {
+            // Function exists and user owns it - proceed with update
+            Some(_) => {}
+            None if !server.github_auth.can_upload_project(&username, name) => {
+                // New function - enforce project limit
                 return Err(FunctionError::PermissionDenied(
                     "You have reached the maximum limit of 10 projects".to_string(),
                 ));
             }
-            // Register ownership
-            match server.github_auth.add_project(&username, &name).await {
-                Ok(_) => debug!("Added project '{}' for user '{}'", name, username),
-                Err(e) => {
-                    error!("Failed to add project: {}", e);
-                    return Err(FunctionError::InternalError(format!(
-                        "Failed to add project: {e}"
-                    )));
-                }
-            }
-        }
-
-        // When publishing a new version, clear any existing cache entry
-        if let Some(server) = SERVER.get() {
-            server.remove_from_cache(&name).await;
+            None => {}
         }
 
-        // Create a temporary file path to avoid race conditions
-        let temp_path = artifact_path.with_extension("wasm.tmp");
-
-        // Write to temporary path first
-        let mut file = fs::File::create(&temp_path).map_err(|e| {
-            FunctionError::InternalError(format!("Failed to create temp file: {e}"))
-        })?;
-        file.write_all(&artifact_bytes)
-            .map_err(|e| FunctionError::InternalError(format!("Failed to write temp file: {e}")))?;
-
-        // Ensure file is flushed to disk
-        file.sync_all()
-            .map_err(|e| FunctionError::InternalError(format!("Failed to sync temp file: {e}")))?;
-
-        // Atomically rename to final path
-        fs::rename(&temp_path, &artifact_path)
-            .map_err(|e| FunctionError::InternalError(format!("Failed to commit file: {e}")))?;
-
-        // Create function info with both subdomain and path-based URLs
-        let now = chrono::Utc::now().to_rfc3339();
-        let function_info = FunctionInfo {
-            name: name.clone(),
-            owner: username,
-            published_at: now,
-            usage: format!("https://{name}.faasta.lol or https://faasta.lol/{name}"),
-        };
-
-        // Serialize metadata with bincode
-        let meta =
-            bincode::encode_to_vec(&function_info, bincode::config::standard()).map_err(|e| {
-                FunctionError::InternalError(format!("Failed to serialize function metadata: {e}"))
-            })?;
-        server.metadata_db.put_function(&name, &meta).map_err(|e| {
-            FunctionError::InternalError(format!("Failed to persist function metadata: {e}"))
-        })?;
-
-        Ok(format!("Function '{name}' published successfully"))
+        Ok(username)
     }
 
-    pub(crate) async fn list_functions_impl(
+    pub async fn list_functions_impl(
         &self,
         github_auth_token: String,
     ) -> FunctionResult<Vec<FunctionInfo>> {
@@ -198,11 +475,8 @@ impl FunctionServiceImpl {
                 // Get function info from the functions tree
                 if let Ok(Some(value)) = server.metadata_db.get_function(&project_name) {
                     // Deserialize the function info
-                    match bincode::decode_from_slice::<FunctionInfo, _>(
-                        &value,
-                        bincode::config::standard(),
-                    ) {
-                        Ok((function_info, _)) => {
+                    match record::decode::<FunctionInfo>(&value) {
+                        Ok(function_info) => {
                             user_functions.push(function_info);
                         }
                         Err(e) => {
@@ -216,13 +490,14 @@ impl FunctionServiceImpl {
         Ok(user_functions)
     }
 
-    pub(crate) async fn unpublish_impl(
+    pub async fn unpublish_impl(
         &self,
         name: String,
         github_auth_token: String,
     ) -> FunctionResult<()> {
         info!("Processing unpublish request for function: {name}");
 
+        let _deploy = deploy_lock::acquire(&name)?;
         let server = SERVER.get().unwrap();
         // Use the new combined authentication function
         let (username, is_valid) = server
@@ -242,6 +517,14 @@ impl FunctionServiceImpl {
         }
 
         info!("Authentication successful for user: {username}");
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_UNPUBLISH,
+            &name,
+            &[],
+        )?;
+        self.require_elevation(&username, &github_auth_token)?;
 
         // Check if function exists
         let entry_result = server.metadata_db.get_function(&name).map_err(|e| {
@@ -250,11 +533,8 @@ impl FunctionServiceImpl {
 
         if let Some(entry_bytes) = entry_result {
             // Deserialize the function info
-            let function_info = match bincode::decode_from_slice::<FunctionInfo, _>(
-                &entry_bytes,
-                bincode::config::standard(),
-            ) {
-                Ok((info, _)) => info,
+            let function_info = match record::decode::<FunctionInfo>(&entry_bytes) {
+                Ok(info) => info,
                 Err(e) => {
                     error!("Failed to deserialize function info: {}", e);
                     return Err(FunctionError::InternalError(format!(
@@ -274,9 +554,16 @@ impl FunctionServiceImpl {
                 ));
             }
 
+            // Drop the loaded component and anything cached from it
+            server.remove_from_cache(&name).await;
+
             // Remove known WASI component artifact formats for the function.
-            for extension in ["wasm", "cwasm"] {
-                let artifact_path = server.functions_dir.join(format!("{name}.{extension}"));
+            let artifact_paths = ["wasm", "cwasm"]
+                .map(|extension| server.functions_dir.join(format!("{name}.{extension}")));
+            for artifact_path in artifact_paths
+                .into_iter()
+                .chain([publish::original_path(server, &name)])
+            {
                 if artifact_path.exists() {
                     if let Err(e) = fs::remove_file(&artifact_path) {
                         error!("Failed to remove artifact {}: {e}", artifact_path.display());
@@ -289,6 +576,41 @@ impl FunctionServiceImpl {
                 }
             }
 
+            let site_dir = server.static_site_dir(&name);
+            if site_dir.exists() {
+                if let Err(e) = fs::remove_dir_all(&site_dir) {
+                    error!("Failed to remove static site {}: {e}", site_dir.display());
+                } else {
+                    debug!("Successfully removed static site for function '{name}'");
+                }
+            }
+
+            if let Err(e) = server.remove_sandbox(&name) {
+                error!("Failed to remove sandbox for '{name}': {e:#}");
+            }
+
+            if let Err(e) = server.function_configs.remove(&name) {
+                error!("Failed to remove function config for '{name}': {e}");
+            }
+
+            if let Err(e) = server.secrets.clear(&name).await {
+                error!("Failed to remove secrets for '{name}': {e:#}");
+            }
+
+            if let Err(e) = function_logs::remove(&name) {
+                error!("Failed to remove logs for '{name}': {e:#}");
+            }
+
+            if let Some(kv) = kv_store::opened()
+                && let Err(e) = kv.remove_function(&name)
+            {
+                error!("Failed to remove key-value data for '{name}': {e:#}");
+            }
+
+            if let Err(e) = server.metadata_db.delete(Tree::Artifacts, &name) {
+                error!("Failed to remove the artifact record for '{name}': {e}");
+            }
+
             // Remove metadata from sqlite
             match server.metadata_db.delete_function(&name) {
                 Ok(_) => debug!("Successfully removed metadata for function '{name}'"),
@@ -316,10 +638,7 @@ impl FunctionServiceImpl {
         }
     }
 
-    pub(crate) async fn get_metrics_impl(
-        &self,
-        github_auth_token: String,
-    ) -> FunctionResult<Metrics> {
+    pub async fn get_metrics_impl(&self, github_auth_token: String) -> FunctionResult<Metrics> {
         // Use the new combined authentication function
         let server = SERVER.get().unwrap();
         let (username, is_valid) = server
@@ -335,58 +654,1007 @@ impl FunctionServiceImpl {
         }
 
         // Use the metrics module to get persisted metrics
-        let metrics = get_metrics();
+        let metrics = get_metrics(Some(&username));
 
         Ok(metrics)
     }
-}
 
-// Now implement the trait methods that use the reference-based implementations
-#[bitrpc::async_trait]
-impl FunctionService for FunctionServiceImpl {
-    async fn publish(
+    pub async fn rotate_signing_key_impl(
         &self,
-        artifact_bytes: Vec<u8>,
-        name: String,
         github_auth_token: String,
-    ) -> bitrpc::Result<FunctionResult<String>> {
-        Ok(self
-            .publish_impl(artifact_bytes, name, github_auth_token)
-            .await)
+    ) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+        let (username, is_valid) = server
+            .github_auth
+            .authenticate_github(&github_auth_token)
+            .await
+            .map_err(|e| FunctionError::AuthError(format!("Authentication error: {e}")))?;
+
+        if !is_valid || username.is_empty() {
+            return Err(FunctionError::AuthError(
+                "Invalid GitHub authentication token".to_string(),
+            ));
+        }
+
+        // Replacing an existing key needs a signature from it, and issuing the first one
+        // needs a two-factor elevation, so a leaked GitHub token alone cannot take over
+        // signing.
+        let enrolled = server.request_signing.is_enrolled(&username).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to read signing key: {e}"))
+        })?;
+        let two_factor = server.two_factor.is_enabled(&username).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to read two-factor settings: {e}"))
+        })?;
+        if !enrolled && !two_factor {
+            return Err(FunctionError::PermissionDenied(
+                "Enable two-factor authentication before requesting a signing key".to_string(),
+            ));
+        }
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_ROTATE_SIGNING_KEY,
+            &username,
+            &[],
+        )?;
+        self.require_elevation(&username, &github_auth_token)?;
+        let key = server.request_signing.rotate(&username).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to store signing key: {e}"))
+        })?;
+        info!("Issued a new request-signing key for '{username}'");
+        Ok(hex::encode(key))
     }
 
-    async fn list_functions(
+    pub async fn list_sessions_impl(
         &self,
         github_auth_token: String,
-    ) -> bitrpc::Result<FunctionResult<Vec<FunctionInfo>>> {
-        Ok(self.list_functions_impl(github_auth_token).await)
+    ) -> FunctionResult<Vec<SessionInfo>> {
+        let server = SERVER.get().unwrap();
+        let username = self.authenticate(&github_auth_token).await?;
+        let current = server.github_auth.session_id(&github_auth_token);
+        Ok(server.github_auth.sessions.list(&username, &current))
     }
 
-    async fn unpublish(
+    pub async fn revoke_session_impl(
         &self,
-        name: String,
+        session_id: String,
         github_auth_token: String,
-    ) -> bitrpc::Result<FunctionResult<()>> {
-        Ok(self.unpublish_impl(name, github_auth_token).await)
+    ) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        let username = self.authenticate(&github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_REVOKE_SESSION,
+            &session_id,
+            &[],
+        )?;
+
+        let revoked = server
+            .github_auth
+            .sessions
+            .revoke(&username, &session_id)
+            .map_err(|e| {
+                error!("Failed to revoke session {session_id}: {e:#}");
+                FunctionError::InternalError(format!("Failed to revoke session: {e}"))
+            })?;
+        if !revoked {
+            return Err(FunctionError::NotFound(format!(
+                "Session '{session_id}' not found"
+            )));
+        }
+        info!("Revoked session {session_id} of '{username}'");
+        Ok(())
     }
 
-    async fn get_metrics(
+    pub async fn setup_totp_impl(&self, github_auth_token: String) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+        let username = self.authenticate(&github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_SETUP_TOTP,
+            &username,
+            &[],
+        )?;
+        // Replacing the secret of an enabled second factor needs that factor
+        self.require_elevation(&username, &github_auth_token)?;
+        server.two_factor.setup(&username).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to store two-factor secret: {e}"))
+        })
+    }
+
+    pub async fn confirm_totp_impl(
         &self,
+        code: String,
         github_auth_token: String,
-    ) -> bitrpc::Result<FunctionResult<Metrics>> {
-        Ok(self.get_metrics_impl(github_auth_token).await)
+    ) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        let username = self.authenticate(&github_auth_token).await?;
+        server
+            .two_factor
+            .confirm(&username, &code)
+            .map_err(|e| FunctionError::AuthError(e.to_string()))?;
+        info!("Enabled two-factor authentication for '{username}'");
+        Ok(())
+    }
+
+    pub async fn elevate_impl(
+        &self,
+        code: String,
+        github_auth_token: String,
+    ) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+        let username = self.authenticate(&github_auth_token).await?;
+        server
+            .two_factor
+            .elevate(&username, &code)
+            .map_err(|e| FunctionError::AuthError(e.to_string()))
+    }
+
+    pub async fn disable_totp_impl(&self, github_auth_token: String) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        let username = self.authenticate(&github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_DISABLE_TOTP,
+            &username,
+            &[],
+        )?;
+        self.require_elevation(&username, &github_auth_token)?;
+        server.two_factor.disable(&username).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to disable two-factor: {e}"))
+        })?;
+        info!("Disabled two-factor authentication for '{username}'");
+        Ok(())
+    }
+
+    pub async fn warm_impl(&self, name: String, github_auth_token: String) -> FunctionResult<u64> {
+        let server = SERVER.get().unwrap();
+        self.authorize_owner(&name, &github_auth_token).await?;
+
+        let started = Instant::now();
+        server
+            .warm(&name)
+            .await
+            .map_err(|e| FunctionError::InternalError(format!("Failed to warm function: {e:#}")))?;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        info!("Warmed function '{name}' in {elapsed_ms}ms");
+        Ok(elapsed_ms)
+    }
+
+    /// Job ids are unguessable, so knowing one is enough to read the job's result.
+    pub async fn job_status_impl(&self, job_id: String) -> FunctionResult<JobStatus> {
+        let server = SERVER.get().unwrap();
+        server
+            .jobs
+            .status(&job_id)
+            .map_err(|e| FunctionError::InternalError(format!("Failed to read job: {e:#}")))?
+            .ok_or_else(|| FunctionError::NotFound(format!("Job '{job_id}' not found")))
+    }
+
+    pub async fn list_jobs_impl(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<Vec<JobSummary>> {
+        let server = SERVER.get().unwrap();
+        self.authorize_owner(&name, &github_auth_token).await?;
+        Ok(server.jobs.list(&name))
+    }
+
+    pub async fn get_logs_impl(
+        &self,
+        name: String,
+        since_millis: u64,
+        tail: u32,
+        github_auth_token: String,
+    ) -> FunctionResult<Vec<LogEntry>> {
+        self.authorize_owner(&name, &github_auth_token).await?;
+        Ok(function_logs::read(&name, since_millis, tail as usize))
+    }
+
+    /// The local key-value store, once the caller is known to own function `name`.
+    async fn authorize_kv(
+        &self,
+        name: &str,
+        github_auth_token: &str,
+    ) -> FunctionResult<Arc<LocalKeyValue>> {
+        self.authorize_owner(name, github_auth_token).await?;
+        kv_store::opened().ok_or_else(|| {
+            FunctionError::InvalidInput(
+                "This server does not keep key-value data it can inspect (FAASTA_KV_BACKEND is not 'local')"
+                    .to_string(),
+            )
+        })
+    }
+
+    pub async fn get_kv_usage_impl(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<KvUsage> {
+        let kv = self.authorize_kv(&name, &github_auth_token).await?;
+        kv.usage(&name).map_err(|e| {
+            error!("Failed to read key-value usage of '{name}': {e:#}");
+            FunctionError::InternalError(format!("Failed to read key-value usage: {e}"))
+        })
+    }
+
+    pub async fn list_kv_keys_impl(
+        &self,
+        name: String,
+        bucket: String,
+        github_auth_token: String,
+    ) -> FunctionResult<Vec<KvKey>> {
+        let kv = self.authorize_kv(&name, &github_auth_token).await?;
+        kv.keys(&name, &bucket).map_err(|e| {
+            error!("Failed to list keys of '{name}' bucket '{bucket}': {e:#}");
+            FunctionError::InternalError(format!("Failed to list keys: {e}"))
+        })
+    }
+
+    pub async fn get_kv_value_impl(
+        &self,
+        name: String,
+        bucket: String,
+        key: String,
+        github_auth_token: String,
+    ) -> FunctionResult<Option<Vec<u8>>> {
+        let kv = self.authorize_kv(&name, &github_auth_token).await?;
+        kv.get(&name, &bucket, &key).map_err(|e| {
+            error!("Failed to read '{key}' of '{name}' bucket '{bucket}': {e:#}");
+            FunctionError::InternalError(format!("Failed to read the value: {e}"))
+        })
+    }
+
+    pub async fn cancel_job_impl(
+        &self,
+        job_id: String,
+        github_auth_token: String,
+    ) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        let Some(job) = server.jobs.summary(&job_id) else {
+            return Err(FunctionError::NotFound(format!("Job '{job_id}' not found")));
+        };
+        let username = self
+            .authorize_owner(&job.function_name, &github_auth_token)
+            .await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_CANCEL_JOB,
+            &job_id,
+            &[],
+        )?;
+
+        let cancelled = server.jobs.cancel(&job_id).map_err(|e| {
+            error!("Failed to cancel job {job_id}: {e:#}");
+            FunctionError::InternalError(format!("Failed to cancel job: {e}"))
+        })?;
+        if !cancelled {
+            return Err(FunctionError::InvalidInput(format!(
+                "Job '{job_id}' has already finished"
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn get_function_config_impl(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<FunctionConfig> {
+        let server = SERVER.get().unwrap();
+        self.authorize_owner(&name, &github_auth_token).await?;
+        Ok(server.function_configs.get(&name).as_ref().clone())
+    }
+
+    pub async fn set_function_config_impl(
+        &self,
+        name: String,
+        config: FunctionConfig,
+        github_auth_token: String,
+    ) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        let username = self.authorize_owner(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_SET_FUNCTION_CONFIG,
+            &name,
+            &signing::encoded_payload(&config),
+        )?;
+
+        if let Some(mirror) = &config.mirror {
+            if mirror.percent > 100 {
+                return Err(FunctionError::InvalidInput(
+                    "Mirror percentage must be between 0 and 100".to_string(),
+                ));
+            }
+            if mirror.target == name {
+                return Err(FunctionError::InvalidInput(
+                    "A function cannot mirror traffic to itself".to_string(),
+                ));
+            }
+            let owns_target = server
+                .github_auth
+                .get_user_projects(&username)
+                .is_some_and(|projects| projects.contains(&mirror.target));
+            if !owns_target {
+                return Err(FunctionError::PermissionDenied(format!(
+                    "Mirror target '{}' must be one of your functions",
+                    mirror.target
+                )));
+            }
+        }
+
+        if let Some(experiment) = &config.experiment {
+            if experiment.variants.is_empty() {
+                return Err(FunctionError::InvalidInput(
+                    "An experiment needs at least one variant".to_string(),
+                ));
+            }
+            if experiment
+                .variants
+                .iter()
+                .all(|variant| variant.weight == 0)
+            {
+                return Err(FunctionError::InvalidInput(
+                    "At least one variant must have a non-zero weight".to_string(),
+                ));
+            }
+            traffic::check_names(experiment).map_err(FunctionError::InvalidInput)?;
+            let projects = server
+                .github_auth
+                .get_user_projects(&username)
+                .unwrap_or_default();
+            for variant in &experiment.variants {
+                if variant.function != name && !projects.contains(&variant.function) {
+                    return Err(FunctionError::PermissionDenied(format!(
+                        "Variant function '{}' must be one of your functions",
+                        variant.function
+                    )));
+                }
+            }
+        }
+
+        if config.preload.len() > preload::MAX_TARGETS {
+            return Err(FunctionError::InvalidInput(format!(
+                "A function can preload at most {} others",
+                preload::MAX_TARGETS
+            )));
+        }
+        if config.preload.contains(&name) {
+            return Err(FunctionError::InvalidInput(
+                "A function cannot preload itself".to_string(),
+            ));
+        }
+        if !config.preload.is_empty() {
+            let projects = server
+                .github_auth
+                .get_user_projects(&username)
+                .unwrap_or_default();
+            if let Some(target) = config
+                .preload
+                .iter()
+                .find(|target| !projects.contains(target))
+            {
+                return Err(FunctionError::PermissionDenied(format!(
+                    "Preload target '{target}' must be one of your functions"
+                )));
+            }
+        }
+
+        validate_routes(&config.routes)?;
+        validate_response_headers(&config.response_headers)?;
+        validate_error_pages(&config.error_pages)?;
+        if config
+            .readme
+            .as_ref()
+            .is_some_and(|readme| readme.len() > MAX_README_BYTES)
+        {
+            return Err(FunctionError::InvalidInput(format!(
+                "README exceeds {} KiB",
+                MAX_README_BYTES / 1024
+            )));
+        }
+        if let Some(keep_warm) = &config.keep_warm
+            && (keep_warm.start_hour > 23 || keep_warm.end_hour > 23)
+        {
+            return Err(FunctionError::InvalidInput(
+                "Keep-warm hours must be between 0 and 23".to_string(),
+            ));
+        }
+        if !(1..=MAX_JOB_ATTEMPTS).contains(&config.job_retry.max_attempts) {
+            return Err(FunctionError::InvalidInput(format!(
+                "Jobs may be attempted between 1 and {MAX_JOB_ATTEMPTS} times"
+            )));
+        }
+        if let Some(graphql) = &config.graphql
+            && (!graphql.path.starts_with('/')
+                || graphql.path.contains(['?', '#', '*'])
+                || graphql.path.len() > MAX_GRAPHQL_PATH_LEN)
+        {
+            return Err(FunctionError::InvalidInput(format!(
+                "Invalid GraphQL path '{}': use a path starting with '/'",
+                graphql.path
+            )));
+        }
+        if let Some(message) = config
+            .maintenance
+            .as_ref()
+            .and_then(|maintenance| maintenance.message.as_ref())
+            && message.len() > MAX_MAINTENANCE_MESSAGE_LEN
+        {
+            return Err(FunctionError::InvalidInput(format!(
+                "Maintenance message must be at most {MAX_MAINTENANCE_MESSAGE_LEN} characters"
+            )));
+        }
+
+        server.function_configs.put(&name, config).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to persist function config: {e}"))
+        })?;
+        info!("Updated config for function '{name}'");
+        Ok(())
+    }
+
+    pub async fn list_secrets_impl(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> FunctionResult<Vec<String>> {
+        let server = SERVER.get().unwrap();
+        self.authorize_owner(&name, &github_auth_token).await?;
+        server.secrets.names(&name).await.map_err(|e| {
+            error!("Failed to list secrets for '{name}': {e:#}");
+            FunctionError::InternalError(format!("Failed to list secrets: {e}"))
+        })
+    }
+
+    pub async fn set_secret_impl(
+        &self,
+        name: String,
+        key: String,
+        value: String,
+        github_auth_token: String,
+    ) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        let username = self.authorize_owner(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_SET_SECRET,
+            &name,
+            &signing::secret_payload(&key, &value),
+        )?;
+
+        if !secrets::is_valid_name(&key) {
+            return Err(FunctionError::InvalidInput(format!(
+                "'{key}' is not a valid environment variable name"
+            )));
+        }
+        if value.len() > MAX_SECRET_BYTES {
+            return Err(FunctionError::InvalidInput(format!(
+                "Secret values are limited to {MAX_SECRET_BYTES} bytes"
+            )));
+        }
+        let existing =
+            server.secrets.names(&name).await.map_err(|e| {
+                FunctionError::InternalError(format!("Failed to read secrets: {e}"))
+            })?;
+        if existing.len() >= MAX_SECRETS && !existing.contains(&key) {
+            return Err(FunctionError::InvalidInput(format!(
+                "A function can have at most {MAX_SECRETS} secrets"
+            )));
+        }
+
+        server.secrets.set(&name, &key, &value).await.map_err(|e| {
+            error!("Failed to store secret for '{name}': {e:#}");
+            FunctionError::InternalError(format!("Failed to store secret: {e}"))
+        })?;
+        info!("Updated secret '{key}' for function '{name}'");
+        Ok(())
+    }
+
+    pub async fn delete_secret_impl(
+        &self,
+        name: String,
+        key: String,
+        github_auth_token: String,
+    ) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        let username = self.authorize_owner(&name, &github_auth_token).await?;
+        self.verify_signature(
+            &username,
+            &github_auth_token,
+            signing::OP_DELETE_SECRET,
+            &name,
+            key.as_bytes(),
+        )?;
+
+        let removed = server.secrets.remove(&name, &key).await.map_err(|e| {
+            error!("Failed to remove secret for '{name}': {e:#}");
+            FunctionError::InternalError(format!("Failed to remove secret: {e}"))
+        })?;
+        if !removed {
+            return Err(FunctionError::NotFound(format!(
+                "Function '{name}' has no secret named '{key}'"
+            )));
+        }
+        info!("Removed secret '{key}' from function '{name}'");
+        Ok(())
+    }
+
+    /// Check the call's signature from `username`'s signing key, if they have one.
+    fn verify_signature(
+        &self,
+        username: &str,
+        github_auth_token: &str,
+        operation: &str,
+        name: &str,
+        payload: &[u8],
+    ) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        server
+            .request_signing
+            .verify(username, github_auth_token, operation, name, payload)
+            .map_err(|e| FunctionError::AuthError(e.to_string()))
+    }
+
+    /// Require a live elevation for a destructive call, if `username` enabled two-factor.
+    fn require_elevation(&self, username: &str, github_auth_token: &str) -> FunctionResult<()> {
+        let server = SERVER.get().unwrap();
+        server
+            .two_factor
+            .require_elevation(username, github_auth_token)
+            .map_err(|e| FunctionError::AuthError(e.to_string()))
+    }
+
+    /// Username of the token's holder.
+    async fn authenticate(&self, github_auth_token: &str) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+        let (username, is_valid) = server
+            .github_auth
+            .authenticate_github(github_auth_token)
+            .await
+            .map_err(|e| FunctionError::AuthError(format!("Authentication error: {e}")))?;
+
+        if !is_valid || username.is_empty() {
+            return Err(FunctionError::AuthError(
+                "Invalid GitHub authentication token".to_string(),
+            ));
+        }
+        Ok(username)
+    }
+
+    /// Username of the token's holder, if they own function `name`.
+    pub async fn authorize_owner(
+        &self,
+        name: &str,
+        github_auth_token: &str,
+    ) -> FunctionResult<String> {
+        let server = SERVER.get().unwrap();
+        let username = self.authenticate(github_auth_token).await?;
+
+        let entry_bytes = server
+            .metadata_db
+            .get_function(name)
+            .map_err(|e| {
+                FunctionError::InternalError(format!("Failed to get function metadata: {e}"))
+            })?
+            .ok_or_else(|| FunctionError::NotFound(format!("Function '{name}' not found")))?;
+        let function_info = record::decode::<FunctionInfo>(&entry_bytes).map_err(|e| {
+            FunctionError::InternalError(format!("Failed to deserialize function info: {e}"))
+        })?;
+
+        if function_info.owner != username {
+            return Err(FunctionError::PermissionDenied(
+                "You don't have permission to manage this function".to_string(),
+            ));
+        }
+
+        Ok(username)
+    }
+}
+
+// Now implement the trait methods that use the reference-based implementations
+#[bitrpc::async_trait]
+impl FunctionService for FunctionServiceImpl {
+    async fn publish(
+        &self,
+        artifact_bytes: Vec<u8>,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self
+            .publish_impl(artifact_bytes, name, github_auth_token)
+            .await)
+    }
+
+    async fn upload_chunk(
+        &self,
+        name: String,
+        offset: u64,
+        chunk: Vec<u8>,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<u64>> {
+        Ok(self
+            .upload_chunk_impl(name, offset, chunk, github_auth_token)
+            .await)
+    }
+
+    async fn publish_upload(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self.publish_upload_impl(name, github_auth_token).await)
+    }
+
+    async fn rotate_signing_key(
+        &self,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self.rotate_signing_key_impl(github_auth_token).await)
+    }
+
+    async fn list_sessions(
+        &self,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<SessionInfo>>> {
+        Ok(self.list_sessions_impl(github_auth_token).await)
+    }
+
+    async fn revoke_session(
+        &self,
+        session_id: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self
+            .revoke_session_impl(session_id, github_auth_token)
+            .await)
+    }
+
+    async fn setup_totp(
+        &self,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self.setup_totp_impl(github_auth_token).await)
+    }
+
+    async fn confirm_totp(
+        &self,
+        code: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self.confirm_totp_impl(code, github_auth_token).await)
+    }
+
+    async fn elevate(
+        &self,
+        code: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self.elevate_impl(code, github_auth_token).await)
+    }
+
+    async fn disable_totp(&self, github_auth_token: String) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self.disable_totp_impl(github_auth_token).await)
+    }
+
+    async fn server_info(&self) -> bitrpc::Result<ServerInfo> {
+        Ok(ServerInfo {
+            region: platform::region().map(str::to_string),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: faasta_interface::protocol::PROTOCOL_VERSION,
+        })
+    }
+
+    async fn publish_static(
+        &self,
+        assets: Vec<StaticAsset>,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self
+            .publish_static_impl(assets, name, github_auth_token)
+            .await)
+    }
+
+    async fn artifact_chunks(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<ArtifactChunks>> {
+        Ok(self.artifact_chunks_impl(name, github_auth_token).await)
+    }
+
+    async fn publish_delta(
+        &self,
+        name: String,
+        base_sha256: String,
+        ops: Vec<DeltaOp>,
+        sha256: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self
+            .publish_delta_impl(name, base_sha256, ops, sha256, github_auth_token)
+            .await)
+    }
+
+    async fn publish_oci(
+        &self,
+        reference: String,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<String>> {
+        Ok(self
+            .publish_oci_impl(reference, name, github_auth_token)
+            .await)
+    }
+
+    async fn list_functions(
+        &self,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<FunctionInfo>>> {
+        Ok(self.list_functions_impl(github_auth_token).await)
+    }
+
+    async fn unpublish(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self.unpublish_impl(name, github_auth_token).await)
+    }
+
+    async fn get_metrics(
+        &self,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Metrics>> {
+        Ok(self.get_metrics_impl(github_auth_token).await)
+    }
+
+    async fn warm(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<u64>> {
+        Ok(self.warm_impl(name, github_auth_token).await)
+    }
+
+    async fn job_status(&self, job_id: String) -> bitrpc::Result<FunctionResult<JobStatus>> {
+        Ok(self.job_status_impl(job_id).await)
+    }
+
+    async fn list_jobs(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<JobSummary>>> {
+        Ok(self.list_jobs_impl(name, github_auth_token).await)
+    }
+
+    async fn get_logs(
+        &self,
+        name: String,
+        since_millis: u64,
+        tail: u32,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<LogEntry>>> {
+        Ok(self
+            .get_logs_impl(name, since_millis, tail, github_auth_token)
+            .await)
+    }
+
+    async fn get_kv_usage(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<KvUsage>> {
+        Ok(self.get_kv_usage_impl(name, github_auth_token).await)
+    }
+
+    async fn list_kv_keys(
+        &self,
+        name: String,
+        bucket: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<KvKey>>> {
+        Ok(self
+            .list_kv_keys_impl(name, bucket, github_auth_token)
+            .await)
+    }
+
+    async fn get_kv_value(
+        &self,
+        name: String,
+        bucket: String,
+        key: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Option<Vec<u8>>>> {
+        Ok(self
+            .get_kv_value_impl(name, bucket, key, github_auth_token)
+            .await)
+    }
+
+    async fn cancel_job(
+        &self,
+        job_id: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self.cancel_job_impl(job_id, github_auth_token).await)
+    }
+
+    async fn get_function_config(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<FunctionConfig>> {
+        Ok(self.get_function_config_impl(name, github_auth_token).await)
+    }
+
+    async fn set_function_config(
+        &self,
+        name: String,
+        config: FunctionConfig,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self
+            .set_function_config_impl(name, config, github_auth_token)
+            .await)
+    }
+
+    async fn list_secrets(
+        &self,
+        name: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<Vec<String>>> {
+        Ok(self.list_secrets_impl(name, github_auth_token).await)
+    }
+
+    async fn set_secret(
+        &self,
+        name: String,
+        key: String,
+        value: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self
+            .set_secret_impl(name, key, value, github_auth_token)
+            .await)
+    }
+
+    async fn delete_secret(
+        &self,
+        name: String,
+        key: String,
+        github_auth_token: String,
+    ) -> bitrpc::Result<FunctionResult<()>> {
+        Ok(self.delete_secret_impl(name, key, github_auth_token).await)
+    }
+}
+
+const MAX_ROUTE_RULES: usize = 100;
+const MAX_JOB_ATTEMPTS: u32 = 10;
+
+fn validate_routes(routes: &RouteConfig) -> FunctionResult<()> {
+    if routes.rules.len() > MAX_ROUTE_RULES {
+        return Err(FunctionError::InvalidInput(format!(
+            "At most {MAX_ROUTE_RULES} route rules are allowed"
+        )));
+    }
+    for rule in &routes.rules {
+        let wildcard_count = rule.source.matches('*').count();
+        if !rule.source.starts_with('/')
+            || wildcard_count > 1
+            || (wildcard_count == 1 && !rule.source.ends_with('*'))
+        {
+            return Err(FunctionError::InvalidInput(format!(
+                "Invalid route source '{}': use a path starting with '/' and at most one trailing '*'",
+                rule.source
+            )));
+        }
+        let absolute = is_absolute_url(&rule.destination);
+        if !absolute && !rule.destination.starts_with('/') {
+            return Err(FunctionError::InvalidInput(format!(
+                "Invalid route destination '{}': use a path starting with '/' or an absolute URL",
+                rule.destination
+            )));
+        }
+        match rule.action {
+            RouteAction::Redirect(status) if ![301, 302, 303, 307, 308].contains(&status) => {
+                return Err(FunctionError::InvalidInput(format!(
+                    "Unsupported redirect status {status}"
+                )));
+            }
+            RouteAction::Rewrite if absolute => {
+                return Err(FunctionError::InvalidInput(format!(
+                    "Rewrite destination '{}' must be a path, not a URL",
+                    rule.destination
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+const MAX_RESPONSE_HEADERS: usize = 50;
+
+/// Headers that describe the framing or connection rather than the content and must
+/// stay under the host's control.
+const RESERVED_RESPONSE_HEADERS: &[&str] = &[
+    "connection",
+    "content-length",
+    "keep-alive",
+    "transfer-encoding",
+    "upgrade",
+];
+
+fn validate_response_headers(headers: &[ResponseHeader]) -> FunctionResult<()> {
+    if headers.len() > MAX_RESPONSE_HEADERS {
+        return Err(FunctionError::InvalidInput(format!(
+            "At most {MAX_RESPONSE_HEADERS} response headers are allowed"
+        )));
+    }
+    for header in headers {
+        let Ok(name) = HeaderName::from_bytes(header.name.as_bytes()) else {
+            return Err(FunctionError::InvalidInput(format!(
+                "Invalid header name '{}'",
+                header.name
+            )));
+        };
+        if RESERVED_RESPONSE_HEADERS.contains(&name.as_str()) {
+            return Err(FunctionError::InvalidInput(format!(
+                "Header '{name}' is managed by the platform"
+            )));
+        }
+        if HeaderValue::from_str(&header.value).is_err() {
+            return Err(FunctionError::InvalidInput(format!(
+                "Invalid value for header '{name}'"
+            )));
+        }
+    }
+    Ok(())
+}
+
+const MAX_ERROR_PAGES: usize = 20;
+const MAX_MAINTENANCE_MESSAGE_LEN: usize = 1024;
+const MAX_GRAPHQL_PATH_LEN: usize = 256;
+
+fn validate_error_pages(pages: &[ErrorPage]) -> FunctionResult<()> {
+    if pages.len() > MAX_ERROR_PAGES {
+        return Err(FunctionError::InvalidInput(format!(
+            "At most {MAX_ERROR_PAGES} error pages are allowed"
+        )));
+    }
+    for page in pages {
+        if !error_pages::is_valid_key(&page.status) {
+            return Err(FunctionError::InvalidInput(format!(
+                "Invalid error page status '{}': use a 4xx/5xx code or '4xx'/'5xx'",
+                page.status
+            )));
+        }
+        if page.html.len() > MAX_ERROR_PAGE_BYTES {
+            return Err(FunctionError::InvalidInput(format!(
+                "Error page for {} exceeds {} KiB",
+                page.status,
+                MAX_ERROR_PAGE_BYTES / 1024
+            )));
+        }
     }
+    Ok(())
 }
 
 /// Helper function to create a service implementation with GitHub auth
 pub fn create_service() -> anyhow::Result<FunctionServiceImpl> {
-    use crate::metrics::Timer;
     use tracing::info;
 
     info!("Initializing RPC service...");
-    let rpc_init_timer = Timer::new("rpc_service_initialization".to_string());
     let service = FunctionServiceImpl::new()?;
-    drop(rpc_init_timer); // Explicitly drop to record timing
     info!("RPC service initialization complete");
 
     Ok(service)
@@ -0,0 +1,159 @@
+//! Weighted fair sharing of guest CPU time between function owners.
+//!
+//! Running guests are interrupted every epoch tick, and each interruption charges the
+//! tick to the function's owner. An owner's charge divided by its weight is its virtual
+//! time. When an owner gets more than `MAX_LEAD` of virtual time ahead of the slowest
+//! owner still competing for CPU, its guests are parked at their next tick until the
+//! others catch up, so one hot or spinning function cannot starve other tenants. Owners
+//! whose guests have not run for `CONTENTION_WINDOW` are not competing, and rejoin level
+//! with the competition, with neither banked credit nor debt.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How far, in weighted CPU time, an owner may run ahead of the slowest competitor.
+const MAX_LEAD: Duration = Duration::from_millis(50);
+/// Owners whose guests last ran longer ago than this are not competing for CPU.
+const CONTENTION_WINDOW: Duration = Duration::from_millis(50);
+/// How often a parked guest checks whether it may run again.
+const PARK_POLL: Duration = Duration::from_millis(5);
+
+#[derive(Default)]
+pub struct CpuScheduler {
+    /// Operator-assigned weights; owners not listed have weight 1
+    weights: HashMap<String, u32>,
+    /// CPU time a single invocation may use before it is aborted
+    budget: Option<Duration>,
+    accounts: Mutex<HashMap<String, Account>>,
+}
+
+struct Account {
+    /// Charged CPU time divided by the owner's weight, in nanoseconds
+    virtual_nanos: u128,
+    last_ran: Instant,
+}
+
+impl CpuScheduler {
+    pub fn new(weights: HashMap<String, u32>, budget: Option<Duration>) -> Self {
+        Self {
+            weights,
+            budget,
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn weight(&self, owner: &str) -> u32 {
+        self.weights.get(owner).copied().unwrap_or(1).max(1)
+    }
+
+    pub fn budget(&self) -> Option<Duration> {
+        self.budget
+    }
+
+    /// Charge `cpu` to `owner` and report whether its guests should be parked.
+    pub fn charge(&self, owner: &str, cpu: Duration) -> bool {
+        let now = Instant::now();
+        let mut accounts = lock(&self.accounts);
+        let floor = competition_floor(&accounts, owner, now);
+        let charge = cpu.as_nanos() / u128::from(self.weight(owner));
+        let account = accounts.entry(owner.to_string()).or_insert(Account {
+            virtual_nanos: floor.unwrap_or(0),
+            last_ran: now,
+        });
+        if let Some(floor) = floor
+            && now.duration_since(account.last_ran) > CONTENTION_WINDOW
+        {
+            // Owners returning from idle start level with the competition
+            account.virtual_nanos = floor;
+        }
+        account.virtual_nanos += charge;
+        account.last_ran = now;
+        is_ahead(account.virtual_nanos, floor)
+    }
+
+    /// Wait until `owner` is no longer ahead of the competition.
+    pub async fn wait_turn(self: Arc<Self>, owner: String) {
+        loop {
+            tokio::time::sleep(PARK_POLL).await;
+            let accounts = lock(&self.accounts);
+            let Some(account) = accounts.get(&owner) else {
+                return;
+            };
+            let floor = competition_floor(&accounts, &owner, Instant::now());
+            if !is_ahead(account.virtual_nanos, floor) {
+                return;
+            }
+        }
+    }
+}
+
+/// The lowest virtual time among other owners competing for CPU.
+fn competition_floor(
+    accounts: &HashMap<String, Account>,
+    owner: &str,
+    now: Instant,
+) -> Option<u128> {
+    accounts
+        .iter()
+        .filter(|(name, account)| {
+            name.as_str() != owner && now.duration_since(account.last_ran) <= CONTENTION_WINDOW
+        })
+        .map(|(_, account)| account.virtual_nanos)
+        .min()
+}
+
+fn is_ahead(virtual_nanos: u128, floor: Option<u128>) -> bool {
+    floor.is_some_and(|floor| virtual_nanos > floor + MAX_LEAD.as_nanos())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    /// Ticks `owner` can be charged before it is parked, with `other` just charged once.
+    fn ticks_until_parked(scheduler: &CpuScheduler, owner: &str, other: &str) -> u32 {
+        scheduler.charge(other, TICK);
+        (1..=1000)
+            .find(|_| scheduler.charge(owner, TICK))
+            .expect("owner was never parked")
+    }
+
+    #[test]
+    fn lone_owner_is_never_parked() {
+        let scheduler = CpuScheduler::default();
+        assert!((0..1000).all(|_| !scheduler.charge("alice", TICK)));
+    }
+
+    #[test]
+    fn owner_ahead_of_the_competition_is_parked() {
+        let scheduler = CpuScheduler::default();
+        assert_eq!(ticks_until_parked(&scheduler, "alice", "bob"), 6);
+    }
+
+    #[test]
+    fn weight_scales_the_share() {
+        let scheduler = CpuScheduler::new(HashMap::from([("alice".to_string(), 4)]), None);
+        assert_eq!(ticks_until_parked(&scheduler, "alice", "bob"), 21);
+        assert_eq!(scheduler.weight("bob"), 1);
+    }
+
+    #[test]
+    fn newcomer_starts_level_with_the_competition() {
+        let scheduler = CpuScheduler::default();
+        for _ in 0..100 {
+            scheduler.charge("alice", TICK);
+        }
+        // Bob joins at Alice's virtual time, so Alice isn't parked for her past usage
+        assert!(!scheduler.charge("bob", TICK));
+        assert!(!scheduler.charge("alice", TICK));
+    }
+}
@@ -0,0 +1,434 @@
+//! Per-function secrets, handed to the guest as environment variables.
+//!
+//! Secrets live in a [`SecretsProvider`] chosen with `FAASTA_SECRETS_BACKEND`: the
+//! metadata store, encrypted with `FAASTA_SECRETS_KEY` (`local`, the default),
+//! HashiCorp Vault's KV v2 engine (`vault`) or AWS Secrets Manager (`aws`). The last
+//! two keep secret values out of the faasta database entirely.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use aws_sdk_secretsmanager::config::{
+    BehaviorVersion, Credentials as AwsCredentials, Region as AwsRegion,
+};
+use dashmap::DashMap;
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+use reqwest::{Client as HttpClient, StatusCode};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::db::{MetadataStore, Tree};
+
+/// How long a function's secrets are reused before the provider is asked again, so
+/// changes made directly in Vault or AWS are picked up without a restart.
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// A function's secrets, by environment variable name.
+pub type SecretMap = BTreeMap<String, String>;
+
+/// A function's secrets as guest environment variables
+type SecretEnv = Arc<Vec<(String, String)>>;
+
+/// Storage for per-function secrets.
+pub trait SecretsProvider: Send + Sync {
+    /// All secrets of a function; empty if it has none.
+    fn load(&self, function: String) -> BoxFuture<'_, Result<SecretMap>>;
+    /// Replace all secrets of a function. An empty map removes them.
+    fn store(&self, function: String, secrets: SecretMap) -> BoxFuture<'_, Result<()>>;
+}
+
+pub struct Secrets {
+    provider: Box<dyn SecretsProvider>,
+    /// When each function's secrets were loaded, and what they were
+    cache: DashMap<String, (Instant, SecretEnv)>,
+    /// Serializes read-modify-write updates
+    write_lock: Mutex<()>,
+}
+
+impl Secrets {
+    pub fn from_env(metadata_db: Arc<dyn MetadataStore>) -> Result<Self> {
+        let backend =
+            std::env::var("FAASTA_SECRETS_BACKEND").unwrap_or_else(|_| "local".to_string());
+        let provider: Box<dyn SecretsProvider> = match backend.as_str() {
+            "local" => Box::new(LocalSecrets::from_env(metadata_db)?),
+            "vault" => Box::new(VaultSecrets::from_env()?),
+            "aws" => Box::new(AwsSecrets::from_env()?),
+            other => bail!("unsupported FAASTA_SECRETS_BACKEND '{other}'"),
+        };
+        Ok(Self::new(provider))
+    }
+
+    pub fn new(provider: Box<dyn SecretsProvider>) -> Self {
+        Self {
+            provider,
+            cache: DashMap::new(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// The environment a function's guest runs with.
+    pub async fn env(&self, function: &str) -> Result<SecretEnv> {
+        if let Some(entry) = self.cache.get(function)
+            && entry.0.elapsed() < CACHE_TTL
+        {
+            return Ok(entry.1.clone());
+        }
+        let env: SecretEnv = Arc::new(
+            self.provider
+                .load(function.to_string())
+                .await?
+                .into_iter()
+                .collect(),
+        );
+        self.cache
+            .insert(function.to_string(), (Instant::now(), env.clone()));
+        Ok(env)
+    }
+
+    /// Names of a function's secrets; values are never returned.
+    pub async fn names(&self, function: &str) -> Result<Vec<String>> {
+        Ok(self
+            .provider
+            .load(function.to_string())
+            .await?
+            .into_keys()
+            .collect())
+    }
+
+    pub async fn set(&self, function: &str, name: &str, value: &str) -> Result<()> {
+        self.update(function, |secrets| {
+            secrets.insert(name.to_string(), value.to_string());
+        })
+        .await
+    }
+
+    /// Remove one secret. Returns whether it existed.
+    pub async fn remove(&self, function: &str, name: &str) -> Result<bool> {
+        let mut removed = false;
+        self.update(function, |secrets| {
+            removed = secrets.remove(name).is_some();
+        })
+        .await?;
+        Ok(removed)
+    }
+
+    /// Remove all of a function's secrets, e.g. when it is unpublished.
+    pub async fn clear(&self, function: &str) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        self.provider
+            .store(function.to_string(), SecretMap::new())
+            .await?;
+        self.cache.remove(function);
+        Ok(())
+    }
+
+    async fn update(&self, function: &str, change: impl FnOnce(&mut SecretMap)) -> Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut secrets = self.provider.load(function.to_string()).await?;
+        change(&mut secrets);
+        self.provider.store(function.to_string(), secrets).await?;
+        self.cache.remove(function);
+        Ok(())
+    }
+}
+
+/// Whether `name` can be used as an environment variable name.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Secrets in the metadata store, sealed with AES-256-GCM. The function name is bound
+/// as associated data, so a record cannot be moved to another function.
+struct LocalSecrets {
+    db: Arc<dyn MetadataStore>,
+    key: Option<LessSafeKey>,
+}
+
+impl LocalSecrets {
+    fn from_env(db: Arc<dyn MetadataStore>) -> Result<Self> {
+        // Without a key, functions simply have no secrets until one is configured
+        let key = match std::env::var("FAASTA_SECRETS_KEY") {
+            Ok(hex_key) => {
+                let bytes = hex::decode(hex_key.trim())
+                    .context("FAASTA_SECRETS_KEY must be 64 hex characters")?;
+                let key = UnboundKey::new(&AES_256_GCM, &bytes)
+                    .map_err(|_| anyhow!("FAASTA_SECRETS_KEY must be 64 hex characters"))?;
+                Some(LessSafeKey::new(key))
+            }
+            Err(_) => None,
+        };
+        Ok(Self { db, key })
+    }
+
+    fn key(&self) -> Result<&LessSafeKey> {
+        self.key
+            .as_ref()
+            .context("FAASTA_SECRETS_KEY is required for FAASTA_SECRETS_BACKEND=local")
+    }
+}
+
+impl SecretsProvider for LocalSecrets {
+    fn load(&self, function: String) -> BoxFuture<'_, Result<SecretMap>> {
+        async move {
+            let Some(sealed) = self.db.get(Tree::Secrets, &function)? else {
+                return Ok(SecretMap::new());
+            };
+            ensure!(
+                sealed.len() > NONCE_LEN,
+                "secrets record for '{function}' is truncated"
+            );
+            let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+            let nonce = Nonce::try_assume_unique_for_key(nonce)
+                .map_err(|_| anyhow!("secrets record for '{function}' is corrupt"))?;
+            let mut buffer = ciphertext.to_vec();
+            let plaintext = self
+                .key()?
+                .open_in_place(nonce, Aad::from(function.as_bytes()), &mut buffer)
+                .map_err(|_| anyhow!("failed to decrypt secrets for '{function}'"))?;
+            serde_json::from_slice(plaintext)
+                .with_context(|| format!("failed to decode secrets for '{function}'"))
+        }
+        .boxed()
+    }
+
+    fn store(&self, function: String, secrets: SecretMap) -> BoxFuture<'_, Result<()>> {
+        async move {
+            if secrets.is_empty() {
+                return self.db.delete(Tree::Secrets, &function);
+            }
+            let nonce: [u8; NONCE_LEN] = rand::random();
+            let mut buffer = serde_json::to_vec(&secrets)?;
+            self.key()?
+                .seal_in_place_append_tag(
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::from(function.as_bytes()),
+                    &mut buffer,
+                )
+                .map_err(|_| anyhow!("failed to encrypt secrets for '{function}'"))?;
+            let mut sealed = nonce.to_vec();
+            sealed.extend_from_slice(&buffer);
+            self.db.put(Tree::Secrets, &function, &sealed)
+        }
+        .boxed()
+    }
+}
+
+#[derive(Deserialize)]
+struct VaultRead {
+    data: VaultData,
+}
+
+#[derive(Deserialize)]
+struct VaultData {
+    data: SecretMap,
+}
+
+/// One KV v2 secret per function at `<mount>/<prefix>/<function>`.
+struct VaultSecrets {
+    client: HttpClient,
+    address: String,
+    token: String,
+    mount: String,
+    prefix: String,
+}
+
+impl VaultSecrets {
+    fn from_env() -> Result<Self> {
+        let address = std::env::var("VAULT_ADDR")
+            .context("VAULT_ADDR is required for FAASTA_SECRETS_BACKEND=vault")?;
+        let token = std::env::var("VAULT_TOKEN")
+            .context("VAULT_TOKEN is required for FAASTA_SECRETS_BACKEND=vault")?;
+        Ok(Self {
+            client: HttpClient::new(),
+            address: address.trim_end_matches('/').to_string(),
+            token,
+            mount: std::env::var("FAASTA_SECRETS_VAULT_MOUNT")
+                .unwrap_or_else(|_| "secret".to_string()),
+            prefix: std::env::var("FAASTA_SECRETS_VAULT_PREFIX")
+                .unwrap_or_else(|_| "faasta".to_string()),
+        })
+    }
+
+    fn url(&self, kind: &str, function: &str) -> String {
+        format!(
+            "{}/v1/{}/{kind}/{}/{function}",
+            self.address, self.mount, self.prefix
+        )
+    }
+}
+
+impl SecretsProvider for VaultSecrets {
+    fn load(&self, function: String) -> BoxFuture<'_, Result<SecretMap>> {
+        async move {
+            let response = self
+                .client
+                .get(self.url("data", &function))
+                .header("X-Vault-Token", &self.token)
+                .send()
+                .await
+                .context("failed to reach Vault")?;
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(SecretMap::new());
+            }
+            let read: VaultRead = response
+                .error_for_status()
+                .context("Vault rejected the secrets read")?
+                .json()
+                .await
+                .context("failed to parse Vault response")?;
+            Ok(read.data.data)
+        }
+        .boxed()
+    }
+
+    fn store(&self, function: String, secrets: SecretMap) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let request = if secrets.is_empty() {
+                // Deleting the metadata removes every version
+                self.client.delete(self.url("metadata", &function))
+            } else {
+                self.client
+                    .post(self.url("data", &function))
+                    .json(&json!({ "data": secrets }))
+            };
+            request
+                .header("X-Vault-Token", &self.token)
+                .send()
+                .await
+                .context("failed to reach Vault")?
+                .error_for_status()
+                .context("Vault rejected the secrets write")?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// One secret per function named `<prefix><function>`, holding a JSON object.
+struct AwsSecrets {
+    client: SecretsManagerClient,
+    prefix: String,
+}
+
+impl AwsSecrets {
+    fn from_env() -> Result<Self> {
+        let access_key = std::env::var("AWS_ACCESS_KEY_ID")
+            .context("AWS_ACCESS_KEY_ID is required for FAASTA_SECRETS_BACKEND=aws")?;
+        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY")
+            .context("AWS_SECRET_ACCESS_KEY is required for FAASTA_SECRETS_BACKEND=aws")?;
+        let region = std::env::var("AWS_REGION")
+            .context("AWS_REGION is required for FAASTA_SECRETS_BACKEND=aws")?;
+
+        let mut config = aws_sdk_secretsmanager::config::Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .credentials_provider(AwsCredentials::new(
+                access_key, secret_key, None, None, "faasta",
+            ))
+            .region(AwsRegion::new(region));
+        if let Ok(endpoint) = std::env::var("FAASTA_SECRETS_AWS_ENDPOINT") {
+            config = config.endpoint_url(endpoint);
+        }
+        Ok(Self {
+            client: SecretsManagerClient::from_conf(config.build()),
+            prefix: std::env::var("FAASTA_SECRETS_AWS_PREFIX")
+                .unwrap_or_else(|_| "faasta/".to_string()),
+        })
+    }
+}
+
+impl SecretsProvider for AwsSecrets {
+    fn load(&self, function: String) -> BoxFuture<'_, Result<SecretMap>> {
+        async move {
+            let secret_id = format!("{}{function}", self.prefix);
+            let output = match self
+                .client
+                .get_secret_value()
+                .secret_id(&secret_id)
+                .send()
+                .await
+            {
+                Ok(output) => output,
+                Err(err)
+                    if err
+                        .as_service_error()
+                        .is_some_and(|err| err.is_resource_not_found_exception()) =>
+                {
+                    return Ok(SecretMap::new());
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| format!("failed to read {secret_id}"));
+                }
+            };
+            let value = output
+                .secret_string()
+                .with_context(|| format!("{secret_id} has no string value"))?;
+            serde_json::from_str(value)
+                .with_context(|| format!("{secret_id} is not a JSON object of strings"))
+        }
+        .boxed()
+    }
+
+    fn store(&self, function: String, secrets: SecretMap) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let secret_id = format!("{}{function}", self.prefix);
+            if secrets.is_empty() {
+                return match self
+                    .client
+                    .delete_secret()
+                    .secret_id(&secret_id)
+                    .force_delete_without_recovery(true)
+                    .send()
+                    .await
+                {
+                    Ok(_) => Ok(()),
+                    Err(err)
+                        if err
+                            .as_service_error()
+                            .is_some_and(|err| err.is_resource_not_found_exception()) =>
+                    {
+                        Ok(())
+                    }
+                    Err(err) => Err(err).with_context(|| format!("failed to delete {secret_id}")),
+                };
+            }
+
+            let value = serde_json::to_string(&secrets)?;
+            match self
+                .client
+                .put_secret_value()
+                .secret_id(&secret_id)
+                .secret_string(&value)
+                .send()
+                .await
+            {
+                Ok(_) => Ok(()),
+                Err(err)
+                    if err
+                        .as_service_error()
+                        .is_some_and(|err| err.is_resource_not_found_exception()) =>
+                {
+                    self.client
+                        .create_secret()
+                        .name(&secret_id)
+                        .secret_string(value)
+                        .send()
+                        .await
+                        .with_context(|| format!("failed to create {secret_id}"))?;
+                    Ok(())
+                }
+                Err(err) => Err(err).with_context(|| format!("failed to update {secret_id}")),
+            }
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, header};
+use axum::middleware::Next;
+
+const CONTENT_SECURITY_POLICY: &str = "default-src 'none'; frame-ancestors 'none'";
+const CORS_ALLOW_METHODS: &str = "GET, POST, OPTIONS";
+const CORS_ALLOW_HEADERS: &str = "authorization, content-type, idempotency-key";
+const CORS_MAX_AGE_SECS: &str = "600";
+
+/// `Strict-Transport-Security` value for `max_age` seconds, or `None` when it is 0.
+/// `preload` opts the domain into browsers' preload lists.
+pub fn hsts_header(max_age: u64, preload: bool) -> Option<HeaderValue> {
+    (max_age > 0).then(|| {
+        let preload = if preload { "; preload" } else { "" };
+        HeaderValue::from_str(&format!("max-age={max_age}; includeSubDomains{preload}"))
+            .expect("HSTS header is valid ASCII")
+    })
+}
+
+/// Security and CORS headers added to the platform's own endpoints. Function
+/// responses are left to their owners.
+pub struct HeaderPolicy {
+    hsts: Option<HeaderValue>,
+    /// Allowed CORS origins; `*` allows any origin.
+    cors_origins: Vec<String>,
+}
+
+impl HeaderPolicy {
+    pub fn new(hsts: Option<HeaderValue>, cors_origins: Vec<String>) -> Self {
+        Self { hsts, cors_origins }
+    }
+
+    fn allowed_origin(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        let origin = headers.get(header::ORIGIN)?;
+        let origin_str = origin.to_str().ok()?;
+        if self.cors_origins.iter().any(|allowed| allowed == "*") {
+            Some(HeaderValue::from_static("*"))
+        } else if self
+            .cors_origins
+            .iter()
+            .any(|allowed| allowed == origin_str)
+        {
+            Some(origin.clone())
+        } else {
+            None
+        }
+    }
+}
+
+pub async fn apply(
+    State(policy): State<Arc<HeaderPolicy>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let allowed_origin = policy.allowed_origin(request.headers());
+    let is_preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = if is_preflight && allowed_origin.is_some() {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static(CORS_ALLOW_METHODS),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            HeaderValue::from_static(CORS_ALLOW_HEADERS),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_MAX_AGE,
+            HeaderValue::from_static(CORS_MAX_AGE_SECS),
+        );
+        response
+    } else {
+        next.run(request).await
+    };
+
+    let headers = response.headers_mut();
+    if let Some(origin) = allowed_origin {
+        if origin != "*" {
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    if let Some(hsts) = &policy.hsts {
+        headers.insert(header::STRICT_TRANSPORT_SECURITY, hsts.clone());
+    }
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static(CONTENT_SECURITY_POLICY),
+    );
+    response
+}
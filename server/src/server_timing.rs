@@ -0,0 +1,254 @@
+//! `Server-Timing` entries reported by functions.
+//!
+//! A function can say where its time went by sending `Server-Timing` in its response
+//! headers or trailers (`faasta::http::ServerTiming` in the SDK). The header reaches
+//! the client untouched, and each entry's duration is added to per-function totals
+//! shown by `cargo faasta metrics` next to the platform's own timings. Totals are kept
+//! in memory since the node started, for a bounded number of names per function.
+//!
+//! Functions that opt in also get the platform's own entries, prefixed `faasta-`: the
+//! response cache's verdict, how long a cold start spent loading the component, and
+//! instantiation and handler time, so cold starts are visible in browser devtools.
+
+use std::collections::BTreeMap;
+
+use dashmap::DashMap;
+use faasta_interface::ServerTimingMetric;
+use http::{HeaderMap, HeaderValue, Response};
+use once_cell::sync::Lazy;
+
+use crate::function_key::MetricKey;
+use crate::response_cache::CACHE_STATUS_HEADER;
+use crate::wasm_function::InvocationTiming;
+
+pub const HEADER: &str = "server-timing";
+/// Distinct entry names tracked per function; later names are ignored
+const MAX_NAMES: usize = 32;
+const MAX_NAME_LEN: usize = 64;
+
+static TOTALS: Lazy<DashMap<MetricKey, BTreeMap<String, Total>>> = Lazy::new(DashMap::new);
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Total {
+    count: u64,
+    micros: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    /// `dur` in milliseconds, if the entry has one
+    pub duration_ms: Option<f64>,
+}
+
+/// Add the durations in a response's `Server-Timing` headers and trailers to the
+/// totals under `metric_key`.
+pub fn record(metric_key: &MetricKey, headers: &HeaderMap, trailers: &HeaderMap) {
+    let entries: Vec<Entry> = headers
+        .get_all(HEADER)
+        .iter()
+        .chain(trailers.get_all(HEADER))
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse)
+        .collect();
+    if entries.is_empty() {
+        return;
+    }
+    let mut totals = TOTALS.entry(metric_key.clone()).or_default();
+    for entry in entries {
+        let Some(duration_ms) = entry.duration_ms else {
+            continue;
+        };
+        if totals.len() >= MAX_NAMES && !totals.contains_key(&entry.name) {
+            continue;
+        }
+        let total = totals.entry(entry.name).or_default();
+        total.count += 1;
+        total.micros = total
+            .micros
+            .saturating_add((duration_ms * 1000.0).round() as u64);
+    }
+}
+
+/// Totals recorded under `metric_key`, by name.
+pub fn snapshot(metric_key: &MetricKey) -> Vec<ServerTimingMetric> {
+    TOTALS
+        .get(metric_key)
+        .map(|totals| {
+            totals
+                .iter()
+                .map(|(name, total)| ServerTimingMetric {
+                    name: name.clone(),
+                    count: total.count,
+                    total_micros: total.micros,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn forget(metric_key: &MetricKey) {
+    TOTALS.remove(metric_key);
+}
+
+/// Append the platform's entries for `response` to its `Server-Timing`. Responses
+/// served from the cache only get the cache entry, as no instance ran for them.
+pub fn annotate<B>(response: &mut Response<B>) {
+    let mut entries = Vec::new();
+    if let Some(status) = response
+        .headers()
+        .get(CACHE_STATUS_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        entries.push(format!("faasta-cache;desc={}", status.to_ascii_lowercase()));
+    }
+    if let Some(timing) = response.extensions().get::<InvocationTiming>() {
+        if let Some(load) = timing.load {
+            entries.push(format!(
+                "faasta-load;dur={:.3};desc=\"cold start\"",
+                millis(load)
+            ));
+        }
+        entries.push(format!(
+            "faasta-instantiate;dur={:.3}",
+            millis(timing.instantiate)
+        ));
+        entries.push(format!("faasta-handler;dur={:.3}", millis(timing.handler)));
+    }
+    if !entries.is_empty()
+        && let Ok(value) = HeaderValue::from_str(&entries.join(", "))
+    {
+        response.headers_mut().append(HEADER, value);
+    }
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Entries of one `Server-Timing` value, e.g. `db;dur=53.2, cache;desc="Cache Read"`.
+/// Malformed entries are skipped.
+pub fn parse(value: &str) -> Vec<Entry> {
+    split_outside_quotes(value, ',')
+        .into_iter()
+        .filter_map(|metric| {
+            let mut params = split_outside_quotes(metric, ';').into_iter();
+            let name = params.next()?.trim();
+            if name.is_empty() || name.len() > MAX_NAME_LEN || !name.bytes().all(is_token) {
+                return None;
+            }
+            let duration_ms = params
+                .filter_map(|param| param.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("dur"))
+                .and_then(|(_, value)| value.trim().trim_matches('"').parse::<f64>().ok())
+                .filter(|duration| duration.is_finite() && *duration >= 0.0);
+            Some(Entry {
+                name: name.to_string(),
+                duration_ms,
+            })
+        })
+        .collect()
+}
+
+fn split_outside_quotes(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, ch) in value.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if ch == separator && !quoted => {
+                parts.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// RFC 9110 token characters
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function_key::FunctionKey;
+
+    fn entry(name: &str, duration_ms: Option<f64>) -> Entry {
+        Entry {
+            name: name.to_string(),
+            duration_ms,
+        }
+    }
+
+    #[test]
+    fn parses_entries() {
+        assert_eq!(
+            parse(r#"db;dur=53.2, cache;desc="Cache, Read";dur=23.2, miss, bad name;dur=1"#),
+            [
+                entry("db", Some(53.2)),
+                entry("cache", Some(23.2)),
+                entry("miss", None),
+            ]
+        );
+        assert_eq!(parse("total;dur=-1"), [entry("total", None)]);
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn totals_headers_and_trailers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER, HeaderValue::from_static("db;dur=1.5, render;dur=2"));
+        let mut trailers = HeaderMap::new();
+        trailers.insert(HEADER, HeaderValue::from_static("db;dur=0.5"));
+        let key = MetricKey::Function(FunctionKey::new("timing-owner", "timing-test"));
+        record(&key, &headers, &trailers);
+
+        let totals = snapshot(&key);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].name, "db");
+        assert_eq!((totals[0].count, totals[0].total_micros), (2, 2000));
+        assert_eq!((totals[1].count, totals[1].total_micros), (1, 2000));
+
+        forget(&key);
+        assert!(snapshot(&key).is_empty());
+    }
+
+    #[test]
+    fn annotates_invocations_and_cache_hits() {
+        let mut response = Response::new(());
+        response.extensions_mut().insert(InvocationTiming {
+            load: Some(std::time::Duration::from_millis(40)),
+            instantiate: std::time::Duration::from_micros(1500),
+            handler: std::time::Duration::from_millis(3),
+            ..InvocationTiming::default()
+        });
+        annotate(&mut response);
+        let names: Vec<_> = parse(response.headers()[HEADER].to_str().unwrap())
+            .into_iter()
+            .map(|entry| (entry.name, entry.duration_ms))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("faasta-load".to_string(), Some(40.0)),
+                ("faasta-instantiate".to_string(), Some(1.5)),
+                ("faasta-handler".to_string(), Some(3.0)),
+            ]
+        );
+
+        let mut cached = Response::new(());
+        cached
+            .headers_mut()
+            .insert(CACHE_STATUS_HEADER, HeaderValue::from_static("HIT"));
+        annotate(&mut cached);
+        assert_eq!(cached.headers()[HEADER], "faasta-cache;desc=hit");
+    }
+}
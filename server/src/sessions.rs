@@ -0,0 +1,164 @@
+//! Auth tokens that have been used with the management API, so users can see what has
+//! access to their account and revoke a leaked token server-side.
+//!
+//! A session is keyed by a hash of its token; the token itself is never stored. A
+//! revoked token is refused before GitHub is asked about it, for as long as the record
+//! is kept. Sessions that go unused for [`IDLE_RETENTION`] are forgotten at startup.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use faasta_interface::SessionInfo;
+use ring::digest::{SHA256, digest};
+use tracing::warn;
+
+use crate::db::{MetadataStore, Tree};
+
+/// How stale a session's last-used time may get before it is written again
+const TOUCH_INTERVAL_SECS: i64 = 60;
+/// How long an unrevoked session is kept without being used
+const IDLE_RETENTION: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+const HINT_LEN: usize = 4;
+
+pub struct Sessions {
+    db: Arc<dyn MetadataStore>,
+    sessions: DashMap<String, Session>,
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
+struct Session {
+    username: String,
+    token_hint: String,
+    /// Unix seconds
+    created_at: i64,
+    last_used_at: i64,
+    revoked_at: Option<i64>,
+}
+
+/// Id of the session `token` belongs to.
+pub fn session_id(token: &str) -> String {
+    hex::encode(&digest(&SHA256, token.as_bytes()).as_ref()[..16])
+}
+
+impl Sessions {
+    pub fn new(db: Arc<dyn MetadataStore>) -> Result<Self> {
+        let sessions = DashMap::new();
+        let cutoff = Utc::now().timestamp() - IDLE_RETENTION.as_secs() as i64;
+        for (id, encoded) in db.scan(Tree::Sessions).context("failed to load sessions")? {
+            match bincode::decode_from_slice::<Session, _>(&encoded, bincode::config::standard()) {
+                Ok((session, _))
+                    if session.revoked_at.is_none() && session.last_used_at < cutoff =>
+                {
+                    db.delete(Tree::Sessions, &id)?;
+                }
+                Ok((session, _)) => {
+                    sessions.insert(id, session);
+                }
+                Err(err) => {
+                    warn!("dropping unreadable session {id}: {err}");
+                    db.delete(Tree::Sessions, &id)?;
+                }
+            }
+        }
+        Ok(Self { db, sessions })
+    }
+
+    pub fn is_revoked(&self, id: &str) -> bool {
+        self.sessions
+            .get(id)
+            .is_some_and(|session| session.revoked_at.is_some())
+    }
+
+    /// Record that `username` authenticated with `token`, whose session is `id`.
+    pub fn touch(&self, id: &str, username: &str, token: &str) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let mut session = self
+            .sessions
+            .entry(id.to_string())
+            .or_insert_with(|| Session {
+                username: username.to_string(),
+                token_hint: hint(token),
+                created_at: now,
+                last_used_at: 0,
+                revoked_at: None,
+            });
+        if session.username == username && now - session.last_used_at < TOUCH_INTERVAL_SECS {
+            return Ok(());
+        }
+        session.username = username.to_string();
+        session.last_used_at = now;
+        let encoded = bincode::encode_to_vec(&*session, bincode::config::standard())?;
+        self.db.put(Tree::Sessions, id, &encoded)
+    }
+
+    /// `username`'s sessions that haven't been revoked, most recently used first.
+    /// `current` is the session of the caller.
+    pub fn list(&self, username: &str, current: &str) -> Vec<SessionInfo> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|entry| entry.username == username && entry.revoked_at.is_none())
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        sessions.sort_by_key(|(_, session)| std::cmp::Reverse(session.last_used_at));
+        sessions
+            .into_iter()
+            .map(|(id, session)| SessionInfo {
+                current: id == current,
+                session_id: id,
+                token_hint: session.token_hint,
+                created_at: rfc3339(session.created_at),
+                last_used_at: rfc3339(session.last_used_at),
+            })
+            .collect()
+    }
+
+    /// Revoke `username`'s session `id`. Returns false if they have no such session.
+    pub fn revoke(&self, username: &str, id: &str) -> Result<bool> {
+        let Some(mut session) = self.sessions.get_mut(id) else {
+            return Ok(false);
+        };
+        if session.username != username || session.revoked_at.is_some() {
+            return Ok(false);
+        }
+        let mut revoked = session.clone();
+        revoked.revoked_at = Some(Utc::now().timestamp());
+        let encoded = bincode::encode_to_vec(&revoked, bincode::config::standard())?;
+        self.db.put(Tree::Sessions, id, &encoded)?;
+        *session = revoked;
+        Ok(true)
+    }
+}
+
+/// The last few characters of a token, enough to tell tokens apart.
+fn hint(token: &str) -> String {
+    let skip = token.chars().count().saturating_sub(HINT_LEN);
+    format!("…{}", token.chars().skip(skip).collect::<String>())
+}
+
+fn rfc3339(unix_secs: i64) -> String {
+    DateTime::<Utc>::from_timestamp(unix_secs, 0)
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_and_hints_reveal_little_of_the_token() {
+        let token = "ghp_0123456789abcdefWXYZ";
+        let id = session_id(token);
+        assert_eq!(id.len(), 32);
+        assert_eq!(id, session_id(token));
+        assert_ne!(id, session_id("ghp_0123456789abcdefWXYy"));
+        assert!(!id.contains("WXYZ"));
+        assert_eq!(hint(token), "…WXYZ");
+        assert_eq!(hint("ab"), "…ab");
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::io::SeekFrom;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use axum::body::Body;
+use bytes::BytesMut;
+use chrono::{DateTime, Utc};
+use faasta_interface::{
+    MAX_STATIC_ASSET_DEPTH, MAX_STATIC_ASSET_PATH_LEN, MAX_STATIC_ASSET_SIZE,
+    MAX_STATIC_SITE_FILES, MAX_STATIC_SITE_SIZE, StaticAsset, error_code,
+};
+use http::{HeaderMap, HeaderValue, Method, Response, StatusCode, header};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::error;
+
+use crate::error_body;
+
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+const REVALIDATE_CACHE_CONTROL: &str = "public, max-age=0, must-revalidate";
+/// Size of the reads a file body is streamed in
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Serve a file from a static-site deployment.
+///
+/// `path` is the request path below the site's mount point. Directories resolve to
+/// their `index.html`, and a root-level `404.html` is used for missing files.
+pub async fn serve(
+    root: &Path,
+    method: &Method,
+    path: &str,
+    headers: &HeaderMap,
+) -> Response<Body> {
+    if method != Method::GET && method != Method::HEAD {
+        let mut response = error_body::response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            error_code::METHOD_NOT_ALLOWED,
+            "Static sites only serve GET and HEAD",
+        );
+        response
+            .headers_mut()
+            .insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+        return response;
+    }
+
+    let Some(relative) = decode_path(path) else {
+        return error_body::response(
+            StatusCode::BAD_REQUEST,
+            error_code::BAD_REQUEST,
+            "Invalid path",
+        );
+    };
+
+    let (file_path, status) = match resolve_file(root, &relative).await {
+        Some(file_path) => (file_path, StatusCode::OK),
+        None => match resolve_file(root, Path::new("404.html")).await {
+            Some(not_found) => (not_found, StatusCode::NOT_FOUND),
+            None => {
+                return error_body::response(
+                    StatusCode::NOT_FOUND,
+                    error_code::NOT_FOUND,
+                    "File not found",
+                );
+            }
+        },
+    };
+
+    // Conditional and range requests are answered from the metadata alone; the file
+    // is only read for the bytes that are sent
+    let metadata = match tokio::fs::metadata(&file_path).await {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            error!("failed to stat {}: {err}", file_path.display());
+            return internal_error();
+        }
+    };
+
+    let total = metadata.len();
+    let modified = metadata.modified().ok();
+    let modified_secs = modified
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let etag = format!("\"{modified_secs:x}-{total:x}\"");
+
+    let mut builder = Response::builder()
+        .header(header::CONTENT_TYPE, content_type(&file_path))
+        .header(header::ETAG, &etag)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, cache_control(&file_path));
+    if let Some(modified) = modified {
+        let modified: DateTime<Utc> = modified.into();
+        builder = builder.header(
+            header::LAST_MODIFIED,
+            modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        );
+    }
+
+    if status == StatusCode::OK && etag_matches(headers, &etag) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| status == StatusCode::OK && if_range_matches(headers, &etag));
+    let (status, start, len) = match range.map(|range| parse_range(range, total)) {
+        None | Some(RangeRequest::Ignored) => (status, 0, total),
+        Some(RangeRequest::Unsatisfiable) => {
+            let mut response = error_body::response(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                error_code::RANGE_NOT_SATISFIABLE,
+                "Requested range is outside the file",
+            );
+            if let Ok(content_range) = HeaderValue::from_str(&format!("bytes */{total}")) {
+                response
+                    .headers_mut()
+                    .insert(header::CONTENT_RANGE, content_range);
+            }
+            return response;
+        }
+        Some(RangeRequest::Satisfiable { start, end }) => {
+            builder = builder.header(
+                header::CONTENT_RANGE,
+                format!("bytes {start}-{end}/{total}"),
+            );
+            (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+        }
+    };
+
+    builder = builder.status(status).header(header::CONTENT_LENGTH, len);
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        match file_body(&file_path, start, len).await {
+            Ok(body) => body,
+            Err(err) => {
+                error!("failed to read {}: {err}", file_path.display());
+                return internal_error();
+            }
+        }
+    };
+    builder.body(body).unwrap_or_else(|_| internal_error())
+}
+
+/// Stream `len` bytes of the file at `path`, starting `start` bytes in.
+async fn file_body(path: &Path, start: u64, len: u64) -> std::io::Result<Body> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let chunks = futures_util::stream::try_unfold(file.take(len), |mut file| async move {
+        let mut chunk = BytesMut::with_capacity(READ_CHUNK);
+        let read = file.read_buf(&mut chunk).await?;
+        Ok::<_, std::io::Error>((read > 0).then(|| (chunk.freeze(), file)))
+    });
+    Ok(Body::from_stream(chunks))
+}
+
+/// Check that a deployment path is a plain relative path that stays inside the site root.
+pub fn is_valid_asset_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Check an uploaded site against the limits on file count, total and per-file size,
+/// and path length and depth, before anything is written to disk. Also rejects paths
+/// that repeat or that are both a file and a directory, which couldn't be laid out.
+pub fn check_assets(assets: &[StaticAsset]) -> Result<(), String> {
+    if assets.is_empty() {
+        return Err("A static site needs at least one file".to_string());
+    }
+    if assets.len() > MAX_STATIC_SITE_FILES {
+        return Err(format!(
+            "Too many files. A static site may contain at most {MAX_STATIC_SITE_FILES} files"
+        ));
+    }
+    let total_size: usize = assets.iter().map(|asset| asset.contents.len()).sum();
+    if total_size > MAX_STATIC_SITE_SIZE {
+        return Err(format!(
+            "Static site too large. Maximum allowed size is 100MB, but received {total_size} bytes"
+        ));
+    }
+
+    let mut files = HashSet::new();
+    let mut directories = HashSet::new();
+    for asset in assets {
+        let path = asset.path.as_str();
+        if path.len() > MAX_STATIC_ASSET_PATH_LEN || !is_valid_asset_path(path) {
+            return Err(format!("Invalid asset path '{path}'"));
+        }
+        if path.split('/').count() > MAX_STATIC_ASSET_DEPTH {
+            return Err(format!(
+                "Asset path '{path}' is nested too deeply (at most {MAX_STATIC_ASSET_DEPTH} levels)"
+            ));
+        }
+        if asset.contents.len() > MAX_STATIC_ASSET_SIZE {
+            return Err(format!(
+                "Asset '{path}' is too large ({} bytes). Files may be at most {} MB",
+                asset.contents.len(),
+                MAX_STATIC_ASSET_SIZE / 1024 / 1024
+            ));
+        }
+        if !files.insert(path) {
+            return Err(format!("Asset path '{path}' appears more than once"));
+        }
+        directories.extend(path.match_indices('/').map(|(index, _)| &path[..index]));
+    }
+    if let Some(path) = files.iter().find(|path| directories.contains(*path)) {
+        return Err(format!(
+            "Asset path '{path}' is both a file and a directory"
+        ));
+    }
+    Ok(())
+}
+
+async fn resolve_file(root: &Path, relative: &Path) -> Option<PathBuf> {
+    let candidate = root.join(relative);
+    let metadata = tokio::fs::metadata(&candidate).await.ok()?;
+    if metadata.is_file() {
+        return Some(candidate);
+    }
+    if metadata.is_dir() {
+        let index = candidate.join("index.html");
+        if tokio::fs::metadata(&index).await.ok()?.is_file() {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Percent-decode a request path and reject anything that could escape the site root.
+fn decode_path(path: &str) -> Option<PathBuf> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(index + 1..index + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+    let relative = decoded.trim_start_matches('/');
+    if relative.is_empty() {
+        return Some(PathBuf::new());
+    }
+    is_valid_asset_path(relative.trim_end_matches('/')).then(|| PathBuf::from(relative))
+}
+
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(|candidate| candidate.trim().trim_start_matches("W/"))
+                .any(|candidate| candidate == "*" || candidate == etag)
+        })
+}
+
+fn if_range_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.trim() == etag)
+}
+
+enum RangeRequest {
+    /// Malformed or multi-range requests are answered with the full body.
+    Ignored,
+    Unsatisfiable,
+    Satisfiable {
+        start: u64,
+        end: u64,
+    },
+}
+
+fn parse_range(range: &str, total: u64) -> RangeRequest {
+    let Some(spec) = range.strip_prefix("bytes=") else {
+        return RangeRequest::Ignored;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Ignored;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Ignored;
+    };
+
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(total.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, total.saturating_sub(1)),
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return RangeRequest::Unsatisfiable;
+            }
+            (total.saturating_sub(suffix), total.saturating_sub(1))
+        }
+        _ => return RangeRequest::Ignored,
+    };
+    if total == 0 || start >= total {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Satisfiable { start, end }
+}
+
+/// Fingerprinted assets (`app.3f9a1c2e.js`, `index-BqR3x9aZ.css`) never change under
+/// the same name, so they can be cached forever; everything else is revalidated.
+fn cache_control(path: &Path) -> &'static str {
+    let is_html = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("html"));
+    let fingerprinted = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| {
+            stem.split(['.', '-', '_']).any(|part| {
+                (8..=32).contains(&part.len())
+                    && part.chars().all(|c| c.is_ascii_alphanumeric())
+                    && part.chars().any(|c| c.is_ascii_digit())
+                    && part.chars().any(|c| c.is_ascii_alphabetic())
+            })
+        });
+    if fingerprinted && !is_html {
+        IMMUTABLE_CACHE_CONTROL
+    } else {
+        REVALIDATE_CACHE_CONTROL
+    }
+}
+
+fn content_type(path: &Path) -> HeaderValue {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    let content_type = match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        _ => "application/octet-stream",
+    };
+    HeaderValue::from_static(content_type)
+}
+
+fn internal_error() -> Response<Body> {
+    error_body::response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        error_code::INTERNAL,
+        "Failed to read the site",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(path: &str, len: usize) -> StaticAsset {
+        StaticAsset {
+            path: path.to_string(),
+            contents: vec![0; len],
+        }
+    }
+
+    #[test]
+    fn accepts_ordinary_sites() {
+        let site = [
+            asset("index.html", 512),
+            asset("css/site.css", 128),
+            asset("css/fonts/a.woff2", 2048),
+        ];
+        assert_eq!(check_assets(&site), Ok(()));
+    }
+
+    #[test]
+    fn rejects_crafted_uploads() {
+        let rejected = |assets: &[StaticAsset]| check_assets(assets).is_err();
+        assert!(rejected(&[]));
+        assert!(rejected(&[asset("../escape.html", 1)]));
+        assert!(rejected(&[asset("/etc/passwd", 1)]));
+        assert!(rejected(&[asset("a\\b.html", 1)]));
+        assert!(rejected(&[asset("big.bin", MAX_STATIC_ASSET_SIZE + 1)]));
+        let deep = vec!["d"; MAX_STATIC_ASSET_DEPTH].join("/") + "/file";
+        assert!(rejected(&[asset(&deep, 1)]));
+        let long = "a".repeat(MAX_STATIC_ASSET_PATH_LEN + 1);
+        assert!(rejected(&[asset(&long, 1)]));
+        assert!(rejected(&[asset("index.html", 1), asset("index.html", 1)]));
+        assert!(rejected(&[asset("docs", 1), asset("docs/index.html", 1)]));
+
+        let many: Vec<_> = (0..=MAX_STATIC_SITE_FILES)
+            .map(|index| asset(&format!("{index}.txt"), 0))
+            .collect();
+        assert!(rejected(&many));
+        let wide: Vec<_> = (0..=MAX_STATIC_SITE_SIZE / MAX_STATIC_ASSET_SIZE)
+            .map(|index| asset(&format!("{index}.bin"), MAX_STATIC_ASSET_SIZE))
+            .collect();
+        assert!(rejected(&wide));
+    }
+
+    #[tokio::test]
+    async fn serves_ranges_and_conditionals_from_disk() {
+        use http_body_util::BodyExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let contents: Vec<u8> = (0..200_000u32).map(|index| index as u8).collect();
+        std::fs::write(root.path().join("data.bin"), &contents).unwrap();
+        let root = root.path();
+        let get = |headers: HeaderMap| async move {
+            let response = serve(root, &Method::GET, "/data.bin", &headers).await;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            (status, headers, body)
+        };
+
+        let (status, full, body) = get(HeaderMap::new()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, contents);
+        let etag = full[header::ETAG].clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=70000-70009"));
+        let (status, range, body) = get(headers).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(range[header::CONTENT_RANGE], "bytes 70000-70009/200000");
+        assert_eq!(body, contents[70_000..70_010]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag);
+        let (status, _, body) = get(headers).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+
+        let head = serve(root, &Method::HEAD, "/data.bin", &HeaderMap::new()).await;
+        assert_eq!(head.headers()[header::CONTENT_LENGTH], "200000");
+    }
+}
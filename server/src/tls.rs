@@ -0,0 +1,232 @@
+//! TLS settings shared by the public and management listeners.
+
+use anyhow::{Context, Result, bail, ensure};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::{ClientHello, ProducesTickets, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{ServerConfig, SupportedCipherSuite, SupportedProtocolVersion};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::crypto;
+
+#[derive(Clone, Debug)]
+pub struct TlsPolicy {
+    /// Refuse TLS 1.2 handshakes
+    pub tls13_only: bool,
+    /// Allowed cipher suites by IANA name; empty keeps the provider's defaults
+    pub cipher_suites: Vec<String>,
+    /// ALPN protocols in order of preference
+    pub alpn_protocols: Vec<String>,
+    /// How long each session ticket key is used; `None` disables session tickets
+    pub ticket_rotation: Option<Duration>,
+}
+
+impl TlsPolicy {
+    /// The installed crypto provider, restricted to the allowed cipher suites.
+    pub fn provider(&self) -> Result<Arc<CryptoProvider>> {
+        let mut provider = CryptoProvider::get_default()
+            .context("no rustls crypto provider is installed")?
+            .as_ref()
+            .clone();
+        if self.cipher_suites.is_empty() {
+            return Ok(Arc::new(provider));
+        }
+        for name in &self.cipher_suites {
+            ensure!(
+                provider
+                    .cipher_suites
+                    .iter()
+                    .any(|suite| suite_name(*suite).eq_ignore_ascii_case(name)),
+                "cipher suite '{name}' is not supported by the crypto provider"
+            );
+        }
+        provider.cipher_suites.retain(|suite| {
+            self.cipher_suites
+                .iter()
+                .any(|name| suite_name(*suite).eq_ignore_ascii_case(name))
+        });
+        Ok(Arc::new(provider))
+    }
+
+    /// Server config presenting `certificate`, optionally requiring client certificates
+    /// accepted by `client_verifier`.
+    pub fn server_config(
+        &self,
+        certificate: Arc<ServerCertificate>,
+        client_verifier: Option<Arc<dyn ClientCertVerifier>>,
+    ) -> Result<Arc<ServerConfig>> {
+        let versions: &[&SupportedProtocolVersion] = if self.tls13_only {
+            &[&rustls::version::TLS13]
+        } else {
+            rustls::DEFAULT_VERSIONS
+        };
+        let builder = ServerConfig::builder_with_provider(self.provider()?)
+            .with_protocol_versions(versions)
+            .context("the allowed cipher suites do not cover the allowed TLS versions")?;
+        let builder = match client_verifier {
+            Some(verifier) => builder.with_client_cert_verifier(verifier),
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_cert_resolver(certificate);
+        config.alpn_protocols = self
+            .alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+        if let Some(interval) = self.ticket_rotation {
+            config.ticketer = Arc::new(RotatingTicketer::new(interval)?);
+        }
+        Ok(Arc::new(config))
+    }
+}
+
+/// The server's certificate and key. The OCSP response stapled to handshakes can be
+/// replaced while the listeners are running.
+#[derive(Debug)]
+pub struct ServerCertificate {
+    key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ServerCertificate {
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Arc<Self>> {
+        let provider =
+            CryptoProvider::get_default().context("no rustls crypto provider is installed")?;
+        let signing_key = provider
+            .key_provider
+            .load_private_key(load_private_key(key_path)?)
+            .with_context(|| format!("unsupported private key in {key_path:?}"))?;
+        let key = CertifiedKey::new(load_certificates(cert_path)?, signing_key);
+        key.keys_match()
+            .with_context(|| format!("{key_path:?} does not match {cert_path:?}"))?;
+        Ok(Arc::new(Self {
+            key: RwLock::new(Arc::new(key)),
+        }))
+    }
+
+    /// The certificate chain, leaf first.
+    pub fn chain(&self) -> Vec<CertificateDer<'static>> {
+        self.current().cert.clone()
+    }
+
+    /// Staple `response` to subsequent handshakes, or stop stapling with `None`.
+    pub fn set_ocsp(&self, response: Option<Vec<u8>>) {
+        let mut key = self.current().as_ref().clone();
+        key.ocsp = response;
+        if let Ok(mut current) = self.key.write() {
+            *current = Arc::new(key);
+        }
+    }
+
+    fn current(&self) -> Arc<CertifiedKey> {
+        match self.key.read() {
+            Ok(key) => key.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+impl ResolvesServerCert for ServerCertificate {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+fn suite_name(suite: SupportedCipherSuite) -> String {
+    format!("{:?}", suite.suite())
+}
+
+/// Session ticket encrypter whose key is replaced every `interval`. Tickets issued
+/// under the previous key are still accepted, so a ticket lives at most two intervals.
+#[derive(Debug)]
+struct RotatingTicketer {
+    interval: Duration,
+    keys: RwLock<TicketKeys>,
+}
+
+#[derive(Debug)]
+struct TicketKeys {
+    current: Arc<dyn ProducesTickets>,
+    previous: Option<Arc<dyn ProducesTickets>>,
+    rotated_at: Instant,
+}
+
+impl RotatingTicketer {
+    fn new(interval: Duration) -> Result<Self> {
+        Ok(Self {
+            interval,
+            keys: RwLock::new(TicketKeys {
+                current: crypto::ticketer()?,
+                previous: None,
+                rotated_at: Instant::now(),
+            }),
+        })
+    }
+
+    fn rotate_if_due(&self) {
+        let due = |keys: &TicketKeys| keys.rotated_at.elapsed() >= self.interval;
+        if !self.keys.read().is_ok_and(|keys| due(&keys)) {
+            return;
+        }
+        let Ok(mut keys) = self.keys.write() else {
+            return;
+        };
+        if !due(&keys) {
+            return;
+        }
+        match crypto::ticketer() {
+            Ok(next) => {
+                keys.previous = Some(std::mem::replace(&mut keys.current, next));
+                keys.rotated_at = Instant::now();
+            }
+            Err(err) => warn!("failed to rotate the session ticket key: {err:#}"),
+        }
+    }
+}
+
+impl ProducesTickets for RotatingTicketer {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.interval.as_secs().try_into().unwrap_or(u32::MAX)
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        self.rotate_if_due();
+        self.keys.read().ok()?.current.encrypt(plain)
+    }
+
+    fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>> {
+        self.rotate_if_due();
+        let keys = self.keys.read().ok()?;
+        keys.current
+            .decrypt(cipher)
+            .or_else(|| keys.previous.as_ref()?.decrypt(cipher))
+    }
+}
+
+pub(crate) fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("failed to open cert file: {path:?}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to read certificates from {path:?}"))?;
+    if certs.is_empty() {
+        bail!("no certificates found in {path:?}");
+    }
+    Ok(certs)
+}
+
+pub(crate) fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("failed to open key file: {path:?}"))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .context("failed to parse private key")?
+        .ok_or_else(|| anyhow::anyhow!("no private key found in {path:?}"))
+}
@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use faasta_interface::{ExperimentConfig, Variant, VariantAssignment, VariantMetric};
+use http::header::COOKIE;
+use http::{HeaderMap, StatusCode};
+use once_cell::sync::Lazy;
+
+use crate::fnv::fnv1a;
+use crate::function_key::MetricKey;
+use crate::latency::Histogram;
+
+pub const VARIANT_HEADER: &str = "x-faasta-variant";
+
+/// Calls per variant, under the metric key of the function running the experiment
+static VARIANT_CALLS: Lazy<DashMap<MetricKey, BTreeMap<String, VariantCalls>>> =
+    Lazy::new(DashMap::new);
+
+#[derive(Clone, Debug, Default)]
+struct VariantCalls {
+    latency: Histogram,
+    server_errors: u64,
+}
+
+/// Variant chosen for a request, and the cookie to set if the assignment is new.
+pub struct VariantSelection<'a> {
+    pub variant: &'a Variant,
+    pub set_cookie: Option<String>,
+}
+
+/// Assign a request to one of the experiment's variants.
+///
+/// A header or cookie whose value names a variant pins the client to it. Otherwise the
+/// assignment key is hashed onto the weighted variants, so the same client keeps landing
+/// on the same variant; clients without a key are assigned at random.
+pub fn select_variant<'a>(
+    experiment: &'a ExperimentConfig,
+    headers: &HeaderMap,
+    client_ip: Option<IpAddr>,
+) -> Option<VariantSelection<'a>> {
+    let total_weight: u64 = experiment
+        .variants
+        .iter()
+        .map(|variant| u64::from(variant.weight))
+        .sum();
+    if total_weight == 0 {
+        return None;
+    }
+
+    let key = match &experiment.assignment {
+        VariantAssignment::Header(name) => headers
+            .get(name.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned),
+        VariantAssignment::Cookie(name) => cookie_value(headers, name),
+        VariantAssignment::ClientIp => client_ip.map(|ip| ip.to_string()),
+    };
+
+    if let Some(key) = &key
+        && let Some(variant) = experiment.variants.iter().find(|v| &v.name == key)
+    {
+        return Some(VariantSelection {
+            variant,
+            set_cookie: None,
+        });
+    }
+
+    let point = match &key {
+        Some(key) => fnv1a(key.as_bytes()) % total_weight,
+        None => rand::random_range(0..total_weight),
+    };
+    let mut cumulative = 0;
+    let variant = experiment.variants.iter().find(|variant| {
+        cumulative += u64::from(variant.weight);
+        point < cumulative
+    })?;
+
+    let set_cookie = match &experiment.assignment {
+        VariantAssignment::Cookie(name) => Some(format!(
+            "{name}={}; Path=/; Max-Age=2592000; SameSite=Lax",
+            variant.name
+        )),
+        _ => None,
+    };
+
+    Some(VariantSelection {
+        variant,
+        set_cookie,
+    })
+}
+
+/// Check that variant names are unique, and that they and the assignment cookie's name
+/// are RFC 6265 tokens. Both go into `Set-Cookie` as they are, so anything else could
+/// add attributes to the cookie, e.g. a variant named `a; Domain=faasta.lol`.
+pub fn check_names(experiment: &ExperimentConfig) -> Result<(), String> {
+    if let VariantAssignment::Cookie(name) = &experiment.assignment
+        && !is_token(name)
+    {
+        return Err(format!("Invalid cookie name '{name}'"));
+    }
+    for (index, variant) in experiment.variants.iter().enumerate() {
+        if !is_token(&variant.name) {
+            return Err(format!(
+                "Invalid variant name '{}'. Use letters, digits and !#$%&'*+-.^_`|~",
+                variant.name
+            ));
+        }
+        if experiment.variants[..index]
+            .iter()
+            .any(|other| other.name == variant.name)
+        {
+            return Err(format!("Duplicate variant name '{}'", variant.name));
+        }
+    }
+    Ok(())
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Count one call that the experiment of the function under `metric_key` sent to
+/// `variant`.
+pub fn record(metric_key: &MetricKey, variant: &str, status: StatusCode, duration: Duration) {
+    let mut variants = VARIANT_CALLS.entry(metric_key.clone()).or_default();
+    let calls = variants.entry(variant.to_string()).or_default();
+    calls.latency.record(duration);
+    if status.is_server_error() {
+        calls.server_errors += 1;
+    }
+}
+
+pub fn snapshot(metric_key: &MetricKey) -> Vec<VariantMetric> {
+    VARIANT_CALLS
+        .get(metric_key)
+        .map(|variants| {
+            variants
+                .iter()
+                .map(|(name, calls)| VariantMetric {
+                    name: name.clone(),
+                    server_errors: calls.server_errors,
+                    latency: calls.latency.distribution(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn forget(metric_key: &MetricKey) {
+    VARIANT_CALLS.remove(metric_key);
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn experiment(assignment: VariantAssignment, weights: &[(&str, u32)]) -> ExperimentConfig {
+        ExperimentConfig {
+            assignment,
+            variants: weights
+                .iter()
+                .map(|(name, weight)| Variant {
+                    name: name.to_string(),
+                    function: format!("fn-{name}"),
+                    weight: *weight,
+                })
+                .collect(),
+        }
+    }
+
+    fn with_header(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn assigned(experiment: &ExperimentConfig, headers: &HeaderMap, ip: &str) -> String {
+        select_variant(experiment, headers, Some(ip.parse().unwrap()))
+            .unwrap()
+            .variant
+            .name
+            .clone()
+    }
+
+    #[test]
+    fn splits_traffic_by_weight() {
+        let experiment = experiment(
+            VariantAssignment::ClientIp,
+            &[("control", 3), ("treatment", 1), ("off", 0)],
+        );
+        let mut treatment = 0;
+        for index in 0..4000u32 {
+            let ip = std::net::Ipv4Addr::from(0x0a00_0000 + index * 7919).to_string();
+            match assigned(&experiment, &HeaderMap::new(), &ip).as_str() {
+                "treatment" => treatment += 1,
+                "off" => panic!("a zero-weight variant was chosen"),
+                _ => {}
+            }
+        }
+        assert!((800..1200).contains(&treatment), "{treatment}");
+
+        let unweighted = ExperimentConfig {
+            variants: vec![],
+            ..experiment
+        };
+        assert!(select_variant(&unweighted, &HeaderMap::new(), None).is_none());
+    }
+
+    #[test]
+    fn assignments_are_sticky() {
+        let by_ip = experiment(VariantAssignment::ClientIp, &[("a", 1), ("b", 1)]);
+        let first = assigned(&by_ip, &HeaderMap::new(), "203.0.113.9");
+        for _ in 0..10 {
+            assert_eq!(assigned(&by_ip, &HeaderMap::new(), "203.0.113.9"), first);
+        }
+
+        let by_header = experiment(
+            VariantAssignment::Header("x-user".into()),
+            &[("a", 1), ("b", 1)],
+        );
+        let headers = with_header("x-user", "user-42");
+        let first = assigned(&by_header, &headers, "203.0.113.9");
+        assert_eq!(assigned(&by_header, &headers, "198.51.100.1"), first);
+        // A value naming a variant pins the client to it
+        assert_eq!(
+            assigned(&by_header, &with_header("x-user", "b"), "203.0.113.9"),
+            "b"
+        );
+    }
+
+    #[test]
+    fn cookie_assignments_are_remembered() {
+        let experiment = experiment(
+            VariantAssignment::Cookie("ab".into()),
+            &[("a", 1), ("b", 1)],
+        );
+        let fresh = select_variant(&experiment, &HeaderMap::new(), None).unwrap();
+        let cookie = fresh.set_cookie.expect("a new assignment sets the cookie");
+        assert!(cookie.starts_with(&format!("ab={};", fresh.variant.name)));
+
+        let returning = with_header("cookie", &format!("theme=dark; ab={}", fresh.variant.name));
+        let again = select_variant(&experiment, &returning, None).unwrap();
+        assert_eq!(again.variant.name, fresh.variant.name);
+        assert_eq!(again.set_cookie, None);
+    }
+
+    #[test]
+    fn names_must_be_cookie_tokens() {
+        let cookie = |name: &str| VariantAssignment::Cookie(name.into());
+        assert_eq!(
+            check_names(&experiment(cookie("ab"), &[("a", 1), ("b-2.x", 1)])),
+            Ok(())
+        );
+        let rejected = [
+            experiment(cookie("ab"), &[("a; Domain=faasta.lol", 1)]),
+            experiment(cookie("ab"), &[("a=b", 1)]),
+            experiment(cookie("ab"), &[("a,b", 1)]),
+            experiment(cookie("ab"), &[("a b", 1)]),
+            experiment(cookie("ab"), &[("a\tb", 1)]),
+            experiment(cookie("ab"), &[("", 1)]),
+            experiment(cookie("ab"), &[("a", 1), ("a", 1)]),
+            experiment(cookie("ab; Domain=faasta.lol"), &[("a", 1)]),
+            experiment(cookie(""), &[("a", 1)]),
+            experiment(VariantAssignment::ClientIp, &[("a\u{7f}", 1)]),
+        ];
+        for experiment in rejected {
+            assert!(check_names(&experiment).is_err(), "{experiment:?}");
+        }
+    }
+
+    #[test]
+    fn reads_cookies_across_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(COOKIE, HeaderValue::from_static("a=1;b=2"));
+        headers.append(
+            COOKIE,
+            HeaderValue::from_static(" session=xyz ;  ab=treatment"),
+        );
+        assert_eq!(cookie_value(&headers, "b").as_deref(), Some("2"));
+        assert_eq!(cookie_value(&headers, "ab").as_deref(), Some("treatment"));
+        assert_eq!(cookie_value(&headers, "session").as_deref(), Some("xyz"));
+        assert_eq!(cookie_value(&headers, "missing"), None);
+        assert_eq!(cookie_value(&headers, "ab=treatment"), None);
+    }
+
+    #[test]
+    fn records_calls_per_variant() {
+        let key = MetricKey::Function(crate::function_key::FunctionKey::new("ab-owner", "ab"));
+        record(&key, "a", StatusCode::OK, Duration::from_millis(3));
+        record(&key, "a", StatusCode::BAD_GATEWAY, Duration::from_millis(4));
+        record(&key, "b", StatusCode::OK, Duration::from_millis(5));
+
+        let variants = snapshot(&key);
+        assert_eq!(variants.len(), 2);
+        assert_eq!(
+            (variants[0].latency.count, variants[0].server_errors),
+            (2, 1)
+        );
+        assert_eq!(
+            (variants[1].latency.count, variants[1].server_errors),
+            (1, 0)
+        );
+
+        forget(&key);
+        assert!(snapshot(&key).is_empty());
+    }
+}
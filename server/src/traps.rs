@@ -0,0 +1,180 @@
+//! Classification of failed guest invocations.
+//!
+//! Traps and resource limits surface from wasmtime as opaque errors. They are sorted
+//! into a few kinds with their own status and error code, so clients can tell a
+//! function that ran out of memory from one that crashed or ran too long, and the last
+//! one per function is kept with a truncated backtrace for its owner to read in
+//! `cargo faasta metrics`.
+
+use std::fmt;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use faasta_interface::{InvocationFailure, error_code};
+use http::StatusCode;
+use wasmtime::{PoolConcurrencyLimitError, Trap, WasmBacktrace};
+
+/// Backtrace frames kept for the owner
+const MAX_BACKTRACE_FRAMES: usize = 12;
+
+/// Returned by the epoch callback when an invocation uses up its CPU budget.
+#[derive(Debug)]
+pub struct CpuBudgetExceeded {
+    pub budget: Duration,
+}
+
+impl fmt::Display for CpuBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "exceeded the CPU budget of {} ms",
+            self.budget.as_millis()
+        )
+    }
+}
+
+impl std::error::Error for CpuBudgetExceeded {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    CpuBudget,
+    OutOfMemory,
+    StackOverflow,
+    Unreachable,
+    /// Out-of-bounds memory or table access, bad indirect call, ...
+    Crashed,
+    /// No instance slot was free in the pool
+    PoolExhausted,
+}
+
+impl Fault {
+    /// Sort a failed invocation. `memory_denied` is set when the guest tried to grow
+    /// a memory beyond its limit during the invocation.
+    pub fn classify(err: &anyhow::Error, memory_denied: bool) -> Option<Self> {
+        if find::<CpuBudgetExceeded>(err).is_some() {
+            return Some(Fault::CpuBudget);
+        }
+        if find::<PoolConcurrencyLimitError>(err).is_some() {
+            return Some(Fault::PoolExhausted);
+        }
+        let trap = find::<Trap>(err)?;
+        // Guests abort on a failed allocation, which shows up as some other trap
+        if memory_denied {
+            return Some(Fault::OutOfMemory);
+        }
+        Some(match trap {
+            Trap::Interrupt => Fault::CpuBudget,
+            Trap::StackOverflow => Fault::StackOverflow,
+            Trap::UnreachableCodeReached => Fault::Unreachable,
+            _ => Fault::Crashed,
+        })
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            Fault::CpuBudget => StatusCode::GATEWAY_TIMEOUT,
+            Fault::PoolExhausted => StatusCode::SERVICE_UNAVAILABLE,
+            Fault::OutOfMemory | Fault::StackOverflow | Fault::Unreachable | Fault::Crashed => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Fault::CpuBudget => error_code::CPU_BUDGET_EXCEEDED,
+            Fault::OutOfMemory => error_code::OUT_OF_MEMORY,
+            Fault::StackOverflow => error_code::STACK_OVERFLOW,
+            Fault::Unreachable => error_code::FUNCTION_PANICKED,
+            Fault::Crashed => error_code::FUNCTION_TRAPPED,
+            Fault::PoolExhausted => error_code::AT_CAPACITY,
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Fault::CpuBudget => "Function exceeded its CPU time limit",
+            Fault::OutOfMemory => "Function exceeded its memory limit",
+            Fault::StackOverflow => "Function overflowed its stack",
+            Fault::Unreachable => "Function panicked or aborted",
+            Fault::Crashed => "Function crashed",
+            Fault::PoolExhausted => "Server is at capacity, try again shortly",
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Last classified failure of each function.
+#[derive(Default)]
+pub struct FailureLog {
+    entries: DashMap<String, InvocationFailure>,
+}
+
+impl FailureLog {
+    pub fn record(&self, function_name: &str, fault: Fault, err: &anyhow::Error) {
+        let backtrace = find::<WasmBacktrace>(err)
+            .map(format_backtrace)
+            .unwrap_or_default();
+        let failures = self
+            .entries
+            .get(function_name)
+            .map_or(0, |entry| entry.failures);
+        self.entries.insert(
+            function_name.to_string(),
+            InvocationFailure {
+                code: fault.code().to_string(),
+                message: fault.message().to_string(),
+                detail: format!("{err:#}"),
+                backtrace,
+                failed_at: chrono::Utc::now().to_rfc3339(),
+                failures: failures + 1,
+            },
+        );
+    }
+
+    pub fn clear(&self, function_name: &str) {
+        self.entries.remove(function_name);
+    }
+
+    pub fn last(&self, function_name: &str) -> Option<InvocationFailure> {
+        self.entries.get(function_name).map(|entry| entry.clone())
+    }
+}
+
+/// Find `T` in the error, whether it was converted from a `wasmtime::Error` or not.
+fn find<T>(err: &anyhow::Error) -> Option<&T>
+where
+    T: fmt::Display + fmt::Debug + Send + Sync + 'static,
+{
+    err.downcast_ref::<T>()
+        .or_else(|| err.downcast_ref::<wasmtime::Error>()?.downcast_ref::<T>())
+}
+
+/// The innermost frames of a guest backtrace, one per line.
+fn format_backtrace(backtrace: &WasmBacktrace) -> String {
+    let frames = backtrace.frames();
+    let mut lines: Vec<String> = frames
+        .iter()
+        .take(MAX_BACKTRACE_FRAMES)
+        .enumerate()
+        .map(|(index, frame)| {
+            let module = frame.module().name().unwrap_or("<module>");
+            match frame.func_name() {
+                Some(name) => format!("{index}: {module}!{name}"),
+                None => format!("{index}: {module}!<wasm function {}>", frame.func_index()),
+            }
+        })
+        .collect();
+    if frames.len() > MAX_BACKTRACE_FRAMES {
+        lines.push(format!(
+            "... {} more frames",
+            frames.len() - MAX_BACKTRACE_FRAMES
+        ));
+    }
+    lines.join("\n")
+}
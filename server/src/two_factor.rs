@@ -0,0 +1,304 @@
+//! Optional TOTP second factor for destructive management calls.
+//!
+//! Users who enable it must exchange a current code for an elevation token (see
+//! `faasta_interface::elevation`) before unpublishing a function, issuing a signing
+//! key or turning the second factor off. Elevation tokens live in memory only, for
+//! [`ELEVATION_TTL_SECS`], so a restart asks for a new code. After [`MAX_FAILURES`]
+//! wrong codes in a row a user can't elevate for [`LOCKOUT`], which keeps the code
+//! space from being guessed with a stolen GitHub token.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, bail};
+use bincode::{Decode, Encode};
+use dashmap::DashMap;
+use faasta_interface::elevation::{self, ELEVATION_TTL_SECS};
+use faasta_interface::signing;
+use ring::hmac;
+
+use crate::db::{MetadataStore, Tree};
+
+const ISSUER: &str = "Faasta";
+const STEP_SECS: u64 = 30;
+const DIGITS: u32 = 6;
+/// Steps either side of the current one whose codes are accepted, for clock drift
+const SKEW_STEPS: u64 = 1;
+const SECRET_LEN: usize = 20;
+/// Wrong codes in a row after which elevation is locked
+pub const MAX_FAILURES: u32 = 5;
+/// How long elevation stays locked
+pub const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+pub struct TwoFactor {
+    db: Arc<dyn MetadataStore>,
+    /// Elevation token -> holder and expiry
+    elevations: DashMap<String, Elevation>,
+    /// Username -> wrong codes since the last accepted one
+    failures: DashMap<String, Failures>,
+}
+
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    locked_until: Option<Instant>,
+}
+
+struct Elevation {
+    username: String,
+    expires: Instant,
+}
+
+/// Record in `Tree::TwoFactor`, by username
+#[derive(Clone, Debug, Default, Encode, Decode)]
+struct TotpRecord {
+    /// Secret codes are checked against once enabled
+    active: Option<Vec<u8>>,
+    /// Secret from `setup` awaiting confirmation
+    pending: Option<Vec<u8>>,
+    /// Last step a code was accepted for, so a code can't be replayed
+    last_step: u64,
+}
+
+impl TwoFactor {
+    pub fn new(db: Arc<dyn MetadataStore>) -> Self {
+        Self {
+            db,
+            elevations: DashMap::new(),
+            failures: DashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self, username: &str) -> Result<bool> {
+        Ok(self.record(username)?.active.is_some())
+    }
+
+    /// Generate a secret for `username` to confirm; returns its `otpauth://` URI.
+    pub fn setup(&self, username: &str) -> Result<String> {
+        let secret: [u8; SECRET_LEN] = rand::random();
+        let mut record = self.record(username)?;
+        record.pending = Some(secret.to_vec());
+        self.store(username, &record)?;
+        Ok(format!(
+            "otpauth://totp/{ISSUER}:{username}?secret={}&issuer={ISSUER}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}",
+            base32(&secret)
+        ))
+    }
+
+    /// Enable the pending secret if `code` is current for it.
+    pub fn confirm(&self, username: &str, code: &str) -> Result<()> {
+        let mut record = self.record(username)?;
+        let Some(pending) = record.pending.take() else {
+            bail!("no two-factor setup in progress; run setup first");
+        };
+        let Some(step) = matching_step(&pending, code, unix_now(), 0) else {
+            bail!("invalid two-factor code");
+        };
+        record.active = Some(pending);
+        record.last_step = step;
+        self.store(username, &record)
+    }
+
+    /// Exchange a current code for an elevation token. Fails without checking the code
+    /// while the user is locked out.
+    pub fn elevate(&self, username: &str, code: &str) -> Result<String> {
+        let now = Instant::now();
+        // Held until the code is checked, so concurrent guesses are counted one by one
+        let mut failures = self.failures.entry(username.to_string()).or_default();
+        if let Some(until) = failures.locked_until {
+            if until > now {
+                bail!(
+                    "too many invalid two-factor codes; try again in {}s",
+                    (until - now).as_secs() + 1
+                );
+            }
+            *failures = Failures::default();
+        }
+        let mut record = self.record(username)?;
+        let Some(secret) = &record.active else {
+            bail!("two-factor authentication is not enabled");
+        };
+        let Some(step) = matching_step(secret, code, unix_now(), record.last_step) else {
+            failures.count += 1;
+            if failures.count >= MAX_FAILURES {
+                failures.locked_until = Some(now + LOCKOUT);
+            }
+            bail!("invalid or already used two-factor code");
+        };
+        record.last_step = step;
+        self.store(username, &record)?;
+        drop(failures);
+        self.failures.remove(username);
+
+        self.elevations
+            .retain(|_, elevation| elevation.expires > now);
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        self.elevations.insert(
+            token.clone(),
+            Elevation {
+                username: username.to_string(),
+                expires: now + Duration::from_secs(ELEVATION_TTL_SECS),
+            },
+        );
+        Ok(token)
+    }
+
+    /// Check that `github_auth_token` carries a live elevation of `username`, if they
+    /// enabled two-factor authentication.
+    pub fn require_elevation(&self, username: &str, github_auth_token: &str) -> Result<()> {
+        if !self.is_enabled(username)? {
+            return Ok(());
+        }
+        let token = signing::split_token(github_auth_token).0;
+        let elevated = elevation::split_token(token).1.is_some_and(|token| {
+            self.elevations.get(token).is_some_and(|elevation| {
+                elevation.username == username && elevation.expires > Instant::now()
+            })
+        });
+        if !elevated {
+            bail!("this operation requires a two-factor code; pass one with --otp");
+        }
+        Ok(())
+    }
+
+    pub fn disable(&self, username: &str) -> Result<()> {
+        self.elevations
+            .retain(|_, elevation| elevation.username != username);
+        self.failures.remove(username);
+        self.db.delete(Tree::TwoFactor, username)
+    }
+
+    fn record(&self, username: &str) -> Result<TotpRecord> {
+        Ok(match self.db.get(Tree::TwoFactor, username)? {
+            Some(encoded) => bincode::decode_from_slice(&encoded, bincode::config::standard())?.0,
+            None => TotpRecord::default(),
+        })
+    }
+
+    fn store(&self, username: &str, record: &TotpRecord) -> Result<()> {
+        let encoded = bincode::encode_to_vec(record, bincode::config::standard())?;
+        self.db.put(Tree::TwoFactor, username, &encoded)
+    }
+}
+
+/// The step within [`SKEW_STEPS`] of `now` that `code` is valid for, if it is later
+/// than `after`.
+fn matching_step(secret: &[u8], code: &str, now: u64, after: u64) -> Option<u64> {
+    let code: u32 = code.trim().parse().ok()?;
+    let current = now / STEP_SECS;
+    (current.saturating_sub(SKEW_STEPS)..=current + SKEW_STEPS)
+        .filter(|step| *step > after)
+        .find(|step| totp(secret, *step) == code)
+}
+
+/// RFC 6238 code for `step`, with HMAC-SHA1 as authenticator apps expect.
+fn totp(secret: &[u8], step: u64) -> u32 {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let mac = hmac::sign(&key, &step.to_be_bytes());
+    let mac = mac.as_ref();
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        mac[offset] & 0x7f,
+        mac[offset + 1],
+        mac[offset + 2],
+        mac[offset + 3],
+    ]);
+    truncated % 10u32.pow(DIGITS)
+}
+
+/// RFC 4648 base32 without padding, the form `otpauth://` secrets take.
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut encoded = String::new();
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u16::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[usize::from((buffer >> bits) & 0x1f)] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)] as char);
+    }
+    encoded
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        // Last six digits of the SHA1 vectors in RFC 6238 appendix B
+        assert_eq!(totp(RFC_SECRET, 59 / STEP_SECS), 287082);
+        assert_eq!(totp(RFC_SECRET, 1111111109 / STEP_SECS), 81804);
+        assert_eq!(totp(RFC_SECRET, 2000000000 / STEP_SECS), 279037);
+    }
+
+    #[test]
+    fn accepts_neighbouring_steps_once() {
+        let now = 1111111109;
+        let code = format!("{:06}", totp(RFC_SECRET, now / STEP_SECS - 1));
+        assert_eq!(
+            matching_step(RFC_SECRET, &code, now, 0),
+            Some(now / STEP_SECS - 1)
+        );
+        assert_eq!(
+            matching_step(RFC_SECRET, &code, now, now / STEP_SECS - 1),
+            None
+        );
+        assert_eq!(matching_step(RFC_SECRET, "not a code", now, 0), None);
+        let stale = format!("{:06}", totp(RFC_SECRET, now / STEP_SECS - 3));
+        assert_eq!(matching_step(RFC_SECRET, &stale, now, 0), None);
+    }
+
+    #[test]
+    fn locks_out_after_repeated_wrong_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::db::open(crate::db::MetadataBackend::Redb, dir.path()).unwrap();
+        let two_factor = TwoFactor::new(db);
+        two_factor
+            .store(
+                "alice",
+                &TotpRecord {
+                    active: Some(RFC_SECRET.to_vec()),
+                    ..TotpRecord::default()
+                },
+            )
+            .unwrap();
+        let current = format!("{:06}", totp(RFC_SECRET, unix_now() / STEP_SECS));
+        let wrong = format!("{:06}", (current.parse::<u32>().unwrap() + 1) % 1_000_000);
+        for _ in 0..MAX_FAILURES {
+            let err = two_factor.elevate("alice", &wrong).unwrap_err();
+            assert!(err.to_string().contains("invalid"), "{err}");
+        }
+        // Even the right code is refused until the lockout ends
+        let err = two_factor.elevate("alice", &current).unwrap_err();
+        assert!(err.to_string().contains("too many"), "{err}");
+        assert!(two_factor.elevate("bob", &current).is_err());
+
+        two_factor.failures.get_mut("alice").unwrap().locked_until = Some(Instant::now());
+        assert!(two_factor.elevate("alice", &current).is_ok());
+        assert!(!two_factor.failures.contains_key("alice"));
+    }
+
+    #[test]
+    fn encodes_base32() {
+        assert_eq!(base32(b""), "");
+        assert_eq!(base32(b"f"), "MY");
+        assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+}
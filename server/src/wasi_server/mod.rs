@@ -1,35 +1,77 @@
+use std::convert::Infallible;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
 
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result, bail, ensure};
 use axum::body::Body;
 use bytes::Bytes;
+use faasta_interface::{
+    ArtifactHealth, FunctionInfo, InvocationFailure, Priority, ResponseHeader, RuntimeStats,
+};
+use futures_util::StreamExt;
 use http::{HeaderMap, Method, Response, Uri, header::HeaderName, header::HeaderValue};
+use http_body::{Frame, SizeHint};
+use http_body_util::StreamBody;
 use once_cell::sync::OnceCell;
 use tracing::debug;
 
-use crate::db::Database;
+use crate::admission::AdmissionController;
+use crate::chaos;
+use crate::cpu_time;
+use crate::db::{MetadataStore, record};
+use crate::dedicated::DedicatedTier;
+use crate::function_config::FunctionConfigStore;
+use crate::function_key::{FunctionKey, MetricKey};
 use crate::github_auth::GitHubAuth;
-use crate::metrics::Timer;
-use crate::wasm_function::{WasmFunctionRuntime, WasmRequest, WasmResponse, WireHeader};
+use crate::jobs::Jobs;
+use crate::metrics::{self, Timer};
+use crate::platform;
+use crate::preload::{self, Preloader};
+use crate::publish;
+use crate::request_signing::RequestSigning;
+use crate::response_cache::ResponseCache;
+use crate::scheduler::CpuScheduler;
+use crate::secrets::Secrets;
+use crate::server_timing;
+use crate::two_factor::TwoFactor;
+use crate::wasm_function::{
+    InstanceSettings, PoolConfig, WasmFunctionRuntime, WasmRequest, WasmResponse, guest_cpu_time,
+};
 
 pub static SERVER: OnceCell<Arc<FaastaServer>> = OnceCell::new();
 
+static NESTED_SUBDOMAINS: AtomicBool = AtomicBool::new(false);
+
 pub struct FaastaServer {
-    pub metadata_db: Arc<Database>,
+    pub metadata_db: Arc<dyn MetadataStore>,
     pub base_domain: String,
     pub functions_dir: PathBuf,
-    sandbox_root: PathBuf,
+    pub sandbox_root: PathBuf,
     pub github_auth: GitHubAuth,
+    pub function_configs: FunctionConfigStore,
+    pub request_signing: RequestSigning,
+    pub two_factor: TwoFactor,
+    pub response_cache: Arc<ResponseCache>,
+    pub admission: AdmissionController,
+    pub secrets: Secrets,
+    pub jobs: Arc<Jobs>,
+    preloader: Preloader,
     invoker: FunctionInvoker,
 }
 
 impl FaastaServer {
     pub async fn new(
-        metadata_db: Arc<Database>,
+        metadata_db: Arc<dyn MetadataStore>,
         base_domain: String,
         functions_dir: PathBuf,
         invoker: FunctionInvoker,
+        admission: AdmissionController,
+        response_cache: ResponseCache,
+        job_timeout: Duration,
     ) -> Result<Self> {
         if !functions_dir.exists() {
             std::fs::create_dir_all(&functions_dir).with_context(|| {
@@ -45,15 +87,34 @@ impl FaastaServer {
             .with_context(|| format!("failed to create sandbox directory at {:?}", sandbox_root))?;
 
         let github_auth = GitHubAuth::new(metadata_db.clone()).await?;
+        let function_configs = FunctionConfigStore::new(metadata_db.clone())?;
+        let request_signing = RequestSigning::new(metadata_db.clone());
+        let two_factor = TwoFactor::new(metadata_db.clone());
+        let secrets = Secrets::from_env(metadata_db.clone())
+            .context("failed to set up the secrets backend")?;
+        let response_cache = Arc::new(response_cache);
+        response_cache.clone().spawn_periodic_sweep();
+        let jobs = Arc::new(Jobs::new(job_timeout, metadata_db.clone())?);
+        jobs.clone().spawn_periodic_sweep();
 
-        Ok(Self {
+        let server = Self {
             metadata_db,
             base_domain,
             functions_dir,
             sandbox_root,
             github_auth,
+            function_configs,
+            request_signing,
+            two_factor,
+            response_cache,
+            admission,
+            secrets,
+            jobs,
+            preloader: Preloader::default(),
             invoker,
-        })
+        };
+        publish::recover(&server).context("failed to recover interrupted publishes")?;
+        Ok(server)
     }
 
     pub fn artifact_path(&self, function_name: &str) -> PathBuf {
@@ -65,6 +126,15 @@ impl FaastaServer {
         }
     }
 
+    /// Directory holding the assets of a static-site deployment.
+    pub fn static_site_dir(&self, function_name: &str) -> PathBuf {
+        self.functions_dir.join("sites").join(function_name)
+    }
+
+    pub fn is_static_site(&self, function_name: &str) -> bool {
+        self.static_site_dir(function_name).is_dir()
+    }
+
     fn ensure_exists(path: &Path) -> Result<()> {
         if !path.exists() {
             bail!("function artifact missing at {}", path.display());
@@ -73,14 +143,28 @@ impl FaastaServer {
     }
 
     pub async fn prepare_sandbox_path(&self, function_name: &str) -> Result<PathBuf> {
-        let sandbox_path = self.sandbox_root.join(function_name);
+        let sandbox_path = sandbox_path(&self.sandbox_root, function_name)?;
         std::fs::create_dir_all(&sandbox_path)
             .with_context(|| format!("failed to prepare sandbox for {function_name}"))?;
         Ok(sandbox_path)
     }
 
+    /// Delete the function's sandbox directory, so a later function of the same name
+    /// starts with an empty one.
+    pub fn remove_sandbox(&self, function_name: &str) -> Result<()> {
+        let sandbox_path = sandbox_path(&self.sandbox_root, function_name)?;
+        match std::fs::remove_dir_all(&sandbox_path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("failed to remove sandbox {sandbox_path:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub async fn remove_from_cache(&self, function_name: &str) {
         self.invoker.remove(function_name);
+        self.response_cache.purge_function(function_name);
+        self.preloader.forget(function_name);
         debug!("removed cached function runtime state {function_name}");
     }
 
@@ -91,118 +175,483 @@ impl FaastaServer {
         uri: Uri,
         headers: HeaderMap,
         body: Bytes,
+    ) -> Result<Response<Body>> {
+        self.invoke_recorded(function_name, false, method, uri, headers, body)
+            .await
+    }
+
+    /// Copy a request to the function's shadow target when the mirror sample selects it.
+    /// The shadow response is discarded and its timing is recorded under a separate metric.
+    pub fn mirror(
+        self: &Arc<Self>,
+        function_name: &str,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        body: &Bytes,
+    ) {
+        let config = self.function_configs.get(function_name);
+        let Some(mirror) = config.mirror.as_ref() else {
+            return;
+        };
+        if mirror.percent == 0 || rand::random_range(0..100u8) >= mirror.percent {
+            return;
+        }
+        if !self.function_exists(&mirror.target) {
+            debug!(
+                "mirror target '{}' for '{function_name}' is not published",
+                mirror.target
+            );
+            return;
+        }
+
+        let server = self.clone();
+        let target = mirror.target.clone();
+        let (method, uri, headers, body) =
+            (method.clone(), uri.clone(), headers.clone(), body.clone());
+        tokio::spawn(async move {
+            // Shadow traffic is the first thing to go under pressure
+            let Some(_permit) = server.admission_for(&target).try_admit(Priority::Batch) else {
+                debug!("shedding shadow invocation of '{target}'");
+                return;
+            };
+            if let Err(err) = server
+                .invoke_recorded(&target, true, method, uri, headers, body)
+                .await
+            {
+                debug!("shadow invocation of '{target}' failed: {err:?}");
+            }
+        });
+    }
+
+    /// Warm the function's dependencies that aren't resident, in the background.
+    pub fn preload(self: &Arc<Self>, function_name: &str) {
+        let config = self.function_configs.get(function_name);
+        for target in self.preloader.targets(function_name, &config.preload) {
+            if self.is_loaded(&target)
+                || !self.function_exists(&target)
+                || !self.preloader.begin(&target)
+            {
+                continue;
+            }
+            let server = self.clone();
+            tokio::spawn(async move {
+                // Speculative work only uses capacity real traffic leaves over
+                let Some(_permit) = server.admission_for(&target).try_admit(Priority::Batch) else {
+                    return;
+                };
+                debug!("preloading '{target}'");
+                if let Err(err) = server.warm(&target).await {
+                    debug!("failed to preload '{target}': {err:#}");
+                }
+            });
+        }
+    }
+
+    /// Invoke the function, recording its timing under its own metrics or, for
+    /// `shadow` traffic, its shadow metrics.
+    async fn invoke_recorded(
+        &self,
+        function_name: &str,
+        shadow: bool,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
     ) -> Result<Response<Body>> {
         let artifact_path = self.artifact_path(function_name);
         Self::ensure_exists(&artifact_path)?;
 
-        let _sandbox_path = self
+        let sandbox_path = self
             .prepare_sandbox_path(function_name)
             .await
             .with_context(|| format!("failed to prepare sandbox for '{function_name}'"))?;
 
-        let _timer = Timer::new(function_name.to_string());
+        let secrets = self
+            .secrets
+            .env(function_name)
+            .await
+            .with_context(|| format!("failed to load secrets for '{function_name}'"))?;
+        let mut env = Vec::clone(&secrets);
+        platform::apply(self, function_name, &mut env);
+
+        let owner = self.owner(function_name);
+        let key = FunctionKey::new(owner.clone(), function_name);
+        let metric_key = if shadow {
+            MetricKey::Shadow(key)
+        } else {
+            MetricKey::Function(key)
+        };
+        let config = self.function_configs.get(function_name);
+        let settings = InstanceSettings {
+            env,
+            deterministic: config.deterministic.clone(),
+            sandbox: Some(sandbox_path),
+        };
+        let mut timer = Timer::new(metric_key.clone());
         let request = build_faasta_request(method, uri, headers, body);
         let response = self
             .invoker
-            .invoke(function_name, &artifact_path, request)
+            .invoke(function_name, &owner, &artifact_path, request, &settings)
+            .await;
+        timer.charge_cpu(guest_cpu_time(&response));
+        if let Ok(response) = &response {
+            timer.set_cold_start(response.timing.load.is_some());
+        }
+        let response =
+            response.with_context(|| format!("worker failed for function '{function_name}'"))?;
+        server_timing::record(&metric_key, &response.headers, &response.trailers);
+        if self.preloader.should_scan(function_name) {
+            let targets =
+                preload::referenced_functions(&response.headers, &response.body, &self.base_domain)
+                    .into_iter()
+                    .filter(|target| {
+                        target != function_name
+                            && self.function_exists(target)
+                            && self.owner(target) == owner
+                    })
+                    .collect();
+            self.preloader.learn(function_name, targets);
+        }
+        let timing = response.timing;
+        let cpu_meter = response.cpu_meter.clone();
+        let mut response = faasta_response_to_http(response);
+        if let Some(meter) = cpu_meter {
+            response = hold_until_body_ends(
+                response,
+                StreamedCpu {
+                    key: metric_key,
+                    meter,
+                    charged: timing.cpu,
+                },
+            );
+        }
+        response.extensions_mut().insert(timing);
+        apply_response_headers(response.headers_mut(), &config.response_headers);
+        Ok(response)
+    }
+
+    /// Load, compile and instantiate a function ahead of traffic. Static sites have
+    /// nothing to warm.
+    pub async fn warm(&self, function_name: &str) -> Result<()> {
+        if self.is_static_site(function_name) {
+            return Ok(());
+        }
+        let artifact_path = self.artifact_path(function_name);
+        Self::ensure_exists(&artifact_path)?;
+        self.prepare_sandbox_path(function_name)
+            .await
+            .with_context(|| format!("failed to prepare sandbox for '{function_name}'"))?;
+        self.invoker
+            .warm(function_name, &self.owner(function_name), &artifact_path)
             .await
-            .with_context(|| format!("worker failed for function '{function_name}'"))?;
-        Ok(faasta_response_to_http(response))
+            .with_context(|| format!("failed to warm function '{function_name}'"))
     }
 
+    /// Whether the function's compiled component is resident.
+    pub fn is_loaded(&self, function_name: &str) -> bool {
+        self.invoker.is_loaded(&self.function_key(function_name))
+    }
+
+    /// Compiled components held across the shared and dedicated runtimes.
+    pub fn runtime_stats(&self) -> RuntimeStats {
+        self.invoker.stats()
+    }
+
+    /// Whether the function runs in the dedicated isolation tier.
+    pub fn is_dedicated(&self, function_name: &str) -> bool {
+        self.invoker.dedicated(function_name).is_some()
+    }
+
+    /// The admission controller guarding the pool the function runs in.
+    pub fn admission_for(&self, function_name: &str) -> &AdmissionController {
+        match self.invoker.dedicated(function_name) {
+            Some(tier) => &tier.admission,
+            None => &self.admission,
+        }
+    }
+
+    /// Load failures of the function's artifact, if it failed to load recently.
+    pub fn artifact_health(&self, function_name: &str) -> Option<ArtifactHealth> {
+        self.invoker.artifact_health(function_name)
+    }
+
+    /// The function's most recent trapped or limited invocation.
+    pub fn last_failure(&self, function_name: &str) -> Option<InvocationFailure> {
+        self.invoker.last_failure(function_name)
+    }
+
+    /// Drop the function's compiled component so its next request starts cold.
+    pub fn unload(&self, function_name: &str) {
+        self.invoker.remove(function_name);
+    }
+
+    /// Whether this node dispatches to the function: it is published with an artifact in
+    /// place, and not pinned to other regions than this node's. The name is checked
+    /// against the metadata store first, so unknown names never reach the filesystem.
+    /// Not for deciding ownership, which holds on every node.
     pub fn function_exists(&self, function_name: &str) -> bool {
-        self.artifact_path(function_name).exists()
+        self.is_published(function_name)
+            && platform::serves(&self.function_configs.get(function_name).regions)
+            && (self.artifact_path(function_name).exists() || self.is_static_site(function_name))
+    }
+
+    /// Key of the function's compiled component and metrics.
+    pub fn function_key(&self, function_name: &str) -> FunctionKey {
+        FunctionKey::new(self.owner(function_name), function_name)
+    }
+
+    /// The owner a function's CPU time is charged to. A function whose record can't
+    /// be read is charged to itself.
+    pub fn owner(&self, function_name: &str) -> String {
+        let info = self
+            .metadata_db
+            .get_function(function_name)
+            .ok()
+            .flatten()
+            .and_then(|entry| record::decode::<FunctionInfo>(&entry).ok());
+        match info {
+            Some(info) => info.owner,
+            None => function_name.to_string(),
+        }
+    }
+
+    fn is_published(&self, function_name: &str) -> bool {
+        match self.metadata_db.get_function(function_name) {
+            Ok(entry) => entry.is_some(),
+            Err(err) => {
+                debug!("failed to look up function '{function_name}': {err:#}");
+                false
+            }
+        }
     }
 }
 
 pub struct FunctionInvoker {
     runtime: WasmFunctionRuntime,
+    dedicated: Option<DedicatedTier>,
 }
 
 impl FunctionInvoker {
-    pub async fn wasm() -> Result<Self> {
+    pub async fn wasm(pool: PoolConfig, cpu: CpuScheduler) -> Result<Self> {
         Ok(Self {
-            runtime: WasmFunctionRuntime::new().await?,
+            runtime: WasmFunctionRuntime::new(pool, cpu).await?,
+            dedicated: None,
         })
     }
 
+    /// Serve the tier's functions from its own pool and threads.
+    pub fn with_dedicated(mut self, tier: DedicatedTier) -> Self {
+        self.dedicated = Some(tier);
+        self
+    }
+
+    fn dedicated(&self, function_name: &str) -> Option<&DedicatedTier> {
+        self.dedicated
+            .as_ref()
+            .filter(|tier| tier.serves(function_name))
+    }
+
+    /// The runtime holding the function's compiled component.
+    fn runtime(&self, function_name: &str) -> &WasmFunctionRuntime {
+        match self.dedicated(function_name) {
+            Some(tier) => tier.runtime(),
+            None => &self.runtime,
+        }
+    }
+
     async fn invoke(
         &self,
         function_name: &str,
+        owner: &str,
         artifact_path: &Path,
         request: WasmRequest,
+        settings: &InstanceSettings,
     ) -> Result<WasmResponse> {
-        self.runtime
-            .invoke(function_name, artifact_path, request)
-            .await
+        let response = match self.dedicated(function_name) {
+            Some(tier) => {
+                tier.invoke(function_name, owner, artifact_path, request, settings)
+                    .await
+            }
+            None => {
+                self.runtime
+                    .invoke(function_name, owner, artifact_path, request, settings)
+                    .await
+            }
+        }?;
+        if chaos::function_error() {
+            return Ok(WasmResponse {
+                status: http::StatusCode::INTERNAL_SERVER_ERROR,
+                headers: HeaderMap::new(),
+                body: Bytes::from_static(b"chaos: injected function error\n"),
+                stream: None,
+                trailers: HeaderMap::new(),
+                timing: response.timing,
+                cpu_meter: None,
+            });
+        }
+        Ok(response)
     }
 
     fn remove(&self, function_name: &str) {
-        self.runtime.remove(function_name);
+        self.runtime(function_name).remove(function_name);
+    }
+
+    async fn warm(&self, function_name: &str, owner: &str, artifact_path: &Path) -> Result<()> {
+        match self.dedicated(function_name) {
+            Some(tier) => tier.warm(function_name, owner, artifact_path).await,
+            None => self.runtime.warm(function_name, owner, artifact_path).await,
+        }
+    }
+
+    fn is_loaded(&self, key: &FunctionKey) -> bool {
+        self.runtime(&key.name).is_loaded(key)
+    }
+
+    fn stats(&self) -> RuntimeStats {
+        let mut stats = self.runtime.stats();
+        if let Some(tier) = &self.dedicated {
+            let dedicated = tier.runtime().stats();
+            stats.cached_components += dedicated.cached_components;
+            stats.loads_in_progress += dedicated.loads_in_progress;
+        }
+        stats
+    }
+
+    fn artifact_health(&self, function_name: &str) -> Option<ArtifactHealth> {
+        self.runtime(function_name).artifact_health(function_name)
+    }
+
+    fn last_failure(&self, function_name: &str) -> Option<InvocationFailure> {
+        self.runtime(function_name).last_failure(function_name)
+    }
+}
+
+/// Convert an incoming request into the form handed to the guest. Headers and body
+/// are moved, not copied.
+pub fn build_faasta_request(
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> WasmRequest {
+    WasmRequest {
+        method,
+        uri,
+        headers,
+        body,
     }
 }
 
-fn build_faasta_request(method: Method, uri: Uri, headers: HeaderMap, body: Bytes) -> WasmRequest {
-    let method_code = match method {
-        Method::GET => 0,
-        Method::POST => 1,
-        Method::PUT => 2,
-        Method::DELETE => 3,
-        Method::PATCH => 4,
-        Method::HEAD => 5,
-        Method::OPTIONS => 6,
-        _ => 0,
+/// Convert a guest response back into an HTTP response without copying its body.
+pub fn faasta_response_to_http(resp: WasmResponse) -> Response<Body> {
+    let body = if let Some(mut stream) = resp.stream {
+        let read = (!resp.body.is_empty()).then(|| Ok(Frame::data(resp.body)));
+        let rest = futures_util::stream::poll_fn(move |cx| stream.poll_recv(cx));
+        Body::new(StreamBody::new(
+            futures_util::stream::iter(read).chain(rest),
+        ))
+    } else if resp.trailers.is_empty() {
+        Body::from(resp.body)
+    } else {
+        let frames = [
+            Ok::<_, Infallible>(Frame::data(resp.body)),
+            Ok(Frame::trailers(resp.trailers)),
+        ];
+        Body::new(StreamBody::new(futures_util::stream::iter(frames)))
     };
+    let mut response = Response::new(body);
+    *response.status_mut() = resp.status;
+    *response.headers_mut() = resp.headers;
+    response
+}
 
-    let mut header_vec = Vec::new();
-    for (name, value) in headers.iter() {
-        header_vec.push(WireHeader {
-            name: name.as_str().to_string(),
-            value: value.to_str().unwrap_or("").to_string(),
-        });
+/// Keep `guard` alive until the response body has been sent or dropped, e.g. the
+/// admission permit of a response that is still streaming.
+pub fn hold_until_body_ends<G>(response: Response<Body>, guard: G) -> Response<Body>
+where
+    G: Send + Unpin + 'static,
+{
+    response.map(|body| {
+        Body::new(Guarded {
+            body,
+            _guard: guard,
+        })
+    })
+}
+
+struct Guarded<G> {
+    body: Body,
+    _guard: G,
+}
+
+impl<G: Unpin> http_body::Body for Guarded<G> {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
     }
 
-    let uri_string = uri.to_string();
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
 
-    WasmRequest {
-        method: method_code,
-        uri: uri_string,
-        headers: header_vec,
-        body: body.to_vec(),
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
     }
 }
 
-fn faasta_response_to_http(resp: WasmResponse) -> Response<Body> {
-    let mut response = Response::builder()
-        .status(resp.status)
-        .body(Body::from(resp.body))
-        .unwrap_or_else(|_| Response::builder().status(500).body(Body::empty()).unwrap());
+/// Charges a streamed call for the CPU its guest spends writing the body once the
+/// body is done with.
+struct StreamedCpu {
+    key: MetricKey,
+    meter: cpu_time::Meter,
+    /// Already charged when the head was recorded
+    charged: Duration,
+}
+
+impl Drop for StreamedCpu {
+    fn drop(&mut self) {
+        metrics::charge_cpu(&self.key, self.meter.spent().saturating_sub(self.charged));
+    }
+}
 
-    let headers_mut = response.headers_mut();
-    for header in resp.headers {
-        if let (Ok(name), Ok(val)) = (
-            HeaderName::from_bytes(header.name.as_bytes()),
-            HeaderValue::from_str(header.value.as_str()),
-        ) {
-            headers_mut.append(name, val);
+/// Add the owner's configured headers to a function response. Headers already set by
+/// the function are kept unless the rule asks to replace them.
+fn apply_response_headers(headers: &mut HeaderMap, rules: &[ResponseHeader]) {
+    for rule in rules {
+        let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(rule.name.as_bytes()),
+            HeaderValue::from_str(&rule.value),
+        ) else {
+            continue;
+        };
+        if rule.replace || !headers.contains_key(&name) {
+            headers.insert(name, value);
         }
     }
+}
 
-    response
+/// The function's sandbox directory, mounted into its instances. Names that could
+/// resolve outside `root` are refused.
+fn sandbox_path(root: &Path, function_name: &str) -> Result<PathBuf> {
+    ensure!(
+        sanitize_function_name(function_name).is_some(),
+        "invalid function name '{function_name}'"
+    );
+    Ok(root.join(function_name))
 }
 
+/// Function name addressed by a request: the subdomain for hosts under the base domain,
+/// otherwise the first path segment. A subdomain that is not a valid name resolves to
+/// nothing rather than falling back to the path.
 pub fn resolve_function_name(host: Option<&str>, path: &str, base_domain: &str) -> Option<String> {
-    if let Some(host) = host {
-        let host = host.split(':').next().unwrap_or(host);
-        if host.ends_with(base_domain) {
-            let parts = host.split('.').collect::<Vec<_>>();
-            if parts.len() > 2 {
-                let name = parts[0];
-                if !name.is_empty() {
-                    return Some(name.to_string());
-                }
-            }
-        }
+    if let Some(labels) = host.and_then(|host| subdomain_labels(host, base_domain)) {
+        return name_from_labels(&labels, NESTED_SUBDOMAINS.load(Ordering::Relaxed));
     }
 
     let trimmed = path.trim_start_matches('/');
@@ -213,13 +662,175 @@ pub fn resolve_function_name(host: Option<&str>, path: &str, base_domain: &str)
     }
 }
 
+/// Resolve `label.name.base_domain` hosts to `name` instead of rejecting them.
+pub fn allow_nested_subdomains(allow: bool) {
+    NESTED_SUBDOMAINS.store(allow, Ordering::Relaxed);
+}
+
+/// Function name addressed by a `name.base_domain` host, if any. Hosts are compared
+/// case-insensitively and the name is returned in lower case; hosts with more than one
+/// label before the base domain only resolve when nested subdomains are allowed.
+pub fn function_name_from_host(host: Option<&str>, base_domain: &str) -> Option<String> {
+    let labels = subdomain_labels(host?, base_domain)?;
+    name_from_labels(&labels, NESTED_SUBDOMAINS.load(Ordering::Relaxed))
+}
+
+/// The lower-cased labels in front of the base domain, without the port.
+fn subdomain_labels(host: &str, base_domain: &str) -> Option<String> {
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|byte| byte.is_ascii_digit()) => host,
+        _ => host,
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let base_domain = base_domain.trim_end_matches('.').to_ascii_lowercase();
+    let labels = host.strip_suffix(&base_domain)?.strip_suffix('.')?;
+    Some(labels.to_string())
+}
+
+/// The function name in the subdomain `labels`; with `nested`, the label next to the
+/// base domain.
+fn name_from_labels(labels: &str, nested: bool) -> Option<String> {
+    let name = if nested {
+        labels.rsplit('.').next()?
+    } else {
+        labels
+    };
+    sanitize_function_name(name)
+}
+
 pub fn sanitize_function_name(function_name: &str) -> Option<String> {
-    let valid = function_name
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    let valid = !function_name.is_empty()
+        && function_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
     if valid {
         Some(function_name.to_string())
     } else {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "faasta.lol";
+
+    fn resolve(host: &str, path: &str) -> Option<String> {
+        resolve_function_name(Some(host), path, BASE)
+    }
+
+    #[test]
+    fn sandboxes_stay_below_the_root() {
+        let root = Path::new("/srv/functions/sandbox");
+        assert_eq!(sandbox_path(root, "photos").unwrap(), root.join("photos"));
+        for name in ["", "..", ".", "a/b", "../other", "/etc"] {
+            assert!(sandbox_path(root, name).is_err(), "{name:?} was accepted");
+        }
+    }
+
+    #[tokio::test]
+    async fn streamed_bodies_hold_their_permit_until_they_end() {
+        use http_body_util::BodyExt;
+
+        let admission = AdmissionController::new(1, 100, 0, 1);
+        let permit = admission.try_admit(Priority::Critical).unwrap();
+        let (frames, stream) = tokio::sync::mpsc::channel(1);
+        let response = faasta_response_to_http(WasmResponse {
+            status: http::StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"head"),
+            stream: Some(stream),
+            trailers: HeaderMap::new(),
+            timing: Default::default(),
+            cpu_meter: None,
+        });
+        let mut body = hold_until_body_ends(response, permit).into_body();
+
+        assert!(body.frame().await.is_some());
+        assert_eq!(admission.stats().in_flight, 1);
+        frames
+            .send(Ok(Frame::data(Bytes::from_static(b"tail"))))
+            .await
+            .unwrap();
+        drop(frames);
+        assert!(body.frame().await.is_some());
+        assert_eq!(admission.stats().in_flight, 1);
+        drop(body);
+        assert_eq!(admission.stats().in_flight, 0);
+    }
+
+    #[test]
+    fn subdomains_are_case_insensitive() {
+        assert_eq!(resolve("hello.faasta.lol", "/"), Some("hello".into()));
+        assert_eq!(resolve("HeLLo.FAASTA.lol:443", "/"), Some("hello".into()));
+        assert_eq!(resolve("hello.faasta.lol.", "/x"), Some("hello".into()));
+    }
+
+    #[test]
+    fn apex_and_foreign_hosts_use_the_path() {
+        assert_eq!(resolve("faasta.lol", "/hello/x"), Some("hello".into()));
+        assert_eq!(resolve("evilfaasta.lol", "/hello"), Some("hello".into()));
+        assert_eq!(resolve("127.0.0.1:8080", "/hello"), Some("hello".into()));
+        assert_eq!(resolve("faasta.lol", "/"), None);
+    }
+
+    #[test]
+    fn invalid_subdomains_do_not_fall_back_to_the_path() {
+        for host in [
+            "a.hello.faasta.lol",
+            ".faasta.lol",
+            "he%6c.faasta.lol",
+            "a b.faasta.lol",
+        ] {
+            assert_eq!(resolve(host, "/hello"), None, "{host}");
+        }
+    }
+
+    #[test]
+    fn nested_subdomains_use_the_label_next_to_the_base_domain() {
+        assert_eq!(name_from_labels("www.hello", false), None);
+        assert_eq!(name_from_labels("www.hello", true), Some("hello".into()));
+        assert_eq!(name_from_labels("hello", true), Some("hello".into()));
+        assert_eq!(name_from_labels("www.", true), None);
+    }
+
+    #[test]
+    fn sanitize_rejects_empty_and_special_names() {
+        assert_eq!(sanitize_function_name("my_fn-2"), Some("my_fn-2".into()));
+        for name in ["", ".", "..", "a/b", "a.b", "é", "a\0"] {
+            assert_eq!(sanitize_function_name(name), None, "{name:?}");
+        }
+    }
+
+    /// Random hosts built from characters that matter to the parser never panic and
+    /// only ever resolve to a valid, lower-case name.
+    #[test]
+    fn random_hosts_resolve_to_valid_names() {
+        const ALPHABET: &[u8] = b"aZ0-_.:/[]% faasta.lol";
+        for _ in 0..20_000 {
+            let len = rand::random_range(0..24);
+            let mut host: String = (0..len)
+                .map(|_| ALPHABET[rand::random_range(0..ALPHABET.len())] as char)
+                .collect();
+            if rand::random_bool(0.5) {
+                host.push_str(".faasta.lol");
+            }
+            for nested in [false, true] {
+                let name = subdomain_labels(&host, BASE)
+                    .and_then(|labels| name_from_labels(&labels, nested));
+                if let Some(name) = name {
+                    assert_eq!(
+                        sanitize_function_name(&name).as_ref(),
+                        Some(&name),
+                        "{host:?}"
+                    );
+                    assert_eq!(name, name.to_ascii_lowercase(), "{host:?}");
+                }
+            }
+            if let Some(name) = resolve_function_name(Some(&host), "/", BASE) {
+                assert!(!name.is_empty() && !name.contains('/'), "{host:?}");
+            }
+        }
+    }
+}
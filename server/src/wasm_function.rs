@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, anyhow, bail, ensure};
 use aws_sdk_s3::Client as S3Client;
@@ -7,8 +8,10 @@ use aws_sdk_s3::config::{Credentials as S3Credentials, Region as S3Region};
 use aws_sdk_s3::primitives::ByteStream;
 use bytes::Bytes;
 use dashmap::DashMap;
+use faasta_interface::{ArtifactHealth, Deterministic, InvocationFailure, LogStream, RuntimeStats};
 use futures_util::FutureExt;
-use http::{HeaderName, HeaderValue, Method, Request, Uri};
+use http::{HeaderMap, Method, Request, StatusCode, Uri, header};
+use http_body::Frame;
 use http_body_util::{BodyExt, Full};
 use omnia::{Backend, Host};
 use omnia_wasi_blobstore::{
@@ -23,59 +26,249 @@ use omnia_wasi_sql::{
     WasiSqlCtxView,
 };
 use redis::AsyncCommands;
+use tokio::sync::{OnceCell, mpsc, oneshot};
+use tokio::task::JoinHandle;
 use tokio_postgres::types::ToSql;
-use tracing::debug;
+use tracing::{debug, warn};
 use wasmtime::component::{Component, Linker, ResourceTable};
-use wasmtime::{Config, Engine, OptLevel, Store};
-use wasmtime_wasi::{WasiCtx, WasiCtxView, WasiView};
+use wasmtime::{
+    Config, Engine, InstanceAllocationStrategy, OptLevel, PoolingAllocationConfig, ResourceLimiter,
+    Store, UpdateDeadline,
+};
+use wasmtime_wasi::cli::AsyncStdoutStream;
+use wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxView, WasiView};
 use wasmtime_wasi_http::WasiHttpCtx;
 use wasmtime_wasi_http::p3::bindings::ServicePre;
 use wasmtime_wasi_http::p3::bindings::http::types::ErrorCode;
 use wasmtime_wasi_http::p3::{Request as WasiHttpRequest, WasiHttpCtxView, WasiHttpView};
 
+use crate::cpu_time;
+use crate::determinism;
+use crate::encryption;
+use crate::fnv::fnv1a;
+use crate::function_key::FunctionKey;
+use crate::function_logs::Capture;
+use crate::guest_log::{self, GuestLog};
+use crate::image_transform::{self, ImageHost};
+use crate::kv_store::{self, LocalKeyValue};
+use crate::quarantine::Quarantine;
+use crate::scheduler::CpuScheduler;
+use crate::traps::{CpuBudgetExceeded, FailureLog, Fault};
+
+/// A request handed to a guest. Bodies are reference-counted `Bytes`, so moving a
+/// request or response between the host and the guest never copies the payload.
 #[derive(Debug, Clone)]
-pub struct WireHeader {
-    pub name: String,
-    pub value: String,
+pub struct WasmRequest {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
 }
 
-#[derive(Debug, Clone)]
-pub struct WasmRequest {
-    pub method: u8,
-    pub uri: String,
-    pub headers: Vec<WireHeader>,
-    pub body: Vec<u8>,
+/// Per-function setup of the instance that handles a request.
+#[derive(Clone, Debug, Default)]
+pub struct InstanceSettings {
+    /// Environment variables, including the function's secrets
+    pub env: Vec<(String, String)>,
+    /// Fixed clocks and randomness
+    pub deterministic: Option<Deterministic>,
+    /// Host directory the guest sees as [`SANDBOX_GUEST_PATH`]
+    pub sandbox: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone)]
+/// Where a function's sandbox directory is mounted in the guest.
+pub const SANDBOX_GUEST_PATH: &str = "/data";
+
+/// Response bodies that grow past this are streamed to the client as the guest writes
+/// them instead of being buffered first.
+pub const STREAM_AFTER_BYTES: usize = 1024 * 1024;
+/// Frames of a streamed body the guest may write ahead of the client
+const STREAM_FRAMES: usize = 16;
+
+/// The rest of a streamed response body, ending with its trailers if any. An `Err`
+/// means the guest failed part-way through.
+pub type BodyStream = mpsc::Receiver<Result<Frame<Bytes>>>;
+
+#[derive(Debug)]
 pub struct WasmResponse {
-    pub status: u16,
-    pub headers: Vec<WireHeader>,
-    pub body: Vec<u8>,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// The whole body, or for a streamed response what was read before streaming began
+    pub body: Bytes,
+    /// Set when the guest is still writing the body: `text/event-stream` responses and
+    /// those over [`STREAM_AFTER_BYTES`]
+    pub stream: Option<BodyStream>,
+    /// Fields the guest sent after the body, e.g. a `Server-Timing` it computed while
+    /// writing it
+    pub trailers: HeaderMap,
+    pub timing: InvocationTiming,
+    /// For a streamed response, the invocation's CPU time, still growing while the
+    /// guest writes the body
+    pub cpu_meter: Option<cpu_time::Meter>,
+}
+
+/// Where an invocation's time went.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InvocationTiming {
+    /// Loading the component, when it wasn't resident
+    pub load: Option<Duration>,
+    pub instantiate: Duration,
+    /// Running the handler and reading its response
+    pub handler: Duration,
+    /// CPU time the guest ran for during instantiation and the handler
+    pub cpu: Duration,
+}
+
+/// Context on a failed invocation: the CPU time its guest ran for before failing.
+#[derive(Clone, Copy, Debug)]
+pub struct GuestCpu(pub Duration);
+
+impl std::fmt::Display for GuestCpu {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "guest ran for {:.3} ms of CPU",
+            self.0.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// CPU time the guest of an invocation ran for, whether or not it succeeded.
+pub fn guest_cpu_time(result: &Result<WasmResponse>) -> Duration {
+    match result {
+        Ok(response) => response.timing.cpu,
+        Err(err) => err
+            .downcast_ref::<GuestCpu>()
+            .map_or(Duration::ZERO, |spent| spent.0),
+    }
+}
+
+/// Aborts the task running an invocation if the caller gives up on it before it is
+/// detached.
+struct Invocation<T>(Option<JoinHandle<T>>);
+
+impl<T> Invocation<T> {
+    fn detach(&mut self) {
+        self.0 = None;
+    }
 }
 
+impl<T> Drop for Invocation<T> {
+    fn drop(&mut self) {
+        if let Some(task) = &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// Outcome of one load, with the error flattened to its message so every waiter gets
+/// a copy
+type LoadFlight = OnceCell<std::result::Result<Arc<ServicePre<WasmRequestState>>, String>>;
+
 type RequestBody =
     http_body_util::combinators::MapErr<Full<Bytes>, fn(std::convert::Infallible) -> ErrorCode>;
 
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+/// Bytes a guest may write to stdout or stderr in one call
+const OUTPUT_WRITE_BUDGET: usize = 64 * 1024;
+
+/// Slot counts for the pooling allocator. Every slot is reserved at startup, so these
+/// bound both concurrency and the address space the server claims.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolConfig {
+    /// Concurrent component instances (one per in-flight request)
+    pub component_instances: u32,
+    /// Core module instances, tables and stacks across all component instances
+    pub core_instances: u32,
+    /// Linear memories across all component instances
+    pub memories: u32,
+    /// Largest size a single linear memory may grow to
+    pub max_memory_bytes: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            component_instances: 100,
+            core_instances: 1_000,
+            memories: 100,
+            max_memory_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.component_instances > 0,
+            "the pool needs at least one component instance"
+        );
+        ensure!(
+            self.core_instances >= self.component_instances,
+            "core instances ({}) must be at least the component instances ({})",
+            self.core_instances,
+            self.component_instances
+        );
+        ensure!(
+            self.memories > 0,
+            "the pool needs at least one linear memory"
+        );
+        ensure!(
+            self.max_memory_bytes >= WASM_PAGE_SIZE
+                && self.max_memory_bytes.is_multiple_of(WASM_PAGE_SIZE),
+            "max memory size must be a non-zero multiple of 64 KiB"
+        );
+        Ok(())
+    }
+
+    /// Memory in use if every pooled memory grows to its limit.
+    pub fn worst_case_memory_bytes(&self) -> u64 {
+        u64::from(self.memories) * self.max_memory_bytes as u64
+    }
+}
+
 pub struct WasmFunctionRuntime {
     engine: Engine,
     linker: Linker<WasmRequestState>,
-    cache: DashMap<String, Arc<ServicePre<WasmRequestState>>>,
+    cache: DashMap<FunctionKey, Arc<ServicePre<WasmRequestState>>>,
+    /// Loads in progress, shared by every request that finds the function cold
+    loading: DashMap<FunctionKey, Arc<LoadFlight>>,
+    quarantine: Quarantine,
+    failures: FailureLog,
+    cpu: Arc<CpuScheduler>,
     keyvalue: KeyValueProvider,
     blobstore: BlobstoreProvider,
     sql: SqlProvider,
+    images: ImageHost,
 }
 
 impl WasmFunctionRuntime {
-    pub async fn new() -> Result<Self> {
+    pub async fn new(pool: PoolConfig, cpu: CpuScheduler) -> Result<Self> {
+        pool.validate()?;
+
         let mut config = Config::new();
         config.wasm_component_model(true);
         config.wasm_component_model_async(true);
         config.memory_init_cow(true);
         config.cranelift_opt_level(OptLevel::Speed);
+        config.epoch_interruption(true);
+        // Every request gets a fresh Store for isolation. The pooling allocator makes
+        // that cheap: instance, memory, table and stack slots are reserved up front and
+        // recycled between requests, with memories reset to their copy-on-write image
+        // rather than reallocated.
+        let mut pooling = PoolingAllocationConfig::default();
+        pooling
+            .total_component_instances(pool.component_instances)
+            .total_core_instances(pool.core_instances)
+            .total_memories(pool.memories)
+            .total_tables(pool.core_instances)
+            .total_stacks(pool.core_instances)
+            .max_memory_size(pool.max_memory_bytes);
+        config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling));
 
         let engine = Engine::new(&config)
             .map_err(|err| anyhow!("failed to create wasmtime engine: {err}"))?;
+        spawn_epoch_ticker(engine.clone());
         let mut linker = Linker::new(&engine);
         wasmtime_wasi::p3::add_to_linker(&mut linker)
             .map_err(|err| anyhow!("failed to add WASI p3 imports to linker: {err}"))?;
@@ -89,43 +282,108 @@ impl WasmFunctionRuntime {
             .map_err(|err| anyhow!("failed to add WASI blobstore imports to linker: {err}"))?;
         <WasiSql as Host<WasmRequestState>>::add_to_linker(&mut linker)
             .map_err(|err| anyhow!("failed to add WASI SQL imports to linker: {err}"))?;
+        image_transform::add_to_linker(&mut linker, |state: &mut WasmRequestState| {
+            &mut state.images
+        })?;
+        guest_log::add_to_linker(&mut linker, |state: &mut WasmRequestState| &mut state.log)?;
 
         let keyvalue = KeyValueProvider::from_env().await?;
         let blobstore = BlobstoreProvider::from_env().await?;
         let sql = SqlProvider::from_env().await?;
+        let images = ImageHost::new(image_concurrency()?);
 
         Ok(Self {
             engine,
             linker,
             cache: DashMap::new(),
+            loading: DashMap::new(),
+            quarantine: Quarantine::default(),
+            failures: FailureLog::default(),
+            cpu: Arc::new(cpu),
             keyvalue,
             blobstore,
             sql,
+            images,
         })
     }
 
+    /// Handle one request in a fresh instance set up by `settings`, charging its CPU
+    /// time to `owner`.
     pub async fn invoke(
         &self,
         function_name: &str,
+        owner: &str,
         artifact_path: &Path,
         request: WasmRequest,
+        settings: &InstanceSettings,
     ) -> Result<WasmResponse> {
-        let pre = self.load(function_name, artifact_path)?;
-        let tenant = TenantId::new(function_name);
-        let sql = self.sql.for_tenant(&tenant).await?;
-        let mut store = Store::new(
-            &self.engine,
-            WasmRequestState::new(
-                TenantKeyValue::new(tenant.clone(), self.keyvalue.clone()),
-                TenantBlobstore::new(tenant, self.blobstore.clone()),
-                sql,
-            ),
-        );
-        let request = build_hyper_request(request)?;
-        let service = pre
-            .instantiate_async(&mut store)
-            .await
-            .map_err(|err| anyhow!("failed to instantiate WASI HTTP service component: {err}"))?;
+        let started = Instant::now();
+        let key = FunctionKey::new(owner, function_name);
+        let resident = self.cache.contains_key(&key);
+        let pre = self.load(&key, artifact_path).await?;
+        let load = (!resident).then(|| started.elapsed());
+        let mut store = self.new_store(function_name, owner, settings).await?;
+        let meter = cpu_time::Meter::default();
+        let (head_tx, mut head_rx) = oneshot::channel();
+        // The store runs on its own task so the guest can go on writing a streamed body
+        // once the head of its response has been handed on
+        let mut invocation = Invocation(Some(tokio::spawn(cpu_time::measure_into(
+            async move {
+                let result = Self::handle(&mut store, &pre, request, head_tx).await;
+                (result, store.data().limits.memory_denied)
+            },
+            meter.clone(),
+        ))));
+        let (result, memory_denied) = tokio::select! {
+            biased;
+            Ok(head) = &mut head_rx => (Ok(head), false),
+            finished = invocation.0.as_mut().expect("invocation is attached") => match finished {
+                Ok((Ok(Some(response)), memory_denied)) => (Ok(response), memory_denied),
+                Ok((Ok(None), _)) => (Err(anyhow!("streamed response lost its head")), false),
+                Ok((Err(err), memory_denied)) => (Err(err), memory_denied),
+                Err(err) => (Err(anyhow!("invocation panicked: {err}")), false),
+            },
+        };
+        // Either finished, or still writing a streamed body
+        invocation.detach();
+        let cpu = meter.spent();
+        let result = match result {
+            Ok(mut response) => {
+                response.timing.load = load;
+                response.timing.cpu = cpu;
+                if response.stream.is_some() {
+                    response.cpu_meter = Some(meter);
+                }
+                Ok(response)
+            }
+            Err(err) => Err(err.context(GuestCpu(cpu))),
+        };
+        if let Err(err) = &result
+            && let Some(fault) = Fault::classify(err, memory_denied)
+        {
+            // Running out of pool slots is the server's problem, not the function's
+            if fault != Fault::PoolExhausted {
+                self.failures.record(function_name, fault, err);
+            }
+            return result.context(fault);
+        }
+        result
+    }
+
+    /// Run the guest on `request`. A response that streams is sent on `head` as soon as
+    /// its head is ready, and `None` is returned once the guest has finished writing it.
+    async fn handle(
+        store: &mut Store<WasmRequestState>,
+        pre: &ServicePre<WasmRequestState>,
+        request: WasmRequest,
+        head: oneshot::Sender<WasmResponse>,
+    ) -> Result<Option<WasmResponse>> {
+        let request = build_hyper_request(request);
+        let started = Instant::now();
+        let service = pre.instantiate_async(&mut *store).await.map_err(|err| {
+            anyhow::Error::from(err).context("failed to instantiate WASI HTTP service component")
+        })?;
+        let instantiate = started.elapsed();
         let (wasi_request, request_io) = WasiHttpRequest::from_http(request);
 
         store
@@ -136,58 +394,273 @@ impl WasmFunctionRuntime {
                 };
                 let response =
                     accessor.with(|store| response.into_http(store, async { Ok(()) }))?;
-                let (response, ()) =
-                    futures_util::try_join!(hyper_response_to_worker(response), async {
-                        request_io.await.context("failed to consume request body")
-                    },)?;
+                let timing = move || InvocationTiming {
+                    instantiate,
+                    handler: started.elapsed() - instantiate,
+                    ..InvocationTiming::default()
+                };
+                let stream_to = move |mut response: WasmResponse| {
+                    response.timing = timing();
+                    let _ = head.send(response);
+                };
+                let (mut response, ()) = futures_util::try_join!(
+                    hyper_response_to_worker(response, Some(stream_to)),
+                    async { request_io.await.context("failed to consume request body") },
+                )?;
+                if let Some(response) = &mut response {
+                    response.timing = timing();
+                }
                 Ok(response)
             })
             .await?
     }
 
+    /// Drop the compiled component and any load or invocation failures, e.g. after a
+    /// new publish.
     pub fn remove(&self, function_name: &str) {
-        self.cache.remove(function_name);
+        // By name rather than key: the function's record, and with it its owner, may
+        // already be gone
+        self.cache.retain(|key, _| key.name != function_name);
+        self.loading.retain(|key, _| key.name != function_name);
+        self.quarantine.clear(function_name);
+        self.failures.clear(function_name);
     }
 
-    fn load(
+    async fn new_store(
         &self,
         function_name: &str,
+        owner: &str,
+        settings: &InstanceSettings,
+    ) -> Result<Store<WasmRequestState>> {
+        let tenant = TenantId::new(function_name);
+        let sql = self.sql.for_tenant(&tenant).await?;
+        let mut store = Store::new(
+            &self.engine,
+            WasmRequestState::new(
+                settings,
+                TenantKeyValue::new(tenant.clone(), self.keyvalue.clone()),
+                TenantBlobstore::new(tenant, self.blobstore.clone()),
+                sql,
+                self.images.clone(),
+                GuestLog::new(function_name),
+            )?,
+        );
+        // Hand the worker thread back to the scheduler every epoch tick so a guest
+        // stuck in a compute loop cannot starve other requests on the same thread, and
+        // park it while its owner is over its share of CPU.
+        let cpu = self.cpu.clone();
+        let owner = owner.to_string();
+        let mut used = Duration::ZERO;
+        store.epoch_deadline_callback(move |_| {
+            used += EPOCH_TICK;
+            if let Some(budget) = cpu.budget()
+                && used > budget
+            {
+                return Err(CpuBudgetExceeded { budget }.into());
+            }
+            if cpu.charge(&owner, EPOCH_TICK) {
+                let turn = cpu.clone().wait_turn(owner.clone());
+                return Ok(UpdateDeadline::YieldCustom(1, Box::pin(turn)));
+            }
+            Ok(UpdateDeadline::Yield(1))
+        });
+        store.set_epoch_deadline(1);
+        store.limiter(|state| &mut state.limits);
+        Ok(store)
+    }
+
+    pub fn is_loaded(&self, key: &FunctionKey) -> bool {
+        self.cache.contains_key(key)
+    }
+
+    pub fn stats(&self) -> RuntimeStats {
+        RuntimeStats {
+            cached_components: self.cache.len() as u64,
+            loads_in_progress: self.loading.len() as u64,
+        }
+    }
+
+    pub fn artifact_health(&self, function_name: &str) -> Option<ArtifactHealth> {
+        self.quarantine.health(function_name)
+    }
+
+    pub fn last_failure(&self, function_name: &str) -> Option<InvocationFailure> {
+        self.failures.last(function_name)
+    }
+
+    /// Compile the component if needed and instantiate it once, so the first request
+    /// doesn't pay for either.
+    pub async fn warm(&self, function_name: &str, owner: &str, artifact_path: &Path) -> Result<()> {
+        let pre = self
+            .load(&FunctionKey::new(owner, function_name), artifact_path)
+            .await?;
+        let mut store = self
+            .new_store(function_name, owner, &InstanceSettings::default())
+            .await?;
+        pre.instantiate_async(&mut store)
+            .await
+            .map_err(|err| anyhow!("failed to instantiate WASI HTTP service component: {err}"))?;
+        Ok(())
+    }
+
+    /// The compiled component, loading it if it isn't resident. Concurrent requests for
+    /// a cold function wait on a single load and share its outcome, so a burst of
+    /// traffic compiles the component once rather than once per request.
+    async fn load(
+        &self,
+        key: &FunctionKey,
         artifact_path: &Path,
     ) -> Result<Arc<ServicePre<WasmRequestState>>> {
-        if let Some(entry) = self.cache.get(function_name) {
+        if let Some(entry) = self.cache.get(key) {
             return Ok(entry.clone());
         }
+        self.quarantine.check(&key.name)?;
+
+        let flight = self.loading.entry(key.clone()).or_default().clone();
+        let outcome = flight
+            .get_or_init(|| async {
+                self.load_now(key, artifact_path)
+                    .map_err(|err| format!("{err:#}"))
+            })
+            .await
+            .clone();
+        self.loading
+            .remove_if(key, |_, current| Arc::ptr_eq(current, &flight));
+        outcome.map_err(|err| anyhow!(err))
+    }
 
+    fn load_now(
+        &self,
+        key: &FunctionKey,
+        artifact_path: &Path,
+    ) -> Result<Arc<ServicePre<WasmRequestState>>> {
+        if let Some(entry) = self.cache.get(key) {
+            return Ok(entry.clone());
+        }
+        let function_name = key.name.as_str();
+        let pre = match self
+            .load_component(function_name, artifact_path)
+            .and_then(|component| self.pre_instantiate(&component))
+        {
+            Ok(pre) => pre,
+            Err(err) => {
+                self.quarantine.record_failure(function_name, &err);
+                return Err(err);
+            }
+        };
+        self.quarantine.clear(function_name);
+        let pre = Arc::new(pre);
+        self.cache.insert(key.clone(), pre.clone());
+        Ok(pre)
+    }
+
+    /// Components are compiled once and the result kept as a `.cwasm` beside the
+    /// `.wasm`. A precompiled copy this engine cannot load, for instance one built by an
+    /// older Wasmtime or with different engine settings, is recompiled from the `.wasm`
+    /// and replaced. Functions with only a `.cwasm` have nothing to fall back to.
+    fn load_component(&self, function_name: &str, artifact_path: &Path) -> Result<Component> {
+        if is_precompiled(artifact_path) {
+            return self.read_component(function_name, artifact_path);
+        }
+        let precompiled = artifact_path.with_extension("cwasm");
+        if is_up_to_date(&precompiled, artifact_path) {
+            match self.read_component(function_name, &precompiled) {
+                Ok(component) => return Ok(component),
+                Err(err) => warn!("recompiling '{function_name}' from its component: {err:#}"),
+            }
+        }
+        let component = self.read_component(function_name, artifact_path)?;
+        if let Err(err) = store_precompiled(function_name, &component, &precompiled) {
+            warn!("failed to cache the compiled component of '{function_name}': {err:#}");
+        }
+        Ok(component)
+    }
+
+    fn read_component(&self, function_name: &str, artifact_path: &Path) -> Result<Component> {
         debug!(
-            "compiling WASI HTTP component for {function_name} from {}",
+            "loading WASI HTTP component for {function_name} from {}",
             artifact_path.display()
         );
-        let component =
-            if artifact_path.extension().and_then(|ext| ext.to_str()) == Some("cwasm") {
-                // SAFETY: precompiled artifacts are only loaded from the configured functions
-                // directory. Wasmtime validates that the artifact matches this engine.
-                unsafe { Component::deserialize_file(&self.engine, artifact_path) }
+        let precompiled = is_precompiled(artifact_path);
+        if encryption::enabled() {
+            let bytes = encryption::read_artifact(function_name, artifact_path)?;
+            if precompiled {
+                // SAFETY: as below; these are the decrypted contents of such an artifact.
+                unsafe { Component::deserialize(&self.engine, &bytes) }
             } else {
-                Component::from_file(&self.engine, artifact_path)
+                Component::new(&self.engine, &bytes)
             }
-            .map_err(|err| {
-                anyhow!(
-                    "failed to load component {}: {err}",
-                    artifact_path.display()
-                )
-            })?;
-
-        let pre =
-            ServicePre::new(self.linker.instantiate_pre(&component).map_err(|err| {
+        } else if precompiled {
+            // SAFETY: precompiled artifacts are only loaded from the configured functions
+            // directory. Wasmtime validates that the artifact matches this engine.
+            unsafe { Component::deserialize_file(&self.engine, artifact_path) }
+        } else {
+            Component::from_file(&self.engine, artifact_path)
+        }
+        .map_err(|err| {
+            anyhow!(
+                "failed to load component {}: {err}",
+                artifact_path.display()
+            )
+        })
+    }
+
+    fn pre_instantiate(&self, component: &Component) -> Result<ServicePre<WasmRequestState>> {
+        ServicePre::new(
+            self.linker.instantiate_pre(component).map_err(|err| {
                 anyhow!("failed to pre-instantiate WASI HTTP p3 component: {err}")
-            })?)
-            .map_err(|err| anyhow!("component does not export wasi:http/service world: {err}"))?;
-        let pre = Arc::new(pre);
-        self.cache.insert(function_name.to_string(), pre.clone());
-        Ok(pre)
+            })?,
+        )
+        .map_err(|err| anyhow!("component does not export wasi:http/service world: {err}"))
     }
 }
 
+fn is_precompiled(artifact_path: &Path) -> bool {
+    artifact_path.extension().and_then(|ext| ext.to_str()) == Some("cwasm")
+}
+
+/// Whether `precompiled` exists and was written after `component`.
+fn is_up_to_date(precompiled: &Path, component: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(precompiled), modified(component)) {
+        (Ok(precompiled), Ok(component)) => precompiled >= component,
+        _ => false,
+    }
+}
+
+/// Write the compiled component to `path`, sealed like the component it came from.
+fn store_precompiled(function_name: &str, component: &Component, path: &Path) -> Result<()> {
+    let compiled = component
+        .serialize()
+        .map_err(|err| anyhow!("failed to serialize component: {err}"))?;
+    let compiled = encryption::seal(&compiled, function_name.as_bytes())?;
+    // Concurrent cold starts each write their own file; the last rename wins.
+    let staging = path.with_extension(format!("{:08x}.cwasm.tmp", rand::random::<u32>()));
+    std::fs::write(&staging, compiled)
+        .with_context(|| format!("failed to write {}", staging.display()))?;
+    if let Err(err) = std::fs::rename(&staging, path) {
+        let _ = std::fs::remove_file(&staging);
+        return Err(err)
+            .with_context(|| format!("failed to move {} into place", staging.display()));
+    }
+    Ok(())
+}
+
+/// How often running guests are asked to yield to the async scheduler.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+fn spawn_epoch_ticker(engine: Engine) {
+    std::thread::Builder::new()
+        .name("wasm-epoch".to_string())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        })
+        .expect("failed to spawn epoch ticker thread");
+}
+
 struct WasmRequestState {
     wasi: WasiCtx,
     http: WasiHttpCtx,
@@ -195,18 +668,84 @@ struct WasmRequestState {
     keyvalue: TenantKeyValue,
     blobstore: TenantBlobstore,
     sql: TenantSql,
+    images: ImageHost,
+    log: GuestLog,
+    limits: MemoryLimits,
+}
+
+/// Enforces the pool's memory limit like wasmtime would, but notes when a guest hits
+/// it, so the trap that usually follows can be reported as running out of memory.
+#[derive(Default)]
+struct MemoryLimits {
+    memory_denied: bool,
+}
+
+impl ResourceLimiter for MemoryLimits {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        let allowed = maximum.is_none_or(|maximum| desired <= maximum);
+        self.memory_denied |= !allowed;
+        Ok(allowed)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        Ok(maximum.is_none_or(|maximum| desired <= maximum))
+    }
 }
 
 impl WasmRequestState {
-    fn new(keyvalue: TenantKeyValue, blobstore: TenantBlobstore, sql: TenantSql) -> Self {
-        Self {
-            wasi: WasiCtx::builder().build(),
+    fn new(
+        settings: &InstanceSettings,
+        keyvalue: TenantKeyValue,
+        blobstore: TenantBlobstore,
+        sql: TenantSql,
+        images: ImageHost,
+        log: GuestLog,
+    ) -> Result<Self> {
+        let mut wasi = WasiCtx::builder();
+        wasi.envs(&settings.env);
+        // Output goes to the function's own log, for `cargo faasta logs`
+        wasi.stdout(AsyncStdoutStream::new(
+            OUTPUT_WRITE_BUDGET,
+            Capture::new(log.function(), LogStream::Stdout),
+        ));
+        wasi.stderr(AsyncStdoutStream::new(
+            OUTPUT_WRITE_BUDGET,
+            Capture::new(log.function(), LogStream::Stderr),
+        ));
+        if let Some(config) = &settings.deterministic {
+            determinism::apply(&mut wasi, config);
+        }
+        // The guest sees only its own directory; paths can't resolve outside a preopen
+        if let Some(sandbox) = &settings.sandbox {
+            wasi.preopened_dir(
+                sandbox,
+                SANDBOX_GUEST_PATH,
+                DirPerms::all(),
+                FilePerms::all(),
+            )
+            .map_err(|err| anyhow!("failed to open sandbox {}: {err}", sandbox.display()))?;
+        }
+        Ok(Self {
+            wasi: wasi.build(),
             http: WasiHttpCtx::new(),
             table: ResourceTable::new(),
             keyvalue,
             blobstore,
             sql,
-        }
+            images,
+            log,
+            limits: MemoryLimits::default(),
+        })
     }
 }
 
@@ -258,6 +797,7 @@ impl omnia_wasi_sql::WasiSqlView for WasmRequestState {
 
 #[derive(Clone, Debug)]
 struct TenantId {
+    function: String,
     namespace: String,
     hash: String,
 }
@@ -265,6 +805,7 @@ struct TenantId {
 impl TenantId {
     fn new(function_name: &str) -> Self {
         Self {
+            function: function_name.to_string(),
             namespace: format!("fn:{function_name}"),
             hash: stable_tenant_hash(function_name),
         }
@@ -288,15 +829,7 @@ impl TenantId {
 }
 
 fn stable_tenant_hash(function_name: &str) -> String {
-    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
-    const FNV_PRIME: u64 = 0x100000001b3;
-
-    let mut hash = FNV_OFFSET_BASIS;
-    for byte in function_name.as_bytes() {
-        hash ^= u64::from(*byte);
-        hash = hash.wrapping_mul(FNV_PRIME);
-    }
-    format!("{hash:016x}")
+    format!("{:016x}", fnv1a(function_name.as_bytes()))
 }
 
 fn guest_resource_name(name: &str) -> String {
@@ -307,6 +840,17 @@ fn guest_resource_name(name: &str) -> String {
     }
 }
 
+/// Image transforms allowed to run at once, from `FAASTA_IMAGE_CONCURRENCY`; defaults
+/// to the number of CPUs.
+fn image_concurrency() -> Result<usize> {
+    match std::env::var("FAASTA_IMAGE_CONCURRENCY") {
+        Ok(value) => value
+            .parse()
+            .with_context(|| format!("invalid FAASTA_IMAGE_CONCURRENCY '{value}'")),
+        Err(_) => Ok(std::thread::available_parallelism().map_or(1, usize::from)),
+    }
+}
+
 fn env_or_default(key: &str, default: &str) -> String {
     std::env::var(key).unwrap_or_else(|_| default.to_string())
 }
@@ -315,6 +859,7 @@ fn env_or_default(key: &str, default: &str) -> String {
 enum KeyValueProvider {
     Memory(KeyValueDefault),
     Valkey(ValkeyKeyValue),
+    Local(Arc<LocalKeyValue>),
 }
 
 impl std::fmt::Debug for KeyValueProvider {
@@ -322,6 +867,7 @@ impl std::fmt::Debug for KeyValueProvider {
         match self {
             Self::Memory(_) => formatter.write_str("KeyValueProvider::Memory"),
             Self::Valkey(_) => formatter.write_str("KeyValueProvider::Valkey"),
+            Self::Local(_) => formatter.write_str("KeyValueProvider::Local"),
         }
     }
 }
@@ -331,6 +877,7 @@ impl KeyValueProvider {
         match env_or_default("FAASTA_KV_BACKEND", "memory").as_str() {
             "memory" => Ok(Self::Memory(KeyValueDefault::connect().await?)),
             "valkey" => Ok(Self::Valkey(ValkeyKeyValue::from_env().await?)),
+            "local" => Ok(Self::Local(kv_store::shared()?)),
             other => bail!("unsupported FAASTA_KV_BACKEND '{other}'"),
         }
     }
@@ -454,6 +1001,7 @@ impl WasiKeyValueCtx for TenantKeyValue {
         let guest_name = guest_resource_name(&identifier);
         let host_name = self.tenant.resource_name(&identifier);
         let valkey_prefix = self.tenant.valkey_prefix(&guest_name);
+        let function = self.tenant.function.clone();
         let inner = self.inner.clone();
         async move {
             match inner {
@@ -466,6 +1014,11 @@ impl WasiKeyValueCtx for TenantKeyValue {
                     prefix: valkey_prefix,
                     valkey,
                 }) as Arc<dyn Bucket>),
+                KeyValueProvider::Local(kv) => Ok(Arc::new(LocalBucket {
+                    function,
+                    guest_name,
+                    kv,
+                }) as Arc<dyn Bucket>),
             }
         }
         .boxed()
@@ -504,6 +1057,74 @@ impl Bucket for TenantBucket {
     }
 }
 
+/// A bucket in the local store, limited to the function's quota
+#[derive(Clone)]
+struct LocalBucket {
+    function: String,
+    guest_name: String,
+    kv: Arc<LocalKeyValue>,
+}
+
+impl std::fmt::Debug for LocalBucket {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("LocalBucket")
+            .field("function", &self.function)
+            .field("name", &self.guest_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LocalBucket {
+    /// Run `op` on the store off the async workers, since commits wait for the disk.
+    fn run<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&LocalKeyValue, &str, &str) -> Result<T> + Send + 'static,
+    ) -> omnia::FutureResult<T> {
+        let bucket = self.clone();
+        async move {
+            tokio::task::spawn_blocking(move || {
+                op(&bucket.kv, &bucket.function, &bucket.guest_name)
+            })
+            .await
+            .context("key-value operation panicked")?
+        }
+        .boxed()
+    }
+}
+
+impl Bucket for LocalBucket {
+    fn name(&self) -> &'static str {
+        Box::leak(self.guest_name.clone().into_boxed_str())
+    }
+
+    fn get(&self, key: String) -> omnia::FutureResult<Option<Vec<u8>>> {
+        self.run(move |kv, function, bucket| kv.get(function, bucket, &key))
+    }
+
+    fn set(&self, key: String, value: Vec<u8>) -> omnia::FutureResult<()> {
+        self.run(move |kv, function, bucket| kv.set(function, bucket, &key, &value))
+    }
+
+    fn delete(&self, key: String) -> omnia::FutureResult<()> {
+        self.run(move |kv, function, bucket| kv.delete(function, bucket, &key))
+    }
+
+    fn exists(&self, key: String) -> omnia::FutureResult<bool> {
+        self.run(move |kv, function, bucket| Ok(kv.get(function, bucket, &key)?.is_some()))
+    }
+
+    fn keys(&self) -> omnia::FutureResult<Vec<String>> {
+        self.run(|kv, function, bucket| {
+            Ok(kv
+                .keys(function, bucket)?
+                .into_iter()
+                .map(|key| key.key)
+                .collect())
+        })
+    }
+}
+
 #[derive(Clone)]
 struct TenantBlobstore {
     tenant: TenantId,
@@ -1231,7 +1852,8 @@ fn quote_pg_ident(identifier: &str) -> String {
     format!("\"{}\"", identifier.replace('"', "\"\""))
 }
 
-fn validate_single_statement(query: &str) -> Result<()> {
+/// Reject guest SQL containing more than one statement.
+pub fn validate_single_statement(query: &str) -> Result<()> {
     let mut in_string = false;
     let mut chars = query.chars().peekable();
     while let Some(ch) = chars.next() {
@@ -1252,7 +1874,8 @@ fn validate_single_statement(query: &str) -> Result<()> {
     Ok(())
 }
 
-fn rewrite_qmark_params(query: &str) -> String {
+/// Turn `?` placeholders outside string literals into Postgres `$n` parameters.
+pub fn rewrite_qmark_params(query: &str) -> String {
     let mut rewritten = String::with_capacity(query.len());
     let mut in_string = false;
     let mut index = 1usize;
@@ -1340,75 +1963,271 @@ fn postgres_row_to_wasi(index: usize, row: &tokio_postgres::Row) -> Result<Row>
     })
 }
 
-fn build_hyper_request(request: WasmRequest) -> Result<Request<RequestBody>> {
-    let mut builder = Request::builder()
-        .method(method_from_wire(request.method))
-        .uri(request.uri.parse::<Uri>().context("invalid request URI")?);
-
-    let headers = builder
-        .headers_mut()
-        .context("failed to prepare request headers")?;
-    for header in request.headers {
-        if let (Ok(name), Ok(value)) = (
-            HeaderName::from_bytes(header.name.as_bytes()),
-            HeaderValue::from_str(&header.value),
-        ) {
-            headers.append(name, value);
-        }
-    }
-
-    builder
-        .body(
-            Full::new(Bytes::from(request.body))
-                .map_err(infallible_to_error_code as fn(std::convert::Infallible) -> ErrorCode),
-        )
-        .context("failed to build request")
+fn build_hyper_request(request: WasmRequest) -> Request<RequestBody> {
+    let mut hyper_request = Request::new(
+        Full::new(request.body)
+            .map_err(infallible_to_error_code as fn(std::convert::Infallible) -> ErrorCode),
+    );
+    *hyper_request.method_mut() = request.method;
+    *hyper_request.uri_mut() = request.uri;
+    *hyper_request.headers_mut() = request.headers;
+    hyper_request
 }
 
 fn infallible_to_error_code(never: std::convert::Infallible) -> ErrorCode {
     match never {}
 }
 
-fn method_from_wire(method: u8) -> Method {
-    match method {
-        0 => Method::GET,
-        1 => Method::POST,
-        2 => Method::PUT,
-        3 => Method::DELETE,
-        4 => Method::PATCH,
-        5 => Method::HEAD,
-        6 => Method::OPTIONS,
-        _ => Method::GET,
-    }
-}
-
-async fn hyper_response_to_worker<B>(response: hyper::Response<B>) -> Result<WasmResponse>
+/// Read the guest's response. Without `stream_to` the body is always buffered;
+/// otherwise a response that should stream is handed to `stream_to` and the rest of its
+/// body pumped into the response's [`BodyStream`], and `None` is returned.
+async fn hyper_response_to_worker<B>(
+    response: hyper::Response<B>,
+    stream_to: Option<impl FnOnce(WasmResponse)>,
+) -> Result<Option<WasmResponse>>
 where
     B: http_body::Body<Data = Bytes>,
     B::Error: std::fmt::Debug,
 {
     let (parts, body) = response.into_parts();
-    let body = body
-        .collect()
-        .await
-        .map_err(|err| anyhow::anyhow!("failed to read WASI response body: {err:?}"))?
-        .to_bytes()
-        .to_vec();
-
-    let headers = parts
+    let mut body = std::pin::pin!(body);
+    let event_stream = parts
         .headers
-        .iter()
-        .filter_map(|(name, value)| {
-            value.to_str().ok().map(|value| WireHeader {
-                name: name.as_str().to_string(),
-                value: value.to_string(),
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let mut chunks = Vec::new();
+    let mut buffered = 0;
+    let mut trailers = HeaderMap::new();
+    while !(stream_to.is_some() && (event_stream || buffered > STREAM_AFTER_BYTES)) {
+        let Some(frame) = body.frame().await else {
+            return Ok(Some(WasmResponse {
+                status: parts.status,
+                headers: parts.headers,
+                body: concat(chunks),
+                stream: None,
+                trailers,
+                timing: InvocationTiming::default(),
+                cpu_meter: None,
+            }));
+        };
+        let frame = frame.map_err(|err| anyhow!("failed to read WASI response body: {err:?}"))?;
+        match frame.into_data() {
+            Ok(data) => {
+                buffered += data.len();
+                chunks.push(data);
+            }
+            Err(frame) => {
+                if let Ok(more) = frame.into_trailers() {
+                    trailers.extend(more);
+                }
+            }
+        }
+    }
+
+    let (frames, stream) = mpsc::channel(STREAM_FRAMES);
+    if let Some(stream_to) = stream_to {
+        stream_to(WasmResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body: concat(chunks),
+            stream: Some(stream),
+            trailers: HeaderMap::new(),
+            timing: InvocationTiming::default(),
+            cpu_meter: None,
+        });
+    }
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|err| anyhow!("failed to read WASI response body: {err:?}"));
+        let failed = frame.is_err();
+        if let Err(err) = &frame {
+            warn!("streamed response body failed: {err:#}");
+        }
+        // Stop running the guest once the client has gone away
+        if frames.send(frame).await.is_err() || failed {
+            break;
+        }
+    }
+    Ok(None)
+}
+
+fn concat(chunks: Vec<Bytes>) -> Bytes {
+    match <[Bytes; 1]>::try_from(chunks) {
+        Ok([chunk]) => chunk,
+        Err(chunks) => chunks.concat().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderName, HeaderValue};
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::wasi_server::{build_faasta_request, faasta_response_to_http};
+
+    fn method() -> impl Strategy<Value = Method> {
+        prop_oneof![
+            Just(Method::GET),
+            Just(Method::HEAD),
+            Just(Method::POST),
+            Just(Method::PUT),
+            Just(Method::DELETE),
+            Just(Method::OPTIONS),
+            Just(Method::PATCH),
+            "[A-Z]{1,12}".prop_map(|name| Method::from_bytes(name.as_bytes()).unwrap()),
+        ]
+    }
+
+    fn uri() -> impl Strategy<Value = Uri> {
+        (
+            "(/[A-Za-z0-9._~!$&'()*+,;=:@-]{1,16}){0,6}",
+            "([A-Za-z0-9=&%+._-]{0,32})?",
+        )
+            .prop_map(|(path, query)| {
+                let path = if path.is_empty() {
+                    "/".to_string()
+                } else {
+                    path
+                };
+                let uri = if query.is_empty() {
+                    path
+                } else {
+                    format!("{path}?{query}")
+                };
+                uri.parse().unwrap()
             })
+    }
+
+    /// Repeated names are kept, and values may hold any byte HTTP allows.
+    fn headers() -> impl Strategy<Value = HeaderMap> {
+        let value = prop::collection::vec(
+            any::<u8>().prop_filter("control byte", |byte| {
+                *byte == b'\t' || (*byte >= 0x20 && *byte != 0x7f)
+            }),
+            0..48,
+        );
+        prop::collection::vec(("[a-z][a-z0-9-]{0,24}", value), 0..24).prop_map(|pairs| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.append(
+                    HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                    HeaderValue::from_bytes(&value).unwrap(),
+                );
+            }
+            headers
         })
-        .collect();
+    }
 
-    Ok(WasmResponse {
-        status: parts.status.as_u16(),
-        headers,
-        body,
-    })
+    fn body() -> impl Strategy<Value = Bytes> {
+        prop::collection::vec(any::<u8>(), 0..8192).prop_map(Bytes::from)
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn buffered<B>(response: hyper::Response<B>) -> WasmResponse
+    where
+        B: http_body::Body<Data = Bytes>,
+        B::Error: std::fmt::Debug,
+    {
+        block_on(hyper_response_to_worker(response, None::<fn(WasmResponse)>))
+            .unwrap()
+            .expect("buffered without somewhere to stream to")
+    }
+
+    proptest! {
+        /// What the host hands to the guest is exactly the request it received.
+        #[test]
+        fn requests_reach_the_guest_unchanged(
+            method in method(),
+            uri in uri(),
+            headers in headers(),
+            body in body(),
+        ) {
+            let request = build_faasta_request(method.clone(), uri.clone(), headers.clone(), body.clone());
+            let (parts, guest_body) = build_hyper_request(request).into_parts();
+            prop_assert_eq!(parts.method, method);
+            prop_assert_eq!(parts.uri, uri);
+            prop_assert_eq!(parts.headers, headers);
+            let guest_body = block_on(guest_body.collect()).unwrap().to_bytes();
+            prop_assert_eq!(guest_body, body);
+        }
+
+        /// What the client receives is exactly the response the guest produced.
+        #[test]
+        fn responses_reach_the_client_unchanged(
+            status in 100u16..1000,
+            headers in headers(),
+            body in body(),
+        ) {
+            let status = StatusCode::from_u16(status).unwrap();
+            let mut guest_response = hyper::Response::new(Full::new(body.clone()));
+            *guest_response.status_mut() = status;
+            *guest_response.headers_mut() = headers.clone();
+
+            let response = buffered(guest_response);
+            let (parts, client_body) = faasta_response_to_http(response).into_parts();
+            prop_assert_eq!(parts.status, status);
+            prop_assert_eq!(parts.headers, headers);
+            let client_body = block_on(client_body.collect()).unwrap().to_bytes();
+            prop_assert_eq!(client_body, body);
+        }
+    }
+
+    #[test]
+    fn trailers_reach_the_client() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("server-timing", HeaderValue::from_static("db;dur=12"));
+        let sent = trailers.clone();
+        let guest_response = hyper::Response::new(
+            Full::new(Bytes::from_static(b"done")).with_trailers(async move { Some(Ok(sent)) }),
+        );
+
+        let response = buffered(guest_response);
+        assert_eq!(response.trailers, trailers);
+        let collected = block_on(faasta_response_to_http(response).into_body().collect()).unwrap();
+        assert_eq!(collected.trailers(), Some(&trailers));
+        assert_eq!(collected.to_bytes(), "done");
+    }
+
+    #[test]
+    fn event_streams_and_large_bodies_are_streamed() {
+        let large = Bytes::from(vec![b'x'; STREAM_AFTER_BYTES + 1]);
+        let cases = [
+            (
+                "text/event-stream",
+                vec![Bytes::from_static(b"data: 1\n\n"); 3],
+            ),
+            (
+                "application/octet-stream",
+                vec![large.clone(), large.clone(), large],
+            ),
+        ];
+        for (content_type, chunks) in cases {
+            let sent = concat(chunks.clone());
+            let frames = chunks
+                .into_iter()
+                .map(|chunk| Ok::<_, std::convert::Infallible>(Frame::data(chunk)));
+            let mut guest_response = hyper::Response::new(http_body_util::StreamBody::new(
+                futures_util::stream::iter(frames),
+            ));
+            guest_response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+            let (head_tx, head_rx) = oneshot::channel();
+            let stream_to = move |response: WasmResponse| head_tx.send(response).unwrap();
+            let read = block_on(hyper_response_to_worker(guest_response, Some(stream_to)));
+            assert!(read.unwrap().is_none(), "{content_type} was buffered");
+            let response = head_rx.blocking_recv().unwrap();
+            assert!(response.body.len() < sent.len(), "{content_type}");
+            let client_body = faasta_response_to_http(response).into_body();
+            assert_eq!(block_on(client_body.collect()).unwrap().to_bytes(), sent);
+        }
+    }
 }
@@ -0,0 +1,206 @@
+//! Harness shared by the integration tests: a `faasta-server` process on ephemeral
+//! ports, with its own temp dirs, a self-signed certificate and a stand-in for the
+//! GitHub API.
+
+// Each test binary uses its own subset of the harness
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use faasta_interface::FunctionResult;
+use rcgen::CertifiedKey;
+use tempfile::TempDir;
+
+pub const USERNAME: &str = "e2e-user";
+pub const TOKEN: &str = "e2e-token";
+/// A second account, for checks between users
+pub const OTHER_USERNAME: &str = "e2e-other";
+pub const OTHER_TOKEN: &str = "e2e-other-token";
+/// Another token of [`USERNAME`], which GitHub takes [`SLOW_AUTH`] to check, keeping
+/// the call that presents it in progress meanwhile
+pub const SLOW_TOKEN: &str = "e2e-slow-token";
+pub const SLOW_AUTH: Duration = Duration::from_secs(2);
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Answers every request with an empty 200
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/hello.wat");
+
+/// The component to publish: the one `FAASTA_E2E_COMPONENT` points at, or the checked-in
+/// fixture.
+pub fn component() -> Vec<u8> {
+    match std::env::var_os("FAASTA_E2E_COMPONENT") {
+        Some(path) => std::fs::read(&path)
+            .unwrap_or_else(|err| panic!("failed to read {:?}: {err}", Path::new(&path))),
+        None => wat::parse_file(FIXTURE).unwrap_or_else(|err| panic!("{FIXTURE}: {err}")),
+    }
+}
+
+/// Answers every request like GitHub's `/user` does for the holders of [`TOKEN`],
+/// [`OTHER_TOKEN`] and [`SLOW_TOKEN`].
+pub fn fake_github() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || answer_github(stream));
+        }
+    });
+    addr
+}
+
+fn answer_github(mut stream: TcpStream) {
+    let mut login = None;
+    {
+        let mut reader = BufReader::new(&stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).is_ok_and(|read| read > 0) && line != "\r\n" {
+            if line.to_ascii_lowercase().starts_with("authorization:") {
+                let token = line.trim_end().rsplit(' ').next().unwrap_or_default();
+                if token == SLOW_TOKEN {
+                    thread::sleep(SLOW_AUTH);
+                }
+                login = [
+                    (TOKEN, USERNAME),
+                    (OTHER_TOKEN, OTHER_USERNAME),
+                    (SLOW_TOKEN, USERNAME),
+                ]
+                .into_iter()
+                .find(|(known, _)| *known == token)
+                .map(|(_, username)| username);
+            }
+            line.clear();
+        }
+    }
+    let (status, body) = match login {
+        Some(login) => ("200 OK", format!(r#"{{"login":"{login}"}}"#)),
+        None => (
+            "401 Unauthorized",
+            r#"{"message":"Bad credentials"}"#.to_string(),
+        ),
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+/// An address nothing is listening on, for the server to bind
+pub fn free_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+/// A `faasta-server` process, killed on drop
+pub struct Server {
+    process: Child,
+    dir: TempDir,
+    pub public: SocketAddr,
+    pub management: SocketAddr,
+    pub certificate: reqwest::Certificate,
+}
+
+impl Server {
+    /// Start the server with everything it writes under a temp dir, the management
+    /// endpoints on a plain-HTTP loopback listener and tokens checked by `github`.
+    pub fn start(github: SocketAddr) -> Self {
+        Self::start_with(github, &[])
+    }
+
+    /// [`Server::start`], with `overrides` set in the server's environment on top of
+    /// the harness's own settings.
+    pub fn start_with(github: SocketAddr, overrides: &[(&str, &str)]) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+        std::fs::write(dir.path().join("cert.pem"), cert.pem()).unwrap();
+        std::fs::write(dir.path().join("key.pem"), key_pair.serialize_pem()).unwrap();
+        let certificate = reqwest::Certificate::from_pem(cert.pem().as_bytes()).unwrap();
+
+        let public = free_addr();
+        let management = free_addr();
+        let log = File::create(dir.path().join("server.log")).unwrap();
+        // Relative paths, and the defaults of everything not set here, land in `dir`
+        let process = Command::new(env!("CARGO_BIN_EXE_faasta-server"))
+            .current_dir(dir.path())
+            .env_clear()
+            .envs([
+                ("LISTEN_ADDR", public.to_string()),
+                ("HTTP_LISTEN_ADDR", free_addr().to_string()),
+                ("MANAGEMENT_LISTEN_ADDR", management.to_string()),
+                ("MANAGEMENT_PLAINTEXT", "true".to_string()),
+                ("BASE_DOMAIN", "e2e.test".to_string()),
+                ("TLS_CERT", "cert.pem".to_string()),
+                ("TLS_KEY", "key.pem".to_string()),
+                ("POOL_INSTANCES", "4".to_string()),
+                ("POOL_CORE_INSTANCES", "40".to_string()),
+                ("POOL_MEMORIES", "4".to_string()),
+                ("FAASTA_GITHUB_API_URL", format!("http://{github}")),
+            ])
+            .envs(overrides.iter().copied())
+            .stdout(log.try_clone().unwrap())
+            .stderr(log)
+            .spawn()
+            .expect("failed to start faasta-server");
+        Self {
+            process,
+            dir,
+            public,
+            management,
+            certificate,
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.process.id()
+    }
+
+    pub fn log(&self) -> String {
+        std::fs::read_to_string(self.dir.path().join("server.log")).unwrap_or_default()
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("https://{}{path}", self.public)
+    }
+
+    /// Wait for `/healthz` to answer over TLS, failing with the server's log if it
+    /// exits or takes too long.
+    pub async fn wait_ready(&mut self, https: &reqwest::Client) {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Ok(response) = https.get(self.url("/healthz")).send().await
+                && response.status().is_success()
+            {
+                return;
+            }
+            if let Some(status) = self.process.try_wait().unwrap() {
+                panic!("faasta-server exited with {status}:\n{}", self.log());
+            }
+            if Instant::now() > deadline {
+                panic!("faasta-server did not become healthy:\n{}", self.log());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+pub fn ok<T>(result: Result<FunctionResult<T>, bitrpc::RpcError>) -> T {
+    match result.expect("RPC failed") {
+        Ok(value) => value,
+        Err(err) => panic!("call was refused: {err:?}"),
+    }
+}
@@ -0,0 +1,219 @@
+//! End-to-end test of `cargo faasta`'s RPC client, the management endpoints and
+//! function dispatch against a `faasta-server` process on ephemeral ports, with its own
+//! temp dirs, a self-signed certificate and a stand-in for the GitHub API.
+//!
+//! It publishes the component in `tests/fixtures` unless `FAASTA_E2E_COMPONENT` points
+//! at another WASI HTTP component that answers `GET /`, e.g.
+//!
+//! ```sh
+//! FAASTA_E2E_COMPONENT=path/to/function.wasm cargo test -p server --test e2e
+//! ```
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cargo_faasta::run::connect_to_function_service;
+use common::{
+    OTHER_TOKEN, SLOW_AUTH, SLOW_TOKEN, Server, TOKEN, USERNAME, component, fake_github, ok,
+};
+use faasta_interface::{FunctionError, StaticAsset, protocol};
+
+mod common;
+
+const FUNCTION: &str = "e2e-function";
+const METRICS_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn publish_list_invoke_unpublish() {
+    let mut server = Server::start(fake_github());
+    let rpc = tokio::runtime::Runtime::new().unwrap();
+    let http = &rpc;
+    let https = reqwest::Client::builder()
+        .add_root_certificate(server.certificate.clone())
+        .build()
+        .unwrap();
+    http.block_on(server.wait_ready(&https));
+
+    let client = rpc
+        .block_on(connect_to_function_service(&format!(
+            "http://{}",
+            server.management
+        )))
+        .unwrap();
+    let info = rpc.block_on(client.server_info()).unwrap();
+    assert_eq!(info.protocol_version, protocol::PROTOCOL_VERSION);
+    assert!(ok(rpc.block_on(client.list_functions(TOKEN.to_string()))).is_empty());
+    assert!(matches!(
+        rpc.block_on(client.list_functions("not-a-token".to_string()))
+            .unwrap(),
+        Err(FunctionError::AuthError(_))
+    ));
+    assert!(matches!(
+        rpc.block_on(client.publish(
+            b"not a component".to_vec(),
+            FUNCTION.to_string(),
+            "not-a-token".to_string()
+        ))
+        .unwrap(),
+        Err(FunctionError::AuthError(_))
+    ));
+
+    ok(rpc.block_on(client.publish(component(), FUNCTION.to_string(), TOKEN.to_string())));
+    let functions = ok(rpc.block_on(client.list_functions(TOKEN.to_string())));
+    let functions: Vec<_> = functions
+        .iter()
+        .map(|function| (function.name.as_str(), function.owner.as_str()))
+        .collect();
+    assert_eq!(functions, [(FUNCTION, USERNAME)]);
+
+    let invoke = server.url(&format!("/{FUNCTION}/"));
+    for _ in 0..3 {
+        let status = http.block_on(https.get(&invoke).send()).unwrap().status();
+        assert!(status.is_success(), "{status}:\n{}", server.log());
+    }
+
+    // Calls are recorded once the response is on its way, so give them a moment
+    let deadline = Instant::now() + METRICS_TIMEOUT;
+    let function = loop {
+        let metrics = ok(rpc.block_on(client.get_metrics(TOKEN.to_string())));
+        let function = metrics
+            .function_metrics
+            .into_iter()
+            .find(|function| function.function_name == FUNCTION);
+        match function {
+            Some(function) if function.call_count >= 3 => break function,
+            _ if Instant::now() > deadline => {
+                panic!("calls were not recorded:\n{}", server.log())
+            }
+            _ => thread::sleep(Duration::from_millis(100)),
+        }
+    };
+    assert_eq!(function.call_count, 3);
+    assert!(function.artifact_bytes > 0);
+    let public: serde_json::Value = http
+        .block_on(async {
+            https
+                .get(server.url("/v1/metrics"))
+                .send()
+                .await?
+                .json()
+                .await
+        })
+        .unwrap();
+    assert!(public["total_calls"].as_u64() >= Some(3), "{public}");
+    // Only functions whose owners opted in are listed publicly
+    assert_eq!(
+        public["function_metrics"],
+        serde_json::json!([]),
+        "{public}"
+    );
+
+    ok(rpc.block_on(client.unpublish(FUNCTION.to_string(), TOKEN.to_string())));
+    assert!(ok(rpc.block_on(client.list_functions(TOKEN.to_string()))).is_empty());
+    let status = http.block_on(https.get(&invoke).send()).unwrap().status();
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn functions_pinned_elsewhere_keep_their_owner() {
+    let mut server = Server::start_with(fake_github(), &[("REGION", "eu")]);
+    let rpc = tokio::runtime::Runtime::new().unwrap();
+    let https = reqwest::Client::builder()
+        .add_root_certificate(server.certificate.clone())
+        .build()
+        .unwrap();
+    rpc.block_on(server.wait_ready(&https));
+    let client = rpc
+        .block_on(connect_to_function_service(&format!(
+            "http://{}",
+            server.management
+        )))
+        .unwrap();
+
+    ok(rpc.block_on(client.publish(component(), FUNCTION.to_string(), TOKEN.to_string())));
+    let mut config =
+        ok(rpc.block_on(client.get_function_config(FUNCTION.to_string(), TOKEN.to_string())));
+    config.regions = vec!["us".to_string()];
+    ok(rpc.block_on(client.set_function_config(FUNCTION.to_string(), config, TOKEN.to_string())));
+
+    // This node doesn't serve the function, but it still isn't up for grabs
+    let taken = rpc
+        .block_on(client.publish(component(), FUNCTION.to_string(), OTHER_TOKEN.to_string()))
+        .unwrap();
+    assert!(
+        matches!(taken, Err(FunctionError::PermissionDenied(_))),
+        "{taken:?}"
+    );
+    let functions = ok(rpc.block_on(client.list_functions(TOKEN.to_string())));
+    assert_eq!(functions.len(), 1);
+    assert_eq!(functions[0].owner, USERNAME);
+    assert!(ok(rpc.block_on(client.list_functions(OTHER_TOKEN.to_string()))).is_empty());
+}
+
+#[test]
+fn racing_deploys_of_a_name_conflict() {
+    let mut server = Server::start(fake_github());
+    let rpc = tokio::runtime::Runtime::new().unwrap();
+    let https = reqwest::Client::builder()
+        .add_root_certificate(server.certificate.clone())
+        .build()
+        .unwrap();
+    rpc.block_on(server.wait_ready(&https));
+    let client = rpc
+        .block_on(connect_to_function_service(&format!(
+            "http://{}",
+            server.management
+        )))
+        .unwrap();
+
+    // The slow token keeps the first publish between taking the name's lock and
+    // committing while the others arrive
+    let site = |contents: &str| {
+        vec![StaticAsset {
+            path: "index.html".to_string(),
+            contents: contents.as_bytes().to_vec(),
+        }]
+    };
+    let (first, (second, unpublish)) = rpc.block_on(async {
+        tokio::join!(
+            client.publish_static(site("first"), FUNCTION.to_string(), SLOW_TOKEN.to_string()),
+            async {
+                tokio::time::sleep(SLOW_AUTH / 4).await;
+                let second = client
+                    .publish_static(site("second"), FUNCTION.to_string(), TOKEN.to_string())
+                    .await;
+                let unpublish = client
+                    .unpublish(FUNCTION.to_string(), TOKEN.to_string())
+                    .await;
+                (second, unpublish)
+            }
+        )
+    });
+    let (second, unpublish) = (second.unwrap(), unpublish.unwrap());
+    assert!(
+        matches!(second, Err(FunctionError::Conflict(_))),
+        "{second:?}"
+    );
+    assert!(
+        matches!(unpublish, Err(FunctionError::Conflict(_))),
+        "{unpublish:?}"
+    );
+    ok(first);
+
+    // Only the first publish took effect, and it left a working function behind
+    let functions = ok(rpc.block_on(client.list_functions(TOKEN.to_string())));
+    let functions: Vec<_> = functions
+        .iter()
+        .map(|function| (function.name.as_str(), function.owner.as_str()))
+        .collect();
+    assert_eq!(functions, [(FUNCTION, USERNAME)]);
+    let invoke = server.url(&format!("/{FUNCTION}/"));
+    let body = rpc
+        .block_on(async { https.get(&invoke).send().await?.text().await })
+        .unwrap();
+    assert_eq!(body, "first", "{}", server.log());
+
+    // Once it has finished, the name is free to deploy again
+    ok(rpc.block_on(client.publish(component(), FUNCTION.to_string(), TOKEN.to_string())));
+    ok(rpc.block_on(client.unpublish(FUNCTION.to_string(), TOKEN.to_string())));
+}
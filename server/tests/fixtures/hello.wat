@@ -0,0 +1,313 @@
+;; The smallest WASIp3 HTTP service: it answers every request with an empty 200. The
+;; core module below is what a guest compiler would emit; the rest is the component
+;; wrapping `wit-component` generates for it against the `wasi:http/service` world.
+(component
+  (type $ty-wasi:http/types@0.3.0-rc-2026-03-15 (;0;)
+    (instance
+      (export (;0;) "fields" (type (sub resource)))
+      (export (;1;) "response" (type (sub resource)))
+      (export (;2;) "headers" (type (eq 0)))
+      (export (;3;) "trailers" (type (eq 0)))
+      (type (;4;) (option string))
+      (type (;5;) (option u16))
+      (type (;6;) (record (field "rcode" 4) (field "info-code" 5)))
+      (export (;7;) "DNS-error-payload" (type (eq 6)))
+      (type (;8;) (option u8))
+      (type (;9;) (record (field "alert-id" 8) (field "alert-message" 4)))
+      (export (;10;) "TLS-alert-received-payload" (type (eq 9)))
+      (type (;11;) (option u32))
+      (type (;12;) (record (field "field-name" 4) (field "field-size" 11)))
+      (export (;13;) "field-size-payload" (type (eq 12)))
+      (type (;14;) (option u64))
+      (type (;15;) (option 13))
+      (type (;16;) (variant (case "DNS-timeout") (case "DNS-error" 7) (case "destination-not-found") (case "destination-unavailable") (case "destination-IP-prohibited") (case "destination-IP-unroutable") (case "connection-refused") (case "connection-terminated") (case "connection-timeout") (case "connection-read-timeout") (case "connection-write-timeout") (case "connection-limit-reached") (case "TLS-protocol-error") (case "TLS-certificate-error") (case "TLS-alert-received" 10) (case "HTTP-request-denied") (case "HTTP-request-length-required") (case "HTTP-request-body-size" 14) (case "HTTP-request-method-invalid") (case "HTTP-request-URI-invalid") (case "HTTP-request-URI-too-long") (case "HTTP-request-header-section-size" 11) (case "HTTP-request-header-size" 15) (case "HTTP-request-trailer-section-size" 11) (case "HTTP-request-trailer-size" 13) (case "HTTP-response-incomplete") (case "HTTP-response-header-section-size" 11) (case "HTTP-response-header-size" 13) (case "HTTP-response-body-size" 14) (case "HTTP-response-trailer-section-size" 11) (case "HTTP-response-trailer-size" 13) (case "HTTP-response-transfer-coding" 4) (case "HTTP-response-content-coding" 4) (case "HTTP-response-timeout") (case "HTTP-upgrade-failed") (case "HTTP-protocol-error") (case "loop-detected") (case "configuration-error") (case "internal-error" 4)))
+      (export (;17;) "error-code" (type (eq 16)))
+      (export (;18;) "request" (type (sub resource)))
+      (type (;19;) (own 0))
+      (type (;20;) (func (result 19)))
+      (export (;0;) "[constructor]fields" (func (type 20)))
+      (type (;21;) (own 2))
+      (type (;22;) (stream u8))
+      (type (;23;) (option 22))
+      (type (;24;) (own 3))
+      (type (;25;) (option 24))
+      (type (;26;) (result 25 (error 17)))
+      (type (;27;) (future 26))
+      (type (;28;) (own 1))
+      (type (;29;) (result (error 17)))
+      (type (;30;) (future 29))
+      (type (;31;) (tuple 28 30))
+      (type (;32;) (func (param "headers" 21) (param "contents" 23) (param "trailers" 27) (result 31)))
+      (export (;1;) "[static]response.new" (func (type 32)))
+    )
+  )
+  (import "wasi:http/types@0.3.0-rc-2026-03-15" (instance $wasi:http/types@0.3.0-rc-2026-03-15 (;0;) (type $ty-wasi:http/types@0.3.0-rc-2026-03-15)))
+  (core module $main (;0;)
+    (type (;0;) (func (result i32)))
+    (type (;1;) (func (param i32 i32 i32 i32 i32)))
+    (type (;2;) (func (result i64)))
+    (type (;3;) (func (param i32 i32) (result i32)))
+    (type (;4;) (func (param i32)))
+    (type (;5;) (func (param i32 i32 i32 i64 i32 i32 i32 i32)))
+    (type (;6;) (func (param i32 i32)))
+    (type (;7;) (func (param i32) (result i32)))
+    (type (;8;) (func (param i32 i32 i32) (result i32)))
+    (import "wasi:http/types@0.3.0-rc-2026-03-15" "[constructor]fields" (func $fields (;0;) (type 0)))
+    (import "wasi:http/types@0.3.0-rc-2026-03-15" "[static]response.new" (func $response_new (;1;) (type 1)))
+    (import "wasi:http/types@0.3.0-rc-2026-03-15" "[future-new-1][static]response.new" (func $future_new (;2;) (type 2)))
+    (import "wasi:http/types@0.3.0-rc-2026-03-15" "[async-lower][future-write-1][static]response.new" (func $future_write (;3;) (type 3)))
+    (import "wasi:http/types@0.3.0-rc-2026-03-15" "[future-drop-writable-1][static]response.new" (func $drop_writable (;4;) (type 4)))
+    (import "wasi:http/types@0.3.0-rc-2026-03-15" "[future-drop-readable-2][static]response.new" (func $drop_readable (;5;) (type 4)))
+    (import "wasi:http/types@0.3.0-rc-2026-03-15" "[resource-drop]request" (func $drop_request (;6;) (type 4)))
+    (import "[export]wasi:http/handler@0.3.0-rc-2026-03-15" "[task-return]handle" (func $task_return (;7;) (type 5)))
+    (import "$root" "[waitable-set-new]" (func $waitable_set_new (;8;) (type 0)))
+    (import "$root" "[waitable-join]" (func $waitable_join (;9;) (type 6)))
+    (memory (;0;) 1)
+    (global $trailers (;0;) (mut i32) i32.const 0)
+    (export "memory" (memory 0))
+    (export "[async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle" (func 10))
+    (export "[callback][async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle" (func 11))
+    (func (;10;) (type 7) (param $request i32) (result i32)
+      (local $future i64) (local $set i32)
+      local.get $request
+      call $drop_request
+      call $future_new
+      local.set $future
+      local.get $future
+      i64.const 32
+      i64.shr_u
+      i32.wrap_i64
+      global.set $trailers
+      call $fields
+      i32.const 0
+      i32.const 0
+      local.get $future
+      i32.wrap_i64
+      i32.const 0
+      call $response_new
+      i32.const 4
+      i32.load
+      call $drop_readable
+      i32.const 0
+      i32.const 0
+      i32.load
+      i32.const 0
+      i64.const 0
+      i32.const 0
+      i32.const 0
+      i32.const 0
+      i32.const 0
+      call $task_return
+      global.get $trailers
+      i32.const 64
+      call $future_write
+      i32.const -1
+      i32.ne
+      if ;; label = @1
+        global.get $trailers
+        call $drop_writable
+        i32.const 0
+        return
+      end
+      call $waitable_set_new
+      local.set $set
+      global.get $trailers
+      local.get $set
+      call $waitable_join
+      local.get $set
+      i32.const 4
+      i32.shl
+      i32.const 2
+      i32.or
+    )
+    (func (;11;) (type 8) (param i32 i32 i32) (result i32)
+      global.get $trailers
+      i32.const 0
+      call $waitable_join
+      global.get $trailers
+      call $drop_writable
+      i32.const 0
+    )
+    (@producers
+      (processed-by "wit-component" "0.247.0")
+    )
+  )
+  (core module $wit-component-shim-module (;1;)
+    (type (;0;) (func (param i32 i32 i32 i32 i32)))
+    (type (;1;) (func (param i32 i32) (result i32)))
+    (type (;2;) (func (param i32 i32 i32 i64 i32 i32 i32 i32)))
+    (table (;0;) 3 3 funcref)
+    (export "0" (func 0))
+    (export "1" (func 1))
+    (export "2" (func 2))
+    (export "$imports" (table 0))
+    (func (;0;) (type 0) (param i32 i32 i32 i32 i32)
+      local.get 0
+      local.get 1
+      local.get 2
+      local.get 3
+      local.get 4
+      i32.const 0
+      call_indirect (type 0)
+    )
+    (func (;1;) (type 1) (param i32 i32) (result i32)
+      local.get 0
+      local.get 1
+      i32.const 1
+      call_indirect (type 1)
+    )
+    (func (;2;) (type 2) (param i32 i32 i32 i64 i32 i32 i32 i32)
+      local.get 0
+      local.get 1
+      local.get 2
+      local.get 3
+      local.get 4
+      local.get 5
+      local.get 6
+      local.get 7
+      i32.const 2
+      call_indirect (type 2)
+    )
+    (@producers
+      (processed-by "wit-component" "0.247.0")
+    )
+  )
+  (core module $wit-component-fixup (;2;)
+    (type (;0;) (func (param i32 i32 i32 i32 i32)))
+    (type (;1;) (func (param i32 i32) (result i32)))
+    (type (;2;) (func (param i32 i32 i32 i64 i32 i32 i32 i32)))
+    (import "" "0" (func (;0;) (type 0)))
+    (import "" "1" (func (;1;) (type 1)))
+    (import "" "2" (func (;2;) (type 2)))
+    (import "" "$imports" (table (;0;) 3 3 funcref))
+    (elem (;0;) (i32.const 0) func 0 1 2)
+    (@producers
+      (processed-by "wit-component" "0.247.0")
+    )
+  )
+  (core instance $wit-component-shim-instance (;0;) (instantiate $wit-component-shim-module))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "request" (type $request (;1;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "response" (type $response (;2;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "error-code" (type $error-code (;3;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "[constructor]fields" (func $"[constructor]fields" (;0;)))
+  (core func $"[constructor]fields" (;0;) (canon lower (func $"[constructor]fields")))
+  (alias core export $wit-component-shim-instance "0" (core func $"indirect-wasi:http/types@0.3.0-rc-2026-03-15-[static]response.new" (;1;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "trailers" (type $trailers (;4;)))
+  (type (;5;) (own $trailers))
+  (type (;6;) (option 5))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "error-code" (type $"#type7 error-code" (@name "error-code") (;7;)))
+  (type (;8;) (result 6 (error $"#type7 error-code")))
+  (type (;9;) (future 8))
+  (core func $future.new (;2;) (canon future.new 9))
+  (alias core export $wit-component-shim-instance "1" (core func $"wasi:http/types@0.3.0-rc-2026-03-15-[future-write-1][static]response.new" (;3;)))
+  (core func $future.drop-writable (;4;) (canon future.drop-writable 9))
+  (type (;10;) (result (error $"#type7 error-code")))
+  (type (;11;) (future 10))
+  (core func $future.drop-readable (;5;) (canon future.drop-readable 11))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "request" (type $"#type12 request" (@name "request") (;12;)))
+  (core func $resource.drop (;6;) (canon resource.drop $"#type12 request"))
+  (core instance $wasi:http/types@0.3.0-rc-2026-03-15 (;1;)
+    (export "[constructor]fields" (func $"[constructor]fields"))
+    (export "[static]response.new" (func $"indirect-wasi:http/types@0.3.0-rc-2026-03-15-[static]response.new"))
+    (export "[future-new-1][static]response.new" (func $future.new))
+    (export "[async-lower][future-write-1][static]response.new" (func $"wasi:http/types@0.3.0-rc-2026-03-15-[future-write-1][static]response.new"))
+    (export "[future-drop-writable-1][static]response.new" (func $future.drop-writable))
+    (export "[future-drop-readable-2][static]response.new" (func $future.drop-readable))
+    (export "[resource-drop]request" (func $resource.drop))
+  )
+  (alias core export $wit-component-shim-instance "2" (core func $task-return-handle (;7;)))
+  (core instance $"[export]wasi:http/handler@0.3.0-rc-2026-03-15" (;2;)
+    (export "[task-return]handle" (func $task-return-handle))
+  )
+  (core func $waitable-set.new (;8;) (canon waitable-set.new))
+  (core func $waitable.join (;9;) (canon waitable.join))
+  (core instance $$root (;3;)
+    (export "[waitable-set-new]" (func $waitable-set.new))
+    (export "[waitable-join]" (func $waitable.join))
+  )
+  (core instance $main (;4;) (instantiate $main
+      (with "wasi:http/types@0.3.0-rc-2026-03-15" (instance $wasi:http/types@0.3.0-rc-2026-03-15))
+      (with "[export]wasi:http/handler@0.3.0-rc-2026-03-15" (instance $"[export]wasi:http/handler@0.3.0-rc-2026-03-15"))
+      (with "$root" (instance $$root))
+    )
+  )
+  (alias core export $main "memory" (core memory $memory (;0;)))
+  (alias core export $wit-component-shim-instance "$imports" (core table $"shim table" (;0;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "[static]response.new" (func $"[static]response.new" (;1;)))
+  (core func $"#core-func10 indirect-wasi:http/types@0.3.0-rc-2026-03-15-[static]response.new" (@name "indirect-wasi:http/types@0.3.0-rc-2026-03-15-[static]response.new") (;10;) (canon lower (func $"[static]response.new") (memory $memory)))
+  (core func $future.write (;11;) (canon future.write 9 (memory $memory) string-encoding=utf8 async))
+  (type (;13;) (own $response))
+  (type (;14;) (result 13 (error $error-code)))
+  (core func $task.return (;12;) (canon task.return (result 14) (memory $memory) string-encoding=utf8))
+  (core instance $fixup-args (;5;)
+    (export "$imports" (table $"shim table"))
+    (export "0" (func $"#core-func10 indirect-wasi:http/types@0.3.0-rc-2026-03-15-[static]response.new"))
+    (export "1" (func $future.write))
+    (export "2" (func $task.return))
+  )
+  (core instance $fixup (;6;) (instantiate $wit-component-fixup
+      (with "" (instance $fixup-args))
+    )
+  )
+  (type (;15;) (own $request))
+  (type (;16;) (func async (param "request" 15) (result 14)))
+  (alias core export $main "[async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle" (core func $"[async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle" (;13;)))
+  (alias core export $main "[callback][async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle" (core func $"[callback][async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle" (;14;)))
+  (func $handle (;2;) (type 16) (canon lift (core func $"[async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle") (memory $memory) string-encoding=utf8 async (callback $"[callback][async-lift]wasi:http/handler@0.3.0-rc-2026-03-15#handle")))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "request" (type $"#type17 request" (@name "request") (;17;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "response" (type $"#type18 response" (@name "response") (;18;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "DNS-error-payload" (type $DNS-error-payload (;19;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "TLS-alert-received-payload" (type $TLS-alert-received-payload (;20;)))
+  (alias export $wasi:http/types@0.3.0-rc-2026-03-15 "field-size-payload" (type $field-size-payload (;21;)))
+  (component $wasi:http/handler@0.3.0-rc-2026-03-15-shim-component (;0;)
+    (import "import-type-request" (type (;0;) (sub resource)))
+    (import "import-type-response" (type (;1;) (sub resource)))
+    (type (;2;) (option string))
+    (type (;3;) (option u16))
+    (type (;4;) (record (field "rcode" 2) (field "info-code" 3)))
+    (import "import-type-DNS-error-payload" (type (;5;) (eq 4)))
+    (type (;6;) (option u8))
+    (type (;7;) (record (field "alert-id" 6) (field "alert-message" 2)))
+    (import "import-type-TLS-alert-received-payload" (type (;8;) (eq 7)))
+    (type (;9;) (option u32))
+    (type (;10;) (record (field "field-name" 2) (field "field-size" 9)))
+    (import "import-type-field-size-payload" (type (;11;) (eq 10)))
+    (type (;12;) (option u64))
+    (type (;13;) (option 11))
+    (type (;14;) (variant (case "DNS-timeout") (case "DNS-error" 5) (case "destination-not-found") (case "destination-unavailable") (case "destination-IP-prohibited") (case "destination-IP-unroutable") (case "connection-refused") (case "connection-terminated") (case "connection-timeout") (case "connection-read-timeout") (case "connection-write-timeout") (case "connection-limit-reached") (case "TLS-protocol-error") (case "TLS-certificate-error") (case "TLS-alert-received" 8) (case "HTTP-request-denied") (case "HTTP-request-length-required") (case "HTTP-request-body-size" 12) (case "HTTP-request-method-invalid") (case "HTTP-request-URI-invalid") (case "HTTP-request-URI-too-long") (case "HTTP-request-header-section-size" 9) (case "HTTP-request-header-size" 13) (case "HTTP-request-trailer-section-size" 9) (case "HTTP-request-trailer-size" 11) (case "HTTP-response-incomplete") (case "HTTP-response-header-section-size" 9) (case "HTTP-response-header-size" 11) (case "HTTP-response-body-size" 12) (case "HTTP-response-trailer-section-size" 9) (case "HTTP-response-trailer-size" 11) (case "HTTP-response-transfer-coding" 2) (case "HTTP-response-content-coding" 2) (case "HTTP-response-timeout") (case "HTTP-upgrade-failed") (case "HTTP-protocol-error") (case "loop-detected") (case "configuration-error") (case "internal-error" 2)))
+    (import "import-type-error-code" (type (;15;) (eq 14)))
+    (import "import-type-request0" (type (;16;) (eq 0)))
+    (type (;17;) (own 16))
+    (import "import-type-response0" (type (;18;) (eq 1)))
+    (type (;19;) (own 18))
+    (import "import-type-error-code0" (type (;20;) (eq 15)))
+    (type (;21;) (result 19 (error 20)))
+    (type (;22;) (func async (param "request" 17) (result 21)))
+    (import "import-func-handle" (func (;0;) (type 22)))
+    (export (;23;) "request" (type 0))
+    (export (;24;) "response" (type 1))
+    (export (;25;) "error-code" (type 15))
+    (type (;26;) (own 23))
+    (type (;27;) (own 24))
+    (type (;28;) (result 27 (error 25)))
+    (type (;29;) (func async (param "request" 26) (result 28)))
+    (export (;1;) "handle" (func 0) (func (type 29)))
+  )
+  (instance $wasi:http/handler@0.3.0-rc-2026-03-15-shim-instance (;1;) (instantiate $wasi:http/handler@0.3.0-rc-2026-03-15-shim-component
+      (with "import-func-handle" (func $handle))
+      (with "import-type-request" (type $"#type17 request"))
+      (with "import-type-response" (type $"#type18 response"))
+      (with "import-type-DNS-error-payload" (type $DNS-error-payload))
+      (with "import-type-TLS-alert-received-payload" (type $TLS-alert-received-payload))
+      (with "import-type-field-size-payload" (type $field-size-payload))
+      (with "import-type-error-code" (type $"#type7 error-code"))
+      (with "import-type-request0" (type $request))
+      (with "import-type-response0" (type $response))
+      (with "import-type-error-code0" (type $error-code))
+    )
+  )
+  (export $wasi:http/handler@0.3.0-rc-2026-03-15 (;2;) "wasi:http/handler@0.3.0-rc-2026-03-15" (instance $wasi:http/handler@0.3.0-rc-2026-03-15-shim-instance))
+  (@producers
+    (processed-by "wit-component" "0.247.0")
+  )
+)
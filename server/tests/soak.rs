@@ -0,0 +1,209 @@
+//! Soak test: publishes, invokes and unpublishes functions against a `faasta-server`
+//! process for a while, sampling its resident memory, open file descriptors, in-flight
+//! invocations and loaded components after every cycle, and fails if any of them
+//! leaks.
+//!
+//! Every cycle unpublishes what it published, so once a cycle ends the component
+//! cache must be empty and no invocation may still hold a pool slot. Memory and
+//! descriptors may settle during the first cycles, so they are compared with a
+//! baseline taken after [`WARMUP_CYCLES`].
+//!
+//! It publishes the same component as the end-to-end test, only runs on Linux and
+//! takes `FAASTA_SOAK_SECS` (default 300), so it is ignored by default:
+//!
+//! ```sh
+//! FAASTA_SOAK_SECS=3600 \
+//!     cargo test -p server --test soak -- --ignored --nocapture
+//! ```
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use cargo_faasta::run::connect_to_function_service;
+use common::{Server, TOKEN, component, fake_github, ok};
+
+mod common;
+
+/// Names cycled through, so caches see more than one key
+const FUNCTIONS: usize = 4;
+const INVOCATIONS_PER_CYCLE: usize = 16;
+const WARMUP_CYCLES: usize = 5;
+/// Resident memory above the baseline tolerated, for allocator and cache slack
+const DEFAULT_RSS_SLACK_MB: u64 = 64;
+/// Descriptors above the baseline tolerated, e.g. pooled connections
+const FD_SLACK: usize = 16;
+/// How long in-flight invocations get to finish once their responses are read
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    cycle: usize,
+    rss_bytes: u64,
+    fds: usize,
+    in_flight: u64,
+    cached_components: u64,
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.parse().unwrap_or_else(|_| panic!("invalid {name}")))
+        .unwrap_or(default)
+}
+
+fn rss_bytes(pid: u32) -> u64 {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).unwrap();
+    let kib: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .expect("no VmRSS in /proc status");
+    kib * 1024
+}
+
+fn open_fds(pid: u32) -> usize {
+    std::fs::read_dir(format!("/proc/{pid}/fd"))
+        .unwrap()
+        .count()
+}
+
+/// What grew past its allowance, compared with the sample after warm-up.
+fn leaks(samples: &[Sample], rss_slack: u64) -> Vec<String> {
+    let mut leaks = Vec::new();
+    for sample in samples {
+        if sample.cached_components > 0 {
+            leaks.push(format!(
+                "cycle {}: {} components still loaded after unpublishing",
+                sample.cycle, sample.cached_components
+            ));
+        }
+        if sample.in_flight > 0 {
+            leaks.push(format!(
+                "cycle {}: {} invocations still hold a pool slot",
+                sample.cycle, sample.in_flight
+            ));
+        }
+    }
+    let (Some(baseline), Some(last)) = (samples.get(WARMUP_CYCLES), samples.last()) else {
+        return leaks;
+    };
+    if last.rss_bytes > baseline.rss_bytes + rss_slack {
+        leaks.push(format!(
+            "resident memory grew from {} MiB after cycle {} to {} MiB after cycle {}",
+            baseline.rss_bytes >> 20,
+            baseline.cycle,
+            last.rss_bytes >> 20,
+            last.cycle
+        ));
+    }
+    if last.fds > baseline.fds + FD_SLACK {
+        leaks.push(format!(
+            "open file descriptors grew from {} after cycle {} to {} after cycle {}",
+            baseline.fds, baseline.cycle, last.fds, last.cycle
+        ));
+    }
+    leaks
+}
+
+#[test]
+#[ignore = "long-running"]
+fn deploy_invoke_unpublish_without_leaks() {
+    if !Path::new("/proc/self/status").exists() {
+        eprintln!("no /proc to sample the server from; skipping");
+        return;
+    }
+    let component = component();
+    let duration = Duration::from_secs(env_u64("FAASTA_SOAK_SECS", 300));
+    let rss_slack = env_u64("FAASTA_SOAK_RSS_SLACK_MB", DEFAULT_RSS_SLACK_MB) << 20;
+
+    // Room for every invocation of a cycle at once, below the admission watermark
+    let pool = (INVOCATIONS_PER_CYCLE * 2).to_string();
+    let mut server = Server::start_with(
+        fake_github(),
+        &[("POOL_INSTANCES", &pool), ("POOL_MEMORIES", &pool)],
+    );
+    let rpc = tokio::runtime::Runtime::new().unwrap();
+    let http = &rpc;
+    let https = reqwest::Client::builder()
+        .add_root_certificate(server.certificate.clone())
+        .build()
+        .unwrap();
+    http.block_on(server.wait_ready(&https));
+    let client = rpc
+        .block_on(connect_to_function_service(&format!(
+            "http://{}",
+            server.management
+        )))
+        .unwrap();
+
+    let started = Instant::now();
+    let mut samples = Vec::new();
+    let mut cycle = 0;
+    while started.elapsed() < duration || cycle <= WARMUP_CYCLES {
+        let name = format!("soak-{}", cycle % FUNCTIONS);
+        ok(rpc.block_on(client.publish(component.clone(), name.clone(), TOKEN.to_string())));
+        let url = server.url(&format!("/{name}/"));
+        let statuses = http.block_on(async {
+            let calls: Vec<_> = (0..INVOCATIONS_PER_CYCLE)
+                .map(|_| {
+                    let request = https.get(&url);
+                    tokio::spawn(async move {
+                        let response = request.send().await?;
+                        let status = response.status();
+                        response.bytes().await?;
+                        Ok::<_, reqwest::Error>(status)
+                    })
+                })
+                .collect();
+            let mut statuses = Vec::new();
+            for call in calls {
+                statuses.push(call.await.unwrap());
+            }
+            statuses
+        });
+        for status in statuses {
+            let status = status.unwrap_or_else(|err| {
+                panic!("cycle {cycle}: request failed: {err}\n{}", server.log())
+            });
+            assert!(status.is_success(), "cycle {cycle}: {status}");
+        }
+        ok(rpc.block_on(client.unpublish(name, TOKEN.to_string())));
+
+        // Permits are released once responses finish streaming, just after the client
+        // has read them
+        let deadline = Instant::now() + SETTLE_TIMEOUT;
+        let metrics = loop {
+            let metrics = ok(rpc.block_on(client.get_metrics(TOKEN.to_string())));
+            if metrics.admission.in_flight == 0 || Instant::now() > deadline {
+                break metrics;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        let sample = Sample {
+            cycle,
+            rss_bytes: rss_bytes(server.pid()),
+            fds: open_fds(server.pid()),
+            in_flight: metrics.admission.in_flight,
+            cached_components: metrics.runtime.cached_components,
+        };
+        if cycle % 10 == 0 {
+            eprintln!(
+                "cycle {cycle} after {:?}: rss {} MiB, {} fds",
+                started.elapsed(),
+                sample.rss_bytes >> 20,
+                sample.fds
+            );
+        }
+        samples.push(sample);
+        cycle += 1;
+    }
+
+    let leaks = leaks(&samples, rss_slack);
+    assert!(
+        leaks.is_empty(),
+        "{} cycles leaked:\n{}\nlast samples: {:#?}",
+        samples.len(),
+        leaks.join("\n"),
+        &samples[samples.len().saturating_sub(10)..]
+    );
+}